@@ -0,0 +1,161 @@
+//! Measures the speedup of skipping idle loops, checking that the emulated state is the same with
+//! and without skipping.
+//!
+//! Two workloads are timed. A program polling I_STAT for VBlank for a few emulated seconds, which
+//! needs no BIOS, and a BIOS booting up to the shell, since the BIOS idles heavily while waiting
+//! for VBlank. Run it with `SHIMMER_BIOS=path/to/bios.bin cargo bench -p shimmer --bench
+//! bios_boot`, or without `SHIMMER_BIOS` to only time the polling program.
+
+use shimmer::{
+    Condition, Emulator, EmulatorBuilder, SHELL_ENTRY, StopReason,
+    core::{CYCLES_SECOND, cpu::Reg, mem::Address},
+};
+use std::time::{Duration, Instant};
+use strum::VariantArray;
+use tinylog::logger::LoggerFamily;
+
+/// Number of cycles within which the BIOS must have reached the shell.
+const MAX_BOOT_CYCLES: u64 = 30 * CYCLES_SECOND;
+
+/// Number of cycles the polling program runs for.
+const POLL_CYCLES: u64 = 10 * CYCLES_SECOND;
+
+/// Where the polling program is loaded and executed from.
+const PROGRAM: Address = Address(0x8000_2000);
+
+/// Polls I_STAT until VBlank is requested, then acknowledges it, counts it in `$t3` and polls
+/// again.
+const VBLANK_COUNTER: [u32; 10] = [
+    // lui $t1, 0x1F80
+    0x3C09_1F80,
+    // loop: lw $t0, 0x1070($t1)
+    0x8D28_1070,
+    0,
+    // andi $t0, $t0, 1
+    0x3108_0001,
+    // beq $t0, $zero, loop
+    0x1100_FFFC,
+    0,
+    // sw $zero, 0x1070($t1)
+    0xAD20_1070,
+    // addiu $t3, $t3, 1
+    0x256B_0001,
+    // j loop
+    0x0800_0000 | (((PROGRAM.0 + 4) >> 2) & 0x03FF_FFFF),
+    0,
+];
+
+/// Number of runs timed with each setting. The fastest one is reported.
+const RUNS: usize = 3;
+
+/// The state of the system at the end of a run.
+#[derive(PartialEq, Eq)]
+struct State {
+    cycles: u64,
+    regs: Vec<u32>,
+    ram: Vec<u8>,
+}
+
+impl State {
+    fn of(emulator: &mut Emulator, cycles: u64) -> Self {
+        let psx = emulator.psx();
+        Self {
+            cycles,
+            regs: Reg::VARIANTS
+                .iter()
+                .map(|&reg| psx.cpu.regs.read(reg))
+                .collect(),
+            ram: psx.memory.ram.to_vec(),
+        }
+    }
+}
+
+fn builder(bios: Vec<u8>, skip_idle_loops: bool) -> EmulatorBuilder {
+    let logger = LoggerFamily::builder()
+        .build()
+        .logger("psx", tinylog::Level::Error);
+    Emulator::builder()
+        .bios(bios)
+        .logger(logger)
+        .skip_idle_loops(skip_idle_loops)
+}
+
+fn poll(skip_idle_loops: bool) -> (State, Duration) {
+    let mut emulator = builder(Vec::new(), skip_idle_loops)
+        .build()
+        .expect("emulator should build");
+    emulator.load_raw(PROGRAM, &VBLANK_COUNTER.map(u32::to_le_bytes).concat());
+    emulator.set_pc(PROGRAM);
+
+    let start = Instant::now();
+    emulator.cycle_for(POLL_CYCLES);
+    let elapsed = start.elapsed();
+
+    (State::of(&mut emulator, POLL_CYCLES), elapsed)
+}
+
+fn boot(bios: &[u8], skip_idle_loops: bool) -> (State, Duration) {
+    let mut emulator = builder(bios.to_vec(), skip_idle_loops)
+        .build()
+        .expect("emulator should build");
+
+    let start = Instant::now();
+    let result = emulator.run_until(MAX_BOOT_CYCLES, &[Condition::PcEquals(SHELL_ENTRY)], |_| {
+        false
+    });
+    let elapsed = start.elapsed();
+    assert_eq!(
+        result.reason,
+        StopReason::Condition(0),
+        "BIOS didn't reach the shell"
+    );
+
+    (State::of(&mut emulator, result.cycles), elapsed)
+}
+
+fn fastest(run: impl Fn(bool) -> (State, Duration), skip_idle_loops: bool) -> (State, Duration) {
+    let mut best = run(skip_idle_loops);
+    for _ in 1..RUNS {
+        let (state, elapsed) = run(skip_idle_loops);
+        assert!(state == best.0, "runs with the same settings diverged");
+        best.1 = best.1.min(elapsed);
+    }
+
+    best
+}
+
+/// Times `run` with and without skipping idle loops, checks both end in the same state and
+/// reports the speedup.
+fn compare(name: &str, run: impl Fn(bool) -> (State, Duration)) {
+    let (executed, executing_time) = fastest(&run, false);
+    let (skipped, skipping_time) = fastest(&run, true);
+    assert!(
+        executed == skipped,
+        "skipping idle loops changed the state at the end of {name}"
+    );
+
+    println!(
+        "{name}: {} cycles ({:.2} emulated seconds)",
+        executed.cycles,
+        executed.cycles as f64 / CYCLES_SECOND as f64
+    );
+    println!("  executing idle loops: {executing_time:?}");
+    println!("  skipping idle loops:  {skipping_time:?}");
+    println!(
+        "  speedup: {:.2}x",
+        executing_time.as_secs_f64() / skipping_time.as_secs_f64()
+    );
+}
+
+fn main() {
+    compare("polling for VBlank", poll);
+
+    let Some(path) = std::env::var_os("SHIMMER_BIOS") else {
+        eprintln!("set SHIMMER_BIOS to the path of a BIOS image to also time a BIOS boot");
+        return;
+    };
+    let bios = std::fs::read(path).expect("BIOS image should be readable");
+    compare("booting to the shell", |skip_idle_loops| {
+        boot(&bios, skip_idle_loops)
+    });
+}
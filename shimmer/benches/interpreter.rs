@@ -0,0 +1,87 @@
+//! Runs a CPU-bound loop of common instructions without a BIOS, reporting how many instructions
+//! the interpreter executes per second.
+//!
+//! The loop mixes ALU operations, a load, a store and a jump, so the numbers are dominated by
+//! instruction decode and dispatch. Compare runs of `cargo bench -p shimmer --bench interpreter`
+//! before and after a change to the interpreter to measure its effect.
+
+use shimmer::{
+    Emulator,
+    core::{CYCLES_SECOND, cpu::Reg, mem::Address},
+};
+use std::time::{Duration, Instant};
+use tinylog::logger::LoggerFamily;
+
+/// Where the loop is loaded and executed from.
+const PROGRAM: Address = Address(0x8000_2000);
+
+/// The loop, which increments `$t0` once per iteration.
+const LOOP: [u32; 7] = [
+    // addiu $t0, $t0, 1
+    0x2508_0001,
+    // xor $t1, $t1, $t0
+    0x0128_4826,
+    // sll $t2, $t1, 3
+    0x0009_50C0,
+    // sw $t2, 0x1000($zero)
+    0xAC0A_1000,
+    // lw $t3, 0x1000($zero)
+    0x8C0B_1000,
+    // j PROGRAM
+    0x0800_0000 | ((PROGRAM.0 >> 2) & 0x03FF_FFFF),
+    // addu $t4, $t3, $t0
+    0x0168_6021,
+];
+
+/// Number of emulated cycles each run executes for.
+const CYCLES: u64 = 5 * CYCLES_SECOND;
+
+/// Number of timed runs. The fastest one is reported.
+const RUNS: usize = 5;
+
+/// Runs the loop for [`CYCLES`], returning the amount of instructions executed and how long it
+/// took.
+fn run() -> (u64, Duration) {
+    let logger = LoggerFamily::builder()
+        .build()
+        .logger("psx", tinylog::Level::Error);
+    let mut emulator = Emulator::builder()
+        .bios(Vec::new())
+        .logger(logger)
+        .skip_idle_loops(false)
+        .build()
+        .expect("emulator should build");
+
+    emulator.load_raw(PROGRAM, &LOOP.map(u32::to_le_bytes).concat());
+    emulator.set_pc(PROGRAM);
+
+    let start = Instant::now();
+    emulator.cycle_for(CYCLES);
+    let elapsed = start.elapsed();
+
+    let iterations = u64::from(emulator.psx().cpu.regs.read(Reg::T0));
+    (iterations * LOOP.len() as u64, elapsed)
+}
+
+fn main() {
+    let (instructions, mut elapsed) = run();
+    for _ in 1..RUNS {
+        let (run_instructions, run_elapsed) = run();
+        assert_eq!(
+            run_instructions, instructions,
+            "runs of the same loop diverged"
+        );
+        elapsed = elapsed.min(run_elapsed);
+    }
+
+    println!(
+        "executed {instructions} instructions in {:.2} emulated seconds",
+        CYCLES as f64 / CYCLES_SECOND as f64
+    );
+    println!("fastest run: {elapsed:?}");
+    println!(
+        "{:.2} million instructions per second, {:.2}x real time",
+        instructions as f64 / elapsed.as_secs_f64() / 1e6,
+        CYCLES as f64 / CYCLES_SECOND as f64 / elapsed.as_secs_f64()
+    );
+}
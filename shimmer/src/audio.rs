@@ -0,0 +1,187 @@
+//! [`AudioBuffer`], the ring buffer between the emulated audio output, which produces samples at
+//! the pace of emulation, and a frontend's audio sink, which consumes them at the pace of the
+//! host.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+/// A stereo sample: left and right.
+pub type Sample = [i16; 2];
+
+/// Where the emulator pushes the samples of the SPU output, at 44100Hz of emulated time. Attached
+/// with [`EmulatorBuilder::audio`](crate::EmulatorBuilder::audio).
+pub trait AudioSink: Send {
+    /// Consumes the next sample.
+    fn push(&mut self, sample: Sample);
+}
+
+/// What an [`AudioBuffer`] outputs when it's drained faster than it's filled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnderrunPolicy {
+    /// Repeat the last sample, which avoids pops when the output wasn't silent.
+    #[default]
+    RepeatLast,
+    /// Output silence.
+    Silence,
+}
+
+/// A fixed capacity ring buffer of samples. The capacity sets the latency of the audio path.
+///
+/// When filled faster than it's drained, the oldest samples are dropped to keep the latency
+/// bounded. When drained faster than it's filled, samples are made up according to its
+/// [`UnderrunPolicy`]. Both are counted, and the fill level is exposed so that frontends can adapt
+/// the pace of emulation to keep it around half.
+#[derive(Debug, Clone)]
+pub struct AudioBuffer {
+    samples: VecDeque<Sample>,
+    capacity: usize,
+    policy: UnderrunPolicy,
+    last: Sample,
+    overruns: u64,
+    underruns: u64,
+}
+
+impl AudioBuffer {
+    /// Creates an empty buffer holding up to `capacity` samples.
+    pub fn new(capacity: usize, policy: UnderrunPolicy) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            policy,
+            last: [0; 2],
+            overruns: 0,
+            underruns: 0,
+        }
+    }
+
+    /// Pushes a sample, dropping the oldest one if the buffer is full.
+    pub fn push(&mut self, sample: Sample) {
+        if self.capacity == 0 {
+            self.overruns += 1;
+            return;
+        }
+
+        if self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+            self.overruns += 1;
+        }
+
+        self.samples.push_back(sample);
+    }
+
+    /// Pops the oldest sample or, if the buffer is empty, makes one up according to the
+    /// [`UnderrunPolicy`].
+    pub fn pop(&mut self) -> Sample {
+        match self.samples.pop_front() {
+            Some(sample) => {
+                self.last = sample;
+                sample
+            }
+            None => {
+                self.underruns += 1;
+                match self.policy {
+                    UnderrunPolicy::RepeatLast => self.last,
+                    UnderrunPolicy::Silence => [0; 2],
+                }
+            }
+        }
+    }
+
+    /// Fills `out` with popped samples.
+    pub fn drain_into(&mut self, out: &mut [Sample]) {
+        for sample in out {
+            *sample = self.pop();
+        }
+    }
+
+    /// Amount of samples waiting to be popped.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Whether there are no samples waiting to be popped.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Maximum amount of samples the buffer holds.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// How full the buffer is, from 0 (empty) to 1 (full).
+    pub fn fill_level(&self) -> f64 {
+        if self.capacity == 0 {
+            return 0.0;
+        }
+
+        self.samples.len() as f64 / self.capacity as f64
+    }
+
+    /// Amount of samples dropped because the buffer was full.
+    pub fn overruns(&self) -> u64 {
+        self.overruns
+    }
+
+    /// Amount of samples made up because the buffer was empty.
+    pub fn underruns(&self) -> u64 {
+        self.underruns
+    }
+}
+
+impl AudioSink for AudioBuffer {
+    fn push(&mut self, sample: Sample) {
+        AudioBuffer::push(self, sample);
+    }
+}
+
+/// Lets a frontend keep a handle to the sink, e.g. to drain an [`AudioBuffer`] from its audio
+/// thread.
+impl<S: AudioSink> AudioSink for Arc<Mutex<S>> {
+    fn push(&mut self, sample: Sample) {
+        self.lock().unwrap().push(sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPACITY: usize = 4;
+
+    #[test]
+    fn overruns_drop_the_oldest_samples_and_underruns_follow_the_policy() {
+        for (policy, underrun_sample) in [
+            (UnderrunPolicy::RepeatLast, [6, -6]),
+            (UnderrunPolicy::Silence, [0, 0]),
+        ] {
+            let mut buffer = AudioBuffer::new(CAPACITY, policy);
+
+            // slow drain: 6 samples pushed into room for 4, so the first 2 are dropped
+            for i in 1..=6 {
+                buffer.push([i, -i]);
+            }
+            assert_eq!(buffer.fill_level(), 1.0, "{policy:?}");
+            assert_eq!(buffer.overruns(), 2, "{policy:?}");
+
+            // fast drain: 2 samples more than were left
+            let mut out = [[0; 2]; 6];
+            buffer.drain_into(&mut out);
+            assert_eq!(
+                out,
+                [
+                    [3, -3],
+                    [4, -4],
+                    [5, -5],
+                    [6, -6],
+                    underrun_sample,
+                    underrun_sample,
+                ],
+                "{policy:?}"
+            );
+            assert_eq!(buffer.underruns(), 2, "{policy:?}");
+        }
+    }
+}
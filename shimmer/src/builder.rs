@@ -0,0 +1,234 @@
+//! A builder for [`Emulator`]s, which puts construction inputs and optional subsystems in a single
+//! place.
+
+use crate::{
+    Config, Emulator, EmulatorError, Settings,
+    audio::AudioSink,
+    cdrom, cpu, flight_recorder,
+    gpu::interface::Renderer,
+    sio0::{
+        self,
+        memcard::{MemoryCard, MemoryCardError},
+    },
+    unimplemented,
+};
+use std::{ffi::CString, path::PathBuf};
+use tinylog::Logger;
+
+/// Builds an [`Emulator`]. Created by [`Emulator::builder`].
+///
+/// Only the BIOS and the logger are required: every other input has a default, which is the same
+/// as leaving the corresponding subsystem unused (no disc, no memory cards, no renderer, no audio
+/// sink).
+#[derive(Default)]
+pub struct EmulatorBuilder {
+    bios: Option<Vec<u8>>,
+    logger: Option<Logger>,
+    disc: Option<PathBuf>,
+    expansion1: Option<Vec<u8>>,
+    sideload_args: Vec<CString>,
+    region_policy: cdrom::RegionPolicy,
+    sio0_timing: sio0::Timing,
+    skip_idle_loops: bool,
+    skip_post_delays: bool,
+    flight_recorder: flight_recorder::Settings,
+    jump_alignment: cpu::JumpAlignment,
+    kernel_stdout_limit: Option<usize>,
+    unimplemented_reporting: unimplemented::Reporting,
+    settings: Settings,
+    renderer: Option<Box<dyn Renderer>>,
+    audio: Option<Box<dyn AudioSink>>,
+    memory_cards: Vec<(usize, PathBuf)>,
+}
+
+impl From<Config> for EmulatorBuilder {
+    /// Starts from every construction input in `config`, e.g. to rebuild an emulator with the
+    /// same configuration on reset.
+    fn from(config: Config) -> Self {
+        Self {
+            bios: Some(config.bios),
+            logger: Some(config.logger),
+            disc: config.rom_path,
+            expansion1: config.expansion1,
+            sideload_args: config.sideload_args,
+            region_policy: config.region_policy,
+            sio0_timing: config.sio0_timing,
+            skip_idle_loops: config.skip_idle_loops,
+            skip_post_delays: config.skip_post_delays,
+            flight_recorder: config.flight_recorder,
+            jump_alignment: config.jump_alignment,
+            kernel_stdout_limit: config.kernel_stdout_limit,
+            unimplemented_reporting: config.unimplemented_reporting,
+            settings: config.settings,
+            renderer: None,
+            audio: None,
+            memory_cards: Vec::new(),
+        }
+    }
+}
+
+impl EmulatorBuilder {
+    /// Sets the BIOS ROM data. Required.
+    pub fn bios(mut self, bios: Vec<u8>) -> Self {
+        self.bios = Some(bios);
+        self
+    }
+
+    /// Sets the root logger. Required.
+    pub fn logger(mut self, logger: Logger) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    /// Inserts the disc image at `path` into the CD-ROM drive.
+    pub fn disc(mut self, path: PathBuf) -> Self {
+        self.disc = Some(path);
+        self
+    }
+
+    /// Maps the expansion ROM `rom`, e.g. the one of a cheat device, to the Expansion 1 region.
+    pub fn expansion1(mut self, rom: Vec<u8>) -> Self {
+        self.expansion1 = Some(rom);
+        self
+    }
+
+    /// Sets the arguments passed to sideloaded executables.
+    pub fn sideload_args(mut self, args: Vec<CString>) -> Self {
+        self.sideload_args = args;
+        self
+    }
+
+    /// Sets how the CD-ROM drive chooses the region it reports discs as licensed for.
+    pub fn region_policy(mut self, policy: cdrom::RegionPolicy) -> Self {
+        self.region_policy = policy;
+        self
+    }
+
+    /// Sets the timing of SIO0 transfers and acknowledges.
+    pub fn sio0_timing(mut self, timing: sio0::Timing) -> Self {
+        self.sio0_timing = timing;
+        self
+    }
+
+    /// Sets whether to skip idle loops. See [`Config::skip_idle_loops`].
+    pub fn skip_idle_loops(mut self, skip: bool) -> Self {
+        self.skip_idle_loops = skip;
+        self
+    }
+
+    /// Sets whether to fast-forward the delay loops of the BIOS while it boots. See
+    /// [`Config::skip_post_delays`].
+    pub fn skip_post_delays(mut self, skip: bool) -> Self {
+        self.skip_post_delays = skip;
+        self
+    }
+
+    /// Sets the configuration of the flight recorder of bus accesses.
+    pub fn flight_recorder(mut self, settings: flight_recorder::Settings) -> Self {
+        self.flight_recorder = settings;
+        self
+    }
+
+    /// Sets how jumps to a register handle targets which aren't word aligned.
+    pub fn jump_alignment(mut self, alignment: cpu::JumpAlignment) -> Self {
+        self.jump_alignment = alignment;
+        self
+    }
+
+    /// Sets the maximum size of the kernel stdout in bytes. See [`Config::kernel_stdout_limit`].
+    pub fn kernel_stdout_limit(mut self, limit: usize) -> Self {
+        self.kernel_stdout_limit = Some(limit);
+        self
+    }
+
+    /// Sets how unimplemented commands are reported. See [`Config::unimplemented_reporting`].
+    pub fn unimplemented_reporting(mut self, reporting: unimplemented::Reporting) -> Self {
+        self.unimplemented_reporting = reporting;
+        self
+    }
+
+    /// Sets the initial runtime settings, which can be changed later with
+    /// [`Emulator::set_settings`].
+    pub fn settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Attaches `renderer` from the start. See [`Emulator::set_renderer`].
+    pub fn renderer(mut self, renderer: Box<dyn Renderer>) -> Self {
+        self.renderer = Some(renderer);
+        self
+    }
+
+    /// Attaches `sink` from the start. See [`Emulator::set_audio_sink`].
+    pub fn audio(mut self, sink: Box<dyn AudioSink>) -> Self {
+        self.audio = Some(sink);
+        self
+    }
+
+    /// Inserts the memory card backed by the file at `path` into `slot`, which must be 0 or 1. See
+    /// [`MemoryCard::open`].
+    pub fn memory_card(mut self, slot: usize, path: PathBuf) -> Self {
+        self.memory_cards.push((slot, path));
+        self
+    }
+
+    /// Validates the required inputs and returns the construction inputs as a [`Config`], e.g. for
+    /// [`Emulator::self_test`] or to rebuild an emulator later. The renderer, the audio sink and
+    /// the memory cards are left out.
+    pub fn into_config(self) -> Result<Config, EmulatorError> {
+        Ok(Config {
+            bios: self.bios.ok_or(EmulatorError::MissingBios)?,
+            rom_path: self.disc,
+            expansion1: self.expansion1,
+            logger: self.logger.ok_or(EmulatorError::MissingLogger)?,
+            sideload_args: self.sideload_args,
+            region_policy: self.region_policy,
+            sio0_timing: self.sio0_timing,
+            skip_idle_loops: self.skip_idle_loops,
+            skip_post_delays: self.skip_post_delays,
+            flight_recorder: self.flight_recorder,
+            jump_alignment: self.jump_alignment,
+            kernel_stdout_limit: self.kernel_stdout_limit,
+            unimplemented_reporting: self.unimplemented_reporting,
+            settings: self.settings,
+        })
+    }
+
+    /// Validates the inputs and builds the [`Emulator`].
+    pub fn build(mut self) -> Result<Emulator, EmulatorError> {
+        let renderer = self.renderer.take();
+        let audio = self.audio.take();
+        let paths = std::mem::take(&mut self.memory_cards);
+        let config = self.into_config()?;
+
+        let mut memory_cards = Vec::with_capacity(paths.len());
+        for (slot, path) in paths {
+            let card = if slot < sio0::MEMORY_CARD_SLOTS {
+                MemoryCard::open(path)
+            } else {
+                Err(MemoryCardError::NoSuchSlot { slot })
+            };
+
+            let card = card.map_err(|source| EmulatorError::MemoryCard { slot, source })?;
+            memory_cards.push((slot, card));
+        }
+
+        let mut emulator = Emulator::new(config)?;
+        if let Some(renderer) = renderer {
+            emulator.set_renderer(renderer);
+        }
+
+        if let Some(sink) = audio {
+            emulator.set_audio_sink(sink);
+        }
+
+        for (slot, card) in memory_cards {
+            emulator
+                .insert_memory_card(slot, Some(card))
+                .expect("slot should have been validated");
+        }
+
+        Ok(emulator)
+    }
+}
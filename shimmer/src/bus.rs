@@ -1,14 +1,23 @@
-use std::hint::{cold_path, unreachable_unchecked};
+use std::hint::cold_path;
 
-use crate::{PSX, cdrom, scheduler::Event, sio0};
+use crate::{
+    PSX, cdrom,
+    flight_recorder::AccessKind,
+    gpu,
+    scheduler::Event,
+    sio0, spu,
+    timers::{Id as TimerId, reschedule as reschedule_timer, sync as sync_timer},
+};
 use bitos::integer::u7;
 use easyerr::Error;
 use shimmer_core::{
     cdrom::RegWrite as CdromRegWrite,
-    dma,
+    dma, interrupts,
     mem::{Address, Primitive, PrimitiveRw, Region, io},
+    spu::Control as SpuControl,
+    timers,
 };
-use tinylog::{debug, trace, warn};
+use tinylog::{debug, info, trace, warn};
 use zerocopy::IntoBytes;
 
 #[derive(Debug, Clone, Copy, Error)]
@@ -18,7 +27,9 @@ pub struct MisalignedAddressErr {
     pub alignment: u32,
 }
 
-/// Helper function to perform masked writes.
+/// Helper function to perform masked writes. Register types with bits that are read-only or always
+/// read as zero define a `WRITE_MASK`, which should be used with this function when writing to
+/// them.
 fn write_masked<P: Primitive, B: bitos::Bits>(src: P, offset: usize, mask: B::Bits, dst: &mut B)
 where
     B::Bits: zerocopy::IntoBytes
@@ -37,6 +48,43 @@ where
     *dst = B::from_bits(new);
 }
 
+/// Returns the offset and size of the narrowest naturally aligned access covering the bytes of a
+/// word selected by `enables`, where bit `i` selects byte `i`. The selected bytes must be
+/// contiguous.
+fn covering_access(enables: u8) -> (u32, u32) {
+    debug_assert!(enables != 0 && enables <= 0xF);
+
+    let first = enables.trailing_zeros();
+    let last = 7 - enables.leading_zeros();
+    if first == last {
+        (first, 1)
+    } else if first / 2 == last / 2 {
+        (first & !1, 2)
+    } else {
+        (0, 4)
+    }
+}
+
+/// Returns a mask of the bytes of a word selected by `enables`, where bit `i` selects byte `i`.
+fn byte_mask(enables: u8) -> u32 {
+    u32::from_le_bytes(std::array::from_fn(|i| {
+        if enables & (1 << i) != 0 { 0xFF } else { 0 }
+    }))
+}
+
+/// Returns the index of the voice of the voice register `reg` and its offset into
+/// [`Voice::regs`](shimmer_core::spu::Voice::regs), in bytes.
+fn voice_reg_offset(reg: io::Reg) -> (usize, usize) {
+    let offset = (reg.address().value() - io::Reg::Voice0Volume.address().value()) as usize;
+    (offset / 16, offset % 16)
+}
+
+/// Returns the offset of the reverb configuration register `reg` into
+/// [`Reverb::config`](shimmer_core::spu::Reverb::config), in bytes.
+fn reverb_config_offset(reg: io::Reg) -> usize {
+    (reg.address().value() - io::Reg::ReverbApfOffset1.address().value()) as usize
+}
+
 impl PSX {
     fn read_io_ports<P, const SILENT: bool>(&mut self, addr: Address) -> P
     where
@@ -71,6 +119,10 @@ impl PSX {
                     let bytes = self.interrupts.mask.as_bytes();
                     P::read_from_buf(&bytes[offset..])
                 }
+                io::Reg::RamSize => {
+                    let bytes = self.memory.ram_size.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
                 io::Reg::Dma0Base
                 | io::Reg::Dma1Base
                 | io::Reg::Dma2Base
@@ -113,14 +165,7 @@ impl PSX {
                     P::read_from_buf(&bytes[offset..])
                 }
                 io::Reg::Gp0 => {
-                    let value = self.gpu.response_queue.pop_front();
-                    let value = if let Some(value) = value {
-                        value
-                    } else {
-                        warn!(self.loggers.gpu, "reading from empty response queue");
-                        0
-                    };
-
+                    let value = self.gpu.response_queue.read();
                     P::read_from_buf(&value.as_bytes()[offset..])
                 }
                 io::Reg::Gp1 => {
@@ -134,6 +179,7 @@ impl PSX {
                     P::read_from_buf(self.cdrom.read(reg).as_bytes())
                 }
                 io::Reg::Timer1Value => {
+                    sync_timer(self, TimerId::Timer1);
                     let bytes = self.timers.timer1.value.as_bytes();
                     P::read_from_buf(&bytes[offset..])
                 }
@@ -153,6 +199,7 @@ impl PSX {
                     P::read_from_buf(&bytes[offset..])
                 }
                 io::Reg::Timer2Value => {
+                    sync_timer(self, TimerId::Timer2);
                     let bytes = self.timers.timer2.value.as_bytes();
                     P::read_from_buf(&bytes[offset..])
                 }
@@ -195,6 +242,80 @@ impl PSX {
                     self.scheduler.schedule(Event::Sio(sio0::Event::Update), 0);
                     P::read_from_buf(&bytes[offset..])
                 }
+                io::Reg::SramInterruptAddress => {
+                    let bytes = self.spu.irq_address.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::SramAddress => {
+                    let bytes = self.spu.transfer_address.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::SpuControl => {
+                    let bytes = self.spu.control.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::SramControl => {
+                    let bytes = self.spu.transfer_control.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::SpuStatus => {
+                    let bytes = self.spu.status().to_bits();
+                    P::read_from_buf(&bytes.as_bytes()[offset..])
+                }
+                io::Reg::ReverbVolume => {
+                    let bytes = self.spu.reverb.output_volume.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::MainVolume => {
+                    let bytes = self.spu.main_volume.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                _ if reg.is_spu_voice() => {
+                    let (voice, start) = voice_reg_offset(reg);
+                    let bytes = self.spu.voices[voice].regs.as_bytes();
+                    P::read_from_buf(&bytes[start + offset..])
+                }
+                io::Reg::VoiceKeyOn => {
+                    let bytes = self.spu.key_on.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::VoiceKeyOff => {
+                    let bytes = self.spu.key_off.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::VoiceChannelFmMode => {
+                    let bytes = self.spu.fm_mode.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::VoiceChannelNoiseMode => {
+                    let bytes = self.spu.noise_mode.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::VoiceChannelEnabled => {
+                    let bytes = self.spu.endx.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::CdVolume => {
+                    let bytes = self.spu.cd_volume.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::ExternVolume => {
+                    let bytes = self.spu.extern_volume.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::SramReverbAddress => {
+                    let bytes = self.spu.reverb.work_area_start.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                io::Reg::VoiceChannelReverbMode => {
+                    let bytes = self.spu.reverb.voices.as_bytes();
+                    P::read_from_buf(&bytes[offset..])
+                }
+                _ if reg.is_reverb_config() => {
+                    let start = reverb_config_offset(reg) + offset;
+                    let bytes = self.spu.reverb.config.as_bytes();
+                    P::read_from_buf(&bytes[start..])
+                }
                 _ => default(),
             };
 
@@ -230,13 +351,31 @@ impl PSX {
             };
 
             let offset = phys.value() - region.start().value();
-            if region == Region::Ram {
-                return self.memory.ram[offset as usize..].read();
+            if region == Region::Ram
+                && let Some(offset) = self.memory.ram_offset(phys)
+            {
+                return self.memory.ram[offset..].read();
             } else {
                 cold_path();
+                if !SILENT && let Some(delay) = self.memory.expansion_delay(region) {
+                    self.bus_stall += delay.read_cycles(size_of::<P>() as u32);
+                }
+
                 match region {
-                    Region::Ram => unsafe { unreachable_unchecked() },
-                    Region::RamMirror => self.memory.ram[(offset & 0x001F_FFFF) as usize..].read(),
+                    Region::Ram | Region::RamMirror => {
+                        let Some(offset) = self.memory.ram_offset(phys) else {
+                            if !SILENT {
+                                warn!(
+                                    self.loggers.bus,
+                                    "read from {addr} ({phys}) which is outside of the RAM window"
+                                );
+                            }
+
+                            return [0, 0, 0, 0].read();
+                        };
+
+                        self.memory.ram[offset..].read()
+                    }
                     Region::Expansion1 => self.memory.expansion_1[offset as usize..].read(),
                     Region::ScratchPad => self.memory.scratchpad[offset as usize..].read(),
                     Region::IOPorts => self.read_io_ports::<P, SILENT>(addr),
@@ -250,19 +389,116 @@ impl PSX {
         }
     }
 
+    /// Records an access in the flight recorder, if it's active.
+    #[inline(always)]
+    pub(crate) fn record_access<P: Primitive>(
+        &mut self,
+        kind: AccessKind,
+        addr: Address,
+        value: P,
+    ) {
+        if self.flight_recorder.active() {
+            self.flight_recorder
+                .record(self.scheduler.elapsed(), kind, addr, value);
+        }
+    }
+
     #[inline(always)]
     pub fn read<P, const SILENT: bool>(&mut self, addr: Address) -> Result<P, MisalignedAddressErr>
     where
         P: Primitive,
     {
         (addr.is_aligned(P::ALIGNMENT))
-            .then(|| self.read_unaligned::<P, SILENT>(addr))
+            .then(|| {
+                let value = self.read_unaligned::<P, SILENT>(addr);
+                self.record_access(AccessKind::Read, addr, value);
+                value
+            })
             .ok_or(MisalignedAddressErr {
                 addr,
                 alignment: P::ALIGNMENT,
             })
     }
 
+    /// Reads a primitive from memory without any side effects. Returns [`None`] if `addr` is not
+    /// backed by plain memory (e.g. if it's an IO port).
+    pub fn peek<P>(&self, addr: Address) -> Option<P>
+    where
+        P: Primitive,
+    {
+        let phys = addr.physical()?;
+        let region = phys.region()?;
+        let offset = (phys.value() - region.start().value()) as usize;
+
+        let buf = match region {
+            Region::Ram | Region::RamMirror => &self.memory.ram[self.memory.ram_offset(phys)?..],
+            Region::Expansion1 => &self.memory.expansion_1[offset..],
+            Region::ScratchPad => &self.memory.scratchpad[offset..],
+            Region::IOPorts => return None,
+            Region::Expansion2 => &self.memory.expansion_2[offset..],
+            Region::Expansion3 => &self.memory.expansion_3[offset..],
+            Region::BIOS => &self.memory.bios[offset..],
+        };
+
+        Some(buf.read())
+    }
+
+    /// Formats an annotated hex dump of `len` bytes starting at `addr`, 16 bytes per line. Each
+    /// line is annotated with the memory regions and IO registers it covers.
+    ///
+    /// Memory is read as in [`PSX::peek`], so mirrors are resolved and nothing has side effects.
+    /// IO ports can't be read without side effects, so their bytes show as `--`.
+    pub fn hexdump(&self, addr: Address, len: u32) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        for line_start in (0..len).step_by(16) {
+            let line_len = (len - line_start).min(16);
+            let mut hex = String::with_capacity(48);
+            let mut ascii = String::with_capacity(16);
+            let mut labels: Vec<String> = Vec::new();
+
+            for offset in line_start..line_start + line_len {
+                let addr = addr + offset;
+                let label = match io::Reg::reg_and_offset(addr) {
+                    Some((reg, _)) => format!("{reg:?}"),
+                    None => match addr.region() {
+                        Some(region) => format!("{region:?}"),
+                        None => "Unmapped".to_owned(),
+                    },
+                };
+
+                if labels.last() != Some(&label) {
+                    labels.push(label);
+                }
+
+                match self.peek::<u8>(addr) {
+                    Some(byte) => {
+                        _ = write!(hex, "{byte:02X} ");
+                        ascii.push(if byte.is_ascii_graphic() || byte == b' ' {
+                            char::from(byte)
+                        } else {
+                            '.'
+                        });
+                    }
+                    None => {
+                        hex.push_str("-- ");
+                        ascii.push('.');
+                    }
+                }
+            }
+
+            _ = writeln!(
+                out,
+                "{}  {hex:<48} |{ascii:<16}|  {}",
+                addr + line_start,
+                labels.join(", ")
+            );
+        }
+
+        out
+    }
+
     fn write_io_ports<P, const SILENT: bool>(&mut self, addr: Address, value: P)
     where
         P: Primitive,
@@ -300,6 +536,11 @@ impl PSX {
             }
 
             match reg {
+                _ if reg.is_read_only() => {
+                    if !SILENT {
+                        trace!(self.loggers.bus, "ignoring write to read-only {reg:?}");
+                    }
+                }
                 io::Reg::InterruptStatus => {
                     let stat_bytes = &mut self.interrupts.status.as_mut_bytes()[offset..];
                     let value_bytes = value.as_bytes();
@@ -309,8 +550,16 @@ impl PSX {
                     }
                 }
                 io::Reg::InterruptMask => {
-                    let reg_bytes = self.interrupts.mask.as_mut_bytes();
-                    value.write_to(&mut reg_bytes[offset..]);
+                    write_masked(
+                        value,
+                        offset,
+                        interrupts::Mask::WRITE_MASK as u32,
+                        &mut self.interrupts.mask,
+                    );
+                }
+                io::Reg::RamSize => {
+                    let bytes = self.memory.ram_size.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
                 }
                 io::Reg::Dma0Base
                 | io::Reg::Dma1Base
@@ -320,8 +569,12 @@ impl PSX {
                 | io::Reg::Dma5Base
                 | io::Reg::Dma6Base => {
                     let channel = reg.dma_channel().unwrap();
-                    let bytes = self.dma.channels[channel as usize].base.as_mut_bytes();
-                    value.write_to(&mut bytes[offset..]);
+                    write_masked(
+                        value,
+                        offset,
+                        dma::ChannelBase::WRITE_MASK as u32,
+                        &mut self.dma.channels[channel as usize].base,
+                    );
 
                     self.scheduler.schedule(Event::DmaUpdate, 0);
                 }
@@ -347,8 +600,12 @@ impl PSX {
                 | io::Reg::Dma4Control
                 | io::Reg::Dma5Control => {
                     let channel = reg.dma_channel().unwrap();
-                    let bytes = self.dma.channels[channel as usize].control.as_mut_bytes();
-                    value.write_to(&mut bytes[offset..]);
+                    write_masked(
+                        value,
+                        offset,
+                        dma::ChannelControl::WRITE_MASK as u32,
+                        &mut self.dma.channels[channel as usize].control,
+                    );
 
                     self.scheduler.schedule(Event::DmaUpdate, 0);
                 }
@@ -392,7 +649,7 @@ impl PSX {
                 io::Reg::Gp0 => {
                     let mut raw = 0u32;
                     value.write_to(&mut raw.as_mut_bytes()[offset..]);
-                    self.gpu.render_queue.push_back(raw);
+                    gpu::push_gp0(self, raw);
 
                     self.scheduler.schedule(Event::Gpu, 0);
                     self.scheduler.schedule(Event::DmaUpdate, 0);
@@ -400,7 +657,7 @@ impl PSX {
                 io::Reg::Gp1 => {
                     let mut raw = 0u32;
                     value.write_to(&mut raw.as_mut_bytes()[offset..]);
-                    self.gpu.display_queue.push_back(raw);
+                    gpu::push_gp1(self, raw);
 
                     self.scheduler.schedule(Event::Gpu, 0);
                     self.scheduler.schedule(Event::DmaUpdate, 0);
@@ -418,36 +675,56 @@ impl PSX {
                         .schedule(Event::Cdrom(cdrom::Event::Update), 0);
                 }
                 io::Reg::Timer1Value => {
+                    sync_timer(self, TimerId::Timer1);
                     let bytes = self.timers.timer1.value.as_mut_bytes();
                     value.write_to(&mut bytes[offset..]);
+                    reschedule_timer(self, TimerId::Timer1);
                 }
                 io::Reg::Timer1Mode => {
+                    sync_timer(self, TimerId::Timer1);
                     self.timers.timer1.value = 0;
 
-                    let bytes = self.timers.timer1.mode.as_mut_bytes();
-                    value.write_to(&mut bytes[offset..]);
+                    write_masked(
+                        value,
+                        offset,
+                        timers::TimerMode::WRITE_MASK as u32,
+                        &mut self.timers.timer1.mode,
+                    );
 
                     self.timers.timer1.mode.set_no_irq(true);
+                    reschedule_timer(self, TimerId::Timer1);
                 }
                 io::Reg::Timer1Target => {
+                    sync_timer(self, TimerId::Timer1);
                     let bytes = self.timers.timer1.target.as_mut_bytes();
                     value.write_to(&mut bytes[offset..]);
+                    reschedule_timer(self, TimerId::Timer1);
                 }
                 io::Reg::Timer2Value => {
+                    sync_timer(self, TimerId::Timer2);
                     let bytes = self.timers.timer2.value.as_mut_bytes();
                     value.write_to(&mut bytes[offset..]);
+                    reschedule_timer(self, TimerId::Timer2);
                 }
                 io::Reg::Timer2Mode => {
+                    sync_timer(self, TimerId::Timer2);
                     self.timers.timer2.value = 0;
 
-                    let bytes = self.timers.timer2.mode.as_mut_bytes();
-                    value.write_to(&mut bytes[offset..]);
+                    write_masked(
+                        value,
+                        offset,
+                        timers::TimerMode::WRITE_MASK as u32,
+                        &mut self.timers.timer2.mode,
+                    );
 
                     self.timers.timer2.mode.set_no_irq(true);
+                    reschedule_timer(self, TimerId::Timer2);
                 }
                 io::Reg::Timer2Target => {
+                    sync_timer(self, TimerId::Timer2);
                     let bytes = self.timers.timer2.target.as_mut_bytes();
                     value.write_to(&mut bytes[offset..]);
+                    reschedule_timer(self, TimerId::Timer2);
                 }
                 io::Reg::JoyData => {
                     let mut bytes = [0; 4];
@@ -456,19 +733,106 @@ impl PSX {
                     self.sio0.tx = Some(bytes[0]);
                     self.scheduler.schedule(Event::Sio(sio0::Event::Update), 0);
                 }
-                io::Reg::JoyStat => {
-                    // read only
-                }
                 io::Reg::JoyMode => {
-                    let bytes = self.sio0.mode.as_mut_bytes();
-                    value.write_to(&mut bytes[offset..]);
+                    write_masked(
+                        value,
+                        offset,
+                        shimmer_core::sio0::Mode::WRITE_MASK as u16,
+                        &mut self.sio0.mode,
+                    );
                     self.scheduler.schedule(Event::Sio(sio0::Event::Update), 0);
                 }
                 io::Reg::JoyControl => {
-                    let bytes = self.sio0.control.as_mut_bytes();
-                    value.write_to(&mut bytes[offset..]);
+                    write_masked(
+                        value,
+                        offset,
+                        shimmer_core::sio0::Control::WRITE_MASK as u16,
+                        &mut self.sio0.control,
+                    );
                     self.scheduler.schedule(Event::Sio(sio0::Event::Update), 0);
                 }
+                io::Reg::SramInterruptAddress => {
+                    let bytes = self.spu.irq_address.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                io::Reg::SramAddress => {
+                    let mut address = self.spu.transfer_address;
+                    value.write_to(&mut address.as_mut_bytes()[offset..]);
+                    spu::write_transfer_address(self, address);
+                }
+                io::Reg::SramFifo => {
+                    let mut data = 0u16;
+                    value.write_to(&mut data.as_mut_bytes()[offset..]);
+                    spu::write_transfer(self, data);
+                }
+                io::Reg::SpuControl => {
+                    let mut bits = self.spu.control.to_bits();
+                    value.write_to(&mut bits.as_mut_bytes()[offset..]);
+                    spu::write_control(self, SpuControl::from_bits(bits));
+                }
+                io::Reg::SramControl => {
+                    let bytes = self.spu.transfer_control.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                io::Reg::ReverbVolume => {
+                    let bytes = self.spu.reverb.output_volume.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                io::Reg::MainVolume => {
+                    let bytes = self.spu.main_volume.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                _ if reg.is_spu_voice() => {
+                    let (index, start) = voice_reg_offset(reg);
+                    let voice = &mut self.spu.voices[index];
+                    value.write_to(&mut voice.regs.as_mut_bytes()[start + offset..]);
+
+                    // the repeat address can be changed while the voice plays
+                    if start + offset + size_of::<P>() > 14 {
+                        voice.repeat_address = voice.repeat_address_reg();
+                    }
+                }
+                io::Reg::VoiceKeyOn => {
+                    let mut voices = 0u32;
+                    value.write_to(&mut voices.as_mut_bytes()[offset..]);
+                    value.write_to(&mut self.spu.key_on.as_mut_bytes()[offset..]);
+                    spu::key_on(self, voices);
+                }
+                io::Reg::VoiceKeyOff => {
+                    let mut voices = 0u32;
+                    value.write_to(&mut voices.as_mut_bytes()[offset..]);
+                    value.write_to(&mut self.spu.key_off.as_mut_bytes()[offset..]);
+                    spu::key_off(self, voices);
+                }
+                io::Reg::VoiceChannelFmMode => {
+                    let bytes = self.spu.fm_mode.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                io::Reg::VoiceChannelNoiseMode => {
+                    let bytes = self.spu.noise_mode.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                io::Reg::CdVolume => {
+                    let bytes = self.spu.cd_volume.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                io::Reg::ExternVolume => {
+                    let bytes = self.spu.extern_volume.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                io::Reg::SramReverbAddress => {
+                    let bytes = self.spu.reverb.work_area_start.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                io::Reg::VoiceChannelReverbMode => {
+                    let bytes = self.spu.reverb.voices.as_mut_bytes();
+                    value.write_to(&mut bytes[offset..]);
+                }
+                _ if reg.is_reverb_config() => {
+                    let start = reverb_config_offset(reg) + offset;
+                    let bytes = self.spu.reverb.config.as_mut_bytes();
+                    value.write_to(&mut bytes[start..]);
+                }
                 _ => default(),
             };
         } else {
@@ -486,6 +850,20 @@ impl PSX {
         }
     }
 
+    /// Records a boot progress code written to the POST register.
+    fn write_post(&mut self, code: u8) {
+        match self.memory.post {
+            Some(previous) if previous == code => (),
+            Some(previous) => info!(
+                self.loggers.bus,
+                "POST code changed from 0x{previous:02X} to 0x{code:02X}"
+            ),
+            None => info!(self.loggers.bus, "POST code set to 0x{code:02X}"),
+        }
+
+        self.memory.post = Some(code);
+    }
+
     pub fn write_unaligned<P, const SILENT: bool>(&mut self, addr: Address, value: P)
     where
         P: Primitive,
@@ -503,15 +881,36 @@ impl PSX {
             };
 
             let offset = phys.value() - region.start().value();
+            if !SILENT && let Some(delay) = self.memory.expansion_delay(region) {
+                self.bus_stall += delay.write_cycles(size_of::<P>() as u32);
+            }
+
             match region {
-                Region::Ram => self.memory.ram[offset as usize..].write(value),
-                Region::RamMirror => {
-                    self.memory.ram[(offset & 0x001F_FFFF) as usize..].write(value);
+                Region::Ram | Region::RamMirror => {
+                    let Some(offset) = self.memory.ram_offset(phys) else {
+                        if !SILENT {
+                            warn!(
+                                self.loggers.bus,
+                                "write to {addr} ({phys}) which is outside of the RAM window"
+                            );
+                        }
+
+                        return;
+                    };
+
+                    self.memory.ram[offset..].write(value);
                 }
                 Region::Expansion1 => self.memory.expansion_1[offset as usize..].write(value),
                 Region::ScratchPad => self.memory.scratchpad[offset as usize..].write(value),
                 Region::IOPorts => self.write_io_ports::<P, SILENT>(addr, value),
-                Region::Expansion2 => self.memory.expansion_2[offset as usize..].write(value),
+                Region::Expansion2 => {
+                    self.memory.expansion_2[offset as usize..].write(value);
+
+                    let post = io::Reg::Post as u32 - Region::Expansion2.start().value();
+                    if (offset..offset + size_of::<P>() as u32).contains(&post) {
+                        self.write_post(self.memory.expansion_2[post as usize]);
+                    }
+                }
                 Region::Expansion3 => self.memory.expansion_3[offset as usize..].write(value),
                 Region::BIOS => self.memory.bios[offset as usize..].write(value),
             }
@@ -530,10 +929,395 @@ impl PSX {
         P: Primitive,
     {
         (addr.is_aligned(P::ALIGNMENT))
-            .then(|| self.write_unaligned::<P, SILENT>(addr, value))
+            .then(|| {
+                self.record_access(AccessKind::Write, addr, value);
+                self.write_unaligned::<P, SILENT>(addr, value);
+            })
             .ok_or(MisalignedAddressErr {
                 addr,
                 alignment: P::ALIGNMENT,
             })
     }
+
+    /// Reads the bytes selected by `enables` (bit `i` selects byte `i`) of the word containing
+    /// `addr`, as done by `LWL` and `LWR`. Unselected bytes read as zero.
+    ///
+    /// Like on hardware, the bus sees a single access: the narrowest naturally aligned one covering
+    /// the selected bytes. Read side effects of IO ports, such as popping a GPUREAD word, therefore
+    /// happen exactly once per instruction.
+    pub fn read_partial<const SILENT: bool>(&mut self, addr: Address, enables: u8) -> u32 {
+        let word = Address(addr.value() & !3);
+        let (offset, size) = covering_access(enables);
+
+        let value = match size {
+            1 => u32::from(self.read_unaligned::<u8, SILENT>(word + offset)) << (8 * offset),
+            2 => u32::from(self.read_unaligned::<u16, SILENT>(word + offset)) << (8 * offset),
+            _ => self.read_unaligned::<u32, SILENT>(word),
+        };
+
+        let mask = byte_mask(enables);
+        self.record_access(AccessKind::Read, word, value & mask);
+        value & mask
+    }
+
+    /// Returns the current value of the IO word at `word`, without the side effects of reading it,
+    /// for partial writes to merge into. Words whose writes aren't stored, such as GP0 or the CDROM
+    /// registers, are zero, like the unwritten bytes of narrow writes to them.
+    fn io_word(&mut self, word: Address) -> u32 {
+        match io::Reg::reg_and_offset(word).map(|(reg, _)| reg) {
+            Some(
+                io::Reg::Gp0
+                | io::Reg::Gp1
+                | io::Reg::Cdrom0
+                | io::Reg::Cdrom1
+                | io::Reg::Cdrom2
+                | io::Reg::Cdrom3
+                | io::Reg::JoyData
+                | io::Reg::JoyStat,
+            ) => 0,
+            Some(io::Reg::Timer1Mode) => self.timers.timer1.mode.to_bits(),
+            Some(io::Reg::Timer2Mode) => self.timers.timer2.mode.to_bits(),
+            Some(io::Reg::JoyMode) => {
+                let mut bytes = [0; 4];
+                bytes[..2].copy_from_slice(self.sio0.mode.as_bytes());
+                bytes[2..].copy_from_slice(self.sio0.control.as_bytes());
+                u32::from_le_bytes(bytes)
+            }
+            _ => self.read_io_ports::<u32, true>(word),
+        }
+    }
+
+    /// Writes the bytes selected by `enables` (bit `i` selects byte `i`) of `value` to the word
+    /// containing `addr`, as done by `SWL` and `SWR`.
+    ///
+    /// Like on hardware, the bus sees a single access: the narrowest naturally aligned one covering
+    /// the selected bytes. Memory only has the selected bytes written. IO ports don't decode byte
+    /// enables, so a three byte write to one of them is a single word write of the selected bytes
+    /// merged into the current value of the register. Reading that value has no side effects.
+    pub fn write_partial<const SILENT: bool>(&mut self, addr: Address, value: u32, enables: u8) {
+        let word = Address(addr.value() & !3);
+        self.record_access(AccessKind::Write, word, value);
+
+        let (offset, size) = covering_access(enables);
+        let bytes = value.to_le_bytes();
+
+        match size {
+            1 => self.write_unaligned::<u8, SILENT>(word + offset, bytes[offset as usize]),
+            2 => self.write_unaligned::<u16, SILENT>(
+                word + offset,
+                u16::from_le_bytes([bytes[offset as usize], bytes[offset as usize + 1]]),
+            ),
+            _ if enables == 0xF => self.write_unaligned::<u32, SILENT>(word, value),
+            _ if word.region() == Some(Region::IOPorts) => {
+                let mask = byte_mask(enables);
+                let current = self.io_word(word);
+                self.write_unaligned::<u32, SILENT>(word, (current & !mask) | (value & mask));
+            }
+            _ => {
+                for (i, &byte) in bytes.iter().enumerate() {
+                    if enables & (1 << i) != 0 {
+                        self.write_unaligned::<u8, SILENT>(word + i as u32, byte);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Emulator, PSX, testing};
+    use shimmer_core::{
+        CYCLES_MILLIS,
+        cpu::Reg,
+        dma, interrupts,
+        mem::{Address, Primitive, Region, io},
+        sio0, timers,
+    };
+    use strum::VariantArray;
+
+    const PROGRAM: Address = Address(0x8000_2000);
+
+    /// Writes all ones to the 32-bit `reg`, returning what it read as before and after the write.
+    fn write_ones(psx: &mut PSX, reg: io::Reg) -> (u64, u64) {
+        let before = testing::read::<u32>(psx, reg);
+        testing::write::<u32>(psx, reg, 0xFFFF_FFFF);
+        let after = testing::read::<u32>(psx, reg);
+        (u64::from(before), u64::from(after))
+    }
+
+    /// Writes all ones to the 16-bit `reg`, returning what it read as before and after the write.
+    fn write_ones_16(psx: &mut PSX, reg: io::Reg) -> (u64, u64) {
+        let before = testing::read::<u16>(psx, reg);
+        testing::write::<u16>(psx, reg, 0xFFFF);
+        let after = testing::read::<u16>(psx, reg);
+        (u64::from(before), u64::from(after))
+    }
+
+    /// Reads a `P` from every aligned offset of `reg`, then writes back what was read and a value
+    /// with every bit set.
+    fn probe<P>(emulator: &mut Emulator, reg: io::Reg)
+    where
+        P: Primitive,
+    {
+        let addresses = (0..reg.width() as u32).map(|offset| Address(reg as u32 + offset));
+        for addr in addresses.filter(|addr| addr.value() % P::ALIGNMENT == 0) {
+            let psx = emulator.psx_mut();
+            if let Ok(value) = psx.read::<P, true>(addr) {
+                _ = psx.write::<P, true>(addr, value);
+                _ = psx.write::<P, true>(addr, P::read_from_buf(&[0xFF; 4]));
+            }
+        }
+    }
+
+    /// The value a register reads as after writing all ones to it: bits in `mask` are set, and
+    /// the others keep their value.
+    fn masked(before: u64, mask: u64) -> u64 {
+        (before & !mask) | mask
+    }
+
+    #[test]
+    fn masked_registers_store_only_writable_bits() {
+        use io::Reg::*;
+
+        let checklist = [
+            (InterruptMask, interrupts::Mask::WRITE_MASK),
+            (Dma0Base, dma::ChannelBase::WRITE_MASK),
+            (Dma1Base, dma::ChannelBase::WRITE_MASK),
+            (Dma2Base, dma::ChannelBase::WRITE_MASK),
+            (Dma3Base, dma::ChannelBase::WRITE_MASK),
+            (Dma4Base, dma::ChannelBase::WRITE_MASK),
+            (Dma5Base, dma::ChannelBase::WRITE_MASK),
+            (Dma6Base, dma::ChannelBase::WRITE_MASK),
+            (Dma0Control, dma::ChannelControl::WRITE_MASK),
+            (Dma1Control, dma::ChannelControl::WRITE_MASK),
+            (Dma2Control, dma::ChannelControl::WRITE_MASK),
+            (Dma3Control, dma::ChannelControl::WRITE_MASK),
+            (Dma4Control, dma::ChannelControl::WRITE_MASK),
+            (Dma5Control, dma::ChannelControl::WRITE_MASK),
+            (Dma6Control, dma::ChannelControl::DMA6_WRITE_MASK),
+        ];
+
+        let mut psx = testing::psx();
+        for (reg, mask) in checklist {
+            let (before, after) = write_ones(&mut psx, reg);
+            assert_eq!(after, masked(before, mask), "{reg:?}");
+        }
+
+        for (reg, mask) in [
+            (JoyMode, sio0::Mode::WRITE_MASK),
+            (JoyControl, sio0::Control::WRITE_MASK),
+        ] {
+            let (before, after) = write_ones_16(&mut psx, reg);
+            assert_eq!(after, masked(before, mask), "{reg:?}");
+        }
+    }
+
+    #[test]
+    fn dma_interrupt_flags_are_acknowledged_by_writing_ones() {
+        let mut psx = testing::psx();
+        let (before, after) = write_ones(&mut psx, io::Reg::DmaInterrupt);
+
+        let flags = dma::InterruptControl::CHANNEL_INTERRUPT_FLAGS_MASK;
+        let mask = dma::InterruptControl::WRITE_MASK;
+        assert_eq!(after, masked(before, mask) & !flags);
+    }
+
+    #[test]
+    fn timer_modes_keep_status_bits_and_reserved_bits_read_as_zero() {
+        let mut psx = testing::psx();
+        for reg in [io::Reg::Timer1Mode, io::Reg::Timer2Mode] {
+            let (_, after) = write_ones(&mut psx, reg);
+
+            // writing the mode resets the IRQ flag to "no IRQ" and clears the reached flags
+            let expected = timers::TimerMode::WRITE_MASK | timers::TimerMode::NO_IRQ_MASK;
+            assert_eq!(after, expected, "{reg:?}");
+            assert_eq!(after & 0xFFFF_E000, 0, "{reg:?}");
+        }
+    }
+
+    #[test]
+    fn hexdump_annotates_ram_lines() {
+        let mut psx = testing::psx();
+        for (offset, byte) in b"Hello, world!\0\x01\x02hey!".iter().enumerate() {
+            psx.write::<u8, true>(Address(0x8000_0100 + offset as u32), *byte)
+                .unwrap();
+        }
+
+        let dump = psx.hexdump(Address(0x8000_0100), 20);
+        let lines: Vec<_> = dump.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "0x8000_0100  48 65 6C 6C 6F 2C 20 77 6F 72 6C 64 21 00 01 02  |Hello, world!...|  Ram",
+                "0x8000_0110  68 65 79 21                                      |hey!            |  Ram",
+            ]
+        );
+    }
+
+    #[test]
+    fn hexdump_hides_io_ports() {
+        let psx = testing::psx();
+
+        // the end of the unmapped area after the scratchpad, and the first IO registers
+        let dump = psx.hexdump(Address(0x1F80_0FF8), 16);
+        assert_eq!(
+            dump,
+            format!(
+                "0x1F80_0FF8  {}|{}|  Unmapped, Expansion1Base, Expansion2Base\n",
+                "-- ".repeat(16),
+                ".".repeat(16)
+            )
+        );
+    }
+
+    /// Sets the RAM window to `window`, as encoded in RAM_SIZE.
+    fn set_ram_window(psx: &mut PSX, window: u32) {
+        testing::write::<u32>(psx, io::Reg::RamSize, window << 9);
+    }
+
+    fn read_ram(psx: &mut PSX, addr: u32) -> u32 {
+        psx.read::<u32, true>(Address(addr)).unwrap()
+    }
+
+    #[test]
+    fn ram_is_mirrored_throughout_the_window() {
+        let mut psx = testing::psx();
+        psx.write::<u32, true>(Address(0x10), 0x1234_5678).unwrap();
+
+        // 8 MiB, as configured by the BIOS
+        for mirror in [
+            0x0020_0010,
+            0x0040_0010,
+            0x0060_0010,
+            0x8060_0010,
+            0xA020_0010,
+        ] {
+            assert_eq!(read_ram(&mut psx, mirror), 0x1234_5678, "{mirror:#010X}");
+            assert_eq!(psx.peek::<u32>(Address(mirror)), Some(0x1234_5678));
+        }
+
+        psx.write::<u32, true>(Address(0x0060_0010), 0xCAFE_BABE)
+            .unwrap();
+        assert_eq!(read_ram(&mut psx, 0x10), 0xCAFE_BABE);
+    }
+
+    #[test]
+    fn accesses_outside_of_the_ram_window_are_locked() {
+        let mut psx = testing::psx();
+        psx.write::<u32, true>(Address(0x10), 0x1111_1111).unwrap();
+        psx.write::<u32, true>(Address(0x0010_0010), 0x2222_2222)
+            .unwrap();
+
+        // 4 MiB: the first mirror is still mapped, the others are locked
+        set_ram_window(&mut psx, 1);
+        assert_eq!(read_ram(&mut psx, 0x0020_0010), 0x1111_1111);
+        assert_eq!(read_ram(&mut psx, 0x0040_0010), 0);
+        assert_eq!(psx.peek::<u32>(Address(0x0040_0010)), None);
+
+        psx.write::<u32, true>(Address(0x0040_0010), 0x3333_3333)
+            .unwrap();
+        assert_eq!(read_ram(&mut psx, 0x10), 0x1111_1111);
+
+        // 2 MiB: every mirror is locked
+        set_ram_window(&mut psx, 4);
+        assert_eq!(read_ram(&mut psx, 0x0020_0010), 0);
+        assert_eq!(read_ram(&mut psx, 0x0010_0010), 0x2222_2222);
+
+        // 1 MiB: even the second half of RAM itself is locked, in every segment
+        set_ram_window(&mut psx, 0);
+        assert_eq!(read_ram(&mut psx, 0x10), 0x1111_1111);
+        for addr in [0x0010_0010, 0x8010_0010, 0xA010_0010] {
+            assert_eq!(read_ram(&mut psx, addr), 0, "{addr:#010X}");
+            assert_eq!(psx.peek::<u32>(Address(addr)), None, "{addr:#010X}");
+        }
+
+        psx.write::<u32, true>(Address(0x8010_0010), 0x4444_4444)
+            .unwrap();
+
+        // the locked part of RAM kept its contents
+        set_ram_window(&mut psx, 5);
+        assert_eq!(read_ram(&mut psx, 0x0010_0010), 0x2222_2222);
+    }
+
+    #[test]
+    fn expansion1_rom_reads_back_in_every_segment() {
+        let rom = (0..=255).collect::<Vec<u8>>();
+        let mut emulator = testing::emulator(testing::builder().expansion1(rom.clone()));
+
+        let start = Region::Expansion1.start().value();
+        for segment in [0x0000_0000, 0x8000_0000, 0xA000_0000] {
+            for (offset, word) in rom.chunks_exact(4).enumerate() {
+                let addr = Address(segment | (start + 4 * offset as u32));
+                let expected = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+                assert_eq!(
+                    read_ram(&mut emulator.psx, addr.value()),
+                    expected,
+                    "{addr}"
+                );
+            }
+        }
+
+        // past the end of the ROM
+        assert_eq!(read_ram(&mut emulator.psx, start + 0x100), 0);
+    }
+
+    #[test]
+    fn expansion_reads_stall_for_the_configured_delay() {
+        // lw $t1, 0($t0); nop; nop; nop
+        let code = [0x8D09_0000, 0, 0, 0u32];
+
+        // cycles taken by the code reading from `addr` with the given delay/size register
+        let run = |addr: u32, delay: u32| {
+            let mut emulator = testing::emulator(testing::builder());
+            emulator.load_raw(PROGRAM, &code.map(u32::to_le_bytes).concat());
+            emulator.set_pc(PROGRAM);
+            testing::write::<u32>(&mut emulator.psx, io::Reg::Expansion1Delay, delay);
+            emulator.psx.cpu.regs.write(Reg::T0, addr);
+            emulator.step_instructions(code.len() as u64)
+        };
+
+        // read delay of 7, i.e. 8 cycles per access, over an 8-bit and a 16-bit bus
+        let expansion = Region::Expansion1.start().value();
+        for (delay, expected) in [(0x0000_0070, 32), (0x0000_1070, 16)] {
+            let stall = run(expansion, delay) - run(0x8000_3000, delay);
+            assert_eq!(stall, expected, "{delay:08X}");
+        }
+    }
+
+    #[test]
+    fn every_io_register_can_be_accessed_with_every_width() {
+        let mut emulator = testing::emulator(testing::builder());
+        for &reg in io::Reg::VARIANTS {
+            probe::<u8>(&mut emulator, reg);
+            probe::<u16>(&mut emulator, reg);
+            probe::<u32>(&mut emulator, reg);
+
+            // let the events scheduled by the writes, such as transfers and commands, run
+            emulator.cycle_for(CYCLES_MILLIS);
+        }
+    }
+
+    #[test]
+    fn writes_to_read_only_registers_are_ignored() {
+        let mut psx = testing::psx();
+        for &reg in io::Reg::VARIANTS.iter().filter(|reg| reg.is_read_only()) {
+            let stubs = psx.memory.io_stubs.to_vec();
+            let (before, after) = if reg.width() == 4 {
+                let before = testing::read::<u32>(&mut psx, reg);
+                testing::write::<u32>(&mut psx, reg, !before);
+                (before, testing::read::<u32>(&mut psx, reg))
+            } else {
+                let before = testing::read::<u16>(&mut psx, reg);
+                testing::write::<u16>(&mut psx, reg, !before);
+                (
+                    u32::from(before),
+                    u32::from(testing::read::<u16>(&mut psx, reg)),
+                )
+            };
+
+            assert_eq!(after, before, "{reg:?}");
+            assert_eq!(psx.memory.io_stubs[..], stubs[..], "{reg:?}");
+        }
+    }
 }
@@ -1,6 +1,8 @@
 mod command;
 mod control;
 mod interrupt;
+pub mod region;
+pub mod rom;
 
 use crate::{PSX, scheduler};
 use shimmer_core::{
@@ -8,12 +10,12 @@ use shimmer_core::{
     cdrom::{Bank, Command, InterruptKind, Mode, Reg, RegWrite, Sector},
     interrupts::Interrupt,
 };
-use std::{
-    collections::VecDeque,
-    io::{Read, Seek},
-};
+use std::collections::VecDeque;
 use tinylog::{debug, error, info, trace, warn};
 
+pub use region::{LicenseRegion, RegionPolicy};
+pub use rom::*;
+
 pub const CDROM_VERSION: [u8; 4] = [0x94, 0x09, 0x19, 0xc0];
 
 pub const COMPLETE_GETID_DELAY: Cycles = 574 * CYCLES_MICROS;
@@ -21,9 +23,17 @@ pub const COMPLETE_PAUSE_DELAY: Cycles = 64 * CYCLES_MILLIS + 36 * CYCLES_MICROS
 pub const COMPLETE_PAUSE_NOP_DELAY: Cycles = 232 * CYCLES_MICROS;
 pub const READ_DELAY: Cycles = 13 * CYCLES_MILLIS + 325 * CYCLES_MICROS;
 pub const SEEK_DELAY: Cycles = 1 * CYCLES_MILLIS;
+/// Delay between the acknowledge and the completion of Init, which spins the motor up. Roughly
+/// the time it takes to read a sector at single speed.
+pub const COMPLETE_INIT_DELAY: Cycles = READ_DELAY;
+
+/// The mode set by Init and Reset: the whole sector except for the sync bytes is read.
+pub const DEFAULT_MODE: u8 = 0x20;
 
-pub trait Rom: std::fmt::Debug + std::io::Read + std::io::Seek + Send {}
-impl<T> Rom for T where T: std::fmt::Debug + std::io::Read + std::io::Seek + Send {}
+/// Error code for when the drive is not ready (e.g. no disk).
+pub const ERROR_NOT_READY: u8 = 0x80;
+/// Error code for when a seek or read fails.
+pub const ERROR_SEEK_FAILED: u8 = 0x04;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Event {
@@ -38,17 +48,40 @@ pub struct Cdrom {
     rom: Option<Box<dyn Rom>>,
     command_queue: VecDeque<u8>,
     interrupt_queue: VecDeque<InterruptKind>,
+    region_policy: RegionPolicy,
+    bios_region: Option<LicenseRegion>,
 }
 
 impl Cdrom {
-    pub fn new(rom: Option<Box<dyn Rom>>) -> Self {
+    pub fn new(
+        rom: Option<Box<dyn Rom>>,
+        region_policy: RegionPolicy,
+        bios_region: Option<LicenseRegion>,
+    ) -> Self {
         Self {
             rom,
             command_queue: VecDeque::new(),
             interrupt_queue: VecDeque::new(),
+            region_policy,
+            bios_region,
         }
     }
 
+    /// The region reported in the GetID response, according to the region policy. Defaults to
+    /// America if it can't be determined.
+    fn reported_region(&mut self) -> LicenseRegion {
+        let disc_region =
+            |rom: &mut Option<Box<dyn Rom>>| rom.as_deref_mut().and_then(LicenseRegion::from_disc);
+
+        let region = match self.region_policy {
+            RegionPolicy::AutoMatchBios => self.bios_region.or_else(|| disc_region(&mut self.rom)),
+            RegionPolicy::ForceDisc => disc_region(&mut self.rom),
+            RegionPolicy::Force(region) => Some(region),
+        };
+
+        region.unwrap_or(LicenseRegion::America)
+    }
+
     fn next_interrupt(&mut self, psx: &mut PSX) {
         if psx.cdrom.interrupt_status.kind() == InterruptKind::None
             && let Some(kind) = self.interrupt_queue.pop_front()
@@ -68,6 +101,48 @@ impl Cdrom {
         self.rom = None;
     }
 
+    /// Aborts any ongoing read or seek, cancelling their pending events, and resets the mode.
+    fn abort(&mut self, psx: &mut PSX) {
+        psx.cdrom.status.set_read(false);
+        psx.cdrom.status.set_seek(false);
+        psx.cdrom.mode = Mode::from_bits(DEFAULT_MODE);
+
+        psx.scheduler.cancel_if(|event| {
+            matches!(
+                event,
+                scheduler::Event::Cdrom(Event::Read | Event::Complete(_))
+            )
+        });
+    }
+
+    /// Stops reading and responds with an INT5 error.
+    fn disk_error(&mut self, psx: &mut PSX, code: u8) {
+        psx.cdrom.status.set_read(false);
+
+        let stat = psx.cdrom.status.with_error(true);
+        psx.cdrom.result_queue.extend([stat.to_bits(), code]);
+        self.interrupt_queue.push_back(InterruptKind::DiskError);
+    }
+
+    fn read_sector(rom: &mut dyn Rom, psx: &mut PSX) -> Result<(), RomError> {
+        info!(psx.loggers.cdrom, "read from sector {}", psx.cdrom.location);
+        let size = psx.cdrom.mode.sector_size().value();
+        let offset = psx.cdrom.mode.sector_size().offset();
+
+        if let Some(index) = psx.cdrom.location.index() {
+            let mut sector = [0; SECTOR_LEN];
+            let kind = rom.read_sector(index as u32, &mut sector)?;
+            trace!(psx.loggers.cdrom, "sector is {kind:?}");
+
+            psx.cdrom.sector_data = sector[offset..offset + size].iter().copied().collect();
+        } else {
+            error!(psx.loggers.cdrom, "reading from pregap");
+            psx.cdrom.sector_data = VecDeque::from(vec![0; size]);
+        }
+
+        Ok(())
+    }
+
     pub fn update(&mut self, psx: &mut PSX, event: Event) {
         psx.cdrom.status.set_shell_open(self.rom.is_none());
         psx.cdrom.update_status();
@@ -113,9 +188,28 @@ impl Cdrom {
 
                 let mut push_stat = true;
                 match cmd {
-                    Command::Nop | Command::Demute | Command::Mute => (),
+                    Command::Nop => (),
+                    Command::Mute => {
+                        psx.cdrom.muted = true;
+                    }
+                    Command::Demute => {
+                        psx.cdrom.muted = false;
+                    }
+                    // responds with INT3, then INT2 once the motor is spinning
                     Command::Init => {
-                        sched_complete(psx, READ_DELAY);
+                        self.abort(psx);
+                        psx.cdrom.status.set_motor_on(true);
+                        sched_complete(psx, COMPLETE_INIT_DELAY);
+                    }
+                    // behaves like opening and closing the shell. unlike Init, there's no second
+                    // response
+                    Command::Reset => {
+                        self.abort(psx);
+                        self.interrupt_queue.clear();
+                        psx.cdrom.parameter_queue.clear();
+                        psx.cdrom.result_queue.clear();
+                        psx.cdrom.sector_data.clear();
+                        psx.cdrom.muted = false;
                     }
                     Command::Test => {
                         let param = psx.cdrom.parameter_queue.pop_front().unwrap_or_default();
@@ -207,13 +301,16 @@ impl Cdrom {
             Event::Complete(cmd) => {
                 let mut push_stat = true;
                 match cmd {
-                    Command::Init => {
-                        psx.cdrom.mode = Mode::from_bits(0x20);
-                    }
+                    Command::Init => (),
                     Command::GetID => {
-                        psx.cdrom
-                            .result_queue
-                            .extend([0x02, 0x00, 0x20, 0x00, 0x53, 0x43, 0x45, 0x41]);
+                        let region = self.reported_region();
+                        info!(
+                            psx.loggers.cdrom,
+                            "reporting disc as licensed for {region:?}"
+                        );
+
+                        psx.cdrom.result_queue.extend([0x02, 0x00, 0x20, 0x00]);
+                        psx.cdrom.result_queue.extend(region.license());
                         push_stat = false;
                     }
                     Command::Pause => {
@@ -236,35 +333,34 @@ impl Cdrom {
                     return;
                 }
 
-                let Some(rom) = &mut self.rom else {
-                    panic!("reading without a disk");
+                let result = match &mut self.rom {
+                    Some(rom) => Self::read_sector(rom.as_mut(), psx).map_err(Some),
+                    None => Err(None),
                 };
 
-                info!(psx.loggers.cdrom, "read from sector {}", psx.cdrom.location);
-                let size = psx.cdrom.mode.sector_size().value();
-                let offset = psx.cdrom.mode.sector_size().offset();
-
-                if let Some(index) = psx.cdrom.location.index() {
-                    let mut buf = vec![0; size];
-                    let start_byte = index * 0x930;
-                    rom.seek(std::io::SeekFrom::Start(start_byte + offset as u64))
-                        .unwrap();
-                    rom.read_exact(&mut buf).unwrap();
-
-                    psx.cdrom.sector_data = VecDeque::from(buf);
-                } else {
-                    error!(psx.loggers.cdrom, "reading from pregap");
-                    psx.cdrom.sector_data = VecDeque::from(vec![0; size]);
-                }
-
-                psx.cdrom.location.advance();
-                psx.scheduler.schedule(
-                    scheduler::Event::Cdrom(Event::Read),
-                    READ_DELAY / psx.cdrom.mode.speed().factor(),
-                );
+                match result {
+                    Ok(()) => {
+                        psx.cdrom.location.advance();
+                        psx.scheduler.schedule(
+                            scheduler::Event::Cdrom(Event::Read),
+                            READ_DELAY / psx.cdrom.mode.speed().factor(),
+                        );
 
-                psx.cdrom.result_queue.push_back(psx.cdrom.status.to_bits());
-                self.interrupt_queue.push_back(InterruptKind::DataReady);
+                        psx.cdrom.result_queue.push_back(psx.cdrom.status.to_bits());
+                        self.interrupt_queue.push_back(InterruptKind::DataReady);
+                    }
+                    Err(Some(e)) => {
+                        error!(
+                            psx.loggers.cdrom,
+                            "failed to read sector {}: {e}", psx.cdrom.location
+                        );
+                        self.disk_error(psx, ERROR_SEEK_FAILED);
+                    }
+                    Err(None) => {
+                        error!(psx.loggers.cdrom, "reading without a disk");
+                        self.disk_error(psx, ERROR_NOT_READY);
+                    }
+                }
             }
         }
 
@@ -286,3 +382,227 @@ impl Cdrom {
         psx.cdrom.update_status();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::command::{ACK_DEFAULT_DELAY, ACK_INIT_DELAY};
+    use super::*;
+    use crate::testing;
+    use std::io::Cursor;
+
+    /// Returns a cooked image whose license sector holds `license`.
+    fn disc(license: &[u8]) -> Box<dyn Rom> {
+        let mut image = vec![0; 8 * DATA_LEN];
+        let start = 4 * DATA_LEN + 16;
+        image[start..start + license.len()].copy_from_slice(license);
+        Box::new(BinRom::new(Cursor::new(image)).unwrap())
+    }
+
+    /// Completes GetID with the given disc, policy and BIOS region, returning the response.
+    fn get_id(
+        rom: Option<Box<dyn Rom>>,
+        policy: RegionPolicy,
+        bios_region: Option<LicenseRegion>,
+    ) -> Vec<u8> {
+        let mut psx = testing::psx();
+        let mut cdrom = Cdrom::new(rom, policy, bios_region);
+        cdrom.update(&mut psx, Event::Complete(Command::GetID));
+        psx.cdrom.result_queue.into_iter().collect()
+    }
+
+    fn response(license: &[u8; 4]) -> Vec<u8> {
+        [[0x02, 0x00, 0x20, 0x00].as_slice(), license].concat()
+    }
+
+    // the license text is split oddly on actual discs
+    const AMERICA: &[u8] = b"Licensed  by          Sony Computer Entertainment Amer  ica ";
+    const EUROPE: &[u8] = b"Licensed  by          Sony Computer Entertainment Euro pe   ";
+
+    #[test]
+    fn detects_the_region_of_the_bios() {
+        let mut bios = vec![0xFF; 64];
+        bios.extend(b"System ROM Version 4.1 12/16/97 E\0");
+        assert_eq!(LicenseRegion::from_bios(&bios), Some(LicenseRegion::Europe));
+
+        // early versions have no region letter
+        let bios = b"System ROM Version 1.0 09/22/94\0".to_vec();
+        assert_eq!(LicenseRegion::from_bios(&bios), None);
+    }
+
+    #[test]
+    fn auto_match_bios_reports_the_bios_region() {
+        let id = get_id(
+            Some(disc(EUROPE)),
+            RegionPolicy::AutoMatchBios,
+            Some(LicenseRegion::Japan),
+        );
+        assert_eq!(id, response(b"SCEI"));
+
+        // unless it's unknown, in which case the disc decides
+        let id = get_id(Some(disc(EUROPE)), RegionPolicy::AutoMatchBios, None);
+        assert_eq!(id, response(b"SCEE"));
+    }
+
+    #[test]
+    fn force_disc_reports_the_disc_region() {
+        let id = get_id(
+            Some(disc(AMERICA)),
+            RegionPolicy::ForceDisc,
+            Some(LicenseRegion::Japan),
+        );
+        assert_eq!(id, response(b"SCEA"));
+
+        let id = get_id(
+            Some(disc(EUROPE)),
+            RegionPolicy::ForceDisc,
+            Some(LicenseRegion::America),
+        );
+        assert_eq!(id, response(b"SCEE"));
+    }
+
+    #[test]
+    fn force_reports_the_given_region() {
+        let id = get_id(
+            Some(disc(AMERICA)),
+            RegionPolicy::Force(LicenseRegion::Europe),
+            Some(LicenseRegion::Japan),
+        );
+        assert_eq!(id, response(b"SCEE"));
+    }
+
+    #[test]
+    fn unknown_regions_report_america() {
+        let id = get_id(
+            Some(disc(b"no license here")),
+            RegionPolicy::ForceDisc,
+            None,
+        );
+        assert_eq!(id, response(b"SCEA"));
+
+        let id = get_id(None, RegionPolicy::AutoMatchBios, None);
+        assert_eq!(id, response(b"SCEA"));
+    }
+
+    /// Runs the events of the drive for `cycles` cycles, acknowledging interrupts as soon as
+    /// they're raised, like a program polling the drive would. Returns the kind of each
+    /// interrupt along with its response.
+    fn run(cdrom: &mut Cdrom, psx: &mut PSX, cycles: Cycles) -> Vec<(InterruptKind, Vec<u8>)> {
+        let end = psx.scheduler.elapsed() + cycles;
+        let mut interrupts = Vec::new();
+        while let Some(after) = psx.scheduler.until_next() {
+            if psx.scheduler.elapsed() + after > end {
+                break;
+            }
+
+            psx.scheduler.advance(after);
+            while let Some(event) = psx.scheduler.pop() {
+                if let scheduler::Event::Cdrom(event) = event {
+                    cdrom.update(psx, event);
+                }
+
+                loop {
+                    let kind = psx.cdrom.interrupt_status.kind();
+                    if kind == InterruptKind::None {
+                        break;
+                    }
+
+                    interrupts.push((kind, psx.cdrom.result_queue.drain(..).collect()));
+                    cdrom.ack_interrupt_status(psx, 0b111);
+                }
+            }
+        }
+
+        psx.scheduler.advance(end - psx.scheduler.elapsed());
+        interrupts
+    }
+
+    /// Issues the command `cmd` with the given parameters.
+    fn command(cdrom: &mut Cdrom, psx: &mut PSX, cmd: u8, params: &[u8]) {
+        for &param in params {
+            cdrom.push_parameter(psx, param);
+        }
+
+        cdrom.command(psx, cmd);
+    }
+
+    fn drive() -> (Cdrom, PSX) {
+        let mut psx = testing::psx();
+        psx.scheduler.cancel_if(|_| true);
+        let cdrom = Cdrom::new(Some(disc(AMERICA)), RegionPolicy::default(), None);
+        (cdrom, psx)
+    }
+
+    fn kinds(interrupts: &[(InterruptKind, Vec<u8>)]) -> Vec<InterruptKind> {
+        interrupts.iter().map(|(kind, _)| *kind).collect()
+    }
+
+    fn reading(stat: u8) -> bool {
+        shimmer_core::cdrom::Status::from_bits(stat).read()
+    }
+
+    const SET_LOCATION: u8 = 0x02;
+    const READ_N: u8 = 0x06;
+    const INIT: u8 = 0x0A;
+    const MUTE: u8 = 0x0B;
+    const DEMUTE: u8 = 0x0C;
+    const SET_MODE: u8 = 0x0E;
+
+    #[test]
+    fn init_stops_an_ongoing_read() {
+        let (mut cdrom, mut psx) = drive();
+
+        // read at double speed from 00:02:00
+        command(&mut cdrom, &mut psx, SET_MODE, &[0x80]);
+        run(&mut cdrom, &mut psx, ACK_DEFAULT_DELAY);
+        command(&mut cdrom, &mut psx, SET_LOCATION, &[0x00, 0x02, 0x00]);
+        run(&mut cdrom, &mut psx, ACK_DEFAULT_DELAY);
+        command(&mut cdrom, &mut psx, READ_N, &[]);
+
+        let read_delay = READ_DELAY / 2;
+        let interrupts = run(&mut cdrom, &mut psx, ACK_DEFAULT_DELAY + 2 * read_delay);
+        assert_eq!(
+            kinds(&interrupts),
+            [
+                InterruptKind::Acknowledge,
+                InterruptKind::DataReady,
+                InterruptKind::DataReady
+            ]
+        );
+
+        // the next sector is due before Init completes, but after it's acknowledged
+        command(&mut cdrom, &mut psx, INIT, &[]);
+        let interrupts = run(
+            &mut cdrom,
+            &mut psx,
+            ACK_INIT_DELAY + COMPLETE_INIT_DELAY + 4 * read_delay,
+        );
+
+        assert_eq!(
+            kinds(&interrupts),
+            [InterruptKind::Acknowledge, InterruptKind::Complete]
+        );
+        for (_, response) in &interrupts {
+            assert_eq!(response.len(), 1);
+            assert!(!reading(response[0]));
+        }
+
+        assert!(!psx.cdrom.status.read());
+        assert_eq!(psx.cdrom.mode.to_bits(), DEFAULT_MODE);
+        assert_eq!(psx.scheduler.len(), 0);
+    }
+
+    #[test]
+    fn mute_and_demute_are_acknowledged() {
+        let (mut cdrom, mut psx) = drive();
+
+        command(&mut cdrom, &mut psx, MUTE, &[]);
+        let interrupts = run(&mut cdrom, &mut psx, COMPLETE_INIT_DELAY);
+        assert_eq!(kinds(&interrupts), [InterruptKind::Acknowledge]);
+        assert!(psx.cdrom.muted);
+
+        command(&mut cdrom, &mut psx, DEMUTE, &[]);
+        let interrupts = run(&mut cdrom, &mut psx, COMPLETE_INIT_DELAY);
+        assert_eq!(kinds(&interrupts), [InterruptKind::Acknowledge]);
+        assert!(!psx.cdrom.muted);
+    }
+}
@@ -0,0 +1,79 @@
+//! Licensing regions and how the drive chooses which one to report for a disc.
+
+use super::rom::{Rom, SECTOR_LEN};
+
+/// The sector of a disc holding the license text.
+const LICENSE_SECTOR: u32 = 4;
+
+/// A licensing region. The BIOS refuses to boot discs licensed for a region other than its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LicenseRegion {
+    Japan,
+    America,
+    Europe,
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+impl LicenseRegion {
+    /// The license string reported by GetID for discs of this region.
+    pub fn license(self) -> [u8; 4] {
+        match self {
+            Self::Japan => *b"SCEI",
+            Self::America => *b"SCEA",
+            Self::Europe => *b"SCEE",
+        }
+    }
+
+    /// Detects the region of a BIOS image from the region letter at the end of its version
+    /// string, e.g. `System ROM Version 4.1 12/16/97 A`. Early BIOS versions have no region letter.
+    pub fn from_bios(bios: &[u8]) -> Option<Self> {
+        let start = find(bios, b"System ROM Version")?;
+        let len = bios[start..].iter().position(|&byte| byte == 0)?;
+        let version = bios[start..start + len].trim_ascii_end();
+
+        match version.last()? {
+            b'J' => Some(Self::Japan),
+            b'A' => Some(Self::America),
+            b'E' => Some(Self::Europe),
+            _ => None,
+        }
+    }
+
+    /// Detects the region of a disc from the license text in its license sector, e.g. `Licensed
+    /// by Sony Computer Entertainment America`.
+    pub fn from_disc(rom: &mut (impl Rom + ?Sized)) -> Option<Self> {
+        let mut sector = [0; SECTOR_LEN];
+        rom.read_sector(LICENSE_SECTOR, &mut sector).ok()?;
+
+        if find(&sector, b"Amer").is_some() {
+            Some(Self::America)
+        } else if find(&sector, b"Euro").is_some() {
+            Some(Self::Europe)
+        } else if find(&sector, b"Inc.").is_some() {
+            Some(Self::Japan)
+        } else {
+            None
+        }
+    }
+}
+
+/// How the drive chooses the region it reports in the GetID response.
+///
+/// This only affects the region lock: video timing is always chosen by the program through the
+/// display mode command, so e.g. a PAL game booted on an NTSC BIOS still gets PAL timing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegionPolicy {
+    /// Report the region of the BIOS, so that discs of any region boot. Falls back to the region
+    /// of the disc if the region of the BIOS is unknown.
+    #[default]
+    AutoMatchBios,
+    /// Report the region of the disc, so that the region lock applies as on hardware.
+    ForceDisc,
+    /// Always report the given region.
+    Force(LicenseRegion),
+}
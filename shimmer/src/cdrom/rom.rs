@@ -0,0 +1,361 @@
+//! CD-ROM images and the interface used to access them.
+
+use easyerr::{Error, ResultExt};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Length of a raw CD sector, in bytes.
+pub const SECTOR_LEN: usize = 2352;
+/// Length of the user data of a Mode 2 Form 1 sector, in bytes.
+pub const DATA_LEN: usize = 2048;
+/// Length of the subchannel data of a sector, in bytes.
+pub const SUBCHANNEL_LEN: usize = 96;
+/// Number of sectors in the pregap before LBA 0.
+pub const PREGAP_LEN: u32 = 150;
+
+/// The sync pattern at the start of every data sector.
+const SYNC: [u8; 12] = [
+    0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00,
+];
+
+#[derive(Debug, Error)]
+pub enum RomError {
+    #[error("sector {lba} is out of bounds (image has {count} sectors)")]
+    OutOfBounds { lba: u32, count: u32 },
+    #[error("image length {len} is not a multiple of any known sector size")]
+    UnknownFormat { len: u64 },
+    #[error("couldn't access the image")]
+    Io { source: std::io::Error },
+}
+
+/// The kind of a sector, as identified by its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectorKind {
+    Audio,
+    Mode1,
+    Mode2Form1,
+    Mode2Form2,
+}
+
+impl SectorKind {
+    /// Identifies the kind of a raw sector.
+    pub fn detect(sector: &[u8; SECTOR_LEN]) -> Self {
+        if sector[..12] != SYNC {
+            return Self::Audio;
+        }
+
+        match sector[15] {
+            2 if sector[18] & 0x20 != 0 => Self::Mode2Form2,
+            2 => Self::Mode2Form1,
+            _ => Self::Mode1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackKind {
+    Data,
+    Audio,
+}
+
+/// A track of a disc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Track {
+    /// The number of this track, starting at 1.
+    pub number: u8,
+    pub kind: TrackKind,
+    /// The LBA of the first sector of this track.
+    pub start: u32,
+    /// The length of this track, in sectors.
+    pub len: u32,
+}
+
+/// A CD-ROM image, addressed by sector.
+pub trait Rom: std::fmt::Debug + Send {
+    /// Reads the raw sector at `lba` into `buf`, returning its kind.
+    fn read_sector(&mut self, lba: u32, buf: &mut [u8; SECTOR_LEN])
+    -> Result<SectorKind, RomError>;
+
+    /// Returns the total number of sectors in this image.
+    fn sector_count(&self) -> u32;
+
+    /// Returns the tracks of this image.
+    fn track_info(&self) -> &[Track];
+
+    /// Reads the subchannel data of the sector at `lba` into `buf`. Returns `false` if this image
+    /// has no subchannel data.
+    fn read_subchannel(
+        &mut self,
+        lba: u32,
+        buf: &mut [u8; SUBCHANNEL_LEN],
+    ) -> Result<bool, RomError> {
+        _ = (lba, buf);
+        Ok(false)
+    }
+}
+
+/// The sector format of a [`BinRom`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinFormat {
+    /// Raw 2352 byte sectors.
+    Raw,
+    /// 2048 byte sectors containing only user data.
+    Cooked,
+}
+
+impl BinFormat {
+    fn sector_len(self) -> u64 {
+        match self {
+            BinFormat::Raw => SECTOR_LEN as u64,
+            BinFormat::Cooked => DATA_LEN as u64,
+        }
+    }
+}
+
+/// A single track image, either raw or cooked. Cooked sectors are framed as Mode 2 Form 1 sectors
+/// with a synthesized header and no EDC/ECC.
+#[derive(Debug)]
+pub struct BinRom<R> {
+    reader: R,
+    format: BinFormat,
+    tracks: [Track; 1],
+}
+
+impl<R> BinRom<R>
+where
+    R: Read + Seek,
+{
+    /// Creates a new [`BinRom`], detecting its format from its length and contents.
+    pub fn new(mut reader: R) -> Result<Self, RomError> {
+        let len = reader.seek(SeekFrom::End(0)).context(RomCtx::Io)?;
+        let raw = len % SECTOR_LEN as u64 == 0;
+        let cooked = len % DATA_LEN as u64 == 0;
+
+        let format = match (raw, cooked) {
+            (true, true) if len == 0 => BinFormat::Raw,
+            (true, true) => {
+                let mut sync = [0; 12];
+                reader.seek(SeekFrom::Start(0)).context(RomCtx::Io)?;
+                reader.read_exact(&mut sync).context(RomCtx::Io)?;
+
+                if sync == SYNC {
+                    BinFormat::Raw
+                } else {
+                    BinFormat::Cooked
+                }
+            }
+            (true, false) => BinFormat::Raw,
+            (false, true) => BinFormat::Cooked,
+            (false, false) => return Err(RomError::UnknownFormat { len }),
+        };
+
+        Ok(Self::with_format(reader, format, len))
+    }
+
+    fn with_format(reader: R, format: BinFormat, len: u64) -> Self {
+        let count = u32::try_from(len / format.sector_len()).unwrap_or(u32::MAX);
+        Self {
+            reader,
+            format,
+            tracks: [Track {
+                number: 1,
+                kind: TrackKind::Data,
+                start: 0,
+                len: count,
+            }],
+        }
+    }
+
+    pub fn format(&self) -> BinFormat {
+        self.format
+    }
+}
+
+fn to_bcd(value: u32) -> u8 {
+    let value = value as u8;
+    ((value / 10) << 4) | (value % 10)
+}
+
+impl<R> Rom for BinRom<R>
+where
+    R: std::fmt::Debug + Read + Seek + Send,
+{
+    fn read_sector(
+        &mut self,
+        lba: u32,
+        buf: &mut [u8; SECTOR_LEN],
+    ) -> Result<SectorKind, RomError> {
+        let count = self.sector_count();
+        if lba >= count {
+            return Err(RomError::OutOfBounds { lba, count });
+        }
+
+        let start = u64::from(lba) * self.format.sector_len();
+        self.reader
+            .seek(SeekFrom::Start(start))
+            .context(RomCtx::Io)?;
+
+        match self.format {
+            BinFormat::Raw => {
+                self.reader.read_exact(buf).context(RomCtx::Io)?;
+                Ok(SectorKind::detect(buf))
+            }
+            BinFormat::Cooked => {
+                let address = lba + PREGAP_LEN;
+                buf.fill(0);
+                buf[..12].copy_from_slice(&SYNC);
+                buf[12] = to_bcd(address / (60 * 75));
+                buf[13] = to_bcd((address / 75) % 60);
+                buf[14] = to_bcd(address % 75);
+                buf[15] = 2;
+
+                // subheader (repeated twice): file, channel, submode (data), coding info
+                buf[16..24].copy_from_slice(&[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x08, 0x00]);
+
+                self.reader
+                    .read_exact(&mut buf[24..24 + DATA_LEN])
+                    .context(RomCtx::Io)?;
+
+                Ok(SectorKind::Mode2Form1)
+            }
+        }
+    }
+
+    fn sector_count(&self) -> u32 {
+        self.tracks[0].len
+    }
+
+    fn track_info(&self) -> &[Track] {
+        &self.tracks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Returns a raw sector with the given mode and submode, filled with `fill` after the header.
+    fn raw_sector(mode: u8, submode: u8, fill: u8) -> [u8; SECTOR_LEN] {
+        let mut sector = [fill; SECTOR_LEN];
+        sector[..12].copy_from_slice(&SYNC);
+        sector[12..15].fill(0);
+        sector[15] = mode;
+        sector[16..24].copy_from_slice(&[0, 0, submode, 0, 0, 0, submode, 0]);
+        sector
+    }
+
+    fn rom(image: Vec<u8>) -> BinRom<Cursor<Vec<u8>>> {
+        BinRom::new(Cursor::new(image)).unwrap()
+    }
+
+    #[test]
+    fn detects_format_from_length() {
+        assert_eq!(
+            rom(raw_sector(2, 0x08, 0).to_vec()).format(),
+            BinFormat::Raw
+        );
+        assert_eq!(rom(vec![0xAA; DATA_LEN]).format(), BinFormat::Cooked);
+        assert_eq!(rom(Vec::new()).format(), BinFormat::Raw);
+
+        assert!(matches!(
+            BinRom::new(Cursor::new(vec![0; 1000])),
+            Err(RomError::UnknownFormat { len: 1000 })
+        ));
+    }
+
+    #[test]
+    fn detects_format_from_sync_if_length_is_ambiguous() {
+        // a multiple of both sector lengths: 128 raw or 147 cooked sectors
+        let len = 128 * SECTOR_LEN;
+        assert_eq!(len % DATA_LEN, 0);
+
+        let mut image = vec![0xAA; len];
+        let cooked = rom(image.clone());
+        assert_eq!(cooked.format(), BinFormat::Cooked);
+        assert_eq!(cooked.sector_count(), 147);
+
+        image[..12].copy_from_slice(&SYNC);
+        let raw = rom(image);
+        assert_eq!(raw.format(), BinFormat::Raw);
+        assert_eq!(raw.sector_count(), 128);
+    }
+
+    #[test]
+    fn reads_raw_sectors_as_is() {
+        let sectors = [
+            raw_sector(2, 0x08, 1),
+            raw_sector(2, 0x20, 2),
+            raw_sector(1, 0x00, 3),
+            [4; SECTOR_LEN],
+        ];
+
+        let mut rom = rom(sectors.concat());
+        assert_eq!(rom.sector_count(), 4);
+        assert_eq!(
+            rom.track_info(),
+            [Track {
+                number: 1,
+                kind: TrackKind::Data,
+                start: 0,
+                len: 4,
+            }]
+        );
+
+        let kinds = [
+            SectorKind::Mode2Form1,
+            SectorKind::Mode2Form2,
+            SectorKind::Mode1,
+            SectorKind::Audio,
+        ];
+
+        let mut buf = [0; SECTOR_LEN];
+        for (lba, (sector, kind)) in sectors.iter().zip(kinds).enumerate() {
+            assert_eq!(rom.read_sector(lba as u32, &mut buf).unwrap(), kind);
+            assert_eq!(&buf, sector);
+        }
+    }
+
+    #[test]
+    fn frames_cooked_sectors() {
+        // enough sectors for the synthesized address to cross a second
+        let image: Vec<u8> = (0..80u8).flat_map(|i| [i; DATA_LEN]).collect();
+        let mut rom = rom(image);
+        assert_eq!(rom.sector_count(), 80);
+
+        let mut buf = [0xFF; SECTOR_LEN];
+        for (lba, msf) in [(0, [0x00, 0x02, 0x00]), (79, [0x00, 0x03, 0x04])] {
+            assert_eq!(
+                rom.read_sector(lba, &mut buf).unwrap(),
+                SectorKind::Mode2Form1
+            );
+            assert_eq!(buf[..12], SYNC);
+            assert_eq!(buf[12..15], msf);
+            assert_eq!(buf[15], 2);
+            assert_eq!(buf[16..24], [0, 0, 0x08, 0, 0, 0, 0x08, 0]);
+            assert!(buf[24..24 + DATA_LEN].iter().all(|&b| b == lba as u8));
+            assert!(buf[24 + DATA_LEN..].iter().all(|&b| b == 0));
+            assert_eq!(SectorKind::detect(&buf), SectorKind::Mode2Form1);
+        }
+    }
+
+    #[test]
+    fn rejects_sectors_out_of_bounds() {
+        let mut buf = [0; SECTOR_LEN];
+        for mut rom in [rom(raw_sector(2, 0x08, 0).to_vec()), rom(vec![0; DATA_LEN])] {
+            assert!(rom.read_sector(0, &mut buf).is_ok());
+            assert!(matches!(
+                rom.read_sector(1, &mut buf),
+                Err(RomError::OutOfBounds { lba: 1, count: 1 })
+            ));
+        }
+    }
+
+    #[test]
+    fn bin_images_have_no_subchannel() {
+        let mut buf = [0xFF; SUBCHANNEL_LEN];
+        for mut rom in [rom(raw_sector(2, 0x08, 0).to_vec()), rom(vec![0; DATA_LEN])] {
+            assert!(!rom.read_subchannel(0, &mut buf).unwrap());
+            assert!(buf.iter().all(|&b| b == 0xFF));
+        }
+    }
+}
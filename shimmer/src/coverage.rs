@@ -0,0 +1,292 @@
+//! Implementation coverage reports, listing which CPU instructions, GTE commands and GPU commands
+//! are supported by the emulator.
+//!
+//! The CPU reports are built from the dispatch tables of the [`cpu`](crate::cpu) module, and the
+//! GTE and GPU reports from their decoders, so that new opcodes show up in them. How well each GPU
+//! command is supported mirrors the dispatch of the [`gpu`](crate::gpu) module.
+
+use crate::cpu;
+use shimmer_core::{
+    gpu::cmd::{
+        DisplayCommand, DisplayOpcode, EnvironmentOpcode, MiscOpcode, RenderingCommand,
+        RenderingOpcode,
+    },
+    gte::instr::Instruction as GteInstruction,
+};
+
+/// How well an instruction or command is supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Support {
+    /// Fully implemented.
+    Implemented,
+    /// Accepted, but has no effect or only part of it.
+    Stubbed,
+    /// Not implemented at all.
+    Unimplemented,
+}
+
+/// An entry of a coverage report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    /// Name of the instruction or command.
+    pub name: &'static str,
+    /// Opcode of the instruction or command.
+    pub code: u8,
+    pub support: Support,
+}
+
+impl Entry {
+    const fn new(name: &'static str, code: u8, support: Support) -> Self {
+        Self {
+            name,
+            code,
+            support,
+        }
+    }
+}
+
+/// Builds the entries of a dispatch table of the interpreter, sorted by opcode.
+fn cpu_entries<Op>(table: &[(Op, Support)], code: impl Fn(Op) -> u8) -> Vec<Entry>
+where
+    Op: Copy + Into<&'static str>,
+{
+    let mut entries: Vec<_> = table
+        .iter()
+        .map(|&(op, support)| Entry::new(op.into(), code(op), support))
+        .collect();
+
+    entries.sort_by_key(|entry| entry.code);
+    entries
+}
+
+/// Returns the coverage of the primary CPU opcodes.
+pub fn cpu_opcodes() -> Vec<Entry> {
+    cpu_entries(cpu::OPCODES, |op| op as u8)
+}
+
+/// Returns the coverage of the CPU opcodes under
+/// [`Opcode::SPECIAL`](shimmer_core::cpu::instr::Opcode::SPECIAL).
+pub fn cpu_special_opcodes() -> Vec<Entry> {
+    cpu_entries(cpu::SPECIAL_OPCODES, |op| op as u8)
+}
+
+/// Returns the coverage of the coprocessor opcodes.
+pub fn cpu_cop_opcodes() -> Vec<Entry> {
+    cpu_entries(cpu::COP_OPCODES, |op| op as u8)
+}
+
+/// All of the commands of the GTE.
+const GTE_COMMANDS: [(&str, u8); 22] = [
+    ("RTPS", 0x01),
+    ("NCLIP", 0x06),
+    ("OP", 0x0C),
+    ("DPCS", 0x10),
+    ("INTPL", 0x11),
+    ("MVMVA", 0x12),
+    ("NCDS", 0x13),
+    ("CDP", 0x14),
+    ("NCDT", 0x16),
+    ("NCCS", 0x1B),
+    ("CC", 0x1C),
+    ("NCS", 0x1E),
+    ("NCT", 0x20),
+    ("SQR", 0x28),
+    ("DCPL", 0x29),
+    ("DPCT", 0x2A),
+    ("AVSZ3", 0x2D),
+    ("AVSZ4", 0x2E),
+    ("RTPT", 0x30),
+    ("GPF", 0x3D),
+    ("GPL", 0x3E),
+    ("NCCT", 0x3F),
+];
+
+/// Returns the coverage of the GTE commands.
+pub fn gte_commands() -> Vec<Entry> {
+    GTE_COMMANDS
+        .iter()
+        .map(|&(name, code)| {
+            let support = if GteInstruction::from_bits(u32::from(code)).op().is_some() {
+                Support::Implemented
+            } else {
+                Support::Unimplemented
+            };
+
+            Entry::new(name, code, support)
+        })
+        .collect()
+}
+
+/// Returns the name and support of a GP0 command, if its opcode is valid. Commands which span a
+/// range of opcodes (such as polygons) are named after the range.
+fn gp0_support(cmd: RenderingCommand) -> Option<(&'static str, Support)> {
+    let opcode = cmd.opcode();
+    let entry = match opcode {
+        RenderingOpcode::Misc => {
+            let op = cmd.misc_opcode()?;
+            let support = match op {
+                MiscOpcode::NOP | MiscOpcode::QuickRectangleFill => Support::Implemented,
+                // there's no texture cache to clear
+                MiscOpcode::ClearCache => Support::Stubbed,
+                MiscOpcode::InterruptRequest => Support::Unimplemented,
+            };
+
+            (op.into(), support)
+        }
+        RenderingOpcode::Environment => {
+            let op = cmd.environment_opcode()?;
+            let support = match op {
+                EnvironmentOpcode::DrawingSettings
+                | EnvironmentOpcode::TexWindowSettings
+                | EnvironmentOpcode::DrawingAreaTopLeft
+                | EnvironmentOpcode::DrawingAreaBottomRight
+                | EnvironmentOpcode::DrawingOffset
+                | EnvironmentOpcode::MaskSettings => Support::Implemented,
+            };
+
+            (op.into(), support)
+        }
+        // lines are decoded but not drawn
+        RenderingOpcode::Line => (opcode.into(), Support::Stubbed),
+        RenderingOpcode::Polygon
+        | RenderingOpcode::Rectangle
+        | RenderingOpcode::VramToVramBlit
+        | RenderingOpcode::CpuToVramBlit
+        | RenderingOpcode::VramToCpuBlit => (opcode.into(), Support::Implemented),
+    };
+
+    Some(entry)
+}
+
+/// Returns the coverage of the GP0 commands. Commands which span a range of opcodes (such as
+/// polygons) are listed once, with the first opcode of the range.
+pub fn gp0_commands() -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    for code in 0..=255u8 {
+        let cmd = RenderingCommand::from_bits(u32::from(code) << 24);
+        let Some((name, support)) = gp0_support(cmd) else {
+            continue;
+        };
+
+        // ranges of opcodes, and environment opcodes mirrored in E8h..=EFh, are only listed once
+        if entries.iter().any(|entry| entry.name == name) {
+            continue;
+        }
+
+        entries.push(Entry::new(name, code, support));
+    }
+
+    entries
+}
+
+/// Returns the coverage of the GP1 commands.
+pub fn gp1_commands() -> Vec<Entry> {
+    (0..64u8)
+        .filter_map(|code| {
+            let op = DisplayCommand::from_bits(u32::from(code) << 24).opcode()?;
+            let support = match op {
+                DisplayOpcode::ResetGpu
+                | DisplayOpcode::ResetCommandBuffer
+                | DisplayOpcode::AcknowledgeGpuInterrupt
+                | DisplayOpcode::DisplayEnabled
+                | DisplayOpcode::DmaDirection
+                | DisplayOpcode::DisplayArea
+                | DisplayOpcode::HorizontalDisplayRange
+                | DisplayOpcode::VerticalDisplayRange
+                | DisplayOpcode::DisplayMode
+                | DisplayOpcode::AllowTextureDisable
+                | DisplayOpcode::ReadGpuRegister => Support::Implemented,
+                DisplayOpcode::VramSizeV1 => Support::Unimplemented,
+            };
+
+            Some(Entry::new(op.into(), code, support))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shimmer_core::cpu::instr::{CopOpcode, Instruction, SpecialOpcode};
+
+    /// Checks that every opcode in `0..count` which decodes is dispatched exactly once.
+    fn assert_dispatches_every_opcode<Op>(
+        entries: &[Entry],
+        count: u8,
+        decode: impl Fn(u8) -> Option<Op>,
+    ) {
+        let decodable: Vec<u8> = (0..count).filter(|&code| decode(code).is_some()).collect();
+        let dispatched: Vec<u8> = entries.iter().map(|entry| entry.code).collect();
+        assert_eq!(dispatched, decodable);
+    }
+
+    fn implemented(entries: &[Entry]) -> usize {
+        entries
+            .iter()
+            .filter(|entry| entry.support == Support::Implemented)
+            .count()
+    }
+
+    #[test]
+    fn cpu_coverage_matches_dispatch() {
+        let opcodes = cpu_opcodes();
+        assert_dispatches_every_opcode(&opcodes, 64, |code| {
+            Instruction::from_bits(u32::from(code) << 26).op()
+        });
+        assert_eq!(opcodes.len(), 38);
+        assert_eq!(implemented(&opcodes), 32);
+
+        let special = cpu_special_opcodes();
+        assert_dispatches_every_opcode(&special, 64, |code| {
+            Instruction::from_bits(u32::from(code)).special_op()
+        });
+        assert_eq!(special.len(), 28);
+        assert_eq!(implemented(&special), 28);
+
+        let cop = cpu_cop_opcodes();
+        assert_dispatches_every_opcode(&cop, 16, |code| {
+            Instruction::from_bits(u32::from(code) << 21).cop_op()
+        });
+        assert_eq!(cop.len(), 5);
+        assert_eq!(implemented(&cop), 4);
+
+        let names: Vec<&str> = opcodes.iter().map(|entry| entry.name).collect();
+        assert_eq!(names[..4], ["SPECIAL", "BZ", "JMP", "JAL"]);
+        assert_eq!(
+            special
+                .iter()
+                .find(|entry| entry.code == 0x0D)
+                .map(|entry| entry.name),
+            Some(<&str>::from(SpecialOpcode::BREAK))
+        );
+        assert_eq!(
+            cop.iter().find(|entry| entry.code == 0x08),
+            Some(&Entry::new(
+                CopOpcode::BRANCH.into(),
+                0x08,
+                Support::Unimplemented
+            ))
+        );
+    }
+
+    #[test]
+    fn gpu_coverage_lists_each_command_once() {
+        let gp0 = gp0_commands();
+        let codes: Vec<u8> = gp0.iter().map(|entry| entry.code).collect();
+        assert_eq!(
+            codes,
+            [
+                0x00, 0x01, 0x02, 0x1F, 0x20, 0x40, 0x60, 0x80, 0xA0, 0xC0, 0xE1, 0xE2, 0xE3, 0xE4,
+                0xE5, 0xE6
+            ]
+        );
+        assert_eq!(gp0[5], Entry::new("Line", 0x40, Support::Stubbed));
+
+        let gp1 = gp1_commands();
+        assert_eq!(gp1.len(), 12);
+        assert_eq!(
+            gp1.last(),
+            Some(&Entry::new("VramSizeV1", 0x20, Support::Unimplemented))
+        );
+    }
+}
@@ -2,12 +2,22 @@
 
 mod arith_logic;
 mod coproc;
+mod delay;
 mod exception;
 mod gte;
+mod hooks;
+mod idle;
 mod jump_branch;
+mod kernel_call;
 mod load_store;
-
-use crate::PSX;
+mod profile;
+mod redirect;
+mod stdout;
+
+use crate::{PSX, coverage::Support, flight_recorder::AccessKind};
+use delay::DelayLoops;
+use idle::IdleLoops;
+use redirect::FileRedirect;
 use shimmer_core::{
     Cycles,
     cpu::{
@@ -15,17 +25,158 @@ use shimmer_core::{
         cop0::Exception,
         instr::{CopOpcode, Instruction, Opcode, SpecialOpcode},
     },
+    exe,
     interrupts::Interrupt,
     kernel,
     mem::{Address, Region, io},
 };
-use std::hint::cold_path;
+use std::{collections::VecDeque, hint::cold_path, path::PathBuf};
 use tinylog::{debug, error, info, trace, warn};
 
+pub use hooks::KernelHook;
+pub use kernel_call::{KernelArg, KernelCall};
+pub use profile::{HEATMAP_BUCKET_LEN, Heatmap, Profile};
+pub use stdout::StdoutMirror;
+
 // these are only the general exception vectors...
 const EXCEPTION_VECTOR_KSEG0: Address = Address(0x8000_0080);
 const EXCEPTION_VECTOR_KSEG1: Address = Address(0xBFC0_0180);
 
+/// An exception which broke execution. See [`Interpreter::break_on_exception`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionBreak {
+    pub exception: Exception,
+    /// Address of the instruction which triggered the exception.
+    pub address: Address,
+}
+
+/// How jumps to a register (JR and JALR) handle targets which aren't word aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JumpAlignment {
+    /// Jump to the target as is, raising an address error once it's fetched, as the hardware
+    /// does.
+    #[default]
+    Exception,
+    /// Clear the low bits of the target, as some emulators do.
+    Mask,
+}
+
+/// Defines a method dispatching instructions to the handler of their opcode, along with a table of
+/// how well each opcode is supported. The coverage reports of [`crate::coverage`] are built from
+/// these tables, so they can't drift from what is actually executed.
+macro_rules! dispatch {
+    (
+        fn $name:ident($opcode:ident);
+        $(#[$meta:meta])*
+        const $table:ident;
+        $($op:ident => $handler:ident, $support:ident;)*
+    ) => {
+        impl Interpreter {
+            #[inline(always)]
+            fn $name(&mut self, psx: &mut PSX, instr: Instruction, op: $opcode) -> u64 {
+                match op {
+                    $($opcode::$op => self.$handler(psx, instr),)*
+                }
+            }
+        }
+
+        $(#[$meta])*
+        pub(crate) const $table: &[($opcode, Support)] = &[$(($opcode::$op, Support::$support),)*];
+    };
+}
+
+dispatch! {
+    fn exec_op(Opcode);
+    /// The primary opcodes and how well they're supported.
+    const OPCODES;
+    SPECIAL => exec_special, Implemented;
+    BZ => bz, Implemented;
+    JMP => jmp, Implemented;
+    JAL => jal, Implemented;
+    BEQ => beq, Implemented;
+    BNE => bne, Implemented;
+    BLEZ => blez, Implemented;
+    BGTZ => bgtz, Implemented;
+    ADDI => addi, Implemented;
+    ADDIU => addiu, Implemented;
+    SLTI => slti, Implemented;
+    SLTIU => sltiu, Implemented;
+    ANDI => andi, Implemented;
+    ORI => ori, Implemented;
+    XORI => xori, Implemented;
+    LUI => lui, Implemented;
+    COP0 => exec_cop0, Implemented;
+    // there's no COP1 and COP3 in the PSX
+    COP1 => cop_instr, Stubbed;
+    COP2 => exec_cop2, Implemented;
+    COP3 => cop_instr, Stubbed;
+    LB => lb, Implemented;
+    LH => lh, Implemented;
+    LWL => lwl, Implemented;
+    LW => lw, Implemented;
+    LBU => lbu, Implemented;
+    LHU => lhu, Implemented;
+    LWR => lwr, Implemented;
+    SB => sb, Implemented;
+    SH => sh, Implemented;
+    SWL => swl, Implemented;
+    SW => sw, Implemented;
+    SWR => swr, Implemented;
+    LWC0 => lwc, Implemented;
+    LWC1 => lwc, Stubbed;
+    LWC2 => lwc, Implemented;
+    LWC3 => lwc, Stubbed;
+    SWC0 => swc, Implemented;
+    SWC1 => swc, Stubbed;
+    SWC2 => swc, Implemented;
+    SWC3 => swc, Stubbed;
+}
+
+dispatch! {
+    fn exec_special_op(SpecialOpcode);
+    /// The opcodes under [`Opcode::SPECIAL`] and how well they're supported.
+    const SPECIAL_OPCODES;
+    SLL => sll, Implemented;
+    SRL => srl, Implemented;
+    SRA => sra, Implemented;
+    SLLV => sllv, Implemented;
+    SRLV => srlv, Implemented;
+    SRAV => srav, Implemented;
+    JR => jr, Implemented;
+    JALR => jalr, Implemented;
+    SYSCALL => syscall, Implemented;
+    BREAK => breakpoint, Implemented;
+    MFHI => mfhi, Implemented;
+    MTHI => mthi, Implemented;
+    MFLO => mflo, Implemented;
+    MTLO => mtlo, Implemented;
+    MULT => mult, Implemented;
+    MULTU => multu, Implemented;
+    DIV => div, Implemented;
+    DIVU => divu, Implemented;
+    ADD => add, Implemented;
+    ADDU => addu, Implemented;
+    SUB => sub, Implemented;
+    SUBU => subu, Implemented;
+    AND => and, Implemented;
+    OR => or, Implemented;
+    XOR => xor, Implemented;
+    NOR => nor, Implemented;
+    SLT => slt, Implemented;
+    SLTU => sltu, Implemented;
+}
+
+dispatch! {
+    fn exec_cop_op(CopOpcode);
+    /// The coprocessor opcodes and how well they're supported.
+    const COP_OPCODES;
+    MFC => mfc, Implemented;
+    CFC => cfc, Implemented;
+    MTC => mtc, Implemented;
+    CTC => ctc, Implemented;
+    BRANCH => cop_branch, Unimplemented;
+}
+
 /// An interpreter of the R3000 CPU.
 #[derive(Default)]
 pub struct Interpreter {
@@ -35,17 +186,58 @@ pub struct Interpreter {
     pending_load: Option<RegLoad>,
     load_delay_slot: Option<RegLoad>,
     instr_delay_slot: (Instruction, Address),
+    /// Exception raised while fetching the instruction in the delay slot, if any. It's only taken
+    /// once that instruction is executed.
+    fetch_exception: Option<Exception>,
+    /// Redirection of kernel CD-ROM file functions, if enabled.
+    file_redirect: Option<FileRedirect>,
+    /// Arguments of the sideloaded executable, packed with [`exe::pack_args`].
+    sideload_args: Vec<u8>,
+    /// Idle loop detection state, if enabled.
+    idle_loops: Option<IdleLoops>,
+    /// Delay loop detection state, if the delay loops of the BIOS are fast-forwarded while it
+    /// boots.
+    delay_loops: Option<DelayLoops>,
+    /// Exceptions which break execution when triggered.
+    break_on: Vec<Exception>,
+    /// The last exception which broke execution, until it's taken.
+    exception_break: Option<ExceptionBreak>,
+    /// The most recent kernel calls, oldest first.
+    recent_kernel_calls: VecDeque<KernelCall>,
+    /// Hooks on kernel functions, in registration order.
+    kernel_hooks: Vec<(kernel::Function, KernelHook)>,
+    /// Where the characters printed by the kernel are mirrored to.
+    stdout_mirror: StdoutMirror,
+    /// How misaligned jump targets are handled.
+    jump_alignment: JumpAlignment,
+    /// Execution counts of the instruction profiler, if enabled.
+    profile: Option<Box<Profile>>,
+    /// Execution counts of the heatmap, if enabled.
+    heatmap: Option<Box<Heatmap>>,
 }
 
+/// How many of the most recent kernel calls are kept around.
+const RECENT_KERNEL_CALLS: usize = 16;
+
 const DEFAULT_DELAY: Cycles = 2;
 const MEMORY_OP_DELAY: Cycles = 7;
 
 impl Interpreter {
+    /// Creates a new [`Interpreter`] which passes the given packed arguments to sideloaded
+    /// executables.
+    pub fn new(sideload_args: Vec<u8>) -> Self {
+        Self {
+            sideload_args,
+            ..Default::default()
+        }
+    }
+
     #[cold]
     #[inline(never)]
     fn sideload(&mut self, psx: &mut PSX) {
         if let Some(exe) = &psx.memory.sideload {
             self.instr_delay_slot = (Instruction::NOP, exe.header.initial_pc);
+            self.fetch_exception = None;
             psx.cpu.regs.write_pc(exe.header.initial_pc.value());
             psx.cpu.regs.write(Reg::GP, exe.header.initial_gp);
 
@@ -63,6 +255,15 @@ impl Interpreter {
                 psx.cpu.regs.write(Reg::SP, initial_sp);
             }
 
+            if let Some(argc) = self.sideload_args.first_chunk::<4>() {
+                let argc = u32::from_le_bytes(*argc);
+                psx.memory.scratchpad[..self.sideload_args.len()]
+                    .copy_from_slice(&self.sideload_args);
+
+                psx.cpu.regs.write(Reg::A0, argc);
+                psx.cpu.regs.write(Reg::A1, exe::ARGS_ADDR.value() + 4);
+            }
+
             info!(psx.loggers.cpu, "sideloaded!");
         }
     }
@@ -75,14 +276,18 @@ impl Interpreter {
         exception: Exception,
     ) {
         let in_branch_delay = address.value().wrapping_add(4) != delay_slot.value();
-        psx.cop0.regs.write(
-            shimmer_core::cpu::cop0::Reg::COP0_EPC,
-            if in_branch_delay {
-                address.value().wrapping_sub(4)
-            } else {
-                address.value()
-            },
-        );
+        psx.cop0.regs.set_epc(if in_branch_delay {
+            address.value().wrapping_sub(4)
+        } else {
+            address.value()
+        });
+
+        if self.break_on.contains(&exception) {
+            self.exception_break = Some(ExceptionBreak { exception, address });
+        }
+
+        psx.flight_recorder
+            .exception_triggered(exception, psx.scheduler.elapsed());
 
         if exception != Exception::Interrupt {
             info!(
@@ -106,6 +311,7 @@ impl Interpreter {
 
         // flush pipeline
         self.instr_delay_slot = (Instruction::NOP, self.current_addr);
+        self.fetch_exception = None;
 
         // update sr
         psx.cop0.regs.system_status_mut().start_exception();
@@ -140,6 +346,41 @@ impl Interpreter {
         self.trigger_exception_at(psx, self.current_addr, self.instr_delay_slot.1, exception);
     }
 
+    /// Makes execution break whenever `exception` is triggered, or stops doing so if `enabled` is
+    /// `false`. The exception is still taken as usual: the break only records it, so that the
+    /// emulator can stop once the CPU is at the exception vector.
+    pub fn break_on_exception(&mut self, exception: Exception, enabled: bool) {
+        self.break_on.retain(|&e| e != exception);
+        if enabled {
+            self.break_on.push(exception);
+        }
+    }
+
+    /// Whether an exception broke execution and the break hasn't been taken yet.
+    #[inline(always)]
+    pub fn has_exception_break(&self) -> bool {
+        self.exception_break.is_some()
+    }
+
+    /// Takes the last exception which broke execution, if any.
+    pub fn take_exception_break(&mut self) -> Option<ExceptionBreak> {
+        self.exception_break.take()
+    }
+
+    /// Flushes the pipeline and continues execution at `addr`, as if the CPU had just jumped
+    /// there. Pending loads are discarded.
+    pub fn jump_to(&mut self, psx: &mut PSX, addr: Address) {
+        self.pending_load = None;
+        self.load_delay_slot = None;
+
+        let (instr, exception) = fetch(psx, addr);
+        self.instr_delay_slot = (instr, addr);
+        self.fetch_exception = exception;
+        psx.cpu.regs.write_pc(addr.value().wrapping_add(4));
+
+        self.forget_idle_loop();
+    }
+
     /// Cancels a pending load to the given register, if it exists.
     fn cancel_load(&mut self, reg: Reg) {
         if self.pending_load.is_some_and(|load| load.reg == reg) {
@@ -156,141 +397,99 @@ impl Interpreter {
             .cause_mut()
             .set_system_interrupt_pending(requested_interrupt.is_some());
 
-        if let Some(requested_interrupt) = requested_interrupt {
-            let system_status = psx.cop0.regs.system_status();
-            if !system_status.system_interrupts_enabled() {
-                return false;
-            }
+        let delivered = psx.cop0.regs.delivered_interrupt_lines();
+        if delivered == 0 {
+            return false;
+        }
 
-            if requested_interrupt != Interrupt::VBlank {
-                info!(
-                    psx.loggers.cpu,
-                    "triggered interrupt {:?} at {}",
-                    requested_interrupt, self.instr_delay_slot.1;
-                );
-            }
+        // line 2 is the system interrupt controller, lines 0 and 1 are software interrupts. a
+        // system interrupt masked by SR.IM2 can be pending while a software one is delivered
+        let system_delivered = delivered & (1 << 2) != 0;
+        match requested_interrupt {
+            Some(Interrupt::VBlank) if system_delivered => (),
+            Some(requested_interrupt) if system_delivered => info!(
+                psx.loggers.cpu,
+                "triggered interrupt {:?} at {}",
+                requested_interrupt, self.instr_delay_slot.1;
+            ),
+            _ => debug!(
+                psx.loggers.cpu,
+                "triggered software interrupt (lines {:02b}) at {}",
+                delivered & 0b11, self.instr_delay_slot.1;
+            ),
+        }
 
-            self.trigger_exception(psx, Exception::Interrupt);
+        self.trigger_exception(psx, Exception::Interrupt);
 
-            true
-        } else {
-            false
-        }
+        true
     }
 
     fn cop_instr(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
         if let Some(cop_op) = instr.cop_op() {
-            match cop_op {
-                CopOpcode::MFC => self.mfc(psx, instr),
-                CopOpcode::CFC => self.cfc(psx, instr),
-                CopOpcode::MTC => self.mtc(psx, instr),
-                CopOpcode::CTC => self.ctc(psx, instr),
-                CopOpcode::BRANCH => todo!(),
-            }
+            self.exec_cop_op(psx, instr, cop_op)
         } else {
             // TODO: warn
             DEFAULT_DELAY
         }
     }
 
-    fn exec(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
-        if let Some(op) = instr.op() {
-            match op {
-                Opcode::LUI => self.lui(psx, instr),
-                Opcode::ORI => self.ori(psx, instr),
-                Opcode::SW => self.sw(psx, instr),
-                Opcode::ADDIU => self.addiu(psx, instr),
-                Opcode::JMP => self.jmp(psx, instr),
-                Opcode::BNE => self.bne(psx, instr),
-                Opcode::ADDI => self.addi(psx, instr),
-                Opcode::LW => self.lw(psx, instr),
-                Opcode::SH => self.sh(psx, instr),
-                Opcode::JAL => self.jal(psx, instr),
-                Opcode::ANDI => self.andi(psx, instr),
-                Opcode::SB => self.sb(psx, instr),
-                Opcode::LB => self.lb(psx, instr),
-                Opcode::BEQ => self.beq(psx, instr),
-                Opcode::BGTZ => self.bgtz(psx, instr),
-                Opcode::BLEZ => self.blez(psx, instr),
-                Opcode::LBU => self.lbu(psx, instr),
-                Opcode::BZ => self.bz(psx, instr),
-                Opcode::SLTI => self.slti(psx, instr),
-                Opcode::SLTIU => self.sltiu(psx, instr),
-                Opcode::LHU => self.lhu(psx, instr),
-                Opcode::LH => self.lh(psx, instr),
-                Opcode::LWL => self.lwl(psx, instr),
-                Opcode::LWR => self.lwr(psx, instr),
-                Opcode::SWL => self.swl(psx, instr),
-                Opcode::SWR => self.swr(psx, instr),
-                Opcode::XORI => self.xori(psx, instr),
-                Opcode::COP0 => {
-                    if instr.cop_cmd() {
-                        // TODO: check if really RFE
-                        return self.rfe(psx, instr);
-                    }
+    /// Coprocessor branches aren't emulated: they're reported as unimplemented and never taken.
+    fn cop_branch(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
+        let cop: &'static str = instr.cop().into();
+        if psx.unimplemented.record(format!("{cop} branch")) {
+            error!(
+                psx.loggers.cpu,
+                "unimplemented {cop} branch at {} - not taking it", self.current_addr
+            );
+        }
 
-                    self.cop_instr(psx, instr)
-                }
-                Opcode::COP2 => {
-                    if instr.cop_cmd() {
-                        self.exec_gte(
-                            psx,
-                            shimmer_core::gte::instr::Instruction::from_bits(instr.imm25().value()),
-                        );
-
-                        return DEFAULT_DELAY;
-                    }
+        DEFAULT_DELAY
+    }
 
-                    self.cop_instr(psx, instr)
-                }
-                Opcode::COP1 | Opcode::COP3 => self.cop_instr(psx, instr),
-                Opcode::LWC0 | Opcode::LWC1 | Opcode::LWC2 | Opcode::LWC3 => self.lwc(psx, instr),
-                Opcode::SWC0 | Opcode::SWC1 | Opcode::SWC2 | Opcode::SWC3 => self.swc(psx, instr),
-                Opcode::SPECIAL => {
-                    if let Some(op) = instr.special_op() {
-                        match op {
-                            SpecialOpcode::SLL => self.sll(psx, instr),
-                            SpecialOpcode::OR => self.or(psx, instr),
-                            SpecialOpcode::SLTU => self.sltu(psx, instr),
-                            SpecialOpcode::ADDU => self.addu(psx, instr),
-                            SpecialOpcode::JR => self.jr(psx, instr),
-                            SpecialOpcode::JALR => self.jalr(psx, instr),
-                            SpecialOpcode::SRL => self.srl(psx, instr),
-                            SpecialOpcode::AND => self.and(psx, instr),
-                            SpecialOpcode::ADD => self.add(psx, instr),
-                            SpecialOpcode::SUBU => self.subu(psx, instr),
-                            SpecialOpcode::SRA => self.sra(psx, instr),
-                            SpecialOpcode::DIV => self.div(psx, instr),
-                            SpecialOpcode::MFLO => self.mflo(psx, instr),
-                            SpecialOpcode::SYSCALL => self.syscall(psx, instr),
-                            SpecialOpcode::MFHI => self.mfhi(psx, instr),
-                            SpecialOpcode::MTLO => self.mtlo(psx, instr),
-                            SpecialOpcode::MTHI => self.mthi(psx, instr),
-                            SpecialOpcode::SLT => self.slt(psx, instr),
-                            SpecialOpcode::DIVU => self.divu(psx, instr),
-                            SpecialOpcode::SLLV => self.sllv(psx, instr),
-                            SpecialOpcode::NOR => self.nor(psx, instr),
-                            SpecialOpcode::SRAV => self.srav(psx, instr),
-                            SpecialOpcode::SRLV => self.srlv(psx, instr),
-                            SpecialOpcode::MULTU => self.multu(psx, instr),
-                            SpecialOpcode::XOR => self.xor(psx, instr),
-                            SpecialOpcode::MULT => self.mult(psx, instr),
-                            SpecialOpcode::SUB => self.sub(psx, instr),
-                            SpecialOpcode::BREAK => self.breakpoint(psx, instr),
-                        }
-                    } else {
-                        error!(psx.loggers.cpu, "illegal special op");
-                        DEFAULT_DELAY
-                    }
-                }
-            }
+    fn exec_cop0(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
+        if instr.cop_cmd() {
+            // TODO: check if really RFE
+            return self.rfe(psx, instr);
+        }
+
+        self.cop_instr(psx, instr)
+    }
+
+    fn exec_cop2(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
+        if instr.cop_cmd() {
+            self.exec_gte(
+                psx,
+                shimmer_core::gte::instr::Instruction::from_bits(instr.imm25().value()),
+            );
+
+            return DEFAULT_DELAY;
+        }
+
+        self.cop_instr(psx, instr)
+    }
+
+    fn exec_special(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
+        if let Some(op) = instr.special_op() {
+            self.exec_special_op(psx, instr, op)
+        } else {
+            error!(psx.loggers.cpu, "illegal special op");
+            DEFAULT_DELAY
+        }
+    }
+
+    /// Executes `instr`, whose primary opcode has already been decoded into `op`.
+    fn exec(&mut self, psx: &mut PSX, instr: Instruction, op: Option<Opcode>) -> u64 {
+        if let Some(op) = op {
+            self.exec_op(psx, instr, op)
         } else {
             error!(psx.loggers.cpu, "illegal op");
             DEFAULT_DELAY
         }
     }
 
-    fn log_kernel_calls(&mut self, psx: &mut PSX) {
+    /// Logs the kernel function being called at the current address, if any, and runs the hooks
+    /// on it. Returns the value to return from the call with if a hook overrode it.
+    fn log_kernel_calls(&mut self, psx: &mut PSX) -> Option<u32> {
         let func = match self.current_addr.value() {
             0xA0 => {
                 cold_path();
@@ -307,22 +506,35 @@ impl Interpreter {
                 let code = psx.cpu.regs.read(Reg::R9) as u8;
                 kernel::Function::c0(code)
             }
-            _ => return,
+            _ => return None,
         };
 
         if let Some(func) = func {
+            if let Some(value) = self.run_kernel_hooks(psx, func) {
+                debug!(
+                    psx.loggers.kernel,
+                    "kernel function {func:?} overridden by a hook, returning 0x{value:08X}"
+                );
+                return Some(value);
+            }
+
             if func == kernel::Function::PutChar {
+                // A(3Ch) forwards to B(3Dh), so only capture the latter
+                if self.current_addr.value() != 0xB0 {
+                    return None;
+                }
+
                 let char = psx.cpu.regs.read(Reg::A0);
                 if let Ok(char) = char::try_from(char) {
-                    print!("{char}");
+                    self.mirror_stdout(char);
                     if char == '\r' {
-                        psx.memory.kernel_stdout.push('\n');
+                        psx.memory.push_kernel_stdout('\n');
                     } else {
-                        psx.memory.kernel_stdout.push(char);
+                        psx.memory.push_kernel_stdout(char);
                     }
                 }
 
-                return;
+                return None;
             }
 
             let ignore = [
@@ -332,36 +544,16 @@ impl Interpreter {
             ];
 
             if ignore.contains(&func) {
-                return;
+                return None;
             }
 
-            let args = match func.args() {
-                0 => vec![],
-                1 => vec![psx.cpu.regs.read(Reg::A0)],
-                2 => vec![psx.cpu.regs.read(Reg::A0), psx.cpu.regs.read(Reg::A1)],
-                3 => vec![
-                    psx.cpu.regs.read(Reg::A0),
-                    psx.cpu.regs.read(Reg::A1),
-                    psx.cpu.regs.read(Reg::A2),
-                ],
-                _ => vec![
-                    psx.cpu.regs.read(Reg::A0),
-                    psx.cpu.regs.read(Reg::A1),
-                    psx.cpu.regs.read(Reg::A2),
-                    psx.cpu.regs.read(Reg::A3),
-                ],
-            };
-
-            let args = args
-                .into_iter()
-                .map(|x| format!("0x{x:08X}"))
-                .collect::<Vec<_>>()
-                .join(", ");
-
-            debug!(
-                psx.loggers.kernel,
-                "executed kernel function {func:?}({args})"
-            );
+            if self.recent_kernel_calls.len() == RECENT_KERNEL_CALLS {
+                self.recent_kernel_calls.pop_front();
+            }
+
+            let call = KernelCall::decode(psx, func);
+            debug!(psx.loggers.kernel, "executed kernel function {call}");
+            self.recent_kernel_calls.push_back(call);
         } else {
             let code = psx.cpu.regs.read(Reg::R9) as u8;
             warn!(
@@ -369,6 +561,40 @@ impl Interpreter {
                 "executed unknown kernel function 0x{:02X} at {}", code, self.current_addr
             );
         }
+
+        None
+    }
+
+    /// Services kernel file functions on `cdrom:` paths from the files in `root` instead of the
+    /// disc.
+    pub fn redirect_cdrom_files(&mut self, root: PathBuf) {
+        self.file_redirect = Some(FileRedirect::new(root));
+    }
+
+    /// Returns from the kernel function being called at the current address with the given value,
+    /// as if it had been executed by the kernel.
+    fn return_from_kernel_call(&mut self, psx: &mut PSX, value: u32) {
+        if let Some(load) = self.load_delay_slot.take() {
+            psx.cpu.regs.write(load.reg, load.value);
+        }
+
+        psx.cpu.regs.write(Reg::V0, value);
+
+        let ra = Address(psx.cpu.regs.read(Reg::RA));
+        let (instr, exception) = fetch(psx, ra);
+        self.instr_delay_slot = (instr, ra);
+        self.fetch_exception = exception;
+        psx.cpu.regs.write_pc(ra.value().wrapping_add(4));
+    }
+
+    /// Returns the most recent kernel calls, oldest first.
+    pub fn recent_kernel_calls(&self) -> impl Iterator<Item = &KernelCall> {
+        self.recent_kernel_calls.iter()
+    }
+
+    /// Address of the instruction currently executing, or of the last one executed.
+    pub fn current_addr(&self) -> Address {
+        self.current_addr
     }
 
     pub fn load_delay_slot(&self) -> Option<RegLoad> {
@@ -380,66 +606,240 @@ impl Interpreter {
     }
 
     /// Executes the next instruction and returns how many cycles it takes to complete.
+    ///
+    /// At most one exception is taken per instruction. When more than one is possible, they're
+    /// resolved in hardware priority order:
+    /// 1. bus error on the fetch of the instruction
+    /// 2. interrupt (except for GTE instructions, which are always executed)
+    /// 3. address error on the fetch of the instruction
+    /// 4. code breakpoint (BPC)
+    /// 5. exceptions raised by the execution of the instruction (reserved instruction, overflow,
+    ///    syscall, break, data breakpoints, data address and bus errors)
     pub fn exec_next(&mut self, psx: &mut PSX) -> u64 {
         if self.instr_delay_slot.1.value() == 0x8003_0000 {
             cold_path();
+            // the shell is starting, so the BIOS is done booting
+            self.delay_loops = None;
             self.sideload(psx);
         }
 
         let pc = Address(psx.cpu.regs.read_pc());
-        let Ok(fetched) = psx.read::<_, true>(pc) else {
-            if let Some(load) = self.load_delay_slot.take() {
-                psx.cpu.regs.write(load.reg, load.value);
-            }
-
-            self.trigger_exception_at(
-                psx,
-                self.instr_delay_slot.1,
-                psx.cpu.regs.read_pc().into(),
-                Exception::AddressErrorLoad,
-            );
-            return DEFAULT_DELAY;
-        };
+        let (fetched, fetch_exception) = fetch(psx, pc);
 
-        let (current_instr, current_addr) = std::mem::replace(
-            &mut self.instr_delay_slot,
-            (Instruction::from_bits(fetched), pc),
-        );
+        let (current_instr, current_addr) =
+            std::mem::replace(&mut self.instr_delay_slot, (fetched, pc));
+        let current_exception = std::mem::replace(&mut self.fetch_exception, fetch_exception);
 
         self.current_addr = current_addr;
+        psx.flight_recorder.set_pc(current_addr);
         psx.cpu
             .regs
             .write_pc(psx.cpu.regs.read_pc().wrapping_add(4));
 
-        self.log_kernel_calls(psx);
+        let overridden = self.log_kernel_calls(psx);
 
-        self.pending_load = self.load_delay_slot.take();
-        let cycles = if current_instr.op().is_some_and(|op| op == Opcode::COP2)
-            || !self.check_interrupts(psx)
+        if current_exception.is_none()
+            && let Some(value) = overridden.or_else(|| {
+                self.file_redirect
+                    .as_mut()?
+                    .intercept(psx, self.current_addr)
+            })
         {
-            self.exec(psx, current_instr)
-        } else {
+            cold_path();
+            self.return_from_kernel_call(psx, value);
+            self.forget_idle_loop();
+            return DEFAULT_DELAY;
+        }
+
+        // decoded once here and reused when executing
+        let op = current_instr.op();
+
+        self.pending_load = self.load_delay_slot.take();
+        let cycles = if current_exception == Some(Exception::BusErrorInstruction) {
+            cold_path();
+            self.trigger_exception(psx, Exception::BusErrorInstruction);
+            DEFAULT_DELAY
+        } else if op != Some(Opcode::COP2) && self.check_interrupts(psx) {
+            DEFAULT_DELAY
+        } else if let Some(exception) = current_exception {
+            cold_path();
+            // a misaligned fetch, e.g. after a jump to a misaligned address, faults on the
+            // address of the instruction itself
+            if exception == Exception::AddressErrorLoad {
+                psx.cop0.regs.set_bad_vaddr(current_addr.value());
+            }
+
+            self.trigger_exception(psx, exception);
             DEFAULT_DELAY
+        } else if psx.cop0.regs.breakpoints_armed() && self.check_code_breakpoint(psx) {
+            DEFAULT_DELAY
+        } else {
+            self.profile_instr(current_instr, op, current_addr);
+            self.exec(psx, current_instr, op)
         };
 
         if let Some(load) = self.pending_load {
             psx.cpu.regs.write(load.reg, load.value);
         }
 
-        if let Some(physical) = pc.physical()
-            && (physical.region() == Some(Region::ScratchPad)
-                || physical == io::Reg::InterruptStatus.address()
-                || physical == io::Reg::InterruptMask.address())
-        {
-            self.trigger_exception_at(
-                psx,
-                self.instr_delay_slot.1,
-                psx.cpu.regs.read_pc().into(),
-                Exception::BusErrorInstruction,
-            );
-            return DEFAULT_DELAY;
+        // accesses to slow regions take longer than the instruction itself
+        let cycles = cycles + std::mem::take(&mut psx.bus_stall);
+
+        // delay loops of the BIOS are fast-forwarded by running them in no time
+        let cycles = match &mut self.delay_loops {
+            Some(delay_loops)
+                if delay_loops.observe(psx, current_addr, self.instr_delay_slot.1) =>
+            {
+                0
+            }
+            _ => cycles,
+        };
+
+        if let Some(idle_loops) = &mut self.idle_loops {
+            idle_loops.observe(current_addr, self.instr_delay_slot.1, cycles);
         }
 
         cycles
     }
 }
+
+/// Fetches the instruction at `addr`, returning the exception it raises if the fetch fails. The
+/// returned instruction is a NOP in that case.
+fn fetch(psx: &mut PSX, addr: Address) -> (Instruction, Option<Exception>) {
+    if !addr.is_aligned(4) {
+        return (Instruction::NOP, Some(Exception::AddressErrorLoad));
+    }
+
+    let fetched = psx.read_unaligned::<u32, true>(addr);
+    psx.record_access(AccessKind::Fetch, addr, fetched);
+
+    // instructions can't be fetched from the scratchpad or the interrupt registers
+    if let Some(physical) = addr.physical()
+        && (physical.region() == Some(Region::ScratchPad)
+            || physical == io::Reg::InterruptStatus.address()
+            || physical == io::Reg::InterruptMask.address())
+    {
+        return (Instruction::NOP, Some(Exception::BusErrorInstruction));
+    }
+
+    (Instruction::from_bits(fetched), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Emulator, testing, unimplemented::Reporting};
+    use shimmer_core::{
+        cpu::{
+            Reg as CpuReg,
+            cop0::{Exception, Reg},
+        },
+        interrupts::Interrupt,
+        mem::{Address, io},
+    };
+
+    const PROGRAM: Address = Address(0x8000_2000);
+    // IEc
+    const SR_IEC: u32 = 0x0000_0001;
+
+    /// Steps `count` instructions, one at a time, returning whether the CPU entered the general
+    /// exception handler because of an interrupt.
+    fn interrupted(emulator: &mut Emulator, count: u64) -> bool {
+        (0..count).any(|_| {
+            emulator.step_instructions(1);
+            let psx = emulator.psx();
+            psx.cpu.regs.read_pc() == 0x8000_0080
+                && psx.cop0.regs.cause().exception() == Some(Exception::Interrupt)
+        })
+    }
+
+    #[test]
+    fn system_interrupts_respect_im2() {
+        let mut emulator = testing::emulator(testing::builder());
+        let psx = emulator.psx_mut();
+        psx.cop0.regs.write(Reg::COP0_SR, SR_IEC);
+        testing::write::<u32>(psx, io::Reg::InterruptMask, 1);
+
+        // pending in CAUSE, but masked by SR.IM2
+        emulator.request_interrupt(Interrupt::VBlank);
+        assert!(!interrupted(&mut emulator, 4));
+        let psx = emulator.psx();
+        assert!(psx.cop0.regs.cause().system_interrupt_pending());
+        assert_eq!(psx.cop0.regs.delivered_interrupt_lines(), 0);
+
+        emulator
+            .psx_mut()
+            .cop0
+            .regs
+            .write(Reg::COP0_SR, SR_IEC | (1 << 10));
+        assert!(interrupted(&mut emulator, 1));
+    }
+
+    #[test]
+    fn system_interrupts_require_iec() {
+        let mut emulator = testing::emulator(testing::builder());
+        let psx = emulator.psx_mut();
+        psx.cop0.regs.write(Reg::COP0_SR, 1 << 10);
+        testing::write::<u32>(psx, io::Reg::InterruptMask, 1);
+
+        emulator.request_interrupt(Interrupt::VBlank);
+        assert!(!interrupted(&mut emulator, 4));
+    }
+
+    /// Runs a program which requests the software interrupt on line 0 by writing to CAUSE, with
+    /// SR set to `sr`. Returns whether it was delivered.
+    fn software_interrupt(sr: u32) -> (Emulator, bool) {
+        let mut emulator = testing::emulator(testing::builder());
+
+        // addiu $t0, $zero, 0x100; mtc0 $t0, CAUSE; nop
+        let program = [0x2408_0100, 0x4088_6800, 0];
+        emulator.load_raw(PROGRAM, &program.map(u32::to_le_bytes).concat());
+        emulator.psx_mut().cop0.regs.write(Reg::COP0_SR, sr);
+        emulator.set_pc(PROGRAM);
+
+        let delivered = interrupted(&mut emulator, 3);
+        (emulator, delivered)
+    }
+
+    #[test]
+    fn software_interrupts_are_requested_through_cause() {
+        let (mut emulator, delivered) = software_interrupt(SR_IEC | (1 << 8));
+        assert!(delivered);
+
+        let psx = emulator.psx();
+        assert_eq!(
+            psx.cop0.regs.cause().pending_interrupt_lines_at(0),
+            Some(true)
+        );
+        assert_eq!(psx.cop0.regs.read(Reg::COP0_EPC), PROGRAM.value() + 8);
+    }
+
+    #[test]
+    fn software_interrupts_respect_im() {
+        // only IM1 is enabled, so line 0 stays pending
+        let (mut emulator, delivered) = software_interrupt(SR_IEC | (1 << 9));
+        assert!(!delivered);
+
+        let psx = emulator.psx();
+        assert_eq!(
+            psx.cop0.regs.cause().pending_interrupt_lines_at(0),
+            Some(true)
+        );
+        assert_eq!(psx.cop0.regs.delivered_interrupt_lines(), 0);
+    }
+
+    #[test]
+    fn coprocessor_branches_are_reported_and_not_taken() {
+        let builder = testing::builder().unimplemented_reporting(Reporting::Summary);
+        let mut emulator = testing::emulator(builder);
+
+        // bc0f 16; nop; addiu $t0, $zero, 1; nop
+        let program = [0x4100_0003, 0, 0x2408_0001, 0];
+        emulator.load_raw(PROGRAM, &program.map(u32::to_le_bytes).concat());
+        emulator.set_pc(PROGRAM);
+        emulator.step_instructions(4);
+
+        let psx = emulator.psx();
+        assert_eq!(psx.cpu.regs.read(CpuReg::T0), 1);
+        assert_eq!(psx.unimplemented.counts().get("COP0 branch"), Some(&1));
+    }
+}
@@ -0,0 +1,194 @@
+//! Detection of the delay loops the BIOS runs while it boots, e.g. to give hardware time to
+//! settle, so that they can be fast-forwarded by not charging their cycles.
+//!
+//! A loop is a delay loop if its body is a straight line of register operations and accesses to
+//! the stack ending in a backward branch to its start, and the branch depends on a register
+//! written by the loop, i.e. on a counter. Such a loop doesn't interact with the rest of the
+//! system, so running it in no time only changes how long the boot takes.
+//!
+//! Loop bodies are decoded by the analysis shared with [idle loop detection](super::idle).
+
+use super::{Interpreter, idle};
+use crate::PSX;
+use shimmer_core::{
+    cpu::{Reg, instr::Instruction},
+    mem::Address,
+};
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+/// Maximum length of a delay loop, in instructions, including the delay slot of its branch.
+const MAX_LOOP_LEN: u32 = 16;
+
+/// Whether the loop `code` starting at `start`, whose second to last instruction must be the
+/// branch closing it, is a delay loop.
+fn analyze(start: Address, code: &[Instruction]) -> bool {
+    let Some(effects) = idle::loop_effects(start, code) else {
+        return false;
+    };
+
+    let branch = &effects[effects.len() - 2];
+    let written = |reg: Reg| reg != Reg::R0 && effects.iter().any(|effect| effect.write == reg);
+
+    // memory may only be accessed through the stack, which must stay in place
+    let stack_only = effects.iter().all(|effect| {
+        effect
+            .load
+            .or(effect.store)
+            .is_none_or(|access| access.base == Reg::SP)
+    });
+    if !stack_only || written(Reg::SP) {
+        return false;
+    }
+
+    // the branch must depend on something the loop changes, i.e. on a counter
+    branch.reads.iter().any(|&reg| written(reg))
+}
+
+/// Delay loop detection state.
+#[derive(Debug, Default)]
+pub(super) struct DelayLoops {
+    /// Analyses of the loops seen so far, by start address: their code and whether they're delay
+    /// loops.
+    analyses: BTreeMap<u32, (Vec<u32>, bool)>,
+    /// Addresses of the delay loop being executed, if any.
+    current: Option<RangeInclusive<u32>>,
+}
+
+impl DelayLoops {
+    /// Observes the execution of the instruction at `addr`, after which the next instruction to
+    /// execute is at `next`. Returns whether it's part of a delay loop, in which case its cycles
+    /// shouldn't be charged.
+    pub(super) fn observe(&mut self, psx: &PSX, addr: Address, next: Address) -> bool {
+        if let Some(current) = &self.current {
+            let exit = current.end().wrapping_add(4);
+            if current.contains(&addr.value())
+                && (current.contains(&next.value()) || next.value() == exit)
+            {
+                return true;
+            }
+
+            self.current = None;
+        }
+
+        // a short backwards jump might close a delay loop, which is then fast-forwarded from its
+        // second iteration on
+        let distance = addr.value().wrapping_sub(next.value());
+        if next.value() < addr.value() && distance < MAX_LOOP_LEN * 4 && self.check(psx, next, addr)
+        {
+            self.current = Some(next.value()..=addr.value());
+        }
+
+        false
+    }
+
+    /// Whether the loop from `start` to `end`, the delay slot of its branch, is a delay loop.
+    fn check(&mut self, psx: &PSX, start: Address, end: Address) -> bool {
+        let len = (end.value() - start.value()) / 4 + 1;
+        let Some(code) = (0..len)
+            .map(|i| psx.peek::<u32>(start + 4 * i))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+
+        let (analyzed, is_delay) = self
+            .analyses
+            .entry(start.value())
+            .or_insert_with(|| (Vec::new(), false));
+
+        // code might have been modified since it was analyzed
+        if *analyzed != code {
+            let instructions: Vec<_> = code
+                .iter()
+                .map(|&word| Instruction::from_bits(word))
+                .collect();
+            *is_delay = analyze(start, &instructions);
+            *analyzed = code;
+        }
+
+        *is_delay
+    }
+}
+
+impl Interpreter {
+    /// Enables or disables fast-forwarding the delay loops of the BIOS while it boots. Once the
+    /// shell starts, delay loops are no longer detected.
+    pub fn set_post_delay_skipping(&mut self, enabled: bool) {
+        self.delay_loops = enabled.then(DelayLoops::default);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    const PROGRAM: Address = Address(0x8000_2000);
+
+    fn is_delay_loop(code: &[u32]) -> bool {
+        let code = code
+            .iter()
+            .map(|&word| Instruction::from_bits(word))
+            .collect::<Vec<_>>();
+        analyze(PROGRAM, &code)
+    }
+
+    #[test]
+    fn counter_loops_are_delay_loops() {
+        // loop: addiu $t0, $t0, -1; bne $t0, $zero, loop; nop
+        assert!(is_delay_loop(&[0x2508_FFFF, 0x1500_FFFE, 0x0000_0000]));
+        // loop: sw $t0, 0($sp); addiu $t0, $t0, -1; bgtz $t0, loop; nop
+        assert!(is_delay_loop(&[
+            0xAFA8_0000,
+            0x2508_FFFF,
+            0x1D00_FFFD,
+            0x0000_0000
+        ]));
+    }
+
+    #[test]
+    fn loops_with_side_effects_are_not_delay_loops() {
+        // loop: sw $t1, 0($t2); addiu $t0, $t0, -1; bne $t0, $zero, loop; nop
+        assert!(!is_delay_loop(&[
+            0xAD49_0000,
+            0x2508_FFFF,
+            0x1500_FFFD,
+            0x0000_0000
+        ]));
+        // loop: addiu $sp, $sp, -1; bne $sp, $zero, loop; nop
+        assert!(!is_delay_loop(&[0x27BD_FFFF, 0x17A0_FFFE, 0x0000_0000]));
+    }
+
+    #[test]
+    fn loops_must_branch_on_their_counter_to_their_start() {
+        // loop: addiu $t1, $t1, 1; bne $t0, $zero, loop; nop
+        assert!(!is_delay_loop(&[0x2529_0001, 0x1500_FFFE, 0x0000_0000]));
+        // addiu $t0, $t0, -1; loop: bne $t0, $zero, loop; nop
+        assert!(!is_delay_loop(&[0x2508_FFFF, 0x1500_FFFF, 0x0000_0000]));
+    }
+
+    #[test]
+    fn skipped_delay_loops_take_no_cycles_after_their_first_iteration() {
+        const ITERATIONS: u32 = 1_000;
+
+        // loop: addiu $t0, $t0, -1; bne $t0, $zero, loop; nop
+        let code = [0x2508_FFFF, 0x1500_FFFE, 0x0000_0000u32];
+        let run = |skip| {
+            let mut emulator = testing::emulator(testing::builder().skip_post_delays(skip));
+            emulator.load_raw(PROGRAM, &code.map(u32::to_le_bytes).concat());
+            emulator.set_pc(PROGRAM);
+            emulator.psx.cpu.regs.write(Reg::T0, ITERATIONS);
+
+            let cycles = emulator.step_instructions(3 * u64::from(ITERATIONS));
+            assert_eq!(emulator.psx.cpu.regs.read(Reg::T0), 0);
+            cycles
+        };
+
+        let normal = run(false);
+        let skipped = run(true);
+        assert!(
+            skipped * 100 < normal,
+            "{skipped} cycles skipped, {normal} not"
+        );
+    }
+}
@@ -1,15 +1,479 @@
 use super::{DEFAULT_DELAY, Interpreter};
 use crate::PSX;
-use shimmer_core::cpu::{cop0::Exception, instr::Instruction};
+use shimmer_core::{
+    cpu::{
+        cop0::{Exception, Reg},
+        instr::Instruction,
+    },
+    mem::Address,
+};
+use tinylog::debug;
+
+// vectors of the exceptions raised by hardware breakpoints
+const DEBUG_VECTOR_KSEG0: Address = Address(0x8000_0040);
+const DEBUG_VECTOR_KSEG1: Address = Address(0xBFC0_0140);
 
 impl Interpreter {
-    pub fn syscall(&mut self, psx: &mut PSX, _instr: Instruction) -> u64 {
+    // the 20 bit code of SYSCALL and BREAK is not passed to the handler in any register: it is
+    // read from the instruction itself, at `cop0::Registers::faulting_instruction_addr`
+
+    pub fn syscall(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
+        debug!(psx.loggers.cpu, "syscall"; code = instr.imm20().value());
         self.trigger_exception(psx, Exception::Syscall);
         DEFAULT_DELAY
     }
 
-    pub fn breakpoint(&mut self, psx: &mut PSX, _instr: Instruction) -> u64 {
+    pub fn breakpoint(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
+        debug!(psx.loggers.cpu, "break"; code = instr.imm20().value());
         self.trigger_exception(psx, Exception::Breakpoint);
         DEFAULT_DELAY
     }
+
+    /// Triggers a breakpoint exception caused by a hardware breakpoint. Unlike the BREAK
+    /// instruction, these jump to the debug exception vector.
+    #[cold]
+    fn trigger_debug_exception(&mut self, psx: &mut PSX) {
+        self.trigger_exception(psx, Exception::Breakpoint);
+
+        let vector = if psx
+            .cop0
+            .regs
+            .system_status()
+            .boot_exception_vectors_in_kseg1()
+        {
+            DEBUG_VECTOR_KSEG1
+        } else {
+            DEBUG_VECTOR_KSEG0
+        };
+
+        psx.cpu.regs.write_pc(vector.value());
+    }
+
+    /// Checks whether the current instruction hits the code breakpoint and, if so, triggers a
+    /// debug exception.
+    pub(super) fn check_code_breakpoint(&mut self, psx: &mut PSX) -> bool {
+        let regs = &psx.cop0.regs;
+        if !regs.dcic().code_breakpoint_armed() {
+            return false;
+        }
+
+        let bpc = regs.read(Reg::COP0_BPC);
+        let bpcm = regs.read(Reg::COP0_BPCM);
+        if (self.current_addr.value() ^ bpc) & bpcm != 0 {
+            return false;
+        }
+
+        psx.cop0
+            .regs
+            .dcic_mut()
+            .set_any_break(true)
+            .set_code_break(true);
+
+        self.trigger_debug_exception(psx);
+        true
+    }
+
+    /// Checks whether an access to `addr` hits the data breakpoint and, if so, triggers a debug
+    /// exception.
+    #[inline(always)]
+    pub(super) fn check_data_breakpoint(
+        &mut self,
+        psx: &mut PSX,
+        addr: Address,
+        write: bool,
+    ) -> bool {
+        if !psx.cop0.regs.breakpoints_armed() {
+            return false;
+        }
+
+        let regs = &psx.cop0.regs;
+        if !regs.dcic().data_breakpoint_armed(write) {
+            return false;
+        }
+
+        let bda = regs.read(Reg::COP0_BDA);
+        let bdam = regs.read(Reg::COP0_BDAM);
+        if (addr.value() ^ bda) & bdam != 0 {
+            return false;
+        }
+
+        psx.cop0
+            .regs
+            .dcic_mut()
+            .set_any_break(true)
+            .set_data_break(true)
+            .set_data_read_break(!write)
+            .set_data_write_break(write);
+
+        self.trigger_debug_exception(psx);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Emulator, testing};
+    use shimmer_core::{
+        cpu::{Reg as CpuReg, cop0::DecodedCause},
+        interrupts::Interrupt,
+        mem::io,
+    };
+
+    const PROGRAM: Address = Address(0x8000_2000);
+
+    /// Runs `steps` instructions of `program`, placed at [`PROGRAM`], after letting `configure`
+    /// arm breakpoints. The debug handler at the KSEG0 debug vector sets `$t3` and idles.
+    fn run(program: &[u32], configure: impl FnOnce(&mut PSX), steps: u64) -> Emulator {
+        let mut emulator = testing::emulator(testing::builder());
+
+        // addiu $t3, $zero, 0x40; j IDLE_LOOP; nop
+        let handler = [
+            0x240B_0040,
+            0x0800_0000 | ((testing::IDLE_LOOP.value() >> 2) & 0x03FF_FFFF),
+            0,
+        ];
+        emulator.load_raw(DEBUG_VECTOR_KSEG0, &handler.map(u32::to_le_bytes).concat());
+        let program: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        emulator.load_raw(PROGRAM, &program);
+
+        let psx = emulator.psx_mut();
+        psx.cop0.regs.write(Reg::COP0_SR, 0);
+        configure(psx);
+
+        emulator.set_pc(PROGRAM);
+        emulator.step_instructions(steps);
+        emulator
+    }
+
+    fn arm(psx: &mut PSX, dcic: u32, reg: Reg, mask: Reg, value: u32) {
+        psx.cop0.regs.write(reg, value);
+        psx.cop0.regs.write(mask, 0xFFFF_FFFF);
+        psx.cop0.regs.write(Reg::COP0_DCIC, dcic);
+    }
+
+    // addiu $t0, $zero, 1; addiu $t1, $zero, 2; addiu $t2, $zero, 3
+    const ADDIU_SEQUENCE: [u32; 3] = [0x2408_0001, 0x2409_0002, 0x240A_0003];
+    // master enables and the code breakpoint
+    const DCIC_CODE: u32 = 0xC180_0000;
+    // master enables and the data breakpoint on writes
+    const DCIC_DATA_WRITE: u32 = 0xCA80_0000;
+
+    #[test]
+    fn code_breakpoint_takes_debug_exception() {
+        let bpc = PROGRAM.value() + 4;
+        let mut emulator = run(
+            &ADDIU_SEQUENCE,
+            |psx| arm(psx, DCIC_CODE, Reg::COP0_BPC, Reg::COP0_BPCM, bpc),
+            4,
+        );
+
+        let psx = emulator.psx_mut();
+        assert_eq!(psx.cpu.regs.read(CpuReg::T0), 1);
+        // the instruction at BPC is not executed, and execution continues at the debug vector
+        assert_eq!(psx.cpu.regs.read(CpuReg::T1), 0);
+        assert_eq!(psx.cpu.regs.read(CpuReg::T2), 0);
+        assert_eq!(psx.cpu.regs.read(CpuReg::T3), 0x40);
+
+        let regs = &psx.cop0.regs;
+        assert_eq!(regs.read(Reg::COP0_EPC), bpc);
+        assert_eq!(regs.cause().exception(), Some(Exception::Breakpoint));
+
+        let dcic = regs.dcic();
+        assert!(dcic.any_break());
+        assert!(dcic.code_break());
+        assert!(!dcic.data_break());
+    }
+
+    #[test]
+    fn code_breakpoint_needs_master_enables() {
+        let bpc = PROGRAM.value() + 4;
+        let mut emulator = run(
+            &ADDIU_SEQUENCE,
+            |psx| {
+                arm(
+                    psx,
+                    DCIC_CODE & !0x8000_0000,
+                    Reg::COP0_BPC,
+                    Reg::COP0_BPCM,
+                    bpc,
+                )
+            },
+            3,
+        );
+
+        let psx = emulator.psx_mut();
+        assert_eq!(psx.cpu.regs.read(CpuReg::T1), 2);
+        assert_eq!(psx.cpu.regs.read(CpuReg::T2), 3);
+        assert!(!psx.cop0.regs.dcic().any_break());
+    }
+
+    #[test]
+    fn data_breakpoint_takes_debug_exception_on_write() {
+        // addiu $t0, $zero, 1; sw $t0, 0x100($zero); addiu $t2, $zero, 3
+        let program = [0x2408_0001, 0xAC08_0100, 0x240A_0003];
+        let mut emulator = run(
+            &program,
+            |psx| arm(psx, DCIC_DATA_WRITE, Reg::COP0_BDA, Reg::COP0_BDAM, 0x100),
+            4,
+        );
+
+        let psx = emulator.psx_mut();
+        // the store is not performed
+        assert_eq!(psx.read::<u32, true>(Address(0x100)).unwrap(), 0);
+        assert_eq!(psx.cpu.regs.read(CpuReg::T2), 0);
+        assert_eq!(psx.cpu.regs.read(CpuReg::T3), 0x40);
+
+        let regs = &psx.cop0.regs;
+        assert_eq!(regs.read(Reg::COP0_EPC), PROGRAM.value() + 4);
+        assert_eq!(regs.cause().exception(), Some(Exception::Breakpoint));
+
+        let dcic = regs.dcic();
+        assert!(dcic.any_break());
+        assert!(dcic.data_break());
+        assert!(dcic.data_write_break());
+        assert!(!dcic.data_read_break());
+        assert!(!dcic.code_break());
+    }
+
+    #[test]
+    fn data_breakpoint_on_write_ignores_reads() {
+        // lw $t1, 0x100($zero); addiu $t2, $zero, 3; nop
+        let program = [0x8C09_0100, 0x240A_0003, 0];
+        let mut emulator = run(
+            &program,
+            |psx| {
+                psx.write::<u32, true>(Address(0x100), 0x1234).unwrap();
+                arm(psx, DCIC_DATA_WRITE, Reg::COP0_BDA, Reg::COP0_BDAM, 0x100);
+            },
+            3,
+        );
+
+        let psx = emulator.psx_mut();
+        assert_eq!(psx.cpu.regs.read(CpuReg::T1), 0x1234);
+        assert_eq!(psx.cpu.regs.read(CpuReg::T2), 3);
+        assert!(!psx.cop0.regs.dcic().any_break());
+    }
+
+    /// Runs `program`, placed at [`PROGRAM`], with a general exception handler which reads the
+    /// instruction at EPC into `$k1`, shifted right by 6 so that it holds the code of a `SYSCALL`
+    /// or `BREAK`.
+    fn run_to_handler(program: &[u32]) -> Emulator {
+        let mut emulator = testing::emulator(testing::builder());
+
+        let handler = [
+            0x401A_7000, // mfc0 $k0, EPC
+            0,
+            0x8F5B_0000, // lw $k1, 0($k0)
+            0,
+            0x001B_D982, // srl $k1, $k1, 6
+            0x0800_0000 | ((testing::IDLE_LOOP.value() >> 2) & 0x03FF_FFFF),
+            0,
+        ];
+        emulator.load_raw(
+            Address(0x8000_0080),
+            &handler.map(u32::to_le_bytes).concat(),
+        );
+        let program: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        emulator.load_raw(PROGRAM, &program);
+
+        emulator.psx_mut().cop0.regs.write(Reg::COP0_SR, 0);
+        emulator.set_pc(PROGRAM);
+        emulator.step_instructions(16);
+        emulator
+    }
+
+    #[test]
+    fn handler_reads_syscall_code_from_the_instruction() {
+        // syscall 0x12345
+        let mut emulator = run_to_handler(&[0x0048_D14C]);
+
+        let psx = emulator.psx_mut();
+        assert_eq!(psx.cop0.regs.cause().exception(), Some(Exception::Syscall));
+        assert_eq!(psx.cop0.regs.read(Reg::COP0_EPC), PROGRAM.value());
+        assert_eq!(psx.cpu.regs.read(CpuReg::K1), 0x12345);
+    }
+
+    #[test]
+    fn handler_reads_break_code_from_the_instruction() {
+        // break 0xABCDE
+        let mut emulator = run_to_handler(&[0x02AF_378D]);
+
+        let psx = emulator.psx_mut();
+        assert_eq!(
+            psx.cop0.regs.cause().exception(),
+            Some(Exception::Breakpoint)
+        );
+        assert_eq!(psx.cpu.regs.read(CpuReg::K1), 0xABCDE);
+    }
+
+    #[test]
+    fn syscall_in_delay_slot_is_found_past_epc() {
+        // beq $zero, $zero, 2; syscall 0x12345
+        let mut emulator = run_to_handler(&[0x1000_0002, 0x0048_D14C]);
+
+        let psx = emulator.psx_mut();
+        let regs = &psx.cop0.regs;
+        assert_eq!(regs.cause().exception(), Some(Exception::Syscall));
+        assert!(regs.cause().branch_delay());
+        assert_eq!(regs.read(Reg::COP0_EPC), PROGRAM.value());
+
+        let addr = Address(regs.faulting_instruction_addr());
+        assert_eq!(addr, Address(PROGRAM.value() + 4));
+        let instr = Instruction::from_bits(psx.read::<u32, true>(addr).unwrap());
+        assert_eq!(instr.imm20().value(), 0x12345);
+    }
+
+    #[test]
+    fn decoded_cause_matches_the_exception() {
+        // beq $zero, $zero, 2; syscall 0x12345
+        let mut emulator = run_to_handler(&[0x1000_0002, 0x0048_D14C]);
+
+        let regs = &mut emulator.psx_mut().cop0.regs;
+        let cause = regs.decoded_cause();
+        assert_eq!(
+            cause,
+            DecodedCause {
+                exception: Some(Exception::Syscall),
+                pending_interrupts: 0,
+                branch_delay: true,
+            }
+        );
+        assert_eq!(cause.to_string(), "Syscall IP=00000000 BD");
+
+        // software interrupts requested afterwards show up as pending lines
+        regs.write(Reg::COP0_CAUSE, 0x0000_0300);
+        let cause = regs.decoded_cause();
+        assert_eq!(cause.exception, Some(Exception::Syscall));
+        assert_eq!(cause.pending_interrupts, 0b11);
+        assert_eq!(cause.to_string(), "Syscall IP=00000011 BD");
+
+        // break 0xABCDE
+        let mut emulator = run_to_handler(&[0x02AF_378D]);
+        let cause = emulator.psx().cop0.regs.decoded_cause();
+        assert_eq!(cause.to_string(), "Breakpoint IP=00000000");
+    }
+
+    #[test]
+    fn epc_is_read_only_to_software() {
+        let mut emulator = testing::emulator(testing::builder());
+        let regs = &mut emulator.psx_mut().cop0.regs;
+
+        regs.set_epc(0x8000_1234);
+        regs.write(Reg::COP0_EPC, 0xDEAD_BEEF);
+        assert_eq!(regs.read(Reg::COP0_EPC), 0x8000_1234);
+    }
+
+    #[test]
+    fn nested_exceptions_round_trip_through_rfe() {
+        let mut emulator = testing::emulator(testing::builder());
+
+        let resume = PROGRAM.value() + 4;
+        let handler = [
+            0x401B_6000, // mfc0 $k1, SR
+            0x1580_0006, // bne $t4, $zero, inner
+            0x258C_0001, // addiu $t4, $t4, 1
+            0x0360_8025, // or $s0, $k1, $zero
+            0x0000_000C, // syscall
+            0x4012_6000, // mfc0 $s2, SR
+            0x0800_0000 | ((resume >> 2) & 0x03FF_FFFF),
+            0x4200_0010, // rfe
+            // inner:
+            0x0360_8825, // or $s1, $k1, $zero
+            0x401A_7000, // mfc0 $k0, EPC
+            0,
+            0x275A_0004, // addiu $k0, $k0, 4
+            0x0340_0008, // jr $k0
+            0x4200_0010, // rfe
+        ];
+        emulator.load_raw(
+            Address(0x8000_0080),
+            &handler.map(u32::to_le_bytes).concat(),
+        );
+
+        let program = [
+            0x0000_000C, // syscall
+            0x4013_6000, // mfc0 $s3, SR
+            0x0800_0000 | ((testing::IDLE_LOOP.value() >> 2) & 0x03FF_FFFF),
+            0,
+        ];
+        emulator.load_raw(PROGRAM, &program.map(u32::to_le_bytes).concat());
+
+        // old: user mode, previous: user mode with interrupts, current: kernel with interrupts
+        emulator.psx_mut().cop0.regs.write(Reg::COP0_SR, 0b10_11_01);
+        emulator.set_pc(PROGRAM);
+        emulator.step_instructions(64);
+
+        let regs = &emulator.psx().cpu.regs;
+        assert_eq!(regs.read(CpuReg::T4), 2);
+        // each exception pushes kernel mode with interrupts disabled, dropping the old mode
+        assert_eq!(regs.read(CpuReg::S0), 0b11_01_00);
+        assert_eq!(regs.read(CpuReg::S1), 0b01_00_00);
+        // each RFE pops a mode, leaving the old one in place
+        assert_eq!(regs.read(CpuReg::S2), 0b01_01_00);
+        assert_eq!(regs.read(CpuReg::S3), 0b01_01_01);
+    }
+
+    /// Executes a single instruction at `pc`, where [`PROGRAM`] holds a `SYSCALL`, with the
+    /// VBlank interrupt pending and enabled if `interrupt`. Returns the exception taken at `pc`,
+    /// if any.
+    fn first_exception(pc: Address, interrupt: bool) -> Option<Exception> {
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.load_raw(PROGRAM, &0x0000_000Cu32.to_le_bytes());
+        emulator.set_pc(pc);
+
+        let psx = emulator.psx_mut();
+        // IEc and IM2, with the exception vectors in RAM
+        psx.cop0.regs.write(Reg::COP0_SR, 0x0000_0401);
+        psx.cop0.regs.set_epc(0);
+        if interrupt {
+            testing::write::<u32>(psx, io::Reg::InterruptMask, 1);
+            emulator.request_interrupt(Interrupt::VBlank);
+        }
+
+        emulator.step_instructions(1);
+
+        // CAUSE starts out as an interrupt, so make sure an exception was actually taken at `pc`
+        let regs = &emulator.psx.cop0.regs;
+        let taken = regs.read(Reg::COP0_EPC) == pc.value();
+        taken.then(|| regs.cause().exception()).flatten()
+    }
+
+    #[test]
+    fn simultaneous_exceptions_are_taken_by_priority() {
+        let misaligned = Address(PROGRAM.value() + 2);
+        let scratchpad = Address(0x1F80_0000);
+
+        let scenarios = [
+            ("syscall", PROGRAM, false, Exception::Syscall),
+            (
+                "syscall with interrupt",
+                PROGRAM,
+                true,
+                Exception::Interrupt,
+            ),
+            (
+                "misaligned fetch",
+                misaligned,
+                false,
+                Exception::AddressErrorLoad,
+            ),
+            (
+                "misaligned fetch with interrupt",
+                misaligned,
+                true,
+                Exception::Interrupt,
+            ),
+            (
+                "scratchpad fetch with interrupt",
+                scratchpad,
+                true,
+                Exception::BusErrorInstruction,
+            ),
+        ];
+
+        for (name, pc, interrupt, expected) in scenarios {
+            assert_eq!(first_exception(pc, interrupt), Some(expected), "{name}");
+        }
+    }
 }
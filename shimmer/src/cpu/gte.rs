@@ -537,3 +537,69 @@ impl Interpreter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use shimmer_core::gte::Gte;
+
+    #[test]
+    fn rtpt_diff_reports_the_screen_fifos() {
+        let mut psx = testing::psx();
+        let regs = &mut psx.gte.regs;
+
+        // identity rotation, a projection plane at 200 and three vertices in front of it
+        regs.write(Reg::RT_11_12, 0x1000);
+        regs.write(Reg::RT_22_23, 0x1000);
+        regs.write(Reg::RT_33_SS, 0x1000);
+        regs.write(Reg::H, 200);
+        // half a pixel, so that the projection rounds to nearest
+        regs.write(Reg::OFX, 0x8000);
+        regs.write(Reg::OFY, 0x8000);
+        for (xy, z, (x, y)) in [
+            (Reg::VXY0, Reg::VZ0, (10, 20)),
+            (Reg::VXY1, Reg::VZ1, (30, 40)),
+            (Reg::VXY2, Reg::VZ2, (50, 60)),
+        ] {
+            regs.write(xy, (y << 16) | x);
+            regs.write(z, 400);
+        }
+
+        // so that SZ0 changes when the FIFO is shifted
+        regs.write(Reg::SZ3, 100);
+
+        let before = psx.gte.snapshot();
+        psx.gte.regs.write(Reg::FLAG, 0);
+        rtpt(&mut psx, Instruction::from_bits(0x0028_0030));
+        let after = psx.gte.snapshot();
+
+        let diff = Gte::diff(&before, &after);
+        let changed: Vec<_> = diff.iter().map(|change| change.reg.alt_name()).collect();
+        for name in ["SXY0", "SXY1", "SXY2", "SZ0", "SZ1", "SZ2", "SZ3"] {
+            assert!(changed.contains(&name), "{name} not in {changed:?}");
+        }
+
+        // inputs are left alone
+        for name in ["VXY0", "VZ2", "H", "RT11RT12"] {
+            assert!(!changed.contains(&name), "{name} in {changed:?}");
+        }
+
+        let sz0 = diff.iter().find(|change| change.reg == Reg::SZ0).unwrap();
+        assert_eq!((sz0.old, sz0.new), (0, 100));
+        assert_eq!(sz0.to_string(), "SZ0: 0x00000000 -> 0x00000064");
+
+        // half of each coordinate, since the vertices are twice as far as the plane
+        assert_eq!(after[Reg::SXY0 as usize], (10 << 16) | 5);
+        assert_eq!(after[Reg::SXY2 as usize], (30 << 16) | 25);
+    }
+
+    #[test]
+    fn diff_of_identical_snapshots_is_empty() {
+        let mut psx = testing::psx();
+        psx.gte.regs.write(Reg::IR1, 0x1234);
+
+        let snapshot = psx.gte.snapshot();
+        assert!(Gte::diff(&snapshot, &snapshot).is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+//! Hooks on kernel functions, which can observe calls to them or override what they do, e.g. for
+//! HLE experiments.
+
+use super::{Interpreter, KernelCall};
+use crate::PSX;
+use shimmer_core::kernel;
+
+/// A hook on a kernel function. It's called with the decoded call before the kernel executes it,
+/// and returns the value to return from the call with in order to skip the kernel's
+/// implementation, or [`None`] to let it run.
+pub type KernelHook = Box<dyn FnMut(&mut PSX, &KernelCall) -> Option<u32> + Send>;
+
+impl Interpreter {
+    /// Registers `hook` on calls to `function`. Hooks run in registration order, until one of
+    /// them overrides the call.
+    ///
+    /// Functions which can be reached through more than one table, such as `PutChar`, which
+    /// A(3Ch) forwards to B(3Dh), are hooked on every one of them.
+    pub fn hook_kernel_function(&mut self, function: kernel::Function, hook: KernelHook) {
+        self.kernel_hooks.push((function, hook));
+    }
+
+    /// Removes every hook on `function`.
+    pub fn unhook_kernel_function(&mut self, function: kernel::Function) {
+        self.kernel_hooks.retain(|(hooked, _)| *hooked != function);
+    }
+
+    /// Runs the hooks on `function`, returning the value the call was overridden with, if any.
+    pub(super) fn run_kernel_hooks(
+        &mut self,
+        psx: &mut PSX,
+        function: kernel::Function,
+    ) -> Option<u32> {
+        if !self
+            .kernel_hooks
+            .iter()
+            .any(|(hooked, _)| *hooked == function)
+        {
+            return None;
+        }
+
+        let call = KernelCall::decode(psx, function);
+        self.kernel_hooks
+            .iter_mut()
+            .filter(|(hooked, _)| *hooked == function)
+            .find_map(|(_, hook)| hook(psx, &call))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cpu::{KernelArg, StdoutMirror},
+        testing,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn hook_overrides_putchar() {
+        let mut emulator = testing::emulator(testing::builder());
+
+        let captured = Arc::new(Mutex::new(String::new()));
+        let sink = captured.clone();
+        emulator.hook_kernel_function(kernel::Function::PutChar, move |_, call| {
+            let &[KernelArg::Char(char)] = call.args.as_slice() else {
+                return None;
+            };
+
+            sink.lock().unwrap().extend(char::from_u32(char));
+            Some(char)
+        });
+
+        testing::putchar(&mut emulator, "ok");
+
+        assert_eq!(*captured.lock().unwrap(), "ok");
+        assert!(emulator.psx.memory.kernel_stdout.is_empty());
+        // the overridden call returns to its caller
+        assert_eq!(emulator.cpu.instr_delay_slot().1, testing::IDLE_LOOP);
+    }
+
+    #[test]
+    fn observing_hooks_let_the_kernel_run() {
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.set_stdout_mirror(StdoutMirror::Disabled);
+
+        let calls = Arc::new(Mutex::new(0));
+        let counter = calls.clone();
+        emulator.hook_kernel_function(kernel::Function::PutChar, move |_, _| {
+            *counter.lock().unwrap() += 1;
+            None
+        });
+
+        testing::putchar(&mut emulator, "ok");
+        assert_eq!(*calls.lock().unwrap(), 2);
+        assert_eq!(emulator.psx.memory.kernel_stdout, "ok");
+
+        emulator.unhook_kernel_function(kernel::Function::PutChar);
+        testing::putchar(&mut emulator, "!");
+        assert_eq!(*calls.lock().unwrap(), 2);
+        assert_eq!(emulator.psx.memory.kernel_stdout, "ok!");
+    }
+}
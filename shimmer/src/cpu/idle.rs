@@ -0,0 +1,523 @@
+//! Detection of idle loops, in which the CPU spins waiting for an interrupt or for some other
+//! component to change memory.
+//!
+//! A loop is idle if its body is a straight line of register operations and side effect free
+//! loads ending in a backward branch to its start, and every register it reads is either written
+//! earlier in the same iteration or never written by it. Every iteration of such a loop then
+//! computes exactly the same values as the previous one until memory changes, which can only
+//! happen through events.
+
+use super::Interpreter;
+use crate::PSX;
+use shimmer_core::{
+    cpu::{
+        Reg,
+        instr::{BZKind, Instruction, Opcode, SpecialOpcode},
+    },
+    mem::{Address, Region, io},
+};
+use std::collections::HashMap;
+
+/// Maximum length of an idle loop, in instructions, including the delay slot of its branch.
+const MAX_LOOP_LEN: u32 = 8;
+
+/// Addresses at which the interpreter does more than executing the instruction there, namely
+/// the kernel function vectors and the sideload hook.
+const HOOKED_ADDRESSES: [u32; 4] = [0xA0, 0xB0, 0xC0, 0x8003_0000];
+
+/// A memory access performed by a loop.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Access {
+    pub(super) base: Reg,
+    offset: i16,
+    size: u32,
+}
+
+impl Access {
+    fn new(instr: Instruction, size: u32) -> Self {
+        Self {
+            base: instr.rs(),
+            offset: instr.signed_imm16(),
+            size,
+        }
+    }
+
+    /// Whether this load can be skipped, i.e. whether it's aligned and reads from memory or from
+    /// a register which has no side effects when read.
+    fn is_pure(self, psx: &PSX) -> bool {
+        let addr = Address(
+            psx.cpu
+                .regs
+                .read(self.base)
+                .wrapping_add_signed(i32::from(self.offset)),
+        );
+
+        if !addr.is_aligned(self.size) {
+            return false;
+        }
+
+        match addr.region() {
+            Some(Region::Ram | Region::RamMirror | Region::ScratchPad | Region::BIOS) => true,
+            Some(Region::IOPorts) => [io::Reg::InterruptStatus, io::Reg::InterruptMask]
+                .iter()
+                .any(|reg| reg.offset(addr).is_some()),
+            _ => false,
+        }
+    }
+}
+
+/// The registers an instruction of a loop reads and writes, and the memory it accesses.
+pub(super) struct Effect {
+    pub(super) reads: [Reg; 2],
+    pub(super) write: Reg,
+    /// Whether the write only happens after the next instruction, as with loads.
+    delayed: bool,
+    pub(super) load: Option<Access>,
+    pub(super) store: Option<Access>,
+}
+
+impl Effect {
+    fn new(reads: [Reg; 2], write: Reg) -> Self {
+        Self {
+            reads,
+            write,
+            delayed: false,
+            load: None,
+            store: None,
+        }
+    }
+
+    fn load(instr: Instruction, size: u32) -> Self {
+        Self {
+            reads: [instr.rs(), Reg::R0],
+            write: instr.rt(),
+            delayed: true,
+            load: Some(Access::new(instr, size)),
+            store: None,
+        }
+    }
+
+    fn store(instr: Instruction, size: u32) -> Self {
+        Self {
+            store: Some(Access::new(instr, size)),
+            ..Self::new([instr.rs(), instr.rt()], Reg::R0)
+        }
+    }
+
+    /// Returns the effect of the branch `instr`, if it's a branch which can close a loop.
+    fn branch(instr: Instruction) -> Option<Self> {
+        Some(match instr.op()? {
+            Opcode::BEQ | Opcode::BNE => Self::new([instr.rs(), instr.rt()], Reg::R0),
+            Opcode::BLEZ | Opcode::BGTZ => Self::new([instr.rs(), Reg::R0], Reg::R0),
+            Opcode::BZ if matches!(instr.bz_kind(), BZKind::BLTZ | BZKind::BGEZ) => {
+                Self::new([instr.rs(), Reg::R0], Reg::R0)
+            }
+            _ => return None,
+        })
+    }
+
+    /// Returns the effect of `instr`, if it can be part of the body of a loop.
+    fn body(instr: Instruction) -> Option<Self> {
+        let rs = instr.rs();
+        let rt = instr.rt();
+
+        Some(match instr.op()? {
+            Opcode::ADDIU
+            | Opcode::SLTI
+            | Opcode::SLTIU
+            | Opcode::ANDI
+            | Opcode::ORI
+            | Opcode::XORI => Self::new([rs, Reg::R0], rt),
+            Opcode::LUI => Self::new([Reg::R0; 2], rt),
+            Opcode::LB | Opcode::LBU => Self::load(instr, 1),
+            Opcode::LH | Opcode::LHU => Self::load(instr, 2),
+            Opcode::LW => Self::load(instr, 4),
+            Opcode::SB => Self::store(instr, 1),
+            Opcode::SH => Self::store(instr, 2),
+            Opcode::SW => Self::store(instr, 4),
+            Opcode::SPECIAL => match instr.special_op()? {
+                SpecialOpcode::SLL | SpecialOpcode::SRL | SpecialOpcode::SRA => {
+                    Self::new([rt, Reg::R0], instr.rd())
+                }
+                SpecialOpcode::SLLV
+                | SpecialOpcode::SRLV
+                | SpecialOpcode::SRAV
+                | SpecialOpcode::ADDU
+                | SpecialOpcode::SUBU
+                | SpecialOpcode::AND
+                | SpecialOpcode::OR
+                | SpecialOpcode::XOR
+                | SpecialOpcode::NOR
+                | SpecialOpcode::SLT
+                | SpecialOpcode::SLTU => Self::new([rs, rt], instr.rd()),
+                _ => return None,
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// Returns the effects of the instructions of the loop `code` starting at `start`, whose second to
+/// last instruction must be the branch closing it. Returns `None` if it's not such a loop, or its
+/// body isn't a straight line of register operations, loads and stores.
+pub(super) fn loop_effects(start: Address, code: &[Instruction]) -> Option<Vec<Effect>> {
+    let branch_index = code.len().checked_sub(2)?;
+    let branch = code[branch_index];
+
+    // the branch must target the start of the loop
+    let delay_slot = start.value().wrapping_add(4 * code.len() as u32 - 4);
+    let target = delay_slot.wrapping_add_signed(i32::from(branch.signed_imm16()) << 2);
+    if target != start.value() {
+        return None;
+    }
+
+    code.iter()
+        .enumerate()
+        .map(|(index, &instr)| {
+            if index == branch_index {
+                Effect::branch(instr)
+            } else {
+                Effect::body(instr)
+            }
+        })
+        .collect()
+}
+
+/// Analyzes the loop `code` starting at `start`, as in [`loop_effects`]. Returns the loads it
+/// performs if it's idle.
+fn analyze(start: Address, code: &[Instruction]) -> Option<Vec<Access>> {
+    let effects = loop_effects(start, code)?;
+    if effects.iter().any(|effect| effect.store.is_some()) {
+        return None;
+    }
+
+    let written_anywhere =
+        |reg: Reg| reg != Reg::R0 && effects.iter().any(|effect| effect.write == reg);
+    for (index, effect) in effects.iter().enumerate() {
+        for &reg in &effect.reads {
+            if !written_anywhere(reg) {
+                continue;
+            }
+
+            // a register written by the loop must be read only after being written in the same
+            // iteration, otherwise iterations depend on each other
+            let written_before = effects[..index]
+                .iter()
+                .enumerate()
+                .any(|(i, e)| e.write == reg && (!e.delayed || i + 1 < index));
+            if !written_before {
+                return None;
+            }
+        }
+    }
+
+    // load addresses must not change between iterations so that they can be checked upfront
+    let loads: Vec<_> = effects.iter().filter_map(|effect| effect.load).collect();
+    if loads.iter().any(|load| written_anywhere(load.base)) {
+        return None;
+    }
+
+    Some(loads)
+}
+
+/// A loop which has been seen completing a whole iteration.
+#[derive(Debug, Clone, Copy)]
+struct Iteration {
+    start: Address,
+    /// Address of the last instruction of the loop, the delay slot of its branch.
+    end: Address,
+    cycles: u64,
+}
+
+/// The result of analyzing a loop.
+#[derive(Debug, Clone)]
+struct Analysis {
+    code: Vec<u32>,
+    /// The loads performed by the loop, if it's idle.
+    loads: Option<Vec<Access>>,
+}
+
+/// Idle loop detection state.
+#[derive(Debug, Default)]
+pub(super) struct IdleLoops {
+    /// Analyses of the loops seen so far, by start address.
+    analyses: HashMap<u32, Analysis>,
+    /// Start of the loop being executed and the cycles taken since its current iteration started.
+    current: Option<(Address, u64)>,
+    /// The last whole iteration of the loop being executed.
+    last_iteration: Option<Iteration>,
+}
+
+impl IdleLoops {
+    /// Observes the execution of the instruction at `addr`, which took `cycles` and after which
+    /// the next instruction to execute is at `next`.
+    pub(super) fn observe(&mut self, addr: Address, next: Address, cycles: u64) {
+        if let Some((_, elapsed)) = &mut self.current {
+            *elapsed += cycles;
+        }
+
+        if next.value() == addr.value().wrapping_add(4) {
+            return;
+        }
+
+        // control flow was redirected: a jump back to the start of the current loop completes an
+        // iteration, while a short backwards jump starts a new loop
+        let distance = addr.value().wrapping_sub(next.value());
+        if next.value() < addr.value() && distance < MAX_LOOP_LEN * 4 {
+            self.last_iteration = match self.current {
+                Some((start, cycles)) if start == next => Some(Iteration {
+                    start,
+                    end: addr,
+                    cycles,
+                }),
+                _ => None,
+            };
+
+            self.current = Some((next, 0));
+        } else {
+            self.forget();
+        }
+    }
+
+    /// Forgets the loop being executed.
+    pub(super) fn forget(&mut self) {
+        self.current = None;
+        self.last_iteration = None;
+    }
+
+    /// Returns the cycles an iteration of the idle loop `iteration` takes, if its code is idle
+    /// and its loads are free of side effects.
+    fn check(&mut self, psx: &PSX, iteration: Iteration) -> Option<u64> {
+        let len = (iteration.end.value() - iteration.start.value()) / 4 + 1;
+        if HOOKED_ADDRESSES
+            .iter()
+            .any(|&addr| (iteration.start.value()..=iteration.end.value()).contains(&addr))
+        {
+            return None;
+        }
+
+        let code = (0..len)
+            .map(|i| psx.peek::<u32>(iteration.start + 4 * i))
+            .collect::<Option<Vec<_>>>()?;
+
+        let analysis = self
+            .analyses
+            .entry(iteration.start.value())
+            .or_insert_with(|| Analysis {
+                code: Vec::new(),
+                loads: None,
+            });
+
+        // code might have been modified since it was analyzed
+        if analysis.code != code {
+            let instructions: Vec<_> = code
+                .iter()
+                .map(|&word| Instruction::from_bits(word))
+                .collect();
+            analysis.loads = analyze(iteration.start, &instructions);
+            analysis.code = code;
+        }
+
+        let loads = analysis.loads.as_ref()?;
+        loads
+            .iter()
+            .all(|load| load.is_pure(psx))
+            .then_some(iteration.cycles)
+    }
+}
+
+impl Interpreter {
+    /// Enables or disables idle loop detection. See [`Interpreter::idle_loop_iteration`].
+    pub fn set_idle_loop_detection(&mut self, enabled: bool) {
+        self.idle_loops = enabled.then(IdleLoops::default);
+    }
+
+    /// Forgets the loop being executed, so that it's only considered idle again after a whole
+    /// iteration of it is observed. Must be called whenever memory might have been changed by
+    /// something other than the CPU, e.g. after processing events.
+    pub fn forget_idle_loop(&mut self) {
+        if let Some(idle_loops) = &mut self.idle_loops {
+            idle_loops.forget();
+        }
+    }
+
+    /// If idle loop detection is enabled and the CPU is at the start of an iteration of an idle
+    /// loop, returns how many cycles an iteration takes.
+    ///
+    /// Every following iteration of the loop will then execute the same instructions on the same
+    /// values, until memory is changed by something other than the CPU, so iterations can be
+    /// skipped by simply advancing time. Loops are only considered idle if no interrupt is
+    /// pending and no hardware breakpoint is armed. Skipped iterations are neither profiled nor
+    /// recorded, so loops are also never idle while the instruction profiler, the execution
+    /// heatmap or the flight recorder is enabled.
+    pub fn idle_loop_iteration(&mut self, psx: &PSX) -> Option<u64> {
+        let idle_loops = self.idle_loops.as_mut()?;
+        let iteration = idle_loops.last_iteration?;
+        if self.instr_delay_slot.1 != iteration.start {
+            return None;
+        }
+
+        if self.load_delay_slot.is_some()
+            || self.fetch_exception.is_some()
+            || psx.cop0.regs.breakpoints_armed()
+            || self.profile.is_some()
+            || self.heatmap.is_some()
+            || psx.flight_recorder.active()
+        {
+            return None;
+        }
+
+        let mut status = psx.interrupts.status;
+        if status.mask(&psx.interrupts.mask).requested().is_some() {
+            return None;
+        }
+
+        idle_loops.check(psx, iteration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Emulator, testing};
+    use shimmer_core::CYCLES_SECOND;
+    use strum::VariantArray;
+
+    const PROGRAM: Address = Address(0x8000_2000);
+    const LOOP: Address = Address(PROGRAM.value() + 4);
+
+    /// Polls I_STAT until VBlank is requested, then acknowledges it, counts it in `$t3` and polls
+    /// again.
+    const VBLANK_COUNTER: [u32; 10] = [
+        0x3C09_1F80, // lui $t1, 0x1F80
+        0x8D28_1070, // loop: lw $t0, 0x1070($t1)
+        0,
+        0x3108_0001, // andi $t0, $t0, 1
+        0x1100_FFFC, // beq $t0, $zero, loop
+        0,
+        0xAD20_1070,                                       // sw $zero, 0x1070($t1)
+        0x256B_0001,                                       // addiu $t3, $t3, 1
+        0x0800_0000 | ((LOOP.value() >> 2) & 0x03FF_FFFF), // j loop
+        0,
+    ];
+
+    fn instructions(words: &[u32]) -> Vec<Instruction> {
+        words
+            .iter()
+            .map(|&word| Instruction::from_bits(word))
+            .collect()
+    }
+
+    fn emulator(skip_idle_loops: bool) -> Emulator {
+        let mut emulator = testing::emulator(testing::builder().skip_idle_loops(skip_idle_loops));
+        let program: Vec<u8> = VBLANK_COUNTER
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect();
+        emulator.load_raw(PROGRAM, &program);
+        emulator.set_pc(PROGRAM);
+        emulator
+    }
+
+    #[test]
+    fn polling_loops_are_idle() {
+        let loads = analyze(LOOP, &instructions(&VBLANK_COUNTER[1..6])).unwrap();
+        assert_eq!(loads.len(), 1);
+        assert_eq!(loads[0].base, Reg::R9);
+        assert_eq!(loads[0].offset, 0x1070);
+    }
+
+    #[test]
+    fn loops_carrying_values_between_iterations_are_not_idle() {
+        // loop: addiu $t0, $t0, 1; bne $t0, $zero, loop; nop
+        let code = instructions(&[0x2508_0001, 0x1500_FFFE, 0]);
+        assert!(analyze(LOOP, &code).is_none());
+    }
+
+    #[test]
+    fn loops_with_stores_are_not_idle() {
+        // loop: sw $zero, 0($t1); beq $zero, $zero, loop; nop
+        let code = instructions(&[0xAD20_0000, 0x1000_FFFE, 0]);
+        assert!(analyze(LOOP, &code).is_none());
+    }
+
+    #[test]
+    fn loops_loading_through_written_registers_are_not_idle() {
+        // loop: lw $t1, 0($t1); nop; beq $zero, $zero, loop; nop
+        let code = instructions(&[0x8D29_0000, 0, 0x1000_FFFD, 0]);
+        assert!(analyze(LOOP, &code).is_none());
+    }
+
+    #[test]
+    fn loops_are_idle_after_a_whole_iteration() {
+        let mut emulator = emulator(true);
+
+        // the setup and one iteration, which starts tracking the loop
+        emulator.step_instructions(6);
+        assert_eq!(emulator.cpu.instr_delay_slot().1, LOOP);
+        assert_eq!(emulator.cpu.idle_loop_iteration(&emulator.psx), None);
+
+        // a second one, which completes a whole iteration
+        emulator.step_instructions(5);
+        assert_eq!(emulator.cpu.instr_delay_slot().1, LOOP);
+        assert!(emulator.cpu.idle_loop_iteration(&emulator.psx).is_some());
+    }
+
+    #[test]
+    fn loops_are_not_idle_while_observed() {
+        type Observe = fn(&mut Emulator, bool);
+        let observers: [(&str, Observe); 3] = [
+            ("profiler", |emulator, enabled| {
+                emulator.set_profiling(enabled)
+            }),
+            ("heatmap", |emulator, enabled| emulator.set_heatmap(enabled)),
+            ("flight recorder", |emulator, enabled| {
+                emulator.psx_mut().flight_recorder.set_enabled(enabled);
+            }),
+        ];
+
+        for (name, observe) in observers {
+            let mut emulator = emulator(true);
+            observe(&mut emulator, true);
+            emulator.step_instructions(11);
+            assert_eq!(
+                emulator.cpu.idle_loop_iteration(&emulator.psx),
+                None,
+                "{name}"
+            );
+
+            observe(&mut emulator, false);
+            assert!(
+                emulator.cpu.idle_loop_iteration(&emulator.psx).is_some(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn skipping_idle_loops_does_not_change_emulation() {
+        let mut skipping = emulator(true);
+        let mut executing = emulator(false);
+        skipping.cycle_for(CYCLES_SECOND / 10);
+        executing.cycle_for(CYCLES_SECOND / 10);
+
+        assert_eq!(skipping.elapsed_cycles(), executing.elapsed_cycles());
+        assert_eq!(
+            skipping.cpu.instr_delay_slot().1,
+            executing.cpu.instr_delay_slot().1
+        );
+
+        for &reg in Reg::VARIANTS {
+            let skipped = skipping.psx().cpu.regs.read(reg);
+            let executed = executing.psx().cpu.regs.read(reg);
+            assert_eq!(skipped, executed, "{reg:?}");
+        }
+
+        // about 6 frames were counted
+        assert!((5..=6).contains(&skipping.psx().cpu.regs.read(Reg::R11)));
+        assert_eq!(
+            skipping.psx().interrupts.status.to_bits(),
+            executing.psx().interrupts.status.to_bits()
+        );
+    }
+}
@@ -1,8 +1,22 @@
-use super::{DEFAULT_DELAY, Interpreter};
+use super::{DEFAULT_DELAY, Interpreter, JumpAlignment};
 use crate::{PSX, cpu::Reg};
 use shimmer_core::cpu::instr::{BZKind, Instruction};
 
 impl Interpreter {
+    /// Sets how jumps to a register handle targets which aren't word aligned.
+    pub fn set_jump_alignment(&mut self, alignment: JumpAlignment) {
+        self.jump_alignment = alignment;
+    }
+
+    /// Returns the address a jump to the register value `target` actually jumps to.
+    #[inline(always)]
+    fn register_jump_target(&self, target: u32) -> u32 {
+        match self.jump_alignment {
+            JumpAlignment::Exception => target,
+            JumpAlignment::Mask => target & !0b11,
+        }
+    }
+
     /// `pc = (pc & (0b1111 << 28)) | (imm26 << 2)`
     pub fn jmp(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
         let high = self.instr_delay_slot.1.value() & (0b1111 << 28);
@@ -52,7 +66,7 @@ impl Interpreter {
     /// `pc = rs`
     pub fn jr(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
         let rs = psx.cpu.regs.read(instr.rs());
-        psx.cpu.regs.write_pc(rs);
+        psx.cpu.regs.write_pc(self.register_jump_target(rs));
 
         DEFAULT_DELAY
     }
@@ -75,7 +89,7 @@ impl Interpreter {
         psx.cpu.regs.write(instr.rd(), psx.cpu.regs.read_pc());
         self.cancel_load(instr.rd());
 
-        psx.cpu.regs.write_pc(rs);
+        psx.cpu.regs.write_pc(self.register_jump_target(rs));
 
         DEFAULT_DELAY
     }
@@ -131,3 +145,61 @@ impl Interpreter {
         DEFAULT_DELAY
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Emulator, EmulatorBuilder, cpu::JumpAlignment, testing};
+    use shimmer_core::{
+        cpu::{
+            Reg,
+            cop0::{self, Exception},
+        },
+        mem::Address,
+    };
+
+    const PROGRAM: Address = Address(0x8000_2000);
+    const TARGET: u32 = PROGRAM.value() + 0x101;
+
+    /// Runs `jump` to `$t0`, set to [`TARGET`], its delay slot and the fetch of the target.
+    fn jump_to_target(builder: EmulatorBuilder, jump: u32) -> Emulator {
+        let mut emulator = testing::emulator(builder);
+        emulator.load_raw(PROGRAM, &[jump, 0u32].map(u32::to_le_bytes).concat());
+        emulator.set_pc(PROGRAM);
+        emulator.psx.cpu.regs.write(Reg::T0, TARGET);
+        emulator.step_instructions(3);
+        emulator
+    }
+
+    #[test]
+    fn misaligned_jumps_raise_an_address_error_on_fetch() {
+        // jr $t0; jalr $ra, $t0
+        for jump in [0x0100_0008, 0x0100_F809] {
+            let emulator = jump_to_target(testing::builder(), jump);
+
+            let regs = &emulator.psx.cop0.regs;
+            assert_eq!(regs.cause().exception(), Some(Exception::AddressErrorLoad));
+            assert_eq!(regs.read(cop0::Reg::COP0_EPC), TARGET);
+            assert_eq!(regs.read(cop0::Reg::COP0_BAD_VADDR), TARGET);
+        }
+    }
+
+    #[test]
+    fn misaligned_jalr_still_links() {
+        // jalr $ra, $t0
+        let emulator = jump_to_target(testing::builder(), 0x0100_F809);
+        assert_eq!(emulator.psx.cpu.regs.read(Reg::RA), PROGRAM.value() + 8);
+    }
+
+    #[test]
+    fn masked_jumps_land_on_the_word_containing_the_target() {
+        let builder = testing::builder().jump_alignment(JumpAlignment::Mask);
+        // jr $t0
+        let emulator = jump_to_target(builder, 0x0100_0008);
+
+        assert_eq!(emulator.cpu.current_addr(), Address(TARGET & !0b11));
+        assert_ne!(
+            emulator.psx.cop0.regs.cause().exception(),
+            Some(Exception::AddressErrorLoad)
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Decoding of kernel function calls, which turns their raw arguments into typed values using the
+//! metadata in [`kernel::Function::args`].
+
+use crate::PSX;
+use shimmer_core::{cpu::Reg, kernel, mem::Address};
+use std::fmt;
+
+/// Maximum amount of bytes read from guest memory when decoding a string argument.
+const KERNEL_CSTR_MAX_LEN: u32 = 64;
+
+/// A decoded argument of a kernel function call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KernelArg {
+    Int(i32),
+    Hex(u32),
+    Pointer(u32),
+    /// A pointer to a null terminated string, along with the string if it's readable. Strings
+    /// longer than 64 bytes are cut short.
+    Str {
+        addr: u32,
+        value: Option<String>,
+        truncated: bool,
+    },
+    Char(u32),
+    Fd(i32),
+    EventClass(u32),
+    EventSpec(u32),
+}
+
+impl KernelArg {
+    /// Decodes a raw argument value according to its kind, reading strings from guest memory.
+    pub fn decode(psx: &PSX, kind: kernel::ArgKind, value: u32) -> Self {
+        match kind {
+            kernel::ArgKind::Int => Self::Int(value as i32),
+            kernel::ArgKind::Hex => Self::Hex(value),
+            kernel::ArgKind::Pointer => Self::Pointer(value),
+            kernel::ArgKind::CStr => {
+                let string = peek_cstr(psx, value);
+                Self::Str {
+                    addr: value,
+                    truncated: string.as_ref().is_some_and(|(_, truncated)| *truncated),
+                    value: string.map(|(string, _)| string),
+                }
+            }
+            kernel::ArgKind::Char => Self::Char(value),
+            kernel::ArgKind::Fd => Self::Fd(value as i32),
+            kernel::ArgKind::EventClass => Self::EventClass(value),
+            kernel::ArgKind::EventSpec => Self::EventSpec(value),
+        }
+    }
+}
+
+impl fmt::Display for KernelArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Int(value) | Self::Fd(value) => write!(f, "{value}"),
+            Self::Hex(value) => write!(f, "0x{value:X}"),
+            Self::Pointer(0) => write!(f, "NULL"),
+            Self::Pointer(value) => write!(f, "0x{value:08X}"),
+            Self::Str {
+                value: Some(ref string),
+                truncated,
+                ..
+            } => {
+                write!(f, "{string:?}")?;
+                if truncated {
+                    write!(f, "...")?;
+                }
+
+                Ok(())
+            }
+            Self::Str { addr: 0, .. } => write!(f, "NULL"),
+            Self::Str { addr, .. } => write!(f, "0x{addr:08X} (invalid)"),
+            Self::Char(value) => match char::from_u32(value).filter(|c| !c.is_control()) {
+                Some(char) => write!(f, "{char:?}"),
+                None => write!(f, "0x{value:02X}"),
+            },
+            Self::EventClass(value) => match kernel::event_class_name(value) {
+                Some(name) => f.write_str(name),
+                None => write!(f, "0x{value:08X}"),
+            },
+            Self::EventSpec(value) => match kernel::event_spec_name(value) {
+                Some(name) => f.write_str(name),
+                None => write!(f, "0x{value:04X}"),
+            },
+        }
+    }
+}
+
+/// A call to a kernel function, with its arguments decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelCall {
+    /// Address the function was called from.
+    pub caller: Address,
+    pub function: kernel::Function,
+    pub args: Vec<KernelArg>,
+}
+
+impl KernelCall {
+    /// Decodes a call to `function` from the current state of the CPU registers and the stack.
+    pub fn decode(psx: &PSX, function: kernel::Function) -> Self {
+        let args = function
+            .args()
+            .iter()
+            .enumerate()
+            .map(|(index, &kind)| KernelArg::decode(psx, kind, kernel_arg(psx, index)))
+            .collect();
+
+        Self {
+            caller: Address(psx.cpu.regs.read(Reg::RA)),
+            function,
+            args,
+        }
+    }
+}
+
+impl fmt::Display for KernelCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}(", self.function)?;
+        for (index, arg) in self.args.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{arg}")?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+/// Returns the value of the `index`-th argument of a kernel function call.
+fn kernel_arg(psx: &PSX, index: usize) -> u32 {
+    match index {
+        0 => psx.cpu.regs.read(Reg::A0),
+        1 => psx.cpu.regs.read(Reg::A1),
+        2 => psx.cpu.regs.read(Reg::A2),
+        3 => psx.cpu.regs.read(Reg::A3),
+        _ => {
+            let sp = psx.cpu.regs.read(Reg::SP);
+            let addr = Address(sp.wrapping_add(4 * index as u32));
+            psx.peek::<u32>(addr).unwrap_or_default()
+        }
+    }
+}
+
+/// Reads a null terminated string from guest memory, reading at most [`KERNEL_CSTR_MAX_LEN`]
+/// bytes. Returns the string and whether it was cut short.
+fn peek_cstr(psx: &PSX, addr: u32) -> Option<(String, bool)> {
+    let mut bytes = Vec::new();
+    for offset in 0..KERNEL_CSTR_MAX_LEN {
+        let byte = psx.peek::<u8>(Address(addr.wrapping_add(offset)))?;
+        if byte == 0 {
+            return Some((String::from_utf8_lossy(&bytes).into_owned(), false));
+        }
+
+        bytes.push(byte);
+    }
+
+    Some((String::from_utf8_lossy(&bytes).into_owned(), true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    const STRING: Address = Address(0x8000_2000);
+
+    fn psx_with_string(string: &[u8]) -> PSX {
+        let mut psx = testing::psx();
+        for (offset, &byte) in string.iter().enumerate() {
+            psx.write::<u8, true>(Address(STRING.value() + offset as u32), byte)
+                .unwrap();
+        }
+
+        psx
+    }
+
+    #[test]
+    fn open_is_decoded_with_its_path() {
+        const PATH: &str = "cdrom:PSX.EXE;1";
+
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.load_raw(STRING, format!("{PATH}\0").as_bytes());
+
+        // B(32h) open(STRING, 1)
+        let regs = &mut emulator.psx.cpu.regs;
+        regs.write(Reg::A0, STRING.value());
+        regs.write(Reg::A1, 1);
+        regs.write(Reg::T1, 0x32);
+        emulator.set_pc(Address(0xB0));
+        emulator.step_instructions(1);
+
+        let call = emulator.cpu.recent_kernel_calls().last().unwrap();
+        assert_eq!(call.function, kernel::Function::Open);
+        assert_eq!(
+            call.args,
+            [
+                KernelArg::Str {
+                    addr: STRING.value(),
+                    value: Some(PATH.to_owned()),
+                    truncated: false,
+                },
+                KernelArg::Hex(1),
+            ]
+        );
+        assert_eq!(call.to_string(), format!("Open({PATH:?}, 0x1)"));
+    }
+
+    #[test]
+    fn long_strings_are_cut_short() {
+        let psx = psx_with_string(&[b'a'; 2 * KERNEL_CSTR_MAX_LEN as usize]);
+
+        let arg = KernelArg::decode(&psx, kernel::ArgKind::CStr, STRING.value());
+        let expected = "a".repeat(KERNEL_CSTR_MAX_LEN as usize);
+        assert_eq!(
+            arg,
+            KernelArg::Str {
+                addr: STRING.value(),
+                value: Some(expected.clone()),
+                truncated: true,
+            }
+        );
+        assert_eq!(arg.to_string(), format!("{expected:?}..."));
+    }
+
+    #[test]
+    fn unreadable_and_null_arguments_are_shown_as_such() {
+        let psx = testing::psx();
+
+        let decode = |kind, value| KernelArg::decode(&psx, kind, value).to_string();
+        assert_eq!(decode(kernel::ArgKind::CStr, 0), "NULL");
+        assert_eq!(
+            decode(kernel::ArgKind::CStr, 0x1F80_1000),
+            "0x1F801000 (invalid)"
+        );
+        assert_eq!(decode(kernel::ArgKind::Pointer, 0), "NULL");
+        assert_eq!(decode(kernel::ArgKind::Char, u32::from(b'\n')), "0x0A");
+        assert_eq!(decode(kernel::ArgKind::Fd, u32::MAX), "-1");
+    }
+}
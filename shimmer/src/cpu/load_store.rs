@@ -16,6 +16,10 @@ impl Interpreter {
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
 
+        if self.check_data_breakpoint(psx, addr, true) {
+            return MEMORY_OP_DELAY;
+        }
+
         if psx.write::<u32, false>(addr, rt).is_err() {
             self.trigger_exception(psx, Exception::AddressErrorStore);
         }
@@ -28,6 +32,10 @@ impl Interpreter {
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
 
+        if self.check_data_breakpoint(psx, addr, false) {
+            return MEMORY_OP_DELAY;
+        }
+
         if let Ok(value) = psx.read::<u32, false>(addr) {
             self.cancel_load(instr.rt());
             self.load_delay_slot = Some(RegLoad {
@@ -51,6 +59,10 @@ impl Interpreter {
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
 
+        if self.check_data_breakpoint(psx, addr, true) {
+            return MEMORY_OP_DELAY;
+        }
+
         if psx.write::<u16, false>(addr, rt as u16).is_err() {
             self.trigger_exception(psx, Exception::AddressErrorStore);
         }
@@ -68,6 +80,10 @@ impl Interpreter {
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
 
+        if self.check_data_breakpoint(psx, addr, true) {
+            return MEMORY_OP_DELAY;
+        }
+
         if psx.write::<u8, false>(addr, rt as u8).is_err() {
             self.trigger_exception(psx, Exception::AddressErrorStore);
         }
@@ -80,6 +96,10 @@ impl Interpreter {
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
 
+        if self.check_data_breakpoint(psx, addr, false) {
+            return MEMORY_OP_DELAY;
+        }
+
         if let Ok(value) = psx.read::<i8, false>(addr) {
             self.cancel_load(instr.rt());
             self.load_delay_slot = Some(RegLoad {
@@ -98,6 +118,10 @@ impl Interpreter {
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
 
+        if self.check_data_breakpoint(psx, addr, false) {
+            return MEMORY_OP_DELAY;
+        }
+
         if let Ok(value) = psx.read::<u8, false>(addr) {
             self.cancel_load(instr.rt());
             self.load_delay_slot = Some(RegLoad {
@@ -116,6 +140,10 @@ impl Interpreter {
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
 
+        if self.check_data_breakpoint(psx, addr, false) {
+            return MEMORY_OP_DELAY;
+        }
+
         if let Ok(value) = psx.read::<u16, false>(addr) {
             self.cancel_load(instr.rt());
             self.load_delay_slot = Some(RegLoad {
@@ -134,6 +162,10 @@ impl Interpreter {
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
 
+        if self.check_data_breakpoint(psx, addr, false) {
+            return MEMORY_OP_DELAY;
+        }
+
         if let Ok(value) = psx.read::<i16, false>(addr) {
             self.cancel_load(instr.rt());
             self.load_delay_slot = Some(RegLoad {
@@ -177,6 +209,12 @@ impl Interpreter {
         DEFAULT_DELAY
     }
 
+    /// Loads the bytes from `rs + signed_imm16` down to the start of its word into the upper bytes
+    /// of `rt`, keeping the others. Delayed by one instruction.
+    ///
+    /// If a load to `rt` is still in its delay slot (such as the one of the `LWR` in the usual
+    /// `LWL`/`LWR` pair), its value is merged with instead of the current value of `rt`, and it is
+    /// cancelled since the result of this load already includes it.
     pub fn lwl(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
         let rs = psx.cpu.regs.read(instr.rs());
         let rt = if let Some(load) = self.pending_load
@@ -188,23 +226,28 @@ impl Interpreter {
         };
 
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
-        let len = addr.value() % 4 + 1;
 
-        let mut result = rt.to_be_bytes();
-        for (i, byte) in (0..len).zip(result.iter_mut()) {
-            let addr = addr - i;
-            *byte = psx.read_unaligned::<u8, false>(addr);
+        if self.check_data_breakpoint(psx, addr, false) {
+            return MEMORY_OP_DELAY;
         }
 
+        // the word holds bytes 0..=k of it in its upper bytes
+        let k = addr.value() % 4;
+        let word = psx.read_partial::<false>(addr, (1 << (k + 1)) - 1);
+        let result = (rt & (0x00FF_FFFF >> (8 * k))) | (word << (24 - 8 * k));
+
         self.cancel_load(instr.rt());
         self.load_delay_slot = Some(RegLoad {
             reg: instr.rt(),
-            value: u32::from_be_bytes(result),
+            value: result,
         });
 
         MEMORY_OP_DELAY
     }
 
+    /// Loads the bytes from `rs + signed_imm16` up to the end of its word into the lower bytes of
+    /// `rt`, keeping the others. Delayed by one instruction, and merges with a pending load to `rt`
+    /// in the same way as [`Interpreter::lwl`].
     pub fn lwr(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
         let rs = psx.cpu.regs.read(instr.rs());
         let rt = if let Some(load) = self.pending_load
@@ -216,54 +259,79 @@ impl Interpreter {
         };
 
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
-        let len = 4 - addr.value() % 4;
 
-        let mut result = rt.to_le_bytes();
-        for (i, byte) in (0..len).zip(result.iter_mut()) {
-            let addr = addr + i;
-            *byte = psx.read_unaligned::<u8, false>(addr);
+        if self.check_data_breakpoint(psx, addr, false) {
+            return MEMORY_OP_DELAY;
         }
 
+        // the word holds bytes k..=3 of it in its lower bytes
+        let k = addr.value() % 4;
+        let word = psx.read_partial::<false>(addr, 0xF & (0xF << k));
+        let result = (rt & !(0xFFFF_FFFF >> (8 * k))) | (word >> (8 * k));
+
         self.cancel_load(instr.rt());
         self.load_delay_slot = Some(RegLoad {
             reg: instr.rt(),
-            value: u32::from_le_bytes(result),
+            value: result,
         });
 
         MEMORY_OP_DELAY
     }
 
+    /// Stores the upper bytes of `rt` from `rs + signed_imm16` down to the start of its word.
     pub fn swl(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
+        if psx.cop0.regs.system_status().isolate_cache() {
+            return DEFAULT_DELAY;
+        }
+
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
-        let len = addr.value() % 4 + 1;
 
-        let value = psx.cpu.regs.read(instr.rt()).to_be_bytes();
-        for (i, byte) in (0..len).zip(value.iter()) {
-            let addr = addr - i;
-            psx.write_unaligned::<u8, false>(addr, *byte);
+        if self.check_data_breakpoint(psx, addr, true) {
+            return MEMORY_OP_DELAY;
         }
 
+        // bytes 0..=k of the word take the upper bytes of rt
+        let k = addr.value() % 4;
+        let value = psx.cpu.regs.read(instr.rt()) >> (24 - 8 * k);
+        psx.write_partial::<false>(addr, value, (1 << (k + 1)) - 1);
+
         MEMORY_OP_DELAY
     }
 
+    /// Stores the lower bytes of `rt` from `rs + signed_imm16` up to the end of its word.
     pub fn swr(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
+        if psx.cop0.regs.system_status().isolate_cache() {
+            return DEFAULT_DELAY;
+        }
+
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
-        let len = 4 - addr.value() % 4;
 
-        let value = psx.cpu.regs.read(instr.rt()).to_le_bytes();
-        for (i, byte) in (0..len).zip(value.iter()) {
-            let addr = addr + i;
-            psx.write_unaligned::<u8, false>(addr, *byte);
+        if self.check_data_breakpoint(psx, addr, true) {
+            return MEMORY_OP_DELAY;
         }
 
+        // bytes k..=3 of the word take the lower bytes of rt
+        let k = addr.value() % 4;
+        let value = psx.cpu.regs.read(instr.rt()) << (8 * k);
+        psx.write_partial::<false>(addr, value, 0xF & (0xF << k));
+
         MEMORY_OP_DELAY
     }
 
     pub fn swc(&mut self, psx: &mut PSX, instr: Instruction) -> u64 {
+        if psx.cop0.regs.system_status().isolate_cache() {
+            return DEFAULT_DELAY;
+        }
+
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
+
+        if self.check_data_breakpoint(psx, addr, true) {
+            return MEMORY_OP_DELAY;
+        }
+
         let system_status = psx.cop0.regs.system_status();
 
         match instr.cop() {
@@ -291,6 +359,10 @@ impl Interpreter {
         let rs = psx.cpu.regs.read(instr.rs());
         let addr = Address(rs.wrapping_add_signed(i32::from(instr.signed_imm16())));
 
+        if self.check_data_breakpoint(psx, addr, false) {
+            return MEMORY_OP_DELAY;
+        }
+
         if let Ok(value) = psx.read::<_, true>(addr) {
             let system_status = psx.cop0.regs.system_status();
             match instr.cop() {
@@ -311,3 +383,233 @@ impl Interpreter {
         MEMORY_OP_DELAY
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Emulator, testing};
+    use shimmer_core::{
+        cpu::Reg,
+        mem::{Address, io},
+    };
+
+    const PROGRAM: Address = Address(0x8000_2000);
+    const DATA: Address = Address(0x8000_3000);
+    /// Byte `i` of the data is `0x10 + i`.
+    const DATA_BYTES: [u8; 8] = [0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17];
+
+    // lui $t1, 0x8000; ori $t1, $t1, 0x3000 + offset
+    fn data_addr(offset: u32) -> [u32; 2] {
+        [0x3C09_8000, 0x3529_3000 + offset]
+    }
+
+    /// Runs `program`, placed at [`PROGRAM`], for `steps` instructions with [`DATA_BYTES`] at
+    /// [`DATA`].
+    fn run(program: &[u32], steps: u64) -> Emulator {
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.load_raw(DATA, &DATA_BYTES);
+        let program: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        emulator.load_raw(PROGRAM, &program);
+        emulator.set_pc(PROGRAM);
+        emulator.step_instructions(steps);
+        emulator
+    }
+
+    #[test]
+    fn lwr_then_lwl_loads_unaligned_words() {
+        for offset in 0..4 {
+            let mut program = data_addr(offset).to_vec();
+            program.extend([
+                0x2408_FFFF, // addiu $t0, $zero, -1
+                0x9928_0000, // lwr $t0, 0($t1)
+                0x8928_0003, // lwl $t0, 3($t1)
+                0x0100_5821, // addu $t3, $t0, $zero
+                0x0100_6021, // addu $t4, $t0, $zero
+            ]);
+
+            let mut emulator = run(&program, 7);
+            let regs = &emulator.psx().cpu.regs;
+
+            // in the delay slot of LWL, only LWR has reached the register
+            let mut partial = [0xFF; 4];
+            partial[..4 - offset as usize].copy_from_slice(&DATA_BYTES[offset as usize..4]);
+            assert_eq!(
+                regs.read(Reg::T3),
+                u32::from_le_bytes(partial),
+                "offset {offset}"
+            );
+
+            let offset = offset as usize;
+            let expected = u32::from_le_bytes(DATA_BYTES[offset..offset + 4].try_into().unwrap());
+            assert_eq!(regs.read(Reg::T4), expected, "offset {offset}");
+            assert_eq!(regs.read(Reg::T0), expected, "offset {offset}");
+        }
+    }
+
+    #[test]
+    fn lwl_then_lwr_loads_unaligned_words() {
+        for offset in 0..4 {
+            let mut program = data_addr(offset).to_vec();
+            program.extend([
+                0x8928_0003, // lwl $t0, 3($t1)
+                0x9928_0000, // lwr $t0, 0($t1)
+                0,
+            ]);
+
+            let mut emulator = run(&program, 5);
+            let offset = offset as usize;
+            let expected = u32::from_le_bytes(DATA_BYTES[offset..offset + 4].try_into().unwrap());
+            assert_eq!(
+                emulator.psx().cpu.regs.read(Reg::T0),
+                expected,
+                "offset {offset}"
+            );
+        }
+    }
+
+    #[test]
+    fn unaligned_loads_merge_with_a_pending_load() {
+        let mut program = data_addr(0).to_vec();
+        program.extend([
+            0x8D28_0004, // lw $t0, 4($t1)
+            0x9928_0001, // lwr $t0, 1($t1)
+            0,
+        ]);
+
+        let mut emulator = run(&program, 5);
+        // the upper byte comes from the LW still in its delay slot, not from the stale register
+        assert_eq!(emulator.psx().cpu.regs.read(Reg::T0), 0x1713_1211);
+    }
+
+    #[test]
+    fn unaligned_loads_keep_the_bytes_they_dont_load() {
+        for offset in 0..4 {
+            let mut program = data_addr(offset).to_vec();
+            program.extend([
+                0x2408_FFFF, // addiu $t0, $zero, -1
+                0x8928_0000, // lwl $t0, 0($t1)
+                0x2409_FFFF, // addiu $t1, $zero, -1
+                0x0100_5821, // addu $t3, $t0, $zero
+            ]);
+
+            let mut emulator = run(&program, 6);
+
+            // LWL loads bytes from the start of the word up to the address into the upper bytes
+            let offset = offset as usize;
+            let mut expected = [0xFF; 4];
+            expected[3 - offset..].copy_from_slice(&DATA_BYTES[..=offset]);
+            let expected = u32::from_le_bytes(expected);
+            assert_eq!(
+                emulator.psx().cpu.regs.read(Reg::T3),
+                expected,
+                "offset {offset}"
+            );
+        }
+    }
+
+    #[test]
+    fn unaligned_loads_from_gpuread_pop_one_word_each() {
+        const BLIT: [u32; 4] = [0xAAAA_1111, 0xBBBB_2222, 0xCCCC_3333, 0xDDDD_4444];
+
+        let program = [
+            0x3C08_1F80, // lui $t0, 0x1F80
+            0x9909_1812, // lwr $t1, 0x1812($t0)
+            0x8909_1815, // lwl $t1, 0x1815($t0)
+            0x890A_1811, // lwl $t2, 0x1811($t0)
+            0x990B_1810, // lwr $t3, 0x1810($t0)
+            0x890B_1813, // lwl $t3, 0x1813($t0)
+            0,
+            0,
+        ];
+
+        let mut emulator = testing::emulator(testing::builder());
+        let program: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        emulator.load_raw(PROGRAM, &program);
+        emulator.psx_mut().gpu.response_queue.start_blit(BLIT);
+        emulator.set_pc(PROGRAM);
+        emulator.step_instructions(8);
+
+        let psx = emulator.psx();
+        let regs = &psx.cpu.regs;
+
+        // LWR reads the upper half of GPUREAD, popping a word, and LWL the lower half of GPUSTAT
+        assert_eq!(regs.read(Reg::T1) & 0xFFFF, 0xAAAA);
+        // LWL reads the lower half of GPUREAD, popping another word
+        assert_eq!(regs.read(Reg::T2), 0x2222_0000);
+        // while an aligned pair reads GPUREAD twice, as on hardware
+        assert_eq!(regs.read(Reg::T3), 0xDDDD_4444);
+        assert!(psx.gpu.response_queue.is_empty());
+    }
+
+    #[test]
+    fn three_byte_stores_to_io_merge_with_the_register() {
+        let program = [
+            0x3C08_1F80, // lui $t0, 0x1F80
+            0x3C09_AABB, // lui $t1, 0xAABB
+            0x3529_CCDD, // ori $t1, $t1, 0xCCDD
+            0xA909_10F2, // swl $t1, 0x10F2($t0)
+            0xB909_10F1, // swr $t1, 0x10F1($t0)
+        ];
+
+        let mut emulator = testing::emulator(testing::builder());
+        testing::write::<u32>(emulator.psx_mut(), io::Reg::DmaControl, 0x0765_4321);
+        let program: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+        emulator.load_raw(PROGRAM, &program);
+        emulator.set_pc(PROGRAM);
+
+        // SWL replaces the lower three bytes, keeping the upper one
+        emulator.step_instructions(4);
+        assert_eq!(
+            testing::read::<u32>(emulator.psx_mut(), io::Reg::DmaControl),
+            0x07AA_BBCC
+        );
+
+        // SWR replaces the upper three bytes, keeping the lower one
+        emulator.step_instructions(1);
+        assert_eq!(
+            testing::read::<u32>(emulator.psx_mut(), io::Reg::DmaControl),
+            0xBBCC_DDCC
+        );
+    }
+
+    #[test]
+    fn stores_with_the_cache_isolated_dont_reach_ram() {
+        let program = [
+            // lui $t2, 0x8000; ori $t2, $t2, 0x3000
+            0x3C0A_8000,
+            0x354A_3000,
+            // lui $t1, 0xDEAD; ori $t1, $t1, 0xBEEF
+            0x3C09_DEAD,
+            0x3529_BEEF,
+            // lui $t0, 0x1091; mtc0 $t0, $sr; nop - isolate the cache
+            0x3C08_1091,
+            0x4088_6000,
+            0x0000_0000,
+            // sw, sh, sb, swl and swr of $t1 to $t2
+            0xAD49_0000,
+            0xA549_0004,
+            0xA149_0008,
+            0xA949_000F,
+            0xB949_0010,
+            // lui $t0, 0x1090; mtc0 $t0, $sr; nop - stop isolating it
+            0x3C08_1090,
+            0x4088_6000,
+            0x0000_0000,
+            // sw $t1, 0x20($t2); nop
+            0xAD49_0020,
+            0x0000_0000,
+        ];
+
+        let emulator = run(&program, program.len() as u64);
+
+        let mut expected = [0; 0x14];
+        expected[..DATA_BYTES.len()].copy_from_slice(&DATA_BYTES);
+        let ram = (0..0x14)
+            .map(|offset| emulator.psx.peek::<u8>(Address(DATA.value() + offset)))
+            .collect::<Option<Vec<_>>>()
+            .unwrap();
+        assert_eq!(ram, expected);
+
+        let stored = emulator.psx.peek::<u32>(Address(DATA.value() + 0x20));
+        assert_eq!(stored, Some(0xDEAD_BEEF));
+    }
+}
@@ -0,0 +1,282 @@
+//! An instruction-level profiler, which counts how many times each CPU opcode and GTE command is
+//! executed in order to find the instructions worth optimizing, and an execution heatmap, which
+//! counts how many instructions are executed in each region of memory in order to find where a
+//! program spends its time.
+
+use super::Interpreter;
+use shimmer_core::{
+    cpu::instr::{Instruction, Opcode, SpecialOpcode},
+    gte::instr::{Instruction as GteInstruction, Opcode as GteOpcode},
+    mem::Address,
+};
+use std::collections::BTreeMap;
+
+/// Length of the regions of memory whose executed instructions are counted together by the
+/// [`Heatmap`], in bytes.
+pub const HEATMAP_BUCKET_LEN: u32 = 256;
+
+/// Execution counts of the CPU opcodes and GTE commands, indexed by their code.
+///
+/// Instructions which trigger an exception instead of executing aren't counted, and neither are
+/// the iterations of idle loops skipped by [`Config::skip_idle_loops`](crate::Config).
+#[derive(Debug, Clone)]
+pub struct Profile {
+    opcodes: [u64; 64],
+    special_opcodes: [u64; 64],
+    gte_commands: [u64; 64],
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            opcodes: [0; 64],
+            special_opcodes: [0; 64],
+            gte_commands: [0; 64],
+        }
+    }
+}
+
+impl Profile {
+    #[inline(always)]
+    fn record(&mut self, instr: Instruction, op: Option<Opcode>) {
+        let bits = instr.to_bits();
+        let code = (bits & 0x3F) as usize;
+
+        self.opcodes[(bits >> 26) as usize] += 1;
+        match op {
+            Some(Opcode::SPECIAL) => self.special_opcodes[code] += 1,
+            Some(Opcode::COP2) if instr.cop_cmd() => self.gte_commands[code] += 1,
+            _ => (),
+        }
+    }
+
+    /// How many times instructions with the primary opcode `op` were executed. For
+    /// [`Opcode::COP2`], this includes GTE commands.
+    pub fn opcode(&self, op: Opcode) -> u64 {
+        self.opcodes[op as usize]
+    }
+
+    /// How many times instructions with the special opcode `op` were executed.
+    pub fn special_opcode(&self, op: SpecialOpcode) -> u64 {
+        self.special_opcodes[op as usize]
+    }
+
+    /// How many times the GTE command `op` was executed.
+    pub fn gte_command(&self, op: GteOpcode) -> u64 {
+        self.gte_commands[op as usize]
+    }
+
+    /// Total amount of instructions executed.
+    pub fn total(&self) -> u64 {
+        self.opcodes.iter().sum()
+    }
+
+    /// Returns the name and execution count of every instruction executed at least once, most
+    /// executed first. Special opcodes are listed instead of [`Opcode::SPECIAL`], and GTE
+    /// commands are listed on their own, prefixed with `GTE `.
+    pub fn hottest(&self) -> Vec<(String, u64)> {
+        let mut entries = Vec::new();
+        for code in 0..64u32 {
+            if let Some(op) = Instruction::from_bits(code << 26).op()
+                && op != Opcode::SPECIAL
+            {
+                entries.push((<&str>::from(op).to_owned(), self.opcodes[code as usize]));
+            }
+
+            if let Some(op) = Instruction::from_bits(code).special_op() {
+                entries.push((
+                    <&str>::from(op).to_owned(),
+                    self.special_opcodes[code as usize],
+                ));
+            }
+
+            if let Some(op) = GteInstruction::from_bits(code).op() {
+                entries.push((format!("GTE {op:?}"), self.gte_commands[code as usize]));
+            }
+        }
+
+        entries.retain(|(_, count)| *count > 0);
+        entries.sort_by(|a, b| b.1.cmp(&a.1));
+        entries
+    }
+}
+
+/// Amount of instructions executed in each region of [`HEATMAP_BUCKET_LEN`] bytes, keyed by the
+/// virtual address of the region, so that mirrors are counted separately.
+///
+/// Like the [`Profile`], it doesn't count instructions which trigger an exception, nor skipped
+/// idle loops.
+#[derive(Debug, Clone, Default)]
+pub struct Heatmap {
+    buckets: BTreeMap<u32, u64>,
+}
+
+impl Heatmap {
+    #[inline(always)]
+    fn record(&mut self, addr: Address) {
+        *self
+            .buckets
+            .entry(addr.value() / HEATMAP_BUCKET_LEN)
+            .or_default() += 1;
+    }
+
+    /// How many instructions were executed in the region containing `addr`.
+    pub fn count(&self, addr: Address) -> u64 {
+        self.buckets
+            .get(&(addr.value() / HEATMAP_BUCKET_LEN))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the start address and the instruction count of every region in which an
+    /// instruction was executed, hottest first.
+    pub fn hottest(&self) -> Vec<(Address, u64)> {
+        let mut entries = self
+            .buckets
+            .iter()
+            .map(|(&bucket, &count)| (Address(bucket * HEATMAP_BUCKET_LEN), count))
+            .collect::<Vec<_>>();
+
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        entries
+    }
+}
+
+impl Interpreter {
+    /// Enables or disables the instruction profiler. Enabling it starts counting from zero. While
+    /// disabled, it costs a single check per instruction.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(Box::default);
+    }
+
+    /// Returns the execution counts gathered by the instruction profiler, if it's enabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    /// Enables or disables the execution heatmap. Enabling it starts counting from zero.
+    pub fn set_heatmap(&mut self, enabled: bool) {
+        self.heatmap = enabled.then(Box::default);
+    }
+
+    /// Returns the execution heatmap, if it's enabled.
+    pub fn heatmap(&self) -> Option<&Heatmap> {
+        self.heatmap.as_deref()
+    }
+
+    /// Counts the execution of `instr` at `addr`, if profiling or the heatmap are enabled.
+    #[inline(always)]
+    pub(super) fn profile_instr(&mut self, instr: Instruction, op: Option<Opcode>, addr: Address) {
+        if let Some(profile) = &mut self.profile {
+            profile.record(instr, op);
+        }
+
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record(addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use shimmer_core::cpu::Reg;
+
+    const PROGRAM: Address = Address(0x8000_2000);
+    const ITERATIONS: u32 = 1_000;
+
+    #[test]
+    fn profiler_counts_every_instruction_of_a_loop() {
+        // loop: addiu $t0, $t0, -1; nclip; bne $t0, $zero, loop; nop
+        let busy_loop = [0x2508_FFFF, 0x4A00_0006, 0x1500_FFFD, 0x0000_0000u32];
+
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.load_raw(PROGRAM, &busy_loop.map(u32::to_le_bytes).concat());
+        emulator.set_pc(PROGRAM);
+        emulator.psx.cpu.regs.write(Reg::T0, ITERATIONS);
+
+        emulator.set_profiling(true);
+        emulator.step_instructions(4 * u64::from(ITERATIONS));
+
+        let profile = emulator.profile().unwrap();
+        let iterations = u64::from(ITERATIONS);
+        assert_eq!(profile.opcode(Opcode::ADDIU), iterations);
+        assert_eq!(profile.opcode(Opcode::BNE), iterations);
+        assert_eq!(profile.opcode(Opcode::COP2), iterations);
+        assert_eq!(profile.special_opcode(SpecialOpcode::SLL), iterations);
+        assert_eq!(profile.gte_command(GteOpcode::NCLIP), iterations);
+        assert_eq!(profile.total(), 4 * iterations);
+    }
+
+    #[test]
+    fn hottest_lists_special_opcodes_and_gte_commands_on_their_own() {
+        let mut profile = Profile::default();
+        // addiu $t0, $t0, -1; nclip; nop
+        for (bits, times) in [(0x2508_FFFF, 1), (0x4A00_0006, 2), (0x0000_0000, 3)] {
+            let instr = Instruction::from_bits(bits);
+            for _ in 0..times {
+                profile.record(instr, instr.op());
+            }
+        }
+
+        let hottest = profile.hottest();
+        let names = hottest
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [("SLL", 3), ("GTE NCLIP", 2), ("COP2", 2), ("ADDIU", 1)]
+        );
+    }
+
+    #[test]
+    fn disabling_the_profiler_drops_the_counts() {
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.set_profiling(true);
+        emulator.step_instructions(10);
+        assert_eq!(emulator.profile().unwrap().total(), 10);
+
+        emulator.set_profiling(false);
+        assert!(emulator.profile().is_none());
+
+        emulator.set_profiling(true);
+        assert_eq!(emulator.profile().unwrap().total(), 0);
+    }
+
+    #[test]
+    fn heatmap_counts_a_loop_in_its_region() {
+        // loop: addiu $t0, $t0, -1; bne $t0, $zero, loop; nop
+        let busy_loop = [0x2508_FFFF, 0x1500_FFFE, 0x0000_0000u32];
+
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.load_raw(PROGRAM, &busy_loop.map(u32::to_le_bytes).concat());
+        emulator.set_pc(PROGRAM);
+        emulator.psx.cpu.regs.write(Reg::T0, ITERATIONS);
+
+        emulator.set_heatmap(true);
+        let executed = 3 * u64::from(ITERATIONS);
+        emulator.step_instructions(executed);
+
+        let heatmap = emulator.heatmap().unwrap();
+        assert_eq!(heatmap.hottest(), [(PROGRAM, executed)]);
+        assert_eq!(heatmap.count(Address(PROGRAM.value() + 8)), executed);
+    }
+
+    #[test]
+    fn heatmap_keeps_mirrors_apart_and_breaks_ties_by_address() {
+        let mut heatmap = Heatmap::default();
+        for (addr, times) in [(0xA000_0100, 2), (0x8000_0100, 2), (0x8000_01FC, 1)] {
+            for _ in 0..times {
+                heatmap.record(Address(addr));
+            }
+        }
+
+        assert_eq!(
+            heatmap.hottest(),
+            [(Address(0x8000_0100), 3), (Address(0xA000_0100), 2)]
+        );
+        assert_eq!(heatmap.count(Address(0x0000_0100)), 0);
+    }
+}
@@ -0,0 +1,416 @@
+//! Redirection of the kernel's CD-ROM file functions to a host directory.
+
+use crate::PSX;
+use shimmer_core::{cpu::Reg, kernel, mem::Address};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+use tinylog::{info, warn};
+
+/// Size of a CD-ROM sector as seen by the kernel file functions.
+const SECTOR_LEN: u32 = 2048;
+/// Maximum length of a path, in bytes.
+const PATH_MAX_LEN: u32 = 128;
+/// File descriptors handed out by the redirection start here, so that they never collide with
+/// the ones of the kernel (which go from 0 to 15).
+const FD_BASE: u32 = 0x100;
+
+/// Error codes of the kernel, as returned by `GetLastError` and `GetLastFileError`.
+mod errno {
+    pub const ENOENT: u32 = 0x02;
+    pub const EBADF: u32 = 0x09;
+    pub const EINVAL: u32 = 0x16;
+}
+
+/// Return value of kernel file functions on failure (-1).
+const FAILURE: u32 = u32::MAX;
+
+#[derive(Debug)]
+struct HostFile {
+    file: File,
+    len: u32,
+    position: u32,
+    error: u32,
+}
+
+/// Services the kernel file functions for `cdrom:` paths from a host directory.
+#[derive(Debug)]
+pub struct FileRedirect {
+    root: PathBuf,
+    files: Vec<Option<HostFile>>,
+    /// Error of the last redirected call, if the last file call was redirected.
+    last_error: Option<u32>,
+}
+
+/// Reads a null terminated string from guest memory.
+fn read_cstr(psx: &PSX, addr: u32) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for offset in 0..PATH_MAX_LEN {
+        let byte = psx.peek::<u8>(Address(addr.wrapping_add(offset)))?;
+        if byte == 0 {
+            return Some(bytes);
+        }
+
+        bytes.push(byte);
+    }
+
+    None
+}
+
+/// Strips the `cdrom:` device prefix and the `;1` version suffix of a path, returning its
+/// components. Returns `None` if the path is not on the CD-ROM device.
+fn cdrom_path(path: &[u8]) -> Option<Vec<String>> {
+    let path = std::str::from_utf8(path).ok()?;
+    let (device, path) = path.split_once(':')?;
+    if !matches!(device.to_ascii_lowercase().as_str(), "cdrom" | "cdrom0") {
+        return None;
+    }
+
+    let path = path.rsplit_once(';').map_or(path, |(path, _)| path);
+    Some(
+        path.split(['\\', '/'])
+            .filter(|component| !component.is_empty())
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+impl FileRedirect {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            files: Vec::new(),
+            last_error: None,
+        }
+    }
+
+    /// Resolves the components of a guest path to a file inside the root directory. Components
+    /// are matched case insensitively and must name existing entries, so the resolved path can
+    /// never escape the root.
+    fn resolve(&self, components: &[String]) -> Option<PathBuf> {
+        let mut path = self.root.clone();
+        for component in components {
+            if component == "." || component == ".." {
+                return None;
+            }
+
+            let entry = std::fs::read_dir(&path).ok()?.find_map(|entry| {
+                let entry = entry.ok()?;
+                entry
+                    .file_name()
+                    .to_str()?
+                    .eq_ignore_ascii_case(component)
+                    .then(|| entry.path())
+            })?;
+
+            path = entry;
+        }
+
+        let root = self.root.canonicalize().ok()?;
+        let path = path.canonicalize().ok()?;
+        (path.starts_with(&root) && path.is_file()).then_some(path)
+    }
+
+    fn file_mut(&mut self, fd: u32) -> Option<&mut HostFile> {
+        let index = fd.checked_sub(FD_BASE)?;
+        self.files.get_mut(index as usize)?.as_mut()
+    }
+
+    fn open(&mut self, psx: &mut PSX, path: &Path) -> u32 {
+        let file = File::open(path).and_then(|file| Ok((file.metadata()?.len(), file)));
+        let Ok((len, file)) = file else {
+            warn!(
+                psx.loggers.kernel,
+                "couldn't open redirected file {}",
+                path.display()
+            );
+            self.last_error = Some(errno::ENOENT);
+            return FAILURE;
+        };
+
+        let file = HostFile {
+            file,
+            len: u32::try_from(len).unwrap_or(u32::MAX),
+            position: 0,
+            error: 0,
+        };
+
+        let index = match self.files.iter().position(Option::is_none) {
+            Some(index) => {
+                self.files[index] = Some(file);
+                index
+            }
+            None => {
+                self.files.push(Some(file));
+                self.files.len() - 1
+            }
+        };
+
+        let fd = FD_BASE + index as u32;
+        info!(psx.loggers.kernel, "opened redirected file {}", path.display(); fd = fd);
+
+        self.last_error = Some(0);
+        fd
+    }
+
+    fn seek(&mut self, fd: u32, offset: u32, whence: u32) -> u32 {
+        let Some(file) = self.file_mut(fd) else {
+            self.last_error = Some(errno::EBADF);
+            return FAILURE;
+        };
+
+        // files on disc take up whole sectors, so their end is at the end of their last sector
+        let position = match whence {
+            0 => Some(offset),
+            1 => file.position.checked_add_signed(offset as i32),
+            2 => file
+                .len
+                .next_multiple_of(SECTOR_LEN)
+                .checked_add_signed(offset as i32),
+            _ => None,
+        };
+
+        // the CD-ROM driver can only seek to sector boundaries
+        let Some(position) = position.filter(|position| position % SECTOR_LEN == 0) else {
+            file.error = errno::EINVAL;
+            self.last_error = Some(errno::EINVAL);
+            return FAILURE;
+        };
+
+        file.position = position;
+        self.last_error = Some(0);
+        position
+    }
+
+    fn read(&mut self, psx: &mut PSX, fd: u32, dst: u32, len: u32) -> u32 {
+        let Some(file) = self.file_mut(fd) else {
+            self.last_error = Some(errno::EBADF);
+            return FAILURE;
+        };
+
+        // the CD-ROM driver always transfers whole sectors, so the tail of the last sector is
+        // written to the destination even if it goes past the requested length
+        let sectors = len.div_ceil(SECTOR_LEN);
+        let mut data = vec![0; (sectors * SECTOR_LEN) as usize];
+
+        let available = file
+            .len
+            .saturating_sub(file.position)
+            .min(data.len() as u32);
+        let result = file
+            .file
+            .seek(SeekFrom::Start(u64::from(file.position)))
+            .and_then(|_| file.file.read_exact(&mut data[..available as usize]));
+
+        if result.is_err() {
+            file.error = errno::EINVAL;
+            self.last_error = Some(errno::EINVAL);
+            return FAILURE;
+        }
+
+        file.position = file.position.saturating_add(sectors * SECTOR_LEN);
+        for (offset, byte) in data.into_iter().enumerate() {
+            psx.write_unaligned::<u8, true>(Address(dst.wrapping_add(offset as u32)), byte);
+        }
+
+        self.last_error = Some(0);
+        available.min(len)
+    }
+
+    fn close(&mut self, fd: u32) -> u32 {
+        let Some(file) = fd
+            .checked_sub(FD_BASE)
+            .and_then(|index| self.files.get_mut(index as usize))
+            .filter(|file| file.is_some())
+        else {
+            self.last_error = Some(errno::EBADF);
+            return FAILURE;
+        };
+
+        *file = None;
+        self.last_error = Some(0);
+        fd
+    }
+
+    /// Services the kernel function being called at `addr`, if it should be redirected. Returns
+    /// the value the function would return.
+    pub fn intercept(&mut self, psx: &mut PSX, addr: Address) -> Option<u32> {
+        let code = psx.cpu.regs.read(Reg::T1) as u8;
+        let func = match addr.value() {
+            0xA0 => kernel::Function::a0(code)?,
+            0xB0 => kernel::Function::b0(code)?,
+            _ => return None,
+        };
+
+        let [a0, a1, a2] = [Reg::A0, Reg::A1, Reg::A2].map(|reg| psx.cpu.regs.read(reg));
+        let ours = a0
+            .checked_sub(FD_BASE)
+            .is_some_and(|index| self.files.get(index as usize).is_some_and(Option::is_some));
+
+        let result = match func {
+            kernel::Function::Open => {
+                let components = read_cstr(psx, a0).and_then(|path| cdrom_path(&path));
+                let Some(components) = components else {
+                    self.last_error = None;
+                    return None;
+                };
+
+                match self.resolve(&components) {
+                    Some(path) => self.open(psx, &path),
+                    None => {
+                        warn!(
+                            psx.loggers.kernel,
+                            "redirected file {} not found",
+                            components.join("\\")
+                        );
+                        self.last_error = Some(errno::ENOENT);
+                        FAILURE
+                    }
+                }
+            }
+            kernel::Function::Seek if ours => self.seek(a0, a1, a2),
+            kernel::Function::Read if ours => self.read(psx, a0, a1, a2),
+            kernel::Function::Close if ours => self.close(a0),
+            kernel::Function::GetLastError => self.last_error?,
+            kernel::Function::GetLastFileError if ours => self.file_mut(a0).unwrap().error,
+            kernel::Function::Seek
+            | kernel::Function::Read
+            | kernel::Function::Write
+            | kernel::Function::Close => {
+                self.last_error = None;
+                return None;
+            }
+            _ => return None,
+        };
+
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Condition, SHELL_ENTRY, StopReason, testing};
+    use shimmer_core::exe::{Executable, Header};
+    use std::ffi::CString;
+
+    /// Where the test executable is loaded.
+    const DESTINATION: Address = Address(0x8001_0000);
+    /// Where the path of the redirected file is placed, inside the executable.
+    const PATH: Address = Address(0x8001_0400);
+    /// Where the executable reads the file to.
+    const BUFFER: Address = Address(0x8002_0000);
+    /// Length of the redirected file, which ends in the middle of its third sector.
+    const FILE_LEN: usize = 5000;
+
+    /// Encodes `lui $reg, value >> 16; ori $reg, $reg, value & 0xFFFF`.
+    fn li(reg: Reg, value: u32) -> [u32; 2] {
+        let reg = reg as u32;
+        [
+            0x3C00_0000 | (reg << 16) | (value >> 16),
+            0x3400_0000 | (reg << 21) | (reg << 16) | (value & 0xFFFF),
+        ]
+    }
+
+    /// Encodes a call to the A(`code`) kernel function with the given arguments, which moves its
+    /// result to `result`.
+    fn call(code: u32, args: [u32; 3], result: Reg) -> Vec<u32> {
+        let mut instrs: Vec<_> = [Reg::A0, Reg::A1, Reg::A2]
+            .into_iter()
+            .zip(args)
+            .flat_map(|(reg, value)| li(reg, value))
+            .collect();
+
+        instrs.extend([
+            0x2409_0000 | code, // addiu $t1, $zero, code
+            0x240A_00A0,        // addiu $t2, $zero, 0xA0
+            0x0140_F809,        // jalr $t2
+            0,
+            0x0040_0021 | ((result as u32) << 11), // addu $result, $v0, $zero
+        ]);
+        instrs
+    }
+
+    #[test]
+    fn sideloaded_exe_opens_reads_and_seeks_a_redirected_file() {
+        let data: Vec<u8> = (0..FILE_LEN).map(|i| (i * 7 + i / 256) as u8).collect();
+        let dir = testing::scratch_dir("redirect");
+        std::fs::write(dir.join("DATA.BIN"), &data).unwrap();
+
+        // open, read 16 bytes, seek to the second sector, read it, seek to the last sector from
+        // the end, read past the end of the file and close
+        let fd = FD_BASE;
+        let mut code = [
+            call(0x00, [PATH.value(), 1, 0], Reg::S0),
+            call(0x02, [fd, BUFFER.value(), 16], Reg::S1),
+            call(0x01, [fd, SECTOR_LEN, 0], Reg::S2),
+            call(0x02, [fd, BUFFER.value() + 0x1000, SECTOR_LEN], Reg::S3),
+            call(0x01, [fd, SECTOR_LEN.wrapping_neg(), 2], Reg::S4),
+            call(0x02, [fd, BUFFER.value() + 0x2000, SECTOR_LEN], Reg::S5),
+            call(0x04, [fd, 0, 0], Reg::S6),
+        ]
+        .concat();
+
+        // done: j done; nop
+        let done = Address(DESTINATION.value() + 4 * code.len() as u32);
+        code.extend([0x0800_0000 | ((done.value() >> 2) & 0x03FF_FFFF), 0]);
+
+        let mut program: Vec<u8> = code.iter().flat_map(|word| word.to_le_bytes()).collect();
+        program.resize((PATH.value() - DESTINATION.value()) as usize, 0);
+        program.extend(b"cdrom:\\data.bin;1\0");
+
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.redirect_cdrom_files(dir);
+        emulator.psx_mut().memory.sideload = Some(Executable {
+            header: Header {
+                initial_pc: DESTINATION,
+                initial_gp: 0,
+                destination: DESTINATION,
+                length: program.len() as u32,
+                data_start: Address(0),
+                data_length: 0,
+                bss_start: Address(0),
+                bss_length: 0,
+                initial_sp_base: 0,
+                initial_sp_offset: 0,
+                marker: CString::default(),
+            },
+            program,
+        });
+        emulator.set_pc(SHELL_ENTRY);
+
+        let result = emulator.run_until(1_000_000, &[Condition::PcEquals(done)], |_| false);
+        assert_eq!(result.reason, StopReason::Condition(0));
+
+        let psx = emulator.psx();
+        let results = [
+            Reg::S0,
+            Reg::S1,
+            Reg::S2,
+            Reg::S3,
+            Reg::S4,
+            Reg::S5,
+            Reg::S6,
+        ]
+        .map(|reg| psx.cpu.regs.read(reg));
+        assert_eq!(results, [fd, 16, 2048, 2048, 4096, 904, fd]);
+
+        // reads transfer whole sectors, even past the requested length
+        let ram = |addr: Address, len: usize| {
+            let offset = (addr.value() & 0x1F_FFFF) as usize;
+            &psx.memory.ram[offset..][..len]
+        };
+        assert_eq!(ram(BUFFER, 2048), &data[..2048]);
+        assert_eq!(
+            ram(Address(BUFFER.value() + 0x1000), 2048),
+            &data[2048..4096]
+        );
+
+        // and past the end of the file, which reads as zeroes
+        let last = ram(Address(BUFFER.value() + 0x2000), 2048);
+        assert_eq!(&last[..904], &data[4096..]);
+        assert!(last[904..].iter().all(|&byte| byte == 0));
+    }
+}
@@ -0,0 +1,92 @@
+//! Mirroring of the kernel's TTY output, which is also accumulated in
+//! [`Memory::kernel_stdout`](shimmer_core::mem::Memory::kernel_stdout).
+
+use super::Interpreter;
+use std::io::Write;
+
+/// Where the characters printed by the kernel are mirrored to, as they're printed.
+#[derive(Default)]
+pub enum StdoutMirror {
+    /// The stdout of the process.
+    #[default]
+    Process,
+    /// A writer, e.g. to capture the output without polluting the stdout of the process.
+    Writer(Box<dyn Write + Send>),
+    /// Nowhere.
+    Disabled,
+}
+
+impl Interpreter {
+    /// Sets where the characters printed by the kernel are mirrored to.
+    pub fn set_stdout_mirror(&mut self, mirror: StdoutMirror) {
+        self.stdout_mirror = mirror;
+    }
+
+    /// Mirrors a character printed by the kernel. Errors are ignored, since the output is still
+    /// accumulated in memory.
+    pub(super) fn mirror_stdout(&mut self, char: char) {
+        match &mut self.stdout_mirror {
+            StdoutMirror::Process => print!("{char}"),
+            StdoutMirror::Writer(writer) => _ = write!(writer, "{char}"),
+            StdoutMirror::Disabled => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use std::sync::{Arc, Mutex};
+
+    /// A writer which keeps what's written to it around, shared with the test which created it.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn kernel_output_is_mirrored_to_the_writer() {
+        let mut emulator = testing::emulator(testing::builder());
+        let captured = Captured::default();
+        emulator.set_stdout_mirror(StdoutMirror::Writer(Box::new(captured.clone())));
+
+        testing::putchar(&mut emulator, "ok");
+
+        assert_eq!(*captured.0.lock().unwrap(), b"ok");
+        assert_eq!(emulator.psx.memory.kernel_stdout, "ok");
+    }
+
+    #[test]
+    fn disabled_mirror_still_accumulates_the_output() {
+        let mut emulator = testing::emulator(testing::builder());
+        let captured = Captured::default();
+        emulator.set_stdout_mirror(StdoutMirror::Writer(Box::new(captured.clone())));
+        testing::putchar(&mut emulator, "o");
+
+        emulator.set_stdout_mirror(StdoutMirror::Disabled);
+        testing::putchar(&mut emulator, "k");
+
+        assert_eq!(*captured.0.lock().unwrap(), b"o");
+        assert_eq!(emulator.psx.memory.kernel_stdout, "ok");
+    }
+
+    #[test]
+    fn configured_limit_bounds_the_kernel_output() {
+        let mut emulator = testing::emulator(testing::builder().kernel_stdout_limit(4));
+        emulator.set_stdout_mirror(StdoutMirror::Disabled);
+
+        testing::putchar(&mut emulator, "hello");
+
+        assert_eq!(emulator.psx.memory.kernel_stdout, "ello");
+    }
+}
@@ -0,0 +1,109 @@
+//! A summary of the emulator state meant to be printed when something goes terribly wrong, e.g.
+//! when emulation panics.
+
+use crate::Emulator;
+use shimmer_core::cpu::{Reg, instr::Instruction};
+use std::fmt::Write;
+
+/// Number of flight recorder records included in a crash report.
+const CRASH_REPORT_BUS_RECORDS: usize = 32;
+
+impl Emulator {
+    /// Writes a one page summary of the emulator state: where the CPU is, what it's executing,
+    /// what the scheduler is waiting on, the most recent kernel calls and, if the flight recorder
+    /// is enabled, the most recent bus accesses.
+    ///
+    /// This is meant to be called after a panic, when the state might be inconsistent, so it only
+    /// peeks memory and never panics on its own.
+    pub fn crash_report(&mut self) -> String {
+        let mut report = String::new();
+        _ = self.write_crash_report(&mut report);
+        report
+    }
+
+    fn write_crash_report(&mut self, out: &mut String) -> std::fmt::Result {
+        let regs = &self.psx.cpu.regs;
+        let current = self.cpu.current_addr();
+        let (delay_instr, delay_addr) = self.cpu.instr_delay_slot();
+
+        writeln!(out, "=== crash report ===")?;
+        writeln!(out, "pc: {:08X}", regs.read_pc())?;
+        match self.psx.peek::<u32>(current) {
+            Some(raw) => writeln!(
+                out,
+                "current: {current} {:08X} {}",
+                raw,
+                Instruction::from_bits(raw)
+            )?,
+            None => writeln!(out, "current: {current} (not in plain memory)")?,
+        }
+        writeln!(out, "delay slot: {delay_addr} {delay_instr}")?;
+        if let Some(load) = self.cpu.load_delay_slot() {
+            writeln!(out, "pending load: {:?} <- {:08X}", load.reg, load.value)?;
+        }
+
+        writeln!(
+            out,
+            "ra: {:08X} sp: {:08X} v0: {:08X} a0: {:08X}",
+            regs.read(Reg::RA),
+            regs.read(Reg::SP),
+            regs.read(Reg::V0),
+            regs.read(Reg::A0)
+        )?;
+
+        let now = self.psx.scheduler.elapsed();
+        writeln!(out, "\nscheduler time: {now}")?;
+        let mut pending = self.psx.scheduler.pending().collect::<Vec<_>>();
+        pending.sort_by_key(|(time, _)| *time);
+        for (time, event) in pending {
+            writeln!(out, "  +{:<10} {event:?}", time.saturating_sub(now))?;
+        }
+
+        writeln!(out, "\nrecent kernel calls:")?;
+        for call in self.cpu.recent_kernel_calls() {
+            writeln!(out, "  {} {call}", call.caller)?;
+        }
+
+        let records = self.psx.flight_recorder.records();
+        if !records.is_empty() {
+            writeln!(out, "\nrecent bus accesses:")?;
+            let start = records.len().saturating_sub(CRASH_REPORT_BUS_RECORDS);
+            for record in &records[start..] {
+                writeln!(out, "  {record}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{flight_recorder, testing};
+    use shimmer_core::CYCLES_SECOND;
+    use std::panic::AssertUnwindSafe;
+
+    #[test]
+    fn report_after_a_panic_names_the_faulting_context() {
+        let mut emulator = testing::emulator(testing::builder().flight_recorder(
+            flight_recorder::Settings {
+                enabled: true,
+                ..Default::default()
+            },
+        ));
+
+        // a debug hook panicking after the first instruction
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            emulator.run_until(CYCLES_SECOND, &[], |_| panic!("injected panic"))
+        }));
+        assert!(result.is_err());
+
+        let report = emulator.crash_report();
+        let pc = emulator.psx.cpu.regs.read_pc();
+        let current = emulator.cpu.current_addr();
+        assert!(report.contains(&format!("pc: {pc:08X}")), "{report}");
+        assert!(report.contains(&format!("current: {current}")), "{report}");
+        assert!(report.contains("scheduler time: "), "{report}");
+        assert!(report.contains("recent bus accesses:"), "{report}");
+    }
+}
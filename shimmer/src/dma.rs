@@ -1,14 +1,50 @@
 //! An implementation of the DMA controller.
 
-use crate::{PSX, cdrom, scheduler::Event};
+use crate::{PSX, bus::MisalignedAddressErr, cdrom, gpu, scheduler::Event, spu};
 use bitos::{BitUtils, integer::u24};
+use easyerr::{Error, ResultExt};
 use shimmer_core::{
+    Cycles,
     dma::{Channel, ChannelInterruptMode, DataDirection, TransferDirection, TransferMode},
+    gpu::cmd::parser::DecodedRenderingCommand,
     interrupts::Interrupt,
     mem::Address,
 };
 use tinylog::{error, info, trace, warn};
 
+#[derive(Debug, Error)]
+pub enum DmaError {
+    #[error("couldn't access memory")]
+    Memory { source: MisalignedAddressErr },
+    #[error("GPU response queue is empty")]
+    EmptyResponseQueue,
+}
+
+/// A read-only view of an ongoing transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveTransfer {
+    pub channel: Channel,
+    pub mode: TransferMode,
+    /// Words left to transfer. Unknown for linked list transfers, which only know they're done
+    /// once they reach the end marker.
+    pub remaining_words: Option<u32>,
+    /// Address of the next word to transfer or, for linked list transfers, of the next node.
+    pub current_addr: u32,
+}
+
+/// Statistics of the transfers executed on a channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    pub transfers_started: u64,
+    /// Words read from or written to memory, including linked list node headers.
+    pub words_moved: u64,
+    /// Cycles between the start and the end of transfers, including the time spent yielded or
+    /// stalled.
+    pub cycles_spent: Cycles,
+    /// Base address of the last transfer started.
+    pub last_base: u32,
+}
+
 /// The progress made by a transfer.
 enum Progress {
     /// The transfer is still ongoing.
@@ -27,7 +63,7 @@ struct BurstTransfer {
 }
 
 impl BurstTransfer {
-    fn advance(&mut self, psx: &mut PSX) -> Progress {
+    fn advance(&mut self, psx: &mut PSX, stats: &mut ChannelStats) -> Result<Progress, DmaError> {
         let channel_state = &psx.dma.channels[self.channel as usize];
         let increment = match channel_state.control.data_direction() {
             DataDirection::Forward => 4,
@@ -39,14 +75,14 @@ impl BurstTransfer {
                 if self.remaining > 1 {
                     let prev = self.current_addr.wrapping_add_signed(increment) & 0x00FF_FFFF;
                     psx.write::<_, true>(Address(self.current_addr), prev)
-                        .unwrap();
+                        .context(DmaCtx::Memory)?;
 
                     self.remaining -= 1;
 
                     Progress::Ongoing
                 } else {
                     psx.write::<_, true>(Address(self.current_addr), 0x00FF_FFFF)
-                        .unwrap();
+                        .context(DmaCtx::Memory)?;
 
                     // alt behaviour
                     let channel_state = &mut psx.dma.channels[self.channel as usize];
@@ -67,7 +103,7 @@ impl BurstTransfer {
                 ];
 
                 psx.write::<_, true>(Address(self.current_addr), u32::from_le_bytes(data))
-                    .unwrap();
+                    .context(DmaCtx::Memory)?;
 
                 self.remaining -= 1;
                 if self.remaining == 0 {
@@ -87,13 +123,20 @@ impl BurstTransfer {
                 }
             }
             _ => {
-                error!(psx.loggers.dma, "unimplemented burst transfer");
+                if psx
+                    .unimplemented
+                    .record(format!("burst transfer on {:?}", self.channel))
+                {
+                    error!(psx.loggers.dma, "unimplemented burst transfer");
+                }
+
                 Progress::Finished
             }
         };
 
+        stats.words_moved += 1;
         self.current_addr = self.current_addr.wrapping_add_signed(increment);
-        progress
+        Ok(progress)
     }
 }
 
@@ -103,7 +146,7 @@ struct SliceTransfer {
 }
 
 impl SliceTransfer {
-    fn advance(&mut self, psx: &mut PSX) -> Progress {
+    fn advance(&mut self, psx: &mut PSX, stats: &mut ChannelStats) -> Result<Progress, DmaError> {
         let channel_state = &psx.dma.channels[self.channel as usize];
         let count = channel_state.block_control.count();
         let len = channel_state.block_control.len();
@@ -119,18 +162,50 @@ impl SliceTransfer {
             match self.channel {
                 Channel::GPU => match transfer_direction {
                     TransferDirection::DeviceToRam => {
-                        let value = psx.gpu.response_queue.pop_front().unwrap();
+                        let value = psx
+                            .gpu
+                            .response_queue
+                            .pop_front()
+                            .ok_or(DmaError::EmptyResponseQueue)?;
                         psx.write::<u32, true>(Address(current_addr), value)
-                            .unwrap();
+                            .context(DmaCtx::Memory)?;
+                    }
+                    TransferDirection::RamToDevice => {
+                        let word = psx
+                            .read::<u32, true>(Address(current_addr))
+                            .context(DmaCtx::Memory)?;
+                        gpu::push_gp0(psx, word);
+                    }
+                },
+                Channel::SPU => match transfer_direction {
+                    TransferDirection::DeviceToRam => {
+                        let low = spu::read_transfer(psx);
+                        let high = spu::read_transfer(psx);
+                        psx.write::<u32, true>(
+                            Address(current_addr),
+                            u32::from(low) | (u32::from(high) << 16),
+                        )
+                        .context(DmaCtx::Memory)?;
                     }
                     TransferDirection::RamToDevice => {
-                        let word = psx.read::<u32, true>(Address(current_addr)).unwrap();
-                        psx.gpu.render_queue.push_back(word);
+                        let word = psx
+                            .read::<u32, true>(Address(current_addr))
+                            .context(DmaCtx::Memory)?;
+                        spu::write_transfer(psx, word as u16);
+                        spu::write_transfer(psx, (word >> 16) as u16);
                     }
                 },
-                _ => error!(psx.loggers.dma, "unimplemented slice transfer"),
+                _ => {
+                    if psx
+                        .unimplemented
+                        .record(format!("slice transfer on {:?}", self.channel))
+                    {
+                        error!(psx.loggers.dma, "unimplemented slice transfer");
+                    }
+                }
             }
 
+            stats.words_moved += 1;
             current_addr = current_addr.wrapping_add_signed(increment);
         }
 
@@ -139,11 +214,11 @@ impl SliceTransfer {
         channel_state.base.set_addr(u24::new(current_addr));
         channel_state.block_control.set_count(count - 1);
 
-        if count > 1 {
+        Ok(if count > 1 {
             Progress::Yielded
         } else {
             Progress::Finished
-        }
+        })
     }
 }
 
@@ -153,12 +228,14 @@ struct LinkedTransfer {
 }
 
 impl LinkedTransfer {
-    fn advance(&mut self, psx: &mut PSX) -> Progress {
+    fn advance(&mut self, psx: &mut PSX, stats: &mut ChannelStats) -> Result<Progress, DmaError> {
         assert_eq!(self.channel, Channel::GPU);
 
         let channel_status = &psx.dma.channels[self.channel as usize];
         let current_addr = channel_status.base.addr().value() & !0b11;
-        let node = psx.read::<u32, true>(Address(current_addr)).unwrap();
+        let node = psx
+            .read::<u32, true>(Address(current_addr))
+            .context(DmaCtx::Memory)?;
         let next = node.bits(0, 24);
         let words = node.bits(24, 32);
 
@@ -166,22 +243,120 @@ impl LinkedTransfer {
 
         for i in 0..words {
             let addr = current_addr + (i + 1) * 4;
-            let word = psx.read::<u32, true>(Address(addr)).unwrap();
-            psx.gpu.render_queue.push_back(word);
+            let word = psx
+                .read::<u32, true>(Address(addr))
+                .context(DmaCtx::Memory)?;
+            gpu::push_gp0(psx, word);
         }
 
+        stats.words_moved += u64::from(words) + 1;
         psx.dma.channels[self.channel as usize]
             .base
             .set_addr(u24::new(next));
 
-        if next == 0x00FF_FFFF {
+        Ok(if next == 0x00FF_FFFF {
             Progress::Finished
         } else {
             Progress::Yielded
+        })
+    }
+}
+
+/// A packet found while walking an ordering table.
+#[derive(Debug, Clone)]
+pub struct OtPacket {
+    /// Address of the node holding the packet.
+    pub addr: u32,
+    /// Amount of words in the packet, excluding the node header.
+    pub words: u32,
+    /// The first command of the packet, if it could be decoded.
+    pub first_command: Option<DecodedRenderingCommand>,
+}
+
+/// A summary of an ordering table, as returned by [`walk_ordering_table`].
+#[derive(Debug, Clone, Default)]
+pub struct OtReport {
+    pub base: u32,
+    /// Nodes visited, including empty ones. Empty nodes are usually the entries of the table
+    /// itself.
+    pub nodes: u32,
+    /// The nodes which hold at least one word.
+    pub packets: Vec<OtPacket>,
+    /// Whether the walk reached the end marker. If not, it either hit the node limit or a node
+    /// outside of memory.
+    pub terminated: bool,
+}
+
+impl OtReport {
+    /// Total amount of words in the packets, excluding node headers.
+    pub fn words(&self) -> u32 {
+        self.packets.iter().map(|packet| packet.words).sum()
+    }
+}
+
+impl std::fmt::Display for OtReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "OT at {}: {} entries, {} packets, {} KB",
+            Address(self.base),
+            self.nodes as usize - self.packets.len(),
+            self.packets.len(),
+            self.words() * 4 / 1024,
+        )?;
+
+        if !self.terminated {
+            write!(f, " (unterminated)")?;
         }
+
+        Ok(())
     }
 }
 
+/// Walks the linked list starting at `base` the same way a linked list transfer would, visiting
+/// at most `max_nodes` nodes, and summarizes it. Nothing is executed and no side effects are
+/// triggered: memory is only peeked.
+pub fn walk_ordering_table(psx: &PSX, base: Address, max_nodes: u32) -> OtReport {
+    let mut report = OtReport {
+        base: base.value(),
+        ..Default::default()
+    };
+
+    let mut current_addr = base.value() & 0x00FF_FFFC;
+    while report.nodes < max_nodes {
+        let Some(node) = psx.peek::<u32>(Address(current_addr)) else {
+            return report;
+        };
+
+        let next = node.bits(0, 24);
+        let words = node.bits(24, 32);
+        report.nodes += 1;
+
+        if words > 0 {
+            let packet = (1..=words)
+                .map(|i| psx.peek::<u32>(Address(current_addr + i * 4)))
+                .collect::<Option<Vec<_>>>();
+
+            report.packets.push(OtPacket {
+                addr: current_addr,
+                words,
+                first_command: packet
+                    .and_then(|packet| DecodedRenderingCommand::decode(&packet))
+                    .map(|(cmd, _)| cmd),
+            });
+        }
+
+        if next == 0x00FF_FFFF {
+            report.terminated = true;
+            return report;
+        }
+
+        current_addr = next & !0b11;
+    }
+
+    report
+}
+
 /// The state of the executor.
 #[derive(Default)]
 enum State {
@@ -200,24 +375,119 @@ fn update_master_interrupt(psx: &mut PSX) {
 
 /// A DMA transfer executor.
 #[derive(Default)]
-pub struct Dma(State);
+pub struct Dma {
+    state: State,
+    stats: [ChannelStats; 7],
+    /// Time at which the ongoing transfer started.
+    started_at: Cycles,
+}
 
 impl Dma {
     #[inline(always)]
     pub fn ongoing(&self) -> bool {
-        !matches!(self.0, State::Idle)
+        !matches!(self.state, State::Idle)
+    }
+
+    /// Returns the statistics of every channel, indexed by [`Channel`].
+    pub fn stats(&self) -> &[ChannelStats; 7] {
+        &self.stats
+    }
+
+    /// Returns a view of the ongoing transfer, if any.
+    pub fn active(&self, psx: &PSX) -> Option<ActiveTransfer> {
+        let (channel, mode) = match &self.state {
+            State::Idle => return None,
+            State::BurstTransfer(transfer) => {
+                return Some(ActiveTransfer {
+                    channel: transfer.channel,
+                    mode: TransferMode::Burst,
+                    remaining_words: Some(transfer.remaining),
+                    current_addr: transfer.current_addr,
+                });
+            }
+            State::SliceTransfer(transfer) => (transfer.channel, TransferMode::Slice),
+            State::LinkedTransfer(transfer) => (transfer.channel, TransferMode::LinkedList),
+        };
+
+        let channel_state = &psx.dma.channels[channel as usize];
+        let block_control = &channel_state.block_control;
+        let remaining_words = (mode == TransferMode::Slice)
+            .then(|| u32::from(block_control.count()) * u32::from(block_control.len()));
+
+        Some(ActiveTransfer {
+            channel,
+            mode,
+            remaining_words,
+            current_addr: channel_state.base.addr().value() & !0b11,
+        })
+    }
+
+    /// Whether the ongoing transfer is waiting for the GPU to drain its render queue.
+    fn stalled(&self, psx: &PSX) -> bool {
+        let channel = match &self.state {
+            State::SliceTransfer(transfer) => transfer.channel,
+            State::LinkedTransfer(transfer) => transfer.channel,
+            _ => return false,
+        };
+
+        channel == Channel::GPU
+            && psx.dma.channels[channel as usize]
+                .control
+                .transfer_direction()
+                == TransferDirection::RamToDevice
+            && psx.gpu.render_queue_full()
     }
 
     pub fn advance(&mut self, psx: &mut PSX) {
         update_master_interrupt(psx);
 
-        let (channel, progress) = match &mut self.0 {
-            State::BurstTransfer(transfer) => (transfer.channel, transfer.advance(psx)),
-            State::SliceTransfer(transfer) => (transfer.channel, transfer.advance(psx)),
-            State::LinkedTransfer(transfer) => (transfer.channel, transfer.advance(psx)),
+        if self.stalled(psx) {
+            trace!(
+                psx.loggers.dma,
+                "GPU render queue is full - stalling transfer"
+            );
+            psx.scheduler.schedule(Event::Gpu, 0);
+            psx.scheduler
+                .schedule(Event::DmaAdvance, Channel::GPU.cycles_per_word());
+            return;
+        }
+
+        let stats = &mut self.stats;
+        let (channel, progress) = match &mut self.state {
+            State::BurstTransfer(transfer) => {
+                let stats = &mut stats[transfer.channel as usize];
+                (transfer.channel, transfer.advance(psx, stats))
+            }
+            State::SliceTransfer(transfer) => {
+                let stats = &mut stats[transfer.channel as usize];
+                (transfer.channel, transfer.advance(psx, stats))
+            }
+            State::LinkedTransfer(transfer) => {
+                let stats = &mut stats[transfer.channel as usize];
+                (transfer.channel, transfer.advance(psx, stats))
+            }
             State::Idle => unreachable!(),
         };
 
+        let progress = match progress {
+            Ok(progress) => progress,
+            Err(err) => {
+                error!(
+                    psx.loggers.dma,
+                    "aborting transfer on channel {channel:?}: {err}";
+                );
+
+                self.state = State::Idle;
+                self.stats[channel as usize].cycles_spent +=
+                    psx.scheduler.elapsed() - self.started_at;
+                psx.dma.channels[channel as usize]
+                    .control
+                    .set_transfer_ongoing(false);
+
+                return;
+            }
+        };
+
         match channel {
             Channel::GPU => {
                 psx.scheduler.schedule(Event::Gpu, 0);
@@ -228,10 +498,15 @@ impl Dma {
                     .schedule(Event::Cdrom(cdrom::Event::Update), 0);
             }
             _ => {
-                error!(
-                    psx.loggers.dma,
-                    "advancing unimplemented channel: {channel:?}"
-                )
+                if psx
+                    .unimplemented
+                    .record(format!("advancing channel {channel:?}"))
+                {
+                    error!(
+                        psx.loggers.dma,
+                        "advancing unimplemented channel: {channel:?}"
+                    )
+                }
             }
         }
 
@@ -274,7 +549,9 @@ impl Dma {
                     "finished transfer on channel {channel:?}";
                 );
 
-                self.0 = State::Idle;
+                self.state = State::Idle;
+                self.stats[channel as usize].cycles_spent +=
+                    psx.scheduler.elapsed() - self.started_at;
 
                 let channel_control = &mut psx.dma.channels[channel as usize].control;
                 channel_control.set_transfer_ongoing(false);
@@ -296,7 +573,7 @@ impl Dma {
     pub fn update(&mut self, psx: &mut PSX) {
         update_master_interrupt(psx);
 
-        if matches!(self.0, State::Idle) {
+        if matches!(self.state, State::Idle) {
             let mut enabled_channels = psx.dma.control.enabled_channels();
             enabled_channels.sort_unstable_by_key(|(_, priority)| std::cmp::Reverse(*priority));
 
@@ -324,6 +601,12 @@ impl Dma {
                     }
 
                     channel_state.control.set_force_transfer(false);
+
+                    let stats = &mut self.stats[channel as usize];
+                    stats.transfers_started += 1;
+                    stats.last_base = channel_state.base.addr().value();
+                    self.started_at = psx.scheduler.elapsed();
+
                     match channel_state
                         .control
                         .transfer_mode()
@@ -343,7 +626,7 @@ impl Dma {
                                 base = Address(current_addr), remaining = remaining
                             );
 
-                            self.0 = State::BurstTransfer(BurstTransfer {
+                            self.state = State::BurstTransfer(BurstTransfer {
                                 channel,
                                 current_addr,
                                 remaining,
@@ -355,7 +638,7 @@ impl Dma {
                                 "starting slice transfer on channel {channel:?}";
                             );
 
-                            self.0 = State::SliceTransfer(SliceTransfer { channel });
+                            self.state = State::SliceTransfer(SliceTransfer { channel });
                         }
                         TransferMode::LinkedList => {
                             info!(
@@ -363,7 +646,7 @@ impl Dma {
                                 "starting linked transfer on channel {channel:?}";
                             );
 
-                            self.0 = State::LinkedTransfer(LinkedTransfer { channel });
+                            self.state = State::LinkedTransfer(LinkedTransfer { channel });
                         }
                     }
 
@@ -375,3 +658,230 @@ impl Dma {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        StopReason,
+        gpu::interface::Primitive,
+        testing::{self, Recorder},
+    };
+    use shimmer_core::{
+        CYCLES_SECOND,
+        gpu::{LINKED_LIST_NODE_WORDS, RENDER_QUEUE_LIMIT},
+        mem::io,
+    };
+
+    const OT_END: u32 = 0x0001_0000;
+
+    /// Starts clearing an ordering table of `len` entries which ends at [`OT_END`].
+    fn start_otc(psx: &mut PSX, len: u32) {
+        testing::write::<u32>(psx, io::Reg::Dma6Base, OT_END);
+        testing::write::<u32>(psx, io::Reg::Dma6BlockControl, len);
+        // enable channel 6
+        testing::write::<u32>(psx, io::Reg::DmaControl, 0x0800_0000);
+        // start and trigger, the direction is fixed to backwards
+        testing::write::<u32>(psx, io::Reg::Dma6Control, 0x1100_0002);
+    }
+
+    #[test]
+    fn active_dma_reports_the_ongoing_transfer() {
+        let mut emulator = testing::emulator(testing::builder());
+        assert_eq!(emulator.active_dma().count(), 0);
+
+        start_otc(&mut emulator.psx, 16);
+        emulator.dma.update(&mut emulator.psx);
+
+        let transfer = ActiveTransfer {
+            channel: Channel::OTC,
+            mode: TransferMode::Burst,
+            remaining_words: Some(16),
+            current_addr: OT_END,
+        };
+        assert_eq!(emulator.active_dma().collect::<Vec<_>>(), [transfer]);
+
+        for _ in 0..3 {
+            emulator.dma.advance(&mut emulator.psx);
+        }
+
+        let transfer = ActiveTransfer {
+            remaining_words: Some(13),
+            current_addr: OT_END - 3 * 4,
+            ..transfer
+        };
+        assert_eq!(emulator.active_dma().collect::<Vec<_>>(), [transfer]);
+
+        while emulator.dma.ongoing() {
+            emulator.dma.advance(&mut emulator.psx);
+        }
+        assert_eq!(emulator.active_dma().count(), 0);
+    }
+
+    fn poke(psx: &mut PSX, addr: u32, value: u32) {
+        psx.write::<u32, true>(Address(addr), value).unwrap();
+    }
+
+    fn peek(psx: &mut PSX, addr: u32) -> u32 {
+        psx.read::<u32, true>(Address(addr)).unwrap()
+    }
+
+    /// Links a packet of `words`, placed at `addr`, into the entry of the ordering table at
+    /// `entry`, in front of the packets already there.
+    fn insert_packet(psx: &mut PSX, entry: u32, addr: u32, words: &[u32]) {
+        let next = peek(psx, entry) & 0x00FF_FFFF;
+        poke(psx, addr, ((words.len() as u32) << 24) | next);
+        for (i, &word) in words.iter().enumerate() {
+            poke(psx, addr + 4 * (i as u32 + 1), word);
+        }
+
+        poke(psx, entry, addr);
+    }
+
+    #[test]
+    fn otc_transfer_updates_the_channel_stats() {
+        let mut emulator = testing::emulator(testing::builder());
+        start_otc(&mut emulator.psx, 16);
+        emulator.cycle_for(1000);
+
+        let stats = emulator.dma_stats()[Channel::OTC as usize];
+        assert_eq!(stats.transfers_started, 1);
+        assert_eq!(stats.words_moved, 16);
+        assert_eq!(stats.last_base, OT_END);
+        assert!(stats.cycles_spent >= 16 * Channel::OTC.cycles_per_word());
+
+        // the other channels are untouched
+        let others = emulator
+            .dma_stats()
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != Channel::OTC as usize);
+        for (_, stats) in others {
+            assert_eq!(*stats, ChannelStats::default());
+        }
+    }
+
+    #[test]
+    fn ordering_table_walk_summarizes_packets() {
+        let mut emulator = testing::emulator(testing::builder());
+        start_otc(&mut emulator.psx, 16);
+        emulator.cycle_for(1000);
+
+        // monochrome triangle
+        let triangle = [0x2000_00FF, 0x0000_0000, 0x0000_0010, 0x0010_0000];
+        // fill rectangle
+        let fill = [0x0200_FF00, 0x0000_0000, 0x0010_0010];
+        insert_packet(&mut emulator.psx, OT_END - 8, 0x2000, &triangle);
+        insert_packet(&mut emulator.psx, OT_END - 8, 0x3000, &fill);
+
+        let report = emulator.walk_ordering_table(Address(OT_END), 100);
+        assert!(report.terminated);
+        assert_eq!(report.nodes, 18);
+        assert_eq!(report.words(), 7);
+
+        let packets: Vec<_> = report.packets.iter().map(|p| (p.addr, p.words)).collect();
+        assert_eq!(packets, [(0x3000, 3), (0x2000, 4)]);
+        assert!(matches!(
+            report.packets[0].first_command,
+            Some(DecodedRenderingCommand::QuickRectangleFill { .. })
+        ));
+        assert!(matches!(
+            report.packets[1].first_command,
+            Some(DecodedRenderingCommand::Polygon { .. })
+        ));
+        assert_eq!(
+            report.to_string(),
+            "OT at 0x0001_0000: 16 entries, 2 packets, 0 KB"
+        );
+    }
+
+    #[test]
+    fn ordering_table_walk_stops_at_the_node_limit() {
+        let mut emulator = testing::emulator(testing::builder());
+        start_otc(&mut emulator.psx, 16);
+        emulator.cycle_for(1000);
+
+        let report = emulator.walk_ordering_table(Address(OT_END), 4);
+        assert!(!report.terminated);
+        assert_eq!(report.nodes, 4);
+        assert!(report.packets.is_empty());
+        assert!(report.to_string().ends_with("(unterminated)"));
+    }
+
+    #[test]
+    fn linked_list_of_a_megabyte_keeps_the_render_queue_bounded() {
+        const LIST: u32 = 0x0001_0000;
+        const LIST_LEN: u32 = 1024 * 1024;
+        const FILLS_PER_NODE: u32 = 5;
+        // a header and three words per fill
+        const NODE_LEN: u32 = 4 * (1 + 3 * FILLS_PER_NODE);
+        const NODES: u32 = LIST_LEN / NODE_LEN;
+
+        let recorder = Recorder::default();
+        let mut emulator =
+            testing::emulator(testing::builder().renderer(Box::new(recorder.clone())));
+
+        // GP0(02h) quick fills, each with its index as its color
+        let mut list = Vec::with_capacity(LIST_LEN as usize);
+        for node in 0..NODES {
+            let next = if node + 1 == NODES {
+                0x00FF_FFFF
+            } else {
+                LIST + (node + 1) * NODE_LEN
+            };
+            list.extend_from_slice(&(((3 * FILLS_PER_NODE) << 24) | next).to_le_bytes());
+
+            for fill in node * FILLS_PER_NODE..(node + 1) * FILLS_PER_NODE {
+                let (x, y) = ((fill % 64) * 16, (fill / 64) % 512);
+                for word in [0x0200_0000 | fill, (y << 16) | x, 0x0001_0010] {
+                    list.extend_from_slice(&word.to_le_bytes());
+                }
+            }
+        }
+        emulator.load_raw(Address(0x8000_0000 | LIST), &list);
+
+        let psx = emulator.psx_mut();
+        // GP1(04h) DMA direction CPU to GP0
+        testing::write::<u32>(psx, io::Reg::Gp1, 0x0400_0002);
+        let dma_control = psx.dma.control.to_bits();
+        testing::write::<u32>(psx, io::Reg::DmaControl, dma_control | (1 << 11));
+        testing::write::<u32>(psx, io::Reg::Dma2Base, LIST);
+        // from RAM, linked list mode, started
+        testing::write::<u32>(psx, io::Reg::Dma2Control, 0x0100_0401);
+
+        let mut peak = 0;
+        let result = emulator.run_until(10 * CYCLES_SECOND, &[], |psx| {
+            peak = peak.max(psx.gpu.render_queue.len());
+            !psx.dma.channels[Channel::GPU as usize]
+                .control
+                .transfer_ongoing()
+                && psx.gpu.render_queue.is_empty()
+        });
+        assert_eq!(result.reason, StopReason::Predicate);
+
+        // a step started just below the limit can still push a whole node
+        assert!(
+            peak <= RENDER_QUEUE_LIMIT + LINKED_LIST_NODE_WORDS,
+            "the render queue peaked at {peak} words"
+        );
+        assert_eq!(emulator.psx().gpu.watchdog.dropped, 0);
+
+        let fills: Vec<_> = recorder
+            .take_primitives()
+            .into_iter()
+            .map(|primitive| {
+                let Primitive::Rectangle(rect) = primitive else {
+                    panic!("expected a rectangle, got {primitive:?}");
+                };
+
+                let color = rect.top_left.color;
+                u32::from_le_bytes([color.r, color.g, color.b, 0])
+            })
+            .collect();
+        assert!(
+            fills.iter().copied().eq(0..NODES * FILLS_PER_NODE),
+            "{} fills executed out of order or not at all",
+            fills.len()
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! A flight recorder of bus accesses, which keeps the last few thousand of them around to help
+//! figure out how the system got into a bad state, e.g. why a program crashed.
+
+use shimmer_core::{
+    CYCLES_MILLIS, Cycles,
+    cpu::cop0::Exception,
+    mem::{Address, Primitive},
+};
+use std::fmt;
+
+/// The kind of a bus access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    /// An instruction fetch.
+    Fetch,
+}
+
+/// A bus access recorded by the [`FlightRecorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusRecord {
+    /// Address of the instruction being executed when the access happened.
+    pub pc: u32,
+    pub addr: u32,
+    /// The value read or written, zero extended.
+    pub value: u32,
+    /// Width of the access, in bytes.
+    pub width: u8,
+    pub kind: AccessKind,
+}
+
+impl fmt::Display for BusRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Read => "R",
+            AccessKind::Write => "W",
+            AccessKind::Fetch => "F",
+        };
+
+        write!(
+            f,
+            "{:08X} {kind}{} {:08X} {:0width$X}",
+            self.pc,
+            self.width,
+            self.addr,
+            self.value,
+            width = 2 * self.width as usize
+        )
+    }
+}
+
+/// Configuration of the [`FlightRecorder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Whether to record every access from the start.
+    pub enabled: bool,
+    /// Maximum amount of records kept. Once full, the oldest records are overwritten.
+    pub capacity: usize,
+    /// Exceptions which enable recording for `arm_cycles` cycles when triggered, so that the
+    /// accesses following them are captured even if recording is disabled.
+    pub arm_on: Vec<Exception>,
+    /// For how many cycles recording stays enabled after one of the `arm_on` exceptions.
+    pub arm_cycles: Cycles,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 64 * 1024,
+            arm_on: Vec::new(),
+            arm_cycles: 10 * CYCLES_MILLIS,
+        }
+    }
+}
+
+/// Records the last bus accesses into a fixed-size ring buffer.
+#[derive(Debug, Default)]
+pub struct FlightRecorder {
+    settings: Settings,
+    /// Whether accesses are currently being recorded, either because recording is enabled or
+    /// because it's armed.
+    active: bool,
+    /// Cycle at which recording stops if it's only armed.
+    armed_until: Cycles,
+    /// Address of the instruction being executed.
+    pc: u32,
+    records: Vec<BusRecord>,
+    /// Index at which the next record is written once the buffer is full.
+    next: usize,
+}
+
+impl FlightRecorder {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            active: settings.enabled,
+            settings,
+            ..Default::default()
+        }
+    }
+
+    /// Whether accesses are currently being recorded. This is the only check done on the bus
+    /// when the recorder is disabled.
+    #[inline(always)]
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Enables or disables recording every access. Disabling it doesn't discard the records.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.settings.enabled = enabled;
+        self.active = enabled;
+    }
+
+    /// Sets the address of the instruction being executed, which is attached to the following
+    /// records.
+    #[inline(always)]
+    pub fn set_pc(&mut self, pc: Address) {
+        self.pc = pc.value();
+    }
+
+    /// Arms the recorder if `exception` is one of the exceptions it's configured to arm on.
+    pub fn exception_triggered(&mut self, exception: Exception, now: Cycles) {
+        if self.settings.arm_on.contains(&exception) {
+            self.armed_until = now + self.settings.arm_cycles;
+            self.active = true;
+        }
+    }
+
+    /// Records an access of `value` at `addr` which happened at `now`, if the recorder is still
+    /// active by then.
+    #[cold]
+    #[inline(never)]
+    pub fn record<P: Primitive>(&mut self, now: Cycles, kind: AccessKind, addr: Address, value: P) {
+        if !self.settings.enabled && now >= self.armed_until {
+            self.active = false;
+            return;
+        }
+
+        let mut bytes = [0; 4];
+        value.write_to(&mut bytes);
+
+        let record = BusRecord {
+            pc: self.pc,
+            addr: addr.value(),
+            value: u32::from_le_bytes(bytes),
+            width: size_of::<P>() as u8,
+            kind,
+        };
+
+        if self.records.len() < self.settings.capacity {
+            self.records.push(record);
+        } else if self.settings.capacity > 0 {
+            self.records[self.next] = record;
+            self.next = (self.next + 1) % self.settings.capacity;
+        }
+    }
+
+    /// Returns the records, ordered from oldest to newest.
+    pub fn records(&mut self) -> &[BusRecord] {
+        self.records.rotate_left(self.next);
+        self.next = 0;
+
+        &self.records
+    }
+
+    /// Discards every record.
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.next = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use shimmer_core::cpu::{Reg as CpuReg, cop0::Reg};
+
+    const PROGRAM: Address = Address(0x8000_2000);
+
+    fn recorder(settings: Settings) -> FlightRecorder {
+        FlightRecorder::new(Settings {
+            capacity: 4,
+            ..settings
+        })
+    }
+
+    fn enabled() -> FlightRecorder {
+        recorder(Settings {
+            enabled: true,
+            ..Default::default()
+        })
+    }
+
+    /// Records a write of `value` at `now`, as the bus does: only if the recorder is active.
+    fn write(recorder: &mut FlightRecorder, now: Cycles, value: u32) {
+        if recorder.active() {
+            recorder.record(now, AccessKind::Write, Address(0x8000_0000), value);
+        }
+    }
+
+    fn values(recorder: &mut FlightRecorder) -> Vec<u32> {
+        recorder
+            .records()
+            .iter()
+            .map(|record| record.value)
+            .collect()
+    }
+
+    #[test]
+    fn records_are_ordered_oldest_first() {
+        let mut recorder = enabled();
+        recorder.set_pc(Address(0x8000_1000));
+        recorder.record(0, AccessKind::Fetch, Address(0x8000_1000), 0x2408_0001u32);
+        recorder.record(1, AccessKind::Read, Address(0x1F80_1814), 0x1234u16);
+        recorder.record(2, AccessKind::Write, Address(0x1F80_1040), 0xABu8);
+
+        let records = recorder.records();
+        assert_eq!(
+            records.iter().map(|r| r.kind).collect::<Vec<_>>(),
+            [AccessKind::Fetch, AccessKind::Read, AccessKind::Write]
+        );
+        assert_eq!(records[1].width, 2);
+        assert_eq!(records[1].value, 0x1234);
+        assert!(records.iter().all(|r| r.pc == 0x8000_1000));
+    }
+
+    #[test]
+    fn full_buffer_overwrites_the_oldest_records() {
+        let mut recorder = enabled();
+        for value in 0..10 {
+            write(&mut recorder, 0, value);
+        }
+
+        assert_eq!(values(&mut recorder), [6, 7, 8, 9]);
+        assert_eq!(values(&mut recorder), [6, 7, 8, 9]);
+
+        // reading the records doesn't disturb the order of the following ones
+        write(&mut recorder, 0, 10);
+        write(&mut recorder, 0, 11);
+        assert_eq!(values(&mut recorder), [8, 9, 10, 11]);
+
+        recorder.clear();
+        write(&mut recorder, 0, 12);
+        assert_eq!(values(&mut recorder), [12]);
+    }
+
+    #[test]
+    fn disabled_recorder_is_inactive() {
+        let mut recorder = recorder(Settings::default());
+        assert!(!recorder.active());
+
+        write(&mut recorder, 0, 1);
+        assert!(values(&mut recorder).is_empty());
+    }
+
+    #[test]
+    fn selected_exceptions_arm_the_recorder_for_a_while() {
+        let mut recorder = recorder(Settings {
+            arm_on: vec![Exception::AddressErrorLoad],
+            arm_cycles: 100,
+            ..Default::default()
+        });
+
+        recorder.exception_triggered(Exception::Syscall, 0);
+        assert!(!recorder.active());
+
+        recorder.exception_triggered(Exception::AddressErrorLoad, 50);
+        assert!(recorder.active());
+        write(&mut recorder, 60, 1);
+        write(&mut recorder, 149, 2);
+
+        // once the window is over, recording stops on the next access
+        write(&mut recorder, 150, 3);
+        assert!(!recorder.active());
+        write(&mut recorder, 160, 4);
+        assert_eq!(values(&mut recorder), [1, 2]);
+
+        // and is armed again by the next exception
+        recorder.exception_triggered(Exception::AddressErrorLoad, 200);
+        write(&mut recorder, 210, 5);
+        assert_eq!(values(&mut recorder), [1, 2, 5]);
+    }
+
+    #[test]
+    fn exceptions_arm_the_recorder_of_the_emulator() {
+        let mut emulator = testing::emulator(testing::builder().flight_recorder(Settings {
+            arm_on: vec![Exception::AddressErrorLoad],
+            ..Default::default()
+        }));
+
+        // addiu $t0, $zero, 1; lw $t1, 1($zero)
+        let program = [0x2408_0001u32, 0x8C09_0001];
+        emulator.load_raw(PROGRAM, &program.map(u32::to_le_bytes).concat());
+        emulator.psx_mut().cop0.regs.write(Reg::COP0_SR, 0);
+        emulator.set_pc(PROGRAM);
+
+        emulator.step_instructions(1);
+        assert_eq!(emulator.psx().cpu.regs.read(CpuReg::T0), 1);
+        assert!(emulator.flight_recorder().is_empty());
+
+        emulator.step_instructions(4);
+        let records = emulator.flight_recorder();
+        assert!(!records.is_empty());
+        assert_eq!(records[0].kind, AccessKind::Fetch);
+        assert_eq!(records[0].addr, 0x8000_0080);
+    }
+}
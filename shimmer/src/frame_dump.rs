@@ -0,0 +1,260 @@
+//! Dumping of displayed frames to disk as an image sequence.
+
+use crate::gpu::DisplayCapture;
+use easyerr::{Error, ResultExt};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
+    thread::JoinHandle,
+};
+use tinylog::{Logger, error, warn};
+
+/// How many captured frames may wait to be written before new ones are dropped.
+const QUEUE_LEN: usize = 8;
+
+/// The file format of dumped frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Binary PPM (P6) images, which video tools such as ffmpeg read directly.
+    Ppm,
+    /// Headerless 24-bit RGB. The dimensions of each frame are listed in the manifest.
+    Raw,
+}
+
+impl FrameFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Ppm => "ppm",
+            FrameFormat::Raw => "rgb",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FrameDumpError {
+    #[error("couldn't create the dump directory")]
+    CreateDir { source: std::io::Error },
+    #[error("couldn't create the manifest")]
+    Manifest { source: std::io::Error },
+}
+
+/// A captured frame waiting to be written.
+struct Frame {
+    index: u64,
+    /// Duration of this frame, in microseconds.
+    duration: f64,
+    capture: DisplayCapture,
+}
+
+fn write_frame(dir: &Path, format: FrameFormat, frame: &Frame) -> std::io::Result<String> {
+    let name = format!("frame_{:06}.{}", frame.index, format.extension());
+    let mut file = BufWriter::new(File::create(dir.join(&name))?);
+
+    if format == FrameFormat::Ppm {
+        write!(
+            file,
+            "P6\n{} {}\n255\n",
+            frame.capture.width, frame.capture.height
+        )?;
+    }
+
+    file.write_all(&frame.capture.rgb)?;
+    file.flush()?;
+
+    Ok(name)
+}
+
+fn worker(
+    dir: PathBuf,
+    format: FrameFormat,
+    mut manifest: BufWriter<File>,
+    frames: Receiver<Frame>,
+    logger: Logger,
+) {
+    for frame in frames {
+        let result = write_frame(&dir, format, &frame).and_then(|name| {
+            writeln!(
+                manifest,
+                "{name} {}x{} {:.3}",
+                frame.capture.width, frame.capture.height, frame.duration
+            )
+        });
+
+        if let Err(err) = result {
+            error!(logger, "failed to write frame {}: {err}", frame.index);
+        }
+    }
+
+    if let Err(err) = manifest.flush() {
+        error!(logger, "failed to write the frame dump manifest: {err}");
+    }
+}
+
+/// An ongoing frame dump. Frames are written by a worker thread, so that emulation never waits
+/// on the disk: if the worker can't keep up, frames are dropped with a warning instead.
+///
+/// Next to the frames, a `manifest.txt` lists the format and frame rate and then one line per
+/// frame with its file name, dimensions and duration in microseconds, which is enough to mux the
+/// sequence into a video at the right rate.
+pub struct FrameDump {
+    sender: Option<SyncSender<Frame>>,
+    worker: Option<JoinHandle<()>>,
+    logger: Logger,
+    next_index: u64,
+    dropped: u64,
+}
+
+impl std::fmt::Debug for FrameDump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameDump")
+            .field("next_index", &self.next_index)
+            .field("dropped", &self.dropped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FrameDump {
+    /// Starts dumping frames into `dir`, creating it if needed. `frame_rate` is the nominal frame
+    /// rate of the video mode, recorded in the manifest.
+    pub fn start(
+        dir: PathBuf,
+        format: FrameFormat,
+        frame_rate: f64,
+        logger: Logger,
+    ) -> Result<Self, FrameDumpError> {
+        std::fs::create_dir_all(&dir).context(FrameDumpCtx::CreateDir)?;
+
+        let mut manifest = File::create(dir.join("manifest.txt"))
+            .map(BufWriter::new)
+            .context(FrameDumpCtx::Manifest)?;
+        writeln!(manifest, "format {}", format.extension())
+            .and_then(|()| writeln!(manifest, "frame_rate {frame_rate:.6}"))
+            .context(FrameDumpCtx::Manifest)?;
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LEN);
+        let worker_logger = logger.clone();
+        let worker = std::thread::Builder::new()
+            .name("frame dump".to_owned())
+            .spawn(move || worker(dir, format, manifest, receiver, worker_logger))
+            .expect("spawning a thread should not fail");
+
+        Ok(Self {
+            sender: Some(sender),
+            worker: Some(worker),
+            logger,
+            next_index: 0,
+            dropped: 0,
+        })
+    }
+
+    /// Queues a captured frame, displayed for `duration` microseconds, to be written.
+    pub fn push(&mut self, capture: DisplayCapture, duration: f64) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        let frame = Frame {
+            index: self.next_index,
+            duration,
+            capture,
+        };
+        self.next_index += 1;
+
+        match sender.try_send(frame) {
+            Ok(()) => (),
+            Err(TrySendError::Full(frame)) => {
+                self.dropped += 1;
+                warn!(
+                    self.logger,
+                    "frame dump can't keep up - dropped frame {}", frame.index;
+                    dropped = self.dropped,
+                );
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                error!(self.logger, "frame dump worker stopped unexpectedly");
+                self.sender = None;
+            }
+        }
+    }
+
+    /// How many frames have been dropped because the worker couldn't keep up.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Stops dumping, waiting for the queued frames to be written.
+    pub fn stop(mut self) {
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        // dropping the sender makes the worker exit once the queue is drained
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            _ = worker.join();
+        }
+    }
+}
+
+impl Drop for FrameDump {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        StopReason,
+        testing::{self, Recorder},
+    };
+    use shimmer_core::{CYCLES_MILLIS, CYCLES_SECOND, mem::io};
+
+    #[test]
+    fn dumps_a_file_per_frame() {
+        let dir = testing::scratch_dir("frame_dump");
+        let mut emulator =
+            testing::emulator(testing::builder().renderer(Box::new(Recorder::default())));
+
+        // 320x240, 15-bit, NTSC, and a fill of the displayed area
+        let psx = emulator.psx_mut();
+        testing::write::<u32>(psx, io::Reg::Gp1, 0x0800_0001);
+        for word in [0x0200_00FF, 0x0000_0000, 0x00F0_0140] {
+            testing::write::<u32>(psx, io::Reg::Gp0, word);
+        }
+        emulator.cycle_for(CYCLES_MILLIS);
+
+        emulator
+            .start_frame_dump(dir.clone(), FrameFormat::Ppm)
+            .unwrap();
+        for _ in 0..10 {
+            let result = emulator.run_until(CYCLES_SECOND, &[], |psx| {
+                psx.interrupts.status.to_bits() & 1 != 0
+            });
+            assert_eq!(result.reason, StopReason::Predicate);
+            testing::write::<u32>(emulator.psx_mut(), io::Reg::InterruptStatus, !1);
+        }
+        emulator.stop_frame_dump();
+
+        let header = b"P6\n320 240\n255\n";
+        for index in 0..10 {
+            let frame = std::fs::read(dir.join(format!("frame_{index:06}.ppm"))).unwrap();
+            assert!(frame.starts_with(header), "frame {index}");
+            assert_eq!(frame.len(), header.len() + 320 * 240 * 3, "frame {index}");
+        }
+        assert!(!dir.join("frame_000010.ppm").exists());
+
+        let manifest = std::fs::read_to_string(dir.join("manifest.txt")).unwrap();
+        let lines: Vec<_> = manifest.lines().collect();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "format ppm");
+        // NTSC frames are 566223 cycles long
+        assert_eq!(lines[1], "frame_rate 59.817422");
+        for (index, line) in lines[2..].iter().enumerate() {
+            assert_eq!(*line, format!("frame_{index:06}.ppm 320x240 16717.538"));
+        }
+    }
+}
@@ -3,23 +3,133 @@ pub mod interface;
 mod display;
 mod rendering;
 
-use crate::{PSX, scheduler::Event};
+use crate::{PSX, scheduler::Event, timers, unimplemented};
 use bitos::integer::{u9, u10, u11};
-use interface::{Command, CopyToVram, Renderer, VramCoords, VramDimensions};
+use interface::{
+    Command, CopyFromVram, CopyToVram, DisplayResolution, NullRenderer, Renderer, ResponseHandle,
+    VramCoords, VramDimensions,
+};
 use shimmer_core::{
     gpu::{
-        VerticalResolution,
+        DISPLAY_QUEUE_CAP, DisplayDepth, QueuePush, VerticalResolution,
         cmd::{
-            DisplayCommand, RenderingCommand,
-            rendering::{
-                CoordPacket, LineCmd, ShadingMode, SizePacket, VertexColorPacket,
-                VertexPositionPacket,
+            RenderingCommand,
+            parser::{
+                DecodedDisplayCommand, DecodedRenderingCommand, LineVertex, is_poly_line_end,
             },
+            rendering::{CoordPacket, LineCmd, ShadingMode, SizePacket},
         },
+        vram,
     },
     interrupts::Interrupt,
 };
-use tinylog::{debug, trace};
+use std::time::Duration;
+use tinylog::{debug, error, trace, warn};
+
+/// How long to wait for the renderer to respond to a VRAM readback before giving up.
+const READBACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The displayed area of VRAM, as 24-bit RGB.
+#[derive(Debug, Clone)]
+pub struct DisplayCapture {
+    pub width: u16,
+    pub height: u16,
+    /// The pixels of the display, row by row, 3 bytes per pixel.
+    pub rgb: Vec<u8>,
+}
+
+/// The displayed area of VRAM, i.e. what the display top-left and resolution sent to the renderer
+/// through [`Command::SetDisplayTopLeft`] and [`Command::SetDisplayResolution`] select.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayRect {
+    /// Top-left corner of the displayed area.
+    pub coords: VramCoords,
+    /// Dimensions of the displayed area in VRAM. In 24-bit mode, each pixel takes 1.5 halfwords
+    /// of VRAM, so it's wider than the horizontal resolution.
+    pub dimensions: VramDimensions,
+    pub resolution: DisplayResolution,
+    pub depth: DisplayDepth,
+    /// Aspect ratio (width over height) of the displayed pixels, which frontends should scale the
+    /// display by to show it with the correct proportions.
+    pub pixel_aspect_ratio: f64,
+}
+
+impl DisplayRect {
+    /// Returns the current displayed area of VRAM.
+    pub fn current(psx: &PSX) -> Self {
+        let horizontal = psx.gpu.status.horizontal_resolution();
+        let vertical = psx.gpu.status.vertical_resolution();
+        let depth = psx.gpu.status.display_depth();
+
+        // in 24-bit mode, each pixel takes 1.5 halfwords of VRAM
+        let width = match depth {
+            DisplayDepth::Limited => horizontal.value(),
+            DisplayDepth::Full => (horizontal.value() * 3).div_ceil(2),
+        };
+
+        Self {
+            coords: VramCoords {
+                x: psx.gpu.display.top_left_x,
+                y: psx.gpu.display.top_left_y,
+            },
+            dimensions: VramDimensions {
+                width: u11::new(width),
+                height: u10::new(vertical.value()),
+            },
+            resolution: DisplayResolution {
+                horizontal,
+                vertical,
+            },
+            depth,
+            pixel_aspect_ratio: psx.gpu.pixel_aspect_ratio(),
+        }
+    }
+}
+
+/// Pushes a word onto the render queue, as if written to GP0, warning if it's dropped because the
+/// queue is full.
+///
+/// This is the only way GP0 words reach the GPU, whether they're written by the CPU or delivered
+/// by DMA, so that the command parser sees a single stream of words in the order they arrived.
+pub(crate) fn push_gp0(psx: &mut PSX, word: u32) {
+    if psx.gpu.push_gp0(word) == QueuePush::Overflowed {
+        warn!(
+            psx.loggers.gpu,
+            "render queue is full, dropping GP0 words";
+            cap = psx.gpu.watchdog.cap,
+        );
+    }
+
+    psx.gpu.update_readiness();
+}
+
+/// Pushes a word onto the display queue, as if written to GP1, warning if it's dropped because
+/// the queue is full.
+pub(crate) fn push_gp1(psx: &mut PSX, word: u32) {
+    if psx.gpu.push_gp1(word) == QueuePush::Overflowed {
+        warn!(
+            psx.loggers.gpu,
+            "display queue is full, dropping GP1 words";
+            cap = DISPLAY_QUEUE_CAP,
+        );
+    }
+}
+
+/// Schedules the first VBlank. The beam starts at the top of the frame, so vertical blanking
+/// starts once it reaches the end of the display range, and not right away.
+pub(crate) fn start(psx: &mut PSX) {
+    let (_, display_end) = psx.gpu.display_lines();
+    psx.scheduler.schedule(
+        Event::VBlank,
+        psx.gpu.timing().cycles_between(0, display_end),
+    );
+}
+
+/// Expands a 5-bit color component to 8 bits.
+fn expand_5bit(value: u16) -> u8 {
+    let value = (value & 0x1F) as u8;
+    (value << 3) | (value >> 2)
+}
 
 /// The state of the interpreter.
 #[derive(Debug, Clone, Default)]
@@ -30,6 +140,7 @@ enum State {
     CpuToVramBlit {
         dest: CoordPacket,
         size: SizePacket,
+        data: Vec<u8>,
     },
     PolyLine {
         cmd: LineCmd,
@@ -41,26 +152,136 @@ enum State {
 pub struct Gpu {
     inner: State,
     renderer: Box<dyn Renderer>,
+    /// Whether `renderer` was attached by the frontend, as opposed to being the fallback
+    /// [`NullRenderer`].
+    attached: bool,
+}
+
+impl Default for Gpu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Gpu {
-    pub fn new(renderer: impl Renderer + 'static) -> Self {
+    /// Creates a new [`Gpu`] with no renderer attached. Until one is, commands are executed by a
+    /// [`NullRenderer`].
+    pub fn new() -> Self {
         Self {
             inner: State::default(),
-            renderer: Box::new(renderer),
+            renderer: Box::new(NullRenderer),
+            attached: false,
         }
     }
 
+    /// Replaces the renderer with `renderer`, or with a [`NullRenderer`] if [`None`]. Returns the
+    /// previous renderer, unless it was the fallback one.
+    ///
+    /// Commands are never split between renderers: a CPU to VRAM blit still waiting for data is
+    /// sent to the new renderer once complete. The previous renderer is sent a
+    /// [`Command::VBlank`] so that it presents everything drawn so far, and its VRAM is copied to
+    /// the new renderer. Then, the drawing and display settings are replayed to the new renderer,
+    /// so that it's in the same state as the previous one before it receives any primitive.
+    pub fn swap_renderer(
+        &mut self,
+        psx: &mut PSX,
+        renderer: Option<Box<dyn Renderer>>,
+    ) -> Option<Box<dyn Renderer>> {
+        let was_attached = self.attached;
+        self.attached = renderer.is_some();
+
+        let renderer = renderer.unwrap_or_else(|| Box::new(NullRenderer));
+        let mut previous = std::mem::replace(&mut self.renderer, renderer);
+        previous.exec(Command::VBlank);
+
+        // the fallback renderer has nothing worth copying
+        if was_attached {
+            let vram = VramDimensions {
+                width: u11::new(vram::VRAM_WIDTH),
+                height: u10::new(vram::VRAM_HEIGHT),
+            };
+            let origin = VramCoords {
+                x: u10::new(0),
+                y: u9::new(0),
+            };
+
+            let (response, receiver) = ResponseHandle::channel();
+            previous.exec(Command::CopyFromVram {
+                request: CopyFromVram {
+                    coords: origin,
+                    dimensions: vram,
+                },
+                response,
+            });
+
+            match receiver.recv_timeout(READBACK_TIMEOUT) {
+                Ok(data) => self.renderer.exec(Command::CopyToVram(CopyToVram {
+                    coords: origin,
+                    dimensions: vram,
+                    data,
+                })),
+                Err(_) => error!(
+                    psx.loggers.gpu,
+                    "previous renderer didn't respond to the VRAM readback - new renderer starts \
+                     with empty VRAM"
+                ),
+            }
+        }
+
+        self.replay_settings(psx);
+        was_attached.then_some(previous)
+    }
+
+    /// Abandons the command being received, if any, such as a CPU to VRAM blit still waiting for
+    /// data. Used when the command buffer is reset.
+    fn abort_command(&mut self, psx: &mut PSX) {
+        if let State::CpuToVramBlit { dest, size, data } = &self.inner {
+            warn!(
+                psx.loggers.gpu,
+                "aborting CPU to VRAM blit";
+                dest = dest, size = size, received = data.len() / 4,
+            );
+        }
+
+        self.inner = State::Idle;
+        psx.gpu.receiving_blit = false;
+        psx.gpu.update_readiness();
+    }
+
+    /// Sends every drawing and display setting to the renderer.
+    fn replay_settings(&mut self, psx: &mut PSX) {
+        self.renderer_exec_drawing_settings(psx);
+        self.renderer_exec_drawing_area(psx);
+        self.renderer
+            .exec(Command::SetTexWindow(psx.gpu.environment.texwindow));
+
+        self.renderer.exec(Command::SetDisplayTopLeft(VramCoords {
+            x: psx.gpu.display.top_left_x,
+            y: psx.gpu.display.top_left_y,
+        }));
+        self.renderer
+            .exec(Command::SetDisplayResolution(DisplayResolution {
+                horizontal: psx.gpu.status.horizontal_resolution(),
+                vertical: psx.gpu.status.vertical_resolution(),
+            }));
+        self.renderer.exec(Command::SetDisplayEnabled(
+            !psx.gpu.status.disable_display(),
+        ));
+        self.renderer
+            .exec(Command::SetDisplayFlipped(psx.gpu.status.flip_screen_x()));
+    }
+
     fn exec_queued_render(&mut self, psx: &mut PSX) {
         loop {
             match &mut self.inner {
                 State::Idle => {
-                    let Some(packet) = psx.gpu.render_queue.front() else {
+                    let Some(&packet) = psx.gpu.render_queue.front() else {
                         return;
                     };
 
-                    let cmd = RenderingCommand::from_bits(*packet);
-                    if psx.gpu.render_queue.len() <= cmd.args() {
+                    let words = psx.gpu.render_queue.make_contiguous();
+                    let Some((cmd, len)) = DecodedRenderingCommand::decode(words) else {
+                        let cmd = RenderingCommand::from_bits(packet);
                         trace!(
                             psx.loggers.gpu,
                             "{cmd:?} is waiting for arguments (has {}/{})",
@@ -68,32 +289,18 @@ impl Gpu {
                             cmd.args(),
                         );
                         return;
-                    }
+                    };
 
-                    psx.gpu.render_queue.pop_front();
+                    psx.gpu.render_queue.drain(..len);
                     self.exec_render(psx, cmd);
                 }
-                State::CpuToVramBlit { dest, size } => {
-                    let effective_width = if size.width() == 0 {
-                        0x400
-                    } else {
-                        ((size.width() - 1) & 0x3FF) + 1
-                    };
-
-                    let effective_height = if size.height() == 0 {
-                        0x200
-                    } else {
-                        ((size.height() - 1) & 0x1FF) + 1
-                    };
-
-                    let count =
-                        (u32::from(effective_width) * u32::from(effective_height)).div_ceil(2);
-                    if psx.gpu.render_queue.len() < count as usize {
-                        return;
-                    }
+                State::CpuToVramBlit { dest, size, data } => {
+                    // consume packets as they arrive so that the render queue doesn't have to
+                    // hold the whole blit
+                    let remaining = size.words() - data.len() / 4;
+                    let available = remaining.min(psx.gpu.render_queue.len());
 
-                    let mut data = Vec::with_capacity(count as usize * 4);
-                    for _ in 0..count {
+                    for _ in 0..available {
                         use zerocopy::byteorder::{LE, U16};
 
                         let packet = psx.gpu.render_queue.pop_front().unwrap();
@@ -108,29 +315,41 @@ impl Gpu {
                         data.extend(b.to_bytes());
                     }
 
+                    if available < remaining {
+                        return;
+                    }
+
+                    // blits are sent in whole words, so the last halfword of a blit with an odd
+                    // amount of pixels is padding
+                    let mut data = std::mem::take(data);
+                    let rect = vram::blit_rect(*dest, *size);
+                    data.truncate(usize::from(rect.width) * usize::from(rect.height) * 2);
+
                     self.renderer.exec(Command::CopyToVram(CopyToVram {
                         coords: VramCoords {
-                            x: u10::new(dest.x()),
-                            y: u9::new(dest.y()),
+                            x: u10::new(rect.x),
+                            y: u9::new(rect.y),
                         },
                         dimensions: VramDimensions {
-                            width: u11::new(effective_width),
-                            height: u10::new(effective_height),
+                            width: u11::new(rect.width),
+                            height: u10::new(rect.height),
                         },
                         data,
                     }));
 
                     self.inner = State::Idle;
 
+                    psx.gpu.receiving_blit = false;
                     psx.gpu.status.set_ready_to_send_vram(false);
+                    psx.gpu.update_readiness();
                     psx.scheduler.schedule(Event::DmaUpdate, 0);
                 }
                 State::PolyLine { cmd, received } => {
-                    let Some(front) = psx.gpu.render_queue.front() else {
+                    let Some(&front) = psx.gpu.render_queue.front() else {
                         return;
                     };
 
-                    if *received >= 2 && (front & 0xF000_F000 == 0x5000_5000) {
+                    if *received >= 2 && is_poly_line_end(front) {
                         debug!(psx.loggers.gpu, "exiting polyline mode",);
                         psx.gpu.render_queue.pop_front();
                         self.inner = State::Idle;
@@ -138,39 +357,17 @@ impl Gpu {
                         return;
                     }
 
-                    match (cmd.shading_mode(), psx.gpu.render_queue.len()) {
-                        (ShadingMode::Flat, _) => {
-                            debug!(
-                                psx.loggers.gpu,
-                                "vertex: {:?}",
-                                VertexPositionPacket::from_bits(
-                                    psx.gpu.render_queue.pop_front().unwrap()
-                                )
-                            );
-
-                            *received += 1;
-                        }
-                        (ShadingMode::Gouraud, x) if x >= 2 => {
-                            debug!(
-                                psx.loggers.gpu,
-                                "gouraud: {:?}",
-                                VertexColorPacket::from_bits(
-                                    psx.gpu.render_queue.pop_front().unwrap()
-                                )
-                            );
-
-                            debug!(
-                                psx.loggers.gpu,
-                                "vertex: {:?}",
-                                VertexPositionPacket::from_bits(
-                                    psx.gpu.render_queue.pop_front().unwrap()
-                                )
-                            );
-
-                            *received += 1;
-                        }
-                        _ => (),
-                    }
+                    let color = (*received == 0 || cmd.shading_mode() == ShadingMode::Flat)
+                        .then_some(cmd.color());
+                    let words = psx.gpu.render_queue.make_contiguous();
+                    let Some((vertex, len)) = LineVertex::decode(words, color) else {
+                        return;
+                    };
+
+                    psx.gpu.render_queue.drain(..len);
+                    debug!(psx.loggers.gpu, "poly-line vertex: {vertex:?}");
+
+                    *received += 1;
                 }
             }
         }
@@ -178,7 +375,7 @@ impl Gpu {
 
     fn exec_queued_display(&mut self, psx: &mut PSX) {
         while let Some(packet) = psx.gpu.display_queue.pop_front() {
-            let cmd = DisplayCommand::from_bits(packet);
+            let cmd = DecodedDisplayCommand::decode(packet);
             self.exec_display(psx, cmd);
         }
     }
@@ -187,23 +384,725 @@ impl Gpu {
     pub fn exec_queued(&mut self, psx: &mut PSX) {
         self.exec_queued_display(psx);
         self.exec_queued_render(psx);
+        psx.gpu.update_readiness();
+    }
+
+    /// Captures the displayed area of VRAM by reading it back from the renderer. Returns [`None`]
+    /// if the renderer doesn't respond in time.
+    pub fn capture_display(&mut self, psx: &PSX) -> Option<DisplayCapture> {
+        let rect = DisplayRect::current(psx);
+        let width = rect.resolution.horizontal.value();
+        let height = rect.resolution.vertical.value();
+        let depth = rect.depth;
+        let vram_width = rect.dimensions.width.value();
+
+        let (response, receiver) = ResponseHandle::channel();
+        let request = CopyFromVram {
+            coords: rect.coords,
+            dimensions: rect.dimensions,
+        };
+        self.renderer
+            .exec(Command::CopyFromVram { request, response });
+
+        let Ok(data) = receiver.recv_timeout(READBACK_TIMEOUT) else {
+            error!(
+                psx.loggers.gpu,
+                "renderer didn't respond to the display capture"
+            );
+            return None;
+        };
+
+        let stride = usize::from(vram_width) * 2;
+        let mut rgb = Vec::with_capacity(usize::from(width) * usize::from(height) * 3);
+        for row in data.chunks_exact(stride) {
+            match depth {
+                DisplayDepth::Limited => {
+                    for pixel in row.chunks_exact(2) {
+                        let pixel = u16::from_le_bytes([pixel[0], pixel[1]]);
+                        rgb.extend([
+                            expand_5bit(pixel),
+                            expand_5bit(pixel >> 5),
+                            expand_5bit(pixel >> 10),
+                        ]);
+                    }
+                }
+                DisplayDepth::Full => rgb.extend_from_slice(&row[..usize::from(width) * 3]),
+            }
+        }
+
+        if psx.gpu.status.disable_display() {
+            rgb.fill(0);
+        }
+
+        Some(DisplayCapture { width, height, rgb })
     }
 
-    /// Performs a VBlank.
+    /// Enters vertical blanking. This happens at the end of the vertical display range, and is
+    /// when the VBlank interrupt is requested, the field flips and timer 1 synchronizes. The frame
+    /// is presented separately, on the [`Event::Present`] this schedules.
     pub fn vblank(&mut self, psx: &mut PSX) {
         trace!(psx.loggers.gpu, "== VBLANK ==");
+        timers::sync(psx, timers::Id::Timer1);
+        psx.gpu.in_vblank = true;
+        psx.gpu.odd_field =
+            psx.gpu.status.vertical_resolution() == VerticalResolution::R480 && !psx.gpu.odd_field;
+
+        // reads as even during vblank
+        psx.gpu.status.set_interlace_odd(false);
+
+        psx.interrupts.status.request(Interrupt::VBlank);
+        psx.timers.timer1.vblank_started();
+
+        // the field being displayed affects drawing to the display area
         if psx.gpu.status.vertical_resolution() == VerticalResolution::R480 {
-            psx.gpu
-                .status
-                .set_interlace_odd(!psx.gpu.status.interlace_odd());
-        } else {
-            psx.gpu.status.set_interlace_odd(false);
+            self.renderer_exec_drawing_settings(psx);
         }
 
-        psx.interrupts.status.request(Interrupt::VBlank);
-        psx.scheduler
-            .schedule(Event::VBlank, u64::from(psx.gpu.cycles_per_vblank()));
+        timers::reschedule(psx, timers::Id::Timer1);
 
+        let (display_start, display_end) = psx.gpu.display_lines();
+        psx.scheduler.schedule(
+            Event::VBlankEnd,
+            psx.gpu.timing().cycles_between(display_end, display_start),
+        );
+        psx.scheduler.schedule(Event::Present, 0);
+    }
+
+    /// Tells the renderer to present everything drawn so far as a frame.
+    pub fn present(&mut self, psx: &mut PSX) {
         self.renderer.exec(Command::VBlank);
+        unimplemented::log_summary(psx);
+    }
+
+    /// Leaves vertical blanking. This happens at the start of the vertical display range.
+    #[expect(clippy::unused_self, reason = "consistency")]
+    pub fn vblank_end(&mut self, psx: &mut PSX) {
+        timers::sync(psx, timers::Id::Timer1);
+        psx.gpu.in_vblank = false;
+        timers::reschedule(psx, timers::Id::Timer1);
+
+        psx.gpu.status.set_interlace_odd(psx.gpu.odd_field);
+
+        let (display_start, display_end) = psx.gpu.display_lines();
+        psx.scheduler.schedule(
+            Event::VBlank,
+            psx.gpu.timing().cycles_between(display_start, display_end),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        READBACK_TIMEOUT,
+        interface::{
+            Command, DrawingArea, Primitive, Renderer, ResponseHandle, soft_vram::SoftVram,
+        },
+    };
+    use crate::{
+        Emulator, StopReason,
+        testing::{self, Recorded, Recorder},
+    };
+    use shimmer_core::{
+        CYCLES_MILLIS, CYCLES_SECOND, Cycles,
+        gpu::{DEFAULT_QUEUE_CAP, DISPLAY_QUEUE_CAP, INFO_RESPONSE_CAP, VideoMode},
+        interrupts::Interrupt,
+        mem::{Address, io},
+    };
+    use std::{
+        sync::{Arc, Mutex},
+        time::Instant,
+    };
+
+    /// NTSC frame rate, with 263 scanlines of 3413 video cycles at 53.693182 MHz.
+    const NTSC_FRAME_RATE: f64 = 59.826;
+    /// Length of an NTSC scanline, in seconds.
+    const NTSC_SCANLINE: f64 = 1.0 / (NTSC_FRAME_RATE * 263.0);
+    /// The texture disable bit of GPUSTAT.
+    const TEXTURE_DISABLE: u32 = 1 << 15;
+
+    fn assert_cycles_near(cycles: Cycles, seconds: f64) {
+        let expected = seconds * CYCLES_SECOND as f64;
+        let error = (cycles as f64 - expected).abs() / expected;
+        assert!(error < 0.0005, "{cycles} cycles, expected about {expected}");
+    }
+
+    #[test]
+    fn vblank_phase_matches_ntsc() {
+        let mut emulator = testing::emulator(testing::builder());
+
+        // timer 1 counts system cycles and resets at the start of vertical blanking
+        testing::write::<u16>(emulator.psx_mut(), io::Reg::Timer1Mode, 0b011);
+
+        let mut starts = Vec::new();
+        let mut ends = Vec::new();
+        for _ in 0..3 {
+            let result = emulator.run_until(CYCLES_SECOND, &[], |psx| {
+                psx.interrupts.status.to_bits() & 1 != 0
+            });
+            assert_eq!(result.reason, StopReason::Predicate);
+            starts.push(emulator.elapsed_cycles());
+
+            // IRQ0 is requested on the same edge vertical blanking starts and timer 1 resets
+            let psx = emulator.psx_mut();
+            assert!(psx.gpu.in_vblank);
+            assert!(!psx.gpu.status.interlace_odd());
+            assert!(testing::read::<u16>(psx, io::Reg::Timer1Value) < 16);
+            testing::write::<u32>(psx, io::Reg::InterruptStatus, !1);
+
+            let result = emulator.run_until(CYCLES_SECOND, &[], |psx| !psx.gpu.in_vblank);
+            assert_eq!(result.reason, StopReason::Predicate);
+            ends.push(emulator.elapsed_cycles());
+
+            // and isn't requested again until the next frame
+            let psx = emulator.psx_mut();
+            assert_eq!(testing::read::<u32>(psx, io::Reg::InterruptStatus) & 1, 0);
+        }
+
+        // the default display area spans scanlines 16 to 256, so blanking lasts 23 of them
+        for (start, end) in starts.iter().zip(&ends) {
+            assert_cycles_near(end - start, 23.0 * NTSC_SCANLINE);
+        }
+
+        for frame in starts.windows(2) {
+            assert_cycles_near(frame[1] - frame[0], 1.0 / NTSC_FRAME_RATE);
+        }
+    }
+
+    #[test]
+    fn gp0_flood_keeps_the_render_queue_bounded() {
+        const WORDS: usize = 4 * DEFAULT_QUEUE_CAP;
+
+        // a program ignoring the readiness bits, writing faster than the GPU consumes
+        let mut emulator = testing::emulator(testing::builder());
+        let psx = emulator.psx_mut();
+        for _ in 0..WORDS {
+            testing::write::<u32>(psx, io::Reg::Gp0, 0);
+        }
+
+        assert_eq!(psx.gpu.render_queue.len(), DEFAULT_QUEUE_CAP);
+        assert_eq!(psx.gpu.watchdog.dropped, (WORDS - DEFAULT_QUEUE_CAP) as u64);
+        assert!(psx.gpu.watchdog.overflowing);
+        assert!(psx.gpu.render_queue_full());
+
+        // once drained, words are accepted again
+        let result = emulator.run_until(CYCLES_SECOND, &[], |psx| psx.gpu.render_queue.is_empty());
+        assert_eq!(result.reason, StopReason::Predicate);
+
+        let psx = emulator.psx_mut();
+        testing::write::<u32>(psx, io::Reg::Gp0, 0);
+        assert!(!psx.gpu.watchdog.overflowing);
+        assert_eq!(psx.gpu.watchdog.dropped, (WORDS - DEFAULT_QUEUE_CAP) as u64);
+    }
+
+    #[test]
+    fn gp1_flood_keeps_the_display_queue_bounded() {
+        let mut emulator = testing::emulator(testing::builder());
+        let psx = emulator.psx_mut();
+        for _ in 0..4 * DISPLAY_QUEUE_CAP {
+            // display enable
+            testing::write::<u32>(psx, io::Reg::Gp1, 0x0300_0000);
+        }
+
+        assert_eq!(psx.gpu.display_queue.len(), DISPLAY_QUEUE_CAP);
+        assert_eq!(psx.gpu.watchdog.dropped, 3 * DISPLAY_QUEUE_CAP as u64);
+    }
+
+    /// What a [`Log`] renderer received.
+    #[derive(Debug, Clone, Copy)]
+    enum Logged {
+        DrawingSettings {
+            write_to_mask: bool,
+            check_mask: bool,
+        },
+        DrawingArea(DrawingArea),
+        TexWindow(u32),
+        Draw,
+        Other,
+    }
+
+    /// A renderer which logs the settings and primitives it receives.
+    #[derive(Debug, Clone, Default)]
+    struct Log(Arc<Mutex<Vec<Logged>>>);
+
+    impl Renderer for Log {
+        fn exec(&mut self, command: Command) {
+            let logged = match command {
+                Command::SetDrawingSettings(settings) => Logged::DrawingSettings {
+                    write_to_mask: settings.write_to_mask,
+                    check_mask: settings.check_mask,
+                },
+                Command::SetDrawingArea(area) => Logged::DrawingArea(area),
+                Command::SetTexWindow(window) => Logged::TexWindow(window.to_bits()),
+                Command::Draw { .. } => Logged::Draw,
+                Command::CopyFromVram { request, response } => {
+                    let len = usize::from(request.dimensions.width.value())
+                        * usize::from(request.dimensions.height.value())
+                        * 2;
+                    response.respond(vec![0; len]);
+                    Logged::Other
+                }
+                _ => Logged::Other,
+            };
+
+            self.0.lock().unwrap().push(logged);
+        }
+    }
+
+    fn gp0(emulator: &mut Emulator, words: &[u32]) {
+        for &word in words {
+            testing::write::<u32>(emulator.psx_mut(), io::Reg::Gp0, word);
+        }
+
+        let result = emulator.run_until(CYCLES_SECOND, &[], |psx| psx.gpu.render_queue.is_empty());
+        assert_eq!(result.reason, StopReason::Predicate);
+    }
+
+    fn gp1(emulator: &mut Emulator, word: u32) {
+        testing::write::<u32>(emulator.psx_mut(), io::Reg::Gp1, word);
+
+        let result = emulator.run_until(CYCLES_SECOND, &[], |psx| psx.gpu.display_queue.is_empty());
+        assert_eq!(result.reason, StopReason::Predicate);
+    }
+
+    fn gpustat(emulator: &mut Emulator) -> u32 {
+        testing::read::<u32>(emulator.psx_mut(), io::Reg::Gp1)
+    }
+
+    #[test]
+    fn texture_disable_bit_needs_to_be_allowed() {
+        let mut emulator = testing::emulator(testing::builder());
+
+        // draw mode with the texture disable bit set
+        gp0(&mut emulator, &[0xE100_0800]);
+        assert_eq!(gpustat(&mut emulator) & TEXTURE_DISABLE, 0);
+
+        // GP1(09h) allows it
+        gp1(&mut emulator, 0x0900_0001);
+        gp0(&mut emulator, &[0xE100_0800]);
+        assert_ne!(gpustat(&mut emulator) & TEXTURE_DISABLE, 0);
+
+        // and disallowing it clears the bit on the next draw mode
+        gp1(&mut emulator, 0x0900_0000);
+        gp0(&mut emulator, &[0xE100_0800]);
+        assert_eq!(gpustat(&mut emulator) & TEXTURE_DISABLE, 0);
+    }
+
+    #[test]
+    fn texpage_y_base_is_a_single_bit() {
+        let mut emulator = testing::emulator(testing::builder());
+        gp1(&mut emulator, 0x0900_0001);
+
+        // y base at line 256, the last one that can be represented
+        gp0(&mut emulator, &[0xE100_0010]);
+        let psx = emulator.psx_mut();
+        assert_eq!(psx.gpu.status.texpage().y_base().value(), 1);
+        assert_eq!(gpustat(&mut emulator) & 0x10, 0x10);
+
+        // bit 11 never extends it to line 512, as it would with 2 MiB of VRAM
+        gp0(&mut emulator, &[0xE100_0800]);
+        let psx = emulator.psx_mut();
+        assert_eq!(psx.gpu.status.texpage().y_base().value(), 0);
+        assert_eq!(gpustat(&mut emulator) & 0x10, 0);
+
+        gp0(&mut emulator, &[0xE100_0810]);
+        let psx = emulator.psx_mut();
+        assert_eq!(psx.gpu.status.texpage().y_base().value(), 1);
+        assert_eq!(
+            gpustat(&mut emulator) & (0x10 | TEXTURE_DISABLE),
+            0x10 | TEXTURE_DISABLE
+        );
+    }
+
+    #[test]
+    fn gp0_words_from_the_cpu_and_dma_form_one_command() {
+        const DATA: Address = Address(0x8000_2000);
+
+        let recorder = Recorder::default();
+        let mut emulator =
+            testing::emulator(testing::builder().renderer(Box::new(recorder.clone())));
+
+        // GP0(02h) quick rectangle fill, with its three words coming from the CPU, then DMA, then
+        // the CPU again
+        testing::write::<u32>(emulator.psx_mut(), io::Reg::Gp0, 0x0200_00FF);
+
+        emulator.load_raw(DATA, &0x0020_0010u32.to_le_bytes());
+        let psx = emulator.psx_mut();
+        let dma_control = psx.dma.control.to_bits();
+        testing::write::<u32>(psx, io::Reg::DmaControl, dma_control | (1 << 11));
+        testing::write::<u32>(psx, io::Reg::Dma2Base, DATA.value());
+        testing::write::<u32>(psx, io::Reg::Dma2BlockControl, 0x0001_0001);
+        // from RAM, slice mode, started and forced
+        testing::write::<u32>(psx, io::Reg::Dma2Control, 0x1100_0201);
+        emulator.cycle_for(CYCLES_MILLIS);
+
+        testing::write::<u32>(emulator.psx_mut(), io::Reg::Gp0, 0x0008_0010);
+        emulator.cycle_for(CYCLES_MILLIS);
+
+        let rects: Vec<_> = recorder
+            .take_primitives()
+            .into_iter()
+            .map(|primitive| {
+                let Primitive::Rectangle(rect) = primitive else {
+                    panic!("expected a rectangle, got {primitive:?}");
+                };
+
+                let top_left = rect.top_left;
+                (
+                    top_left.x.value(),
+                    top_left.y.value(),
+                    rect.width,
+                    rect.height,
+                    top_left.color.r,
+                )
+            })
+            .collect();
+
+        assert_eq!(rects, [(0x10, 0x20, 0x10, 0x08, 0xFF)]);
+    }
+
+    #[test]
+    fn blit_data_that_looks_like_a_command_is_taken_as_data() {
+        const READY_TO_RECEIVE_CMD: u32 = 1 << 26;
+
+        let recorder = Recorder::default();
+        let mut emulator =
+            testing::emulator(testing::builder().renderer(Box::new(recorder.clone())));
+
+        // GP0(A0h) CPU to VRAM blit of 3x1 pixels, which takes two words. The second one looks
+        // like a GP0(60h) monochrome rectangle, but must be taken as data
+        gp0(&mut emulator, &[0xA000_0000, 0x0000_0000, 0x0001_0003]);
+        assert_eq!(gpustat(&mut emulator) & READY_TO_RECEIVE_CMD, 0);
+
+        gp0(&mut emulator, &[0x2222_1111, 0x6000_3333]);
+        assert_ne!(gpustat(&mut emulator) & READY_TO_RECEIVE_CMD, 0);
+
+        // an actual GP0(60h) monochrome rectangle
+        gp0(&mut emulator, &[0x6000_00FF, 0x0000_0000, 0x0001_0001]);
+
+        let recorded = recorder.take();
+        let [
+            Recorded::CopyToVram(copy),
+            Recorded::Draw(Primitive::Rectangle(rect)),
+        ] = recorded.as_slice()
+        else {
+            panic!("expected a copy and a rectangle, got {recorded:?}");
+        };
+
+        assert_eq!(copy.dimensions.width.value(), 3);
+        assert_eq!(copy.dimensions.height.value(), 1);
+        assert_eq!(copy.data, [0x11, 0x11, 0x22, 0x22, 0x33, 0x33]);
+        assert_eq!(rect.top_left.color.r, 0xFF);
+    }
+
+    #[test]
+    fn null_renderer_runs_commands_and_reads_back_zeroes() {
+        // no renderer is attached, so the GPU uses the null renderer
+        let mut emulator = testing::emulator(testing::builder());
+
+        gp0(
+            &mut emulator,
+            &[
+                // GP0(02h) fill a 16x16 rectangle at (0, 0) with white
+                0x02FF_FFFF,
+                0x0000_0000,
+                0x0010_0010,
+                // GP0(60h) monochrome rectangle
+                0x6000_00FF,
+                0x0000_0000,
+                0x0001_0001,
+                // GP0(A0h) CPU to VRAM blit of 2x1 pixels at (0, 0)
+                0xA000_0000,
+                0x0000_0000,
+                0x0001_0002,
+                0x1234_5678,
+                // GP0(C0h) VRAM to CPU blit of the same pixels
+                0xC000_0000,
+                0x0000_0000,
+                0x0001_0002,
+            ],
+        );
+
+        assert_eq!(testing::read::<u32>(emulator.psx_mut(), io::Reg::Gp0), 0);
+    }
+
+    #[test]
+    fn late_renderer_receives_settings_before_primitives() {
+        let mut emulator = testing::emulator(testing::builder());
+
+        // texture window, drawing area from (16, 8) to (335, 247) and both mask bits
+        gp0(
+            &mut emulator,
+            &[0xE200_0421, 0xE300_2010, 0xE403_DD4F, 0xE600_0003],
+        );
+
+        // headless for 100 frames
+        for _ in 0..100 {
+            let result = emulator.run_until(CYCLES_SECOND, &[], |psx| {
+                psx.interrupts.status.to_bits() & 1 != 0
+            });
+            assert_eq!(result.reason, StopReason::Predicate);
+            testing::write::<u32>(emulator.psx_mut(), io::Reg::InterruptStatus, !1);
+        }
+
+        let log = Log::default();
+        assert!(emulator.set_renderer(Box::new(log.clone())).is_none());
+
+        // a 16x16 flat rectangle
+        gp0(&mut emulator, &[0x6000_00FF, 0x0010_0010, 0x0010_0010]);
+
+        let logged = log.0.lock().unwrap().clone();
+        let draw = logged
+            .iter()
+            .position(|logged| matches!(logged, Logged::Draw))
+            .expect("the rectangle is drawn");
+
+        let before = &logged[..draw];
+        assert!(before.iter().any(|logged| matches!(
+            logged,
+            Logged::DrawingSettings {
+                write_to_mask: true,
+                check_mask: true
+            }
+        )));
+        assert!(
+            before
+                .iter()
+                .any(|logged| matches!(logged, Logged::TexWindow(0x421)))
+        );
+        assert!(before.iter().any(|logged| matches!(
+            logged,
+            Logged::DrawingArea(area) if area.coords.x.value() == 16 && area.coords.y.value() == 8
+        )));
+    }
+
+    #[test]
+    fn response_and_display_queues_stay_bounded() {
+        const ITERATIONS: usize = 1_000_000;
+
+        // a program requesting GPU info and 2x2 VRAM to CPU blits without ever reading GPUREAD
+        let mut emulator = testing::emulator(testing::builder());
+        let mut capacities = None;
+        for i in 0..ITERATIONS {
+            let psx = &mut emulator.psx;
+            super::push_gp1(psx, 0x1000_0007);
+            for word in [0xC000_0000, 0x0000_0000, 0x0002_0002] {
+                super::push_gp0(psx, word);
+            }
+
+            emulator.gpu.exec_queued(&mut emulator.psx);
+
+            let gpu = &emulator.psx.gpu;
+            assert!(gpu.response_queue.len() <= INFO_RESPONSE_CAP + 2);
+            assert!(gpu.display_queue.is_empty());
+            assert!(gpu.render_queue.is_empty());
+
+            let current = (gpu.display_queue.capacity(), gpu.render_queue.capacity());
+            match capacities {
+                // give the queues a few iterations to reach their working size
+                _ if i < 100 => (),
+                None => capacities = Some(current),
+                Some(capacities) => assert_eq!(current, capacities, "grew at iteration {i}"),
+            }
+        }
+
+        assert_eq!(emulator.psx.gpu.watchdog.dropped, 0);
+    }
+
+    #[test]
+    fn vblank_is_requested_once_per_frame_in_both_modes() {
+        // enough for the first frame, timed before the mode changed, to go by
+        const WARMUP: Cycles = 50 * CYCLES_MILLIS;
+
+        for mode in [VideoMode::NTSC, VideoMode::PAL] {
+            let mut emulator = testing::emulator(testing::builder());
+            emulator.psx.gpu.status.set_video_mode(mode);
+            emulator.cycle_for(WARMUP);
+
+            let end = emulator.elapsed_cycles() + CYCLES_SECOND;
+            let mut requested = 0;
+            while emulator.elapsed_cycles() < end {
+                emulator.cycle_for(CYCLES_MILLIS);
+
+                let status = testing::read::<u32>(&mut emulator.psx, io::Reg::InterruptStatus);
+                if status & 1 != 0 {
+                    requested += 1;
+                    testing::write::<u32>(&mut emulator.psx, io::Reg::InterruptStatus, !1);
+                }
+            }
+
+            let frame = emulator.psx.gpu.timing().cycles_per_frame();
+            let expected = CYCLES_SECOND as f64 / frame as f64;
+            assert!(
+                (f64::from(requested) - expected).abs() <= 1.0,
+                "{requested} VBlanks in a second in {mode:?} mode, expected {expected:.2}"
+            );
+        }
+    }
+
+    #[test]
+    fn vblank_is_requested_at_the_end_of_the_display_range() {
+        let mut emulator = testing::emulator(testing::builder());
+
+        let timing = emulator.psx.gpu.timing();
+        let (_, display_end) = emulator.psx.gpu.display_lines();
+        let scanline = timing.cycles_between(0, 1);
+        let expected = timing.cycles_between(0, display_end);
+
+        let mut frame_start = 0;
+        for frame in 0..2 {
+            let result = emulator.run_until(2 * timing.cycles_per_frame(), &[], |psx| {
+                psx.interrupts.status.status()[Interrupt::VBlank as usize]
+            });
+            assert_eq!(result.reason, StopReason::Predicate, "frame {frame}");
+
+            let elapsed = emulator.elapsed_cycles() - frame_start;
+            assert!(
+                elapsed.abs_diff(expected) < scanline,
+                "requested at scanline {} of frame {frame}, expected {display_end}",
+                elapsed / scanline
+            );
+
+            testing::write::<u32>(&mut emulator.psx, io::Reg::InterruptStatus, !1);
+            frame_start += timing.cycles_per_frame();
+        }
+    }
+
+    #[test]
+    fn display_rect_follows_the_display_area_and_mode() {
+        let mut emulator = testing::emulator(testing::builder());
+
+        // GP1(05h) display area start and GP1(08h) display mode, along with the expected top-left,
+        // size in VRAM and resolution
+        let cases = [
+            // (64, 256), 320x240, 15-bit
+            (0x0504_0040, 0x0800_0001, (64, 256), (320, 240), (320, 240)),
+            // (512, 0), 640x480 interlaced, 24-bit
+            (0x0500_0200, 0x0800_0037, (512, 0), (960, 480), (640, 480)),
+        ];
+
+        for (area, mode, coords, dimensions, resolution) in cases {
+            gp1(&mut emulator, area);
+            gp1(&mut emulator, mode);
+
+            let rect = emulator.display_rect();
+            assert_eq!(
+                (rect.coords.x.value(), rect.coords.y.value()),
+                coords,
+                "area 0x{area:08X}"
+            );
+            assert_eq!(
+                (
+                    rect.dimensions.width.value(),
+                    rect.dimensions.height.value()
+                ),
+                dimensions,
+                "mode 0x{mode:08X}"
+            );
+            assert_eq!(
+                (
+                    rect.resolution.horizontal.value(),
+                    rect.resolution.vertical.value()
+                ),
+                resolution,
+                "mode 0x{mode:08X}"
+            );
+        }
+    }
+
+    #[test]
+    fn gpuread_returns_blits_then_the_latched_info_result() {
+        const PIXELS: u32 = 0x1234_5678;
+
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.set_renderer(Box::new(SoftVram::new()));
+
+        // GP0(E3h) drawing area top-left at (5, 7), then GP1(10h) reading it back
+        let latch = 5 | (7 << 10);
+        gp0(&mut emulator, &[0xE300_0000 | latch]);
+        gp1(&mut emulator, 0x1000_0003);
+        assert_eq!(
+            testing::read::<u32>(emulator.psx_mut(), io::Reg::Gp0),
+            latch
+        );
+
+        // GP0(A0h) CPU to VRAM blit of 2x1 pixels at (0, 0), then GP0(C0h) VRAM to CPU blit of them
+        gp0(&mut emulator, &[0xA000_0000, 0, 0x0001_0002, PIXELS]);
+        gp0(&mut emulator, &[0xC000_0000, 0, 0x0001_0002]);
+
+        // past the end of the blit, the info result is still latched
+        let reads = [(); 3].map(|_| testing::read::<u32>(emulator.psx_mut(), io::Reg::Gp0));
+        assert_eq!(reads, [PIXELS, latch, latch]);
+    }
+
+    #[test]
+    fn gpu_register_2_reads_back_the_texture_window() {
+        let mut emulator = testing::emulator(testing::builder());
+
+        // GP0(E2h) texture window with every field set, then GP1(10h) reading it back
+        let texwindow = 0x000F_FFFF;
+        gp0(&mut emulator, &[0xE200_0000 | texwindow]);
+        gp1(&mut emulator, 0x1000_0002);
+        assert_eq!(
+            testing::read::<u32>(emulator.psx_mut(), io::Reg::Gp0),
+            texwindow
+        );
+    }
+
+    /// Latches an info result in GPUREAD, runs a VRAM to CPU blit of 2x2 pixels and returns the
+    /// words read from GPUREAD afterwards: the two words of the blit, then the latched result.
+    fn read_blit(emulator: &mut Emulator) -> ([u32; 3], u32) {
+        // GP0(E3h) drawing area top-left at (5, 7), then GP1(10h) reading it back. A blit which
+        // never started would read as this instead
+        let latch = 5 | (7 << 10);
+        gp0(emulator, &[0xE300_0000 | latch]);
+        gp1(emulator, 0x1000_0003);
+
+        // GP0(C0h) VRAM to CPU blit of 2x2 pixels at (0, 0)
+        gp0(emulator, &[0xC000_0000, 0, 0x0002_0002]);
+
+        let reads = [(); 3].map(|_| testing::read::<u32>(emulator.psx_mut(), io::Reg::Gp0));
+        (reads, latch)
+    }
+
+    /// A renderer which ignores every command, dropping the response handles of readbacks.
+    struct DroppingRenderer;
+
+    impl Renderer for DroppingRenderer {
+        fn exec(&mut self, _: Command) {}
+    }
+
+    #[test]
+    fn dropped_readback_response_completes_the_blit_with_zeroes() {
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.set_renderer(Box::new(DroppingRenderer));
+
+        let (reads, latch) = read_blit(&mut emulator);
+        assert_eq!(reads, [0, 0, latch]);
+    }
+
+    /// A renderer which holds on to the response handles of readbacks without ever answering.
+    #[derive(Default)]
+    struct StuckRenderer(Vec<ResponseHandle>);
+
+    impl Renderer for StuckRenderer {
+        fn exec(&mut self, command: Command) {
+            if let Command::CopyFromVram { response, .. } = command {
+                self.0.push(response);
+            }
+        }
+    }
+
+    #[test]
+    fn stuck_readback_completes_the_blit_with_zeroes_after_the_timeout() {
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.set_renderer(Box::new(StuckRenderer::default()));
+
+        let start = Instant::now();
+        let (reads, latch) = read_blit(&mut emulator);
+        let elapsed = start.elapsed();
+
+        assert_eq!(reads, [0, 0, latch]);
+        assert!(elapsed >= READBACK_TIMEOUT, "returned after {elapsed:?}");
     }
 }
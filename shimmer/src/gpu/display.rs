@@ -9,25 +9,31 @@ use crate::{
 use shimmer_core::gpu::{
     Status,
     cmd::{
-        DisplayCommand, DisplayOpcode,
-        environment::{DrawingAreaCornerCmd, DrawingOffsetCmd},
+        environment::{DrawingAreaCornerCmd, DrawingOffsetCmd, TextureWindowSettingsCmd},
+        parser::DecodedDisplayCommand,
     },
 };
 use tinylog::{error, trace, warn};
 
 impl Gpu {
     /// Executes the given display command.
-    pub fn exec_display(&mut self, psx: &mut PSX, cmd: DisplayCommand) {
-        trace!(psx.loggers.gpu, "received display cmd: {cmd:?}");
+    pub fn exec_display(&mut self, psx: &mut PSX, cmd: DecodedDisplayCommand) {
+        trace!(psx.loggers.gpu, "received display cmd: {cmd}");
 
-        match cmd.opcode().unwrap() {
-            DisplayOpcode::ResetGpu => {
+        match cmd {
+            DecodedDisplayCommand::ResetGpu => {
                 // TODO: reset internal registers
                 psx.gpu.status = Status::default();
                 psx.gpu.render_queue.clear();
+                self.abort_command(psx);
+
+                self.renderer.exec(Command::SetDisplayEnabled(
+                    !psx.gpu.status.disable_display(),
+                ));
+                self.renderer
+                    .exec(Command::SetDisplayFlipped(psx.gpu.status.flip_screen_x()));
             }
-            DisplayOpcode::DisplayMode => {
-                let cmd = cmd.display_mode_cmd();
+            DecodedDisplayCommand::DisplayMode(cmd) => {
                 let stat = &mut psx.gpu.status;
 
                 stat.set_horizontal_resolution(cmd.horizontal_resolution());
@@ -43,14 +49,17 @@ impl Gpu {
                         horizontal: cmd.horizontal_resolution(),
                         vertical: cmd.vertical_resolution(),
                     }));
+                self.renderer
+                    .exec(Command::SetDisplayFlipped(cmd.flip_screen_x()));
+
+                // whether the display is interlaced affects drawing to the display area
+                self.renderer_exec_drawing_settings(psx);
             }
-            DisplayOpcode::DmaDirection => {
-                let cmd = cmd.dma_direction_cmd();
+            DecodedDisplayCommand::DmaDirection(cmd) => {
                 psx.gpu.status.set_dma_direction(cmd.direction());
                 psx.scheduler.schedule(Event::DmaUpdate, 0);
             }
-            DisplayOpcode::DisplayArea => {
-                let cmd = cmd.display_area_cmd();
+            DecodedDisplayCommand::DisplayArea(cmd) => {
                 psx.gpu.display.top_left_x = cmd.x();
                 psx.gpu.display.top_left_y = cmd.y();
 
@@ -59,62 +68,71 @@ impl Gpu {
                     y: cmd.y(),
                 }));
             }
-            DisplayOpcode::HorizontalDisplayRange => {
-                let cmd = cmd.horizontal_display_range_cmd();
+            DecodedDisplayCommand::HorizontalDisplayRange(cmd) => {
                 psx.gpu.display.horizontal_range = cmd.x1()..cmd.x2();
             }
-            DisplayOpcode::VerticalDisplayRange => {
-                let cmd = cmd.vertical_display_range_cmd();
+            DecodedDisplayCommand::VerticalDisplayRange(cmd) => {
                 psx.gpu.display.vertical_range = cmd.y1()..cmd.y2();
             }
-            DisplayOpcode::DisplayEnabled => {
-                let cmd = cmd.display_enable_cmd();
-                psx.gpu.status.set_disable_display(cmd.disabled());
+            DecodedDisplayCommand::DisplayEnabled(cmd) => {
+                if psx.gpu.status.disable_display() != cmd.disabled() {
+                    psx.gpu.status.set_disable_display(cmd.disabled());
+                    self.renderer
+                        .exec(Command::SetDisplayEnabled(!cmd.disabled()));
+                }
             }
-            DisplayOpcode::VramSizeV2 => {
-                let cmd = cmd.vram_size_cmd();
-                psx.gpu.environment.double_vram = cmd.double();
+            DecodedDisplayCommand::AllowTextureDisable(cmd) => {
+                psx.gpu.environment.allow_texture_disable = cmd.allow();
             }
-            DisplayOpcode::AcknowledgeGpuInterrupt => {
+            DecodedDisplayCommand::AcknowledgeGpuInterrupt => {
                 psx.gpu.status.set_interrupt_request(false);
             }
-            DisplayOpcode::ResetCommandBuffer => {
+            DecodedDisplayCommand::ResetCommandBuffer => {
                 warn!(psx.loggers.gpu, "reset command buffer");
                 psx.gpu.render_queue.clear();
+                self.abort_command(psx);
             }
-            DisplayOpcode::ReadGpuRegister => {
-                let index = cmd.to_bits() & 0b111;
-                match index {
-                    0 | 1 | 6 | 7 => (),
-                    2 => todo!(),
-                    3 => {
-                        psx.gpu.response_queue.push_front(
-                            DrawingAreaCornerCmd::from_bits(0)
-                                .with_x(psx.gpu.environment.drawing_area_top_left_x)
-                                .with_y(psx.gpu.environment.drawing_area_top_left_y)
-                                .to_bits(),
-                        );
-                    }
-                    4 => {
-                        psx.gpu.response_queue.push_front(
-                            DrawingAreaCornerCmd::from_bits(0)
-                                .with_x(psx.gpu.environment.drawing_area_bottom_right_x)
-                                .with_y(psx.gpu.environment.drawing_area_bottom_right_y)
-                                .to_bits(),
-                        );
-                    }
-                    5 => {
-                        psx.gpu.response_queue.push_front(
-                            DrawingOffsetCmd::from_bits(0)
-                                .with_x(psx.gpu.environment.drawing_offset_x)
-                                .with_y(psx.gpu.environment.drawing_offset_y)
-                                .to_bits(),
-                        );
-                    }
+            DecodedDisplayCommand::ReadGpuRegister(index) => {
+                let value = match index {
+                    0 | 1 | 6 | 7 => return,
+                    2 => TextureWindowSettingsCmd::from_bits(0)
+                        .with_texwindow(psx.gpu.environment.texwindow)
+                        .to_bits(),
+                    3 => DrawingAreaCornerCmd::from_bits(0)
+                        .with_x(psx.gpu.environment.drawing_area_top_left_x)
+                        .with_y(psx.gpu.environment.drawing_area_top_left_y)
+                        .to_bits(),
+                    4 => DrawingAreaCornerCmd::from_bits(0)
+                        .with_x(psx.gpu.environment.drawing_area_bottom_right_x)
+                        .with_y(psx.gpu.environment.drawing_area_bottom_right_y)
+                        .to_bits(),
+                    5 => DrawingOffsetCmd::from_bits(0)
+                        .with_x(psx.gpu.environment.drawing_offset_x)
+                        .with_y(psx.gpu.environment.drawing_offset_y)
+                        .to_bits(),
                     _ => unreachable!(),
+                };
+
+                if let Some(dropped) = psx.gpu.response_queue.push_info(value) {
+                    warn!(
+                        psx.loggers.gpu,
+                        "GPU info results aren't being read - dropping the oldest one";
+                        dropped = dropped,
+                    );
+                }
+            }
+            DecodedDisplayCommand::VramSizeV1 | DecodedDisplayCommand::Unknown(_) => {
+                let kind = match cmd {
+                    DecodedDisplayCommand::Unknown(raw) => {
+                        format!("GP1({:02X}h)", (raw.to_bits() >> 24) & 0x3F)
+                    }
+                    _ => format!("GP1 {cmd}"),
+                };
+
+                if psx.unimplemented.record(kind) {
+                    error!(psx.loggers.gpu, "unimplemented display command: {cmd}");
                 }
             }
-            _ => error!(psx.loggers.gpu, "unimplemented display command: {cmd:?}"),
         }
     }
 }
@@ -1,6 +1,11 @@
 //! The interface for renderer implementations.
 
+pub mod contract;
 pub mod primitive;
+pub mod soft_vram;
+
+#[cfg(feature = "serde")]
+mod bits;
 
 use bitos::integer::{u9, u10, u11};
 use shimmer_core::gpu::{
@@ -12,20 +17,27 @@ pub use primitive::*;
 
 /// VRAM coordinates.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VramCoords {
+    #[cfg_attr(feature = "serde", serde(with = "bits::u10"))]
     pub x: u10,
+    #[cfg_attr(feature = "serde", serde(with = "bits::u9"))]
     pub y: u9,
 }
 
 /// VRAM dimensions.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VramDimensions {
+    #[cfg_attr(feature = "serde", serde(with = "bits::u11"))]
     pub width: u11,
+    #[cfg_attr(feature = "serde", serde(with = "bits::u10"))]
     pub height: u10,
 }
 
 /// 32-bit RGBA color.
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rgba8 {
     pub r: u8,
     pub g: u8,
@@ -42,6 +54,7 @@ impl Rgba8 {
 
 /// Texture configuration.
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TexConfig {
     pub clut: Clut,
     pub texpage: TexPage,
@@ -50,22 +63,52 @@ pub struct TexConfig {
 
 /// A data copy to VRAM.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CopyToVram {
     pub coords: VramCoords,
     pub dimensions: VramDimensions,
+    /// The pixels, row by row, two bytes each. Holds exactly `width * height` pixels.
     pub data: Vec<u8>,
 }
 
 /// A data copy from VRAM.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CopyFromVram {
     pub coords: VramCoords,
     pub dimensions: VramDimensions,
-    pub response: oneshot::Sender<Vec<u8>>,
+}
+
+/// The handle through which the response to a [`Command::CopyFromVram`] is sent.
+///
+/// This handle is not serialized: deserialized commands carry a detached handle, which the
+/// transport should replace with one of its own.
+#[derive(Debug, Default)]
+pub struct ResponseHandle(Option<oneshot::Sender<Vec<u8>>>);
+
+impl ResponseHandle {
+    /// Creates a new handle and the receiver of its response.
+    pub fn channel() -> (Self, oneshot::Receiver<Vec<u8>>) {
+        let (sender, receiver) = oneshot::channel();
+        (Self(Some(sender)), receiver)
+    }
+
+    /// Whether this handle is detached, i.e. has nowhere to send a response to.
+    pub fn is_detached(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Sends the response through this handle. Does nothing if the handle is detached.
+    pub fn respond(self, data: Vec<u8>) {
+        if let Some(sender) = self.0 {
+            _ = sender.send(data);
+        }
+    }
 }
 
 /// A data copy inside VRAM.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CopyInVram {
     pub source: VramCoords,
     pub destination: VramCoords,
@@ -73,13 +116,23 @@ pub struct CopyInVram {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DrawingSettings {
+    /// The blending mode in GPUSTAT. Primitives carry their own effective blending mode, so this
+    /// is only the default for untextured primitives.
     pub blending_mode: BlendingMode,
     pub write_to_mask: bool,
     pub check_mask: bool,
+    /// Whether drawing to the displayed area is allowed. If it isn't, pixels inside the display
+    /// area are not drawn or, if the display is interlaced, only pixels in the lines of the field
+    /// being displayed.
+    pub drawing_to_display: bool,
+    /// If the display is interlaced, whether the field being displayed is the odd one.
+    pub interlaced_odd_field: Option<bool>,
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DrawingArea {
     pub coords: VramCoords,
     pub dimensions: VramDimensions,
@@ -87,6 +140,7 @@ pub struct DrawingArea {
 
 /// Top-Left position of the display.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DisplayResolution {
     pub horizontal: HorizontalResolution,
     pub vertical: VerticalResolution,
@@ -94,12 +148,16 @@ pub struct DisplayResolution {
 
 /// A renderer command.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Command {
     // Configuration
     SetDrawingSettings(DrawingSettings),
     SetDrawingArea(DrawingArea),
     SetDisplayTopLeft(VramCoords),
     SetDisplayResolution(DisplayResolution),
+    SetDisplayEnabled(bool),
+    /// Whether the display output is mirrored horizontally (the reverse flag of GP1(08h)).
+    SetDisplayFlipped(bool),
     SetTexWindow(TexWindow),
 
     // Control
@@ -107,17 +165,228 @@ pub enum Command {
 
     // Copy data
     CopyToVram(CopyToVram),
-    CopyFromVram(CopyFromVram),
+    CopyFromVram {
+        request: CopyFromVram,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        response: ResponseHandle,
+    },
     CopyInVram(CopyInVram),
 
     // Draw
-    Draw { primitive: Primitive },
+    Draw {
+        primitive: Primitive,
+    },
+}
+
+/// A renderer which draws nothing and responds to VRAM readbacks with zeroes.
+///
+/// This is the renderer used by the emulator while no other renderer is attached, so that the GPU
+/// keeps working headless. Renderers which need to keep VRAM contents without a GPU can use
+/// [`soft_vram::SoftVram`] instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullRenderer;
+
+impl Renderer for NullRenderer {
+    fn exec(&mut self, command: Command) {
+        if let Command::CopyFromVram { request, response } = command {
+            let len = usize::from(request.dimensions.width.value())
+                * usize::from(request.dimensions.height.value())
+                * 2;
+            response.respond(vec![0; len]);
+        }
+    }
 }
 
 /// Renderer interface.
+///
+/// # Contract
+///
+/// Implementations must uphold the following, which the emulator relies on:
+///
+/// - Commands must take effect in the order they were given to [`Renderer::exec`]. In particular,
+///   a [`Command::CopyFromVram`] must observe every command before it.
+/// - Every [`Command::CopyFromVram`] must eventually be responded to through its
+///   [`ResponseHandle`], even if the renderer fails to read VRAM (in which case it should respond
+///   with zeroes). The emulator blocks waiting for the response, so a dropped or forgotten handle
+///   stalls emulation until it times out.
+/// - [`Renderer::exec`] must not block for an unbounded amount of time. It may be called from any
+///   thread, but never concurrently, since it takes `&mut self`.
+///
+/// [`contract::Harness`] checks the observable parts of this contract.
 pub trait Renderer: Send + Sync {
     /// Executes a single renderer command. This method should execute as quickly as possible in
     /// order to not disturb emulator timing. It is recommended to offload the rendering to another
     /// thread.
     fn exec(&mut self, command: Command);
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use bitos::integer::{i11, u4, u5, u6};
+    use shimmer_core::gpu::{
+        cmd::rendering::{ShadingMode, TransparencyMode},
+        texture::Depth,
+    };
+
+    /// Number of variants of [`Command`].
+    const COMMAND_VARIANTS: usize = 12;
+
+    /// Index of the variant of `command`. Adding a variant fails to compile here, as a reminder to
+    /// add it to [`commands`].
+    fn variant(command: &Command) -> usize {
+        match command {
+            Command::SetDrawingSettings(_) => 0,
+            Command::SetDrawingArea(_) => 1,
+            Command::SetDisplayTopLeft(_) => 2,
+            Command::SetDisplayResolution(_) => 3,
+            Command::SetDisplayEnabled(_) => 4,
+            Command::SetDisplayFlipped(_) => 5,
+            Command::SetTexWindow(_) => 6,
+            Command::VBlank => 7,
+            Command::CopyToVram(_) => 8,
+            Command::CopyFromVram { .. } => 9,
+            Command::CopyInVram(_) => 10,
+            Command::Draw { .. } => 11,
+        }
+    }
+
+    fn coords(x: u16, y: u16) -> VramCoords {
+        VramCoords {
+            x: u10::new(x),
+            y: u9::new(y),
+        }
+    }
+
+    fn dimensions(width: u16, height: u16) -> VramDimensions {
+        VramDimensions {
+            width: u11::new(width),
+            height: u10::new(height),
+        }
+    }
+
+    fn vertex(x: i16, y: i16) -> Vertex {
+        Vertex {
+            color: Rgba8::new(0x12, 0x34, 0x56),
+            x: i11::new(x),
+            y: i11::new(y),
+            u: 0x78,
+            v: 0x9A,
+        }
+    }
+
+    fn texconfig() -> TexConfig {
+        TexConfig {
+            clut: Clut::default()
+                .with_x_by_16(u6::new(63))
+                .with_y(u9::new(511)),
+            texpage: TexPage::default()
+                .with_x_base(u4::new(15))
+                .with_blending_mode(BlendingMode::Quarter)
+                .with_depth(Depth::Byte),
+            texwindow: TexWindow::default()
+                .with_mask_x(u5::new(1))
+                .with_offset_y(u5::new(31)),
+        }
+    }
+
+    /// Commands of every variant, with fields away from their defaults and at the limits of their
+    /// ranges.
+    fn commands() -> Vec<Command> {
+        let (response, _) = ResponseHandle::channel();
+
+        vec![
+            Command::SetDrawingSettings(DrawingSettings {
+                blending_mode: BlendingMode::Sub,
+                write_to_mask: true,
+                check_mask: false,
+                drawing_to_display: true,
+                interlaced_odd_field: Some(true),
+            }),
+            Command::SetDrawingArea(DrawingArea {
+                coords: coords(1023, 511),
+                dimensions: dimensions(1024, 512),
+            }),
+            Command::SetDisplayTopLeft(coords(320, 240)),
+            Command::SetDisplayResolution(DisplayResolution {
+                horizontal: HorizontalResolution::R640,
+                vertical: VerticalResolution::R480,
+            }),
+            Command::SetDisplayEnabled(true),
+            Command::SetDisplayFlipped(true),
+            Command::SetTexWindow(texconfig().texwindow),
+            Command::VBlank,
+            Command::CopyToVram(CopyToVram {
+                coords: coords(1, 2),
+                dimensions: dimensions(2, 1),
+                data: vec![0x11, 0x22, 0x33, 0x44],
+            }),
+            Command::CopyFromVram {
+                request: CopyFromVram {
+                    coords: coords(3, 4),
+                    dimensions: dimensions(5, 6),
+                },
+                response,
+            },
+            Command::CopyInVram(CopyInVram {
+                source: coords(7, 8),
+                destination: coords(9, 10),
+                dimensions: dimensions(11, 12),
+            }),
+            Command::Draw {
+                primitive: Primitive::Triangle(Triangle {
+                    vertices: [vertex(-1024, 1023), vertex(0, -1), vertex(1023, -1024)],
+                    shading: ShadingMode::Gouraud,
+                    transparency: TransparencyMode::SemiTransparent,
+                    blending_mode: BlendingMode::Add,
+                    texconfig: Some(texconfig()),
+                }),
+            },
+            Command::Draw {
+                primitive: Primitive::Rectangle(Rectangle {
+                    top_left: vertex(-5, 6),
+                    width: 1023,
+                    height: 511,
+                    transparency: TransparencyMode::Opaque,
+                    blending_mode: BlendingMode::Half,
+                    texconfig: None,
+                }),
+            },
+        ]
+    }
+
+    #[test]
+    fn commands_round_trip_through_serde() {
+        let commands = commands();
+
+        let mut covered: Vec<_> = commands.iter().map(variant).collect();
+        covered.dedup();
+        assert_eq!(covered, (0..COMMAND_VARIANTS).collect::<Vec<_>>());
+
+        for command in commands {
+            let serialized = ron::to_string(&command).unwrap();
+            let deserialized: Command = ron::from_str(&serialized).unwrap();
+            assert_eq!(ron::to_string(&deserialized).unwrap(), serialized);
+
+            // the response handle isn't serialized, so it's detached
+            if let Command::CopyFromVram { response, .. } = deserialized {
+                assert!(response.is_detached());
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_integers_fail_to_deserialize() {
+        assert!(ron::from_str::<VramCoords>("(x: 1023, y: 511)").is_ok());
+        assert!(ron::from_str::<VramCoords>("(x: 1024, y: 0)").is_err());
+        assert!(ron::from_str::<VramCoords>("(x: 0, y: 512)").is_err());
+        assert!(ron::from_str::<VramDimensions>("(width: 2048, height: 0)").is_err());
+
+        let vertex =
+            |x: i16| format!("(color: (r: 0, g: 0, b: 0, a: 0), x: {x}, y: 0, u: 0, v: 0)");
+        assert!(ron::from_str::<Vertex>(&vertex(-1024)).is_ok());
+        assert!(ron::from_str::<Vertex>(&vertex(1023)).is_ok());
+        assert!(ron::from_str::<Vertex>(&vertex(-1025)).is_err());
+        assert!(ron::from_str::<Vertex>(&vertex(1024)).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+//! Serde helpers for `bitos` integers, which are (de)serialized as their primitive values.
+//! Deserializing a value which doesn't fit is an error.
+
+macro_rules! unsigned {
+    ($($name:ident: $bits:literal),*) => {
+        $(
+            pub mod $name {
+                use bitos::integer::$name;
+                use serde::{
+                    Deserialize, Deserializer, Serialize, Serializer,
+                    de::{Error, Unexpected},
+                };
+
+                pub fn serialize<S>(value: &$name, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: Serializer,
+                {
+                    value.value().serialize(serializer)
+                }
+
+                pub fn deserialize<'de, D>(deserializer: D) -> Result<$name, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    let value = u16::deserialize(deserializer)?;
+                    if value >= 1 << $bits {
+                        return Err(D::Error::invalid_value(
+                            Unexpected::Unsigned(u64::from(value)),
+                            &concat!("an unsigned ", stringify!($bits), "-bit integer"),
+                        ));
+                    }
+
+                    Ok($name::new(value))
+                }
+            }
+        )*
+    };
+}
+
+unsigned! {
+    u9: 9,
+    u10: 10,
+    u11: 11
+}
+
+pub mod i11 {
+    use bitos::integer::i11;
+    use serde::{
+        Deserialize, Deserializer, Serialize, Serializer,
+        de::{Error, Unexpected},
+    };
+
+    pub fn serialize<S>(value: &i11, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value.value().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<i11, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = i16::deserialize(deserializer)?;
+        if !(-1024..1024).contains(&value) {
+            return Err(D::Error::invalid_value(
+                Unexpected::Signed(i64::from(value)),
+                &"a signed 11-bit integer",
+            ));
+        }
+
+        Ok(i11::new(value))
+    }
+}
@@ -0,0 +1,196 @@
+//! A harness which checks the observable parts of the [`Renderer`] contract.
+//!
+//! Renderer implementations can run [`Harness::run`] against themselves to catch the mistakes
+//! that would otherwise only show up as stalls or corruption in the emulator.
+
+use super::{
+    Command, CopyFromVram, CopyToVram, DrawingArea, DrawingSettings, Renderer, ResponseHandle,
+    VramCoords, VramDimensions,
+};
+use bitos::integer::{u9, u10, u11};
+use easyerr::Error;
+use shimmer_core::gpu::texture::BlendingMode;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Error)]
+pub enum ContractViolation {
+    #[error("executing {command} took {elapsed:?}, longer than the limit of {limit:?}")]
+    SlowExec {
+        command: &'static str,
+        elapsed: Duration,
+        limit: Duration,
+    },
+    #[error("a VRAM readback was not responded to within {timeout:?}")]
+    ReadbackTimeout { timeout: Duration },
+    #[error("a VRAM readback response was dropped without being sent")]
+    ReadbackDropped,
+    #[error("a VRAM readback response has length {len}, but {expected} bytes were requested")]
+    ReadbackLength { len: usize, expected: usize },
+    #[error("a VRAM readback doesn't reflect the data copied to VRAM before it")]
+    ReadbackOutOfOrder,
+}
+
+/// Runs a scripted sequence of commands against a renderer, checking that it upholds the
+/// [`Renderer`] contract.
+#[derive(Debug, Clone, Copy)]
+pub struct Harness {
+    /// The maximum time a single call to [`Renderer::exec`] may take.
+    pub exec_limit: Duration,
+    /// The maximum time to wait for the response of a [`Command::CopyFromVram`].
+    pub response_timeout: Duration,
+    /// Whether to check that VRAM readbacks return the data copied to VRAM before them. This
+    /// verifies that commands execute in order, but only holds for renderers which model VRAM.
+    pub check_vram_contents: bool,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self {
+            exec_limit: Duration::from_millis(100),
+            response_timeout: Duration::from_secs(1),
+            check_vram_contents: true,
+        }
+    }
+}
+
+fn command_name(command: &Command) -> &'static str {
+    match command {
+        Command::SetDrawingSettings(_) => "SetDrawingSettings",
+        Command::SetDrawingArea(_) => "SetDrawingArea",
+        Command::SetDisplayTopLeft(_) => "SetDisplayTopLeft",
+        Command::SetDisplayResolution(_) => "SetDisplayResolution",
+        Command::SetDisplayEnabled(_) => "SetDisplayEnabled",
+        Command::SetDisplayFlipped(_) => "SetDisplayFlipped",
+        Command::SetTexWindow(_) => "SetTexWindow",
+        Command::VBlank => "VBlank",
+        Command::CopyToVram(_) => "CopyToVram",
+        Command::CopyFromVram { .. } => "CopyFromVram",
+        Command::CopyInVram(_) => "CopyInVram",
+        Command::Draw { .. } => "Draw",
+    }
+}
+
+fn coords(x: u16, y: u16) -> VramCoords {
+    VramCoords {
+        x: u10::new(x),
+        y: u9::new(y),
+    }
+}
+
+fn dimensions(width: u16, height: u16) -> VramDimensions {
+    VramDimensions {
+        width: u11::new(width),
+        height: u10::new(height),
+    }
+}
+
+impl Harness {
+    fn exec(
+        &self,
+        renderer: &mut (impl Renderer + ?Sized),
+        command: Command,
+    ) -> Result<(), ContractViolation> {
+        let command_name = command_name(&command);
+        let start = Instant::now();
+        renderer.exec(command);
+
+        let elapsed = start.elapsed();
+        if elapsed > self.exec_limit {
+            return Err(ContractViolation::SlowExec {
+                command: command_name,
+                elapsed,
+                limit: self.exec_limit,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn readback(
+        &self,
+        renderer: &mut (impl Renderer + ?Sized),
+        coords: VramCoords,
+        dimensions: VramDimensions,
+    ) -> Result<Vec<u8>, ContractViolation> {
+        let (response, receiver) = ResponseHandle::channel();
+        self.exec(
+            renderer,
+            Command::CopyFromVram {
+                request: CopyFromVram { coords, dimensions },
+                response,
+            },
+        )?;
+
+        let data = match receiver.recv_timeout(self.response_timeout) {
+            Ok(data) => data,
+            Err(oneshot::RecvTimeoutError::Timeout) => {
+                return Err(ContractViolation::ReadbackTimeout {
+                    timeout: self.response_timeout,
+                });
+            }
+            Err(oneshot::RecvTimeoutError::Disconnected) => {
+                return Err(ContractViolation::ReadbackDropped);
+            }
+        };
+
+        let expected =
+            usize::from(dimensions.width.value()) * usize::from(dimensions.height.value()) * 2;
+        if data.len() != expected {
+            return Err(ContractViolation::ReadbackLength {
+                len: data.len(),
+                expected,
+            });
+        }
+
+        Ok(data)
+    }
+
+    /// Runs the scripted command sequence against `renderer`, returning the first violation of
+    /// the contract found.
+    pub fn run(&self, renderer: &mut (impl Renderer + ?Sized)) -> Result<(), ContractViolation> {
+        self.exec(
+            renderer,
+            Command::SetDrawingSettings(DrawingSettings {
+                blending_mode: BlendingMode::Half,
+                write_to_mask: false,
+                check_mask: false,
+                drawing_to_display: true,
+                interlaced_odd_field: None,
+            }),
+        )?;
+        self.exec(
+            renderer,
+            Command::SetDrawingArea(DrawingArea {
+                coords: coords(0, 0),
+                dimensions: dimensions(1024, 512),
+            }),
+        )?;
+
+        // a readback must be responded to even with nothing drawn
+        self.readback(renderer, coords(0, 0), dimensions(16, 16))?;
+
+        // a copy to VRAM immediately followed by a readback of the same area must be observed by
+        // it, since commands execute in order
+        let data: Vec<u8> = (0..16 * 16 * 2).map(|i| (i * 7 + 3) as u8).collect();
+        self.exec(
+            renderer,
+            Command::CopyToVram(CopyToVram {
+                coords: coords(64, 32),
+                dimensions: dimensions(16, 16),
+                data: data.clone(),
+            }),
+        )?;
+
+        let readback = self.readback(renderer, coords(64, 32), dimensions(16, 16))?;
+        if self.check_vram_contents && readback != data {
+            return Err(ContractViolation::ReadbackOutOfOrder);
+        }
+
+        self.exec(renderer, Command::VBlank)?;
+
+        // a readback which wraps around the edges of VRAM must still be responded to
+        self.readback(renderer, coords(1016, 508), dimensions(16, 8))?;
+
+        Ok(())
+    }
+}
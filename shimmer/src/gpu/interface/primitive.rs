@@ -1,12 +1,18 @@
 use super::{Rgba8, TexConfig};
 use bitos::integer::i11;
-use shimmer_core::gpu::cmd::rendering::{ShadingMode, TransparencyMode};
+use shimmer_core::gpu::{
+    cmd::rendering::{ShadingMode, TransparencyMode},
+    texture::BlendingMode,
+};
 
 /// A single triangle vertex.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vertex {
     pub color: Rgba8,
+    #[cfg_attr(feature = "serde", serde(with = "super::bits::i11"))]
     pub x: i11,
+    #[cfg_attr(feature = "serde", serde(with = "super::bits::i11"))]
     pub y: i11,
     pub u: u8,
     pub v: u8,
@@ -14,10 +20,13 @@ pub struct Vertex {
 
 /// A triangle primitive.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Triangle {
     pub vertices: [Vertex; 3],
     pub shading: ShadingMode,
     pub transparency: TransparencyMode,
+    /// The blending mode used if this triangle is semi-transparent.
+    pub blending_mode: BlendingMode,
     pub texconfig: Option<TexConfig>,
 }
 
@@ -35,11 +44,14 @@ impl Triangle {
 
 /// A rectangle primitive.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rectangle {
     pub top_left: Vertex,
     pub width: u16,
     pub height: u16,
     pub transparency: TransparencyMode,
+    /// The blending mode used if this rectangle is semi-transparent.
+    pub blending_mode: BlendingMode,
     pub texconfig: Option<TexConfig>,
 }
 
@@ -51,6 +63,7 @@ impl Rectangle {
 
 /// A drawing primitive.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Primitive {
     Triangle(Triangle),
     Rectangle(Rectangle),
@@ -0,0 +1,267 @@
+//! [`SoftVram`], a software model of VRAM for renderers which need real VRAM contents without a
+//! GPU, e.g. in tests which read back what was drawn.
+
+use super::{
+    Command, CopyFromVram, CopyInVram, CopyToVram, DrawingArea, Primitive, Rectangle, Renderer,
+    Rgba8, VramCoords, VramDimensions,
+};
+use bitos::integer::{u9, u10, u11};
+use shimmer_core::gpu::vram::{VRAM_HEIGHT, VRAM_WIDTH};
+
+/// VRAM as 16-bit pixels, row by row. Coordinates wrap around its edges, as they do on hardware.
+///
+/// Transfers are exact, while drawing only covers untextured rectangles, which are drawn opaque
+/// with their flat color and clipped to the drawing area. Everything else is ignored, so that the
+/// model stays simple and deterministic.
+#[derive(Debug, Clone)]
+pub struct SoftVram {
+    pixels: Vec<u16>,
+    drawing_area: DrawingArea,
+    write_to_mask: bool,
+}
+
+impl Default for SoftVram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Packs a color into a 15-bit pixel, dropping the low bits of each component.
+fn pack_color(color: Rgba8) -> u16 {
+    u16::from(color.r >> 3) | (u16::from(color.g >> 3) << 5) | (u16::from(color.b >> 3) << 10)
+}
+
+impl SoftVram {
+    /// Creates a zeroed VRAM, with the whole of it as the drawing area.
+    pub fn new() -> Self {
+        Self {
+            pixels: vec![0; usize::from(VRAM_WIDTH) * usize::from(VRAM_HEIGHT)],
+            drawing_area: DrawingArea {
+                coords: VramCoords {
+                    x: u10::new(0),
+                    y: u9::new(0),
+                },
+                dimensions: VramDimensions {
+                    width: u11::new(VRAM_WIDTH),
+                    height: u10::new(VRAM_HEIGHT),
+                },
+            },
+            write_to_mask: false,
+        }
+    }
+
+    #[inline(always)]
+    fn index(x: u16, y: u16) -> usize {
+        usize::from(y % VRAM_HEIGHT) * usize::from(VRAM_WIDTH) + usize::from(x % VRAM_WIDTH)
+    }
+
+    /// Returns the pixel at (`x`, `y`).
+    pub fn pixel(&self, x: u16, y: u16) -> u16 {
+        self.pixels[Self::index(x, y)]
+    }
+
+    /// Sets the pixel at (`x`, `y`) to `value`.
+    pub fn set_pixel(&mut self, x: u16, y: u16, value: u16) {
+        self.pixels[Self::index(x, y)] = value;
+    }
+
+    /// Returns every pixel, row by row.
+    pub fn pixels(&self) -> &[u16] {
+        &self.pixels
+    }
+
+    /// Writes the pixels of `copy` to VRAM.
+    pub fn copy_to(&mut self, copy: &CopyToVram) {
+        let x = copy.coords.x.value();
+        let y = copy.coords.y.value();
+        let width = copy.dimensions.width.value();
+
+        for (index, pixel) in copy.data.chunks_exact(2).enumerate() {
+            let dx = (index % usize::from(width)) as u16;
+            let dy = (index / usize::from(width)) as u16;
+            let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+            self.set_pixel(x.wrapping_add(dx), y.wrapping_add(dy), value);
+        }
+    }
+
+    /// Reads the pixels requested by `copy`, row by row, two bytes each.
+    pub fn copy_from(&self, copy: &CopyFromVram) -> Vec<u8> {
+        let x = copy.coords.x.value();
+        let y = copy.coords.y.value();
+        let width = copy.dimensions.width.value();
+        let height = copy.dimensions.height.value();
+
+        let mut data = Vec::with_capacity(usize::from(width) * usize::from(height) * 2);
+        for dy in 0..height {
+            for dx in 0..width {
+                let pixel = self.pixel(x.wrapping_add(dx), y.wrapping_add(dy));
+                data.extend_from_slice(&pixel.to_le_bytes());
+            }
+        }
+
+        data
+    }
+
+    /// Copies a rectangle of VRAM to another place in it, pixel by pixel.
+    pub fn copy_in(&mut self, copy: &CopyInVram) {
+        let (src_x, src_y) = (copy.source.x.value(), copy.source.y.value());
+        let (dest_x, dest_y) = (copy.destination.x.value(), copy.destination.y.value());
+
+        for dy in 0..copy.dimensions.height.value() {
+            for dx in 0..copy.dimensions.width.value() {
+                let pixel = self.pixel(src_x.wrapping_add(dx), src_y.wrapping_add(dy));
+                self.set_pixel(dest_x.wrapping_add(dx), dest_y.wrapping_add(dy), pixel);
+            }
+        }
+    }
+
+    /// Draws `rectangle` with its flat color, clipped to the drawing area. Textured rectangles
+    /// are ignored.
+    pub fn draw_rectangle(&mut self, rectangle: &Rectangle) {
+        if rectangle.texconfig.is_some() {
+            return;
+        }
+
+        let area = self.drawing_area;
+        let left = i32::from(area.coords.x.value());
+        let top = i32::from(area.coords.y.value());
+        let right = left + i32::from(area.dimensions.width.value());
+        let bottom = top + i32::from(area.dimensions.height.value());
+
+        let x = i32::from(rectangle.top_left.x.value());
+        let y = i32::from(rectangle.top_left.y.value());
+        let mask = if self.write_to_mask { 0x8000 } else { 0 };
+        let value = pack_color(rectangle.top_left.color) | mask;
+
+        for py in y.max(top)..(y + i32::from(rectangle.height)).min(bottom) {
+            for px in x.max(left)..(x + i32::from(rectangle.width)).min(right) {
+                self.set_pixel(px as u16, py as u16, value);
+            }
+        }
+    }
+}
+
+impl Renderer for SoftVram {
+    fn exec(&mut self, command: Command) {
+        match command {
+            Command::SetDrawingSettings(settings) => self.write_to_mask = settings.write_to_mask,
+            Command::SetDrawingArea(area) => self.drawing_area = area,
+            Command::CopyToVram(copy) => self.copy_to(&copy),
+            Command::CopyFromVram { request, response } => {
+                response.respond(self.copy_from(&request))
+            }
+            Command::CopyInVram(copy) => self.copy_in(&copy),
+            Command::Draw {
+                primitive: Primitive::Rectangle(rectangle),
+            } => self.draw_rectangle(&rectangle),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::interface::{Vertex, contract::Harness};
+    use bitos::integer::i11;
+    use shimmer_core::gpu::{cmd::rendering::TransparencyMode, texture::BlendingMode};
+
+    fn rectangle(x: i16, y: i16, width: u16, height: u16, color: Rgba8) -> Rectangle {
+        Rectangle {
+            top_left: Vertex {
+                color,
+                x: i11::new(x),
+                y: i11::new(y),
+                u: 0,
+                v: 0,
+            },
+            width,
+            height,
+            transparency: TransparencyMode::Opaque,
+            blending_mode: BlendingMode::Half,
+            texconfig: None,
+        }
+    }
+
+    fn read(vram: &SoftVram, x: u16, y: u16, width: u16, height: u16) -> Vec<u16> {
+        let data = vram.copy_from(&CopyFromVram {
+            coords: VramCoords {
+                x: u10::new(x),
+                y: u9::new(y),
+            },
+            dimensions: VramDimensions {
+                width: u11::new(width),
+                height: u10::new(height),
+            },
+        });
+
+        data.chunks_exact(2)
+            .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn upholds_the_renderer_contract() {
+        Harness::default().run(&mut SoftVram::new()).unwrap();
+    }
+
+    #[test]
+    fn rectangles_are_drawn_exactly() {
+        let mut vram = SoftVram::new();
+        vram.draw_rectangle(&rectangle(8, 4, 3, 2, Rgba8::new(0xF8, 0x00, 0x80)));
+
+        // read back along with a border of one pixel
+        let expected = (0..4)
+            .flat_map(|dy| (0..5).map(move |dx| (dx, dy)))
+            .map(|(dx, dy)| {
+                let inside = (1..4).contains(&dx) && (1..3).contains(&dy);
+                if inside { 0x401F } else { 0 }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(read(&vram, 7, 3, 5, 4), expected);
+    }
+
+    #[test]
+    fn rectangles_are_clipped_to_the_drawing_area() {
+        let mut vram = SoftVram::new();
+        vram.exec(Command::SetDrawingArea(DrawingArea {
+            coords: VramCoords {
+                x: u10::new(2),
+                y: u9::new(2),
+            },
+            dimensions: VramDimensions {
+                width: u11::new(2),
+                height: u10::new(2),
+            },
+        }));
+        vram.draw_rectangle(&rectangle(0, 0, 8, 8, Rgba8::new(0xFF, 0xFF, 0xFF)));
+
+        assert_eq!(vram.pixels().iter().filter(|&&pixel| pixel != 0).count(), 4);
+        assert_eq!(vram.pixel(2, 2), 0x7FFF);
+        assert_eq!(vram.pixel(3, 3), 0x7FFF);
+    }
+
+    #[test]
+    fn transfers_wrap_around_the_edges() {
+        let mut vram = SoftVram::new();
+        vram.copy_to(&CopyToVram {
+            coords: VramCoords {
+                x: u10::new(VRAM_WIDTH - 1),
+                y: u9::new(VRAM_HEIGHT - 1),
+            },
+            dimensions: VramDimensions {
+                width: u11::new(2),
+                height: u10::new(2),
+            },
+            data: [1u16, 2, 3, 4]
+                .into_iter()
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+        });
+
+        assert_eq!(vram.pixel(VRAM_WIDTH - 1, VRAM_HEIGHT - 1), 1);
+        assert_eq!(vram.pixel(0, VRAM_HEIGHT - 1), 2);
+        assert_eq!(vram.pixel(VRAM_WIDTH - 1, 0), 3);
+        assert_eq!(vram.pixel(0, 0), 4);
+    }
+}
@@ -1,25 +1,33 @@
-use super::{Gpu, interface::CopyInVram};
+use super::{Gpu, READBACK_TIMEOUT, interface::CopyInVram};
 use crate::{
     PSX,
     gpu::{
         State,
         interface::{
-            Command, CopyFromVram, DrawingArea, DrawingSettings, Rgba8, TexConfig, VramCoords,
-            VramDimensions,
+            Command, CopyFromVram, DrawingArea, DrawingSettings, ResponseHandle, Rgba8, TexConfig,
+            VramCoords, VramDimensions,
             primitive::{Primitive, Rectangle, Triangle, Vertex},
         },
     },
     scheduler::Event,
 };
 use bitos::integer::{i11, u9, u10, u11};
-use shimmer_core::gpu::cmd::{
-    EnvironmentOpcode, MiscOpcode, RenderingCommand, RenderingOpcode,
-    rendering::{
-        CoordPacket, LineMode, PolygonMode, RectangleMode, ShadingMode, SizePacket,
-        TransparencyMode, VertexColorPacket, VertexPositionPacket, VertexUVPacket,
+use shimmer_core::gpu::{
+    VerticalResolution,
+    cmd::{
+        environment::{
+            DrawingAreaCornerCmd, DrawingOffsetCmd, DrawingSettingsCmd, MaskSettingsCmd,
+            TextureWindowSettingsCmd,
+        },
+        parser::{DecodedRenderingCommand, LineVertex, PolygonVertex},
+        rendering::{
+            CoordPacket, LineCmd, PolygonCmd, PolygonMode, RectangleCmd, SizePacket,
+            TransparencyMode, VertexColorPacket, VertexPositionPacket, VertexUVPacket,
+        },
     },
+    vram,
 };
-use tinylog::{debug, error, info, trace};
+use tinylog::{debug, error, info, trace, warn};
 
 #[derive(Default)]
 struct VertexPackets {
@@ -41,25 +49,27 @@ impl VertexPackets {
 }
 
 impl Gpu {
-    fn exec_quick_rect_fill(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let cmd = cmd.rectangle_cmd();
-        let color = Rgba8::new(cmd.r(), cmd.g(), cmd.b());
-
-        let position = CoordPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-        let dimensions = SizePacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-        let (x, y) = (position.x(), position.y());
-        let (width, height) = (dimensions.width(), dimensions.height());
+    fn exec_quick_rect_fill(
+        &mut self,
+        psx: &mut PSX,
+        color: VertexColorPacket,
+        position: CoordPacket,
+        dimensions: SizePacket,
+    ) {
+        let color = Rgba8::new(color.r(), color.g(), color.b());
+        let rect = vram::fill_rect(position, dimensions);
         let rectangle = Rectangle {
             top_left: Vertex {
                 color,
-                x: i11::new((x & 0x3F0) as i16),
-                y: i11::new((y & 0x1FF) as i16),
+                x: i11::new(rect.x as i16),
+                y: i11::new(rect.y as i16),
                 u: 0,
                 v: 0,
             },
-            width: ((width & 0x3FF) + 0xF) & !0xF,
-            height: height & 0x1FF,
+            width: rect.width,
+            height: rect.height,
             transparency: TransparencyMode::Opaque,
+            blending_mode: psx.gpu.status.blending_mode(),
             texconfig: None,
         };
 
@@ -69,49 +79,29 @@ impl Gpu {
         });
     }
 
-    fn exec_polygon(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let cmd = cmd.polygon_cmd();
-        let base_color_packet = VertexColorPacket::default()
-            .with_r(cmd.r())
-            .with_g(cmd.g())
-            .with_b(cmd.b());
-
-        let mut vertex = |skip_color| {
-            let color = if skip_color || cmd.shading_mode() == ShadingMode::Flat {
-                base_color_packet
-            } else {
-                VertexColorPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap())
+    fn exec_polygon(&mut self, psx: &mut PSX, cmd: PolygonCmd, vertices: &[PolygonVertex]) {
+        let vertex = |index: usize| {
+            let Some(vertex) = vertices.get(index) else {
+                return VertexPackets::default();
             };
 
-            let mut position =
-                VertexPositionPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-
+            let mut position = vertex.position;
             position.apply_offset(
                 psx.gpu.environment.drawing_offset_x,
                 psx.gpu.environment.drawing_offset_y,
             );
 
-            let uv = if cmd.textured() {
-                VertexUVPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap())
-            } else {
-                VertexUVPacket::default()
-            };
-
             VertexPackets {
-                color,
+                color: vertex.color,
                 position,
-                uv,
+                uv: vertex.uv.unwrap_or_default(),
             }
         };
 
-        let vertex_a = vertex(true);
-        let vertex_b = vertex(false);
-        let vertex_c = vertex(false);
-        let vertex_d = if cmd.polygon_mode() == PolygonMode::Rectangle {
-            vertex(false)
-        } else {
-            VertexPackets::default()
-        };
+        let vertex_a = vertex(0);
+        let vertex_b = vertex(1);
+        let vertex_c = vertex(2);
+        let vertex_d = vertex(3);
 
         let tri_1 = [
             vertex_a.to_vertex(),
@@ -139,7 +129,7 @@ impl Gpu {
             stat.set_blending_mode(texpage.blending_mode());
             stat.set_texpage_depth(texpage.depth());
 
-            if psx.gpu.environment.double_vram {
+            if psx.gpu.environment.allow_texture_disable {
                 stat.set_texture_disable(vertex_b.uv.texture_disable());
             } else {
                 stat.set_texture_disable(false);
@@ -148,10 +138,17 @@ impl Gpu {
             texconfig
         });
 
+        // textured polygons blend according to their own texpage attribute, while untextured ones
+        // use the one last set in GPUSTAT
+        let blending_mode = texconfig.map_or(psx.gpu.status.blending_mode(), |config| {
+            config.texpage.blending_mode()
+        });
+
         let first_triangle = Triangle {
             vertices: tri_1,
             shading: cmd.shading_mode(),
             transparency: cmd.transparency_mode(),
+            blending_mode,
             texconfig,
         };
 
@@ -159,6 +156,7 @@ impl Gpu {
             vertices: tri_2,
             shading: cmd.shading_mode(),
             transparency: cmd.transparency_mode(),
+            blending_mode,
             texconfig,
         };
 
@@ -181,7 +179,7 @@ impl Gpu {
         }
     }
 
-    fn renderer_exec_drawing_area(&mut self, psx: &mut PSX) {
+    pub(super) fn renderer_exec_drawing_area(&mut self, psx: &mut PSX) {
         self.renderer.exec(Command::SetDrawingArea(DrawingArea {
             coords: VramCoords {
                 x: psx.gpu.environment.drawing_area_top_left_x,
@@ -206,19 +204,21 @@ impl Gpu {
         }));
     }
 
-    fn renderer_exec_drawing_settings(&mut self, psx: &mut PSX) {
-        let stat = &mut psx.gpu.status;
+    pub(super) fn renderer_exec_drawing_settings(&mut self, psx: &mut PSX) {
+        let stat = &psx.gpu.status;
+        let interlaced = stat.vertical_resolution() == VerticalResolution::R480;
         self.renderer
             .exec(Command::SetDrawingSettings(DrawingSettings {
                 blending_mode: stat.blending_mode(),
                 write_to_mask: stat.write_to_mask(),
                 check_mask: stat.check_mask(),
+                drawing_to_display: stat.enable_drawing_to_display(),
+                interlaced_odd_field: interlaced.then_some(psx.gpu.odd_field),
             }));
     }
 
-    fn exec_drawing_area_top_left(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let cmd = cmd.drawing_area_corner_cmd();
-        info!(psx.loggers.gpu, "updating drawing area top left"; top_left = cmd.clone());
+    fn exec_drawing_area_top_left(&mut self, psx: &mut PSX, cmd: DrawingAreaCornerCmd) {
+        info!(psx.loggers.gpu, "updating drawing area top left"; top_left = cmd);
 
         psx.gpu.environment.drawing_area_top_left_x = cmd.x();
         psx.gpu.environment.drawing_area_top_left_y = cmd.y();
@@ -226,9 +226,8 @@ impl Gpu {
         self.renderer_exec_drawing_area(psx);
     }
 
-    fn exec_drawing_area_bottom_right(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let cmd = cmd.drawing_area_corner_cmd();
-        info!(psx.loggers.gpu, "updating drawing area bottom right"; bottom_right = cmd.clone());
+    fn exec_drawing_area_bottom_right(&mut self, psx: &mut PSX, cmd: DrawingAreaCornerCmd) {
+        info!(psx.loggers.gpu, "updating drawing area bottom right"; bottom_right = cmd);
 
         psx.gpu.environment.drawing_area_bottom_right_x = cmd.x();
         psx.gpu.environment.drawing_area_bottom_right_y = cmd.y();
@@ -237,18 +236,16 @@ impl Gpu {
     }
 
     #[expect(clippy::unused_self, reason = "consistency")]
-    fn exec_drawing_offset(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let cmd = cmd.drawing_offset_cmd();
-        info!(psx.loggers.gpu, "updating drawing area offset"; offset = cmd.clone());
+    fn exec_drawing_offset(&mut self, psx: &mut PSX, cmd: DrawingOffsetCmd) {
+        info!(psx.loggers.gpu, "updating drawing area offset"; offset = cmd);
 
         psx.gpu.environment.drawing_offset_x = cmd.x();
         psx.gpu.environment.drawing_offset_y = cmd.y();
     }
 
     #[expect(clippy::unused_self, reason = "consistency")]
-    fn exec_drawing_settings(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let settings = cmd.drawing_settings_cmd();
-        info!(psx.loggers.gpu, "updating drawing settings"; settings = settings.clone());
+    fn exec_drawing_settings(&mut self, psx: &mut PSX, settings: DrawingSettingsCmd) {
+        info!(psx.loggers.gpu, "updating drawing settings"; settings = settings);
 
         let stat = &mut psx.gpu.status;
         stat.set_texpage_x_base(settings.texpage().x_base());
@@ -258,7 +255,7 @@ impl Gpu {
         stat.set_compression_mode(settings.compression_mode());
         stat.set_enable_drawing_to_display(settings.enable_drawing_to_display());
 
-        if psx.gpu.environment.double_vram {
+        if psx.gpu.environment.allow_texture_disable {
             stat.set_texture_disable(settings.texture_disable());
         } else {
             stat.set_texture_disable(false);
@@ -271,9 +268,8 @@ impl Gpu {
     }
 
     #[expect(clippy::unused_self, reason = "consistency")]
-    fn exec_texwindow_settings(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let settings = cmd.texture_window_settings_cmd();
-        info!(psx.loggers.gpu, "updating texwindow settings"; settings = settings.clone());
+    fn exec_texwindow_settings(&mut self, psx: &mut PSX, settings: TextureWindowSettingsCmd) {
+        info!(psx.loggers.gpu, "updating texwindow settings"; settings = settings);
         psx.gpu.environment.texwindow = settings.texwindow();
 
         self.renderer
@@ -281,9 +277,8 @@ impl Gpu {
     }
 
     #[expect(clippy::unused_self, reason = "consistency")]
-    fn exec_mask_settings(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let settings = cmd.mask_settings_cmd();
-        info!(psx.loggers.gpu, "updating mask settings"; settings = settings.clone());
+    fn exec_mask_settings(&mut self, psx: &mut PSX, settings: MaskSettingsCmd) {
+        info!(psx.loggers.gpu, "updating mask settings"; settings = settings);
 
         psx.gpu.status.set_write_to_mask(settings.write_to_mask());
         psx.gpu.status.set_check_mask(settings.check_mask());
@@ -291,50 +286,54 @@ impl Gpu {
         self.renderer_exec_drawing_settings(psx);
     }
 
-    fn exec_cpu_to_vram_blit(&mut self, psx: &mut PSX, _: RenderingCommand) {
-        let dest = CoordPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-        let size = SizePacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-
-        info!(psx.loggers.gpu, "starting CPU to VRAM blit"; dest = dest.clone(), size = size.clone());
-        self.inner = State::CpuToVramBlit { dest, size };
+    fn exec_cpu_to_vram_blit(&mut self, psx: &mut PSX, dest: CoordPacket, size: SizePacket) {
+        info!(psx.loggers.gpu, "starting CPU to VRAM blit"; dest = dest, size = size);
+        self.inner = State::CpuToVramBlit {
+            dest,
+            size,
+            data: Vec::new(),
+        };
 
+        psx.gpu.receiving_blit = true;
         psx.gpu.status.set_ready_to_send_vram(false);
+        psx.gpu.update_readiness();
         psx.scheduler.schedule(Event::DmaUpdate, 0);
     }
 
-    fn exec_vram_to_cpu_blit(&mut self, psx: &mut PSX, _: RenderingCommand) {
+    fn exec_vram_to_cpu_blit(&mut self, psx: &mut PSX, src: CoordPacket, size: SizePacket) {
         psx.gpu.status.set_ready_to_send_vram(true);
+        info!(psx.loggers.gpu, "starting VRAM to CPU blit"; src = src, size = size);
 
-        let src = CoordPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-        let size = SizePacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-        info!(psx.loggers.gpu, "starting VRAM to CPU blit"; src = src.clone(), size = size.clone());
-
-        let effective_width = if size.width() == 0 {
-            0x400
-        } else {
-            ((size.width() - 1) & 0x3FF) + 1
-        };
-
-        let effective_height = if size.height() == 0 {
-            0x200
-        } else {
-            ((size.height() - 1) & 0x1FF) + 1
-        };
-
-        let (sender, receiver) = oneshot::channel();
-        let copy = CopyFromVram {
+        let rect = vram::blit_rect(src, size);
+        let (response, receiver) = ResponseHandle::channel();
+        let request = CopyFromVram {
             coords: VramCoords {
-                x: u10::new(src.x()),
-                y: u9::new(src.y()),
+                x: u10::new(rect.x),
+                y: u9::new(rect.y),
             },
             dimensions: VramDimensions {
-                width: u11::new(effective_width),
-                height: u10::new(effective_height),
+                width: u11::new(rect.width),
+                height: u10::new(rect.height),
             },
-            response: sender,
         };
-        self.renderer.exec(Command::CopyFromVram(copy));
-        let data = receiver.recv().unwrap();
+        self.renderer
+            .exec(Command::CopyFromVram { request, response });
+        let data = receiver
+            .recv_timeout(READBACK_TIMEOUT)
+            .unwrap_or_else(|err| {
+                match err {
+                    oneshot::RecvTimeoutError::Timeout => error!(
+                        psx.loggers.gpu,
+                        "renderer timed out on the VRAM to CPU blit - returning zeroes"
+                    ),
+                    oneshot::RecvTimeoutError::Disconnected => error!(
+                        psx.loggers.gpu,
+                        "renderer dropped the VRAM to CPU blit response - returning zeroes"
+                    ),
+                }
+
+                vec![0; usize::from(rect.width) * usize::from(rect.height) * 2]
+            });
 
         let packed = data.chunks(4).map(|chunk| {
             let bytes = [
@@ -347,58 +346,55 @@ impl Gpu {
             u32::from_le_bytes(bytes)
         });
 
-        psx.gpu.response_queue.extend(packed);
+        let discarded = psx.gpu.response_queue.start_blit(packed);
+        if discarded > 0 {
+            warn!(
+                psx.loggers.gpu,
+                "previous VRAM to CPU blit wasn't fully read - discarding its remaining words";
+                discarded = discarded,
+            );
+        }
+
         psx.scheduler.schedule(Event::DmaUpdate, 0);
     }
 
-    fn exec_vram_to_vram_blit(&mut self, psx: &mut PSX, _: RenderingCommand) {
-        let src = CoordPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-        let dest = CoordPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-        let size = SizePacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-
-        let effective_width = if size.width() == 0 {
-            0x400
-        } else {
-            ((size.width() - 1) & 0x3FF) + 1
-        };
-
-        let effective_height = if size.height() == 0 {
-            0x200
-        } else {
-            ((size.height() - 1) & 0x1FF) + 1
-        };
-
+    fn exec_vram_to_vram_blit(&mut self, src: CoordPacket, dest: CoordPacket, size: SizePacket) {
+        let src = vram::blit_rect(src, size);
+        let (dest_x, dest_y) = vram::blit_position(dest);
         let copy = CopyInVram {
             source: VramCoords {
-                x: u10::new(src.x()),
-                y: u9::new(src.y()),
+                x: u10::new(src.x),
+                y: u9::new(src.y),
             },
             destination: VramCoords {
-                x: u10::new(dest.x()),
-                y: u9::new(dest.y()),
+                x: u10::new(dest_x),
+                y: u9::new(dest_y),
             },
             dimensions: VramDimensions {
-                width: u11::new(effective_width),
-                height: u10::new(effective_height),
+                width: u11::new(src.width),
+                height: u10::new(src.height),
             },
         };
         self.renderer.exec(Command::CopyInVram(copy));
     }
 
-    fn exec_rectangle(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let cmd = cmd.rectangle_cmd();
+    fn exec_rectangle(
+        &mut self,
+        psx: &mut PSX,
+        cmd: RectangleCmd,
+        mut position: VertexPositionPacket,
+        uv: Option<VertexUVPacket>,
+        (width, height): (u16, u16),
+    ) {
         let color = Rgba8::new(cmd.r(), cmd.g(), cmd.b());
-
-        let mut position =
-            VertexPositionPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-
         position.apply_offset(
             psx.gpu.environment.drawing_offset_x,
             psx.gpu.environment.drawing_offset_y,
         );
 
-        let (uv, texconfig) = if cmd.textured() {
-            let uv = VertexUVPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
+        let (uv, texconfig) = if let Some(uv) = uv {
+            // rectangles have no texpage attribute of their own: they use the one in GPUSTAT,
+            // which is kept up to date by both GP0(E1h) and textured polygons
             let config = TexConfig {
                 clut: uv.clut(),
                 texpage: psx.gpu.status.texpage(),
@@ -410,16 +406,6 @@ impl Gpu {
             (VertexUVPacket::default(), None)
         };
 
-        let (width, height) = match cmd.rectangle_mode() {
-            RectangleMode::Variable => {
-                let size = SizePacket::from_bits(psx.gpu.render_queue.pop_front().unwrap());
-                (size.width(), size.height())
-            }
-            RectangleMode::SinglePixel => (1, 1),
-            RectangleMode::Sprite8 => (8, 8),
-            RectangleMode::Sprite16 => (16, 16),
-        };
-
         let rectangle = Rectangle {
             top_left: Vertex {
                 color,
@@ -431,6 +417,7 @@ impl Gpu {
             width,
             height,
             transparency: cmd.transparency_mode(),
+            blending_mode: psx.gpu.status.blending_mode(),
             texconfig,
         };
 
@@ -444,68 +431,196 @@ impl Gpu {
         });
     }
 
-    fn exec_line(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        let cmd = cmd.line_cmd();
-        match cmd.line_mode() {
-            LineMode::Single => {
-                for _ in 0..2 {
-                    if cmd.shading_mode() == ShadingMode::Gouraud {
-                        debug!(
-                            psx.loggers.gpu,
-                            "gouraud: {:?}",
-                            VertexColorPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap())
-                        );
+    #[expect(clippy::unused_self, reason = "consistency")]
+    fn exec_line(&mut self, psx: &mut PSX, vertices: [LineVertex; 2]) {
+        for vertex in vertices {
+            debug!(psx.loggers.gpu, "line vertex: {vertex:?}");
+        }
+    }
+
+    fn exec_poly_line(&mut self, psx: &mut PSX, cmd: LineCmd) {
+        debug!(psx.loggers.gpu, "starting polyline mode",);
+        self.inner = State::PolyLine { cmd, received: 0 };
+    }
+
+    /// Executes the given rendering command.
+    pub fn exec_render(&mut self, psx: &mut PSX, cmd: DecodedRenderingCommand) {
+        trace!(psx.loggers.gpu, "executing render cmd: {cmd}");
+
+        match cmd {
+            DecodedRenderingCommand::Nop => trace!(psx.loggers.gpu, "nop"),
+            DecodedRenderingCommand::ClearCache => trace!(psx.loggers.gpu, "clear cache"),
+            DecodedRenderingCommand::QuickRectangleFill {
+                color,
+                position,
+                size,
+            } => self.exec_quick_rect_fill(psx, color, position, size),
+            DecodedRenderingCommand::Polygon { cmd, vertices } => {
+                self.exec_polygon(psx, cmd, &vertices);
+            }
+            DecodedRenderingCommand::Line { vertices, .. } => self.exec_line(psx, vertices),
+            DecodedRenderingCommand::PolyLine { cmd } => self.exec_poly_line(psx, cmd),
+            DecodedRenderingCommand::Rectangle {
+                cmd,
+                position,
+                uv,
+                width,
+                height,
+            } => self.exec_rectangle(psx, cmd, position, uv, (width, height)),
+            DecodedRenderingCommand::VramToVramBlit { src, dest, size } => {
+                self.exec_vram_to_vram_blit(src, dest, size);
+            }
+            DecodedRenderingCommand::CpuToVramBlit { dest, size } => {
+                self.exec_cpu_to_vram_blit(psx, dest, size);
+            }
+            DecodedRenderingCommand::VramToCpuBlit { src, size } => {
+                self.exec_vram_to_cpu_blit(psx, src, size);
+            }
+            DecodedRenderingCommand::DrawingSettings(cmd) => self.exec_drawing_settings(psx, cmd),
+            DecodedRenderingCommand::TexWindowSettings(cmd) => {
+                self.exec_texwindow_settings(psx, cmd);
+            }
+            DecodedRenderingCommand::DrawingAreaTopLeft(cmd) => {
+                self.exec_drawing_area_top_left(psx, cmd);
+            }
+            DecodedRenderingCommand::DrawingAreaBottomRight(cmd) => {
+                self.exec_drawing_area_bottom_right(psx, cmd);
+            }
+            DecodedRenderingCommand::DrawingOffset(cmd) => self.exec_drawing_offset(psx, cmd),
+            DecodedRenderingCommand::MaskSettings(cmd) => self.exec_mask_settings(psx, cmd),
+            DecodedRenderingCommand::InterruptRequest | DecodedRenderingCommand::Unknown(_) => {
+                let kind = match cmd {
+                    DecodedRenderingCommand::Unknown(raw) => {
+                        format!("GP0({:02X}h)", raw.to_bits() >> 24)
                     }
+                    _ => format!("GP0 {cmd}"),
+                };
 
-                    debug!(
-                        psx.loggers.gpu,
-                        "vertex: {:?}",
-                        VertexPositionPacket::from_bits(psx.gpu.render_queue.pop_front().unwrap())
-                    );
+                if psx.unimplemented.record(kind) {
+                    error!(psx.loggers.gpu, "unimplemented rendering command: {cmd}");
                 }
             }
-            LineMode::Poly => {
-                debug!(psx.loggers.gpu, "starting polyline mode",);
-                self.inner = State::PolyLine { cmd, received: 0 };
-            }
         }
     }
+}
 
-    /// Executes the given rendering command.
-    pub fn exec_render(&mut self, psx: &mut PSX, cmd: RenderingCommand) {
-        trace!(
-            psx.loggers.gpu,
-            "executing render cmd: {cmd:?} (0x{:08X})",
-            cmd.to_bits()
-        );
+#[cfg(test)]
+mod tests {
+    use crate::{
+        gpu::interface::Primitive,
+        testing::{self, Recorder},
+    };
+    use shimmer_core::{
+        CYCLES_MILLIS,
+        gpu::texture::{BlendingMode, Depth},
+        mem::io,
+    };
+
+    fn write_gp0(emulator: &mut crate::Emulator, words: &[u32]) {
+        for &word in words {
+            testing::write::<u32>(emulator.psx_mut(), io::Reg::Gp0, word);
+            emulator.cycle_for(CYCLES_MILLIS);
+        }
+    }
 
-        match cmd.opcode() {
-            RenderingOpcode::Misc => match cmd.misc_opcode().unwrap() {
-                MiscOpcode::NOP => trace!(psx.loggers.gpu, "nop"),
-                MiscOpcode::ClearCache => trace!(psx.loggers.gpu, "clear cache"),
-                MiscOpcode::QuickRectangleFill => self.exec_quick_rect_fill(psx, cmd),
-                _ => error!(
-                    psx.loggers.gpu,
-                    "unimplemented rendering (misc) command: {:?}",
-                    cmd.misc_opcode()
-                ),
-            },
-            RenderingOpcode::Environment => match cmd.environment_opcode().unwrap() {
-                EnvironmentOpcode::DrawingAreaTopLeft => self.exec_drawing_area_top_left(psx, cmd),
-                EnvironmentOpcode::DrawingAreaBottomRight => {
-                    self.exec_drawing_area_bottom_right(psx, cmd)
+    #[test]
+    fn textured_rectangles_sample_the_texpage_set_by_draw_mode() {
+        let recorder = Recorder::default();
+        let mut emulator =
+            testing::emulator(testing::builder().renderer(Box::new(recorder.clone())));
+
+        // textured rectangle with its CLUT at (16, 1)
+        let rectangle = [0x6480_8080, 0x0000_0000, 0x0041_0000, 0x0010_0010];
+
+        // draw mode: texpage (320, 256), 15-bit
+        write_gp0(&mut emulator, &[0xE100_0115]);
+        write_gp0(&mut emulator, &rectangle);
+        // draw mode: texpage (576, 0), 4-bit
+        write_gp0(&mut emulator, &[0xE100_0009]);
+        write_gp0(&mut emulator, &rectangle);
+
+        let pages: Vec<_> = recorder
+            .take_primitives()
+            .into_iter()
+            .map(|primitive| {
+                let Primitive::Rectangle(rectangle) = primitive else {
+                    panic!("expected a rectangle, got {primitive:?}");
+                };
+
+                let texconfig = rectangle.texconfig.expect("rectangle should be textured");
+                assert_eq!(texconfig.clut.x_by_16().value(), 1);
+                assert_eq!(texconfig.clut.y().value(), 1);
+
+                let texpage = texconfig.texpage;
+                (
+                    texpage.x_base().value(),
+                    texpage.y_base().value(),
+                    texpage.depth(),
+                )
+            })
+            .collect();
+
+        assert_eq!(pages, [(5, 1, Depth::Full), (9, 0, Depth::Nibble)]);
+    }
+
+    #[test]
+    fn primitives_carry_the_blending_mode_of_their_source() {
+        let recorder = Recorder::default();
+        let mut emulator =
+            testing::emulator(testing::builder().renderer(Box::new(recorder.clone())));
+
+        let words = [
+            // draw mode: blending Add
+            0xE100_0020,
+            // semi-transparent untextured rectangle: blends as in GPUSTAT
+            0x6200_00FF,
+            0x0000_0000,
+            0x0010_0010,
+            // semi-transparent textured triangle with a texpage attribute blending Sub, 15-bit:
+            // blends as in its attribute, which also updates GPUSTAT
+            0x2680_8080,
+            0x0000_0000,
+            0x0000_0000,
+            0x0000_0010,
+            0x0140_0010,
+            0x0010_0000,
+            0x0000_1000,
+            // semi-transparent untextured rectangle: blends as in GPUSTAT, updated by the triangle
+            0x6200_00FF,
+            0x0000_0000,
+            0x0010_0010,
+            // draw mode: blending Quarter
+            0xE100_0060,
+            // semi-transparent textured rectangle: blends as in GPUSTAT
+            0x6680_8080,
+            0x0000_0000,
+            0x0000_0000,
+            0x0010_0010,
+        ];
+
+        write_gp0(&mut emulator, &words);
+
+        let modes: Vec<_> = recorder
+            .take_primitives()
+            .into_iter()
+            .map(|primitive| match primitive {
+                Primitive::Triangle(triangle) => {
+                    (triangle.texconfig.is_some(), triangle.blending_mode)
                 }
-                EnvironmentOpcode::DrawingOffset => self.exec_drawing_offset(psx, cmd),
-                EnvironmentOpcode::DrawingSettings => self.exec_drawing_settings(psx, cmd),
-                EnvironmentOpcode::TexWindowSettings => self.exec_texwindow_settings(psx, cmd),
-                EnvironmentOpcode::MaskSettings => self.exec_mask_settings(psx, cmd),
-            },
-            RenderingOpcode::Polygon => self.exec_polygon(psx, cmd),
-            RenderingOpcode::CpuToVramBlit => self.exec_cpu_to_vram_blit(psx, cmd),
-            RenderingOpcode::VramToCpuBlit => self.exec_vram_to_cpu_blit(psx, cmd),
-            RenderingOpcode::VramToVramBlit => self.exec_vram_to_vram_blit(psx, cmd),
-            RenderingOpcode::Rectangle => self.exec_rectangle(psx, cmd),
-            RenderingOpcode::Line => self.exec_line(psx, cmd),
-        }
+                Primitive::Rectangle(rectangle) => {
+                    (rectangle.texconfig.is_some(), rectangle.blending_mode)
+                }
+            })
+            .collect();
+
+        assert_eq!(
+            modes,
+            [
+                (false, BlendingMode::Add),
+                (true, BlendingMode::Sub),
+                (false, BlendingMode::Sub),
+                (true, BlendingMode::Quarter),
+            ]
+        );
     }
 }
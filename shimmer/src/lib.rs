@@ -10,35 +10,111 @@
 #![feature(cold_path)]
 #![feature(int_roundings)]
 
+pub mod audio;
+mod builder;
 mod bus;
 pub mod cdrom;
+pub mod coverage;
 pub mod cpu;
+mod crash;
 pub mod dma;
+pub mod flight_recorder;
+pub mod frame_dump;
 pub mod gpu;
 pub mod scheduler;
+pub mod self_test;
 pub mod sio0;
+pub mod spu;
+#[cfg(test)]
+mod testing;
 pub mod timers;
+pub mod unimplemented;
 
-use cdrom::Rom;
+use cdrom::{BinRom, Rom, RomError};
 use easyerr::{Error, ResultExt};
+use flight_recorder::{BusRecord, FlightRecorder};
+use frame_dump::{FrameDump, FrameDumpError, FrameFormat};
 use scheduler::{Event, Scheduler};
 use shimmer_core::{
+    CYCLES_SECOND, Cycles,
     cdrom::Cdrom,
-    cpu::{Cpu, cop0::Cop0},
+    cpu::{
+        Cpu, Reg,
+        cop0::{Cop0, Exception, SystemStatus},
+    },
     dma::Controller as DmaController,
+    exe::{self, ArgsError},
     gpu::Gpu,
     gte::Gte,
-    interrupts::Controller as InterruptController,
-    mem::Memory,
-    sio0::Sio0,
+    interrupts::{Controller as InterruptController, Interrupt},
+    kernel,
+    mem::{Address, Memory, io},
+    sio0::{DigitalInput, Sio0},
+    spu::Spu,
     timers::Timers,
 };
 use sio0::Joypad;
-use std::{hint::cold_path, path::PathBuf};
+use std::{ffi::CString, hint::cold_path, ops::RangeInclusive, path::PathBuf, time::Duration};
 use tinylog::Logger;
+use unimplemented::Unimplemented;
 
+pub use builder::EmulatorBuilder;
 pub use shimmer_core as core;
 
+/// Range to which clock multipliers are clamped. See [`Settings::clock_multiplier`].
+pub const CLOCK_MULTIPLIER_RANGE: RangeInclusive<f64> = 0.5..=4.0;
+
+/// The entry point of the shell, which the kernel jumps to once it's done booting.
+pub const SHELL_ENTRY: Address = Address(0x8003_0000);
+
+/// The values the BIOS leaves in the memory control registers once it's done booting.
+const POST_BOOT_MEMORY_CONTROL: [(io::Reg, u32); 10] = [
+    (io::Reg::Expansion1Base, 0x1F00_0000),
+    (io::Reg::Expansion2Base, 0x1F80_2000),
+    (io::Reg::Expansion1Delay, 0x0013_243F),
+    (io::Reg::Expansion3Delay, 0x0000_3022),
+    (io::Reg::BiosDelay, 0x0013_243F),
+    (io::Reg::SpuDelay, 0x2009_31E1),
+    (io::Reg::CdromDelay, 0x0002_0843),
+    (io::Reg::Expansion2Delay, 0x0007_0777),
+    (io::Reg::CommonDelay, 0x0003_1125),
+    (io::Reg::RamSize, 0x0000_0B88),
+];
+
+/// The writes which quiet the devices down the way the BIOS leaves them once it's done booting, in
+/// order: DMA transfers are stopped, with the channels at their default priorities, timers are
+/// stopped, the GPU is reset, which disables the display, and interrupts are acknowledged and
+/// masked.
+const POST_BOOT_IO: [(io::Reg, u32); 16] = [
+    (io::Reg::Dma0Control, 0),
+    (io::Reg::Dma1Control, 0),
+    (io::Reg::Dma2Control, 0),
+    (io::Reg::Dma3Control, 0),
+    (io::Reg::Dma4Control, 0),
+    (io::Reg::Dma5Control, 0),
+    (io::Reg::Dma6Control, 0),
+    (io::Reg::DmaControl, 0x0765_4321),
+    (io::Reg::DmaInterrupt, 0x7F00_0000),
+    (io::Reg::Timer1Mode, 0),
+    (io::Reg::Timer1Target, 0),
+    (io::Reg::Timer2Mode, 0),
+    (io::Reg::Timer2Target, 0),
+    (io::Reg::Gp1, 0),
+    (io::Reg::InterruptStatus, 0),
+    (io::Reg::InterruptMask, 0),
+];
+
+/// The value the BIOS leaves in the cache control register once it's done booting.
+const POST_BOOT_CACHE_CONTROL: u32 = 0x0001_E988;
+
+/// The value of the System Status register when the kernel jumps to the shell: kernel mode, with
+/// interrupts from the interrupt controller enabled and the exception vectors of the kernel, in
+/// RAM, selected.
+const POST_BOOT_SYSTEM_STATUS: u32 = 0x0000_0401;
+
+/// The stack pointer executables start with by default, at the top of RAM.
+const POST_BOOT_STACK: u32 = 0x801F_FFF0;
+
 /// All the loggers of the [`PSX`].
 pub struct Loggers {
     pub root: Logger,
@@ -76,6 +152,13 @@ pub struct PSX {
     pub scheduler: Scheduler,
     /// The loggers of this [`PSX`].
     pub loggers: Loggers,
+    /// The flight recorder of bus accesses.
+    pub flight_recorder: FlightRecorder,
+    /// Tally of the unimplemented commands hit since the last summary.
+    pub unimplemented: Unimplemented,
+    /// Cycles taken by the bus accesses of the CPU on top of the cost of its instructions, e.g.
+    /// by accesses to the slow expansion regions. Charged to the instruction which made them.
+    pub bus_stall: Cycles,
 
     pub memory: Memory,
     pub timers: Timers,
@@ -87,6 +170,66 @@ pub struct PSX {
     pub gpu: Gpu,
     pub cdrom: Cdrom,
     pub sio0: Sio0,
+    pub spu: Spu,
+}
+
+impl PSX {
+    /// Creates the state of a PSX with the given memory, as it is at power on, logging to
+    /// children of `logger`.
+    ///
+    /// Unlike [`Emulator::new`], nothing is scheduled and no subsystem drivers are created, so
+    /// this is enough to exercise the bus and, along with a [`cpu::Interpreter`], the CPU without
+    /// a frontend or a renderer, e.g. in headless tests.
+    pub fn new(memory: Memory, logger: Logger) -> Self {
+        let loggers = Loggers::new(logger);
+        Self {
+            scheduler: Scheduler::new(),
+            flight_recorder: FlightRecorder::default(),
+            unimplemented: Unimplemented::default(),
+            bus_stall: 0,
+
+            memory,
+            timers: Timers::default(),
+            dma: DmaController::default(),
+            cpu: Cpu::default(),
+            cop0: Cop0::default(),
+            gte: Gte::default(),
+            interrupts: InterruptController::default(),
+            gpu: Gpu::default(),
+            cdrom: Cdrom::new(loggers.cdrom.clone()),
+            sio0: Sio0::default(),
+            spu: Spu::default(),
+
+            loggers,
+        }
+    }
+
+    /// Puts the system into the state the BIOS leaves it in once it's done booting, about to enter
+    /// the shell at [`SHELL_ENTRY`], without running it. Meant for tests which want a consistent
+    /// starting point without the cost of a boot.
+    ///
+    /// This sets the CPU, COP0 and the memory control registers, and quiets the devices down
+    /// through their registers: DMA transfers and timers are stopped, the GPU is reset and
+    /// interrupts are acknowledged and masked. General purpose registers are cleared, except for
+    /// the stack pointer, and the program counter is set as a new [`cpu::Interpreter`] expects it.
+    ///
+    /// RAM is left untouched, so the kernel isn't installed unless a BIOS already booted, and the
+    /// code run from here on must not call it or take exceptions otherwise. The CD-ROM drive, the
+    /// SPU and the controllers and memory cards keep their state. See [`Emulator::reset_to_bios`]
+    /// to do the same on a running emulator.
+    pub fn reset_to_bios(&mut self) {
+        self.cpu = Cpu::default();
+        self.cpu.regs.write(Reg::SP, POST_BOOT_STACK);
+        self.cpu.regs.write_pc(SHELL_ENTRY.value());
+        self.cpu.cache_control = POST_BOOT_CACHE_CONTROL;
+
+        self.cop0 = Cop0::default();
+        *self.cop0.regs.system_status_mut() = SystemStatus::from_bits(POST_BOOT_SYSTEM_STATUS);
+
+        for (reg, value) in POST_BOOT_MEMORY_CONTROL.into_iter().chain(POST_BOOT_IO) {
+            _ = self.write::<u32, true>(Address(reg as u32), value);
+        }
+    }
 }
 
 /// Emulator configuration.
@@ -96,14 +239,162 @@ pub struct Config {
     pub bios: Vec<u8>,
     /// The path to the ROM to run.
     pub rom_path: Option<PathBuf>,
+    /// The expansion ROM mapped to the Expansion 1 region, e.g. the one of a cheat device, if
+    /// any. The BIOS runs it at boot if it has a valid header.
+    pub expansion1: Option<Vec<u8>>,
     /// The root logger to use.
     pub logger: Logger,
+    /// Arguments passed to sideloaded executables.
+    pub sideload_args: Vec<CString>,
+    /// How the CD-ROM drive chooses the region it reports discs as licensed for.
+    pub region_policy: cdrom::RegionPolicy,
+    /// Timing of SIO0 transfers and acknowledges, including their jitter.
+    pub sio0_timing: sio0::Timing,
+    /// Whether to detect loops in which the CPU idles waiting for an interrupt and skip straight
+    /// to the next event instead of executing them. The skipped cycles are still accounted for,
+    /// so this doesn't change the results of emulation. Has no effect if the clock multiplier is
+    /// not 1.0, and is suspended while a hardware breakpoint is armed or the instruction profiler,
+    /// the execution heatmap or the flight recorder is enabled, as they observe every instruction.
+    pub skip_idle_loops: bool,
+    /// Whether to fast-forward through the delay loops the BIOS runs while it boots, by running
+    /// them in no time, so that the shell or the game is reached in fewer cycles. Only loops
+    /// which count down without touching anything but registers and the stack are fast-forwarded,
+    /// so the boot takes the same path. Meant for fast test cycles, as it changes boot timing.
+    pub skip_post_delays: bool,
+    /// Configuration of the flight recorder of bus accesses. See [`Emulator::flight_recorder`].
+    pub flight_recorder: flight_recorder::Settings,
+    /// How jumps to a register handle targets which aren't word aligned.
+    pub jump_alignment: cpu::JumpAlignment,
+    /// Maximum size of the kernel stdout in bytes, past which its oldest output is dropped. If
+    /// `None`, it grows without bounds. See [`Memory::kernel_stdout`].
+    pub kernel_stdout_limit: Option<usize>,
+    /// Whether unimplemented commands are logged as they happen or summarized once per frame.
+    pub unimplemented_reporting: unimplemented::Reporting,
+    /// The initial runtime settings.
+    pub settings: Settings,
+}
+
+/// Emulator options which, unlike the ones in [`Config`], can be changed while it runs. See
+/// [`Emulator::set_settings`].
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Factor by which the CPU clock is scaled relative to the rest of the system. Values above
+    /// 1.0 overclock the CPU, which can help games that run too slowly. 1.0 is the original clock.
+    /// Clamped to [`CLOCK_MULTIPLIER_RANGE`].
+    ///
+    /// Only the cycles charged to the CPU are scaled: the scheduler, timers, the GPU, DMA and the
+    /// CD-ROM drive keep running on the original clock. Cycles in which DMA stalls the CPU are
+    /// scaled like the cost of instructions, since otherwise every stall would eat up the time
+    /// the overclock gains. Games which calibrate busy-wait loops against a timer at boot keep
+    /// working, but games which count on the length of an uncalibrated loop run their delays
+    /// faster than intended.
+    ///
+    /// Frontends can change it once the game is known to apply a per-game override.
+    pub clock_multiplier: f64,
+    /// Buttons which, once held together on the joypad, trigger a soft reset (see
+    /// [`Emulator::soft_reset`]), e.g. [`sio0::soft_reset_combo`]. Frontends which prefer to own
+    /// the binding should leave this as `None`.
+    pub soft_reset_combo: Option<DigitalInput>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            clock_multiplier: 1.0,
+            soft_reset_combo: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Clamps the clock multiplier to [`CLOCK_MULTIPLIER_RANGE`].
+    fn clamped(mut self) -> Self {
+        self.clock_multiplier = self.clock_multiplier.clamp(
+            *CLOCK_MULTIPLIER_RANGE.start(),
+            *CLOCK_MULTIPLIER_RANGE.end(),
+        );
+        self
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum EmulatorError {
     #[error("couldn't open ROM file")]
     RomOpen { source: std::io::Error },
+    #[error("couldn't load ROM image")]
+    RomLoad { source: RomError },
+    #[error("invalid sideload arguments")]
+    SideloadArgs { source: ArgsError },
+    #[error("clock multiplier must be positive and finite, but is {multiplier}")]
+    ClockMultiplier { multiplier: f64 },
+    #[error("no BIOS was given")]
+    MissingBios,
+    #[error("no logger was given")]
+    MissingLogger,
+    #[error("BIOS image has length {len}, but the BIOS region is only 512 KiB long")]
+    BiosTooLong { len: usize },
+    #[error("expansion ROM has length {len}, but the Expansion 1 region is only 8 MiB long")]
+    Expansion1TooLong { len: usize },
+    #[error("couldn't open the memory card in slot {slot}")]
+    MemoryCard {
+        slot: usize,
+        source: sio0::memcard::MemoryCardError,
+    },
+}
+
+/// A condition checked by [`Emulator::run_until`] after every instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition<'a> {
+    /// The next instruction to execute is the one at the given address, i.e. execution stops right
+    /// before it. This is not the program counter of the CPU, which already points at the
+    /// instruction after it because of the pipeline.
+    ///
+    /// For instance, `PcEquals(SHELL_ENTRY)` is met once the kernel has jumped to the shell, but
+    /// before anything at [`SHELL_ENTRY`] runs, including the sideload of an executable.
+    PcEquals(Address),
+    /// The kernel standard output contains the given string.
+    KernelStdoutContains(&'a str),
+    /// The memory at the given address holds the given bytes.
+    MemoryEquals(Address, &'a [u8]),
+}
+
+impl Condition<'_> {
+    /// Whether this condition holds. Standard output is only searched if it has changed, since
+    /// doing so after every instruction would be too slow.
+    fn holds(&self, psx: &PSX, next_instr: Address, stdout_changed: bool) -> bool {
+        match *self {
+            Condition::PcEquals(addr) => next_instr == addr,
+            Condition::KernelStdoutContains(text) => {
+                stdout_changed && psx.memory.kernel_stdout.contains(text)
+            }
+            Condition::MemoryEquals(addr, bytes) => {
+                bytes.iter().enumerate().all(|(offset, &byte)| {
+                    psx.peek::<u8>(Address(addr.value().wrapping_add(offset as u32))) == Some(byte)
+                })
+            }
+        }
+    }
+}
+
+/// Why [`Emulator::run_until`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The condition at the given index was met.
+    Condition(usize),
+    /// The predicate returned `true`.
+    Predicate,
+    /// The maximum amount of cycles was executed.
+    MaxCycles,
+    /// An exception set to break execution was triggered. See [`Emulator::break_on_exception`].
+    Exception(cpu::ExceptionBreak),
+}
+
+/// The result of [`Emulator::run_until`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunUntilResult {
+    pub reason: StopReason,
+    /// The amount of cycles executed.
+    pub cycles: u64,
 }
 
 /// The shimmer emulator.
@@ -117,50 +408,94 @@ pub struct Emulator {
     cdrom: cdrom::Cdrom,
     sio0: sio0::Sio0,
     timers: timers::Timers,
+    audio: Option<Box<dyn audio::AudioSink>>,
+
+    settings: Settings,
+    /// Fraction of a system cycle left over from scaling CPU cycles by the clock multiplier.
+    cycle_fraction: f64,
+    /// Whether the soft reset combo was held on the last check, so that holding it only resets
+    /// once.
+    soft_reset_combo_held: bool,
+
+    frame_dump: Option<FrameDump>,
 }
 
 impl Emulator {
-    /// Creates a new [`Emulator`].
-    pub fn new(
-        config: Config,
-        renderer: impl gpu::interface::Renderer + 'static,
-    ) -> Result<Self, EmulatorError> {
-        let gpu = gpu::Gpu::new(renderer);
-        let loggers = Loggers::new(config.logger);
+    /// Returns a builder for an [`Emulator`], which is the preferred way of creating one.
+    pub fn builder() -> EmulatorBuilder {
+        EmulatorBuilder::default()
+    }
+
+    /// Creates a new [`Emulator`]. It starts with no renderer attached: see
+    /// [`Emulator::set_renderer`].
+    ///
+    /// Kept for compatibility while frontends move to [`Emulator::builder`], which is preferred and
+    /// can also attach a renderer, an audio sink and memory cards.
+    pub fn new(config: Config) -> Result<Self, EmulatorError> {
+        let multiplier = config.settings.clock_multiplier;
+        if !(multiplier.is_finite() && multiplier > 0.0) {
+            return Err(EmulatorError::ClockMultiplier { multiplier });
+        }
+
+        let gpu = gpu::Gpu::new();
 
         let rom = config
             .rom_path
             .map(|path| std::fs::File::open(path).context(EmulatorCtx::RomOpen))
+            .transpose()?
+            .map(|file| BinRom::new(std::io::BufReader::new(file)).context(EmulatorCtx::RomLoad))
             .transpose()?;
 
-        Ok(Self {
-            cpu: cpu::Interpreter::default(),
+        let sideload_args =
+            exe::pack_args(&config.sideload_args).context(EmulatorCtx::SideloadArgs)?;
+
+        let bios_region = cdrom::LicenseRegion::from_bios(&config.bios);
+        let mut memory = Memory::with_bios(config.bios)
+            .map_err(|bios| EmulatorError::BiosTooLong { len: bios.len() })?;
+        memory.kernel_stdout_limit = config.kernel_stdout_limit;
+        if let Some(rom) = &config.expansion1 {
+            memory
+                .load_expansion_1(rom)
+                .map_err(|len| EmulatorError::Expansion1TooLong { len })?;
+        }
+
+        let mut psx = PSX::new(memory, config.logger);
+        psx.flight_recorder = FlightRecorder::new(config.flight_recorder);
+        psx.unimplemented = Unimplemented::new(config.unimplemented_reporting);
+
+        let mut cpu = cpu::Interpreter::new(sideload_args);
+        cpu.set_idle_loop_detection(config.skip_idle_loops);
+        cpu.set_post_delay_skipping(config.skip_post_delays);
+        cpu.set_jump_alignment(config.jump_alignment);
+
+        let mut emulator = Self {
+            cpu,
             gpu,
             dma: dma::Dma::default(),
-            cdrom: cdrom::Cdrom::new(rom.map(|r| {
-                let boxed: Box<dyn Rom> = Box::new(r);
-                boxed
-            })),
-            sio0: sio0::Sio0::default(),
-            timers: timers::Timers::new(loggers.timers.clone()),
-
-            psx: PSX {
-                scheduler: Scheduler::new(),
-
-                memory: Memory::with_bios(config.bios).expect("BIOS should fit"),
-                timers: Timers::default(),
-                dma: DmaController::default(),
-                cpu: Cpu::default(),
-                cop0: Cop0::default(),
-                gte: Gte::default(),
-                interrupts: InterruptController::default(),
-                gpu: Gpu::default(),
-                cdrom: Cdrom::new(loggers.cdrom.clone()),
-                sio0: Sio0::default(),
-
-                loggers,
-            },
-        })
+            cdrom: cdrom::Cdrom::new(
+                rom.map(|r| {
+                    let boxed: Box<dyn Rom> = Box::new(r);
+                    boxed
+                }),
+                config.region_policy,
+                bios_region,
+            ),
+            sio0: sio0::Sio0::new(config.sio0_timing),
+            timers: timers::Timers::new(psx.loggers.timers.clone()),
+            audio: None,
+
+            settings: config.settings.clamped(),
+            cycle_fraction: 0.0,
+            soft_reset_combo_held: false,
+
+            frame_dump: None,
+
+            psx,
+        };
+
+        timers::start(&mut emulator.psx);
+        gpu::start(&mut emulator.psx);
+        Ok(emulator)
     }
 
     /// Returns a reference to the state of the system.
@@ -175,10 +510,162 @@ impl Emulator {
         &mut self.psx
     }
 
+    /// Returns the number of cycles emulated so far.
+    #[inline(always)]
+    pub fn elapsed_cycles(&self) -> Cycles {
+        self.psx.scheduler.elapsed()
+    }
+
+    /// Returns the amount of time emulated so far.
+    pub fn elapsed_time(&self) -> Duration {
+        let cycles = self.elapsed_cycles();
+        let secs = cycles / CYCLES_SECOND;
+        let nanos = (cycles % CYCLES_SECOND) * 1_000_000_000 / CYCLES_SECOND;
+
+        Duration::new(secs, nanos as u32)
+    }
+
     pub fn joypad_mut(&mut self) -> &mut Joypad {
         self.sio0.joypad_mut()
     }
 
+    /// Inserts `card` into memory card `slot` (0 or 1), returning the card previously in it.
+    pub fn insert_memory_card(
+        &mut self,
+        slot: usize,
+        card: Option<sio0::memcard::MemoryCard>,
+    ) -> Result<Option<sio0::memcard::MemoryCard>, sio0::memcard::MemoryCardError> {
+        self.sio0.insert_memory_card(slot, card)
+    }
+
+    /// Returns the card in memory card `slot` (0 or 1), if any.
+    pub fn memory_card_mut(
+        &mut self,
+        slot: usize,
+    ) -> Result<Option<&mut sio0::memcard::MemoryCard>, sio0::memcard::MemoryCardError> {
+        self.sio0.memory_card_mut(slot)
+    }
+
+    /// Attaches `renderer`, returning the previously attached one, if any. Can be called at any
+    /// point, including mid-frame: queued GPU commands are executed by the previous renderer and
+    /// its state is handed off to the new one, as described in [`gpu::Gpu::swap_renderer`].
+    pub fn set_renderer(
+        &mut self,
+        renderer: Box<dyn gpu::interface::Renderer>,
+    ) -> Option<Box<dyn gpu::interface::Renderer>> {
+        self.gpu.exec_queued(&mut self.psx);
+        self.gpu.swap_renderer(&mut self.psx, Some(renderer))
+    }
+
+    /// Detaches the current renderer, if any. Until another one is attached, the GPU keeps
+    /// working without drawing anything and VRAM reads back as zeroes.
+    pub fn take_renderer(&mut self) -> Option<Box<dyn gpu::interface::Renderer>> {
+        self.gpu.exec_queued(&mut self.psx);
+        self.gpu.swap_renderer(&mut self.psx, None)
+    }
+
+    /// Attaches `sink`, returning the previously attached one, if any. While a sink is attached, the
+    /// SPU output is produced and pushed to it every [`spu::SAMPLE_CYCLES`].
+    pub fn set_audio_sink(
+        &mut self,
+        sink: Box<dyn audio::AudioSink>,
+    ) -> Option<Box<dyn audio::AudioSink>> {
+        if self.audio.is_none() {
+            // a sample might still be pending from a sink attached earlier
+            self.psx
+                .scheduler
+                .cancel_if(|event| matches!(event, Event::SpuSample));
+            self.psx
+                .scheduler
+                .schedule(Event::SpuSample, spu::SAMPLE_CYCLES);
+        }
+
+        self.audio.replace(sink)
+    }
+
+    /// Detaches the current audio sink, if any. Until another one is attached, the SPU output
+    /// isn't produced.
+    pub fn take_audio_sink(&mut self) -> Option<Box<dyn audio::AudioSink>> {
+        self.audio.take()
+    }
+
+    /// Requests `interrupt` in the interrupt controller, as if the device raising it had done so.
+    /// Whether the CPU takes it still depends on I_MASK and the COP0 status register. Useful for
+    /// testing interrupt handlers without running the device.
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.psx.interrupts.status.request(interrupt);
+    }
+
+    /// Returns the area of VRAM which is currently displayed, e.g. to crop VRAM to what's visible.
+    pub fn display_rect(&self) -> gpu::DisplayRect {
+        gpu::DisplayRect::current(&self.psx)
+    }
+
+    /// Starts dumping every displayed frame into `dir` in the given format, stopping any ongoing
+    /// dump. Frames are captured on VBlank and written in the background.
+    pub fn start_frame_dump(
+        &mut self,
+        dir: PathBuf,
+        format: FrameFormat,
+    ) -> Result<(), FrameDumpError> {
+        let frame_rate = CYCLES_SECOND as f64 / self.psx.gpu.timing().cycles_per_frame() as f64;
+
+        self.stop_frame_dump();
+        self.frame_dump = Some(FrameDump::start(
+            dir,
+            format,
+            frame_rate,
+            self.psx.loggers.gpu.clone(),
+        )?);
+
+        Ok(())
+    }
+
+    /// Stops the ongoing frame dump, if any, waiting for the queued frames to be written.
+    pub fn stop_frame_dump(&mut self) {
+        if let Some(dump) = self.frame_dump.take() {
+            dump.stop();
+        }
+    }
+
+    /// Returns the last boot progress code the BIOS wrote to the POST register, if any. Useful for
+    /// showing boot progress in a frontend.
+    pub fn post_code(&self) -> Option<u8> {
+        self.psx.memory.post
+    }
+
+    /// The current runtime settings.
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Replaces the runtime settings. The clock multiplier is clamped to
+    /// [`CLOCK_MULTIPLIER_RANGE`], and a non-finite one is ignored.
+    pub fn set_settings(&mut self, mut settings: Settings) {
+        if !settings.clock_multiplier.is_finite() {
+            settings.clock_multiplier = self.settings.clock_multiplier;
+        }
+
+        self.settings = settings.clamped();
+    }
+
+    /// The factor by which the CPU clock is scaled. See [`Settings::clock_multiplier`].
+    pub fn clock_multiplier(&self) -> f64 {
+        self.settings.clock_multiplier
+    }
+
+    /// Sets the factor by which the CPU clock is scaled, clamped to [`CLOCK_MULTIPLIER_RANGE`],
+    /// and returns the value actually set. Non-finite values are ignored. See
+    /// [`Settings::clock_multiplier`].
+    pub fn set_clock_multiplier(&mut self, multiplier: f64) -> f64 {
+        self.set_settings(Settings {
+            clock_multiplier: multiplier,
+            ..self.settings.clone()
+        });
+
+        self.settings.clock_multiplier
+    }
+
     pub fn cdrom_mut(&mut self) -> &mut cdrom::Cdrom {
         &mut self.cdrom
     }
@@ -187,11 +674,168 @@ impl Emulator {
         &self.cpu
     }
 
+    /// Makes [`Emulator::cycle_for`] and [`Emulator::run_until`] stop whenever `exception` is
+    /// triggered, right after the CPU jumps to the exception vector. Useful for finding out why a
+    /// program crashes.
+    pub fn break_on_exception(&mut self, exception: Exception) {
+        self.cpu.break_on_exception(exception, true);
+    }
+
+    /// Stops breaking on `exception`. See [`Emulator::break_on_exception`].
+    pub fn clear_break_on_exception(&mut self, exception: Exception) {
+        self.cpu.break_on_exception(exception, false);
+    }
+
+    /// Enables or disables the instruction profiler, which counts how many times each CPU opcode
+    /// and GTE command is executed. Enabling it starts counting from zero.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.cpu.set_profiling(enabled);
+    }
+
+    /// Returns the execution counts gathered by the instruction profiler, if it's enabled. See
+    /// [`Emulator::set_profiling`].
+    pub fn profile(&self) -> Option<&cpu::Profile> {
+        self.cpu.profile()
+    }
+
+    /// Enables or disables the execution heatmap, which counts how many instructions are executed
+    /// in each region of [`cpu::HEATMAP_BUCKET_LEN`] bytes. Enabling it starts counting from zero.
+    pub fn set_heatmap(&mut self, enabled: bool) {
+        self.cpu.set_heatmap(enabled);
+    }
+
+    /// Returns the execution heatmap, if it's enabled. See [`Emulator::set_heatmap`].
+    pub fn heatmap(&self) -> Option<&cpu::Heatmap> {
+        self.cpu.heatmap()
+    }
+
+    /// Returns the bus accesses recorded by the flight recorder, ordered from oldest to newest.
+    /// See [`Config::flight_recorder`].
+    pub fn flight_recorder(&mut self) -> &[BusRecord] {
+        self.psx.flight_recorder.records()
+    }
+
+    /// Returns the DMA transfers currently in progress. The controller executes a single transfer
+    /// at a time, so there's at most one.
+    pub fn active_dma(&self) -> impl Iterator<Item = dma::ActiveTransfer> {
+        self.dma.active(&self.psx).into_iter()
+    }
+
+    /// Returns the transfer statistics of every DMA channel, indexed by channel.
+    pub fn dma_stats(&self) -> &[dma::ChannelStats; 7] {
+        self.dma.stats()
+    }
+
+    /// Walks the ordering table whose linked list starts at `base`, visiting at most `max_nodes`
+    /// nodes, and summarizes its packets. See [`dma::walk_ordering_table`].
+    pub fn walk_ordering_table(&self, base: Address, max_nodes: u32) -> dma::OtReport {
+        dma::walk_ordering_table(&self.psx, base, max_nodes)
+    }
+
+    /// Writes `data` to memory starting at `addr`, bypassing the BIOS. Together with
+    /// [`Emulator::set_pc`], this allows running raw machine code, e.g. short instruction
+    /// sequences when testing the CPU.
+    pub fn load_raw(&mut self, addr: Address, data: &[u8]) {
+        for (offset, &byte) in data.iter().enumerate() {
+            self.psx.write_unaligned::<u8, true>(
+                Address(addr.value().wrapping_add(offset as u32)),
+                byte,
+            );
+        }
+    }
+
+    /// Makes the CPU continue execution at `addr`, flushing its pipeline. The instruction at
+    /// `addr` is the next one executed.
+    pub fn set_pc(&mut self, addr: Address) {
+        self.cpu.jump_to(&mut self.psx, addr);
+    }
+
+    /// Resets the CPU the way the reset button does from the point of view of software: execution
+    /// continues at the reset vector with the System Status register in its reset state, i.e.
+    /// with the boot exception vectors selected. Unlike creating a new emulator, RAM, the CD-ROM
+    /// drive and every other device keep their state, and the BIOS reinitializes what it needs.
+    pub fn soft_reset(&mut self) {
+        self.psx.cop0.regs.reset_system_status();
+        self.cpu
+            .jump_to(&mut self.psx, Address(shimmer_core::cpu::RESET_VECTOR));
+    }
+
+    /// Puts the system into the state the BIOS leaves it in once it's done booting, as in
+    /// [`PSX::reset_to_bios`], and continues execution at the shell entry point. A sideloaded
+    /// executable is loaded right away.
+    ///
+    /// The kernel isn't installed, so code which calls it only runs once the BIOS has booted and
+    /// left it in RAM, e.g. to restart the shell or a sideloaded executable.
+    pub fn reset_to_bios(&mut self) {
+        self.psx.reset_to_bios();
+        self.cpu.jump_to(&mut self.psx, SHELL_ENTRY);
+    }
+
+    /// Soft resets if the configured soft reset combo has just been pressed.
+    fn check_soft_reset_combo(&mut self) {
+        let Some(combo) = self.settings.soft_reset_combo else {
+            return;
+        };
+
+        let input = self.sio0.joypad().digital_input.to_bits();
+        let held = input & combo.to_bits() == combo.to_bits();
+        if held && !self.soft_reset_combo_held {
+            self.soft_reset();
+        }
+
+        self.soft_reset_combo_held = held;
+    }
+
+    /// Redirects the kernel file functions (open, seek, read and close) on `cdrom:` paths to the
+    /// files in `host_dir`, bypassing the CD-ROM drive. Useful for iterating on homebrew without
+    /// rebuilding a disc image.
+    pub fn redirect_cdrom_files(&mut self, host_dir: PathBuf) {
+        self.cpu.redirect_cdrom_files(host_dir);
+    }
+
+    /// Registers a hook on calls to the kernel function `function`, which can observe them or
+    /// override them by returning the value to return from the call with. Hooks take precedence
+    /// over [`Emulator::redirect_cdrom_files`]. See [`cpu::KernelHook`].
+    pub fn hook_kernel_function(
+        &mut self,
+        function: kernel::Function,
+        hook: impl FnMut(&mut PSX, &cpu::KernelCall) -> Option<u32> + Send + 'static,
+    ) {
+        self.cpu.hook_kernel_function(function, Box::new(hook));
+    }
+
+    /// Removes every hook on the kernel function `function`.
+    pub fn unhook_kernel_function(&mut self, function: kernel::Function) {
+        self.cpu.unhook_kernel_function(function);
+    }
+
+    /// Sets where the characters printed by the kernel are mirrored to, as they're printed. By
+    /// default, they're printed to the stdout of the process. Either way, they're also accumulated
+    /// in [`Memory::kernel_stdout`](shimmer_core::mem::Memory::kernel_stdout).
+    pub fn set_stdout_mirror(&mut self, mirror: cpu::StdoutMirror) {
+        self.cpu.set_stdout_mirror(mirror);
+    }
+
     pub fn process_event(&mut self, event: Event) {
         match event {
             Event::VBlank => {
                 self.gpu.vblank(&mut self.psx);
             }
+            Event::Present => {
+                self.gpu.present(&mut self.psx);
+                self.sio0.flush_memory_cards(&mut self.psx);
+                self.check_soft_reset_combo();
+
+                if let Some(dump) = &mut self.frame_dump
+                    && let Some(capture) = self.gpu.capture_display(&self.psx)
+                {
+                    let cycles = self.psx.gpu.timing().cycles_per_frame();
+                    dump.push(capture, cycles as f64 * 1e6 / CYCLES_SECOND as f64);
+                }
+            }
+            Event::VBlankEnd => {
+                self.gpu.vblank_end(&mut self.psx);
+            }
             Event::Timer(event) => {
                 self.timers.update(&mut self.psx, event);
             }
@@ -210,14 +854,66 @@ impl Emulator {
             Event::Sio(event) => {
                 self.sio0.update(&mut self.psx, event);
             }
+            Event::SpuSample => {
+                if let Some(sink) = &mut self.audio {
+                    sink.push(spu::output(&mut self.psx));
+                    self.psx
+                        .scheduler
+                        .schedule(Event::SpuSample, spu::SAMPLE_CYCLES);
+                }
+            }
+        }
+    }
+
+    /// Converts CPU cycles to system cycles according to the clock multiplier, carrying over
+    /// fractional cycles to the next call.
+    #[expect(clippy::float_cmp, reason = "1.0 is exactly representable")]
+    fn scale_cpu_cycles(&mut self, cycles: u64) -> u64 {
+        if self.settings.clock_multiplier == 1.0 {
+            return cycles;
+        }
+
+        self.cycle_fraction += cycles as f64 / self.settings.clock_multiplier;
+        let whole = self.cycle_fraction.trunc();
+        self.cycle_fraction -= whole;
+
+        whole as u64
+    }
+
+    /// If the CPU is at the start of an iteration of an idle loop, skips as many whole iterations
+    /// of it as fit in `remaining` cycles and returns the cycles they take.
+    #[expect(clippy::float_cmp, reason = "1.0 is exactly representable")]
+    fn skip_idle_loop(&mut self, remaining: u64) -> Option<u64> {
+        // with a clock multiplier, the system cycles taken by an iteration depend on the fraction
+        // carried over from the previous one
+        if self.settings.clock_multiplier != 1.0 {
+            return None;
         }
+
+        let iteration = self.cpu.idle_loop_iteration(&self.psx)?;
+        let iterations = remaining / iteration;
+        (iterations > 0).then_some(iterations * iteration)
     }
 
-    fn exec_until_next_event(&mut self, limit: u64) -> u64 {
+    /// Executes until the next event, `limit` cycles have passed, `stop` returns `true` or an
+    /// exception breaks execution. `stop` is checked after every instruction, and receives the
+    /// address of the next instruction to execute.
+    ///
+    /// Iterations of idle loops might be skipped, but since the state of the system is the same
+    /// at every instruction of every iteration, `stop` would have returned `true` during the
+    /// first one if it was going to.
+    fn exec_until_next_event(
+        &mut self,
+        limit: u64,
+        mut stop: impl FnMut(&PSX, Address) -> bool,
+    ) -> u64 {
         let mut cycles = 0;
         let mut remaining = self.psx.scheduler.until_next().unwrap_or(limit).min(limit);
         let mut time_at_event = self.psx.scheduler.elapsed() + remaining;
 
+        // events might have changed the memory the CPU is polling
+        self.cpu.forget_idle_loop();
+
         while remaining > 0 {
             if self.psx.scheduler.last_scheduled_time() < time_at_event {
                 remaining = self.psx.scheduler.until_next().unwrap().min(limit);
@@ -228,30 +924,784 @@ impl Emulator {
             // stall CPU while DMA is ongoing
             let elapsed = if self.dma.ongoing() {
                 cold_path();
-                1
+                self.scale_cpu_cycles(1)
+            } else if let Some(skipped) = self.skip_idle_loop(remaining) {
+                skipped
             } else {
-                self.cpu.exec_next(&mut self.psx)
+                let cycles = self.cpu.exec_next(&mut self.psx);
+                self.scale_cpu_cycles(cycles)
             };
 
             // HACK: trades some precision for ease of implementation, shouldn't matter much. most
             // noticeable effect is slightly higher emulation speed, but it's still pretty minor
             cycles += elapsed.min(remaining);
             remaining -= elapsed.min(remaining);
+
+            if stop(&self.psx, self.cpu.instr_delay_slot().1) || self.cpu.has_exception_break() {
+                break;
+            }
         }
 
         cycles
     }
 
-    pub fn cycle_for(&mut self, cycles: u64) {
+    /// Executes for `cycles` cycles, processing events in between. Stops early if an exception
+    /// breaks execution, returning it. See [`Emulator::break_on_exception`].
+    pub fn cycle_for(&mut self, cycles: u64) -> Option<cpu::ExceptionBreak> {
         let mut remaining = cycles;
         while remaining > 0 {
-            let executed = self.exec_until_next_event(remaining);
+            let executed = self.exec_until_next_event(remaining, |_, _| false);
             self.psx.scheduler.advance(executed);
             remaining -= executed;
 
             while let Some(event) = self.psx.scheduler.pop() {
                 self.process_event(event);
             }
+
+            if let Some(exception_break) = self.cpu.take_exception_break() {
+                return Some(exception_break);
+            }
+        }
+
+        None
+    }
+
+    /// Executes exactly `count` CPU instructions, processing events in between, and returns the
+    /// cycles it took. Cycles in which the CPU is stalled by DMA don't count as instructions.
+    ///
+    /// Unlike [`Emulator::cycle_for`], this is meant for precise stepping, such as by TAS tooling,
+    /// and is therefore slower. It also doesn't stop on exception breaks.
+    pub fn step_instructions(&mut self, count: u64) -> u64 {
+        let mut cycles = 0;
+        let mut executed = 0;
+        while executed < count {
+            // stall CPU while DMA is ongoing
+            let elapsed = if self.dma.ongoing() {
+                cold_path();
+                self.scale_cpu_cycles(1)
+            } else {
+                executed += 1;
+                let cycles = self.cpu.exec_next(&mut self.psx);
+                self.scale_cpu_cycles(cycles)
+            };
+
+            self.psx.scheduler.advance(elapsed);
+            cycles += elapsed;
+            self.cpu.take_exception_break();
+
+            while let Some(event) = self.psx.scheduler.pop() {
+                self.process_event(event);
+            }
+        }
+
+        cycles
+    }
+
+    /// Runs the emulator until one of `conditions` is met, `predicate` returns `true`, an
+    /// exception breaks execution or `max_cycles` have been executed, whichever comes first.
+    ///
+    /// The conditions are checked after every instruction, while `predicate` is only checked
+    /// after events are processed, so prefer conditions whenever possible.
+    pub fn run_until(
+        &mut self,
+        max_cycles: u64,
+        conditions: &[Condition],
+        mut predicate: impl FnMut(&PSX) -> bool,
+    ) -> RunUntilResult {
+        let mut cycles = 0;
+        // forces standard output to be searched on the first check
+        let mut stdout_pushed = u64::MAX;
+        let mut met = None;
+
+        while cycles < max_cycles {
+            let executed = self.exec_until_next_event(max_cycles - cycles, |psx, next_instr| {
+                let stdout_changed = psx.memory.kernel_stdout_pushed != stdout_pushed;
+                stdout_pushed = psx.memory.kernel_stdout_pushed;
+
+                met = conditions
+                    .iter()
+                    .position(|condition| condition.holds(psx, next_instr, stdout_changed));
+                met.is_some()
+            });
+
+            self.psx.scheduler.advance(executed);
+            cycles += executed;
+
+            while let Some(event) = self.psx.scheduler.pop() {
+                self.process_event(event);
+            }
+
+            if let Some(exception_break) = self.cpu.take_exception_break() {
+                return RunUntilResult {
+                    reason: StopReason::Exception(exception_break),
+                    cycles,
+                };
+            }
+
+            if let Some(index) = met {
+                return RunUntilResult {
+                    reason: StopReason::Condition(index),
+                    cycles,
+                };
+            }
+
+            if predicate(&self.psx) {
+                return RunUntilResult {
+                    reason: StopReason::Predicate,
+                    cycles,
+                };
+            }
+        }
+
+        RunUntilResult {
+            reason: StopReason::MaxCycles,
+            cycles,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use audio::{AudioBuffer, UnderrunPolicy};
+    use shimmer_core::{
+        CYCLES_MILLIS,
+        cpu::cop0,
+        exe::{Executable, Header},
+        mem::Region,
+    };
+    use std::sync::{Arc, Mutex};
+    use strum::VariantArray;
+
+    const PROGRAM: Address = Address(0x8000_2000);
+    const MAX_CYCLES: u64 = 100_000;
+
+    // addiu $t0, $zero, 1; addiu $t1, $zero, 2; addiu $t2, $zero, 3
+    const ADDIU_SEQUENCE: [u32; 3] = [0x2408_0001, 0x2409_0002, 0x240A_0003];
+
+    /// Encodes `j target`.
+    fn jump(target: Address) -> u32 {
+        0x0800_0000 | ((target.value() >> 2) & 0x03FF_FFFF)
+    }
+
+    /// Returns an emulator about to execute `program`, placed at [`PROGRAM`] and followed by a
+    /// jump to the idle loop.
+    fn emulator(program: &[u32]) -> Emulator {
+        let mut emulator = testing::emulator(testing::builder());
+        let program: Vec<u8> = program
+            .iter()
+            .copied()
+            .chain([jump(testing::IDLE_LOOP), 0])
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        emulator.load_raw(PROGRAM, &program);
+        emulator.set_pc(PROGRAM);
+        emulator
+    }
+
+    #[test]
+    fn raw_code_runs_at_the_overridden_pc() {
+        const ENTRY: Address = Address(0x8000_0000);
+
+        let program = [
+            0x2408_0028, // addiu $t0, $zero, 40
+            0x2409_0002, // addiu $t1, $zero, 2
+            0x0109_5020, // add $t2, $t0, $t1
+            jump(testing::IDLE_LOOP),
+            0,
+        ];
+
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.load_raw(ENTRY, &program.map(u32::to_le_bytes).concat());
+        emulator.set_pc(ENTRY);
+        assert_eq!(emulator.cpu.instr_delay_slot().1, ENTRY);
+
+        emulator.step_instructions(3);
+        assert_eq!(emulator.psx().cpu.regs.read(Reg::T2), 42);
+
+        emulator.step_instructions(2);
+        assert_eq!(emulator.cpu.instr_delay_slot().1, testing::IDLE_LOOP);
+    }
+
+    #[test]
+    fn pc_equals_stops_before_the_instruction() {
+        let mut emulator = emulator(&ADDIU_SEQUENCE);
+        let result = emulator.run_until(
+            MAX_CYCLES,
+            &[Condition::PcEquals(Address(PROGRAM.value() + 8))],
+            |_| false,
+        );
+
+        assert_eq!(result.reason, StopReason::Condition(0));
+        assert_eq!(
+            emulator.cpu.instr_delay_slot().1,
+            Address(PROGRAM.value() + 8)
+        );
+
+        let regs = &emulator.psx().cpu.regs;
+        assert_eq!(regs.read(Reg::T0), 1);
+        assert_eq!(regs.read(Reg::T1), 2);
+        assert_eq!(regs.read(Reg::T2), 0);
+    }
+
+    #[test]
+    fn pc_equals_shell_entry_stops_before_the_sideload() {
+        const DESTINATION: Address = Address(0x8001_0000);
+
+        let program = [jump(testing::IDLE_LOOP), 0];
+        let mut emulator = emulator(&[jump(SHELL_ENTRY), 0]);
+        emulator.psx_mut().memory.sideload = Some(Executable {
+            header: Header {
+                initial_pc: DESTINATION,
+                initial_gp: 0,
+                destination: DESTINATION,
+                length: 8,
+                data_start: Address(0),
+                data_length: 0,
+                bss_start: Address(0),
+                bss_length: 0,
+                initial_sp_base: 0,
+                initial_sp_offset: 0,
+                marker: CString::default(),
+            },
+            program: program.iter().flat_map(|word| word.to_le_bytes()).collect(),
+        });
+
+        let result = emulator.run_until(MAX_CYCLES, &[Condition::PcEquals(SHELL_ENTRY)], |_| false);
+        assert_eq!(result.reason, StopReason::Condition(0));
+        assert_eq!(
+            emulator.psx_mut().read::<u32, true>(DESTINATION).unwrap(),
+            0
+        );
+
+        emulator.step_instructions(1);
+        assert_eq!(
+            emulator.psx_mut().read::<u32, true>(DESTINATION).unwrap(),
+            program[0]
+        );
+    }
+
+    #[test]
+    fn kernel_stdout_contains_stops_once_printed() {
+        // putchar through B(3Dh), which returns right away
+        let put_char = |char: u8| {
+            [
+                0x2409_003D,                   // addiu $t1, $zero, 0x3D
+                0x2404_0000 | u32::from(char), // addiu $a0, $zero, char
+                0x240A_00B0,                   // addiu $t2, $zero, 0xB0
+                0x0140_F809,                   // jalr $t2
+                0,
+            ]
+        };
+        let program: Vec<u32> = put_char(b'h').into_iter().chain(put_char(b'i')).collect();
+
+        let mut emulator = emulator(&program);
+        // jr $ra; nop
+        emulator.load_raw(
+            Address(0xB0),
+            &[0x03E0_0008u32, 0].map(u32::to_le_bytes).concat(),
+        );
+
+        let result = emulator.run_until(
+            MAX_CYCLES,
+            &[
+                Condition::PcEquals(Address(0x8000_4000)),
+                Condition::KernelStdoutContains("hi"),
+            ],
+            |_| false,
+        );
+
+        assert_eq!(result.reason, StopReason::Condition(1));
+        assert_eq!(emulator.psx().memory.kernel_stdout, "hi");
+    }
+
+    #[test]
+    fn memory_equals_stops_once_written() {
+        const TARGET: Address = Address(0x8000_3000);
+
+        let program = [
+            0x3C08_1234, // lui $t0, 0x1234
+            0x3508_5678, // ori $t0, $t0, 0x5678
+            0x3C09_8000, // lui $t1, 0x8000
+            0xAD28_3000, // sw $t0, 0x3000($t1)
+        ];
+
+        let mut emulator = emulator(&program);
+        let result = emulator.run_until(
+            MAX_CYCLES,
+            &[Condition::MemoryEquals(
+                TARGET,
+                &0x1234_5678u32.to_le_bytes(),
+            )],
+            |_| false,
+        );
+
+        assert_eq!(result.reason, StopReason::Condition(0));
+        assert_eq!(
+            emulator.cpu.instr_delay_slot().1,
+            Address(PROGRAM.value() + 16)
+        );
+    }
+
+    #[test]
+    fn predicate_stops_execution() {
+        let mut emulator = emulator(&ADDIU_SEQUENCE);
+        let result = emulator.run_until(MAX_CYCLES, &[], |psx| psx.cpu.regs.read(Reg::T2) == 3);
+
+        assert_eq!(result.reason, StopReason::Predicate);
+        assert!(result.cycles < MAX_CYCLES);
+    }
+
+    #[test]
+    fn max_cycles_times_out() {
+        let mut emulator = testing::emulator(testing::builder());
+        let result = emulator.run_until(
+            MAX_CYCLES,
+            &[
+                Condition::PcEquals(PROGRAM),
+                Condition::KernelStdoutContains("never"),
+                Condition::MemoryEquals(PROGRAM, &[0xFF]),
+            ],
+            |_| false,
+        );
+
+        assert_eq!(result.reason, StopReason::MaxCycles);
+        assert!(result.cycles >= MAX_CYCLES);
+    }
+
+    #[test]
+    fn step_instructions_executes_exactly_the_count() {
+        let mut emulator = emulator(&ADDIU_SEQUENCE);
+
+        let cycles = emulator.step_instructions(2);
+        assert!(cycles >= 2);
+        assert_eq!(
+            emulator.cpu.instr_delay_slot().1,
+            Address(PROGRAM.value() + 8)
+        );
+
+        let regs = &emulator.psx().cpu.regs;
+        assert_eq!(regs.read(Reg::T0), 1);
+        assert_eq!(regs.read(Reg::T1), 2);
+        assert_eq!(regs.read(Reg::T2), 0);
+
+        // the rest of the sequence, the jump to the idle loop and its delay slot
+        emulator.step_instructions(3);
+        assert_eq!(emulator.cpu.instr_delay_slot().1, testing::IDLE_LOOP);
+        assert_eq!(emulator.psx().cpu.regs.read(Reg::T2), 3);
+    }
+
+    #[test]
+    fn requested_interrupts_vector_to_the_handler_when_enabled() {
+        let mut emulator = testing::emulator(testing::builder());
+        emulator.step_instructions(1);
+
+        // nothing happens while the interrupt is masked
+        emulator.request_interrupt(Interrupt::VBlank);
+        emulator.step_instructions(2);
+        assert_ne!(emulator.psx().cpu.regs.read_pc(), 0x8000_0080);
+
+        // IEc and IM2, with the exception vectors in RAM
+        let psx = emulator.psx_mut();
+        psx.cop0.regs.write(cop0::Reg::COP0_SR, 0x0000_0401);
+        testing::write::<u32>(psx, io::Reg::InterruptMask, 1);
+
+        emulator.step_instructions(1);
+        let psx = emulator.psx();
+        assert_eq!(
+            psx.cop0.regs.cause().exception(),
+            Some(Exception::Interrupt)
+        );
+        assert_eq!(psx.cpu.regs.read_pc(), 0x8000_0080);
+    }
+
+    // lui $t1, 0x8000; ori $t1, $t1, 0x3001; lw $t0, 0($t1)
+    const UNALIGNED_LOAD: [u32; 3] = [0x3C09_8000, 0x3529_3001, 0x8D28_0000];
+
+    #[test]
+    fn break_on_exception_halts_on_unaligned_loads() {
+        let mut emulator = emulator(&UNALIGNED_LOAD);
+        emulator.psx_mut().cop0.regs.write(cop0::Reg::COP0_SR, 0);
+        emulator.break_on_exception(Exception::AddressErrorLoad);
+
+        let exception_break = emulator.cycle_for(MAX_CYCLES);
+        assert_eq!(
+            exception_break,
+            Some(cpu::ExceptionBreak {
+                exception: Exception::AddressErrorLoad,
+                address: Address(PROGRAM.value() + 8),
+            })
+        );
+
+        // execution stopped right after jumping to the exception vector
+        let psx = emulator.psx();
+        assert_eq!(psx.cop0.regs.read(cop0::Reg::COP0_BAD_VADDR), 0x8000_3001);
+        assert_eq!(psx.cpu.regs.read_pc(), 0x8000_0080);
+    }
+
+    #[test]
+    fn break_on_exception_only_halts_on_the_chosen_exceptions() {
+        let mut emulator = emulator(&UNALIGNED_LOAD);
+        emulator.psx_mut().cop0.regs.write(cop0::Reg::COP0_SR, 0);
+        emulator.break_on_exception(Exception::Syscall);
+        emulator.break_on_exception(Exception::AddressErrorLoad);
+        emulator.clear_break_on_exception(Exception::AddressErrorLoad);
+
+        assert_eq!(emulator.cycle_for(MAX_CYCLES), None);
+        assert_eq!(
+            emulator.psx().cop0.regs.cause().exception(),
+            Some(Exception::AddressErrorLoad)
+        );
+    }
+
+    #[test]
+    fn soft_reset_jumps_to_the_reset_vector_and_keeps_ram() {
+        const MARKER: Address = Address(0x8000_3000);
+
+        let mut emulator = emulator(&[
+            0x3C08_1234, // lui $t0, 0x1234
+            0x3508_5678, // ori $t0, $t0, 0x5678
+            0x3C09_8000, // lui $t1, 0x8000
+            0xAD28_3000, // sw $t0, 0x3000($t1)
+        ]);
+        emulator
+            .psx_mut()
+            .cop0
+            .regs
+            .write(cop0::Reg::COP0_SR, 0x0000_0401);
+        emulator.step_instructions(8);
+
+        emulator.soft_reset();
+        assert_eq!(
+            emulator.cpu.instr_delay_slot().1,
+            Address(shimmer_core::cpu::RESET_VECTOR)
+        );
+
+        let psx = emulator.psx_mut();
+        let status = psx.cop0.regs.system_status();
+        assert!(status.boot_exception_vectors_in_kseg1());
+        assert!(!status.isolate_cache());
+        assert!(!status.system_interrupts_enabled());
+        assert_eq!(psx.read::<u32, true>(MARKER).unwrap(), 0x1234_5678);
+    }
+
+    #[test]
+    fn soft_reset_combo_resets_once_per_press() {
+        let mut emulator = testing::emulator(testing::builder().settings(Settings {
+            soft_reset_combo: Some(sio0::soft_reset_combo()),
+            ..Default::default()
+        }));
+
+        let reset_vector = [Condition::PcEquals(Address(
+            shimmer_core::cpu::RESET_VECTOR,
+        ))];
+        let run = |emulator: &mut Emulator| {
+            let result = emulator.run_until(CYCLES_SECOND / 10, &reset_vector, |_| false);
+            testing::idle(emulator);
+            result.reason
+        };
+
+        assert_eq!(run(&mut emulator), StopReason::MaxCycles);
+
+        emulator.sio0.joypad_mut().digital_input = sio0::soft_reset_combo();
+        assert_eq!(run(&mut emulator), StopReason::Condition(0));
+        // holding the combo doesn't reset again
+        assert_eq!(run(&mut emulator), StopReason::MaxCycles);
+
+        // but pressing it again does
+        emulator.sio0.joypad_mut().digital_input = DigitalInput::default();
+        assert_eq!(run(&mut emulator), StopReason::MaxCycles);
+        emulator.sio0.joypad_mut().digital_input = sio0::soft_reset_combo();
+        assert_eq!(run(&mut emulator), StopReason::Condition(0));
+    }
+
+    #[test]
+    fn run_until_stops_on_exception_breaks() {
+        let mut emulator = emulator(&UNALIGNED_LOAD);
+        emulator.psx_mut().cop0.regs.write(cop0::Reg::COP0_SR, 0);
+        emulator.break_on_exception(Exception::AddressErrorLoad);
+
+        let result = emulator.run_until(MAX_CYCLES, &[], |_| false);
+        assert!(matches!(
+            result.reason,
+            StopReason::Exception(cpu::ExceptionBreak {
+                exception: Exception::AddressErrorLoad,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn post_code_is_the_last_one_written() {
+        const POST: Address = Address(io::Reg::Post as u32);
+
+        let mut emulator = testing::emulator(testing::builder());
+        assert_eq!(emulator.post_code(), None);
+
+        for code in [0x01, 0x02, 0x03, 0x03, 0x07] {
+            emulator.psx_mut().write::<u8, true>(POST, code).unwrap();
+            assert_eq!(emulator.post_code(), Some(code));
+        }
+
+        // wider writes set it through the byte which overlaps the register
+        let word = Address(POST.value() - 1);
+        emulator.psx_mut().write::<u16, true>(word, 0x0F00).unwrap();
+        assert_eq!(emulator.post_code(), Some(0x0F));
+
+        // while writes next to it leave it alone
+        emulator.psx_mut().write::<u8, true>(word, 0xAA).unwrap();
+        assert_eq!(emulator.post_code(), Some(0x0F));
+    }
+
+    /// Runs a loop incrementing `$t0` with the given clock multiplier for a few frames, returning
+    /// the elapsed cycles and `$t0` at each VBlank.
+    fn vblanks(clock_multiplier: f64) -> Vec<(Cycles, u32)> {
+        let mut emulator = testing::emulator(testing::builder().settings(Settings {
+            clock_multiplier,
+            ..Default::default()
+        }));
+
+        // addiu $t0, $t0, 1; j PROGRAM; nop
+        let program = [0x2508_0001, jump(PROGRAM), 0];
+        emulator.load_raw(PROGRAM, &program.map(u32::to_le_bytes).concat());
+        emulator.set_pc(PROGRAM);
+
+        (0..4)
+            .map(|_| {
+                let result = emulator.run_until(CYCLES_SECOND, &[], |psx| {
+                    psx.interrupts.status.to_bits() & 1 != 0
+                });
+                assert_eq!(result.reason, StopReason::Predicate);
+
+                let psx = emulator.psx_mut();
+                testing::write::<u32>(psx, io::Reg::InterruptStatus, !1);
+                (
+                    emulator.elapsed_cycles(),
+                    emulator.psx().cpu.regs.read(Reg::T0),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overclocking_scales_instructions_but_not_vblanks() {
+        let normal = vblanks(1.0);
+        let overclocked = vblanks(2.0);
+
+        for (normal, overclocked) in normal.iter().zip(&overclocked) {
+            assert!(
+                normal.0.abs_diff(overclocked.0) < 8,
+                "{normal:?} vs {overclocked:?}"
+            );
+        }
+
+        for (normal, overclocked) in normal.windows(2).zip(overclocked.windows(2)) {
+            let normal = normal[1].1 - normal[0].1;
+            let overclocked = overclocked[1].1 - overclocked[0].1;
+            let ratio = f64::from(overclocked) / f64::from(normal);
+            assert!((1.95..2.05).contains(&ratio), "ratio is {ratio}");
+        }
+    }
+
+    /// Runs a busy loop of `iterations` with the given clock multiplier, returning the cycles it
+    /// took, the ticks Timer 2 counted meanwhile and the cycles each of its ticks takes.
+    fn busy_loop(multiplier: f64, iterations: u32) -> (u64, u64, u64) {
+        // loop: addiu $t0, $t0, -1; bne $t0, $zero, loop; nop
+        let mut emulator = emulator(&[0x2508_FFFF, 0x1500_FFFE, 0x0000_0000]);
+        emulator.set_clock_multiplier(multiplier);
+        emulator.psx.cpu.regs.write(Reg::T0, iterations);
+
+        // Timer 2 counts the system clock, and writing its mode resets it
+        testing::write::<u32>(&mut emulator.psx, io::Reg::Timer2Mode, 0);
+
+        let cycles = emulator.step_instructions(3 * u64::from(iterations));
+        let ticks = testing::read::<u32>(&mut emulator.psx, io::Reg::Timer2Value) & 0xFFFF;
+        let cycles_per_tick = emulator.psx.timers.timer2.mode.cycles_per_tick();
+        (cycles, u64::from(ticks), cycles_per_tick)
+    }
+
+    #[test]
+    fn overclocked_busy_loops_finish_sooner_but_timer_2_keeps_the_clock() {
+        // short enough for Timer 2 not to wrap around
+        const ITERATIONS: u32 = 5_000;
+
+        let normal = busy_loop(1.0, ITERATIONS);
+        let overclocked = busy_loop(2.0, ITERATIONS);
+
+        let speedup = normal.0 as f64 / overclocked.0 as f64;
+        assert!((1.9..=2.1).contains(&speedup), "speedup is {speedup}");
+
+        for (cycles, ticks, cycles_per_tick) in [normal, overclocked] {
+            let expected = cycles / cycles_per_tick;
+            assert!(
+                ticks.abs_diff(expected) <= expected / 100,
+                "{ticks} ticks in {cycles} cycles, {cycles_per_tick} cycles per tick"
+            );
+        }
+    }
+
+    #[test]
+    fn clock_multiplier_is_clamped() {
+        let mut emulator = testing::emulator(testing::builder());
+        assert_eq!(emulator.set_clock_multiplier(10.0), 4.0);
+        assert_eq!(emulator.set_clock_multiplier(0.1), 0.5);
+        assert_eq!(emulator.set_clock_multiplier(1.5), 1.5);
+
+        // non-finite values are ignored
+        assert_eq!(emulator.set_clock_multiplier(f64::NAN), 1.5);
+        assert_eq!(emulator.set_clock_multiplier(f64::INFINITY), 1.5);
+
+        let settings = |clock_multiplier| Settings {
+            clock_multiplier,
+            ..Settings::default()
+        };
+
+        let emulator = testing::builder().settings(settings(8.0)).build().unwrap();
+        assert_eq!(emulator.clock_multiplier(), 4.0);
+
+        for multiplier in [0.0, -1.0, f64::NAN] {
+            let result = testing::builder().settings(settings(multiplier)).build();
+            assert!(
+                matches!(result, Err(EmulatorError::ClockMultiplier { .. })),
+                "{multiplier}"
+            );
+        }
+    }
+
+    #[test]
+    fn elapsed_time_follows_elapsed_cycles() {
+        let mut emulator = testing::emulator(testing::builder());
+        assert_eq!(emulator.elapsed_time(), Duration::ZERO);
+
+        let cycles = 2 * CYCLES_SECOND + CYCLES_SECOND / 2;
+        emulator.cycle_for(cycles);
+
+        // execution stops at instruction boundaries, so it might overshoot by a few cycles
+        let elapsed = emulator.elapsed_cycles();
+        assert!((cycles..cycles + 16).contains(&elapsed));
+
+        let error = emulator
+            .elapsed_time()
+            .abs_diff(Duration::from_millis(2500));
+        assert!(error < Duration::from_micros(1), "off by {error:?}");
+    }
+
+    #[test]
+    fn expansion1_rom_must_fit_its_region() {
+        let len = Region::Expansion1.len() as usize + 1;
+        let result = testing::builder().expansion1(vec![0; len]).build();
+        assert!(matches!(
+            result,
+            Err(EmulatorError::Expansion1TooLong { len: got }) if got == len
+        ));
+    }
+
+    #[test]
+    fn memory_cards_only_go_in_slots_0_and_1() {
+        let result = testing::builder()
+            .memory_card(2, PathBuf::from("card.mcr"))
+            .build();
+        assert!(matches!(
+            result,
+            Err(EmulatorError::MemoryCard {
+                slot: 2,
+                source: sio0::memcard::MemoryCardError::NoSuchSlot { slot: 2 }
+            })
+        ));
+
+        let mut emulator = testing::emulator(testing::builder());
+        assert!(emulator.insert_memory_card(2, None).is_err());
+        assert!(emulator.memory_card_mut(2).is_err());
+        assert!(emulator.memory_card_mut(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn audio_sink_receives_the_spu_output_while_attached() {
+        const CYCLES: u64 = 100 * CYCLES_MILLIS;
+
+        let buffer = Arc::new(Mutex::new(AudioBuffer::new(8192, UnderrunPolicy::Silence)));
+        let mut emulator = testing::emulator(testing::builder().audio(Box::new(buffer.clone())));
+        emulator.run_until(CYCLES, &[], |_| false);
+
+        let samples = buffer.lock().unwrap().len();
+        let expected = (CYCLES / spu::SAMPLE_CYCLES) as usize;
+        assert!(
+            samples.abs_diff(expected) <= 1,
+            "{samples} samples instead of {expected}"
+        );
+
+        emulator.take_audio_sink();
+        emulator.run_until(CYCLES, &[], |_| false);
+        assert_eq!(buffer.lock().unwrap().len(), samples);
+    }
+
+    #[test]
+    fn headless_psx_reads_back_ram_and_steps_instructions() {
+        let mut psx = testing::psx();
+
+        // addiu $t0, $zero, 42
+        let word = 0x2408_002A;
+        psx.write::<u32, true>(PROGRAM, word).unwrap();
+        assert_eq!(psx.read::<u32, true>(PROGRAM).unwrap(), word);
+
+        let mut interpreter = cpu::Interpreter::default();
+        interpreter.jump_to(&mut psx, PROGRAM);
+        interpreter.exec_next(&mut psx);
+
+        assert_eq!(psx.cpu.regs.read(Reg::T0), 42);
+    }
+
+    #[test]
+    fn runs_are_deterministic_even_when_stopped_at_the_midpoint() {
+        const CYCLES: u64 = 200 * CYCLES_MILLIS;
+
+        // counts iterations, storing the count at an address taken from timer 1:
+        // lui $t0, 0x1F80; lui $t1, 0x8001
+        // loop: lhu $t2, 0x1110($t0); addiu $t3, $t3, 1; andi $t2, $t2, 0x0FFC
+        // addu $t2, $t2, $t1; sw $t3, 0($t2); j loop; nop
+        let code = [
+            0x3C08_1F80,
+            0x3C09_8001,
+            0x950A_1110,
+            0x256B_0001,
+            0x314A_0FFC,
+            0x0149_5021,
+            0xAD4B_0000,
+            jump(Address(PROGRAM.value() + 8)),
+            0,
+        ];
+
+        // the end state of a run, stopping at each of `stops` on the way to the end
+        let run = |stops: &[u64]| {
+            let mut emulator = testing::emulator(testing::builder());
+            emulator.load_raw(PROGRAM, &code.map(u32::to_le_bytes).concat());
+            emulator.set_pc(PROGRAM);
+
+            for &stop in stops.iter().chain(&[CYCLES]) {
+                let remaining = stop - emulator.elapsed_cycles();
+                emulator.run_until(remaining, &[], |_| false);
+            }
+
+            let regs = &emulator.psx.cpu.regs;
+            let regs: Vec<_> = Reg::VARIANTS.iter().map(|&reg| regs.read(reg)).collect();
+            (
+                emulator.elapsed_cycles(),
+                regs,
+                emulator.psx.cpu.regs.read_pc(),
+                emulator.psx.memory.ram.clone(),
+            )
+        };
+
+        let reference = run(&[]);
+        assert_ne!(
+            reference.1[Reg::T3 as usize],
+            0,
+            "the program should have run"
+        );
+        assert!(reference == run(&[]), "two runs ended in different states");
+        assert!(
+            reference == run(&[CYCLES / 2]),
+            "stopping at the midpoint changed the end state"
+        );
+    }
+}
@@ -5,8 +5,14 @@ use crate::{cdrom, sio0, timers};
 /// Possible schedule events.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Event {
-    /// Fire a VBlank.
+    /// Enter vertical blanking, firing the VBlank interrupt.
     VBlank,
+    /// Present the finished frame and do the work due once per frame, e.g. flushing memory cards.
+    /// Scheduled on [`Event::VBlank`], but kept apart from it so that the timing of the interrupt
+    /// doesn't depend on when rendering happens.
+    Present,
+    /// Leave vertical blanking.
+    VBlankEnd,
     /// Update the GPU state machine.
     Gpu,
     /// Update the DMA state machine and possibly start a transfer.
@@ -17,8 +23,29 @@ pub enum Event {
     Cdrom(cdrom::Event),
     /// Update the SIO state machine.
     Sio(sio0::Event),
-    /// Update timers.
+    /// A timer reached a point of interest.
     Timer(timers::Event),
+    /// Produce the next sample of the SPU output for the audio sink.
+    SpuSample,
+}
+
+impl Event {
+    /// The priority of this event among events scheduled for the same cycle. Lower values are
+    /// processed first.
+    ///
+    /// GP0 writes schedule both [`Event::Gpu`] and [`Event::DmaUpdate`] for the same cycle. The GPU
+    /// goes first, so that it consumes the words it received and updates its DMA request before
+    /// the DMA controller looks at it, as happens on hardware where DREQ reflects the FIFO. DMA
+    /// events go last in general for the same reason: every other device gets to update its DMA
+    /// request before the controller re-evaluates them.
+    #[inline(always)]
+    pub(crate) fn priority(self) -> u8 {
+        match self {
+            Self::Gpu => 0,
+            Self::DmaUpdate | Self::DmaAdvance => 2,
+            _ => 1,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -31,6 +58,9 @@ struct ScheduledEvent {
 ///
 /// The scheduler is responsible for keeping track of how many cycles have elapsed and what should
 /// happen next.
+///
+/// Events are processed in a deterministic order: earliest time first, then by
+/// [priority](Event::priority), then in the order they were scheduled.
 #[derive(Debug)]
 pub struct Scheduler {
     /// How many cycles have been executed since the start.
@@ -49,16 +79,11 @@ impl Default for Scheduler {
 
 impl Scheduler {
     pub fn new() -> Self {
-        let mut scheduler = Self {
+        Self {
             elapsed: 0,
             scheduled: Vec::with_capacity(16),
             last_scheduled_time: u64::MAX,
-        };
-
-        scheduler.schedule(Event::VBlank, 0);
-        scheduler.schedule(Event::Timer(timers::Event::Setup), 0);
-
-        scheduler
+        }
     }
 
     #[inline(always)]
@@ -70,11 +95,23 @@ impl Scheduler {
         });
     }
 
+    /// Cancels every scheduled event for which `predicate` returns `true`.
+    #[inline(always)]
+    pub fn cancel_if(&mut self, mut predicate: impl FnMut(&Event) -> bool) {
+        self.scheduled.retain(|e| !predicate(&e.event));
+    }
+
     #[inline(always)]
     pub fn len(&self) -> usize {
         self.scheduled.len()
     }
 
+    /// Returns the scheduled events along with the time at which they'll happen, in no particular
+    /// order.
+    pub fn pending(&self) -> impl Iterator<Item = (u64, Event)> {
+        self.scheduled.iter().map(|e| (e.time, e.event))
+    }
+
     #[inline(always)]
     pub fn advance(&mut self, count: u64) {
         self.elapsed += count;
@@ -88,12 +125,16 @@ impl Scheduler {
             .map(|e| e.time - self.elapsed)
     }
 
+    /// Pops the next event which is due, following the order described in [`Scheduler`].
     #[inline(always)]
     pub fn pop(&mut self) -> Option<Event> {
         self.scheduled
             .iter()
-            .position(|e| e.time <= self.elapsed)
-            .map(|i| self.scheduled.swap_remove(i).event)
+            .enumerate()
+            .filter(|(_, e)| e.time <= self.elapsed)
+            .min_by_key(|(_, e)| (e.time, e.event.priority()))
+            .map(|(i, _)| i)
+            .map(|i| self.scheduled.remove(i).event)
     }
 
     #[inline(always)]
@@ -106,3 +147,73 @@ impl Scheduler {
         self.last_scheduled_time
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Schedules `events` in order, then runs the scheduler until it's empty, returning the time
+    /// at which each event fired.
+    fn fire(events: &[(Event, u64)]) -> Vec<(u64, Event)> {
+        let mut scheduler = Scheduler::new();
+        for &(event, after) in events {
+            scheduler.schedule(event, after);
+        }
+
+        let mut order = Vec::new();
+        while let Some(until_next) = scheduler.until_next() {
+            scheduler.advance(until_next);
+            while let Some(event) = scheduler.pop() {
+                order.push((scheduler.elapsed(), event));
+            }
+        }
+
+        order
+    }
+
+    #[test]
+    fn gpu_goes_before_dma_within_the_same_cycle() {
+        // like a GP0 write, but in the opposite order: the GPU must still go first
+        let order = fire(&[
+            (Event::Gpu, 10),
+            (Event::DmaUpdate, 5),
+            (Event::DmaUpdate, 15),
+            (Event::Gpu, 15),
+            (Event::VBlank, 15),
+        ]);
+
+        assert_eq!(
+            order,
+            [
+                (5, Event::DmaUpdate),
+                (10, Event::Gpu),
+                (15, Event::Gpu),
+                (15, Event::VBlank),
+                (15, Event::DmaUpdate),
+            ]
+        );
+    }
+
+    #[test]
+    fn ordering_is_stable_across_scheduling_orders() {
+        let events = [
+            (Event::DmaAdvance, 0),
+            (Event::DmaUpdate, 0),
+            (Event::Gpu, 0),
+            (Event::Present, 0),
+            (Event::VBlank, 0),
+        ];
+
+        for rotation in 0..events.len() {
+            let mut rotated = events;
+            rotated.rotate_left(rotation);
+
+            // events with the same priority keep the order they were scheduled in, so only the
+            // priorities are independent of it
+            let order = fire(&rotated);
+            let priorities: Vec<_> = order.iter().map(|(_, event)| event.priority()).collect();
+            assert_eq!(priorities, [0, 1, 1, 2, 2], "{rotated:?}");
+            assert_eq!(order, fire(&rotated));
+        }
+    }
+}
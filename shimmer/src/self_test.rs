@@ -0,0 +1,658 @@
+//! A battery of fast internal checks which validate the environment the emulator runs in and
+//! explain what's wrong when it isn't right, boot fingerprints for catching BIOS boot regressions
+//! and a harness for running reference test executables.
+
+use crate::{
+    Condition, Config, Emulator, EmulatorError, SHELL_ENTRY, Settings, StopReason,
+    gpu::interface::{Renderer, contract::Harness},
+    scheduler::{Event, Scheduler},
+};
+use shimmer_core::{
+    cpu::Reg,
+    exe::Executable,
+    gte::{self, Gte},
+    mem::Address,
+};
+use std::fmt::Write;
+use strum::VariantArray;
+
+/// Number of instructions executed by the interpreter smoke run.
+const SMOKE_RUN_INSTRUCTIONS: u64 = 10_000;
+
+/// Where the program of the interpreter smoke run is loaded.
+const SMOKE_PROGRAM_ADDR: Address = Address(0x8001_0000);
+
+/// The program of the interpreter smoke run. It runs a xorshift generator in a loop and mixes its
+/// output into most temporaries through arithmetic, logic and shift instructions, a store and a
+/// load, and a branch which is taken about half of the time.
+const SMOKE_PROGRAM: [u32; 25] = [
+    0x3C08_1234, // lui $t0, 0x1234
+    0x3508_5678, // ori $t0, $t0, 0x5678
+    0x3C14_8002, // lui $s4, 0x8002
+    // loop:
+    0x2529_0001, // addiu $t1, $t1, 1
+    0x0008_5340, // sll $t2, $t0, 13
+    0x010A_4026, // xor $t0, $t0, $t2
+    0x0008_5442, // srl $t2, $t0, 17
+    0x010A_4026, // xor $t0, $t0, $t2
+    0x0008_5140, // sll $t2, $t0, 5
+    0x010A_4026, // xor $t0, $t0, $t2
+    0x0168_5821, // addu $t3, $t3, $t0
+    0x0189_6023, // subu $t4, $t4, $t1
+    0x010B_682A, // slt $t5, $t0, $t3
+    0x01CD_7021, // addu $t6, $t6, $t5
+    0x010B_7824, // and $t7, $t0, $t3
+    0x000B_80C3, // sra $s0, $t3, 3
+    0xAE88_0000, // sw $t0, 0($s4)
+    0x8E95_0000, // lw $s5, 0($s4)
+    0x012B_B025, // or $s6, $t1, $t3
+    0x0501_0002, // bgez $t0, skip
+    0x2652_0001, // addiu $s2, $s2, 1
+    0x2673_0001, // addiu $s3, $s3, 1
+    // skip:
+    0x02F5_B826, // xor $s7, $s7, $s5
+    0x0800_4003, // j loop
+    0x2631_0003, // addiu $s1, $s1, 3
+];
+
+/// The [`register_checksum`] at the end of the interpreter smoke run, as recorded with a known good
+/// build.
+const SMOKE_RUN_CHECKSUM: u64 = 0x754A_4175_DBCE_FE24;
+
+/// Length of a BIOS image. The BIOS region is larger, but the image only fills its start.
+const BIOS_LEN: usize = 512 * 1024;
+
+/// The first instruction of every retail BIOS: `lui $t0, 0x0013`, the start of the setup of the
+/// memory control registers.
+const BIOS_FIRST_INSTRUCTION: u32 = 0x3C08_0013;
+
+/// MD5 hashes of known good BIOS dumps, along with the models and revision they come from.
+const KNOWN_BIOSES: &[(&str, &str)] = &[
+    ("239665b1a3dade1b5a52c06338011044", "SCPH-1000 (v1.0 J)"),
+    ("849515939161e62f6b866f6853006780", "SCPH-3000 (v1.1 J)"),
+    ("dc2b9bf8da62ec93e868cfd29f0d067d", "SCPH-1001 (v2.0 A)"),
+    ("924e392ed05558ffdb115408c263dccf", "SCPH-1001 (v2.2 A)"),
+    ("e2110b8a2b97a8e0b857a45d32f7e187", "SCPH-1002 (v2.2 E)"),
+    ("8dd7d5296a650fac7319bce665a6a53c", "SCPH-5500 (v3.0 J)"),
+    ("490f666e1afb15b7362b406ed1cea246", "SCPH-5501 (v3.0 A)"),
+    ("32736f17079d0b2b7024407c39bd3050", "SCPH-5502 (v3.0 E)"),
+    ("8e4c14f567745eff2f0408c8129f72a6", "SCPH-7000 (v4.0 J)"),
+    ("1e68c231d0896b7eadcad1d7d8e76129", "SCPH-7001 (v4.1 A)"),
+    ("b9d9a0286c33dc6b7237bb13cd46fdee", "SCPH-7502 (v4.1 E)"),
+    ("6e3735ff4c7dc899ee98981385f6f3d0", "SCPH-101 (v4.5 A)"),
+];
+
+/// Amount of RAM hashed by [`Emulator::boot_fingerprint`]. It holds the kernel code and data.
+const FINGERPRINT_RAM_LEN: usize = 64 * 1024;
+
+/// The outcome of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed {
+        /// What went wrong.
+        reason: String,
+        /// How to fix it.
+        hint: &'static str,
+    },
+    Skipped {
+        reason: &'static str,
+    },
+}
+
+/// The result of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: Outcome,
+}
+
+/// The results of [`Emulator::self_test`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    /// Whether none of the checks failed.
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| !matches!(check.outcome, Outcome::Failed { .. }))
+    }
+}
+
+impl std::fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            match &check.outcome {
+                Outcome::Passed => writeln!(f, "[pass] {}", check.name)?,
+                Outcome::Failed { reason, hint } => {
+                    writeln!(f, "[FAIL] {}: {reason}", check.name)?;
+                    writeln!(f, "       hint: {hint}")?;
+                }
+                Outcome::Skipped { reason } => writeln!(f, "[skip] {}: {reason}", check.name)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn failed(reason: impl Into<String>, hint: &'static str) -> Outcome {
+    Outcome::Failed {
+        reason: reason.into(),
+        hint,
+    }
+}
+
+fn check_bios(bios: &[u8]) -> Outcome {
+    const HINT: &str = "use an unmodified BIOS image dumped from a PSX, such as SCPH-1001";
+
+    if bios.len() != BIOS_LEN {
+        return failed(
+            format!(
+                "image is {} bytes long, but PSX BIOS images are {BIOS_LEN} bytes long",
+                bios.len(),
+            ),
+            HINT,
+        );
+    }
+
+    let first = u32::from_le_bytes(bios[..4].try_into().unwrap());
+    if first != BIOS_FIRST_INSTRUCTION {
+        return failed(
+            format!("image starts with {first:#010X}, which no known BIOS does"),
+            HINT,
+        );
+    }
+
+    let hash: String = md5(bios).iter().map(|byte| format!("{byte:02x}")).collect();
+    if !KNOWN_BIOSES.iter().any(|&(known, _)| known == hash) {
+        return failed(
+            format!("image has MD5 {hash}, which matches no known BIOS revision"),
+            "the image is likely a bad dump or has been patched: dump it again, or use an \
+             unmodified BIOS such as SCPH-1001, SCPH-5501 or SCPH-7001",
+        );
+    }
+
+    Outcome::Passed
+}
+
+/// Hashes the given bytes (MD5), the hash BIOS dumps are usually identified by.
+fn md5(bytes: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+    #[rustfmt::skip]
+    const K: [u32; 64] = [
+        0xD76A_A478, 0xE8C7_B756, 0x2420_70DB, 0xC1BD_CEEE,
+        0xF57C_0FAF, 0x4787_C62A, 0xA830_4613, 0xFD46_9501,
+        0x6980_98D8, 0x8B44_F7AF, 0xFFFF_5BB1, 0x895C_D7BE,
+        0x6B90_1122, 0xFD98_7193, 0xA679_438E, 0x49B4_0821,
+        0xF61E_2562, 0xC040_B340, 0x265E_5A51, 0xE9B6_C7AA,
+        0xD62F_105D, 0x0244_1453, 0xD8A1_E681, 0xE7D3_FBC8,
+        0x21E1_CDE6, 0xC337_07D6, 0xF4D5_0D87, 0x455A_14ED,
+        0xA9E3_E905, 0xFCEF_A3F8, 0x676F_02D9, 0x8D2A_4C8A,
+        0xFFFA_3942, 0x8771_F681, 0x6D9D_6122, 0xFDE5_380C,
+        0xA4BE_EA44, 0x4BDE_CFA9, 0xF6BB_4B60, 0xBEBF_BC70,
+        0x289B_7EC6, 0xEAA1_27FA, 0xD4EF_3085, 0x0488_1D05,
+        0xD9D4_D039, 0xE6DB_99E5, 0x1FA2_7CF8, 0xC4AC_5665,
+        0xF429_2244, 0x432A_FF97, 0xAB94_23A7, 0xFC93_A039,
+        0x655B_59C3, 0x8F0C_CC92, 0xFFEF_F47D, 0x8584_5DD1,
+        0x6FA8_7E4F, 0xFE2C_E6E0, 0xA301_4314, 0x4E08_11A1,
+        0xF753_7E82, 0xBD3A_F235, 0x2AD7_D2BB, 0xEB86_D391,
+    ];
+
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((bytes.len() as u64 * 8).to_le_bytes());
+
+    let mut state = [0x6745_2301_u32, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+    for block in message.chunks_exact(64) {
+        let words: [u32; 16] = std::array::from_fn(|i| {
+            u32::from_le_bytes(block[4 * i..4 * i + 4].try_into().unwrap())
+        });
+
+        let [mut a, mut b, mut c, mut d] = state;
+        for (i, &k) in K.iter().enumerate() {
+            let (f, word) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(k)
+                .wrapping_add(words[word])
+                .rotate_left(SHIFTS[(i / 16) * 4 + i % 4]);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    state.map(u32::to_le_bytes).concat().try_into().unwrap()
+}
+
+/// Hashes the given bytes (FNV-1a).
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+/// Hashes the CPU registers and the program counter.
+fn register_checksum(emulator: &Emulator) -> u64 {
+    let regs = &emulator.psx.cpu.regs;
+    fnv1a(
+        Reg::VARIANTS
+            .iter()
+            .map(|&reg| regs.read(reg))
+            .chain(std::iter::once(regs.read_pc()))
+            .flat_map(u32::to_le_bytes),
+    )
+}
+
+fn check_interpreter(config: &Config) -> Outcome {
+    const HINT: &str = "this is a bug in the emulator, please report it along with this output";
+
+    // the checksum was recorded without any of the speedups
+    let config = Config {
+        skip_idle_loops: false,
+        skip_post_delays: false,
+        settings: Settings::default(),
+        ..config.clone()
+    };
+
+    let mut emulator = match Emulator::new(config) {
+        Ok(emulator) => emulator,
+        Err(err) => return failed(format!("couldn't create emulator: {err}"), HINT),
+    };
+
+    emulator.load_raw(
+        SMOKE_PROGRAM_ADDR,
+        &SMOKE_PROGRAM.map(u32::to_le_bytes).concat(),
+    );
+    emulator.set_pc(SMOKE_PROGRAM_ADDR);
+    emulator.step_instructions(SMOKE_RUN_INSTRUCTIONS);
+
+    let checksum = register_checksum(&emulator);
+    if checksum != SMOKE_RUN_CHECKSUM {
+        return failed(
+            format!(
+                "{SMOKE_RUN_INSTRUCTIONS} instructions ended with register checksum \
+                 {checksum:#018X} instead of {SMOKE_RUN_CHECKSUM:#018X}"
+            ),
+            HINT,
+        );
+    }
+
+    Outcome::Passed
+}
+
+fn check_gte() -> Outcome {
+    const HINT: &str = "this is a bug in the GTE register file, please report it";
+
+    let mut gte = Gte::default();
+    let mut errors = String::new();
+    let mut expect = |name: &str, got: u32, expected: u32| {
+        if got != expected {
+            _ = write!(
+                errors,
+                "{name} is {got:#010X} instead of {expected:#010X}; "
+            );
+        }
+    };
+
+    // the screen XY FIFO shifts on writes to SXYP
+    for value in [0x0001_0002, 0x0003_0004, 0x0005_0006] {
+        gte.regs.write(gte::Reg::SXYP, value);
+    }
+    expect("SXY0", gte.regs.read(gte::Reg::SXY0), 0x0001_0002);
+    expect("SXY1", gte.regs.read(gte::Reg::SXY1), 0x0003_0004);
+    expect("SXY2", gte.regs.read(gte::Reg::SXY2), 0x0005_0006);
+
+    // VZ0 is sign extended
+    gte.regs.write(gte::Reg::VZ0, 0xFFFF);
+    expect("VZ0", gte.regs.read(gte::Reg::VZ0), 0xFFFF_FFFF);
+
+    // LZCR counts the leading bits equal to the sign bit of LZCS
+    gte.regs.write(gte::Reg::LZCS, 0x0000_FFFF);
+    expect("LZCR", gte.regs.read(gte::Reg::LZCR), 16);
+    gte.regs.write(gte::Reg::LZCS, 0xFFF0_0000);
+    expect("LZCR", gte.regs.read(gte::Reg::LZCR), 12);
+
+    // IRGB expands into IR1-IR3
+    gte.regs.write(gte::Reg::IRGB, 0x7FFF);
+    expect("IR1", gte.regs.read(gte::Reg::IR1), 0xF80);
+    expect("IR2", gte.regs.read(gte::Reg::IR2), 0xF80);
+    expect("IR3", gte.regs.read(gte::Reg::IR3), 0xF80);
+
+    if errors.is_empty() {
+        Outcome::Passed
+    } else {
+        failed(errors.trim_end_matches("; "), HINT)
+    }
+}
+
+fn check_scheduler() -> Outcome {
+    const HINT: &str = "this is a bug in the scheduler, please report it";
+
+    const EVENTS: [Event; 6] = [
+        Event::VBlank,
+        Event::Present,
+        Event::VBlankEnd,
+        Event::Gpu,
+        Event::DmaUpdate,
+        Event::DmaAdvance,
+    ];
+
+    // a pseudo-random schedule, with few distinct times so that most events share their cycle
+    let mut state = 0x2545_F491_u32;
+    let mut schedule = Vec::new();
+    for _ in 0..256 {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        schedule.push((
+            u64::from(state % 16),
+            EVENTS[(state >> 8) as usize % EVENTS.len()],
+        ));
+    }
+
+    let mut scheduler = Scheduler::new();
+    for &(time, event) in &schedule {
+        scheduler.schedule(event, time);
+    }
+
+    let mut order = Vec::new();
+    while let Some(until_next) = scheduler.until_next() {
+        scheduler.advance(until_next);
+        while let Some(event) = scheduler.pop() {
+            order.push((scheduler.elapsed(), event));
+        }
+    }
+
+    // events must fire by time, then by priority, then in the order they were scheduled
+    let mut expected = schedule;
+    expected.sort_by_key(|&(time, event)| (time, event.priority()));
+
+    if let Some(index) = (0..expected.len()).find(|&i| order.get(i) != Some(&expected[i])) {
+        return failed(
+            format!(
+                "event {index} of {} fired as {:?} instead of {:?}",
+                expected.len(),
+                order.get(index),
+                expected[index]
+            ),
+            HINT,
+        );
+    }
+
+    Outcome::Passed
+}
+
+fn check_renderer(renderer: Option<&mut dyn Renderer>) -> Outcome {
+    const HINT: &str = "the renderer is not upholding its contract: update your graphics drivers \
+                        or try another renderer backend";
+
+    let Some(renderer) = renderer else {
+        return Outcome::Skipped {
+            reason: "no renderer attached",
+        };
+    };
+
+    match Harness::default().run(renderer) {
+        Ok(()) => Outcome::Passed,
+        Err(err) => failed(err.to_string(), HINT),
+    }
+}
+
+/// A summary of the state of the system after booting a BIOS. Comparing it against a fingerprint
+/// recorded with a known good build catches regressions which prevent a BIOS from booting, or
+/// change what it does while booting.
+///
+/// Its [`Display`](std::fmt::Display) representation is stable and meant to be stored as a
+/// golden file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootFingerprint {
+    /// Whether the kernel jumped to the shell.
+    pub reached_shell: bool,
+    /// The amount of cycles executed.
+    pub cycles: u64,
+    /// The last boot progress code written to the POST register, if any.
+    pub post_code: Option<u8>,
+    /// Hash of the start of RAM, which holds the kernel.
+    pub ram_hash: u64,
+    /// What the kernel printed to its standard output.
+    pub kernel_stdout: String,
+}
+
+impl std::fmt::Display for BootFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "reached_shell {}", self.reached_shell)?;
+        writeln!(f, "cycles {}", self.cycles)?;
+        match self.post_code {
+            Some(code) => writeln!(f, "post_code {code:#04X}")?,
+            None => writeln!(f, "post_code none")?,
+        }
+        writeln!(f, "ram_hash {:#018X}", self.ram_hash)?;
+        writeln!(f, "kernel_stdout")?;
+        write!(f, "{}", self.kernel_stdout)
+    }
+}
+
+/// The result of running a reference test executable with [`Emulator::run_test_exe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestExeReport {
+    /// Whether the executable printed its done marker before running out of cycles.
+    pub finished: bool,
+    /// The amount of cycles executed.
+    pub cycles: u64,
+    /// The lines of output which report a failure, i.e. which contain "fail" or "error".
+    pub failures: Vec<String>,
+    /// What the executable printed to the kernel standard output.
+    pub stdout: String,
+}
+
+impl TestExeReport {
+    /// Whether the executable finished without reporting any failure.
+    pub fn passed(&self) -> bool {
+        self.finished && self.failures.is_empty()
+    }
+}
+
+impl std::fmt::Display for TestExeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for failure in &self.failures {
+            writeln!(f, "[FAIL] {failure}")?;
+        }
+
+        if !self.finished {
+            writeln!(f, "[FAIL] didn't finish after {} cycles", self.cycles)?;
+        }
+
+        if self.passed() {
+            writeln!(f, "[pass] finished after {} cycles", self.cycles)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Emulator {
+    /// Boots the BIOS in `config` without a renderer, sideloads the test executable `exe` (e.g.
+    /// one of amidog's or nocash's CPU and GTE tests) and runs it until it prints `done_marker`
+    /// to the kernel standard output or `max_cycles` have been executed.
+    ///
+    /// The executable passes if it finishes and none of the lines it printed report a failure.
+    pub fn run_test_exe(
+        config: &Config,
+        exe: Executable,
+        done_marker: &str,
+        max_cycles: u64,
+    ) -> Result<TestExeReport, EmulatorError> {
+        let mut emulator = Emulator::new(config.clone())?;
+        emulator.psx.memory.sideload = Some(exe);
+
+        let result = emulator.run_until(
+            max_cycles,
+            &[Condition::KernelStdoutContains(done_marker)],
+            |_| false,
+        );
+
+        let stdout = emulator.psx.memory.kernel_stdout.clone();
+        let failures = stdout
+            .lines()
+            .filter(|line| {
+                let line = line.to_ascii_lowercase();
+                line.contains("fail") || line.contains("error")
+            })
+            .map(str::to_owned)
+            .collect();
+
+        Ok(TestExeReport {
+            finished: result.reason == StopReason::Condition(0),
+            cycles: result.cycles,
+            failures,
+            stdout,
+        })
+    }
+
+    /// Boots the BIOS in `config` without a renderer until the kernel jumps to the shell or
+    /// `max_cycles` have been executed, and returns a fingerprint of the resulting state.
+    ///
+    /// Stopping at the shell entry point makes the fingerprint independent of how long the shell
+    /// has been running, so it only changes if booting itself does.
+    pub fn boot_fingerprint(
+        config: &Config,
+        max_cycles: u64,
+    ) -> Result<BootFingerprint, EmulatorError> {
+        let mut emulator = Emulator::new(config.clone())?;
+        let result = emulator.run_until(max_cycles, &[Condition::PcEquals(SHELL_ENTRY)], |_| false);
+
+        let psx = &emulator.psx;
+        Ok(BootFingerprint {
+            reached_shell: result.reason == StopReason::Condition(0),
+            cycles: result.cycles,
+            post_code: psx.memory.post,
+            ram_hash: fnv1a(psx.memory.ram[..FINGERPRINT_RAM_LEN].iter().copied()),
+            kernel_stdout: psx.memory.kernel_stdout.clone(),
+        })
+    }
+
+    /// Runs a battery of fast internal checks against the given configuration and, if given, a
+    /// renderer, without needing a frontend. The renderer's VRAM is overwritten by the checks.
+    ///
+    /// Only the environment the emulator runs in is checked, so that this stays fast. The
+    /// behaviour of the emulator itself is covered by the tests of this crate.
+    ///
+    /// The checks are:
+    /// - the BIOS image is recognized, by its hash, as a known good dump of a PSX BIOS
+    /// - the interpreter runs a few thousand instructions of a fixed program into the state a known
+    ///   good build does
+    /// - the GTE register file behaves as the hardware does
+    /// - the scheduler fires events by time, then by priority, then in the order they were
+    ///   scheduled
+    /// - the renderer upholds its contract, including VRAM readbacks
+    pub fn self_test(config: &Config, renderer: Option<&mut dyn Renderer>) -> SelfTestReport {
+        let checks = vec![
+            CheckResult {
+                name: "BIOS recognition",
+                outcome: check_bios(&config.bios),
+            },
+            CheckResult {
+                name: "interpreter smoke run",
+                outcome: check_interpreter(config),
+            },
+            CheckResult {
+                name: "GTE register file",
+                outcome: check_gte(),
+            },
+            CheckResult {
+                name: "scheduler ordering",
+                outcome: check_scheduler(),
+            },
+            CheckResult {
+                name: "renderer round-trip",
+                outcome: check_renderer(renderer),
+            },
+        ];
+
+        SelfTestReport { checks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gpu::interface::soft_vram::SoftVram, testing};
+
+    #[test]
+    fn self_test_only_checks_the_environment() {
+        let report = Emulator::self_test(&testing::config(), None);
+        let names: Vec<_> = report.checks.iter().map(|check| check.name).collect();
+        assert_eq!(
+            names,
+            [
+                "BIOS recognition",
+                "interpreter smoke run",
+                "GTE register file",
+                "scheduler ordering",
+                "renderer round-trip",
+            ]
+        );
+
+        let outcomes: Vec<_> = report.checks.iter().map(|check| &check.outcome).collect();
+        assert!(matches!(outcomes[0], Outcome::Failed { .. }), "empty BIOS");
+        assert_eq!(outcomes[1..4], [&Outcome::Passed; 3]);
+        assert!(matches!(outcomes[4], Outcome::Skipped { .. }));
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn md5_matches_reference_digests() {
+        let hex = |bytes: &[u8]| -> String {
+            md5(bytes)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect()
+        };
+
+        assert_eq!(hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(hex(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        // padding spills into a second block
+        assert_eq!(hex(&[7; 56]), "c83cb48841cc2ac9a23c308508c67e1b");
+        assert_eq!(hex(&[9; 1000]), "9ac9697038bf5c8d9fb83767fa45d1a6");
+    }
+
+    #[test]
+    fn unknown_bios_images_are_not_recognized() {
+        let mut bios = vec![0; BIOS_LEN];
+        bios[..4].copy_from_slice(&BIOS_FIRST_INSTRUCTION.to_le_bytes());
+
+        let Outcome::Failed { reason, .. } = check_bios(&bios) else {
+            panic!("an image which only looks like a BIOS was recognized");
+        };
+        assert!(
+            reason.contains("matches no known BIOS revision"),
+            "{reason}"
+        );
+    }
+
+    #[test]
+    fn self_test_runs_the_renderer_contract() {
+        let mut renderer = SoftVram::new();
+        let report = Emulator::self_test(&testing::config(), Some(&mut renderer));
+        let renderer = report.checks.last().unwrap();
+        assert_eq!(renderer.name, "renderer round-trip");
+        assert_eq!(renderer.outcome, Outcome::Passed);
+    }
+}
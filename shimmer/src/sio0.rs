@@ -1,10 +1,15 @@
+pub mod memcard;
+mod timing;
+
 use crate::{PSX, scheduler};
+use memcard::{MemoryCard, MemoryCardError};
 use shimmer_core::{
-    CYCLES_MICROS, Cycles,
     interrupts::Interrupt,
     sio0::{AnalogInput, DigitalInput},
 };
-use tinylog::{debug, trace};
+use tinylog::{debug, error, trace};
+
+pub use timing::Timing;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Event {
@@ -32,6 +37,19 @@ enum State {
     },
 }
 
+/// Amount of memory card slots.
+pub const MEMORY_CARD_SLOTS: usize = 2;
+
+/// The button combination games and the kernel conventionally use for soft resets: L1, R1,
+/// Start and Select held together.
+pub fn soft_reset_combo() -> DigitalInput {
+    DigitalInput::default()
+        .with_l1(true)
+        .with_r1(true)
+        .with_start(true)
+        .with_select(true)
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Joypad {
     pub digital_input: DigitalInput,
@@ -39,7 +57,7 @@ pub struct Joypad {
     pub analog_right: AnalogInput,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Default)]
 pub struct Sio0 {
     state: State,
     in_progress: bool,
@@ -47,13 +65,20 @@ pub struct Sio0 {
     joypad: Joypad,
     analog_mode: bool,
     config_mode: bool,
-}
 
-const TRANSFER_DELAY: Cycles = 46 * CYCLES_MICROS;
-const START_ACK_DELAY: Cycles = 3 * CYCLES_MICROS;
-const END_ACK_DELAY: Cycles = 2 * CYCLES_MICROS;
+    memory_cards: [Option<MemoryCard>; MEMORY_CARD_SLOTS],
+
+    delays: timing::Delays,
+}
 
 impl Sio0 {
+    pub fn new(timing: Timing) -> Self {
+        Self {
+            delays: timing::Delays::new(timing),
+            ..Default::default()
+        }
+    }
+
     fn update_status(&mut self, psx: &mut PSX) {
         psx.sio0.status.set_tx_ready(psx.sio0.tx.is_none());
         psx.sio0.status.set_rx_ready(psx.sio0.rx.is_some());
@@ -83,15 +108,17 @@ impl Sio0 {
                 // check if a transfer should start
                 if self.can_transfer(psx) {
                     self.in_progress = true;
-                    psx.scheduler
-                        .schedule(scheduler::Event::Sio(Event::Transfer), TRANSFER_DELAY);
+                    psx.scheduler.schedule(
+                        scheduler::Event::Sio(Event::Transfer),
+                        self.delays.transfer(),
+                    );
                 }
             }
             (_, Event::StartAck) => {
                 trace!(psx.loggers.sio, "start ack");
                 psx.sio0.status.set_device_ready_to_receive(true);
                 psx.scheduler
-                    .schedule(scheduler::Event::Sio(Event::EndAck), END_ACK_DELAY);
+                    .schedule(scheduler::Event::Sio(Event::EndAck), self.delays.end_ack());
 
                 if psx.sio0.control.device_ready_to_receive_interrupt_enable() {
                     psx.sio0.status.set_interrupt_request(true);
@@ -111,8 +138,10 @@ impl Sio0 {
                 let address = psx.sio0.tx.take().unwrap();
                 match address {
                     0x01 if !psx.sio0.control.port_select() => {
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                         self.state = State::JoypadStart;
                     }
                     _ => {}
@@ -137,8 +166,10 @@ impl Sio0 {
                     _ => todo!("unknown command: {command}"),
                 };
 
-                psx.scheduler
-                    .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                psx.scheduler.schedule(
+                    scheduler::Event::Sio(Event::StartAck),
+                    self.delays.start_ack(),
+                );
                 self.state = State::JoypadTransfer { command, stage: 0 };
             }
             (
@@ -166,22 +197,28 @@ impl Sio0 {
                         }
 
                         psx.sio0.rx = Some(0x5A);
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     1 => {
                         debug!(psx.loggers.sio, "sending switches low");
                         psx.sio0.rx = Some(!self.joypad.digital_input.to_bits().to_le_bytes()[0]);
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     2 => {
                         debug!(psx.loggers.sio, "sending switches high");
                         psx.sio0.rx = Some(!self.joypad.digital_input.to_bits().to_le_bytes()[1]);
 
                         if self.analog_mode || self.config_mode {
-                            psx.scheduler
-                                .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                            psx.scheduler.schedule(
+                                scheduler::Event::Sio(Event::StartAck),
+                                self.delays.start_ack(),
+                            );
                         } else {
                             self.state = State::Idle;
                             break 'block;
@@ -192,24 +229,30 @@ impl Sio0 {
                         assert_eq!(data, 0x00);
 
                         psx.sio0.rx = Some(!self.joypad.analog_right.analog_x());
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     4 => {
                         debug!(psx.loggers.sio, "sending right analog y");
                         assert_eq!(data, 0x00);
 
                         psx.sio0.rx = Some(!self.joypad.analog_right.analog_y());
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     5 => {
                         debug!(psx.loggers.sio, "sending left analog x");
                         assert_eq!(data, 0x00);
 
                         psx.sio0.rx = Some(!self.joypad.analog_left.analog_x());
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     6 => {
                         debug!(psx.loggers.sio, "sending left analog y");
@@ -240,23 +283,29 @@ impl Sio0 {
                         assert_eq!(data, 0);
 
                         psx.sio0.rx = Some(0x5A);
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     1 => {
                         debug!(psx.loggers.sio, "sending empty 0 (led)");
                         self.analog_mode = data == 1;
 
                         psx.sio0.rx = Some(0x00);
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     2 => {
                         debug!(psx.loggers.sio, "sending empty 1 (key)");
                         psx.sio0.rx = Some(0x00);
 
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     3 | 4 | 5 | 6 => {
                         debug!(psx.loggers.sio, "sending empty {}", *stage - 1);
@@ -268,8 +317,10 @@ impl Sio0 {
                             self.state = State::Idle;
                             break 'block;
                         } else {
-                            psx.scheduler
-                                .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                            psx.scheduler.schedule(
+                                scheduler::Event::Sio(Event::StartAck),
+                                self.delays.start_ack(),
+                            );
                         }
                     }
                     _ => unreachable!(),
@@ -293,29 +344,37 @@ impl Sio0 {
                         assert_eq!(data, 0);
 
                         psx.sio0.rx = Some(0x5A);
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     1 => {
                         debug!(psx.loggers.sio, "sending type");
 
                         psx.sio0.rx = Some(0x01);
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     2 => {
                         debug!(psx.loggers.sio, "sending padding");
 
                         psx.sio0.rx = Some(0x02);
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     3 => {
                         debug!(psx.loggers.sio, "sending led");
 
                         psx.sio0.rx = Some(self.analog_mode as u8);
-                        psx.scheduler
-                            .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                        psx.scheduler.schedule(
+                            scheduler::Event::Sio(Event::StartAck),
+                            self.delays.start_ack(),
+                        );
                     }
                     4 | 5 | 6 => {
                         debug!(psx.loggers.sio, "sending empty {}", *stage - 4);
@@ -326,8 +385,10 @@ impl Sio0 {
                             self.state = State::Idle;
                             break 'block;
                         } else {
-                            psx.scheduler
-                                .schedule(scheduler::Event::Sio(Event::StartAck), START_ACK_DELAY);
+                            psx.scheduler.schedule(
+                                scheduler::Event::Sio(Event::StartAck),
+                                self.delays.start_ack(),
+                            );
                         }
                     }
                     _ => unreachable!(),
@@ -340,7 +401,145 @@ impl Sio0 {
         self.update_status(psx);
     }
 
+    pub fn joypad(&self) -> &Joypad {
+        &self.joypad
+    }
+
     pub fn joypad_mut(&mut self) -> &mut Joypad {
         &mut self.joypad
     }
+
+    /// Returns memory card `slot`, which must be 0 or 1.
+    fn slot_mut(&mut self, slot: usize) -> Result<&mut Option<MemoryCard>, MemoryCardError> {
+        self.memory_cards
+            .get_mut(slot)
+            .ok_or(MemoryCardError::NoSuchSlot { slot })
+    }
+
+    /// Inserts `card` into memory card `slot` (0 or 1), returning the card previously in it.
+    pub fn insert_memory_card(
+        &mut self,
+        slot: usize,
+        card: Option<MemoryCard>,
+    ) -> Result<Option<MemoryCard>, MemoryCardError> {
+        Ok(std::mem::replace(self.slot_mut(slot)?, card))
+    }
+
+    /// Returns the card in memory card `slot` (0 or 1), if any.
+    pub fn memory_card_mut(
+        &mut self,
+        slot: usize,
+    ) -> Result<Option<&mut MemoryCard>, MemoryCardError> {
+        Ok(self.slot_mut(slot)?.as_mut())
+    }
+
+    /// Flushes dirty memory cards to their backing files, if enough time has passed since their
+    /// last flush.
+    pub fn flush_memory_cards(&mut self, psx: &mut PSX) {
+        for (slot, card) in self.memory_cards.iter_mut().enumerate() {
+            let Some(card) = card else {
+                continue;
+            };
+
+            if let Err(err) = card.auto_flush() {
+                error!(psx.loggers.sio, "failed to flush memory card: {err}"; slot = slot);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use shimmer_core::Cycles;
+
+    /// The bytes sent to read a digital pad: address, read command and three bytes of padding.
+    const PAD_READ: [u8; 5] = [0x01, 0x42, 0x00, 0x00, 0x00];
+
+    /// Reads a digital pad with the given timing, sending each byte once the previous one is done.
+    /// Returns the timeline of SIO0 events and the received bytes.
+    fn read_pad(timing: Timing) -> (Vec<(Cycles, Event)>, Vec<u8>) {
+        let mut psx = testing::psx();
+        psx.scheduler.cancel_if(|_| true);
+        psx.sio0.control.set_selected(true);
+        psx.sio0.control.set_tx_enable(true);
+
+        let mut sio = Sio0::new(timing);
+        let mut timeline = Vec::new();
+        let mut received = Vec::new();
+        for byte in PAD_READ {
+            psx.sio0.tx = Some(byte);
+            sio.update(&mut psx, Event::Update);
+
+            while let Some(after) = psx.scheduler.until_next() {
+                psx.scheduler.advance(after);
+                while let Some(event) = psx.scheduler.pop() {
+                    let scheduler::Event::Sio(event) = event else {
+                        continue;
+                    };
+
+                    timeline.push((psx.scheduler.elapsed(), event));
+                    sio.update(&mut psx, event);
+                }
+            }
+
+            received.extend(psx.sio0.rx.take());
+        }
+
+        (timeline, received)
+    }
+
+    /// Returns the delays between each transfer and the acknowledge following it.
+    fn ack_delays(timeline: &[(Cycles, Event)]) -> Vec<Cycles> {
+        timeline
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [(transfer, Event::Transfer), (ack, Event::StartAck)] => Some(ack - transfer),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn jittery(seed: u64) -> Timing {
+        Timing {
+            jitter: 1000,
+            seed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_timing_has_no_jitter() {
+        let timing = Timing::default();
+        let (timeline, received) = read_pad(timing);
+
+        assert_eq!(received, [0xFF, 0x41, 0x5A, 0xFF, 0xFF]);
+        assert_eq!(ack_delays(&timeline), [timing.start_ack; 4]);
+    }
+
+    #[test]
+    fn same_inputs_produce_identical_timelines() {
+        let (first, received) = read_pad(jittery(42));
+        let (second, _) = read_pad(jittery(42));
+        assert_eq!(first, second);
+        assert_eq!(received, [0xFF, 0x41, 0x5A, 0xFF, 0xFF]);
+
+        let timing = jittery(42);
+        let delays = ack_delays(&first);
+        assert_eq!(delays.len(), 4);
+        assert!(
+            delays
+                .iter()
+                .all(|delay| (timing.start_ack..=timing.start_ack + timing.jitter).contains(delay)),
+            "{delays:?}"
+        );
+    }
+
+    #[test]
+    fn seed_changes_the_jitter() {
+        let (first, _) = read_pad(jittery(1));
+        let (second, _) = read_pad(jittery(2));
+        assert_ne!(first, second);
+    }
 }
@@ -0,0 +1,476 @@
+//! Memory cards and the persistence of their images to the host.
+
+mod directory;
+mod format;
+
+use easyerr::{Error, ResultExt};
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Length of a frame, the unit in which memory cards are read and written.
+pub const FRAME_LEN: usize = 128;
+/// Number of frames in a memory card.
+pub const FRAME_COUNT: usize = 1024;
+/// Length of a memory card image.
+pub const CARD_LEN: usize = FRAME_LEN * FRAME_COUNT;
+
+pub use directory::{
+    BLOCK_FRAMES, BLOCK_LEN, BlockState, DirectoryEntry, DirectoryIssue, SAVE_BLOCKS, Save,
+    SaveFormat,
+};
+pub use format::ImageFormat;
+
+/// Minimum time between automatic flushes of a memory card to its backing file.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Error)]
+pub enum MemoryCardError {
+    #[error("image has length {len}, but memory card images are 128 KiB long")]
+    InvalidLength { len: usize },
+    #[error("couldn't read the backing file")]
+    Read { source: std::io::Error },
+    #[error("couldn't back up the backing file")]
+    Backup { source: std::io::Error },
+    #[error("couldn't write the backing file")]
+    Write { source: std::io::Error },
+    #[error("couldn't recognize the format of an image with length {len}")]
+    UnknownFormat { len: usize },
+    #[error("exporting {format:?} images is not supported")]
+    UnsupportedExport { format: ImageFormat },
+    #[error("memory card is not formatted")]
+    Unformatted,
+    #[error("save file has length {len}, which isn't a whole number of blocks")]
+    InvalidSave { len: usize },
+    #[error("there's no memory card slot {slot}, only slots 0 and 1")]
+    NoSuchSlot { slot: usize },
+    #[error("there's no save starting at block {block}")]
+    NoSuchSave { block: usize },
+    #[error("block chain of save at block {first} is broken at block {block}")]
+    BrokenSave { first: usize, block: usize },
+    #[error("a save named {name} already exists")]
+    SaveExists { name: String },
+    #[error("save needs {needed} blocks, but only {free} are free")]
+    CardFull { needed: usize, free: usize },
+}
+
+/// Appends `suffix` to the file name of `path`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// A memory card, optionally backed by a file on the host.
+///
+/// Writes are not persisted immediately: written frames are marked as dirty and flushed to the
+/// backing file at most once every [`FLUSH_INTERVAL`] by [`MemoryCard::auto_flush`], or whenever
+/// [`MemoryCard::flush_now`] is called or the card is dropped. Flushes write to a temporary file
+/// which then replaces the backing file through a rename, so that a crash never leaves it torn.
+/// The first flush of a session also keeps the previous contents of the backing file in a `.bak`
+/// file next to it.
+pub struct MemoryCard {
+    data: Box<[u8]>,
+    dirty: Box<[bool]>,
+    path: Option<PathBuf>,
+    backed_up: bool,
+    last_flush: Instant,
+}
+
+impl std::fmt::Debug for MemoryCard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryCard")
+            .field("path", &self.path)
+            .field("dirty", &self.dirty())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for MemoryCard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryCard {
+    fn with_data(data: Vec<u8>, path: Option<PathBuf>) -> Self {
+        Self {
+            data: data.into_boxed_slice(),
+            dirty: vec![false; FRAME_COUNT].into_boxed_slice(),
+            path,
+            backed_up: false,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Creates a new, empty memory card with no backing file.
+    pub fn new() -> Self {
+        Self::with_data(vec![0; CARD_LEN], None)
+    }
+
+    /// Opens the memory card backed by the file at `path`. If the file does not exist, the card
+    /// starts out empty and the file is created on the first flush.
+    pub fn open(path: PathBuf) -> Result<Self, MemoryCardError> {
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => vec![0; CARD_LEN],
+            Err(source) => return Err(MemoryCardError::Read { source }),
+        };
+
+        if data.len() != CARD_LEN {
+            return Err(MemoryCardError::InvalidLength { len: data.len() });
+        }
+
+        Ok(Self::with_data(data, Some(path)))
+    }
+
+    /// Imports the memory card image at `path`, detecting its container format. The card has no
+    /// backing file: use [`MemoryCard::export`] to write it back, in any format.
+    pub fn import(path: &Path) -> Result<Self, MemoryCardError> {
+        let data = fs::read(path).context(MemoryCardCtx::Read)?;
+        let format =
+            ImageFormat::detect(&data).ok_or(MemoryCardError::UnknownFormat { len: data.len() })?;
+
+        let card = format.unwrap(&data)?.to_vec();
+        Ok(Self::with_data(card, None))
+    }
+
+    /// Exports the image of this card to `path` in the given container format.
+    pub fn export(&self, path: &Path, format: ImageFormat) -> Result<(), MemoryCardError> {
+        let data = format.wrap(&self.data)?;
+        fs::write(path, data).context(MemoryCardCtx::Write)
+    }
+
+    /// Imports the single save file at `path` into the free blocks of this card, guessing its
+    /// format from its extension. Returns the first block of the save.
+    pub fn import_save(&mut self, path: &Path) -> Result<usize, MemoryCardError> {
+        let save = Save::read(path)?;
+        self.insert_save(&save)
+    }
+
+    /// Exports the save whose first block is `first` to `path` as a single save file.
+    pub fn export_save(
+        &self,
+        first: usize,
+        path: &Path,
+        format: SaveFormat,
+    ) -> Result<(), MemoryCardError> {
+        let save = self.save(first)?;
+        fs::write(path, save.to_bytes(format)).context(MemoryCardCtx::Write)
+    }
+
+    /// The path of the backing file of this card, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The whole image of this card.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the frame at `index`, which must be less than [`FRAME_COUNT`].
+    pub fn frame(&self, index: usize) -> &[u8; FRAME_LEN] {
+        let start = index * FRAME_LEN;
+        self.data[start..start + FRAME_LEN].try_into().unwrap()
+    }
+
+    /// Writes the frame at `index`, which must be less than [`FRAME_COUNT`], and marks it as dirty.
+    pub fn write_frame(&mut self, index: usize, frame: &[u8; FRAME_LEN]) {
+        let start = index * FRAME_LEN;
+        self.data[start..start + FRAME_LEN].copy_from_slice(frame);
+        self.dirty[index] = true;
+    }
+
+    /// Whether this card has writes which haven't been flushed to the backing file yet.
+    pub fn dirty(&self) -> bool {
+        self.dirty.contains(&true)
+    }
+
+    /// The indices of the frames which haven't been flushed to the backing file yet.
+    pub fn dirty_frames(&self) -> impl Iterator<Item = usize> + use<'_> {
+        self.dirty
+            .iter()
+            .enumerate()
+            .filter_map(|(index, dirty)| dirty.then_some(index))
+    }
+
+    /// Flushes this card to its backing file, if it is dirty. Does nothing if there's no backing
+    /// file.
+    pub fn flush_now(&mut self) -> Result<(), MemoryCardError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if !self.dirty() {
+            return Ok(());
+        }
+
+        if !self.backed_up {
+            match fs::copy(path, with_suffix(path, ".bak")) {
+                Ok(_) => (),
+                Err(err) if err.kind() == ErrorKind::NotFound => (),
+                Err(source) => return Err(MemoryCardError::Backup { source }),
+            }
+
+            self.backed_up = true;
+        }
+
+        let temp = with_suffix(path, ".tmp");
+        let mut file = fs::File::create(&temp).context(MemoryCardCtx::Write)?;
+        file.write_all(&self.data).context(MemoryCardCtx::Write)?;
+        file.sync_all().context(MemoryCardCtx::Write)?;
+        fs::rename(&temp, path).context(MemoryCardCtx::Write)?;
+
+        self.dirty.fill(false);
+        self.last_flush = Instant::now();
+
+        Ok(())
+    }
+
+    /// Flushes this card to its backing file if it is dirty and at least [`FLUSH_INTERVAL`] has
+    /// passed since the last flush. Meant to be called periodically, e.g. on every VBlank.
+    pub fn auto_flush(&mut self) -> Result<(), MemoryCardError> {
+        if self.last_flush.elapsed() < FLUSH_INTERVAL {
+            return Ok(());
+        }
+
+        self.flush_now()
+    }
+}
+
+impl Drop for MemoryCard {
+    fn drop(&mut self) {
+        // errors can't be reported from here: frontends should call `flush_now` before exiting if
+        // they want to handle them
+        _ = self.flush_now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Returns a card image whose every byte is `byte`.
+    fn image(byte: u8) -> Vec<u8> {
+        vec![byte; CARD_LEN]
+    }
+
+    /// Returns the path of a backing file holding `contents`, in a fresh directory for `name`.
+    fn backing_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = testing::scratch_dir(name).join("card.mcd");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Simulates the emulator crashing: the card goes away without being dropped, so nothing is
+    /// flushed.
+    #[expect(clippy::mem_forget, reason = "skipping the drop is the point")]
+    fn crash(card: MemoryCard) {
+        std::mem::forget(card);
+    }
+
+    #[test]
+    fn writes_are_dirty_until_flushed() {
+        let path = backing_file("dirty", &image(0));
+        let mut card = MemoryCard::open(path.clone()).unwrap();
+        assert!(!card.dirty());
+
+        card.write_frame(3, &[0xAA; FRAME_LEN]);
+        card.write_frame(700, &[0xBB; FRAME_LEN]);
+        assert!(card.dirty());
+        assert_eq!(card.dirty_frames().collect::<Vec<_>>(), [3, 700]);
+        assert_eq!(fs::read(&path).unwrap(), image(0));
+
+        card.flush_now().unwrap();
+        assert!(!card.dirty());
+        assert_eq!(fs::read(&path).unwrap(), card.data());
+        assert!(!with_suffix(&path, ".tmp").exists());
+    }
+
+    #[test]
+    fn auto_flush_waits_for_the_interval() {
+        let path = backing_file("auto_flush", &image(0));
+        let mut card = MemoryCard::open(path.clone()).unwrap();
+
+        card.write_frame(0, &[1; FRAME_LEN]);
+        card.auto_flush().unwrap();
+        assert!(card.dirty());
+        assert_eq!(fs::read(&path).unwrap(), image(0));
+
+        card.last_flush -= FLUSH_INTERVAL;
+        card.auto_flush().unwrap();
+        assert!(!card.dirty());
+        assert_eq!(fs::read(&path).unwrap(), card.data());
+    }
+
+    #[test]
+    fn dropping_flushes() {
+        let path = backing_file("drop", &image(0));
+        let mut card = MemoryCard::open(path.clone()).unwrap();
+        card.write_frame(10, &[0xCC; FRAME_LEN]);
+        let expected = card.data().to_vec();
+
+        drop(card);
+        assert_eq!(fs::read(&path).unwrap(), expected);
+    }
+
+    #[test]
+    fn first_flush_of_a_session_keeps_a_backup() {
+        let path = backing_file("backup", &image(0x11));
+        let backup = with_suffix(&path, ".bak");
+
+        let mut card = MemoryCard::open(path.clone()).unwrap();
+        card.write_frame(0, &[0x22; FRAME_LEN]);
+        card.flush_now().unwrap();
+        assert_eq!(fs::read(&backup).unwrap(), image(0x11));
+
+        // later flushes of the same session keep the backup as it was
+        card.write_frame(1, &[0x33; FRAME_LEN]);
+        card.flush_now().unwrap();
+        assert_eq!(fs::read(&backup).unwrap(), image(0x11));
+        let flushed = card.data().to_vec();
+        drop(card);
+
+        // while the next session rotates it
+        let mut card = MemoryCard::open(path.clone()).unwrap();
+        card.write_frame(2, &[0x44; FRAME_LEN]);
+        card.flush_now().unwrap();
+        assert_eq!(fs::read(&backup).unwrap(), flushed);
+    }
+
+    #[test]
+    fn crash_before_flush_leaves_the_old_image() {
+        let path = backing_file("crash_before_flush", &image(0x55));
+
+        let mut card = MemoryCard::open(path.clone()).unwrap();
+        for frame in 0..16 {
+            card.write_frame(frame, &[0x66; FRAME_LEN]);
+        }
+        crash(card);
+
+        assert_eq!(fs::read(&path).unwrap(), image(0x55));
+    }
+
+    #[test]
+    fn crash_during_flush_leaves_a_complete_image() {
+        let path = backing_file("crash_during_flush", &image(0x77));
+
+        // a flush which got as far as writing half of the temporary file
+        let mut card = MemoryCard::open(path.clone()).unwrap();
+        card.write_frame(0, &[0x88; FRAME_LEN]);
+        fs::write(with_suffix(&path, ".tmp"), &card.data()[..CARD_LEN / 2]).unwrap();
+        crash(card);
+
+        assert_eq!(fs::read(&path).unwrap(), image(0x77));
+
+        // and the leftover temporary file doesn't get in the way of the next session
+        let mut card = MemoryCard::open(path.clone()).unwrap();
+        assert_eq!(card.data(), image(0x77));
+        card.write_frame(0, &[0x99; FRAME_LEN]);
+        card.flush_now().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), card.data());
+    }
+
+    #[test]
+    fn crash_at_any_point_leaves_a_flushed_image() {
+        let path = backing_file("crash_at_any_point", &image(0));
+
+        // crash after each step of a sequence of scattered writes and periodic flushes, checking
+        // that the backing file always holds the last image flushed, never a mix
+        for crash_at in 0..24 {
+            fs::write(&path, image(0)).unwrap();
+            let mut card = MemoryCard::open(path.clone()).unwrap();
+            let mut flushed = image(0);
+
+            for step in 0..crash_at {
+                card.write_frame((step * 37) % FRAME_COUNT, &[step as u8 + 1; FRAME_LEN]);
+                if step % 5 == 4 {
+                    card.flush_now().unwrap();
+                    flushed = card.data().to_vec();
+                }
+            }
+            crash(card);
+
+            assert_eq!(
+                fs::read(&path).unwrap(),
+                flushed,
+                "crash at step {crash_at}"
+            );
+        }
+    }
+
+    #[test]
+    fn cards_without_backing_file_never_touch_the_host() {
+        let mut card = MemoryCard::new();
+        card.write_frame(0, &[1; FRAME_LEN]);
+        card.flush_now().unwrap();
+        assert!(card.path().is_none());
+    }
+
+    #[test]
+    fn images_are_imported_from_every_format() {
+        let card = fs::read(testing::fixture("memcard/card.mcr")).unwrap();
+
+        for name in ["card.mcr", "card.gme", "card.vmp"] {
+            let path = testing::fixture(&format!("memcard/{name}"));
+            let imported = MemoryCard::import(&path).unwrap();
+            assert_eq!(imported.data(), card, "{name}");
+            assert!(imported.path().is_none());
+            assert!(imported.check_directory().is_empty(), "{name}");
+            assert_eq!(imported.saves().collect::<Vec<_>>(), [1], "{name}");
+        }
+    }
+
+    #[test]
+    fn images_round_trip_through_files_in_every_writable_format() {
+        let dir = testing::scratch_dir("image_round_trip");
+        let card = MemoryCard::import(&testing::fixture("memcard/card.mcr")).unwrap();
+
+        for (name, format) in [
+            ("card.mcr", ImageFormat::Raw),
+            ("card.gme", ImageFormat::DexDrive),
+        ] {
+            let path = dir.join(name);
+            card.export(&path, format).unwrap();
+
+            let imported = MemoryCard::import(&path).unwrap();
+            assert_eq!(imported.data(), card.data(), "{name}");
+        }
+    }
+
+    #[test]
+    fn saves_round_trip_through_files() {
+        let dir = testing::scratch_dir("save_round_trip");
+        let mcs = testing::fixture("memcard/save.mcs");
+        let card = MemoryCard::import(&testing::fixture("memcard/card.mcr")).unwrap();
+        let blocks = &card.data()[BLOCK_LEN..3 * BLOCK_LEN];
+
+        let mut target = MemoryCard::new();
+        target.format();
+        let first = target.import_save(&mcs).unwrap();
+        let imported = target.save(first).unwrap();
+        assert_eq!(imported.name(), "BASLUS-00000GAME");
+        assert_eq!(imported.data(), blocks);
+        assert!(target.check_directory().is_empty());
+
+        // exporting writes the fixture back as it was
+        let path = dir.join("save.mcs");
+        target.export_save(first, &path, SaveFormat::Mcs).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), fs::read(&mcs).unwrap());
+
+        // and raw saves are named after their file
+        let path = dir.join("BASLUS-00000GAME");
+        card.export_save(1, &path, SaveFormat::Raw).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), blocks);
+
+        let mut target = MemoryCard::new();
+        target.format();
+        let first = target.import_save(&path).unwrap();
+        assert_eq!(target.save(first).unwrap(), card.save(1).unwrap());
+    }
+}
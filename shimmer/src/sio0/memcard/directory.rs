@@ -0,0 +1,674 @@
+//! The directory of a memory card, which keeps track of the saves on it and the blocks they use,
+//! and the single save files used to move saves between cards.
+
+use super::{FRAME_LEN, MemoryCard, MemoryCardCtx, MemoryCardError};
+use easyerr::ResultExt;
+use std::path::Path;
+
+/// Number of frames in a block, the unit in which saves are allocated.
+pub const BLOCK_FRAMES: usize = 64;
+/// Length of a block.
+pub const BLOCK_LEN: usize = FRAME_LEN * BLOCK_FRAMES;
+/// Number of blocks available to saves. The first block of a card holds its directory.
+pub const SAVE_BLOCKS: usize = 15;
+
+/// Offset of the next block pointer in a directory frame.
+const NEXT_OFFSET: usize = 0x08;
+/// Offset of the file name in a directory frame.
+const NAME_OFFSET: usize = 0x0A;
+/// Maximum length of a file name.
+const NAME_LEN: usize = 20;
+/// Next block pointer of the last block of a save.
+const NO_NEXT: u16 = 0xFFFF;
+
+/// Number of frames after the directory frames holding the broken frame list.
+const BROKEN_FRAMES: usize = 20;
+
+/// The state of a block, as recorded by its directory frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockState {
+    Free,
+    /// First block of a save.
+    First,
+    /// Middle block of a save.
+    Middle,
+    /// Last block of a save with more than one block.
+    Last,
+    /// A block of a deleted save, which is free to be reused.
+    Deleted(u8),
+    Unknown(u32),
+}
+
+impl BlockState {
+    fn from_bits(value: u32) -> Self {
+        match value {
+            0xA0 => Self::Free,
+            0x51 => Self::First,
+            0x52 => Self::Middle,
+            0x53 => Self::Last,
+            0xA1..=0xA3 => Self::Deleted(value as u8),
+            _ => Self::Unknown(value),
+        }
+    }
+
+    fn to_bits(self) -> u32 {
+        match self {
+            Self::Free => 0xA0,
+            Self::First => 0x51,
+            Self::Middle => 0x52,
+            Self::Last => 0x53,
+            Self::Deleted(value) => value as u32,
+            Self::Unknown(value) => value,
+        }
+    }
+
+    /// Whether a new save can be written to a block in this state.
+    pub fn is_free(self) -> bool {
+        matches!(self, Self::Free | Self::Deleted(_))
+    }
+}
+
+/// A directory frame, which describes a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub state: BlockState,
+    /// Size of the save, in bytes. Only set in the first block of a save.
+    pub size: u32,
+    /// The next block of the save, if any.
+    pub next: Option<usize>,
+    /// File name of the save, e.g. `BASLUS-00000GAME`. Only set in the first block of a save.
+    pub name: String,
+}
+
+/// Computes the checksum of a directory frame, which is stored in its last byte.
+fn checksum(frame: &[u8; FRAME_LEN]) -> u8 {
+    frame[..FRAME_LEN - 1]
+        .iter()
+        .fold(0, |acc, byte| acc ^ byte)
+}
+
+/// Builds a directory frame, computing its checksum.
+fn directory_frame(
+    state: BlockState,
+    size: u32,
+    next: Option<usize>,
+    name: &[u8],
+) -> [u8; FRAME_LEN] {
+    let mut frame = [0; FRAME_LEN];
+    frame[0x00..0x04].copy_from_slice(&state.to_bits().to_le_bytes());
+    frame[0x04..0x08].copy_from_slice(&size.to_le_bytes());
+
+    let next = next.map_or(NO_NEXT, |block| (block - 1) as u16);
+    frame[NEXT_OFFSET..NEXT_OFFSET + 2].copy_from_slice(&next.to_le_bytes());
+
+    let len = name.len().min(NAME_LEN);
+    frame[NAME_OFFSET..NAME_OFFSET + len].copy_from_slice(&name[..len]);
+
+    frame[FRAME_LEN - 1] = checksum(&frame);
+    frame
+}
+
+/// Parses the file name in a directory frame, which is zero terminated ASCII.
+fn name_bytes(frame: &[u8; FRAME_LEN]) -> &[u8] {
+    let name = &frame[NAME_OFFSET..NAME_OFFSET + NAME_LEN];
+    let len = name.iter().position(|&byte| byte == 0).unwrap_or(NAME_LEN);
+    &name[..len]
+}
+
+/// A problem found in the directory of a memory card by [`MemoryCard::check_directory`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectoryIssue {
+    /// The card has no header frame, so the BIOS considers it unformatted.
+    Unformatted,
+    /// The checksum of the directory frame of a block is wrong.
+    BadChecksum { block: usize },
+    /// The chain of blocks of the save starting at `first` is broken at `block`: it points out of
+    /// the card, to a block which doesn't continue a save or back to an earlier block.
+    BrokenChain { first: usize, block: usize },
+    /// The size of the save starting at `first` doesn't match the length of its chain of blocks.
+    SizeMismatch {
+        first: usize,
+        size: u32,
+        blocks: usize,
+    },
+    /// A block is marked as continuing a save, but no save reaches it.
+    Orphan { block: usize },
+}
+
+impl std::fmt::Display for DirectoryIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unformatted => write!(f, "card is not formatted"),
+            Self::BadChecksum { block } => {
+                write!(f, "directory frame of block {block} has a bad checksum")
+            }
+            Self::BrokenChain { first, block } => {
+                write!(
+                    f,
+                    "block chain of save at block {first} is broken at block {block}"
+                )
+            }
+            Self::SizeMismatch {
+                first,
+                size,
+                blocks,
+            } => write!(
+                f,
+                "save at block {first} has size {size}, but spans {blocks} blocks"
+            ),
+            Self::Orphan { block } => write!(f, "block {block} doesn't belong to any save"),
+        }
+    }
+}
+
+/// The format of a single save file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveFormat {
+    /// A `.mcs` file: the directory frame of the first block of the save followed by its blocks.
+    Mcs,
+    /// The blocks of the save alone. The name of the save is the name of the file.
+    Raw,
+}
+
+impl SaveFormat {
+    /// Guesses the format of a save file from the extension of its path. Files with unknown
+    /// extensions are assumed to be raw, since those are named after the save itself.
+    pub fn from_extension(path: &Path) -> Self {
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        match ext.map(str::to_ascii_lowercase).as_deref() {
+            Some("mcs") => Self::Mcs,
+            _ => Self::Raw,
+        }
+    }
+}
+
+/// A single save, detached from any card.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Save {
+    /// The directory frame of the first block of the save.
+    frame: [u8; FRAME_LEN],
+    /// The contents of every block of the save, in order.
+    data: Vec<u8>,
+}
+
+impl Save {
+    /// Parses a save file in the given format. Raw saves are named `name`.
+    pub fn parse(format: SaveFormat, name: &str, file: &[u8]) -> Result<Self, MemoryCardError> {
+        let (frame, data) = match format {
+            SaveFormat::Mcs => {
+                let Some((frame, data)) = file.split_first_chunk::<FRAME_LEN>() else {
+                    return Err(MemoryCardError::InvalidSave { len: file.len() });
+                };
+
+                (*frame, data)
+            }
+            SaveFormat::Raw => {
+                let frame = directory_frame(BlockState::First, 0, None, name.as_bytes());
+                (frame, file)
+            }
+        };
+
+        if data.is_empty() || data.len() % BLOCK_LEN != 0 || data.len() / BLOCK_LEN > SAVE_BLOCKS {
+            return Err(MemoryCardError::InvalidSave { len: file.len() });
+        }
+
+        Ok(Self {
+            frame,
+            data: data.to_vec(),
+        })
+    }
+
+    /// Reads and parses the save file at `path`, guessing its format from its extension.
+    pub fn read(path: &Path) -> Result<Self, MemoryCardError> {
+        let file = std::fs::read(path).context(MemoryCardCtx::Read)?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+
+        Self::parse(SaveFormat::from_extension(path), &name, &file)
+    }
+
+    /// Serializes this save in the given format.
+    pub fn to_bytes(&self, format: SaveFormat) -> Vec<u8> {
+        match format {
+            SaveFormat::Mcs => {
+                let mut file = self.first_frame(None).to_vec();
+                file.extend_from_slice(&self.data);
+                file
+            }
+            SaveFormat::Raw => self.data.clone(),
+        }
+    }
+
+    /// The directory frame of the first block of this save, pointing to `next`. Everything but
+    /// the state, size and next block pointer is kept from the original frame.
+    fn first_frame(&self, next: Option<usize>) -> [u8; FRAME_LEN] {
+        let mut frame = self.frame;
+        frame[0x00..0x04].copy_from_slice(&BlockState::First.to_bits().to_le_bytes());
+        frame[0x04..0x08].copy_from_slice(&(self.data.len() as u32).to_le_bytes());
+
+        let next = next.map_or(NO_NEXT, |block| (block - 1) as u16);
+        frame[NEXT_OFFSET..NEXT_OFFSET + 2].copy_from_slice(&next.to_le_bytes());
+
+        frame[FRAME_LEN - 1] = checksum(&frame);
+        frame
+    }
+
+    /// File name of this save, e.g. `BASLUS-00000GAME`.
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(name_bytes(&self.frame)).into_owned()
+    }
+
+    /// Number of blocks used by this save.
+    pub fn blocks(&self) -> usize {
+        self.data.len() / BLOCK_LEN
+    }
+
+    /// The contents of every block of this save, in order.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl MemoryCard {
+    /// Whether this card has been formatted, i.e. whether it starts with a header frame.
+    pub fn is_formatted(&self) -> bool {
+        self.frame(0).starts_with(b"MC")
+    }
+
+    /// Formats this card, erasing every save on it.
+    pub fn format(&mut self) {
+        let mut header = [0; FRAME_LEN];
+        header[0..2].copy_from_slice(b"MC");
+        header[FRAME_LEN - 1] = checksum(&header);
+        self.write_frame(0, &header);
+
+        let free = directory_frame(BlockState::Free, 0, None, &[]);
+        for block in 1..=SAVE_BLOCKS {
+            self.write_frame(block, &free);
+        }
+
+        let mut broken = [0; FRAME_LEN];
+        broken[0x00..0x04].copy_from_slice(&u32::MAX.to_le_bytes());
+        broken[NEXT_OFFSET..NEXT_OFFSET + 2].copy_from_slice(&NO_NEXT.to_le_bytes());
+        broken[FRAME_LEN - 1] = checksum(&broken);
+        for index in 0..BROKEN_FRAMES {
+            self.write_frame(1 + SAVE_BLOCKS + index, &broken);
+        }
+
+        // the last frame of the directory block is a copy of the header
+        self.write_frame(BLOCK_FRAMES - 1, &header);
+    }
+
+    /// Returns the directory entry of `block`, which must be in `1..=SAVE_BLOCKS`.
+    pub fn entry(&self, block: usize) -> DirectoryEntry {
+        let frame = self.frame(block);
+        let state = u32::from_le_bytes(frame[0x00..0x04].try_into().unwrap());
+        let size = u32::from_le_bytes(frame[0x04..0x08].try_into().unwrap());
+        let next = u16::from_le_bytes(frame[NEXT_OFFSET..NEXT_OFFSET + 2].try_into().unwrap());
+
+        DirectoryEntry {
+            state: BlockState::from_bits(state),
+            size,
+            next: (next != NO_NEXT).then_some(next as usize + 1),
+            name: String::from_utf8_lossy(name_bytes(frame)).into_owned(),
+        }
+    }
+
+    /// Follows the chain of blocks of the save starting at `first`. Returns the blocks of the
+    /// chain, or the block at which the chain is broken.
+    fn chain(&self, first: usize) -> Result<Vec<usize>, usize> {
+        let mut blocks = vec![first];
+        let mut current = first;
+
+        while let Some(next) = self.entry(current).next {
+            if !(1..=SAVE_BLOCKS).contains(&next) || blocks.contains(&next) {
+                return Err(current);
+            }
+
+            if !matches!(
+                self.entry(next).state,
+                BlockState::Middle | BlockState::Last
+            ) {
+                return Err(current);
+            }
+
+            blocks.push(next);
+            current = next;
+        }
+
+        Ok(blocks)
+    }
+
+    /// Returns the first block of every save on this card.
+    pub fn saves(&self) -> impl Iterator<Item = usize> + use<'_> {
+        (1..=SAVE_BLOCKS).filter(|&block| self.entry(block).state == BlockState::First)
+    }
+
+    /// Extracts the save whose first block is `first`.
+    pub fn save(&self, first: usize) -> Result<Save, MemoryCardError> {
+        if !(1..=SAVE_BLOCKS).contains(&first) || self.entry(first).state != BlockState::First {
+            return Err(MemoryCardError::NoSuchSave { block: first });
+        }
+
+        let blocks = self
+            .chain(first)
+            .map_err(|block| MemoryCardError::BrokenSave { first, block })?;
+
+        let mut data = Vec::with_capacity(blocks.len() * BLOCK_LEN);
+        for block in blocks {
+            data.extend_from_slice(&self.data()[block * BLOCK_LEN..(block + 1) * BLOCK_LEN]);
+        }
+
+        Ok(Save {
+            frame: *self.frame(first),
+            data,
+        })
+    }
+
+    /// Writes `save` to the free blocks of this card, relocating its chain of blocks and
+    /// recomputing the checksums of their directory frames. Returns the first block of the save.
+    pub fn insert_save(&mut self, save: &Save) -> Result<usize, MemoryCardError> {
+        if !self.is_formatted() {
+            return Err(MemoryCardError::Unformatted);
+        }
+
+        let name = name_bytes(&save.frame);
+        let exists = self
+            .saves()
+            .any(|first| name_bytes(self.frame(first)) == name);
+
+        if exists {
+            return Err(MemoryCardError::SaveExists { name: save.name() });
+        }
+
+        let free = (1..=SAVE_BLOCKS)
+            .filter(|&block| self.entry(block).state.is_free())
+            .collect::<Vec<_>>();
+
+        let needed = save.blocks();
+        if free.len() < needed {
+            return Err(MemoryCardError::CardFull {
+                needed,
+                free: free.len(),
+            });
+        }
+
+        let blocks = &free[..needed];
+        for (index, &block) in blocks.iter().enumerate() {
+            let next = blocks.get(index + 1).copied();
+            let frame = if index == 0 {
+                save.first_frame(next)
+            } else {
+                let state = if next.is_some() {
+                    BlockState::Middle
+                } else {
+                    BlockState::Last
+                };
+
+                directory_frame(state, 0, next, &[])
+            };
+
+            self.write_frame(block, &frame);
+
+            let data = &save.data[index * BLOCK_LEN..(index + 1) * BLOCK_LEN];
+            for (offset, chunk) in data.chunks_exact(FRAME_LEN).enumerate() {
+                self.write_frame(block * BLOCK_FRAMES + offset, chunk.try_into().unwrap());
+            }
+        }
+
+        Ok(blocks[0])
+    }
+
+    /// Checks the consistency of the directory of this card: the checksums of the directory
+    /// frames, the chains of blocks of every save and their sizes.
+    pub fn check_directory(&self) -> Vec<DirectoryIssue> {
+        if !self.is_formatted() {
+            return vec![DirectoryIssue::Unformatted];
+        }
+
+        let mut issues = Vec::new();
+        for block in 1..=SAVE_BLOCKS {
+            let frame = self.frame(block);
+            if frame[FRAME_LEN - 1] != checksum(frame) {
+                issues.push(DirectoryIssue::BadChecksum { block });
+            }
+        }
+
+        let mut reached = [false; SAVE_BLOCKS + 1];
+        for first in self.saves() {
+            match self.chain(first) {
+                Ok(blocks) => {
+                    let size = self.entry(first).size;
+                    if size as usize != blocks.len() * BLOCK_LEN {
+                        issues.push(DirectoryIssue::SizeMismatch {
+                            first,
+                            size,
+                            blocks: blocks.len(),
+                        });
+                    }
+
+                    for block in blocks {
+                        reached[block] = true;
+                    }
+                }
+                Err(block) => issues.push(DirectoryIssue::BrokenChain { first, block }),
+            }
+        }
+
+        for block in 1..=SAVE_BLOCKS {
+            let continues = matches!(
+                self.entry(block).state,
+                BlockState::Middle | BlockState::Last
+            );
+
+            if continues && !reached[block] {
+                issues.push(DirectoryIssue::Orphan { block });
+            }
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted() -> MemoryCard {
+        let mut card = MemoryCard::new();
+        card.format();
+        card
+    }
+
+    /// Returns a raw save named `name` spanning `blocks` blocks, each filled with `fill` plus its
+    /// index.
+    fn save(name: &str, blocks: usize, fill: u8) -> Save {
+        let data = (0..blocks)
+            .flat_map(|block| std::iter::repeat_n(fill + block as u8, BLOCK_LEN))
+            .collect::<Vec<_>>();
+
+        Save::parse(SaveFormat::Raw, name, &data).unwrap()
+    }
+
+    /// Marks the blocks of the save at `first` as deleted, as the BIOS does.
+    fn delete(card: &mut MemoryCard, first: usize) {
+        let blocks = card.chain(first).unwrap();
+        for (index, &block) in blocks.iter().enumerate() {
+            let state = match index {
+                0 => 0xA1,
+                _ if index == blocks.len() - 1 => 0xA3,
+                _ => 0xA2,
+            };
+
+            let mut frame = *card.frame(block);
+            frame[0x00..0x04].copy_from_slice(&u32::from(state).to_le_bytes());
+            frame[FRAME_LEN - 1] = checksum(&frame);
+            card.write_frame(block, &frame);
+        }
+    }
+
+    #[test]
+    fn formatted_cards_have_a_clean_empty_directory() {
+        let card = formatted();
+
+        assert!(card.is_formatted());
+        assert_eq!(card.saves().count(), 0);
+        assert!(card.check_directory().is_empty());
+        for block in 1..=SAVE_BLOCKS {
+            assert_eq!(card.entry(block).state, BlockState::Free);
+        }
+
+        assert_eq!(
+            MemoryCard::new().check_directory(),
+            [DirectoryIssue::Unformatted]
+        );
+    }
+
+    #[test]
+    fn inserted_saves_are_relocated_into_free_blocks() {
+        let mut card = formatted();
+        assert_eq!(
+            card.insert_save(&save("BASLUS-00001AAAA", 1, 0x10))
+                .unwrap(),
+            1
+        );
+        let second = card
+            .insert_save(&save("BASLUS-00002BBBB", 2, 0x20))
+            .unwrap();
+        assert_eq!(second, 2);
+        assert_eq!(
+            card.insert_save(&save("BASLUS-00003CCCC", 1, 0x30))
+                .unwrap(),
+            4
+        );
+
+        // leave a hole in blocks 2 and 3, so the next save is split around block 4
+        delete(&mut card, second);
+        let inserted = save("BASLUS-00004DDDD", 3, 0x40);
+        let first = card.insert_save(&inserted).unwrap();
+        assert_eq!(first, 2);
+        assert_eq!(card.chain(first).unwrap(), [2, 3, 5]);
+
+        let entries = [2, 3, 5].map(|block| card.entry(block));
+        assert_eq!(entries[0].state, BlockState::First);
+        assert_eq!(entries[0].size, 3 * BLOCK_LEN as u32);
+        assert_eq!(entries[0].name, "BASLUS-00004DDDD");
+        assert_eq!(entries[1].state, BlockState::Middle);
+        assert_eq!(entries[2].state, BlockState::Last);
+        assert_eq!(entries[2].next, None);
+
+        assert!(card.check_directory().is_empty());
+        assert_eq!(card.save(first).unwrap().data(), inserted.data());
+    }
+
+    #[test]
+    fn saves_round_trip_through_mcs_files() {
+        let mut card = formatted();
+        card.insert_save(&save("BESLES-00005EEEE", 1, 0x50))
+            .unwrap();
+        let first = card
+            .insert_save(&save("BESLES-00006FFFF", 2, 0x60))
+            .unwrap();
+
+        let exported = card.save(first).unwrap();
+        let file = exported.to_bytes(SaveFormat::Mcs);
+        assert_eq!(file.len(), FRAME_LEN + 2 * BLOCK_LEN);
+
+        let imported = Save::parse(SaveFormat::Mcs, "ignored", &file).unwrap();
+        assert_eq!(imported.name(), "BESLES-00006FFFF");
+        assert_eq!(imported.data(), exported.data());
+
+        let mut other = formatted();
+        let first = other.insert_save(&imported).unwrap();
+        assert_eq!(first, 1);
+        assert_eq!(other.save(first).unwrap().data(), exported.data());
+        assert!(other.check_directory().is_empty());
+    }
+
+    #[test]
+    fn raw_saves_are_named_after_their_file() {
+        let save = save("BASLUS-00007GGGG", 1, 0x70);
+        assert_eq!(save.name(), "BASLUS-00007GGGG");
+        assert_eq!(save.to_bytes(SaveFormat::Raw), save.data());
+
+        assert!(matches!(
+            Save::parse(SaveFormat::Raw, "TOO-SHORT", &[0; BLOCK_LEN - 1]),
+            Err(MemoryCardError::InvalidSave { .. })
+        ));
+    }
+
+    #[test]
+    fn insertion_errors() {
+        let mut unformatted = MemoryCard::new();
+        assert!(matches!(
+            unformatted.insert_save(&save("A", 1, 0)),
+            Err(MemoryCardError::Unformatted)
+        ));
+
+        let mut card = formatted();
+        card.insert_save(&save("A", 14, 0)).unwrap();
+        assert!(matches!(
+            card.insert_save(&save("A", 1, 0)),
+            Err(MemoryCardError::SaveExists { .. })
+        ));
+        assert!(matches!(
+            card.insert_save(&save("B", 2, 0)),
+            Err(MemoryCardError::CardFull { needed: 2, free: 1 })
+        ));
+    }
+
+    #[test]
+    fn directory_check_finds_inconsistencies() {
+        let mut card = formatted();
+        let first = card.insert_save(&save("A", 3, 0)).unwrap();
+
+        // a bad checksum
+        let mut frame = *card.frame(first);
+        frame[FRAME_LEN - 1] ^= 0xFF;
+        card.write_frame(first, &frame);
+        assert_eq!(
+            card.check_directory(),
+            [DirectoryIssue::BadChecksum { block: first }]
+        );
+        frame[FRAME_LEN - 1] ^= 0xFF;
+        card.write_frame(first, &frame);
+
+        // a size which doesn't match the chain
+        let mut wrong_size = frame;
+        wrong_size[0x04..0x08].copy_from_slice(&(2 * BLOCK_LEN as u32).to_le_bytes());
+        wrong_size[FRAME_LEN - 1] = checksum(&wrong_size);
+        card.write_frame(first, &wrong_size);
+        assert_eq!(
+            card.check_directory(),
+            [DirectoryIssue::SizeMismatch {
+                first,
+                size: 2 * BLOCK_LEN as u32,
+                blocks: 3
+            }]
+        );
+        card.write_frame(first, &frame);
+
+        // a chain pointing out of the card, which leaves the rest of the save orphaned
+        let mut broken = *card.frame(first + 1);
+        broken[NEXT_OFFSET..NEXT_OFFSET + 2].copy_from_slice(&100u16.to_le_bytes());
+        broken[FRAME_LEN - 1] = checksum(&broken);
+        card.write_frame(first + 1, &broken);
+        assert_eq!(
+            card.check_directory(),
+            [
+                DirectoryIssue::BrokenChain {
+                    first,
+                    block: first + 1
+                },
+                DirectoryIssue::Orphan { block: first + 1 },
+                DirectoryIssue::Orphan { block: first + 2 },
+            ]
+        );
+        assert!(matches!(
+            card.save(first),
+            Err(MemoryCardError::BrokenSave { .. })
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+//! Container formats of memory card images used by other emulators and devices.
+
+use super::{CARD_LEN, FRAME_LEN, MemoryCardError};
+use std::path::Path;
+
+/// Magic at the start of DexDrive images.
+const DEXDRIVE_MAGIC: &[u8] = b"123-456-STD";
+/// Length of the header of DexDrive images, which holds a summary of the directory and a 256 byte
+/// comment for each block, starting at offset 0x40.
+const DEXDRIVE_HEADER_LEN: usize = 0xF40;
+
+/// Magic at the start of PSP images.
+const VMP_MAGIC: &[u8] = b"\0PMV";
+/// Length of the header of PSP images, which holds their signature.
+const VMP_HEADER_LEN: usize = 0x80;
+
+/// The container format of a memory card image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// A raw 128 KiB image, usually with a `.mcr`, `.mcd` or `.srm` extension.
+    Raw,
+    /// A DexDrive image (`.gme`): a raw image preceded by a header with a summary of the
+    /// directory and a comment for each block.
+    DexDrive,
+    /// A PSP image (`.vmp`): a raw image preceded by a signed header.
+    Vmp,
+}
+
+impl ImageFormat {
+    /// Detects the format of an image from its length and magic.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data.len() {
+            CARD_LEN => Some(Self::Raw),
+            len if len == DEXDRIVE_HEADER_LEN + CARD_LEN && data.starts_with(DEXDRIVE_MAGIC) => {
+                Some(Self::DexDrive)
+            }
+            len if len == VMP_HEADER_LEN + CARD_LEN && data.starts_with(VMP_MAGIC) => {
+                Some(Self::Vmp)
+            }
+            _ => None,
+        }
+    }
+
+    /// Guesses the format of an image from the extension of its path.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "mcr" | "mcd" | "mc" | "srm" | "mem" | "ps" => Some(Self::Raw),
+            "gme" => Some(Self::DexDrive),
+            "vmp" => Some(Self::Vmp),
+            _ => None,
+        }
+    }
+
+    fn header_len(self) -> usize {
+        match self {
+            Self::Raw => 0,
+            Self::DexDrive => DEXDRIVE_HEADER_LEN,
+            Self::Vmp => VMP_HEADER_LEN,
+        }
+    }
+
+    /// Strips the container of an image in this format, returning the raw image inside.
+    pub fn unwrap(self, data: &[u8]) -> Result<&[u8], MemoryCardError> {
+        let start = self.header_len();
+        if data.len() != start + CARD_LEN {
+            return Err(MemoryCardError::UnknownFormat { len: data.len() });
+        }
+
+        Ok(&data[start..])
+    }
+
+    /// Wraps a raw image in the container of this format.
+    ///
+    /// PSP images are signed with a key derived from the console's, so they can't be written.
+    pub fn wrap(self, card: &[u8]) -> Result<Vec<u8>, MemoryCardError> {
+        if card.len() != CARD_LEN {
+            return Err(MemoryCardError::InvalidLength { len: card.len() });
+        }
+
+        let mut data = match self {
+            Self::Raw => Vec::with_capacity(CARD_LEN),
+            Self::DexDrive => dexdrive_header(card),
+            Self::Vmp => return Err(MemoryCardError::UnsupportedExport { format: self }),
+        };
+
+        data.extend_from_slice(card);
+        Ok(data)
+    }
+}
+
+/// Builds the header of a DexDrive image. Besides the magic, it mirrors the state and next block
+/// pointer of every directory frame and leaves every comment empty.
+fn dexdrive_header(card: &[u8]) -> Vec<u8> {
+    let mut header = vec![0; DEXDRIVE_HEADER_LEN];
+    header[..DEXDRIVE_MAGIC.len()].copy_from_slice(DEXDRIVE_MAGIC);
+    header[0x12] = 0x01;
+    header[0x14] = 0x01;
+    header[0x15] = b'M';
+
+    for block in 0..15 {
+        let frame = &card[(block + 1) * FRAME_LEN..];
+        header[0x16 + block] = frame[0x00];
+        header[0x26 + block] = frame[0x08];
+    }
+
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Reads the memory card image fixture `name`. Every image holds the same card, formatted
+    /// and with a single save of two blocks.
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(testing::fixture(&format!("memcard/{name}"))).unwrap()
+    }
+
+    #[test]
+    fn raw_images_round_trip() {
+        let data = fixture("card.mcr");
+
+        assert_eq!(ImageFormat::detect(&data), Some(ImageFormat::Raw));
+        assert_eq!(ImageFormat::Raw.unwrap(&data).unwrap(), data);
+        assert_eq!(ImageFormat::Raw.wrap(&data).unwrap(), data);
+    }
+
+    #[test]
+    fn dexdrive_images_round_trip() {
+        let card = fixture("card.mcr");
+        let data = fixture("card.gme");
+
+        assert_eq!(ImageFormat::detect(&data), Some(ImageFormat::DexDrive));
+        assert_eq!(ImageFormat::DexDrive.unwrap(&data).unwrap(), card);
+
+        // everything but the comments, which aren't kept, is written back the same
+        let wrapped = ImageFormat::DexDrive.wrap(&card).unwrap();
+        assert_eq!(wrapped.len(), data.len());
+        assert_eq!(wrapped[..0x40], data[..0x40]);
+        assert_eq!(wrapped[DEXDRIVE_HEADER_LEN..], card);
+    }
+
+    #[test]
+    fn vmp_images_are_import_only() {
+        let card = fixture("card.mcr");
+        let data = fixture("card.vmp");
+
+        assert_eq!(ImageFormat::detect(&data), Some(ImageFormat::Vmp));
+        assert_eq!(ImageFormat::Vmp.unwrap(&data).unwrap(), card);
+        assert!(matches!(
+            ImageFormat::Vmp.wrap(&card),
+            Err(MemoryCardError::UnsupportedExport {
+                format: ImageFormat::Vmp
+            })
+        ));
+    }
+
+    #[test]
+    fn images_without_a_known_length_and_magic_are_rejected() {
+        let card = fixture("card.mcr");
+        let data = fixture("card.gme");
+
+        assert_eq!(ImageFormat::detect(&card[1..]), None);
+        assert_eq!(ImageFormat::detect(&data[1..]), None);
+
+        let mut unknown = data.clone();
+        unknown[0] = 0;
+        assert_eq!(ImageFormat::detect(&unknown), None);
+
+        assert!(matches!(
+            ImageFormat::DexDrive.unwrap(&data[1..]),
+            Err(MemoryCardError::UnknownFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn wrapping_rejects_images_of_the_wrong_length() {
+        let card = fixture("card.mcr");
+
+        for format in [ImageFormat::Raw, ImageFormat::DexDrive] {
+            assert!(
+                matches!(
+                    format.wrap(&card[1..]),
+                    Err(MemoryCardError::InvalidLength { len }) if len == CARD_LEN - 1
+                ),
+                "{format:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn formats_are_guessed_from_extensions() {
+        let format = |path: &str| ImageFormat::from_extension(Path::new(path));
+        assert_eq!(format("card.MCR"), Some(ImageFormat::Raw));
+        assert_eq!(format("card.mcd"), Some(ImageFormat::Raw));
+        assert_eq!(format("card.gme"), Some(ImageFormat::DexDrive));
+        assert_eq!(format("card.vmp"), Some(ImageFormat::Vmp));
+        assert_eq!(format("card.txt"), None);
+        assert_eq!(format("card"), None);
+    }
+}
@@ -0,0 +1,82 @@
+//! Timing of SIO0 transfers and acknowledges.
+
+use shimmer_core::{CYCLES_MICROS, Cycles};
+
+/// Timing of SIO0 transfers and of the acknowledge pulses sent by devices.
+///
+/// On hardware, the time a device takes to acknowledge a byte varies slightly. If `jitter` is
+/// non-zero, a pseudo-random amount of cycles in `0..=jitter` is added to every acknowledge delay.
+/// The amounts are drawn from a generator seeded with `seed`, so two runs with the same timing
+/// configuration and inputs always produce exactly the same delays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// Cycles it takes to transfer a byte.
+    pub transfer: Cycles,
+    /// Cycles between the end of a transfer and the start of the acknowledge pulse.
+    pub start_ack: Cycles,
+    /// Duration of the acknowledge pulse, in cycles.
+    pub end_ack: Cycles,
+    /// Maximum amount of cycles added to acknowledge delays.
+    pub jitter: Cycles,
+    /// Seed of the jitter generator.
+    pub seed: u64,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            transfer: 46 * CYCLES_MICROS,
+            start_ack: 3 * CYCLES_MICROS,
+            end_ack: 2 * CYCLES_MICROS,
+            jitter: 0,
+            seed: 0,
+        }
+    }
+}
+
+/// Produces the delays of SIO0 events according to a [`Timing`].
+#[derive(Debug, Clone, Default)]
+pub(super) struct Delays {
+    timing: Timing,
+    /// State of the SplitMix64 jitter generator.
+    state: u64,
+}
+
+impl Delays {
+    pub fn new(timing: Timing) -> Self {
+        Self {
+            timing,
+            state: timing.seed,
+        }
+    }
+
+    /// Next output of the SplitMix64 generator, which is tiny, fast and fully determined by its
+    /// seed.
+    fn next_random(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn jitter(&mut self) -> Cycles {
+        match self.timing.jitter {
+            0 => 0,
+            jitter => self.next_random() % (jitter + 1),
+        }
+    }
+
+    pub fn transfer(&self) -> Cycles {
+        self.timing.transfer
+    }
+
+    pub fn start_ack(&mut self) -> Cycles {
+        self.timing.start_ack + self.jitter()
+    }
+
+    pub fn end_ack(&mut self) -> Cycles {
+        self.timing.end_ack + self.jitter()
+    }
+}
@@ -0,0 +1,487 @@
+//! An implementation of the SPU.
+//!
+//! The SPU RAM, the data transfers to and from it, the IRQ, the decoding and pitch of voices, the
+//! noise generator and the final mixing stage are emulated. Voices play without ADSR envelopes or
+//! interpolation. The output is only produced while an [`AudioSink`](crate::audio::AudioSink) is
+//! attached, and CD audio isn't routed to it yet.
+
+use crate::{PSX, audio::Sample};
+use shimmer_core::{
+    CYCLES_SECOND, Cycles,
+    interrupts::Interrupt,
+    spu::{
+        BLOCK_LEN, BLOCK_LOOP_END, BLOCK_LOOP_REPEAT, BLOCK_LOOP_START, BLOCK_SAMPLES, Control,
+        SPU_RAM_LEN, Spu, VOICES, Voice,
+    },
+};
+
+/// Cycles between two samples of the SPU output, which runs at 44100Hz.
+pub const SAMPLE_CYCLES: Cycles = CYCLES_SECOND / 44100;
+
+/// Value of the pitch counter of a voice once it's past the end of its block.
+const BLOCK_END: u32 = (BLOCK_SAMPLES as u32) << 12;
+
+/// Maximum pitch step of a voice, i.e. four times the output rate.
+const MAX_STEP: u32 = 0x4000;
+
+/// Weights of the previous sample in each ADPCM filter, in 1/64ths.
+const POSITIVE_FILTERS: [i32; 5] = [0, 60, 115, 98, 122];
+
+/// Weights of the sample before the previous one in each ADPCM filter, in 1/64ths.
+const NEGATIVE_FILTERS: [i32; 5] = [0, 0, -52, -55, -60];
+
+/// Requests the SPU IRQ if it's enabled and an access to the SPU RAM at `address` hits the IRQ
+/// address.
+pub(crate) fn check_irq(psx: &mut PSX, address: u32) {
+    if psx.spu.control.irq_enable() && !psx.spu.irq && psx.spu.hits_irq_address(address) {
+        psx.spu.irq = true;
+        psx.interrupts.status.request(Interrupt::SPU);
+    }
+}
+
+/// Returns the address of the next halfword to be transferred and advances it past it, checking
+/// whether the access hits the IRQ address.
+fn advance_transfer(psx: &mut PSX) -> usize {
+    let address = psx.spu.current_address as usize % SPU_RAM_LEN;
+    psx.spu.current_address = ((address + 2) % SPU_RAM_LEN) as u32;
+    check_irq(psx, address as u32);
+
+    address
+}
+
+/// Writes a halfword to the SPU RAM at the current transfer address.
+pub(crate) fn write_transfer(psx: &mut PSX, value: u16) {
+    let address = advance_transfer(psx);
+    psx.spu.ram[address..address + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Reads a halfword from the SPU RAM at the current transfer address.
+pub(crate) fn read_transfer(psx: &mut PSX) -> u16 {
+    let address = advance_transfer(psx);
+    u16::from_le_bytes([psx.spu.ram[address], psx.spu.ram[address + 1]])
+}
+
+/// Sets the transfer address register, which also moves the current transfer address.
+pub(crate) fn write_transfer_address(psx: &mut PSX, value: u16) {
+    psx.spu.transfer_address = value;
+    psx.spu.current_address = u32::from(value) * 8;
+}
+
+/// Sets the SPU control register. Disabling the IRQ acknowledges a pending one.
+pub(crate) fn write_control(psx: &mut PSX, control: Control) {
+    psx.spu.control = control;
+    if !control.irq_enable() {
+        psx.spu.irq = false;
+    }
+}
+
+/// Applies the volume register `volume` to `sample`. Sweep mode isn't emulated, so a sweeping
+/// volume is taken at its maximum.
+fn apply_volume(sample: i32, volume: u16) -> i32 {
+    let volume = if volume & 0x8000 == 0 {
+        // bits 0..15 hold half of the volume, as a signed value
+        i32::from((volume << 1) as i16)
+    } else {
+        0x7FFF
+    };
+
+    (sample * volume) >> 15
+}
+
+/// Mixes the output of the voices with the CD audio input, both left and right, as the final
+/// stage of the SPU does: CD audio is scaled by the CD volume, and the sum by the main volume.
+/// Muting or disabling the SPU only silences the voices.
+pub fn mix(spu: &Spu, voices: [i16; 2], cd: [i16; 2]) -> [i16; 2] {
+    let clamp = |sample: i32| sample.clamp(i16::MIN.into(), i16::MAX.into());
+
+    std::array::from_fn(|channel| {
+        let voices = if spu.control.enable() && spu.control.unmute() {
+            i32::from(voices[channel])
+        } else {
+            0
+        };
+
+        let cd = if spu.control.cd_audio_enable() {
+            (i32::from(cd[channel]) * i32::from(spu.cd_volume[channel] as i16)) >> 15
+        } else {
+            0
+        };
+
+        clamp(apply_volume(clamp(voices + cd), spu.main_volume[channel])) as i16
+    })
+}
+
+/// Decodes the ADPCM block at the current address of `voice`.
+fn decode_block(ram: &[u8], voice: &mut Voice) {
+    let address = voice.current_address as usize;
+    let byte = |index: usize| ram[(address + index) % SPU_RAM_LEN];
+
+    let header = byte(0);
+    let shift = match header & 0x0F {
+        shift @ 0..=12 => shift,
+        // reserved shifts behave as 9
+        _ => 9,
+    };
+    let filter = usize::from((header >> 4) & 0x07).min(POSITIVE_FILTERS.len() - 1);
+    voice.flags = byte(1);
+
+    for (index, sample) in voice.samples.iter_mut().enumerate() {
+        let nibble = (byte(2 + index / 2) >> (4 * (index % 2))) & 0x0F;
+        let raw = i32::from((u16::from(nibble) << 12) as i16) >> shift;
+
+        let [previous, before] = voice.history.map(i32::from);
+        let predicted =
+            (previous * POSITIVE_FILTERS[filter] + before * NEGATIVE_FILTERS[filter] + 32) >> 6;
+        let decoded = (raw + predicted).clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+
+        voice.history = [decoded, voice.history[0]];
+        *sample = decoded;
+    }
+}
+
+/// Decodes the block at the current address of the voice `index`, checking whether the access
+/// hits the IRQ address.
+fn load_block(psx: &mut PSX, index: usize) {
+    let Spu { ram, voices, .. } = &mut psx.spu;
+    let voice = &mut voices[index];
+    decode_block(&ram[..], voice);
+
+    if voice.flags & BLOCK_LOOP_START != 0 {
+        voice.repeat_address = voice.current_address;
+    }
+
+    let address = voice.current_address;
+    check_irq(psx, address);
+}
+
+/// Moves the voice `index` to the block after its current one, following the loop flags of the
+/// current block.
+fn next_block(psx: &mut PSX, index: usize) {
+    let voice = &mut psx.spu.voices[index];
+    if voice.flags & BLOCK_LOOP_END != 0 {
+        voice.current_address = voice.repeat_address;
+        if voice.flags & BLOCK_LOOP_REPEAT == 0 {
+            voice.playing = false;
+        }
+
+        psx.spu.endx |= 1 << index;
+    } else {
+        voice.current_address = (voice.current_address + BLOCK_LEN) % SPU_RAM_LEN as u32;
+    }
+
+    load_block(psx, index);
+}
+
+/// Starts playing the voices whose bits are set in `voices`, from their start address.
+pub(crate) fn key_on(psx: &mut PSX, voices: u32) {
+    for index in (0..VOICES).filter(|index| voices & (1 << index) != 0) {
+        let voice = &mut psx.spu.voices[index];
+        voice.playing = true;
+        voice.current_address = voice.start_address();
+        voice.repeat_address = voice.start_address();
+        voice.pitch_counter = 0;
+        voice.history = [0; 2];
+
+        psx.spu.endx &= !(1 << index);
+        load_block(psx, index);
+    }
+}
+
+/// Stops playing the voices whose bits are set in `voices`. The release phase of the envelope
+/// isn't emulated, so they're silenced right away.
+pub(crate) fn key_off(psx: &mut PSX, voices: u32) {
+    for index in (0..VOICES).filter(|index| voices & (1 << index) != 0) {
+        psx.spu.voices[index].playing = false;
+    }
+}
+
+/// Advances the noise generator by one sample of the SPU output.
+///
+/// The generator is clocked at a rate set by the noise shift and step of the control register: its
+/// timer counts down by the step plus 4 every sample, and reloads with `0x20000 >> shift` on every
+/// clock, which shifts the feedback of the LFSR into its level.
+fn tick_noise(psx: &mut PSX) {
+    let control = psx.spu.control;
+    let step = i32::from(control.noise_step().value()) + 4;
+    let reload = 0x20000 >> control.noise_shift().value();
+
+    psx.spu.noise_timer -= step;
+    if psx.spu.noise_timer < 0 {
+        let level = psx.spu.noise_level;
+        let parity = ((level >> 15) ^ (level >> 12) ^ (level >> 11) ^ (level >> 10) ^ 1) & 1;
+        psx.spu.noise_level = (level << 1) | parity;
+
+        psx.spu.noise_timer += reload;
+        if psx.spu.noise_timer < 0 {
+            psx.spu.noise_timer += reload;
+        }
+    }
+}
+
+/// Advances every voice by one sample of the SPU output, which runs at 44100Hz, and returns their
+/// outputs.
+///
+/// A voice advances through its samples at its sample rate or, if its pitch is modulated, at its
+/// sample rate scaled by the output of the previous voice, from 0 at -0x8000 to almost twice at
+/// 0x7FFF.
+///
+/// A voice in noise mode outputs the noise generator instead of its samples, but still advances
+/// through them, so that it keeps following their loop flags.
+pub fn tick_voices(psx: &mut PSX) -> [i16; VOICES] {
+    tick_noise(psx);
+
+    let mut outputs = [0; VOICES];
+    for index in 0..VOICES {
+        let voice = &psx.spu.voices[index];
+        if !voice.playing {
+            continue;
+        }
+
+        outputs[index] = if psx.spu.noise_mode & (1 << index) != 0 {
+            psx.spu.noise_level as i16
+        } else {
+            voice.samples[(voice.pitch_counter >> 12) as usize]
+        };
+
+        let mut step = u32::from(voice.sample_rate());
+        if index > 0 && psx.spu.fm_mode & (1 << index) != 0 {
+            let factor = (i32::from(outputs[index - 1]) + 0x8000) as u32;
+            step = ((step * factor) >> 15) & 0xFFFF;
+        }
+
+        let voice = &mut psx.spu.voices[index];
+        voice.pitch_counter += step.min(MAX_STEP);
+        while psx.spu.voices[index].pitch_counter >= BLOCK_END {
+            psx.spu.voices[index].pitch_counter -= BLOCK_END;
+            next_block(psx, index);
+        }
+    }
+
+    outputs
+}
+
+/// Produces the next sample of the SPU output: advances the voices, scales each by its left and
+/// right volumes and mixes their sum. CD audio isn't routed to the SPU yet, so it's silent.
+pub fn output(psx: &mut PSX) -> Sample {
+    let outputs = tick_voices(psx);
+
+    let mut voices = [0i32; 2];
+    for (voice, output) in psx.spu.voices.iter().zip(outputs) {
+        for (channel, sum) in voices.iter_mut().enumerate() {
+            *sum += apply_volume(i32::from(output), voice.regs[channel]);
+        }
+    }
+
+    let voices = voices.map(|sum| sum.clamp(i16::MIN.into(), i16::MAX.into()) as i16);
+    mix(&psx.spu, voices, [0; 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use shimmer_core::mem::{Address, io};
+
+    /// SPUCNT enabled, with the IRQ enabled and manual write transfers.
+    const IRQ_CONTROL: u16 = 0x8050;
+
+    /// Returns whether the IRQ flag of SPUSTAT is set and whether the SPU IRQ is requested.
+    fn irq(psx: &mut PSX) -> (bool, bool) {
+        let spustat = testing::read::<u16>(psx, io::Reg::SpuStatus);
+        let requested = psx.interrupts.status.status()[Interrupt::SPU as usize];
+        (spustat & 0x40 != 0, requested)
+    }
+
+    #[test]
+    fn irq_fires_when_a_transfer_reaches_its_address() {
+        let mut psx = testing::psx();
+
+        // the IRQ is at 0x80, and the transfer starts 8 halfwords before it
+        testing::write::<u16>(&mut psx, io::Reg::SpuControl, IRQ_CONTROL);
+        testing::write::<u16>(&mut psx, io::Reg::SramInterruptAddress, 0x0010);
+        testing::write::<u16>(&mut psx, io::Reg::SramAddress, 0x000E);
+
+        for i in 0..8 {
+            testing::write::<u16>(&mut psx, io::Reg::SramFifo, i);
+        }
+        assert_eq!(irq(&mut psx), (false, false));
+
+        testing::write::<u16>(&mut psx, io::Reg::SramFifo, 8);
+        assert_eq!(irq(&mut psx), (true, true));
+
+        // disabling the IRQ acknowledges it
+        testing::write::<u16>(&mut psx, io::Reg::SpuControl, IRQ_CONTROL & !0x40);
+        assert!(!irq(&mut psx).0);
+    }
+
+    #[test]
+    fn reverb_registers_read_back_and_reach_the_reverb_state() {
+        const VOLUME: [u16; 2] = [0x3FFF, 0x2000];
+        const WORK_AREA_START: u16 = 0xE000;
+        const VOICES: u32 = 0x00A5_5A01;
+
+        let mut psx = testing::psx();
+        let config_start = io::Reg::ReverbApfOffset1 as u32;
+        let config_value = |index: u32| 0x1000 + index as u16;
+
+        // SPUCNT enabled, with reverb enabled
+        testing::write::<u16>(&mut psx, io::Reg::SpuControl, 0x8080);
+        testing::write::<u32>(
+            &mut psx,
+            io::Reg::ReverbVolume,
+            u32::from(VOLUME[0]) | (u32::from(VOLUME[1]) << 16),
+        );
+        testing::write::<u16>(&mut psx, io::Reg::SramReverbAddress, WORK_AREA_START);
+        testing::write::<u32>(&mut psx, io::Reg::VoiceChannelReverbMode, VOICES);
+        for index in 0..32 {
+            let addr = Address(config_start + 2 * index);
+            psx.write::<u16, true>(addr, config_value(index)).unwrap();
+        }
+
+        assert_eq!(
+            testing::read::<u32>(&mut psx, io::Reg::ReverbVolume),
+            u32::from(VOLUME[0]) | (u32::from(VOLUME[1]) << 16)
+        );
+        assert_eq!(
+            testing::read::<u16>(&mut psx, io::Reg::SramReverbAddress),
+            WORK_AREA_START
+        );
+        assert_eq!(
+            testing::read::<u32>(&mut psx, io::Reg::VoiceChannelReverbMode),
+            VOICES
+        );
+        for index in 0..32 {
+            let addr = Address(config_start + 2 * index);
+            assert_eq!(
+                psx.read::<u16, true>(addr).unwrap(),
+                config_value(index),
+                "{addr}"
+            );
+        }
+
+        let reverb = psx.spu.reverb;
+        assert!(psx.spu.control.reverb_enable());
+        assert_eq!(reverb.output_volume, VOLUME);
+        assert_eq!(reverb.work_area_start, WORK_AREA_START);
+        assert_eq!(reverb.voices, VOICES);
+        for index in 0..32 {
+            assert_eq!(reverb.config[index as usize], config_value(index));
+        }
+    }
+
+    #[test]
+    fn mixing_scales_voices_and_cd_audio_and_muting_only_silences_voices() {
+        const VOICES: [i16; 2] = [0x1000, 0x1000];
+        const CD: [i16; 2] = [0x2000, 0x2000];
+
+        // main volume at its maximum on the left and at half on the right, CD volume at half on
+        // both
+        let mut spu = Spu::default();
+        spu.main_volume = [0x3FFF, 0x2000];
+        spu.cd_volume = [0x4000, 0x4000];
+
+        // SPUCNT enabled and unmuted, with CD audio enabled: (0x1000 + 0x2000 / 2) scaled by the
+        // main volume
+        spu.control = Control::from_bits(0xC001);
+        assert_eq!(mix(&spu, VOICES, CD), [0x1FFF, 0x1000]);
+
+        // muted: only the CD audio is left
+        spu.control = Control::from_bits(0x8001);
+        assert_eq!(mix(&spu, VOICES, CD), [0x0FFF, 0x0800]);
+    }
+
+    #[test]
+    fn voices_play_at_their_sample_rate_and_are_pitch_modulated() {
+        // a looping ADPCM block, without shift or filter, holding a square wave of period 28: 14
+        // samples of 0x7000 followed by 14 samples of -0x8000
+        const SQUARE: usize = 0x1000;
+        // a looping ADPCM block holding 28 samples of 0x7000
+        const CONSTANT: usize = 0x1010;
+        const TICKS: u32 = 448;
+
+        let mut psx = testing::psx();
+        psx.spu.ram[SQUARE..SQUARE + 2].copy_from_slice(&[0x00, 0x07]);
+        psx.spu.ram[SQUARE + 2..SQUARE + 9].fill(0x77);
+        psx.spu.ram[SQUARE + 9..SQUARE + 16].fill(0x88);
+        psx.spu.ram[CONSTANT..CONSTANT + 2].copy_from_slice(&[0x00, 0x07]);
+        psx.spu.ram[CONSTANT + 2..CONSTANT + 16].fill(0x77);
+
+        // (sample rate, start) of voices 0 to 2: the square at half of the output rate, the
+        // constant and the square at the output rate, modulated by the constant
+        let voices = [(0x0800, SQUARE), (0x1000, CONSTANT), (0x1000, SQUARE)];
+        for (index, (rate, start)) in voices.into_iter().enumerate() {
+            let base = io::Reg::Voice0Volume as u32 + 16 * index as u32;
+            psx.write::<u16, true>(Address(base + 4), rate).unwrap();
+            psx.write::<u16, true>(Address(base + 6), (start / 8) as u16)
+                .unwrap();
+        }
+
+        testing::write::<u32>(&mut psx, io::Reg::VoiceChannelFmMode, 0b100);
+        testing::write::<u32>(&mut psx, io::Reg::VoiceKeyOn, 0b111);
+
+        // count the sign changes of the output of voices 0 and 2, twice per period
+        let mut changes = [0u32; 2];
+        let mut previous = [true; 2];
+        for _ in 0..TICKS {
+            let outputs = tick_voices(&mut psx);
+            for (i, output) in [outputs[0], outputs[2]].into_iter().enumerate() {
+                let positive = output >= 0;
+                if positive != previous[i] {
+                    changes[i] += 1;
+                }
+
+                previous[i] = positive;
+            }
+        }
+
+        // the modulated voice steps 0x1000 * (0x7000 + 0x8000) / 0x8000 = 0x1E00 per tick, and
+        // each half period is 14 samples, i.e. 14 * 0x1000 steps
+        let expected = [
+            TICKS * 0x0800 / (14 * 0x1000),
+            TICKS * 0x1E00 / (14 * 0x1000),
+        ];
+        for (got, expected) in changes.into_iter().zip(expected) {
+            assert!(
+                got.abs_diff(expected) <= 1,
+                "{changes:?} sign changes, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn voices_in_noise_mode_output_the_noise_generator() {
+        // (noise shift, noise step, the first outputs of the generator from power on): the
+        // fastest clock, once per sample, and a clock every 4 samples
+        let cases: [(u16, u16, [u16; 16]); 2] = [
+            (
+                15,
+                3,
+                [
+                    0x0001, 0x0003, 0x0007, 0x000F, 0x001F, 0x003F, 0x007F, 0x00FF, 0x01FF, 0x03FF,
+                    0x07FF, 0x0FFE, 0x1FFD, 0x3FFA, 0x7FF4, 0xFFE8,
+                ],
+            ),
+            (
+                13,
+                0,
+                [
+                    0x0001, 0x0001, 0x0001, 0x0001, 0x0003, 0x0003, 0x0003, 0x0003, 0x0007, 0x0007,
+                    0x0007, 0x0007, 0x000F, 0x000F, 0x000F, 0x000F,
+                ],
+            ),
+        ];
+
+        for (shift, step, expected) in cases {
+            let mut psx = testing::psx();
+            let control = 0x8000 | (shift << 10) | (step << 8);
+            testing::write::<u16>(&mut psx, io::Reg::SpuControl, control);
+            psx.write::<u16, true>(Address(io::Reg::Voice0Volume as u32 + 4), 0x1000)
+                .unwrap();
+            testing::write::<u32>(&mut psx, io::Reg::VoiceChannelNoiseMode, 0b1);
+            testing::write::<u32>(&mut psx, io::Reg::VoiceKeyOn, 0b1);
+
+            let outputs = expected.map(|_| tick_voices(&mut psx)[0] as u16);
+            assert_eq!(outputs, expected, "shift {shift}, step {step}");
+        }
+    }
+}
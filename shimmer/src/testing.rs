@@ -0,0 +1,148 @@
+//! Helpers shared by the unit tests of this crate. They run without a BIOS: an empty one is
+//! mapped, and the CPU is kept spinning in a loop in RAM.
+
+use crate::{
+    Config, Emulator, EmulatorBuilder, PSX,
+    gpu::interface::{Command, CopyToVram, Primitive, Renderer},
+};
+use shimmer_core::{
+    cpu::Reg,
+    mem::{self, Address, Memory, io},
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tinylog::{Logger, logger::LoggerFamily};
+
+/// Where [`idle`] places the loop the CPU spins in.
+pub const IDLE_LOOP: Address = Address(0x8000_1000);
+
+/// Returns a logger which drops everything logged to it.
+pub fn logger() -> Logger {
+    LoggerFamily::builder()
+        .build()
+        .logger("psx", tinylog::Level::Error)
+}
+
+/// Returns a [`PSX`] at power on, with an empty BIOS.
+pub fn psx() -> PSX {
+    PSX::new(Memory::with_bios(Vec::new()).unwrap(), logger())
+}
+
+/// Returns the default configuration, with an empty BIOS.
+pub fn config() -> Config {
+    builder().into_config().unwrap()
+}
+
+/// Returns a builder for an emulator with an empty BIOS.
+pub fn builder() -> EmulatorBuilder {
+    Emulator::builder().bios(Vec::new()).logger(logger())
+}
+
+/// Makes the CPU of `emulator` spin at [`IDLE_LOOP`], so that it can run without a BIOS.
+pub fn idle(emulator: &mut Emulator) {
+    // j IDLE_LOOP; nop
+    let code = [0x0800_0000 | ((IDLE_LOOP.value() >> 2) & 0x03FF_FFFF), 0];
+    emulator.load_raw(IDLE_LOOP, &code.map(u32::to_le_bytes).concat());
+    emulator.set_pc(IDLE_LOOP);
+}
+
+/// Builds an emulator from `builder` with its CPU spinning at [`IDLE_LOOP`].
+pub fn emulator(builder: EmulatorBuilder) -> Emulator {
+    let mut emulator = builder.build().unwrap();
+    idle(&mut emulator);
+    emulator
+}
+
+/// Calls B(3Dh) putchar with every character of `text`, returning to [`IDLE_LOOP`].
+pub fn putchar(emulator: &mut Emulator, text: &str) {
+    for char in text.chars() {
+        let regs = &mut emulator.psx.cpu.regs;
+        regs.write(Reg::A0, u32::from(char));
+        regs.write(Reg::T1, 0x3D);
+        regs.write(Reg::RA, IDLE_LOOP.value());
+        emulator.set_pc(Address(0xB0));
+        emulator.step_instructions(1);
+    }
+}
+
+/// Returns an empty directory in the temporary directory of the host, unique to `name` and this
+/// process, for tests which need files.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("shimmer-{}-{name}", std::process::id()));
+    _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Returns the path of the test fixture `name`, relative to `tests/fixtures`.
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Writes `value` to the IO register `reg`, without logging.
+pub fn write<P>(psx: &mut PSX, reg: io::Reg, value: P)
+where
+    P: mem::Primitive,
+{
+    psx.write::<P, true>(Address(reg as u32), value).unwrap();
+}
+
+/// Reads the IO register `reg`, without logging.
+pub fn read<P>(psx: &mut PSX, reg: io::Reg) -> P
+where
+    P: mem::Primitive,
+{
+    psx.read::<P, true>(Address(reg as u32)).unwrap()
+}
+
+/// Something a [`Recorder`] was asked to do.
+#[derive(Debug, Clone)]
+pub enum Recorded {
+    Draw(Primitive),
+    CopyToVram(CopyToVram),
+}
+
+/// A renderer which records the primitives it's asked to draw and the copies to VRAM, in order.
+/// Readbacks are responded to with zeroes.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder(pub Arc<Mutex<Vec<Recorded>>>);
+
+impl Recorder {
+    /// Takes what has been recorded so far.
+    pub fn take(&self) -> Vec<Recorded> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+
+    /// Takes the primitives drawn so far, dropping everything else recorded.
+    pub fn take_primitives(&self) -> Vec<Primitive> {
+        self.take()
+            .into_iter()
+            .filter_map(|recorded| match recorded {
+                Recorded::Draw(primitive) => Some(primitive),
+                Recorded::CopyToVram(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl Renderer for Recorder {
+    fn exec(&mut self, command: Command) {
+        match command {
+            Command::Draw { primitive } => {
+                self.0.lock().unwrap().push(Recorded::Draw(primitive));
+            }
+            Command::CopyToVram(copy) => self.0.lock().unwrap().push(Recorded::CopyToVram(copy)),
+            Command::CopyFromVram { request, response } => {
+                let len = usize::from(request.dimensions.width.value())
+                    * usize::from(request.dimensions.height.value())
+                    * 2;
+                response.respond(vec![0; len]);
+            }
+            _ => (),
+        }
+    }
+}
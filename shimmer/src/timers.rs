@@ -1,15 +1,136 @@
-use shimmer_core::interrupts::Interrupt;
+//! Timers are counted lazily: their values are only brought up to date when they're accessed,
+//! and each timer has exactly one event scheduled, at its next point of interest. Whenever the
+//! state of a timer changes, its pending event is cancelled and a new one is scheduled.
+
+use shimmer_core::{interrupts::Interrupt, timers::TimerMode};
 use tinylog::Logger;
 
 use crate::{PSX, scheduler};
 
+/// A timer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Event {
-    Setup,
+pub enum Id {
     Timer1,
     Timer2,
 }
 
+/// The point of interest a timer event was scheduled for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cause {
+    /// The timer reaches its target.
+    TargetReached,
+    /// The timer reaches 0xFFFF.
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    pub timer: Id,
+    pub cause: Cause,
+}
+
+/// The counting state of a timer.
+struct Counter<'a> {
+    /// Whether the timer is currently counting, according to its synchronization mode.
+    running: bool,
+    value: &'a mut u16,
+    target: u16,
+    mode: &'a mut TimerMode,
+    synced_at: &'a mut u64,
+}
+
+impl Id {
+    fn interrupt(self) -> Interrupt {
+        match self {
+            Self::Timer1 => Interrupt::Timer1,
+            Self::Timer2 => Interrupt::Timer2,
+        }
+    }
+
+    fn counter(self, psx: &mut PSX) -> Counter<'_> {
+        match self {
+            Self::Timer1 => {
+                let timer = &mut psx.timers.timer1;
+                Counter {
+                    running: timer.should_tick(psx.gpu.in_vblank),
+                    value: &mut timer.value,
+                    target: timer.target,
+                    mode: &mut timer.mode,
+                    synced_at: &mut timer.synced_at,
+                }
+            }
+            Self::Timer2 => {
+                let timer = &mut psx.timers.timer2;
+                Counter {
+                    running: timer.should_tick(),
+                    value: &mut timer.value,
+                    target: timer.target,
+                    mode: &mut timer.mode,
+                    synced_at: &mut timer.synced_at,
+                }
+            }
+        }
+    }
+}
+
+/// Brings the value of `timer` up to date. Must be called before accessing the timer and before
+/// changing anything that affects whether it's running.
+pub(crate) fn sync(psx: &mut PSX, timer: Id) {
+    let now = psx.scheduler.elapsed();
+    let counter = timer.counter(psx);
+
+    if counter.running {
+        let cycles_per_tick = counter.mode.cycles_per_tick();
+        let ticks = (now - *counter.synced_at) / cycles_per_tick;
+
+        *counter.value = counter.mode.advance(*counter.value, counter.target, ticks);
+        *counter.synced_at += ticks * cycles_per_tick;
+    } else {
+        *counter.synced_at = now;
+    }
+}
+
+/// Cancels the pending event of `timer` and schedules one for its next point of interest, if
+/// it's running. Must be called after [`sync`] whenever the state of the timer changes.
+pub(crate) fn reschedule(psx: &mut PSX, timer: Id) {
+    psx.scheduler
+        .cancel_if(|event| matches!(event, scheduler::Event::Timer(e) if e.timer == timer));
+
+    let now = psx.scheduler.elapsed();
+    let counter = timer.counter(psx);
+    if !counter.running {
+        return;
+    }
+
+    let value = *counter.value;
+    let to_target = counter
+        .mode
+        .ticks_until(value, counter.target, counter.target);
+    let to_max = counter.mode.ticks_until(value, counter.target, u16::MAX);
+
+    // if both happen at once, the target event also takes care of the overflow
+    let (ticks, cause) = match (to_target, to_max) {
+        (Some(target), Some(max)) if max < target => (max, Cause::Overflow),
+        (Some(target), _) => (target, Cause::TargetReached),
+        (None, Some(max)) => (max, Cause::Overflow),
+        (None, None) => return,
+    };
+
+    let deadline = *counter.synced_at + ticks * counter.mode.cycles_per_tick();
+    psx.scheduler.schedule(
+        scheduler::Event::Timer(Event { timer, cause }),
+        deadline.saturating_sub(now),
+    );
+}
+
+/// Schedules the first event of every timer.
+pub(crate) fn start(psx: &mut PSX) {
+    for timer in [Id::Timer1, Id::Timer2] {
+        sync(psx, timer);
+        reschedule(psx, timer);
+    }
+}
+
 #[derive(Debug)]
 pub struct Timers {
     _logger: Logger,
@@ -20,97 +141,144 @@ impl Timers {
         Self { _logger: logger }
     }
 
-    fn tick_timer1(&mut self, psx: &mut PSX) {
-        let timer1 = &mut psx.timers.timer1;
-        if !timer1.should_tick() {
-            psx.scheduler.schedule(
-                scheduler::Event::Timer(Event::Timer2),
-                timer1.cycles_per_tick(),
-            );
-            return;
-        }
+    #[expect(clippy::unused_self, reason = "consistency")]
+    pub fn update(&mut self, psx: &mut PSX, event: Event) {
+        sync(psx, event.timer);
 
-        let old_value = timer1.value;
-        timer1.value = timer1.value.wrapping_add(1);
+        let counter = event.timer.counter(psx);
+        let (reached_target, reached_max) = match event.cause {
+            Cause::TargetReached => (true, counter.target == u16::MAX),
+            Cause::Overflow => (false, true),
+        };
 
-        if timer1.value == 0xFFFF {
-            timer1.mode.set_reached_max(true);
-            if timer1.mode.irq_at_max() && timer1.can_raise_irq() {
-                timer1.update_no_irq();
-                psx.interrupts.status.request(Interrupt::Timer2);
-            }
+        if reached_target {
+            counter.mode.set_reached_target(true);
         }
 
-        if timer1.value == timer1.target {
-            timer1.mode.set_reached_target(true);
-            if timer1.mode.irq_when_at_target() && timer1.can_raise_irq() {
-                timer1.update_no_irq();
-                psx.interrupts.status.request(Interrupt::Timer2);
-            }
-        } else if old_value == timer1.target && timer1.mode.reset_at_target() {
-            timer1.value = 0;
+        if reached_max {
+            counter.mode.set_reached_max(true);
         }
 
-        psx.scheduler.schedule(
-            scheduler::Event::Timer(Event::Timer2),
-            timer1.cycles_per_tick(),
-        );
-    }
-
-    fn tick_timer2(&mut self, psx: &mut PSX) {
-        let timer2 = &mut psx.timers.timer2;
-        if !timer2.should_tick() {
-            psx.scheduler.schedule(
-                scheduler::Event::Timer(Event::Timer2),
-                timer2.cycles_per_tick(),
-            );
-            return;
+        let irq = (reached_target && counter.mode.irq_when_at_target())
+            || (reached_max && counter.mode.irq_at_max());
+        let raise = irq && counter.mode.can_raise_irq();
+        if raise {
+            counter.mode.update_no_irq();
+            psx.interrupts.status.request(event.timer.interrupt());
         }
 
-        let old_value = timer2.value;
-        timer2.value = timer2.value.wrapping_add(1);
+        reschedule(psx, event.timer);
+    }
+}
 
-        if timer2.value == 0xFFFF {
-            timer2.mode.set_reached_max(true);
-            if timer2.mode.irq_at_max() && timer2.can_raise_irq() {
-                timer2.update_no_irq();
-                psx.interrupts.status.request(Interrupt::Timer2);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use shimmer_core::mem::io;
+
+    /// Processes the events due in the next `cycles` cycles, dispatching timer events to
+    /// `timers`. Returns when each timer event fired, relative to the start, and its cause.
+    fn run(psx: &mut PSX, timers: &mut Timers, cycles: u64) -> Vec<(u64, Cause)> {
+        let start = psx.scheduler.elapsed();
+        let mut fired = Vec::new();
+        while let Some(after) = psx.scheduler.until_next() {
+            if psx.scheduler.elapsed() + after > start + cycles {
+                break;
             }
-        }
 
-        if timer2.value == timer2.target {
-            timer2.mode.set_reached_target(true);
-            if timer2.mode.irq_when_at_target() && timer2.can_raise_irq() {
-                timer2.update_no_irq();
-                psx.interrupts.status.request(Interrupt::Timer2);
+            psx.scheduler.advance(after);
+            while let Some(event) = psx.scheduler.pop() {
+                if let scheduler::Event::Timer(event) = event {
+                    assert_eq!(event.timer, Id::Timer2);
+                    fired.push((psx.scheduler.elapsed() - start, event.cause));
+                    timers.update(psx, event);
+                }
             }
-        } else if old_value == timer2.target && timer2.mode.reset_at_target() {
-            timer2.value = 0;
         }
 
-        psx.scheduler.schedule(
-            scheduler::Event::Timer(Event::Timer2),
-            timer2.cycles_per_tick(),
+        psx.scheduler
+            .advance(start + cycles - psx.scheduler.elapsed());
+        fired
+    }
+
+    /// Returns a PSX with nothing scheduled, and timer 2 counting the system clock with the given
+    /// target and mode.
+    fn timer2(target: u16, mode: u16) -> (PSX, Timers) {
+        let mut psx = testing::psx();
+        psx.scheduler.cancel_if(|_| true);
+        testing::write::<u16>(&mut psx, io::Reg::Timer2Target, target);
+        testing::write::<u16>(&mut psx, io::Reg::Timer2Mode, mode);
+
+        (psx, Timers::new(testing::logger()))
+    }
+
+    fn pending_timer_events(psx: &PSX) -> usize {
+        psx.scheduler
+            .pending()
+            .filter(|(_, event)| matches!(event, scheduler::Event::Timer(_)))
+            .count()
+    }
+
+    // reset at target, IRQ at target, repeat
+    const RESET_AT_TARGET: u16 = 0x0058;
+    // IRQ at target and at 0xFFFF, repeat
+    const FREE_RUNNING: u16 = 0x0070;
+
+    #[test]
+    fn target_fires_once_per_period() {
+        let (mut psx, mut timers) = timer2(1000, RESET_AT_TARGET);
+
+        // the timer stays at its target for a tick, and ticks every other cycle
+        let period = 2 * 1001;
+        let fired = run(&mut psx, &mut timers, 10 * period + period / 2);
+
+        let expected: Vec<_> = (1..=10)
+            .map(|i| (i * period - 2, Cause::TargetReached))
+            .collect();
+        assert_eq!(fired, expected);
+        assert_eq!(pending_timer_events(&psx), 1);
+        assert_eq!(psx.interrupts.status.requested(), Some(Interrupt::Timer2));
+    }
+
+    #[test]
+    fn target_and_overflow_each_fire_once() {
+        let (mut psx, mut timers) = timer2(1000, FREE_RUNNING);
+        let fired = run(&mut psx, &mut timers, 270_000);
+
+        assert_eq!(
+            fired,
+            [
+                (2 * 1000, Cause::TargetReached),
+                (2 * 65535, Cause::Overflow),
+                (2 * (65536 + 1000), Cause::TargetReached),
+                (2 * (65536 + 65535), Cause::Overflow),
+                (2 * (2 * 65536 + 1000), Cause::TargetReached),
+            ]
         );
     }
 
-    pub fn update(&mut self, psx: &mut PSX, event: Event) {
-        match event {
-            Event::Setup => {
-                psx.timers.timer1.mode.set_no_irq(true);
-                psx.timers.timer2.mode.set_no_irq(true);
-
-                psx.scheduler.schedule(
-                    scheduler::Event::Timer(Event::Timer1),
-                    psx.timers.timer1.cycles_per_tick(),
-                );
-                psx.scheduler.schedule(
-                    scheduler::Event::Timer(Event::Timer2),
-                    psx.timers.timer2.cycles_per_tick(),
-                );
-            }
-            Event::Timer1 => self.tick_timer1(psx),
-            Event::Timer2 => self.tick_timer2(psx),
+    #[test]
+    fn rapid_mode_rewrites_leave_no_stale_events() {
+        let (mut psx, mut timers) = timer2(100, RESET_AT_TARGET);
+
+        for target in 100..200 {
+            testing::write::<u16>(&mut psx, io::Reg::Timer2Target, target);
+            testing::write::<u16>(&mut psx, io::Reg::Timer2Mode, RESET_AT_TARGET);
+            assert_eq!(pending_timer_events(&psx), 1);
         }
+
+        // before it reaches the target, it's moved further away
+        assert!(run(&mut psx, &mut timers, 2 * 150).is_empty());
+        testing::write::<u16>(&mut psx, io::Reg::Timer2Target, 5000);
+        assert_eq!(pending_timer_events(&psx), 1);
+
+        // so only the new target fires, 150 ticks after the rewrite
+        let fired = run(&mut psx, &mut timers, 2 * 5000);
+        assert_eq!(fired, [(2 * (5000 - 150), Cause::TargetReached)]);
+
+        // stopping the timer through its mode cancels its event
+        testing::write::<u16>(&mut psx, io::Reg::Timer2Mode, 0x0001 | (0b11 << 1));
+        assert_eq!(pending_timer_events(&psx), 0);
     }
 }
@@ -0,0 +1,127 @@
+//! Tracking of unimplemented commands hit during emulation, which can either be logged as they
+//! happen or tallied and logged as a summary once per frame, so that games which spam them don't
+//! drown the rest of the log.
+
+use crate::PSX;
+use std::collections::BTreeMap;
+use tinylog::error;
+
+/// How unimplemented commands are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Reporting {
+    /// Every occurrence is logged as it happens.
+    #[default]
+    Each,
+    /// Occurrences are tallied and logged as a single summary at every VBlank.
+    Summary,
+}
+
+/// Tally of the unimplemented commands hit since the last summary.
+#[derive(Debug, Default)]
+pub struct Unimplemented {
+    reporting: Reporting,
+    counts: BTreeMap<String, u64>,
+}
+
+impl Unimplemented {
+    pub fn new(reporting: Reporting) -> Self {
+        Self {
+            reporting,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn reporting(&self) -> Reporting {
+        self.reporting
+    }
+
+    /// Records an occurrence of the unimplemented command `kind`. Returns whether it should be
+    /// logged right away, i.e. whether it's not being summarized.
+    pub fn record(&mut self, kind: String) -> bool {
+        match self.reporting {
+            Reporting::Each => true,
+            Reporting::Summary => {
+                *self.counts.entry(kind).or_default() += 1;
+                false
+            }
+        }
+    }
+
+    /// The amount of occurrences of each kind of unimplemented command since the last summary.
+    pub fn counts(&self) -> &BTreeMap<String, u64> {
+        &self.counts
+    }
+}
+
+/// Logs the summary of the unimplemented commands hit since the last one, if any, and starts a
+/// new one.
+pub fn log_summary(psx: &mut PSX) {
+    if psx.unimplemented.counts.is_empty() {
+        return;
+    }
+
+    let counts = std::mem::take(&mut psx.unimplemented.counts);
+    let summary = counts
+        .iter()
+        .map(|(kind, count)| format!("{kind} x{count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    error!(psx.loggers.root, "unimplemented commands: {summary}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use shimmer_core::{CYCLES_MILLIS, mem::io};
+
+    #[test]
+    fn each_occurrence_is_logged_by_default() {
+        let mut unimplemented = Unimplemented::default();
+        assert!(unimplemented.record("GP0(03h)".to_owned()));
+        assert!(unimplemented.counts().is_empty());
+    }
+
+    #[test]
+    fn summary_tallies_commands_until_vblank() {
+        let builder = testing::builder().unimplemented_reporting(Reporting::Summary);
+        let mut emulator = testing::emulator(builder);
+
+        // sent well before the first VBlank, which logs the summary and starts a new one
+        let commands = [
+            // GP0(03h), unknown
+            (io::Reg::Gp0, 0x0300_0000),
+            (io::Reg::Gp0, 0x0300_0000),
+            // GP0(1Fh), interrupt request
+            (io::Reg::Gp0, 0x1F00_0000),
+            // GP1(0Ah), unknown
+            (io::Reg::Gp1, 0x0A00_0000),
+        ];
+
+        for (reg, command) in commands {
+            testing::write::<u32>(&mut emulator.psx, reg, command);
+            emulator.cycle_for(CYCLES_MILLIS);
+        }
+
+        let tallied = emulator
+            .psx
+            .unimplemented
+            .counts()
+            .iter()
+            .map(|(kind, &count)| (kind.as_str(), count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tallied,
+            [
+                ("GP0 InterruptRequest", 1),
+                ("GP0(03h)", 2),
+                ("GP1(0Ah)", 1)
+            ]
+        );
+
+        let frame = emulator.psx.gpu.timing().cycles_per_frame();
+        emulator.cycle_for(frame);
+        assert!(emulator.psx.unimplemented.counts().is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+//! Checks of emulator features which only show up while a real BIOS runs, using the first BIOS
+//! image found in the directory given by `SHIMMER_BIOS_DIR`.
+//!
+//! BIOS images aren't bundled, so these do nothing unless the variable is set. The same goes for
+//! the firmware of a cheat cartridge, given by `SHIMMER_EXPANSION_ROM`.
+
+use shimmer::{
+    Condition, Emulator, EmulatorBuilder, SHELL_ENTRY, StopReason,
+    core::{
+        CYCLES_MILLIS, CYCLES_SECOND,
+        mem::{Address, Region, io},
+    },
+};
+use tinylog::logger::LoggerFamily;
+
+/// The ID an expansion ROM has after each of its entry points for the BIOS to call them.
+const EXPANSION1_ID: &[u8] = b"Licensed by Sony Computer Entertainment Inc.";
+
+/// Returns the first BIOS image in `SHIMMER_BIOS_DIR`, if the variable is set.
+fn bios() -> Option<Vec<u8>> {
+    let Some(dir) = std::env::var_os("SHIMMER_BIOS_DIR") else {
+        eprintln!("SHIMMER_BIOS_DIR is not set, skipping");
+        return None;
+    };
+
+    let mut images = std::fs::read_dir(&dir)
+        .expect("BIOS directory should be readable")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("bin"))
+        })
+        .collect::<Vec<_>>();
+    images.sort();
+
+    let image = images.first().expect("BIOS directory should have an image");
+    Some(std::fs::read(image).unwrap())
+}
+
+fn builder(bios: Vec<u8>) -> EmulatorBuilder {
+    let logger = LoggerFamily::builder()
+        .build()
+        .logger("psx", tinylog::Level::Error);
+
+    Emulator::builder().bios(bios).logger(logger)
+}
+
+#[test]
+fn bios_calls_the_pre_boot_entry_of_an_expansion_rom() {
+    // the BIOS calls it before initializing the kernel
+    const MAX_CYCLES: u64 = 100 * CYCLES_MILLIS;
+
+    let Some(bios) = bios() else {
+        return;
+    };
+
+    // post-boot entry at 0x00 and pre-boot entry at 0x80, each followed by its ID:
+    // jr $ra; nop
+    let mut rom = vec![0; 0x100];
+    for entry in [0x00, 0x80] {
+        rom[entry..entry + 4].copy_from_slice(&0x03E0_0008u32.to_le_bytes());
+        rom[entry + 4..entry + 4 + EXPANSION1_ID.len()].copy_from_slice(EXPANSION1_ID);
+    }
+
+    let mut emulator = builder(bios).expansion1(rom).build().unwrap();
+
+    // the entry can be called through any of the segments
+    let start = Region::Expansion1.start().value();
+    let entry = [0x0000_0000, 0x8000_0000, 0xA000_0000]
+        .map(|segment| Condition::PcEquals(Address(segment | (start + 0x80))));
+    let result = emulator.run_until(MAX_CYCLES, &entry, |_| false);
+    assert!(
+        matches!(result.reason, StopReason::Condition(_)),
+        "{:?}",
+        result.reason
+    );
+}
+
+#[test]
+fn bios_runs_the_firmware_of_a_cheat_cartridge() {
+    const MAX_CYCLES: u64 = 10 * CYCLES_SECOND;
+
+    let Some(bios) = bios() else {
+        return;
+    };
+
+    let Some(path) = std::env::var_os("SHIMMER_EXPANSION_ROM") else {
+        eprintln!("SHIMMER_EXPANSION_ROM is not set, skipping");
+        return;
+    };
+    let rom = std::fs::read(path).expect("expansion ROM should be readable");
+
+    let mut emulator = builder(bios).expansion1(rom).build().unwrap();
+
+    // the firmware takes over from its pre-boot or post-boot entry, depending on the cartridge
+    let start = Region::Expansion1.start().value();
+    let entries = [0x00, 0x80]
+        .into_iter()
+        .flat_map(|entry| {
+            [0x0000_0000, 0x8000_0000, 0xA000_0000]
+                .map(|segment| Condition::PcEquals(Address(segment | (start + entry))))
+        })
+        .collect::<Vec<_>>();
+    let result = emulator.run_until(MAX_CYCLES, &entries, |_| false);
+    assert!(
+        matches!(result.reason, StopReason::Condition(_)),
+        "{:?}",
+        result.reason
+    );
+}
+
+#[test]
+fn skipping_post_delays_reaches_the_shell_sooner_in_the_same_state() {
+    const MAX_CYCLES: u64 = 10 * CYCLES_SECOND;
+
+    let Some(bios) = bios() else {
+        return;
+    };
+
+    let boot = |skip| {
+        let mut emulator = builder(bios.clone())
+            .skip_post_delays(skip)
+            .build()
+            .unwrap();
+        let result = emulator.run_until(MAX_CYCLES, &[Condition::PcEquals(SHELL_ENTRY)], |_| false);
+        assert_eq!(result.reason, StopReason::Condition(0), "skip: {skip}");
+
+        let stdout = emulator.psx().memory.kernel_stdout.clone();
+        (result.cycles, emulator.post_code(), stdout)
+    };
+
+    let (normal_cycles, normal_post, normal_stdout) = boot(false);
+    let (skipped_cycles, skipped_post, skipped_stdout) = boot(true);
+    assert!(
+        skipped_cycles < normal_cycles,
+        "{skipped_cycles} cycles with delays skipped, {normal_cycles} without"
+    );
+    assert_eq!(skipped_post, normal_post);
+    assert_eq!(skipped_stdout, normal_stdout);
+}
+
+#[test]
+fn reset_to_bios_matches_the_state_the_bios_leaves() {
+    const MAX_CYCLES: u64 = 10 * CYCLES_SECOND;
+
+    let Some(bios) = bios() else {
+        return;
+    };
+
+    // the reference is a real boot, with its delays skipped to keep it short
+    let mut booted = builder(bios.clone())
+        .skip_post_delays(true)
+        .build()
+        .unwrap();
+    let result = booted.run_until(MAX_CYCLES, &[Condition::PcEquals(SHELL_ENTRY)], |_| false);
+    assert_eq!(result.reason, StopReason::Condition(0));
+
+    let mut reset = builder(bios).build().unwrap();
+    reset.psx_mut().reset_to_bios();
+
+    assert_eq!(reset.psx().cpu.regs.read_pc(), SHELL_ENTRY.value());
+    assert_eq!(
+        reset.psx().cpu.cache_control,
+        booted.psx().cpu.cache_control
+    );
+    assert_eq!(
+        reset
+            .psx()
+            .cop0
+            .regs
+            .system_status()
+            .boot_exception_vectors_in_kseg1(),
+        booted
+            .psx()
+            .cop0
+            .regs
+            .system_status()
+            .boot_exception_vectors_in_kseg1()
+    );
+
+    let regs = [
+        io::Reg::Expansion1Base,
+        io::Reg::Expansion2Base,
+        io::Reg::Expansion1Delay,
+        io::Reg::Expansion3Delay,
+        io::Reg::BiosDelay,
+        io::Reg::SpuDelay,
+        io::Reg::CdromDelay,
+        io::Reg::Expansion2Delay,
+        io::Reg::CommonDelay,
+        io::Reg::RamSize,
+    ];
+    for reg in regs {
+        let addr = Address(reg as u32);
+        assert_eq!(
+            reset.psx_mut().read::<u32, true>(addr).unwrap(),
+            booted.psx_mut().read::<u32, true>(addr).unwrap(),
+            "{reg:?}"
+        );
+    }
+}
+
+#[test]
+fn bios_runs_are_deterministic_even_when_stopped_at_the_midpoint() {
+    // enough for the BIOS to go through its hardware setup and start its first transfers
+    const CYCLES: u64 = 200 * CYCLES_MILLIS;
+
+    let Some(bios) = bios() else {
+        return;
+    };
+
+    // the end state of a run, stopping at each of `stops` on the way to the end
+    let run = |stops: &[u64]| {
+        let mut emulator = builder(bios.clone()).build().unwrap();
+        for &stop in stops.iter().chain(&[CYCLES]) {
+            let remaining = stop - emulator.elapsed_cycles();
+            emulator.run_until(remaining, &[], |_| false);
+        }
+
+        let elapsed = emulator.elapsed_cycles();
+        let psx = emulator.psx();
+        (elapsed, psx.cpu.regs.read_pc(), psx.memory.ram.clone())
+    };
+
+    let reference = run(&[]);
+    assert!(reference == run(&[]), "two runs ended in different states");
+    assert!(
+        reference == run(&[CYCLES / 2]),
+        "stopping at the midpoint changed the end state"
+    );
+}
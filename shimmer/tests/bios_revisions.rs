@@ -0,0 +1,77 @@
+//! Boots every BIOS revision found in the directory given by `SHIMMER_BIOS_DIR` up to the shell,
+//! comparing the resulting state against the golden fingerprint stored next to each image.
+//!
+//! BIOS images aren't bundled, so this does nothing unless the variable is set. The fingerprint of
+//! `NAME.bin` is stored in `NAME.bin.golden`, and goldens are recorded intentionally with the
+//! `boot-check --update` subcommand of the frontend, using its default settings as this test does.
+
+use shimmer::{Config, Emulator, core::CYCLES_SECOND};
+use std::path::{Path, PathBuf};
+use tinylog::logger::LoggerFamily;
+
+/// Maximum amount of cycles to run each BIOS for, the default of `boot-check`.
+const MAX_CYCLES: u64 = 10 * CYCLES_SECOND;
+
+fn config(bios: Vec<u8>) -> Config {
+    let logger = LoggerFamily::builder()
+        .build()
+        .logger("psx", tinylog::Level::Error);
+
+    Emulator::builder()
+        .bios(bios)
+        .logger(logger)
+        .into_config()
+        .unwrap()
+}
+
+fn images(dir: &Path) -> Vec<PathBuf> {
+    let mut images: Vec<PathBuf> = std::fs::read_dir(dir)
+        .expect("BIOS directory should be readable")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("bin"))
+        })
+        .collect();
+
+    images.sort();
+    images
+}
+
+#[test]
+fn bios_revisions_boot_to_the_shell() {
+    let Some(dir) = std::env::var_os("SHIMMER_BIOS_DIR") else {
+        eprintln!("SHIMMER_BIOS_DIR is not set, skipping");
+        return;
+    };
+
+    let dir = Path::new(&dir);
+    let images = images(dir);
+    assert!(!images.is_empty(), "no BIOS images in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for image in images {
+        let name = image.display();
+        let bios = std::fs::read(&image).unwrap();
+        let fingerprint = Emulator::boot_fingerprint(&config(bios), MAX_CYCLES).unwrap();
+
+        if !fingerprint.reached_shell {
+            failures.push(format!("{name} didn't reach the shell:\n{fingerprint}"));
+            continue;
+        }
+
+        let mut golden = image.clone().into_os_string();
+        golden.push(".golden");
+        match std::fs::read_to_string(&golden) {
+            Ok(golden) if golden == fingerprint.to_string() => (),
+            Ok(golden) => failures.push(format!(
+                "{name} differs from its golden fingerprint\nexpected:\n{golden}\ngot:\n{fingerprint}"
+            )),
+            Err(err) => failures.push(format!(
+                "{name} has no golden fingerprint ({err}), record it with boot-check --update"
+            )),
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
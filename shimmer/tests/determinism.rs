@@ -0,0 +1,86 @@
+//! Checks that the emulation crates don't use host randomness, wall-clock time or hash maps, whose
+//! iteration order is random, except for the uses justified in `determinism.allow`.
+
+use std::path::{Path, PathBuf};
+
+/// Constructs which can make emulation depend on the host.
+const BANNED: &[&str] = &[
+    "HashMap",
+    "HashSet",
+    "RandomState",
+    "Instant",
+    "SystemTime",
+    "std::random",
+];
+
+/// Directories searched for banned constructs, relative to the workspace root.
+const SOURCES: &[&str] = &["shimmer/src", "shimmer_core/src"];
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_owned()
+}
+
+/// Returns every Rust source file under `dir`, recursively.
+fn sources(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(sources(&path));
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+
+    files
+}
+
+#[test]
+fn emulation_crates_only_use_allowed_host_dependent_constructs() {
+    let root = workspace_root();
+
+    // `FILE CONSTRUCT REASON`, ignoring comments
+    let allow = std::fs::read_to_string(root.join("determinism.allow")).unwrap();
+    let allowed: Vec<(&str, &str)> = allow
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            (fields.next().unwrap(), fields.next().unwrap())
+        })
+        .collect();
+
+    let mut found = Vec::new();
+    for dir in SOURCES {
+        for path in sources(&root.join(dir)) {
+            let relative = path.strip_prefix(&root).unwrap();
+            let file = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            let text = std::fs::read_to_string(&path).unwrap();
+            for (index, line) in text.lines().enumerate() {
+                for &construct in BANNED {
+                    if line.contains(construct) && !allowed.contains(&(file.as_str(), construct)) {
+                        found.push(format!(
+                            "{file}:{}: {construct}: {}",
+                            index + 1,
+                            line.trim()
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    assert!(
+        found.is_empty(),
+        "host dependent constructs without an entry in determinism.allow:\n{}",
+        found.join("\n")
+    );
+}
@@ -348,6 +348,8 @@ pub struct Cdrom {
 
     pub location: Sector,
     pub lock_sector_data: bool,
+    /// Whether CD audio output is muted, through the Mute and Demute commands.
+    pub muted: bool,
 
     pub write_queue: VecDeque<RegWrite>,
     pub parameter_queue: VecDeque<u8>,
@@ -368,6 +370,7 @@ impl Cdrom {
 
             location: Default::default(),
             lock_sector_data: true,
+            muted: false,
 
             write_queue: Default::default(),
             parameter_queue: Default::default(),
@@ -3,13 +3,15 @@
 pub mod cop0;
 pub mod instr;
 
-use crate::mem;
 use bitos::bitos;
 use strum::{EnumMessage, IntoStaticStr, VariantArray};
 
 /// The frequency of the CPU, in Hz.
 pub const FREQUENCY: u32 = 33_870_000;
 
+/// The address the CPU starts executing at after a reset: the start of the BIOS, in KSEG1.
+pub const RESET_VECTOR: u32 = 0xBFC0_0000;
+
 /// A CPU coprocessor kind.
 #[bitos(2)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
@@ -251,7 +253,7 @@ impl Default for Registers {
             gp: Default::default(),
             hi: Default::default(),
             lo: Default::default(),
-            pc: mem::Region::BIOS.start().value() + mem::Segment::KSEG1.start().value(),
+            pc: RESET_VECTOR,
         }
     }
 }
@@ -122,9 +122,20 @@ impl Cause {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CpuMode {
     #[bits(0)]
-    interrupts_enabled: bool,
+    pub interrupts_enabled: bool,
     #[bits(1)]
-    user_mode: bool,
+    pub user_mode: bool,
+}
+
+impl std::fmt::Display for CpuMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "IE={} KU={}",
+            u8::from(self.interrupts_enabled()),
+            u8::from(self.user_mode())
+        )
+    }
 }
 
 /// Represents the value of the System Status register.
@@ -161,6 +172,10 @@ pub struct SystemStatus {
 impl SystemStatus {
     /// Disables interrupts, goes into kernel mode and pushes the new [`CpuMode`] onto the mode
     /// stack.
+    ///
+    /// The current mode becomes the previous one and the previous mode becomes the old one, while
+    /// the old mode is lost. This is what lets the kernel handle an exception raised by an
+    /// exception handler, as long as nesting doesn't go deeper than two levels.
     pub fn start_exception(&mut self) {
         let mut stack = self.cpu_mode_stack();
         stack.copy_within(0..2, 1);
@@ -168,7 +183,10 @@ impl SystemStatus {
         self.set_cpu_mode_stack(stack);
     }
 
-    /// Pops the current [`CpuMode`] from the state stack.
+    /// Pops the current [`CpuMode`] from the state stack, as done by `RFE`.
+    ///
+    /// The previous mode becomes the current one and the old mode becomes the previous one. The
+    /// old mode itself is left untouched, so it's duplicated rather than cleared.
     pub fn restore_from_exception(&mut self) {
         let mut stack = self.cpu_mode_stack();
         stack.copy_within(1..3, 0);
@@ -182,6 +200,149 @@ impl SystemStatus {
     }
 }
 
+/// A decoded view of the CAUSE register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedCause {
+    /// The most recently recognised exception, or `None` if its code is not a known exception.
+    pub exception: Option<Exception>,
+    /// The interrupt lines which are pending, one bit per line.
+    pub pending_interrupts: u8,
+    /// Whether the exception happened in a branch delay slot.
+    pub branch_delay: bool,
+}
+
+impl std::fmt::Display for DecodedCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.exception {
+            Some(exception) => write!(f, "{exception:?}")?,
+            None => write!(f, "Unknown")?,
+        }
+
+        write!(f, " IP={:08b}", self.pending_interrupts)?;
+        if self.branch_delay {
+            write!(f, " BD")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A decoded view of the System Status register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedStatus {
+    /// The CPU mode stack: current, previous and old modes, in this order.
+    pub mode_stack: [CpuMode; 3],
+    /// The interrupt lines which are enabled, one bit per line.
+    pub interrupt_mask: u8,
+    /// Whether the cache is isolated from memory.
+    pub isolate_cache: bool,
+    /// Whether the exception vectors are in KSEG1 (BEV).
+    pub boot_exception_vectors: bool,
+}
+
+impl std::fmt::Display for DecodedStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [current, previous, old] = self.mode_stack;
+        write!(
+            f,
+            "cur: {current}, prev: {previous}, old: {old} IM={:08b}",
+            self.interrupt_mask
+        )?;
+
+        if self.isolate_cache {
+            write!(f, " IsC")?;
+        }
+
+        if self.boot_exception_vectors {
+            write!(f, " BEV")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Represents the value of the DCIC register. It controls the hardware breakpoints and reports
+/// which of them were hit.
+#[bitos(32)]
+#[derive(Debug, Clone)]
+pub struct Dcic {
+    /// Set when any breakpoint is hit.
+    #[bits(0)]
+    pub any_break: bool,
+    /// Set when the code breakpoint (BPC/BPCM) is hit.
+    #[bits(1)]
+    pub code_break: bool,
+    /// Set when the data breakpoint (BDA/BDAM) is hit.
+    #[bits(2)]
+    pub data_break: bool,
+    /// Set when the data breakpoint is hit by a read.
+    #[bits(3)]
+    pub data_read_break: bool,
+    /// Set when the data breakpoint is hit by a write.
+    #[bits(4)]
+    pub data_write_break: bool,
+    /// Set when the jump breakpoint is hit.
+    #[bits(5)]
+    pub jump_break: bool,
+    #[bits(23)]
+    pub super_master_enable_1: bool,
+    /// Enables the code breakpoint.
+    #[bits(24)]
+    pub code_breakpoint: bool,
+    /// Enables the data breakpoint.
+    #[bits(25)]
+    pub data_breakpoint: bool,
+    /// Whether the data breakpoint triggers on reads.
+    #[bits(26)]
+    pub data_breakpoint_on_read: bool,
+    /// Whether the data breakpoint triggers on writes.
+    #[bits(27)]
+    pub data_breakpoint_on_write: bool,
+    #[bits(28)]
+    pub jump_breakpoint: bool,
+    #[bits(29)]
+    pub jump_master_enable: bool,
+    #[bits(30)]
+    pub master_enable: bool,
+    #[bits(31)]
+    pub super_master_enable_2: bool,
+}
+
+impl Dcic {
+    /// Bits of DCIC which can be written to.
+    pub const WRITE_MASK: u32 = 0xFF80_F03F;
+    /// Bits of DCIC which must all be set for the code and data breakpoints to be armed.
+    const MASTER_ENABLE_MASK: u32 = 0xC080_0000;
+
+    /// Whether the master enable bits are set.
+    #[inline(always)]
+    fn master_enabled(&self) -> bool {
+        self.to_bits() & Self::MASTER_ENABLE_MASK == Self::MASTER_ENABLE_MASK
+    }
+
+    /// Whether the code breakpoint is armed.
+    #[inline(always)]
+    pub fn code_breakpoint_armed(&self) -> bool {
+        self.master_enabled() && self.code_breakpoint()
+    }
+
+    /// Whether the data breakpoint is armed for reads (if `write` is false) or writes (if `write`
+    /// is true).
+    #[inline(always)]
+    pub fn data_breakpoint_armed(&self, write: bool) -> bool {
+        let access = if write {
+            self.data_breakpoint_on_write()
+        } else {
+            self.data_breakpoint_on_read()
+        };
+
+        self.master_enabled() && self.data_breakpoint() && access
+    }
+}
+
+/// The processor ID of the PSX CPU, as reported by PRID.
+pub const PROCESSOR_ID: u32 = 0x0000_0002;
+
 /// The registers of COP0.
 #[derive(Clone)]
 pub struct Registers([u32; 32]);
@@ -210,27 +371,139 @@ impl Default for Registers {
     fn default() -> Self {
         let mut regs: [_; 32] = Default::default();
         regs[Reg::COP0_SR as usize] = 0x1090_0000;
+        regs[Reg::COP0_PRID as usize] = PROCESSOR_ID;
 
         Self(regs)
     }
 }
 
 impl Registers {
+    /// Puts the System Status register into the state the CPU resets to: boot exception vectors,
+    /// kernel mode, interrupts disabled and caches not isolated. Other registers keep their
+    /// values, which are undefined after a reset.
+    pub fn reset_system_status(&mut self) {
+        *self.system_status_mut() = SystemStatus::from_bits(Self::default().read(Reg::COP0_SR))
+            .with_boot_exception_vectors_in_kseg1(true);
+    }
+
+    /// Reads the value of a register. Registers which don't exist always read as zero.
     #[inline(always)]
     pub fn read(&self, reg: Reg) -> u32 {
         self.0[reg as usize]
     }
 
+    /// Writes to a register, respecting which of its bits are writable. Writes to read-only
+    /// registers and to registers which don't exist are ignored.
     #[inline(always)]
     pub fn write(&mut self, reg: Reg, value: u32) {
         match reg {
             Reg::COP0_CAUSE => {
                 self.0[reg as usize] = self.0[reg as usize].with_bits(8, 10, value.bits(8, 10))
             }
-            _ => self.0[reg as usize] = value,
+            Reg::COP0_DCIC => self.0[reg as usize] = value & Dcic::WRITE_MASK,
+            Reg::COP0_BPC | Reg::COP0_BDA | Reg::COP0_BDAM | Reg::COP0_BPCM | Reg::COP0_SR => {
+                self.0[reg as usize] = value
+            }
+            _ => (),
+        }
+    }
+
+    /// Records the address exceptions return to in EPC, which is read-only to software.
+    #[inline(always)]
+    pub fn set_epc(&mut self, addr: u32) {
+        self.0[Reg::COP0_EPC as usize] = addr;
+    }
+
+    /// Records the virtual address which caused an address error in BadVaddr, which is read-only
+    /// to software.
+    #[inline(always)]
+    pub fn set_bad_vaddr(&mut self, addr: u32) {
+        self.0[Reg::COP0_BAD_VADDR as usize] = addr;
+    }
+
+    /// The address of the instruction which caused the last exception. This is `EPC`, unless the
+    /// instruction was in a branch delay slot, in which case it's the instruction after it.
+    ///
+    /// Exception handlers use this to fetch the faulting instruction, e.g. to read the code of a
+    /// `SYSCALL` or `BREAK`.
+    #[inline(always)]
+    pub fn faulting_instruction_addr(&self) -> u32 {
+        let epc = self.read(Reg::COP0_EPC);
+        if self.cause().branch_delay() {
+            epc.wrapping_add(4)
+        } else {
+            epc
         }
     }
 
+    /// Whether any of the hardware breakpoints might be armed. This is a cheap check meant to
+    /// gate the more expensive breakpoint checks.
+    #[inline(always)]
+    pub fn breakpoints_armed(&self) -> bool {
+        let dcic = self.dcic();
+        dcic.code_breakpoint() || dcic.data_breakpoint()
+    }
+
+    /// The interrupt lines which would be delivered to the CPU, one bit per line: those pending
+    /// in CAUSE (IP) and enabled in SR (IM), or none if interrupts are disabled (IEc).
+    ///
+    /// Lines 0 and 1 are the software interrupts, which are requested by writing to CAUSE. Line 2
+    /// is connected to the system interrupt controller.
+    #[inline(always)]
+    pub fn delivered_interrupt_lines(&self) -> u8 {
+        let interrupts_enabled = self
+            .system_status()
+            .cpu_mode_stack_at(0)
+            .unwrap()
+            .interrupts_enabled();
+
+        if !interrupts_enabled {
+            return 0;
+        }
+
+        let pending = self.0[Reg::COP0_CAUSE as usize].bits(8, 16);
+        let enabled = self.0[Reg::COP0_SR as usize].bits(8, 16);
+        (pending & enabled) as u8
+    }
+
+    /// Whether an interrupt should be taken by the CPU, i.e. whether any interrupt line is
+    /// delivered. See [`Registers::delivered_interrupt_lines`].
+    #[inline(always)]
+    pub fn interrupt_requested(&self) -> bool {
+        self.delivered_interrupt_lines() != 0
+    }
+
+    /// Decodes the CAUSE register.
+    pub fn decoded_cause(&self) -> DecodedCause {
+        let cause = self.cause();
+        DecodedCause {
+            exception: cause.exception(),
+            pending_interrupts: self.0[Reg::COP0_CAUSE as usize].bits(8, 16) as u8,
+            branch_delay: cause.branch_delay(),
+        }
+    }
+
+    /// Decodes the System Status register.
+    pub fn decoded_status(&self) -> DecodedStatus {
+        let status = self.system_status();
+        DecodedStatus {
+            mode_stack: status.cpu_mode_stack(),
+            interrupt_mask: self.0[Reg::COP0_SR as usize].bits(8, 16) as u8,
+            isolate_cache: status.isolate_cache(),
+            boot_exception_vectors: status.boot_exception_vectors_in_kseg1(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn dcic(&self) -> &Dcic {
+        zerocopy::transmute_ref!(&self.0[Reg::COP0_DCIC as usize])
+    }
+
+    #[inline(always)]
+    pub fn dcic_mut(&mut self) -> &mut Dcic {
+        zerocopy::transmute_mut!(&mut self.0[Reg::COP0_DCIC as usize])
+    }
+
     #[inline(always)]
     pub fn system_status(&self) -> &SystemStatus {
         zerocopy::transmute_ref!(&self.0[Reg::COP0_SR as usize])
@@ -257,3 +530,51 @@ impl Registers {
 pub struct Cop0 {
     pub regs: Registers,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // interrupt mask and BEV, which the mode stack must leave alone
+    const OTHER_BITS: u32 = 0x0040_FF00;
+
+    #[test]
+    fn start_exception_shifts_the_mode_stack_left() {
+        let mut status = SystemStatus::from_bits(OTHER_BITS | 0b10_11_01);
+
+        status.start_exception();
+        assert_eq!(status.to_bits(), OTHER_BITS | 0b11_01_00);
+        assert!(!status.system_interrupts_enabled());
+
+        status.start_exception();
+        assert_eq!(status.to_bits(), OTHER_BITS | 0b01_00_00);
+
+        status.start_exception();
+        assert_eq!(status.to_bits(), OTHER_BITS);
+    }
+
+    #[test]
+    fn rfe_shifts_the_mode_stack_right() {
+        let mut status = SystemStatus::from_bits(OTHER_BITS | 0b10_11_01);
+
+        status.restore_from_exception();
+        assert_eq!(status.to_bits(), OTHER_BITS | 0b10_10_11);
+
+        status.restore_from_exception();
+        assert_eq!(status.to_bits(), OTHER_BITS | 0b10_10_10);
+    }
+
+    #[test]
+    fn nested_exceptions_restore_the_mode_two_levels_deep() {
+        let mut status = SystemStatus::from_bits(OTHER_BITS | 0b10_11_01);
+
+        status.start_exception();
+        status.start_exception();
+        status.restore_from_exception();
+        assert_eq!(status.to_bits(), OTHER_BITS | 0b01_01_00);
+
+        status.restore_from_exception();
+        assert_eq!(status.to_bits(), OTHER_BITS | 0b01_01_01);
+        assert!(status.system_interrupts_enabled());
+    }
+}
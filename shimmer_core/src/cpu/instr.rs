@@ -1,6 +1,7 @@
 //! [`Instruction`], which represents a single MIPS I instruction, and related items.
 
 use super::{COP, Reg};
+use crate::mem::Address;
 use bitos::{
     bitos,
     integer::{u4, u5, u20, u25, u26},
@@ -112,6 +113,27 @@ pub enum SpecialCop0Opcode {
     RFE = 0x10,
 }
 
+/// The kind of effect an [`Instruction`] has, e.g. for highlighting it. See
+/// [`Instruction::classifies_as`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
+pub enum Category {
+    /// Loads from memory into a register, including coprocessor registers.
+    Load,
+    /// Stores a register, including coprocessor registers, into memory.
+    Store,
+    /// Conditionally jumps to an address relative to the instruction.
+    Branch,
+    /// Unconditionally jumps to an absolute address or to the address in a register.
+    Jump,
+    /// Computes a value from registers and immediates, including shifts, multiplications and
+    /// divisions.
+    Arithmetic,
+    /// Moves a value between the CPU and a coprocessor, or executes a coprocessor command.
+    Coprocessor,
+    /// Interacts with the system, such as raising an exception or returning from one.
+    System,
+}
+
 /// A MIPS I instruction.
 #[bitos(32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -423,6 +445,86 @@ impl Instruction {
         })
     }
 
+    /// Returns the kind of effect this instruction has, or [`None`] if it's illegal.
+    pub fn classifies_as(&self) -> Option<Category> {
+        Some(match self.op()? {
+            Opcode::SPECIAL => match self.special_op()? {
+                SpecialOpcode::JR | SpecialOpcode::JALR => Category::Jump,
+                SpecialOpcode::SYSCALL | SpecialOpcode::BREAK => Category::System,
+                _ => Category::Arithmetic,
+            },
+            Opcode::BZ | Opcode::BEQ | Opcode::BNE | Opcode::BLEZ | Opcode::BGTZ => {
+                Category::Branch
+            }
+            Opcode::JMP | Opcode::JAL => Category::Jump,
+            Opcode::ADDI
+            | Opcode::ADDIU
+            | Opcode::SLTI
+            | Opcode::SLTIU
+            | Opcode::ANDI
+            | Opcode::ORI
+            | Opcode::XORI
+            | Opcode::LUI => Category::Arithmetic,
+            Opcode::COP0 if self.cop_cmd() => match self.cop0_special_op()? {
+                SpecialCop0Opcode::RFE => Category::System,
+            },
+            Opcode::COP0 | Opcode::COP1 | Opcode::COP2 | Opcode::COP3 => {
+                if self.cop_cmd() {
+                    Category::Coprocessor
+                } else {
+                    match self.cop_op()? {
+                        CopOpcode::BRANCH => Category::Branch,
+                        _ => Category::Coprocessor,
+                    }
+                }
+            }
+            Opcode::LB
+            | Opcode::LH
+            | Opcode::LWL
+            | Opcode::LW
+            | Opcode::LBU
+            | Opcode::LHU
+            | Opcode::LWR
+            | Opcode::LWC0
+            | Opcode::LWC1
+            | Opcode::LWC2
+            | Opcode::LWC3 => Category::Load,
+            Opcode::SB
+            | Opcode::SH
+            | Opcode::SWL
+            | Opcode::SW
+            | Opcode::SWR
+            | Opcode::SWC0
+            | Opcode::SWC1
+            | Opcode::SWC2
+            | Opcode::SWC3 => Category::Store,
+        })
+    }
+
+    /// Returns the address this instruction, located at `addr`, branches or jumps to, or [`None`]
+    /// if it doesn't or if the target is in a register.
+    ///
+    /// Branches are relative to the delay slot, at `addr + 4`, while jumps replace the low 28 bits
+    /// of the delay slot address, staying in its 256 MB region.
+    pub fn target(&self, addr: Address) -> Option<Address> {
+        let delay_slot = addr.value().wrapping_add(4);
+        let branch =
+            || Address(delay_slot.wrapping_add_signed(i32::from(self.signed_imm16()) << 2));
+
+        match self.op()? {
+            Opcode::BZ | Opcode::BEQ | Opcode::BNE | Opcode::BLEZ | Opcode::BGTZ => Some(branch()),
+            Opcode::COP0 | Opcode::COP1 | Opcode::COP2 | Opcode::COP3
+                if !self.cop_cmd() && self.cop_op() == Some(CopOpcode::BRANCH) =>
+            {
+                Some(branch())
+            }
+            Opcode::JMP | Opcode::JAL => Some(Address(
+                (delay_slot & (0b1111 << 28)) | (self.imm26().value() << 2),
+            )),
+            _ => None,
+        }
+    }
+
     pub fn bz_kind(&self) -> BZKind {
         match (self.bz_ge(), self.bz_link().value() == 0b1000) {
             (true, true) => BZKind::BGEZAL,
@@ -470,3 +572,70 @@ impl Default for Instruction {
         Self::NOP
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instructions_are_classified_by_their_effect() {
+        let expected = [
+            ("lw $t0, 4($a0)", 0x8C88_0004, Some(Category::Load)),
+            ("lwc2 $8, 0($a0)", 0xC888_0000, Some(Category::Load)),
+            ("sw $t0, 4($a0)", 0xAC88_0004, Some(Category::Store)),
+            ("bne $t0, $zero, -2", 0x1500_FFFE, Some(Category::Branch)),
+            ("jal 0", 0x0C00_0000, Some(Category::Jump)),
+            ("jr $ra", 0x03E0_0008, Some(Category::Jump)),
+            (
+                "addiu $t0, $t0, -1",
+                0x2508_FFFF,
+                Some(Category::Arithmetic),
+            ),
+            ("nop", 0x0000_0000, Some(Category::Arithmetic)),
+            ("mtc2 $t0, $0", 0x4888_0000, Some(Category::Coprocessor)),
+            ("rtps", 0x4A18_0001, Some(Category::Coprocessor)),
+            ("rfe", 0x4200_0010, Some(Category::System)),
+            ("syscall", 0x0000_000C, Some(Category::System)),
+            ("illegal opcode 0x3F", 0xFC00_0000, None),
+        ];
+
+        for (name, bits, category) in expected {
+            assert_eq!(
+                Instruction::from_bits(bits).classifies_as(),
+                category,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn targets_are_resolved_relative_to_the_delay_slot() {
+        let expected = [
+            (
+                "bne $t0, $zero, -2",
+                0x1500_FFFE,
+                0x8000_1000,
+                Some(0x8000_0FFC),
+            ),
+            (
+                "beq $zero, $zero, -32768",
+                0x1000_8000,
+                0x8002_0000,
+                Some(0x8000_0004),
+            ),
+            ("j 0x1000", 0x0800_0400, 0x8000_1000, Some(0x8000_1000)),
+            // the delay slot is already in the next 256 MiB region
+            ("j 0x1000", 0x0800_0400, 0x8FFF_FFFC, Some(0x9000_1000)),
+            ("jr $ra", 0x03E0_0008, 0x8000_1000, None),
+            ("addiu $t0, $t0, -1", 0x2508_FFFF, 0x8000_1000, None),
+        ];
+
+        for (name, bits, addr, target) in expected {
+            assert_eq!(
+                Instruction::from_bits(bits).target(Address(addr)),
+                target.map(Address),
+                "{name} at {addr:08X}"
+            );
+        }
+    }
+}
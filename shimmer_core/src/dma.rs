@@ -72,6 +72,10 @@ pub struct ChannelBase {
     pub addr: u24,
 }
 
+impl ChannelBase {
+    pub const WRITE_MASK: u64 = Self::ADDR_MASK;
+}
+
 /// Configuration of the blocks transferred through a DMA channel.
 #[allow(clippy::len_without_is_empty)]
 #[bitos(32)]
@@ -119,6 +123,15 @@ pub struct ChannelControl {
 }
 
 impl ChannelControl {
+    pub const WRITE_MASK: u64 = Self::TRANSFER_DIRECTION_MASK
+        | Self::DATA_DIRECTION_MASK
+        | Self::ALTERNATIVE_BEHAVIOUR_MASK
+        | Self::TRANSFER_MODE_MASK
+        | Self::CHOPPING_DMA_WINDOW_SIZE_MASK
+        | Self::CHOPPING_CPU_WINDOW_SIZE_MASK
+        | Self::TRANSFER_ONGOING_MASK
+        | Self::FORCE_TRANSFER_MASK
+        | Self::BUS_SNOOPING_MASK;
     pub const DMA6_WRITE_MASK: u64 =
         Self::TRANSFER_ONGOING_MASK | Self::FORCE_TRANSFER_MASK | Self::BUS_SNOOPING_MASK;
 }
@@ -1,9 +1,32 @@
 //! Items related to the executable format of the PSX.
+//!
+//! # Arguments
+//! Sideloaded executables receive their arguments the same way the kernel's `Exec` passes them,
+//! which is what PSXSDK and PSn00bSDK expect: `A0` holds `argc` and `A1` holds a pointer to
+//! `argv`. Both live at the start of the scratchpad ([`ARGS_ADDR`]), laid out as:
+//! - `argc`, as a `u32`
+//! - `argv`, a table of `argc` pointers to the arguments followed by a null pointer
+//! - the null terminated arguments themselves
+//!
+//! Everything has to fit in [`ARGS_MAX_LEN`] bytes.
 
 use crate::mem::Address;
 use binrw::BinRead;
+use easyerr::Error;
 use std::ffi::{CStr, CString};
 
+/// Address of the arguments of a sideloaded executable, at the start of the scratchpad.
+pub const ARGS_ADDR: Address = Address(0x1F80_0000);
+/// Maximum length of the packed arguments of a sideloaded executable, which is the size of the
+/// scratchpad.
+pub const ARGS_MAX_LEN: usize = 1024;
+
+#[derive(Debug, Error)]
+pub enum ArgsError {
+    #[error("arguments take {len} bytes, but at most 1024 bytes fit in the scratchpad")]
+    TooLong { len: usize },
+}
+
 /// Header of a PSX executable.
 #[derive(Debug, Clone, BinRead)]
 #[br(magic = b"PS-X EXE\0\0\0\0\0\0\0\0")]
@@ -34,3 +57,80 @@ pub struct Executable {
     #[br(count = header.length)]
     pub program: Vec<u8>,
 }
+
+/// Packs the arguments of an executable as described in the [module docs](self), to be placed at
+/// [`ARGS_ADDR`].
+pub fn pack_args(args: &[CString]) -> Result<Vec<u8>, ArgsError> {
+    let table_len = 4 + 4 * (args.len() + 1);
+    let strings_len: usize = args.iter().map(|arg| arg.as_bytes_with_nul().len()).sum();
+    let len = table_len + strings_len;
+    if len > ARGS_MAX_LEN {
+        return Err(ArgsError::TooLong { len });
+    }
+
+    let mut packed = Vec::with_capacity(len);
+    packed.extend((args.len() as u32).to_le_bytes());
+
+    let mut string_addr = ARGS_ADDR.value() + table_len as u32;
+    for arg in args {
+        packed.extend(string_addr.to_le_bytes());
+        string_addr += arg.as_bytes_with_nul().len() as u32;
+    }
+    packed.extend(0u32.to_le_bytes());
+
+    for arg in args {
+        packed.extend(arg.as_bytes_with_nul());
+    }
+
+    Ok(packed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<CString> {
+        args.iter().map(|&arg| CString::new(arg).unwrap()).collect()
+    }
+
+    fn words(packed: &[u8], count: usize) -> Vec<u32> {
+        packed
+            .chunks_exact(4)
+            .take(count)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn packs_count_pointer_table_and_strings() {
+        let packed = pack_args(&args(&["auto", "x"])).unwrap();
+
+        // argc, argv[0], argv[1], null pointer
+        let table_len = 16;
+        let base = ARGS_ADDR.value();
+        assert_eq!(
+            words(&packed, 4),
+            [2, base + table_len, base + table_len + 5, 0]
+        );
+        assert_eq!(&packed[table_len as usize..], b"auto\0x\0");
+    }
+
+    #[test]
+    fn packs_no_arguments() {
+        let packed = pack_args(&[]).unwrap();
+        assert_eq!(packed, [0; 8]);
+    }
+
+    #[test]
+    fn rejects_arguments_overflowing_the_scratchpad() {
+        // argc, one pointer and the null pointer leave room for a string of 1012 bytes
+        let fits = "a".repeat(ARGS_MAX_LEN - 12 - 1);
+        assert_eq!(pack_args(&args(&[&fits])).unwrap().len(), ARGS_MAX_LEN);
+
+        let overflows = "a".repeat(ARGS_MAX_LEN - 12);
+        assert!(matches!(
+            pack_args(&args(&[&overflows])),
+            Err(ArgsError::TooLong { len }) if len == ARGS_MAX_LEN + 1
+        ));
+    }
+}
@@ -2,6 +2,7 @@
 
 pub mod cmd;
 pub mod texture;
+pub mod vram;
 
 use crate::cpu;
 use bitos::{
@@ -13,6 +14,7 @@ use texture::{BlendingMode, TexPage, TexWindow};
 
 #[bitos(2)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HorizontalResolution {
     #[default]
     R256,
@@ -30,10 +32,21 @@ impl HorizontalResolution {
             Self::R640 => 640,
         }
     }
+
+    /// The divider applied to the video clock to obtain the dot clock of this resolution.
+    pub fn dot_clock_divider(&self) -> u16 {
+        match self {
+            Self::R256 => 10,
+            Self::R320 => 8,
+            Self::R512 => 5,
+            Self::R640 => 4,
+        }
+    }
 }
 
 #[bitos(1)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VerticalResolution {
     #[default]
     R240,
@@ -173,7 +186,9 @@ impl Status {
 /// Environment configuration of the GPU.
 #[derive(Debug, Default)]
 pub struct EnvironmentState {
-    pub double_vram: bool,
+    /// Whether texture disable bits are honored, as set by GP1(09h). When not allowed, the
+    /// texture disable bit of GPUSTAT always reads as zero.
+    pub allow_texture_disable: bool,
 
     pub textured_rect_flip_x: bool,
     pub textured_rect_flip_y: bool,
@@ -199,13 +214,218 @@ pub struct DisplayState {
     pub vertical_range: Range<u10>,
 }
 
+/// Timing of the video signal of a [`VideoMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoTiming {
+    /// Frequency of the video clock, in Hz.
+    pub video_clock: u32,
+    /// Dot clock at which pixels are square in a full interlaced frame, in Hz.
+    pub square_pixel_clock: u32,
+    /// Length of a scanline, in video clock cycles.
+    pub video_cycles_per_scanline: u32,
+    /// Number of scanlines in a frame (non-interlaced).
+    pub scanlines: u16,
+    /// Vertical display range used when the one configured through GP1(07) is unusable.
+    pub default_vertical_range: (u16, u16),
+}
+
+impl VideoTiming {
+    pub const NTSC: Self = Self {
+        video_clock: 53_693_182,
+        square_pixel_clock: 12_272_727,
+        video_cycles_per_scanline: 3413,
+        scanlines: 263,
+        default_vertical_range: (16, 256),
+    };
+
+    pub const PAL: Self = Self {
+        video_clock: 53_203_425,
+        square_pixel_clock: 14_750_000,
+        video_cycles_per_scanline: 3406,
+        scanlines: 314,
+        default_vertical_range: (35, 291),
+    };
+
+    /// Returns the CPU cycles it takes for the beam to go from the start of scanline `from` to the
+    /// start of scanline `to`, wrapping around to the next frame if `to` is not after `from`.
+    pub fn cycles_between(&self, from: u16, to: u16) -> u64 {
+        let lines = if to > from {
+            to - from
+        } else {
+            self.scanlines - from + to
+        };
+
+        let video_cycles = u64::from(lines) * u64::from(self.video_cycles_per_scanline);
+        video_cycles * u64::from(cpu::FREQUENCY) / u64::from(self.video_clock)
+    }
+
+    /// Returns the CPU cycles it takes for the beam to draw a whole frame.
+    pub fn cycles_per_frame(&self) -> u64 {
+        self.cycles_between(0, 0)
+    }
+
+    /// Returns the first scanline of the display area, where vertical blanking ends, and the first
+    /// scanline after it, where vertical blanking starts, given the vertical display range.
+    pub fn display_lines(&self, vertical_range: &Range<u10>) -> (u16, u16) {
+        let start = vertical_range.start.value();
+        let end = vertical_range.end.value();
+        if start < end && end <= self.scanlines {
+            (start, end % self.scanlines)
+        } else {
+            self.default_vertical_range
+        }
+    }
+}
+
+/// Amount of words the GP0 command FIFO holds on hardware.
+pub const GP0_FIFO_LEN: usize = 16;
+
+/// Amount of unprocessed GP0 words above which the GPU stops accepting further words. The real
+/// FIFO only holds [`GP0_FIFO_LEN`] words, but a larger limit keeps DMA transfers fast while still
+/// bounding the render queue.
+pub const RENDER_QUEUE_LIMIT: usize = 4096;
+
+/// Maximum amount of words in a node of a linked list DMA transfer, whose header has an 8-bit
+/// word count.
+pub const LINKED_LIST_NODE_WORDS: usize = 255;
+
+/// Default hard limit on the amount of words in the render queue.
+///
+/// DMA checks [`RENDER_QUEUE_LIMIT`] before each step of a transfer, so a step started just below
+/// it can still push a whole linked list node. On top of that, the cap leaves room for a full
+/// FIFO's worth of words, which is how much a slice block or the CPU can write on hardware without
+/// overflowing it. Only programs ignoring the readiness bits ever hit it.
+pub const DEFAULT_QUEUE_CAP: usize = RENDER_QUEUE_LIMIT + LINKED_LIST_NODE_WORDS + GP0_FIFO_LEN;
+
+/// Hard limit on the amount of words in the display queue. GP1 commands are single words which
+/// take effect immediately on hardware, and the queue is drained as soon as the GPU is updated,
+/// so it only ever needs to hold a handful of them.
+pub const DISPLAY_QUEUE_CAP: usize = 16;
+
+/// Maximum amount of GPU info results waiting to be read from GPUREAD.
+pub const INFO_RESPONSE_CAP: usize = 4;
+
+/// What happened to a word pushed onto a GPU command queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuePush {
+    Queued,
+    /// The word was dropped because the queue is full.
+    Dropped,
+    /// The word was dropped because the queue is full, and it's the first one dropped since the
+    /// queue filled up.
+    Overflowed,
+}
+
+/// Keeps the GPU command queues bounded when a program writes commands faster than they're
+/// consumed, ignoring the readiness bits. Words written to a full queue are dropped, like writes
+/// to a full FIFO on hardware.
+#[derive(Debug, Clone)]
+pub struct QueueWatchdog {
+    /// Maximum amount of words in the render queue. The display queue is always capped at
+    /// [`DISPLAY_QUEUE_CAP`].
+    pub cap: usize,
+    /// Amount of words dropped so far.
+    pub dropped: u64,
+    /// Whether words are currently being dropped.
+    pub overflowing: bool,
+}
+
+impl Default for QueueWatchdog {
+    fn default() -> Self {
+        Self {
+            cap: DEFAULT_QUEUE_CAP,
+            dropped: 0,
+            overflowing: false,
+        }
+    }
+}
+
+impl QueueWatchdog {
+    fn push(&mut self, queue: &mut VecDeque<u32>, cap: usize, word: u32) -> QueuePush {
+        if queue.len() < cap {
+            queue.push_back(word);
+            self.overflowing = false;
+            return QueuePush::Queued;
+        }
+
+        self.dropped += 1;
+        if std::mem::replace(&mut self.overflowing, true) {
+            QueuePush::Dropped
+        } else {
+            QueuePush::Overflowed
+        }
+    }
+}
+
+/// The data waiting to be read from GPUREAD.
+///
+/// There's no such buffer on hardware: GPUREAD returns either the latched result of the last GPU
+/// info request or the current word of the ongoing VRAM to CPU blit. Accordingly, this holds at
+/// most one blit, which is replaced by the next one even if it wasn't fully read, and at most
+/// [`INFO_RESPONSE_CAP`] info results, dropping the oldest one when full. Info results are read
+/// before blit data. Once everything has been read, GPUREAD keeps returning the latched result of
+/// the last info request.
+#[derive(Debug, Default)]
+pub struct ResponseQueue {
+    info: VecDeque<u32>,
+    blit: VecDeque<u32>,
+    latch: u32,
+}
+
+impl ResponseQueue {
+    /// Pops the next word to be read from GPUREAD.
+    pub fn pop_front(&mut self) -> Option<u32> {
+        self.info.pop_front().or_else(|| self.blit.pop_front())
+    }
+
+    /// Reads GPUREAD: the next word waiting to be read, or the latched result of the last info
+    /// request if there's none.
+    pub fn read(&mut self) -> u32 {
+        self.pop_front().unwrap_or(self.latch)
+    }
+
+    /// Amount of words waiting to be read.
+    pub fn len(&self) -> usize {
+        self.info.len() + self.blit.len()
+    }
+
+    /// Whether there are no words waiting to be read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Starts a VRAM to CPU blit with the given data. Returns the amount of words of the previous
+    /// blit which were discarded without being read.
+    pub fn start_blit(&mut self, data: impl IntoIterator<Item = u32>) -> usize {
+        let discarded = self.blit.len();
+        self.blit.clear();
+        self.blit.extend(data);
+
+        discarded
+    }
+
+    /// Queues the result of a GPU info request. Returns the oldest result if it had to be dropped
+    /// to make room for this one.
+    pub fn push_info(&mut self, value: u32) -> Option<u32> {
+        let dropped = if self.info.len() >= INFO_RESPONSE_CAP {
+            self.info.pop_front()
+        } else {
+            None
+        };
+
+        self.info.push_back(value);
+        self.latch = value;
+        dropped
+    }
+}
+
 /// The state of the GPU.
 #[derive(Debug, Default)]
 pub struct Gpu {
     /// GPU status. This is the value of GPUSTAT (GP0).
     pub status: Status,
     /// GPU response. This is the value of GPUREAD (GP1).
-    pub response_queue: VecDeque<u32>,
+    pub response_queue: ResponseQueue,
     /// The queued packets written to GP0.
     pub render_queue: VecDeque<u32>,
     /// The queued packets written to GP1.
@@ -215,14 +435,182 @@ pub struct Gpu {
     pub environment: EnvironmentState,
     /// Display configuration.
     pub display: DisplayState,
+
+    /// Whether the beam is in vertical blanking.
+    pub in_vblank: bool,
+    /// Whether the current field is the odd one. Only alternates in 480 line interlaced mode.
+    pub odd_field: bool,
+    /// Whether a CPU to VRAM blit is waiting for data. Until it has received all of it, every
+    /// GP0 word is data, so the GPU isn't ready to receive commands.
+    pub receiving_blit: bool,
+
+    /// Bounds the command queues.
+    pub watchdog: QueueWatchdog,
 }
 
 impl Gpu {
+    /// The timing of the video signal in the current video mode.
     #[inline]
-    pub fn cycles_per_vblank(&self) -> u32 {
+    pub fn timing(&self) -> VideoTiming {
         match self.status.video_mode() {
-            VideoMode::NTSC => (f64::from(cpu::FREQUENCY) / 59.826) as u32,
-            VideoMode::PAL => (f64::from(cpu::FREQUENCY) / 50.219) as u32,
+            VideoMode::NTSC => VideoTiming::NTSC,
+            VideoMode::PAL => VideoTiming::PAL,
         }
     }
+
+    /// The aspect ratio (width over height) of the displayed pixels in the current display mode,
+    /// which depends on the dot clock of the horizontal resolution and on the video mode.
+    pub fn pixel_aspect_ratio(&self) -> f64 {
+        let timing = self.timing();
+        let divider = if self.status.force_horizontal_368() {
+            7
+        } else {
+            self.status.horizontal_resolution().dot_clock_divider()
+        };
+
+        // without interlacing, each line takes the height of two lines of a full frame
+        let line_height = match self.status.vertical_resolution() {
+            VerticalResolution::R240 => 2.0,
+            VerticalResolution::R480 => 1.0,
+        };
+
+        let dot_clock = f64::from(timing.video_clock) / f64::from(divider);
+        f64::from(timing.square_pixel_clock) / (dot_clock * line_height)
+    }
+
+    /// Returns the first scanline of the display area, where vertical blanking ends, and the first
+    /// scanline after it, where vertical blanking starts.
+    #[inline]
+    pub fn display_lines(&self) -> (u16, u16) {
+        self.timing().display_lines(&self.display.vertical_range)
+    }
+
+    /// Pushes a word written to GP0 onto the render queue, unless it's at the watchdog cap.
+    #[inline]
+    pub fn push_gp0(&mut self, word: u32) -> QueuePush {
+        let cap = self.watchdog.cap;
+        self.watchdog.push(&mut self.render_queue, cap, word)
+    }
+
+    /// Pushes a word written to GP1 onto the display queue, unless it's at
+    /// [`DISPLAY_QUEUE_CAP`].
+    #[inline]
+    pub fn push_gp1(&mut self, word: u32) -> QueuePush {
+        self.watchdog
+            .push(&mut self.display_queue, DISPLAY_QUEUE_CAP, word)
+    }
+
+    /// Whether the render queue is holding too many unprocessed words to accept more.
+    #[inline]
+    pub fn render_queue_full(&self) -> bool {
+        self.render_queue.len() >= RENDER_QUEUE_LIMIT
+    }
+
+    /// Updates the GP0 readiness bits of the status register and the DMA request from the
+    /// amount of queued GP0 words and whether a blit is waiting for data.
+    pub fn update_readiness(&mut self) {
+        let ready = !self.render_queue_full();
+        self.status
+            .set_ready_to_receive_cmd(ready && !self.receiving_blit);
+        self.status.set_ready_to_receive_block(ready);
+        self.status.update_dreq();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_results_drop_the_oldest_when_full() {
+        let mut queue = ResponseQueue::default();
+        for value in 0..INFO_RESPONSE_CAP as u32 {
+            assert_eq!(queue.push_info(value), None);
+        }
+
+        assert_eq!(queue.push_info(100), Some(0));
+        assert_eq!(queue.len(), INFO_RESPONSE_CAP);
+
+        let read: Vec<_> = std::iter::from_fn(|| queue.pop_front()).collect();
+        assert_eq!(read, [1, 2, 3, 100]);
+
+        // the last result stays latched
+        assert_eq!(queue.read(), 100);
+        assert_eq!(queue.read(), 100);
+    }
+
+    #[test]
+    fn blits_replace_unread_blits() {
+        let mut queue = ResponseQueue::default();
+        assert_eq!(queue.start_blit([1, 2, 3]), 0);
+        assert_eq!(queue.read(), 1);
+
+        assert_eq!(queue.start_blit([4, 5]), 2);
+        queue.push_info(6);
+        assert_eq!(queue.len(), 3);
+
+        // info results are read first
+        assert_eq!(queue.read(), 6);
+        assert_eq!(queue.read(), 4);
+        assert_eq!(queue.read(), 5);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn full_queues_drop_words() {
+        let mut gpu = Gpu::default();
+        gpu.watchdog.cap = 4;
+
+        let pushes: Vec<_> = (0..6).map(|word| gpu.push_gp0(word)).collect();
+        assert_eq!(
+            pushes,
+            [
+                QueuePush::Queued,
+                QueuePush::Queued,
+                QueuePush::Queued,
+                QueuePush::Queued,
+                QueuePush::Overflowed,
+                QueuePush::Dropped,
+            ]
+        );
+        assert_eq!(gpu.render_queue, [0, 1, 2, 3]);
+
+        for word in 0..DISPLAY_QUEUE_CAP as u32 {
+            assert_eq!(gpu.push_gp1(word), QueuePush::Queued);
+        }
+        assert_eq!(gpu.push_gp1(0), QueuePush::Overflowed);
+        assert_eq!(gpu.display_queue.len(), DISPLAY_QUEUE_CAP);
+        assert_eq!(gpu.watchdog.dropped, 3);
+
+        // once there's room again, words are accepted
+        gpu.render_queue.pop_front();
+        assert_eq!(gpu.push_gp0(4), QueuePush::Queued);
+        assert!(!gpu.watchdog.overflowing);
+    }
+
+    #[test]
+    fn pixel_aspect_ratio_follows_the_dot_clock_and_video_mode() {
+        let cases = [
+            (HorizontalResolution::R320, VideoMode::NTSC, 0.9143),
+            (HorizontalResolution::R256, VideoMode::PAL, 1.3862),
+        ];
+
+        for (horizontal, video_mode, expected) in cases {
+            let mut gpu = Gpu::default();
+            gpu.status.set_horizontal_resolution(horizontal);
+            gpu.status.set_video_mode(video_mode);
+
+            let ratio = gpu.pixel_aspect_ratio();
+            assert!(
+                (ratio - expected).abs() < 0.001,
+                "{horizontal:?} {video_mode:?} has a ratio of {ratio:.4}, expected {expected:.4}"
+            );
+        }
+
+        // interlacing halves the height of each line
+        let mut gpu = Gpu::default();
+        let progressive = gpu.pixel_aspect_ratio();
+        gpu.status.set_vertical_resolution(VerticalResolution::R480);
+        assert!((gpu.pixel_aspect_ratio() - 2.0 * progressive).abs() < 1e-9);
+    }
 }
@@ -2,14 +2,16 @@
 
 pub mod display;
 pub mod environment;
+pub mod parser;
 pub mod rendering;
 
 use self::{display::*, environment::*, rendering::*};
 use bitos::bitos;
+use strum::IntoStaticStr;
 
 /// The primary opcode of a [`RenderingCommand`].
 #[bitos(3)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
 pub enum RenderingOpcode {
     Misc = 0x0,
     Polygon = 0x1,
@@ -23,7 +25,7 @@ pub enum RenderingOpcode {
 
 /// The misc opcode of a [`RenderingCommand`].
 #[bitos(5)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
 pub enum MiscOpcode {
     /// Does nothing.
     NOP = 0x00,
@@ -36,7 +38,7 @@ pub enum MiscOpcode {
 
 /// The environment opcode of a [`RenderingCommand`].
 #[bitos(3)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
 pub enum EnvironmentOpcode {
     /// Set the drawing setings.
     DrawingSettings = 0x1,
@@ -53,7 +55,7 @@ pub enum EnvironmentOpcode {
 
 /// The primary opcode of a [`DisplayCommand`].
 #[bitos(6)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
 pub enum DisplayOpcode {
     ResetGpu = 0x0,
     ResetCommandBuffer = 0x1,
@@ -64,8 +66,13 @@ pub enum DisplayOpcode {
     HorizontalDisplayRange = 0x6,
     VerticalDisplayRange = 0x7,
     DisplayMode = 0x8,
-    VramSizeV2 = 0x09,
+    /// Allows textures to be disabled through bit 11 of GP0(E1h). Some documents call this the
+    /// VRAM size command, after its meaning on arcade boards with 2 MiB of VRAM. Only the 1 MiB
+    /// VRAM of retail units is emulated, so it's always treated as the former.
+    AllowTextureDisable = 0x09,
     ReadGpuRegister = 0x10,
+    /// Sets the VRAM size on arcade boards. Unsupported, see
+    /// [`DisplayOpcode::AllowTextureDisable`].
     VramSizeV1 = 0x20,
 }
 
@@ -89,7 +96,7 @@ pub struct DisplayCommand {
     #[bits(..)]
     pub display_mode_cmd: DisplayModeCmd,
     #[bits(..)]
-    pub vram_size_cmd: VramSizeCmd,
+    pub allow_texture_disable_cmd: AllowTextureDisableCmd,
 }
 
 impl std::fmt::Debug for DisplayCommand {
@@ -105,7 +112,7 @@ impl std::fmt::Debug for DisplayCommand {
                 DisplayOpcode::HorizontalDisplayRange => self.horizontal_display_range_cmd().fmt(f),
                 DisplayOpcode::VerticalDisplayRange => self.vertical_display_range_cmd().fmt(f),
                 DisplayOpcode::DisplayMode => self.display_mode_cmd().fmt(f),
-                DisplayOpcode::VramSizeV2 => self.vram_size_cmd().fmt(f),
+                DisplayOpcode::AllowTextureDisable => self.allow_texture_disable_cmd().fmt(f),
                 DisplayOpcode::ReadGpuRegister => write!(f, "ReadGpuRegister"),
                 DisplayOpcode::VramSizeV1 => write!(f, "VramSizeV1"),
             },
@@ -204,9 +211,12 @@ impl RenderingCommand {
             }
             RenderingOpcode::Line => {
                 let cmd = self.line_cmd();
-                match cmd.shading_mode() {
-                    ShadingMode::Flat => 2,
-                    ShadingMode::Gouraud => 4,
+                match (cmd.line_mode(), cmd.shading_mode()) {
+                    // the vertices of poly-lines are received after the command itself
+                    (LineMode::Poly, _) => 0,
+                    // the color of the first vertex is in the command
+                    (LineMode::Single, ShadingMode::Flat) => 2,
+                    (LineMode::Single, ShadingMode::Gouraud) => 3,
                 }
             }
             RenderingOpcode::Rectangle => {
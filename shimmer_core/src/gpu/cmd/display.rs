@@ -72,9 +72,11 @@ pub struct DisplayModeCmd {
     pub flip_screen_x: bool,
 }
 
+/// An allow texture disable command.
 #[bitos(32)]
 #[derive(Debug, Clone)]
-pub struct VramSizeCmd {
+pub struct AllowTextureDisableCmd {
+    /// Whether bit 11 of GP0(E1h) disables textures. If not, the bit is ignored.
     #[bits(0)]
-    pub double: bool,
+    pub allow: bool,
 }
@@ -11,7 +11,7 @@ use bitos::{
 
 /// A drawing settings command.
 #[bitos(32)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct DrawingSettingsCmd {
     #[bits(0..9)]
     pub texpage: TexPage,
@@ -19,6 +19,9 @@ pub struct DrawingSettingsCmd {
     pub compression_mode: CompressionMode,
     #[bits(10)]
     pub enable_drawing_to_display: bool,
+    /// Only honored if allowed by GP1(09h). On arcade boards with 2 MiB of VRAM this bit extends
+    /// the texpage Y base instead, which isn't supported: the texpage Y base is a single bit and
+    /// VRAM is always 512 lines tall.
     #[bits(11)]
     pub texture_disable: bool,
     #[bits(12)]
@@ -29,7 +32,7 @@ pub struct DrawingSettingsCmd {
 
 /// A drawing offset command.
 #[bitos(32)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct TextureWindowSettingsCmd {
     #[bits(..20)]
     pub texwindow: TexWindow,
@@ -37,7 +40,7 @@ pub struct TextureWindowSettingsCmd {
 
 /// A drawing area corner command.
 #[bitos(32)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct DrawingAreaCornerCmd {
     #[bits(0..10)]
     pub x: u10,
@@ -47,7 +50,7 @@ pub struct DrawingAreaCornerCmd {
 
 /// A drawing offset command.
 #[bitos(32)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct DrawingOffsetCmd {
     #[bits(0..11)]
     pub x: i11,
@@ -57,7 +60,7 @@ pub struct DrawingOffsetCmd {
 
 /// A drawing offset command.
 #[bitos(32)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct MaskSettingsCmd {
     #[bits(0)]
     pub write_to_mask: bool,
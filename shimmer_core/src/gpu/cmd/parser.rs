@@ -0,0 +1,600 @@
+//! Decoding of GP0 and GP1 words into fully decoded commands, independent of any GPU state.
+
+use super::{
+    DisplayCommand, DisplayOpcode, EnvironmentOpcode, MiscOpcode, RenderingCommand,
+    RenderingOpcode, display::*, environment::*, rendering::*,
+};
+use arrayvec::ArrayVec;
+use std::fmt;
+
+/// Whether the given word terminates a poly-line.
+pub fn is_poly_line_end(word: u32) -> bool {
+    word & 0xF000_F000 == 0x5000_5000
+}
+
+/// A vertex of a polygon, with all of its packets gathered.
+#[derive(Debug, Clone, Default)]
+pub struct PolygonVertex {
+    /// The color of this vertex. For flat shaded polygons and for the first vertex, this is the
+    /// color in the command itself.
+    pub color: VertexColorPacket,
+    pub position: VertexPositionPacket,
+    /// Only present in textured polygons.
+    pub uv: Option<VertexUVPacket>,
+}
+
+impl PolygonVertex {
+    /// Decodes a vertex from the start of `words`, returning it and how many words it consumed.
+    /// If `color` is `None`, the color is read from `words`.
+    fn decode(
+        words: &[u32],
+        color: Option<VertexColorPacket>,
+        textured: bool,
+    ) -> Option<(Self, usize)> {
+        let mut words = words.iter().copied();
+        let mut len = 0;
+        let mut next = || {
+            len += 1;
+            words.next()
+        };
+
+        let color = match color {
+            Some(color) => color,
+            None => VertexColorPacket::from_bits(next()?),
+        };
+        let position = VertexPositionPacket::from_bits(next()?);
+        let uv = if textured {
+            Some(VertexUVPacket::from_bits(next()?))
+        } else {
+            None
+        };
+
+        Some((
+            Self {
+                color,
+                position,
+                uv,
+            },
+            len,
+        ))
+    }
+}
+
+/// A vertex of a line, with all of its packets gathered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LineVertex {
+    /// The color of this vertex. For flat shaded lines and for the first vertex, this is the color
+    /// in the command itself.
+    pub color: VertexColorPacket,
+    pub position: VertexPositionPacket,
+}
+
+impl LineVertex {
+    /// Decodes a vertex from the start of `words`, returning it and how many words it consumed.
+    /// If `color` is `None`, the color is read from `words`.
+    pub fn decode(words: &[u32], color: Option<VertexColorPacket>) -> Option<(Self, usize)> {
+        match color {
+            Some(color) => Some((
+                Self {
+                    color,
+                    position: VertexPositionPacket::from_bits(*words.first()?),
+                },
+                1,
+            )),
+            None => {
+                let [color, position, ..] = *words else {
+                    return None;
+                };
+
+                Some((
+                    Self {
+                        color: VertexColorPacket::from_bits(color),
+                        position: VertexPositionPacket::from_bits(position),
+                    },
+                    2,
+                ))
+            }
+        }
+    }
+}
+
+/// A fully decoded rendering command, i.e. a GP0 command along with its arguments.
+#[derive(Debug, Clone)]
+pub enum DecodedRenderingCommand {
+    Nop,
+    ClearCache,
+    QuickRectangleFill {
+        color: VertexColorPacket,
+        position: CoordPacket,
+        size: SizePacket,
+    },
+    InterruptRequest,
+    Polygon {
+        cmd: PolygonCmd,
+        vertices: ArrayVec<PolygonVertex, 4>,
+    },
+    Line {
+        cmd: LineCmd,
+        vertices: [LineVertex; 2],
+    },
+    /// The start of a poly-line. Its vertices follow the command, up until a word for which
+    /// [`is_poly_line_end`] holds is received (after at least two vertices).
+    PolyLine {
+        cmd: LineCmd,
+    },
+    Rectangle {
+        cmd: RectangleCmd,
+        position: VertexPositionPacket,
+        uv: Option<VertexUVPacket>,
+        width: u16,
+        height: u16,
+    },
+    VramToVramBlit {
+        src: CoordPacket,
+        dest: CoordPacket,
+        size: SizePacket,
+    },
+    /// The start of a CPU to VRAM blit. [`SizePacket::words`] words of data follow the command.
+    CpuToVramBlit {
+        dest: CoordPacket,
+        size: SizePacket,
+    },
+    VramToCpuBlit {
+        src: CoordPacket,
+        size: SizePacket,
+    },
+    DrawingSettings(DrawingSettingsCmd),
+    TexWindowSettings(TextureWindowSettingsCmd),
+    DrawingAreaTopLeft(DrawingAreaCornerCmd),
+    DrawingAreaBottomRight(DrawingAreaCornerCmd),
+    DrawingOffset(DrawingOffsetCmd),
+    MaskSettings(MaskSettingsCmd),
+    /// A command with an unknown opcode.
+    Unknown(RenderingCommand),
+}
+
+impl DecodedRenderingCommand {
+    /// Decodes the command at the start of `words`, returning it and how many words it consumed.
+    /// Returns `None` if `words` does not contain the whole command.
+    pub fn decode(words: &[u32]) -> Option<(Self, usize)> {
+        let cmd = RenderingCommand::from_bits(*words.first()?);
+        if words.len() <= cmd.args() {
+            return None;
+        }
+
+        let args = &words[1..];
+        let decoded = match cmd.opcode() {
+            RenderingOpcode::Misc => match cmd.misc_opcode() {
+                Some(MiscOpcode::NOP) => Self::Nop,
+                Some(MiscOpcode::ClearCache) => Self::ClearCache,
+                Some(MiscOpcode::QuickRectangleFill) => Self::QuickRectangleFill {
+                    color: cmd.rectangle_cmd().color(),
+                    position: CoordPacket::from_bits(args[0]),
+                    size: SizePacket::from_bits(args[1]),
+                },
+                Some(MiscOpcode::InterruptRequest) => Self::InterruptRequest,
+                None => Self::Unknown(cmd),
+            },
+            RenderingOpcode::Polygon => {
+                let cmd = cmd.polygon_cmd();
+                let base_color = cmd.color();
+
+                let mut vertices = ArrayVec::new();
+                let mut len = 1;
+                for i in 0..cmd.polygon_mode().vertices() {
+                    let color =
+                        (i == 0 || cmd.shading_mode() == ShadingMode::Flat).then_some(base_color);
+                    let (vertex, vertex_len) =
+                        PolygonVertex::decode(&words[len..], color, cmd.textured())?;
+
+                    vertices.push(vertex);
+                    len += vertex_len;
+                }
+
+                return Some((Self::Polygon { cmd, vertices }, len));
+            }
+            RenderingOpcode::Line => {
+                let cmd = cmd.line_cmd();
+                if cmd.line_mode() == LineMode::Poly {
+                    return Some((Self::PolyLine { cmd }, 1));
+                }
+
+                let base_color = cmd.color();
+                let (first, first_len) = LineVertex::decode(args, Some(base_color))?;
+                let color = (cmd.shading_mode() == ShadingMode::Flat).then_some(base_color);
+                let (second, second_len) = LineVertex::decode(&args[first_len..], color)?;
+
+                return Some((
+                    Self::Line {
+                        cmd,
+                        vertices: [first, second],
+                    },
+                    1 + first_len + second_len,
+                ));
+            }
+            RenderingOpcode::Rectangle => {
+                let cmd = cmd.rectangle_cmd();
+                let mut args = args.iter().copied();
+
+                let position = VertexPositionPacket::from_bits(args.next()?);
+                let uv = if cmd.textured() {
+                    Some(VertexUVPacket::from_bits(args.next()?))
+                } else {
+                    None
+                };
+
+                let (width, height) = match cmd.rectangle_mode() {
+                    RectangleMode::Variable => {
+                        let size = SizePacket::from_bits(args.next()?);
+                        (size.width(), size.height())
+                    }
+                    RectangleMode::SinglePixel => (1, 1),
+                    RectangleMode::Sprite8 => (8, 8),
+                    RectangleMode::Sprite16 => (16, 16),
+                };
+
+                Self::Rectangle {
+                    cmd,
+                    position,
+                    uv,
+                    width,
+                    height,
+                }
+            }
+            RenderingOpcode::VramToVramBlit => Self::VramToVramBlit {
+                src: CoordPacket::from_bits(args[0]),
+                dest: CoordPacket::from_bits(args[1]),
+                size: SizePacket::from_bits(args[2]),
+            },
+            RenderingOpcode::CpuToVramBlit => Self::CpuToVramBlit {
+                dest: CoordPacket::from_bits(args[0]),
+                size: SizePacket::from_bits(args[1]),
+            },
+            RenderingOpcode::VramToCpuBlit => Self::VramToCpuBlit {
+                src: CoordPacket::from_bits(args[0]),
+                size: SizePacket::from_bits(args[1]),
+            },
+            RenderingOpcode::Environment => match cmd.environment_opcode() {
+                Some(EnvironmentOpcode::DrawingSettings) => {
+                    Self::DrawingSettings(cmd.drawing_settings_cmd())
+                }
+                Some(EnvironmentOpcode::TexWindowSettings) => {
+                    Self::TexWindowSettings(cmd.texture_window_settings_cmd())
+                }
+                Some(EnvironmentOpcode::DrawingAreaTopLeft) => {
+                    Self::DrawingAreaTopLeft(cmd.drawing_area_corner_cmd())
+                }
+                Some(EnvironmentOpcode::DrawingAreaBottomRight) => {
+                    Self::DrawingAreaBottomRight(cmd.drawing_area_corner_cmd())
+                }
+                Some(EnvironmentOpcode::DrawingOffset) => {
+                    Self::DrawingOffset(cmd.drawing_offset_cmd())
+                }
+                Some(EnvironmentOpcode::MaskSettings) => {
+                    Self::MaskSettings(cmd.mask_settings_cmd())
+                }
+                None => Self::Unknown(cmd),
+            },
+        };
+
+        Some((decoded, 1 + cmd.args()))
+    }
+}
+
+struct Color(VertexColorPacket);
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.0.r(), self.0.g(), self.0.b())
+    }
+}
+
+struct Position(VertexPositionPacket);
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.0.x().value(), self.0.y().value())
+    }
+}
+
+impl fmt::Display for DecodedRenderingCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Nop => write!(f, "NOP"),
+            Self::ClearCache => write!(f, "ClearCache"),
+            Self::QuickRectangleFill {
+                color,
+                position,
+                size,
+            } => write!(
+                f,
+                "QuickRectangleFill {} at ({}, {}) size {}x{}",
+                Color(*color),
+                position.x(),
+                position.y(),
+                size.width(),
+                size.height()
+            ),
+            Self::InterruptRequest => write!(f, "InterruptRequest"),
+            Self::Polygon { cmd, vertices } => {
+                write!(f, "Polygon ({:?}", cmd.shading_mode())?;
+                if cmd.textured() {
+                    write!(f, ", textured")?;
+                }
+                if cmd.transparency_mode() == TransparencyMode::SemiTransparent {
+                    write!(f, ", semi-transparent")?;
+                }
+                write!(f, ")")?;
+
+                for vertex in vertices {
+                    write!(f, " {} {}", Position(vertex.position), Color(vertex.color))?;
+                    if let Some(uv) = &vertex.uv {
+                        write!(f, " uv ({}, {})", uv.u(), uv.v())?;
+                    }
+                }
+
+                Ok(())
+            }
+            Self::Line { cmd, vertices } => write!(
+                f,
+                "Line ({:?}) {} {} -> {} {}",
+                cmd.shading_mode(),
+                Position(vertices[0].position),
+                Color(vertices[0].color),
+                Position(vertices[1].position),
+                Color(vertices[1].color)
+            ),
+            Self::PolyLine { cmd } => write!(f, "PolyLine ({:?})", cmd.shading_mode()),
+            Self::Rectangle {
+                cmd,
+                position,
+                uv,
+                width,
+                height,
+            } => {
+                write!(
+                    f,
+                    "Rectangle {} at {} size {width}x{height}",
+                    Color(cmd.color()),
+                    Position(*position)
+                )?;
+
+                if let Some(uv) = uv {
+                    write!(f, " uv ({}, {})", uv.u(), uv.v())?;
+                }
+
+                Ok(())
+            }
+            Self::VramToVramBlit { src, dest, size } => write!(
+                f,
+                "VramToVramBlit from ({}, {}) to ({}, {}) size {}x{}",
+                src.x(),
+                src.y(),
+                dest.x(),
+                dest.y(),
+                size.width(),
+                size.height()
+            ),
+            Self::CpuToVramBlit { dest, size } => write!(
+                f,
+                "CpuToVramBlit to ({}, {}) size {}x{}",
+                dest.x(),
+                dest.y(),
+                size.width(),
+                size.height()
+            ),
+            Self::VramToCpuBlit { src, size } => write!(
+                f,
+                "VramToCpuBlit from ({}, {}) size {}x{}",
+                src.x(),
+                src.y(),
+                size.width(),
+                size.height()
+            ),
+            Self::DrawingSettings(cmd) => write!(f, "{cmd:?}"),
+            Self::TexWindowSettings(cmd) => write!(f, "{cmd:?}"),
+            Self::DrawingAreaTopLeft(cmd) => write!(f, "DrawingAreaTopLeft {cmd:?}"),
+            Self::DrawingAreaBottomRight(cmd) => write!(f, "DrawingAreaBottomRight {cmd:?}"),
+            Self::DrawingOffset(cmd) => write!(f, "{cmd:?}"),
+            Self::MaskSettings(cmd) => write!(f, "{cmd:?}"),
+            Self::Unknown(cmd) => write!(f, "unknown rendering command 0x{:08X}", cmd.to_bits()),
+        }
+    }
+}
+
+/// A fully decoded display command, i.e. a GP1 command.
+#[derive(Debug, Clone)]
+pub enum DecodedDisplayCommand {
+    ResetGpu,
+    ResetCommandBuffer,
+    AcknowledgeGpuInterrupt,
+    DisplayEnabled(DisplayEnableCmd),
+    DmaDirection(DmaDirectionCmd),
+    DisplayArea(DisplayAreaCmd),
+    HorizontalDisplayRange(HorizontalDisplayRangeCmd),
+    VerticalDisplayRange(VerticalDisplayRangeCmd),
+    DisplayMode(DisplayModeCmd),
+    AllowTextureDisable(AllowTextureDisableCmd),
+    /// Reads the internal register with the given index into GPUREAD.
+    ReadGpuRegister(u8),
+    VramSizeV1,
+    /// A command with an unknown opcode.
+    Unknown(DisplayCommand),
+}
+
+impl DecodedDisplayCommand {
+    /// Decodes the given GP1 word.
+    pub fn decode(word: u32) -> Self {
+        let cmd = DisplayCommand::from_bits(word);
+        let Some(opcode) = cmd.opcode() else {
+            return Self::Unknown(cmd);
+        };
+
+        match opcode {
+            DisplayOpcode::ResetGpu => Self::ResetGpu,
+            DisplayOpcode::ResetCommandBuffer => Self::ResetCommandBuffer,
+            DisplayOpcode::AcknowledgeGpuInterrupt => Self::AcknowledgeGpuInterrupt,
+            DisplayOpcode::DisplayEnabled => Self::DisplayEnabled(cmd.display_enable_cmd()),
+            DisplayOpcode::DmaDirection => Self::DmaDirection(cmd.dma_direction_cmd()),
+            DisplayOpcode::DisplayArea => Self::DisplayArea(cmd.display_area_cmd()),
+            DisplayOpcode::HorizontalDisplayRange => {
+                Self::HorizontalDisplayRange(cmd.horizontal_display_range_cmd())
+            }
+            DisplayOpcode::VerticalDisplayRange => {
+                Self::VerticalDisplayRange(cmd.vertical_display_range_cmd())
+            }
+            DisplayOpcode::DisplayMode => Self::DisplayMode(cmd.display_mode_cmd()),
+            DisplayOpcode::AllowTextureDisable => {
+                Self::AllowTextureDisable(cmd.allow_texture_disable_cmd())
+            }
+            DisplayOpcode::ReadGpuRegister => Self::ReadGpuRegister((word & 0b111) as u8),
+            DisplayOpcode::VramSizeV1 => Self::VramSizeV1,
+        }
+    }
+}
+
+impl fmt::Display for DecodedDisplayCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ResetGpu => write!(f, "ResetGpu"),
+            Self::ResetCommandBuffer => write!(f, "ResetCommandBuffer"),
+            Self::AcknowledgeGpuInterrupt => write!(f, "AcknowledgeGpuInterrupt"),
+            Self::DisplayEnabled(cmd) => write!(f, "DisplayEnabled({})", !cmd.disabled()),
+            Self::DmaDirection(cmd) => write!(f, "DmaDirection({:?})", cmd.direction()),
+            Self::DisplayArea(cmd) => write!(
+                f,
+                "DisplayArea at ({}, {})",
+                cmd.x().value(),
+                cmd.y().value()
+            ),
+            Self::HorizontalDisplayRange(cmd) => write!(
+                f,
+                "HorizontalDisplayRange {}..{}",
+                cmd.x1().value(),
+                cmd.x2().value()
+            ),
+            Self::VerticalDisplayRange(cmd) => write!(
+                f,
+                "VerticalDisplayRange {}..{}",
+                cmd.y1().value(),
+                cmd.y2().value()
+            ),
+            Self::DisplayMode(cmd) => write!(f, "{cmd:?}"),
+            Self::AllowTextureDisable(cmd) => {
+                write!(f, "AllowTextureDisable(allow: {})", cmd.allow())
+            }
+            Self::ReadGpuRegister(index) => write!(f, "ReadGpuRegister({index})"),
+            Self::VramSizeV1 => write!(f, "VramSizeV1"),
+            Self::Unknown(cmd) => write!(f, "unknown display command 0x{:08X}", cmd.to_bits()),
+        }
+    }
+}
+
+/// An item of a GP0 word stream.
+#[derive(Debug, Clone)]
+pub enum RenderingItem<'a> {
+    Command(DecodedRenderingCommand),
+    /// Data of the ongoing CPU to VRAM blit.
+    BlitData(&'a [u32]),
+    /// A vertex of the ongoing poly-line.
+    PolyLineVertex(LineVertex),
+    /// The end of the ongoing poly-line.
+    PolyLineEnd,
+}
+
+/// An item parsed by a [`CommandParser`].
+#[derive(Debug, Clone)]
+pub struct Parsed<'a> {
+    pub item: RenderingItem<'a>,
+    /// How many words this item consumed.
+    pub len: usize,
+}
+
+#[derive(Debug, Clone)]
+enum ParserState {
+    Idle,
+    BlitData { remaining: usize },
+    PolyLine { cmd: LineCmd, received: u32 },
+}
+
+/// A parser of GP0 word streams, such as the ones sent through DMA. Keeps track of ongoing CPU
+/// to VRAM blits and poly-lines, just like the GPU itself.
+#[derive(Debug, Clone)]
+pub struct CommandParser<'a> {
+    words: &'a [u32],
+    state: ParserState,
+}
+
+impl<'a> CommandParser<'a> {
+    pub fn new(words: &'a [u32]) -> Self {
+        Self {
+            words,
+            state: ParserState::Idle,
+        }
+    }
+
+    /// The words which haven't been parsed yet. After parsing is done, these are the words of an
+    /// incomplete command at the end of the stream, if any.
+    pub fn remaining(&self) -> &'a [u32] {
+        self.words
+    }
+}
+
+impl<'a> Iterator for CommandParser<'a> {
+    type Item = Parsed<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (item, len) = match &mut self.state {
+            ParserState::Idle => {
+                let (cmd, len) = DecodedRenderingCommand::decode(self.words)?;
+                match &cmd {
+                    DecodedRenderingCommand::CpuToVramBlit { size, .. } => {
+                        self.state = ParserState::BlitData {
+                            remaining: size.words(),
+                        };
+                    }
+                    DecodedRenderingCommand::PolyLine { cmd } => {
+                        self.state = ParserState::PolyLine {
+                            cmd: *cmd,
+                            received: 0,
+                        };
+                    }
+                    _ => (),
+                }
+
+                (RenderingItem::Command(cmd), len)
+            }
+            ParserState::BlitData { remaining } => {
+                if self.words.is_empty() {
+                    return None;
+                }
+
+                let len = (*remaining).min(self.words.len());
+                *remaining -= len;
+
+                let data = &self.words[..len];
+                if *remaining == 0 {
+                    self.state = ParserState::Idle;
+                }
+
+                (RenderingItem::BlitData(data), len)
+            }
+            ParserState::PolyLine { cmd, received } => {
+                if *received >= 2 && is_poly_line_end(*self.words.first()?) {
+                    self.state = ParserState::Idle;
+                    (RenderingItem::PolyLineEnd, 1)
+                } else {
+                    let color = (*received == 0 || cmd.shading_mode() == ShadingMode::Flat)
+                        .then_some(cmd.color());
+                    let (vertex, len) = LineVertex::decode(self.words, color)?;
+
+                    *received += 1;
+                    (RenderingItem::PolyLineVertex(vertex), len)
+                }
+            }
+        };
+
+        self.words = &self.words[len..];
+        Some(Parsed { item, len })
+    }
+}
@@ -5,7 +5,7 @@ use bitos::{bitos, integer::i11};
 
 /// A framebuffer transfer coordinate packet.
 #[bitos(32)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct CoordPacket {
     #[bits(0..16)]
     pub x: u16,
@@ -15,7 +15,7 @@ pub struct CoordPacket {
 
 /// A framebuffer transfer dimensions packet.
 #[bitos(32)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct SizePacket {
     #[bits(0..16)]
     pub width: u16,
@@ -23,6 +23,31 @@ pub struct SizePacket {
     pub height: u16,
 }
 
+impl SizePacket {
+    /// The width of the transfer in VRAM, after wrapping.
+    pub fn effective_width(&self) -> u16 {
+        if self.width() == 0 {
+            0x400
+        } else {
+            ((self.width() - 1) & 0x3FF) + 1
+        }
+    }
+
+    /// The height of the transfer in VRAM, after wrapping.
+    pub fn effective_height(&self) -> u16 {
+        if self.height() == 0 {
+            0x200
+        } else {
+            ((self.height() - 1) & 0x1FF) + 1
+        }
+    }
+
+    /// How many words of data a transfer of this size takes.
+    pub fn words(&self) -> usize {
+        (usize::from(self.effective_width()) * usize::from(self.effective_height())).div_ceil(2)
+    }
+}
+
 /// The texture mode of a rendering command.
 #[bitos(1)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -34,6 +59,7 @@ pub enum TextureMode {
 /// The transparency mode of a rendering command.
 #[bitos(1)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TransparencyMode {
     Opaque = 0,
     SemiTransparent = 1,
@@ -42,6 +68,7 @@ pub enum TransparencyMode {
 /// The shading mode of a rendering command.
 #[bitos(1)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ShadingMode {
     Flat = 0,
     Gouraud = 1,
@@ -78,7 +105,7 @@ impl VertexPositionPacket {
 
 /// A vertex UV packet.
 #[bitos(32)]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct VertexUVPacket {
     #[bits(0..8)]
     pub u: u8,
@@ -112,7 +139,7 @@ impl PolygonMode {
 
 /// A polygon rendering command.
 #[bitos(32)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct PolygonCmd {
     #[bits(0..8)]
     pub r: u8,
@@ -132,6 +159,13 @@ pub struct PolygonCmd {
     pub shading_mode: ShadingMode,
 }
 
+impl PolygonCmd {
+    /// The color of the first vertex.
+    pub fn color(&self) -> VertexColorPacket {
+        VertexColorPacket::from_bits(self.to_bits() & 0x00FF_FFFF)
+    }
+}
+
 /// The line mode of a [`LineCmd`].
 #[bitos(1)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -142,7 +176,7 @@ pub enum LineMode {
 
 /// A line rendering command.
 #[bitos(32)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct LineCmd {
     #[bits(0..8)]
     pub color_r: u8,
@@ -158,6 +192,13 @@ pub struct LineCmd {
     pub shading_mode: ShadingMode,
 }
 
+impl LineCmd {
+    /// The color of the first vertex.
+    pub fn color(&self) -> VertexColorPacket {
+        VertexColorPacket::from_bits(self.to_bits() & 0x00FF_FFFF)
+    }
+}
+
 /// The rectangle mode of a [`RectangleCmd`].
 #[bitos(2)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -170,7 +211,7 @@ pub enum RectangleMode {
 
 /// A rectangle rendering command.
 #[bitos(32)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct RectangleCmd {
     #[bits(0..8)]
     pub r: u8,
@@ -187,3 +228,10 @@ pub struct RectangleCmd {
     #[bits(27..29)]
     pub rectangle_mode: RectangleMode,
 }
+
+impl RectangleCmd {
+    /// The color of the rectangle.
+    pub fn color(&self) -> VertexColorPacket {
+        VertexColorPacket::from_bits(self.to_bits() & 0x00FF_FFFF)
+    }
+}
@@ -18,6 +18,7 @@ pub enum Depth {
 /// The blending mode of a texture.
 #[bitos(2)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BlendingMode {
     /// Final Color = Old / 2 + New / 2
     Half = 0,
@@ -66,3 +67,36 @@ pub struct Clut {
     #[bits(6..15)]
     pub y: u9,
 }
+
+/// Implements the serde traits for bitfield types through their raw bits.
+#[cfg(feature = "serde")]
+macro_rules! impl_serde_bits {
+    ($($type:ty),*) => {
+        $(
+            impl serde::Serialize for $type {
+                fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where
+                    S: serde::Serializer,
+                {
+                    serde::Serialize::serialize(&self.to_bits(), serializer)
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for $type {
+                fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    serde::Deserialize::deserialize(deserializer).map(Self::from_bits)
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "serde")]
+impl_serde_bits! {
+    TexPage,
+    TexWindow,
+    Clut
+}
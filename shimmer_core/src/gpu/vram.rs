@@ -0,0 +1,166 @@
+//! Normalization of the VRAM coordinates and sizes given to GPU commands.
+//!
+//! Each class of command masks its coordinates and sizes differently, so each has its own helper
+//! here and decoders should never mask them inline. Environment commands (drawing area, drawing
+//! offset) don't need one: their fields are already as wide as the hardware ones.
+
+use super::cmd::rendering::{CoordPacket, SizePacket};
+
+/// Width of VRAM, in pixels.
+pub const VRAM_WIDTH: u16 = 1024;
+/// Height of VRAM, in pixels.
+pub const VRAM_HEIGHT: u16 = 512;
+
+/// A rectangle in VRAM, with its coordinates and size already normalized. Rectangles may still
+/// wrap around the edges of VRAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VramRect {
+    /// Always less than [`VRAM_WIDTH`].
+    pub x: u16,
+    /// Always less than [`VRAM_HEIGHT`].
+    pub y: u16,
+    /// At most [`VRAM_WIDTH`].
+    pub width: u16,
+    /// At most [`VRAM_HEIGHT`].
+    pub height: u16,
+}
+
+/// Normalizes the rectangle of a quick rectangle fill (GP0(02h)).
+///
+/// - X is masked with `0x3F0`, i.e. rounded down to a multiple of 16 pixels.
+/// - Y is masked with `0x1FF`.
+/// - The width is masked with `0x3FF` and then rounded up to a multiple of 16 pixels, so widths
+///   from `0x3F1` to `0x3FF` fill the whole VRAM width.
+/// - The height is masked with `0x1FF`.
+///
+/// Unlike blits, a width or height of zero fills nothing.
+pub fn fill_rect(position: CoordPacket, size: SizePacket) -> VramRect {
+    VramRect {
+        x: position.x() & 0x3F0,
+        y: position.y() & 0x1FF,
+        width: ((size.width() & 0x3FF) + 0xF) & !0xF,
+        height: size.height() & 0x1FF,
+    }
+}
+
+/// Normalizes the position of a blit (GP0(80h), GP0(A0h) and GP0(C0h)): X is masked with `0x3FF`
+/// and Y with `0x1FF`.
+pub fn blit_position(position: CoordPacket) -> (u16, u16) {
+    (position.x() & 0x3FF, position.y() & 0x1FF)
+}
+
+/// Normalizes the rectangle of a blit (GP0(80h), GP0(A0h) and GP0(C0h)). The position is
+/// normalized as in [`blit_position`] and the size as in [`SizePacket::effective_width`] and
+/// [`SizePacket::effective_height`]: a width or height of zero means the whole VRAM width or
+/// height.
+pub fn blit_rect(position: CoordPacket, size: SizePacket) -> VramRect {
+    let (x, y) = blit_position(position);
+    VramRect {
+        x,
+        y,
+        width: size.effective_width(),
+        height: size.effective_height(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coords(x: u16, y: u16) -> CoordPacket {
+        CoordPacket::from_bits(u32::from(x) | (u32::from(y) << 16))
+    }
+
+    fn size(width: u16, height: u16) -> SizePacket {
+        SizePacket::from_bits(u32::from(width) | (u32::from(height) << 16))
+    }
+
+    fn rect(x: u16, y: u16, width: u16, height: u16) -> VramRect {
+        VramRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn fill_rounds_x_down_and_width_up_to_16_pixels() {
+        assert_eq!(fill_rect(coords(0x1F, 0), size(1, 1)), rect(0x10, 0, 16, 1));
+        assert_eq!(
+            fill_rect(coords(0x20, 0), size(16, 1)),
+            rect(0x20, 0, 16, 1)
+        );
+        assert_eq!(
+            fill_rect(coords(0x3FF, 0), size(17, 1)),
+            rect(0x3F0, 0, 32, 1)
+        );
+    }
+
+    #[test]
+    fn fill_masks_positions_to_vram() {
+        assert_eq!(
+            fill_rect(coords(0xFC10, 0xFE05), size(16, 1)),
+            rect(0x010, 0x005, 16, 1)
+        );
+        assert_eq!(fill_rect(coords(0, 0x200), size(16, 1)), rect(0, 0, 16, 1));
+    }
+
+    #[test]
+    fn fill_sizes_of_zero_fill_nothing() {
+        assert_eq!(fill_rect(coords(0, 0), size(0, 0)), rect(0, 0, 0, 0));
+        // only the low bits of the size count, so these wrap around to zero
+        assert_eq!(
+            fill_rect(coords(0, 0), size(0x400, 0x200)),
+            rect(0, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn fill_widths_past_the_last_multiple_of_16_fill_all_of_vram() {
+        for width in 0x3F1..=0x3FF {
+            assert_eq!(fill_rect(coords(0, 0), size(width, 1)).width, VRAM_WIDTH);
+        }
+
+        assert_eq!(
+            fill_rect(coords(0, 0), size(0x3F0, 0x1FF)),
+            rect(0, 0, 0x3F0, 0x1FF)
+        );
+    }
+
+    #[test]
+    fn blit_positions_wrap_around_vram() {
+        assert_eq!(blit_position(coords(0x3FF, 0x1FF)), (0x3FF, 0x1FF));
+        assert_eq!(blit_position(coords(0x400, 0x200)), (0, 0));
+        assert_eq!(blit_position(coords(0xFFFF, 0xFFFF)), (0x3FF, 0x1FF));
+        assert_eq!(blit_position(coords(0x40F, 0x20F)), (0xF, 0xF));
+    }
+
+    #[test]
+    fn blit_sizes_of_zero_mean_all_of_vram() {
+        assert_eq!(
+            blit_rect(coords(0, 0), size(0, 0)),
+            rect(0, 0, 0x400, 0x200)
+        );
+        assert_eq!(
+            blit_rect(coords(0, 0), size(0x400, 0x200)),
+            rect(0, 0, 0x400, 0x200)
+        );
+        assert_eq!(
+            blit_rect(coords(0, 0), size(0x401, 0x201)),
+            rect(0, 0, 1, 1)
+        );
+    }
+
+    #[test]
+    fn blit_sizes_are_not_rounded() {
+        assert_eq!(
+            blit_rect(coords(0x7, 0x3), size(3, 5)),
+            rect(0x7, 0x3, 3, 5)
+        );
+        assert_eq!(
+            blit_rect(coords(0x3FF, 0x1FF), size(2, 2)),
+            rect(0x3FF, 0x1FF, 2, 2)
+        );
+    }
+}
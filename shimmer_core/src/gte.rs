@@ -2,6 +2,7 @@ pub mod fixed;
 pub mod instr;
 
 use bitos::{BitUtils, bitos};
+use strum::VariantArray;
 use zerocopy::transmute_mut;
 
 pub type Int44 = fixed::Integer<44>;
@@ -93,7 +94,7 @@ pub enum ControlReg {
 }
 
 #[bitos(6)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, VariantArray)]
 #[repr(u8)]
 pub enum Reg {
     R0,
@@ -243,6 +244,75 @@ impl Reg {
     pub const ZSF4: Reg = Reg::R62;
 
     pub const FLAG: Reg = Reg::R63;
+
+    pub fn alt_name(&self) -> &'static str {
+        match self {
+            Reg::R0 => "VXY0",
+            Reg::R1 => "VZ0",
+            Reg::R2 => "VXY1",
+            Reg::R3 => "VZ1",
+            Reg::R4 => "VXY2",
+            Reg::R5 => "VZ2",
+            Reg::R6 => "RGBC",
+            Reg::R7 => "OTZ",
+            Reg::R8 => "IR0",
+            Reg::R9 => "IR1",
+            Reg::R10 => "IR2",
+            Reg::R11 => "IR3",
+            Reg::R12 => "SXY0",
+            Reg::R13 => "SXY1",
+            Reg::R14 => "SXY2",
+            Reg::R15 => "SXYP",
+            Reg::R16 => "SZ0",
+            Reg::R17 => "SZ1",
+            Reg::R18 => "SZ2",
+            Reg::R19 => "SZ3",
+            Reg::R20 => "RGB0",
+            Reg::R21 => "RGB1",
+            Reg::R22 => "RGB2",
+            Reg::R23 => "RES1",
+            Reg::R24 => "MAC0",
+            Reg::R25 => "MAC1",
+            Reg::R26 => "MAC2",
+            Reg::R27 => "MAC3",
+            Reg::R28 => "IRGB",
+            Reg::R29 => "ORGB",
+            Reg::R30 => "LZCS",
+            Reg::R31 => "LZCR",
+            Reg::R32 => "RT11RT12",
+            Reg::R33 => "RT13RT21",
+            Reg::R34 => "RT22RT23",
+            Reg::R35 => "RT31RT32",
+            Reg::R36 => "RT33",
+            Reg::R37 => "TRX",
+            Reg::R38 => "TRY",
+            Reg::R39 => "TRZ",
+            Reg::R40 => "L11L12",
+            Reg::R41 => "L13L21",
+            Reg::R42 => "L22L23",
+            Reg::R43 => "L31L32",
+            Reg::R44 => "L33",
+            Reg::R45 => "RBK",
+            Reg::R46 => "GBK",
+            Reg::R47 => "BBK",
+            Reg::R48 => "LR1LR2",
+            Reg::R49 => "LR3LG1",
+            Reg::R50 => "LG2LG3",
+            Reg::R51 => "LB1LB2",
+            Reg::R52 => "LB3",
+            Reg::R53 => "RFC",
+            Reg::R54 => "GFC",
+            Reg::R55 => "BFC",
+            Reg::R56 => "OFX",
+            Reg::R57 => "OFY",
+            Reg::R58 => "H",
+            Reg::R59 => "DQA",
+            Reg::R60 => "DQB",
+            Reg::R61 => "ZSF3",
+            Reg::R62 => "ZSF4",
+            Reg::R63 => "FLAG",
+        }
+    }
 }
 
 impl From<DataReg> for Reg {
@@ -257,6 +327,7 @@ impl From<ControlReg> for Reg {
     }
 }
 
+/// A bit of the FLAG register.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Flag {
     ClampedIR0 = 12,
@@ -280,6 +351,14 @@ pub enum Flag {
     OverflowedMAC1 = 30,
 }
 
+impl Flag {
+    /// The writable bits of FLAG. Bits 0..=11 always read as zero, and bit 31 is computed on
+    /// read from the flags in [`Flag::ERROR_MASK`].
+    pub const WRITE_MASK: u32 = 0x7FFF_F000;
+    /// The flags which set the error bit (bit 31) of FLAG: bits 13..=18 and 23..=30.
+    pub const ERROR_MASK: u32 = 0x7F87_E000;
+}
+
 /// The registers of the GTE.
 #[derive(Clone)]
 pub struct Registers([u32; 64]);
@@ -297,7 +376,7 @@ impl Registers {
             Reg::H => self.0[reg as usize] as i16 as i32 as u32,
             Reg::FLAG => {
                 let value = self.0[reg as usize];
-                let err_flag = (value.bits(23, 31) | value.bits(13, 19)) > 0;
+                let err_flag = value & Flag::ERROR_MASK != 0;
                 value | if err_flag { 0x8000_0000 } else { 0 }
             }
             _ => self.0[reg as usize],
@@ -331,7 +410,7 @@ impl Registers {
                 self.update_irgb();
             }
             Reg::ORGB => {
-                // do nothing
+                // read-only: it mirrors IRGB, which is computed from IR1..=IR3
             }
             Reg::LZCS => {
                 self.0[reg as usize] = value;
@@ -342,14 +421,14 @@ impl Registers {
                 };
             }
             Reg::LZCR => {
-                // do nothing
+                // read-only: it's computed from LZCS
             }
             Reg::RT_33_SS | Reg::L_33_SS | Reg::L_B3_SS => {
                 self.0[reg as usize] = value as i16 as i32 as u32
             }
             Reg::DQA => self.0[reg as usize] = value as i16 as i32 as u32,
             Reg::ZSF3 | Reg::ZSF4 => self.0[reg as usize] = value as i16 as i32 as u32,
-            Reg::FLAG => self.0[reg as usize] = value & !0x8000_0FFF,
+            Reg::FLAG => self.0[reg as usize] = value & Flag::WRITE_MASK,
             _ => self.0[reg as usize] = value,
         }
     }
@@ -540,3 +619,96 @@ impl Registers {
 pub struct Gte {
     pub regs: Registers,
 }
+
+/// A register whose value differs between two snapshots of the GTE registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegChange {
+    pub reg: Reg,
+    pub old: u32,
+    pub new: u32,
+}
+
+impl std::fmt::Display for RegChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: 0x{:08X} -> 0x{:08X}",
+            self.reg.alt_name(),
+            self.old,
+            self.new
+        )
+    }
+}
+
+impl Gte {
+    /// Returns the value of every register, indexed by register number, as the CPU would read
+    /// them.
+    pub fn snapshot(&self) -> [u32; 64] {
+        std::array::from_fn(|index| self.regs.read(Reg::VARIANTS[index]))
+    }
+
+    /// Returns the registers whose values differ between the snapshots `old` and `new`, in
+    /// register order.
+    pub fn diff(old: &[u32; 64], new: &[u32; 64]) -> Vec<RegChange> {
+        Reg::VARIANTS
+            .iter()
+            .zip(old.iter().zip(new))
+            .filter(|(_, (old, new))| old != new)
+            .map(|(&reg, (&old, &new))| RegChange { reg, old, new })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a mask with the bits in `bits` set.
+    fn mask(bits: impl IntoIterator<Item = u32>) -> u32 {
+        bits.into_iter().fold(0, |mask, bit| mask | (1 << bit))
+    }
+
+    #[test]
+    fn flag_masks_match_hardware() {
+        assert_eq!(Flag::WRITE_MASK, mask(12..=30));
+        assert_eq!(Flag::ERROR_MASK, mask((13..=18).chain(23..=30)));
+    }
+
+    #[test]
+    fn flag_writes_only_change_writable_bits() {
+        let mut regs = Registers::default();
+
+        regs.write(Reg::FLAG, 0xFFFF_FFFF);
+        assert_eq!(regs.read(Reg::FLAG), 0xFFFF_F000);
+
+        // bits 0..=11 and 31 can't be written, so this clears everything
+        regs.write(Reg::FLAG, 0x8000_0FFF);
+        assert_eq!(regs.read(Reg::FLAG), 0);
+
+        // the error bit is computed from the flags
+        for bit in 12..=30 {
+            regs.write(Reg::FLAG, 1 << bit);
+            let error = Flag::ERROR_MASK & (1 << bit) != 0;
+            assert_eq!(
+                regs.read(Reg::FLAG),
+                (1 << bit) | (u32::from(error) << 31),
+                "bit {bit}"
+            );
+        }
+    }
+
+    #[test]
+    fn orgb_and_lzcr_are_read_only() {
+        let mut regs = Registers::default();
+        regs.write(Reg::IRGB, 0x7FFF);
+        regs.write(Reg::LZCS, 0x0000_FFFF);
+        assert_eq!(regs.read(Reg::ORGB), 0x7FFF);
+        assert_eq!(regs.read(Reg::LZCR), 16);
+
+        regs.write(Reg::ORGB, 0x1234);
+        regs.write(Reg::LZCR, 5);
+        assert_eq!(regs.read(Reg::ORGB), 0x7FFF);
+        assert_eq!(regs.read(Reg::LZCR), 16);
+        assert_eq!(regs.read(Reg::IRGB), 0x7FFF);
+    }
+}
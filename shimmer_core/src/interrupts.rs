@@ -89,6 +89,10 @@ pub struct Mask {
     enabled: [bool; 10],
 }
 
+impl Mask {
+    pub const WRITE_MASK: u64 = Self::ENABLED_MASK;
+}
+
 impl std::fmt::Debug for Mask {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_set()
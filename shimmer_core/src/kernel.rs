@@ -1,68 +1,254 @@
 //! Items related to the kernel of the PSX.
 
+/// The kind of an argument of a kernel function. Used to decide how an argument should be
+/// displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// A signed integer.
+    Int,
+    /// A raw value, such as flags or a handle.
+    Hex,
+    /// A pointer to guest memory.
+    Pointer,
+    /// A pointer to a null terminated string in guest memory.
+    CStr,
+    /// A single character.
+    Char,
+    /// A file descriptor, as returned by `open`.
+    Fd,
+    /// An event class (see [`event_class_name`]).
+    EventClass,
+    /// An event spec (see [`event_spec_name`]).
+    EventSpec,
+}
+
 /// A kernel function.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Function {
+    Abs,
     AddCDROMDevice,
     AddDrv,
+    AddDuartTtyDevice,
     AddMemCardDevice,
     AddNullconDriver,
     AdjustA0Table,
     AllocKernelMemory,
+    AllowNewCard,
+    Atob,
+    Atof,
+    Atoi,
+    Atol,
     BZero,
+    Bcmp,
+    Bcopy,
+    Bsearch,
+    BuCallbackErrBusy,
+    BuCallbackErrEject,
+    BuCallbackErrPrevWrite,
+    BuCallbackErrWrite,
+    BuCallbackOkay,
+    BuInit,
+    Calloc,
+    CardAsyncLoadDirectory,
+    CardInfo,
+    CardInfoSubFunc,
+    CardReadSector,
+    CardWriteSector,
+    CardWriteTest,
     CdAsyncGetStatus,
     CdAsyncReadSector,
     CdAsyncSeekL,
+    CdAsyncSetMode,
+    CdGetLbn,
+    CdGetStatus,
+    CdInit,
     CdInitSubFunc,
+    CdReadSector,
+    CdromDmaIrqFunc1,
+    CdromDmaIrqFunc2,
+    CdromGetInt5ErrCode,
+    CdromIoIrqFunc1,
+    CdromIoIrqFunc2,
     ChangeClearPAD,
     ChangeClearRCnt,
+    ChangeThread,
+    CharToLower,
     CharToUpper,
+    Chdir,
     Close,
     CloseEvent,
+    CloseThread,
+    DelDrv,
+    Delete,
     DeliverEvent,
     DequeueCdIntr,
     DequeueInterruptRP,
+    DevCardClearError,
+    DevCardClose,
+    DevCardErase,
+    DevCardFirstFile,
+    DevCardFormat,
+    DevCardNextFile,
+    DevCardOpen,
+    DevCardRead,
+    DevCardRename,
+    DevCardUndelete,
+    DevCardWrite,
+    DevCdChdir,
+    DevCdClose,
+    DevCdFirstFile,
+    DevCdNextFile,
+    DevCdOpen,
+    DevCdRead,
+    DevSioInOut,
+    DevSioInit,
+    DevSioIoctl,
+    DevSioOpen,
+    DevTtyInOut,
+    DevTtyInit,
+    DevTtyIoctl,
+    DevTtyOpen,
+    DisableEvent,
+    DisableTimerIrq,
     EnableEvent,
     EnableTimerIrq,
     EnqueueCdIntr,
     EnqueueInterruptRP,
     EnqueueSyscallHandler,
     EnqueueTimerAndVblankIrqs,
+    ExceptionHandler,
+    Exec,
+    Exit,
+    FirstFile,
     FlushCache,
+    FlushStdInOutPut,
+    FormatDevice,
     Free,
+    FreeKernelMemory,
+    GetB0Table,
+    GetBuCallbackPort,
+    GetC0Table,
+    GetCardFindMode,
+    GetCardStatus,
+    GetChar,
+    GetConf,
+    GetDeviceFlag,
+    GetFreeEvCBSlot,
+    GetFreeTCBSlot,
+    GetGpuStatus,
+    GetLastError,
+    GetLastFileError,
+    GetSysSp,
+    GetSystemInfo,
+    GetTimer,
+    Getc,
+    Gets,
+    GpuAbortDma,
+    GpuCwp,
+    GpuDw,
+    GpuSendDma,
+    GpuSync,
     HookEntryInt,
+    Index,
+    InitA0B0C0Vectors,
+    InitCard,
     InitDefInt,
     InitHeap,
     InitPad2,
     InitTimer,
     InstallDevices,
     InstallExceptionHandlers,
+    IoAbort,
+    IoAbortRaw,
+    Ioctl,
+    KernelRedirect,
+    Krom2Offset,
+    Krom2RawAdd,
+    Labs,
+    Load,
+    LoadExec,
+    LoadTest,
+    LongJmp,
+    Lsearch,
     Malloc,
+    Memchr,
+    Memcmp,
     Memcpy,
+    Memmove,
     Memset,
+    NextFile,
     Open,
     OpenEvent,
+    OpenThread,
+    PadGetButtons,
+    PadInitAndStart,
+    PrintInstalledDevices,
     Printf,
     PutChar,
+    Putc,
     Puts,
+    Qsort,
     Rand,
     Read,
+    Realloc,
     Remove96,
+    Rename,
     ResetEntryInt,
     RestartTimer,
+    /// C(14h), an unnamed stub which does nothing and returns 0.
+    ReturnZero,
     ReturnFromException,
+    Rindex,
+    Seek,
+    SendGp1Command,
     SendGpuCommandWord,
+    SendGpuLinkedList,
+    SetCardAutoFormat,
+    SetCardFindMode,
+    SetCdromIrqAutoAbort,
+    SetConf,
+    SetIrqAutoAck,
     SetJmp,
+    SetMemSize,
+    Srand,
+    StartCard,
     StartPad2,
+    StopCard,
+    StopPad2,
+    Strcat,
+    Strchr,
     Strcmp,
     Strcpy,
+    Strcspn,
     Strlen,
     Strncat,
     Strncmp,
+    Strncpy,
+    Strpbrk,
+    Strrchr,
+    Strspn,
+    Strstr,
+    Strtod,
+    Strtok,
+    Strtol,
+    Strtoul,
+    SysInitKernelVariables,
     SysInitMemory,
     SystemError,
+    SystemErrorExit,
+    TestDevice,
     TestEvent,
+    ToDigit,
+    TtyCdevInput,
+    TtyCdevScan,
+    TtyCircGetc,
+    TtyCircPutc,
+    Undelete,
     UndeliverEvent,
+    UnresolvedException,
+    WaitCardStatus,
+    WaitEvent,
+    WarmBoot,
     Write,
 }
 
@@ -72,39 +258,146 @@ impl Function {
     pub fn a0(code: u8) -> Option<Self> {
         Some(match code {
             0x00 => Self::Open,
+            0x01 => Self::Seek,
             0x02 => Self::Read,
             0x03 => Self::Write,
             0x04 => Self::Close,
+            0x05 => Self::Ioctl,
+            0x06 => Self::Exit,
+            0x07 => Self::GetDeviceFlag,
+            0x08 => Self::Getc,
+            0x09 => Self::Putc,
+            0x0A => Self::ToDigit,
+            0x0B => Self::Atof,
+            0x0C => Self::Strtoul,
+            0x0D => Self::Strtol,
+            0x0E => Self::Abs,
+            0x0F => Self::Labs,
+            0x10 => Self::Atoi,
+            0x11 => Self::Atol,
+            0x12 => Self::Atob,
             0x13 => Self::SetJmp,
+            0x14 => Self::LongJmp,
+            0x15 => Self::Strcat,
             0x16 => Self::Strncat,
             0x17 => Self::Strcmp,
             0x18 => Self::Strncmp,
             0x19 => Self::Strcpy,
+            0x1A => Self::Strncpy,
             0x1B => Self::Strlen,
+            0x1C => Self::Index,
+            0x1D => Self::Rindex,
+            0x1E => Self::Strchr,
+            0x1F => Self::Strrchr,
+            0x20 => Self::Strpbrk,
+            0x21 => Self::Strspn,
+            0x22 => Self::Strcspn,
+            0x23 => Self::Strtok,
+            0x24 => Self::Strstr,
             0x25 => Self::CharToUpper,
+            0x26 => Self::CharToLower,
+            0x27 => Self::Bcopy,
             0x28 => Self::BZero,
+            0x29 => Self::Bcmp,
             0x2A => Self::Memcpy,
             0x2B => Self::Memset,
+            0x2C => Self::Memmove,
+            0x2D => Self::Memcmp,
+            0x2E => Self::Memchr,
             0x2F => Self::Rand,
+            0x30 => Self::Srand,
+            0x31 => Self::Qsort,
+            0x32 => Self::Strtod,
             0x33 => Self::Malloc,
             0x34 => Self::Free,
+            0x35 => Self::Lsearch,
+            0x36 => Self::Bsearch,
+            0x37 => Self::Calloc,
+            0x38 => Self::Realloc,
             0x39 => Self::InitHeap,
-            0x3B => Self::PutChar,
+            0x3A => Self::SystemErrorExit,
+            0x3B => Self::GetChar,
+            0x3C => Self::PutChar,
+            0x3D => Self::Gets,
             0x3E => Self::Puts,
             0x3F => Self::Printf,
+            0x40 => Self::UnresolvedException,
+            0x41 => Self::LoadTest,
+            0x42 => Self::Load,
+            0x43 => Self::Exec,
             0x44 => Self::FlushCache,
+            0x45 => Self::InitA0B0C0Vectors,
+            0x46 => Self::GpuDw,
+            0x47 => Self::GpuSendDma,
+            0x48 => Self::SendGp1Command,
             0x49 => Self::SendGpuCommandWord,
+            0x4A => Self::GpuCwp,
+            0x4B => Self::SendGpuLinkedList,
+            0x4C => Self::GpuAbortDma,
+            0x4D => Self::GetGpuStatus,
+            0x4E => Self::GpuSync,
+            0x4F | 0x50 | 0x53 | 0x9A | 0x9B | 0xA1 => Self::SystemError,
+            0x51 => Self::LoadExec,
+            0x52 => Self::GetSysSp,
+            0x54 | 0x71 => Self::CdInit,
+            0x55 | 0x70 => Self::BuInit,
             0x56 | 0x72 => Self::Remove96,
+            0x5B => Self::DevTtyInit,
+            0x5C => Self::DevTtyOpen,
+            0x5D => Self::DevTtyInOut,
+            0x5E => Self::DevTtyIoctl,
+            0x5F => Self::DevCdOpen,
+            0x60 => Self::DevCdRead,
+            0x61 => Self::DevCdClose,
+            0x62 => Self::DevCdFirstFile,
+            0x63 => Self::DevCdNextFile,
+            0x64 => Self::DevCdChdir,
+            0x65 => Self::DevCardOpen,
+            0x66 => Self::DevCardRead,
+            0x67 => Self::DevCardWrite,
+            0x68 => Self::DevCardClose,
+            0x69 => Self::DevCardFirstFile,
+            0x6A => Self::DevCardNextFile,
+            0x6B => Self::DevCardErase,
+            0x6C => Self::DevCardUndelete,
+            0x6D => Self::DevCardFormat,
+            0x6E => Self::DevCardRename,
+            0x6F => Self::DevCardClearError,
             0x78 => Self::CdAsyncSeekL,
             0x7C => Self::CdAsyncGetStatus,
             0x7E => Self::CdAsyncReadSector,
+            0x81 => Self::CdAsyncSetMode,
+            0x90 => Self::CdromIoIrqFunc1,
+            0x91 => Self::CdromDmaIrqFunc1,
+            0x92 => Self::CdromIoIrqFunc2,
+            0x93 => Self::CdromDmaIrqFunc2,
+            0x94 => Self::CdromGetInt5ErrCode,
             0x95 => Self::CdInitSubFunc,
             0x96 => Self::AddCDROMDevice,
             0x97 => Self::AddMemCardDevice,
+            0x98 => Self::AddDuartTtyDevice,
             0x99 => Self::AddNullconDriver,
-            0xA1 => Self::SystemError,
+            0x9C => Self::SetConf,
+            0x9D => Self::GetConf,
+            0x9E => Self::SetCdromIrqAutoAbort,
+            0x9F => Self::SetMemSize,
+            0xA0 => Self::WarmBoot,
             0xA2 => Self::EnqueueCdIntr,
             0xA3 => Self::DequeueCdIntr,
+            0xA4 => Self::CdGetLbn,
+            0xA5 => Self::CdReadSector,
+            0xA6 => Self::CdGetStatus,
+            0xA7 => Self::BuCallbackOkay,
+            0xA8 => Self::BuCallbackErrWrite,
+            0xA9 => Self::BuCallbackErrBusy,
+            0xAA => Self::BuCallbackErrEject,
+            0xAB => Self::CardInfo,
+            0xAC => Self::CardAsyncLoadDirectory,
+            0xAD => Self::SetCardAutoFormat,
+            0xAE => Self::BuCallbackErrPrevWrite,
+            0xAF => Self::CardWriteTest,
+            0xB2 => Self::IoAbortRaw,
+            0xB4 => Self::GetSystemInfo,
             _ => return None,
         })
     }
@@ -114,28 +407,74 @@ impl Function {
     pub fn b0(code: u8) -> Option<Self> {
         Some(match code {
             0x00 => Self::AllocKernelMemory,
+            0x01 => Self::FreeKernelMemory,
             0x02 => Self::InitTimer,
+            0x03 => Self::GetTimer,
             0x04 => Self::EnableTimerIrq,
+            0x05 => Self::DisableTimerIrq,
             0x06 => Self::RestartTimer,
             0x07 => Self::DeliverEvent,
             0x08 => Self::OpenEvent,
             0x09 => Self::CloseEvent,
+            0x0A => Self::WaitEvent,
             0x0B => Self::TestEvent,
             0x0C => Self::EnableEvent,
+            0x0D => Self::DisableEvent,
+            0x0E => Self::OpenThread,
+            0x0F => Self::CloseThread,
+            0x10 => Self::ChangeThread,
             0x12 => Self::InitPad2,
             0x13 => Self::StartPad2,
+            0x14 => Self::StopPad2,
+            0x15 => Self::PadInitAndStart,
+            0x16 => Self::PadGetButtons,
             0x17 => Self::ReturnFromException,
             0x18 => Self::ResetEntryInt,
             0x19 => Self::HookEntryInt,
             0x20 => Self::UndeliverEvent,
             0x32 => Self::Open,
+            0x33 => Self::Seek,
             0x34 => Self::Read,
             0x35 => Self::Write,
             0x36 => Self::Close,
+            0x37 => Self::Ioctl,
+            0x38 => Self::Exit,
+            0x39 => Self::GetDeviceFlag,
+            0x3A => Self::Getc,
+            0x3B => Self::Putc,
+            0x3C => Self::GetChar,
             0x3D => Self::PutChar,
+            0x3E => Self::Gets,
             0x3F => Self::Puts,
+            0x40 => Self::Chdir,
+            0x41 => Self::FormatDevice,
+            0x42 => Self::FirstFile,
+            0x43 => Self::NextFile,
+            0x44 => Self::Rename,
+            0x45 => Self::Delete,
+            0x46 => Self::Undelete,
             0x47 => Self::AddDrv,
+            0x48 => Self::DelDrv,
+            0x49 => Self::PrintInstalledDevices,
+            0x4A => Self::InitCard,
+            0x4B => Self::StartCard,
+            0x4C => Self::StopCard,
+            0x4D => Self::CardInfoSubFunc,
+            0x4E => Self::CardWriteSector,
+            0x4F => Self::CardReadSector,
+            0x50 => Self::AllowNewCard,
+            0x51 => Self::Krom2RawAdd,
+            0x52 | 0x5A => Self::SystemError,
+            0x53 => Self::Krom2Offset,
+            0x54 => Self::GetLastError,
+            0x55 => Self::GetLastFileError,
+            0x56 => Self::GetC0Table,
+            0x57 => Self::GetB0Table,
+            0x58 => Self::GetBuCallbackPort,
+            0x59 => Self::TestDevice,
             0x5B => Self::ChangeClearPAD,
+            0x5C => Self::GetCardStatus,
+            0x5D => Self::WaitCardStatus,
             _ => return None,
         })
     }
@@ -148,67 +487,283 @@ impl Function {
             0x01 => Self::EnqueueSyscallHandler,
             0x02 => Self::EnqueueInterruptRP,
             0x03 => Self::DequeueInterruptRP,
+            0x04 => Self::GetFreeEvCBSlot,
+            0x05 => Self::GetFreeTCBSlot,
+            0x06 => Self::ExceptionHandler,
             0x07 => Self::InstallExceptionHandlers,
             0x08 => Self::SysInitMemory,
+            0x09 => Self::SysInitKernelVariables,
             0x0A => Self::ChangeClearRCnt,
+            0x0B => Self::SystemError,
             0x0C => Self::InitDefInt,
+            0x0D => Self::SetIrqAutoAck,
+            0x0E => Self::DevSioInit,
+            0x0F => Self::DevSioOpen,
+            0x10 => Self::DevSioInOut,
+            0x11 => Self::DevSioIoctl,
             0x12 => Self::InstallDevices,
+            0x13 => Self::FlushStdInOutPut,
+            0x14 => Self::ReturnZero,
+            0x15 => Self::TtyCdevInput,
+            0x16 => Self::TtyCdevScan,
+            0x17 => Self::TtyCircGetc,
+            0x18 => Self::TtyCircPutc,
+            0x19 => Self::IoAbort,
+            0x1A => Self::SetCardFindMode,
+            0x1B => Self::KernelRedirect,
             0x1C => Self::AdjustA0Table,
+            0x1D => Self::GetCardFindMode,
             _ => return None,
         })
     }
 
-    /// Returns the amount of arguments required by this function.
-    pub fn args(&self) -> usize {
+    /// Returns the arguments taken by this function. Arguments past the fourth are passed on the
+    /// stack.
+    pub fn args(&self) -> &'static [ArgKind] {
+        use ArgKind::{CStr, Char, EventClass, EventSpec, Fd, Hex, Int, Pointer};
+
         match self {
-            Self::AddDrv => 1,
-            Self::AllocKernelMemory => 1,
-            Self::BZero => 2,
-            Self::CdAsyncGetStatus => 1,
-            Self::CdAsyncReadSector => 3,
-            Self::CdAsyncSeekL => 1,
-            Self::ChangeClearPAD => 1,
-            Self::ChangeClearRCnt => 2,
-            Self::CharToUpper => 1,
-            Self::Close => 1,
-            Self::CloseEvent => 1,
-            Self::DeliverEvent => 1,
-            Self::DequeueInterruptRP => 2,
-            Self::EnableEvent => 1,
-            Self::EnableTimerIrq => 1,
-            Self::EnqueueInterruptRP => 2,
-            Self::EnqueueSyscallHandler => 1,
-            Self::EnqueueTimerAndVblankIrqs => 1,
-            Self::Free => 1,
-            Self::HookEntryInt => 1,
-            Self::InitDefInt => 1,
-            Self::InitHeap => 2,
-            Self::InitPad2 => 4,
-            Self::InitTimer => 3,
-            Self::InstallDevices => 1,
-            Self::Malloc => 1,
-            Self::Memcpy => 3,
-            Self::Memset => 3,
-            Self::Open => 2,
-            Self::OpenEvent => 4,
-            Self::Printf => 4,
-            Self::PutChar => 1,
-            Self::Puts => 1,
-            Self::Read => 3,
-            Self::RestartTimer => 1,
-            Self::SendGpuCommandWord => 1,
-            Self::SetJmp => 1,
-            Self::Strcmp => 2,
-            Self::Strcpy => 2,
-            Self::Strlen => 1,
-            Self::Strncat => 3,
-            Self::Strncmp => 3,
-            Self::SysInitMemory => 2,
-            Self::SystemError => 2,
-            Self::TestEvent => 1,
-            Self::UndeliverEvent => 2,
-            Self::Write => 3,
-            _ => 0,
+            Self::Abs => &[Int],
+            Self::AddDrv => &[Pointer],
+            Self::AllocKernelMemory => &[Int],
+            Self::Atob => &[CStr, Pointer],
+            Self::Atof => &[CStr],
+            Self::Atoi => &[CStr],
+            Self::Atol => &[CStr],
+            Self::BZero => &[Pointer, Int],
+            Self::Bcmp => &[Pointer, Pointer, Int],
+            Self::Bcopy => &[Pointer, Pointer, Int],
+            Self::Bsearch => &[Pointer, Pointer, Int, Int, Pointer],
+            Self::Calloc => &[Int, Int],
+            Self::CardAsyncLoadDirectory => &[Int],
+            Self::CardInfo => &[Int],
+            Self::CardInfoSubFunc => &[Int],
+            Self::CardReadSector => &[Int, Int, Pointer],
+            Self::CardWriteSector => &[Int, Int, Pointer],
+            Self::CardWriteTest => &[Int],
+            Self::CdAsyncGetStatus => &[Pointer],
+            Self::CdAsyncReadSector => &[Int, Pointer, Hex],
+            Self::CdAsyncSeekL => &[Pointer],
+            Self::CdAsyncSetMode => &[Hex],
+            Self::CdGetLbn => &[CStr],
+            Self::CdReadSector => &[Int, Int, Pointer],
+            Self::CdromGetInt5ErrCode => &[Pointer, Pointer],
+            Self::ChangeClearPAD => &[Int],
+            Self::ChangeClearRCnt => &[Int, Int],
+            Self::ChangeThread => &[Hex],
+            Self::CharToLower => &[Char],
+            Self::CharToUpper => &[Char],
+            Self::Chdir => &[CStr],
+            Self::Close => &[Fd],
+            Self::CloseEvent => &[Hex],
+            Self::CloseThread => &[Hex],
+            Self::DelDrv => &[CStr],
+            Self::Delete => &[CStr],
+            Self::DeliverEvent => &[EventClass, EventSpec],
+            Self::DequeueInterruptRP => &[Int, Pointer],
+            Self::DevCardClearError => &[Pointer],
+            Self::DevCardClose => &[Pointer],
+            Self::DevCardErase => &[Pointer, CStr],
+            Self::DevCardFirstFile => &[Pointer, CStr, Pointer],
+            Self::DevCardFormat => &[Pointer],
+            Self::DevCardNextFile => &[Pointer, Pointer],
+            Self::DevCardOpen => &[Pointer, CStr, Hex],
+            Self::DevCardRead => &[Pointer, Pointer, Int],
+            Self::DevCardRename => &[Pointer, CStr, Pointer, CStr],
+            Self::DevCardUndelete => &[Pointer, CStr],
+            Self::DevCardWrite => &[Pointer, Pointer, Int],
+            Self::DevCdChdir => &[Pointer, CStr],
+            Self::DevCdClose => &[Pointer],
+            Self::DevCdFirstFile => &[Pointer, CStr, Pointer],
+            Self::DevCdNextFile => &[Pointer, Pointer],
+            Self::DevCdOpen => &[Pointer, CStr, Hex],
+            Self::DevCdRead => &[Pointer, Pointer, Int],
+            Self::DevSioInOut => &[Pointer, Hex],
+            Self::DevSioIoctl => &[Pointer, Hex, Hex],
+            Self::DevSioOpen => &[Pointer, CStr, Hex],
+            Self::DevTtyInOut => &[Pointer, Hex],
+            Self::DevTtyIoctl => &[Pointer, Hex, Hex],
+            Self::DevTtyOpen => &[Pointer, CStr, Hex],
+            Self::DisableEvent => &[Hex],
+            Self::DisableTimerIrq => &[Int],
+            Self::EnableEvent => &[Hex],
+            Self::EnableTimerIrq => &[Int],
+            Self::EnqueueInterruptRP => &[Int, Pointer],
+            Self::EnqueueSyscallHandler => &[Int],
+            Self::EnqueueTimerAndVblankIrqs => &[Int],
+            Self::Exec => &[Pointer, Hex, Hex],
+            Self::Exit => &[Int],
+            Self::FirstFile => &[CStr, Pointer],
+            Self::FormatDevice => &[CStr],
+            Self::Free => &[Pointer],
+            Self::FreeKernelMemory => &[Pointer],
+            Self::GetCardStatus => &[Int],
+            Self::GetConf => &[Pointer, Pointer, Pointer],
+            Self::GetDeviceFlag => &[Int],
+            Self::GetLastFileError => &[Fd],
+            Self::GetSystemInfo => &[Int],
+            Self::GetTimer => &[Int],
+            Self::Getc => &[Fd],
+            Self::Gets => &[Pointer],
+            Self::GpuCwp => &[Pointer, Int],
+            Self::GpuDw => &[Int, Int, Int, Int, Pointer],
+            Self::GpuSendDma => &[Int, Int, Int, Int, Pointer],
+            Self::HookEntryInt => &[Pointer],
+            Self::Index => &[CStr, Char],
+            Self::InitCard => &[Int],
+            Self::InitDefInt => &[Int],
+            Self::InitHeap => &[Pointer, Int],
+            Self::InitPad2 => &[Pointer, Int, Pointer, Int],
+            Self::InitTimer => &[Int, Int, Hex],
+            Self::InstallDevices => &[Int],
+            Self::IoAbort => &[CStr, CStr],
+            Self::IoAbortRaw => &[Hex],
+            Self::Ioctl => &[Fd, Hex, Hex],
+            Self::KernelRedirect => &[Int],
+            Self::Krom2Offset => &[Hex],
+            Self::Krom2RawAdd => &[Hex],
+            Self::Labs => &[Int],
+            Self::Load => &[CStr, Pointer],
+            Self::LoadExec => &[CStr, Pointer, Int],
+            Self::LoadTest => &[CStr, Pointer],
+            Self::LongJmp => &[Pointer, Hex],
+            Self::Lsearch => &[Pointer, Pointer, Int, Int, Pointer],
+            Self::Malloc => &[Int],
+            Self::Memchr => &[Pointer, Hex, Int],
+            Self::Memcmp => &[Pointer, Pointer, Int],
+            Self::Memcpy => &[Pointer, Pointer, Int],
+            Self::Memmove => &[Pointer, Pointer, Int],
+            Self::Memset => &[Pointer, Hex, Int],
+            Self::NextFile => &[Pointer],
+            Self::Open => &[CStr, Hex],
+            Self::OpenEvent => &[EventClass, EventSpec, Hex, Pointer],
+            Self::OpenThread => &[Pointer, Pointer, Pointer],
+            Self::PadInitAndStart => &[Hex, Pointer, Hex, Hex],
+            Self::Printf => &[CStr, Hex, Hex, Hex],
+            Self::PutChar => &[Char],
+            Self::Putc => &[Char, Fd],
+            Self::Puts => &[CStr],
+            Self::Qsort => &[Pointer, Int, Int, Pointer],
+            Self::Read => &[Fd, Pointer, Int],
+            Self::Realloc => &[Pointer, Int],
+            Self::Rename => &[CStr, CStr],
+            Self::RestartTimer => &[Int],
+            Self::Rindex => &[CStr, Char],
+            Self::Seek => &[Fd, Int, Int],
+            Self::SendGp1Command => &[Hex],
+            Self::SendGpuCommandWord => &[Hex],
+            Self::SendGpuLinkedList => &[Pointer],
+            Self::SetCardAutoFormat => &[Int],
+            Self::SetCardFindMode => &[Int],
+            Self::SetCdromIrqAutoAbort => &[Int, Int],
+            Self::SetConf => &[Int, Int, Pointer],
+            Self::SetIrqAutoAck => &[Int, Int],
+            Self::SetJmp => &[Pointer],
+            Self::SetMemSize => &[Int],
+            Self::Srand => &[Hex],
+            Self::Strcat => &[Pointer, CStr],
+            Self::Strchr => &[CStr, Char],
+            Self::Strcmp => &[CStr, CStr],
+            Self::Strcpy => &[Pointer, CStr],
+            Self::Strcspn => &[CStr, CStr],
+            Self::Strlen => &[CStr],
+            Self::Strncat => &[Pointer, CStr, Int],
+            Self::Strncmp => &[CStr, CStr, Int],
+            Self::Strncpy => &[Pointer, CStr, Int],
+            Self::Strpbrk => &[CStr, CStr],
+            Self::Strrchr => &[CStr, Char],
+            Self::Strspn => &[CStr, CStr],
+            Self::Strstr => &[CStr, CStr],
+            Self::Strtod => &[CStr, Pointer],
+            Self::Strtok => &[CStr, CStr],
+            Self::Strtol => &[CStr, Pointer, Int],
+            Self::Strtoul => &[CStr, Pointer, Int],
+            Self::SysInitMemory => &[Pointer, Int],
+            Self::SystemError => &[Hex, Hex],
+            Self::SystemErrorExit => &[Int],
+            Self::TestDevice => &[CStr],
+            Self::TestEvent => &[Hex],
+            Self::ToDigit => &[Char],
+            Self::TtyCdevInput => &[Pointer, Char],
+            Self::TtyCircGetc => &[Pointer],
+            Self::TtyCircPutc => &[Char, Pointer],
+            Self::Undelete => &[CStr],
+            Self::UndeliverEvent => &[EventClass, EventSpec],
+            Self::WaitCardStatus => &[Int],
+            Self::WaitEvent => &[Hex],
+            Self::Write => &[Fd, Pointer, Int],
+            _ => &[],
         }
     }
 }
+
+/// Returns the name of the given event class, if it's a known one.
+pub fn event_class_name(class: u32) -> Option<&'static str> {
+    Some(match class {
+        0xF000_0001 => "HwVBLANK",
+        0xF000_0002 => "HwGPU",
+        0xF000_0003 => "HwCdRom",
+        0xF000_0004 => "HwDMAC",
+        0xF000_0005 => "HwRTC0",
+        0xF000_0006 => "HwRTC1",
+        0xF000_0007 => "HwRTC2",
+        0xF000_0008 => "HwCNTL",
+        0xF000_0009 => "HwSPU",
+        0xF000_000A => "HwPIO",
+        0xF000_000B => "HwSIO",
+        0xF000_0010 => "HwCPU",
+        0xF000_0011 => "HwCARD",
+        0xF000_0012 => "HwCARD_0",
+        0xF000_0013 => "HwCARD_1",
+        0xF200_0000 => "RCntCNT0",
+        0xF200_0001 => "RCntCNT1",
+        0xF200_0002 => "RCntCNT2",
+        0xF200_0003 => "RCntCNT3",
+        0xF400_0001 => "SwCARD",
+        0xF400_0002 => "SwMATH",
+        _ => return None,
+    })
+}
+
+/// Returns the name of the given event spec, if it's a known one.
+pub fn event_spec_name(spec: u32) -> Option<&'static str> {
+    Some(match spec {
+        0x0001 => "EvSpCOUNT",
+        0x0002 => "EvSpINT",
+        0x0004 => "EvSpIOE",
+        0x0008 => "EvSpCLOSE",
+        0x0010 => "EvSpACK",
+        0x0020 => "EvSpCOMP",
+        0x0040 => "EvSpDR",
+        0x0080 => "EvSpDE",
+        0x0100 => "EvSpTIMOUT",
+        0x0301 => "EvSpEDOM",
+        0x0302 => "EvSpERANGE",
+        0x1000 => "EvSpTRAP",
+        0x2000 => "EvSpNEW",
+        0x4000 => "EvSpUNKNOWN",
+        0x8000 => "EvSpERROR",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn c0_table_has_no_gaps() {
+        let functions = (0x00..=0x1D)
+            .map(|code| Function::c0(code).unwrap_or_else(|| panic!("C({code:02X}h) is missing")))
+            .collect::<Vec<_>>();
+
+        for (code, function) in functions.iter().enumerate() {
+            assert!(
+                !functions[..code].contains(function),
+                "{function:?} is in the C0 table twice"
+            );
+        }
+
+        assert_eq!(Function::c0(0x1E), None);
+    }
+}
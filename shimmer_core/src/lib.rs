@@ -15,6 +15,7 @@ pub mod interrupts;
 pub mod kernel;
 pub mod mem;
 pub mod sio0;
+pub mod spu;
 pub mod timers;
 
 mod util;
@@ -4,8 +4,10 @@ pub mod io;
 
 mod primitive;
 
-use crate::{exe::Executable, util};
+use crate::{Cycles, exe::Executable, util};
 use binrw::BinRead;
+use bitos::{bitos, integer::u4};
+use std::ops::Range;
 
 pub use primitive::{Primitive, PrimitiveRw};
 
@@ -62,12 +64,44 @@ pub enum Region {
 
 #[expect(clippy::len_without_is_empty, reason = "not a collection")]
 impl Region {
-    /// The length of this region, in bytes.
+    /// Every region, in address order.
+    pub const ALL: [Region; 8] = [
+        Region::Ram,
+        Region::RamMirror,
+        Region::Expansion1,
+        Region::ScratchPad,
+        Region::IOPorts,
+        Region::Expansion2,
+        Region::Expansion3,
+        Region::BIOS,
+    ];
+
+    /// Returns the region containing `addr`, if any. Equivalent to [`PhysicalAddress::region`].
+    #[inline(always)]
+    pub const fn from_physical(addr: PhysicalAddress) -> Option<Self> {
+        addr.region()
+    }
+
+    /// The first address of this region.
     #[inline(always)]
     pub const fn start(self) -> PhysicalAddress {
         PhysicalAddress(self as u32)
     }
 
+    /// The physical addresses covered by this region. It's a range of raw values since the end of
+    /// the last region is past the physical address space.
+    #[inline(always)]
+    pub const fn range(self) -> Range<u32> {
+        let start = self.start().value();
+        start..start + self.len()
+    }
+
+    /// The length of this region, in bytes. Same as [`Region::len`].
+    #[inline(always)]
+    pub const fn size(&self) -> u32 {
+        self.len()
+    }
+
     /// The length of this region, in bytes.
     #[inline(always)]
     pub const fn len(&self) -> u32 {
@@ -210,6 +244,16 @@ impl Address {
         }
     }
 
+    /// Returns the memory region this virtual address maps to, if any. Mirrors in KUSEG, KSEG0 and
+    /// KSEG1 map to the same region.
+    #[inline(always)]
+    pub const fn region(self) -> Option<Region> {
+        match self.physical() {
+            Some(phys) => phys.region(),
+            None => None,
+        }
+    }
+
     /// Returns the physical address that this virtual address maps to.
     ///
     /// If the [`segment`](Self::segment) of this address is `KUSEG | KSEG0 | KSEG1`, this is
@@ -268,6 +312,103 @@ impl From<u32> for Address {
     }
 }
 
+/// How much of the first 8 MiB of the physical address space is mapped to RAM. Accesses to the
+/// rest of it are locked or, in some configurations, partly high impedance.
+#[bitos(3)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RamWindow {
+    /// 1 MiB of RAM, 7 MiB locked.
+    Mib1,
+    /// 4 MiB of RAM, 4 MiB locked.
+    Mib4,
+    /// 1 MiB of RAM, 1 MiB high impedance, 6 MiB locked.
+    Mib1HighZ,
+    /// 4 MiB of RAM, 4 MiB high impedance.
+    Mib4HighZ,
+    /// 2 MiB of RAM, 6 MiB locked.
+    Mib2,
+    /// 8 MiB of RAM. This is what the BIOS configures.
+    Mib8,
+    /// 2 MiB of RAM, 2 MiB high impedance, 4 MiB locked.
+    Mib2HighZ,
+    /// 8 MiB of RAM.
+    Mib8Alt,
+}
+
+#[expect(clippy::len_without_is_empty, reason = "not a collection")]
+impl RamWindow {
+    /// The length of the part of the window mapped to RAM, in bytes. Since the PSX only has 2 MiB
+    /// of RAM, it's mirrored throughout it.
+    pub const fn len(self) -> u32 {
+        match self {
+            Self::Mib1 | Self::Mib1HighZ => bytesize::MIB as u32,
+            Self::Mib2 | Self::Mib2HighZ => 2 * bytesize::MIB as u32,
+            Self::Mib4 | Self::Mib4HighZ => 4 * bytesize::MIB as u32,
+            Self::Mib8 | Self::Mib8Alt => 8 * bytesize::MIB as u32,
+        }
+    }
+}
+
+/// The RAM_SIZE register, which configures the memory controller for the RAM chips installed.
+#[bitos(32)]
+#[derive(Debug, Clone, Copy)]
+pub struct RamSize {
+    #[bits(9..12)]
+    pub window: RamWindow,
+}
+
+impl Default for RamSize {
+    /// The value the BIOS initializes the register with.
+    fn default() -> Self {
+        Self::from_bits(0x0000_0B88)
+    }
+}
+
+/// Width of the data bus of a memory region.
+#[bitos(1)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusWidth {
+    Bits8,
+    Bits16,
+}
+
+/// A delay/size register of the memory controller, e.g. [`io::Reg::Expansion1Delay`], which
+/// configures the timing and the width of the data bus of accesses to a memory region.
+#[bitos(32)]
+#[derive(Debug, Clone, Copy)]
+pub struct DelaySize {
+    /// Cycles taken by each write over the bus, minus one.
+    #[bits(0..4)]
+    pub write_delay: u4,
+    /// Cycles taken by each read over the bus, minus one.
+    #[bits(4..8)]
+    pub read_delay: u4,
+    #[bits(12)]
+    pub bus_width: BusWidth,
+}
+
+impl DelaySize {
+    /// Number of accesses over the bus needed to transfer `size` bytes.
+    fn accesses(self, size: u32) -> Cycles {
+        let width = match self.bus_width() {
+            BusWidth::Bits8 => 1,
+            BusWidth::Bits16 => 2,
+        };
+
+        Cycles::from(size.div_ceil(width))
+    }
+
+    /// Cycles taken by a read of `size` bytes.
+    pub fn read_cycles(self, size: u32) -> Cycles {
+        self.accesses(size) * (Cycles::from(self.read_delay().value()) + 1)
+    }
+
+    /// Cycles taken by a write of `size` bytes.
+    pub fn write_cycles(self, size: u32) -> Cycles {
+        self.accesses(size) * (Cycles::from(self.write_delay().value()) + 1)
+    }
+}
+
 pub type BoxedU8Arr<const LEN: usize> = Box<[u8; LEN]>;
 
 /// Collection of memory components, e.g. RAM, BIOS and the Scratchpad.
@@ -288,8 +429,17 @@ pub struct Memory {
     pub io_stubs: BoxedU8Arr<{ Region::IOPorts.len() as usize }>,
     /// Executable to side load, if any.
     pub sideload: Option<Executable>,
-    /// Kernel STDOUT.
+    /// The RAM_SIZE register, which defines how RAM is mirrored.
+    pub ram_size: RamSize,
+    /// Kernel STDOUT. See [`Memory::push_kernel_stdout`].
     pub kernel_stdout: String,
+    /// Maximum size of [`Memory::kernel_stdout`] in bytes, if any.
+    pub kernel_stdout_limit: Option<usize>,
+    /// Amount of characters ever pushed to [`Memory::kernel_stdout`], including the ones dropped
+    /// to keep it within its limit.
+    pub kernel_stdout_pushed: u64,
+    /// The last boot progress code written to the POST register, if any.
+    pub post: Option<u8>,
 }
 
 impl Memory {
@@ -315,7 +465,163 @@ impl Memory {
             io_stubs: util::boxed_array(0),
 
             sideload: None,
+            ram_size: RamSize::default(),
             kernel_stdout: String::new(),
+            kernel_stdout_limit: None,
+            kernel_stdout_pushed: 0,
+            post: None,
         })
     }
+
+    /// The delay/size register of `region`, if it's one of the expansion regions, which are the
+    /// only ones whose access timing is emulated.
+    pub fn expansion_delay(&self, region: Region) -> Option<DelaySize> {
+        let reg = match region {
+            Region::Expansion1 => io::Reg::Expansion1Delay,
+            Region::Expansion2 => io::Reg::Expansion2Delay,
+            Region::Expansion3 => io::Reg::Expansion3Delay,
+            _ => return None,
+        };
+
+        let offset = (reg as u32 - Region::IOPorts.start().value()) as usize;
+        let bytes = self.io_stubs[offset..offset + 4]
+            .try_into()
+            .expect("slice should be exactly 4 bytes long");
+
+        Some(DelaySize::from_bits(u32::from_le_bytes(bytes)))
+    }
+
+    /// Loads an expansion ROM, e.g. the one of a cheat device, at the start of the Expansion 1
+    /// region. The rest of the region is zeroed.
+    ///
+    /// # Errors
+    /// If the ROM is larger than 8 MiB, it's too big to fit and so [`Err`] is returned with its
+    /// length.
+    pub fn load_expansion_1(&mut self, rom: &[u8]) -> Result<(), usize> {
+        if rom.len() > self.expansion_1.len() {
+            return Err(rom.len());
+        }
+
+        self.expansion_1.fill(0);
+        self.expansion_1[..rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    /// Appends a character printed by the kernel to [`Memory::kernel_stdout`], dropping its oldest
+    /// characters if it grows past [`Memory::kernel_stdout_limit`].
+    pub fn push_kernel_stdout(&mut self, char: char) {
+        self.kernel_stdout.push(char);
+        self.kernel_stdout_pushed += 1;
+
+        if let Some(limit) = self.kernel_stdout_limit
+            && self.kernel_stdout.len() > limit
+        {
+            let mut excess = self.kernel_stdout.len() - limit;
+            while !self.kernel_stdout.is_char_boundary(excess) {
+                excess += 1;
+            }
+
+            self.kernel_stdout.drain(..excess);
+        }
+    }
+
+    /// Returns the offset into RAM which the physical address `phys`, in the RAM or RAM mirror
+    /// regions, maps to, or [`None`] if it's outside of the RAM window configured by RAM_SIZE.
+    /// With a window smaller than 2 MiB, this includes the end of the RAM region itself.
+    #[inline(always)]
+    pub fn ram_offset(&self, phys: PhysicalAddress) -> Option<usize> {
+        (phys.value() < self.ram_size.window().len())
+            .then_some((phys.value() & (Region::Ram.len() - 1)) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGIONS: [(Region, u32, u32); 8] = [
+        (Region::Ram, 0x0000_0000, 0x0020_0000),
+        (Region::RamMirror, 0x0020_0000, 0x0060_0000),
+        (Region::Expansion1, 0x1F00_0000, 0x0080_0000),
+        (Region::ScratchPad, 0x1F80_0000, 0x0000_0400),
+        (Region::IOPorts, 0x1F80_1000, 0x0000_2000),
+        (Region::Expansion2, 0x1F80_2000, 0x0000_2000),
+        (Region::Expansion3, 0x1FA0_0000, 0x0020_0000),
+        (Region::BIOS, 0x1FC0_0000, 0x0040_0000),
+    ];
+
+    #[test]
+    fn regions_cover_their_documented_range() {
+        for (region, start, size) in REGIONS {
+            assert_eq!(region.range(), start..start + size, "{region:?}");
+            assert_eq!(region.size(), size, "{region:?}");
+        }
+    }
+
+    #[test]
+    fn addresses_are_classified_in_every_mirrored_segment() {
+        for (region, start, size) in REGIONS {
+            for segment in [Segment::KUSEG, Segment::KSEG0, Segment::KSEG1] {
+                for offset in [0, size - 1] {
+                    let addr = Address(segment.start().value() + start + offset);
+                    assert_eq!(addr.region(), Some(region), "{addr}");
+                }
+            }
+        }
+
+        assert_eq!(Segment::KSEG2.start().region(), None);
+    }
+
+    #[test]
+    fn kernel_stdout_keeps_the_most_recent_output_within_its_limit() {
+        const TEXT: &str = "the quick brown fox jumps over the lazy dog";
+
+        let mut memory = Memory::with_bios(Vec::new()).unwrap();
+        memory.kernel_stdout_limit = Some(16);
+        for char in TEXT.chars() {
+            memory.push_kernel_stdout(char);
+            assert!(memory.kernel_stdout.len() <= 16);
+        }
+
+        assert_eq!(memory.kernel_stdout, &TEXT[TEXT.len() - 16..]);
+    }
+
+    #[test]
+    fn kernel_stdout_limit_drops_whole_characters() {
+        let mut memory = Memory::with_bios(Vec::new()).unwrap();
+        memory.kernel_stdout_limit = Some(4);
+        for char in "aéé".chars() {
+            memory.push_kernel_stdout(char);
+        }
+
+        // 5 bytes, and dropping one would split the first é
+        assert_eq!(memory.kernel_stdout, "éé");
+    }
+
+    #[test]
+    fn delay_size_costs_one_delay_per_access_over_the_bus() {
+        // read delay of 7 and write delay of 3, over an 8-bit bus
+        let narrow = DelaySize::from_bits(0x0000_0073);
+        assert_eq!(narrow.read_cycles(4), 32);
+        assert_eq!(narrow.read_cycles(1), 8);
+        assert_eq!(narrow.write_cycles(2), 8);
+
+        // the same over a 16-bit bus
+        let wide = DelaySize::from_bits(0x0000_1073);
+        assert_eq!(wide.read_cycles(4), 16);
+        assert_eq!(wide.read_cycles(1), 8);
+        assert_eq!(wide.write_cycles(2), 4);
+    }
+
+    #[test]
+    fn only_expansion_regions_have_a_delay() {
+        let mut memory = Memory::with_bios(Vec::new()).unwrap();
+        let offset = (io::Reg::Expansion2Delay as u32 - Region::IOPorts.start().value()) as usize;
+        memory.io_stubs[offset..offset + 4].copy_from_slice(&0x0000_1070u32.to_le_bytes());
+
+        let delay = memory.expansion_delay(Region::Expansion2).unwrap();
+        assert_eq!(delay.read_cycles(4), 16);
+        assert!(memory.expansion_delay(Region::Ram).is_none());
+        assert!(memory.expansion_delay(Region::BIOS).is_none());
+    }
 }
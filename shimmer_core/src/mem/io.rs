@@ -281,6 +281,29 @@ pub enum Reg {
     CdVolume = 0x1F80_1DB0,
     ExternVolume = 0x1F80_1DB4,
 
+    // SPU Reverb
+    ReverbApfOffset1 = 0x1F80_1DC0,
+    ReverbApfOffset2 = 0x1F80_1DC2,
+    ReverbReflectionVolume1 = 0x1F80_1DC4,
+    ReverbCombVolume1 = 0x1F80_1DC6,
+    ReverbCombVolume2 = 0x1F80_1DC8,
+    ReverbCombVolume3 = 0x1F80_1DCA,
+    ReverbCombVolume4 = 0x1F80_1DCC,
+    ReverbReflectionVolume2 = 0x1F80_1DCE,
+    ReverbApfVolume1 = 0x1F80_1DD0,
+    ReverbApfVolume2 = 0x1F80_1DD2,
+    ReverbSameReflectionAddress1 = 0x1F80_1DD4,
+    ReverbCombAddress1 = 0x1F80_1DD8,
+    ReverbCombAddress2 = 0x1F80_1DDC,
+    ReverbSameReflectionAddress2 = 0x1F80_1DE0,
+    ReverbDiffReflectionAddress1 = 0x1F80_1DE4,
+    ReverbCombAddress3 = 0x1F80_1DE8,
+    ReverbCombAddress4 = 0x1F80_1DEC,
+    ReverbDiffReflectionAddress2 = 0x1F80_1DF0,
+    ReverbApfAddress1 = 0x1F80_1DF4,
+    ReverbApfAddress2 = 0x1F80_1DF8,
+    ReverbInputVolume = 0x1F80_1DFC,
+
     // Expansion Region 2
     Post = 0x1F80_2041,
 }
@@ -569,6 +592,29 @@ impl Reg {
             Reg::CdVolume => 4,
             Reg::ExternVolume => 4,
 
+            // SPU Reverb
+            Reg::ReverbApfOffset1 => 2,
+            Reg::ReverbApfOffset2 => 2,
+            Reg::ReverbReflectionVolume1 => 2,
+            Reg::ReverbCombVolume1 => 2,
+            Reg::ReverbCombVolume2 => 2,
+            Reg::ReverbCombVolume3 => 2,
+            Reg::ReverbCombVolume4 => 2,
+            Reg::ReverbReflectionVolume2 => 2,
+            Reg::ReverbApfVolume1 => 2,
+            Reg::ReverbApfVolume2 => 2,
+            Reg::ReverbSameReflectionAddress1 => 4,
+            Reg::ReverbCombAddress1 => 4,
+            Reg::ReverbCombAddress2 => 4,
+            Reg::ReverbSameReflectionAddress2 => 4,
+            Reg::ReverbDiffReflectionAddress1 => 4,
+            Reg::ReverbCombAddress3 => 4,
+            Reg::ReverbCombAddress4 => 4,
+            Reg::ReverbDiffReflectionAddress2 => 4,
+            Reg::ReverbApfAddress1 => 4,
+            Reg::ReverbApfAddress2 => 4,
+            Reg::ReverbInputVolume => 4,
+
             // Expansion Region 2
             Reg::Post => 1,
         }
@@ -589,10 +635,23 @@ impl Reg {
             })
     }
 
+    /// Whether this register is read-only, i.e. whether writes to it are ignored.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Reg::JoyStat | Reg::VoiceChannelEnabled | Reg::SpuStatus
+        )
+    }
+
     pub fn is_spu_voice(&self) -> bool {
         (Reg::Voice0Volume.address()..=Reg::Voice23Repeat.address()).contains(&self.address())
     }
 
+    pub fn is_reverb_config(&self) -> bool {
+        (Reg::ReverbApfOffset1.address()..=Reg::ReverbInputVolume.address())
+            .contains(&self.address())
+    }
+
     pub fn dma_channel(&self) -> Option<dma::Channel> {
         Some(match self {
             Reg::Dma0Base | Reg::Dma0BlockControl | Reg::Dma0Control => dma::Channel::MdecIn,
@@ -60,6 +60,14 @@ pub struct Mode {
     pub clock_polarity: bool,
 }
 
+impl Mode {
+    pub const WRITE_MASK: u64 = Self::BAUDRATE_FACTOR_MASK
+        | Self::CHARACTER_LENGTH_MASK
+        | Self::PARITY_ENABLE_MASK
+        | Self::PARITY_ODD_MASK
+        | Self::CLOCK_POLARITY_MASK;
+}
+
 #[bitos(16)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Control {
@@ -95,6 +103,19 @@ pub struct Control {
     pub port_select: bool,
 }
 
+impl Control {
+    pub const WRITE_MASK: u64 = Self::TX_ENABLE_MASK
+        | Self::SELECTED_MASK
+        | Self::RX_ENABLE_MASK
+        | Self::ACKNOWLEDGE_MASK
+        | Self::READY_TO_RECEIVE_MASK
+        | Self::RESET_MASK
+        | Self::TX_INTERRUPT_ENABLE_MASK
+        | Self::RX_INTERRUPT_ENABLE_MASK
+        | Self::DEVICE_READY_TO_RECEIVE_INTERRUPT_ENABLE_MASK
+        | Self::PORT_SELECT_MASK;
+}
+
 #[bitos(16)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct DigitalInput {
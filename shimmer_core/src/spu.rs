@@ -0,0 +1,227 @@
+//! Items related to the SPU of the PSX.
+
+use crate::{mem::BoxedU8Arr, util};
+use bitos::{
+    bitos,
+    integer::{u2, u4, u6},
+};
+
+/// Length of the SPU RAM, in bytes.
+pub const SPU_RAM_LEN: usize = 512 * 1024;
+
+/// Number of voices of the SPU.
+pub const VOICES: usize = 24;
+
+/// Number of samples in an ADPCM block.
+pub const BLOCK_SAMPLES: usize = 28;
+
+/// Length of an ADPCM block, in bytes.
+pub const BLOCK_LEN: u32 = 16;
+
+/// ADPCM block flag: the voice jumps to its repeat address after this block.
+pub const BLOCK_LOOP_END: u8 = 1 << 0;
+/// ADPCM block flag: along with [`BLOCK_LOOP_END`], the voice keeps playing after the jump.
+pub const BLOCK_LOOP_REPEAT: u8 = 1 << 1;
+/// ADPCM block flag: this block becomes the repeat address of the voice.
+pub const BLOCK_LOOP_START: u8 = 1 << 2;
+
+/// How data is transferred to and from the SPU RAM.
+#[bitos(2)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferMode {
+    Stop,
+    ManualWrite,
+    DmaWrite,
+    DmaRead,
+}
+
+/// The SPU control register (SPUCNT).
+#[bitos(16)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Control {
+    #[bits(0)]
+    pub cd_audio_enable: bool,
+    #[bits(1)]
+    pub external_audio_enable: bool,
+    #[bits(2)]
+    pub cd_audio_reverb: bool,
+    #[bits(3)]
+    pub external_audio_reverb: bool,
+    #[bits(4..6)]
+    pub transfer_mode: TransferMode,
+    /// Whether the IRQ is enabled. Disabling it acknowledges a pending IRQ.
+    #[bits(6)]
+    pub irq_enable: bool,
+    #[bits(7)]
+    pub reverb_enable: bool,
+    #[bits(8..10)]
+    pub noise_step: u2,
+    #[bits(10..14)]
+    pub noise_shift: u4,
+    #[bits(14)]
+    pub unmute: bool,
+    #[bits(15)]
+    pub enable: bool,
+}
+
+/// The SPU status register (SPUSTAT).
+#[bitos(16)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Status {
+    /// Mirrors the low bits of [`Control`] as they're applied.
+    #[bits(0..6)]
+    pub mode: u6,
+    /// Whether an IRQ has been requested and not acknowledged yet.
+    #[bits(6)]
+    pub irq: bool,
+    #[bits(7)]
+    pub dma_request: bool,
+    #[bits(8)]
+    pub dma_write_request: bool,
+    #[bits(9)]
+    pub dma_read_request: bool,
+    #[bits(10)]
+    pub transfer_busy: bool,
+}
+
+/// The state of a voice.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Voice {
+    /// The registers of the voice, as halfwords: volume (left and right), sample rate, start
+    /// address, ADSR (two halfwords), current ADSR volume and repeat address.
+    pub regs: [u16; 8],
+    /// Whether the voice is playing.
+    pub playing: bool,
+    /// Address of the ADPCM block being played.
+    pub current_address: u32,
+    /// Address the voice jumps to at the end of a block with the [`BLOCK_LOOP_END`] flag.
+    pub repeat_address: u32,
+    /// Position in the current block: the index of the sample in bits 12 and up, and the
+    /// fraction of it that has been played below.
+    pub pitch_counter: u32,
+    /// The flags of the current block.
+    pub flags: u8,
+    /// The decoded samples of the current block.
+    pub samples: [i16; BLOCK_SAMPLES],
+    /// The last two decoded samples, newest first, which the ADPCM filters depend on.
+    pub history: [i16; 2],
+}
+
+impl Voice {
+    /// The sample rate register, where 0x1000 is 44100Hz.
+    pub fn sample_rate(&self) -> u16 {
+        self.regs[2]
+    }
+
+    /// The start address register, in bytes.
+    pub fn start_address(&self) -> u32 {
+        u32::from(self.regs[3]) * 8
+    }
+
+    /// The repeat address register, in bytes.
+    pub fn repeat_address_reg(&self) -> u32 {
+        u32::from(self.regs[7]) * 8
+    }
+}
+
+/// The reverb registers. Reverb isn't processed yet, so they only hold what was written to them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Reverb {
+    /// The output volume, left and right.
+    pub output_volume: [u16; 2],
+    /// The start of the reverb work area in SPU RAM, in 8 byte units.
+    pub work_area_start: u16,
+    /// Which voices are sent to the reverb unit, one bit per voice.
+    pub voices: u32,
+    /// The configuration registers, as halfwords, in the order they're mapped in from
+    /// [`Reg::ReverbApfOffset1`](crate::mem::io::Reg::ReverbApfOffset1) on.
+    pub config: [u16; 32],
+}
+
+/// The state of the SPU.
+#[derive(Debug, Clone)]
+pub struct Spu {
+    pub ram: BoxedU8Arr<SPU_RAM_LEN>,
+    pub control: Control,
+    pub voices: [Voice; VOICES],
+    /// The last value written to the key on register.
+    pub key_on: u32,
+    /// The last value written to the key off register.
+    pub key_off: u32,
+    /// Which voices have their pitch modulated by the output of the previous one, one bit per
+    /// voice.
+    pub fm_mode: u32,
+    /// Which voices output the noise generator instead of their samples, one bit per voice.
+    pub noise_mode: u32,
+    /// The output of the noise generator, a 16 bit LFSR.
+    pub noise_level: u16,
+    /// Countdown to the next clock of the noise generator.
+    pub noise_timer: i32,
+    /// Which voices have reached the end of a block with the [`BLOCK_LOOP_END`] flag since they
+    /// were keyed on, one bit per voice (ENDX).
+    pub endx: u32,
+    pub reverb: Reverb,
+    /// The main volume registers, left and right.
+    pub main_volume: [u16; 2],
+    /// The CD audio input volume registers, left and right.
+    pub cd_volume: [u16; 2],
+    /// The external audio input volume registers, left and right.
+    pub extern_volume: [u16; 2],
+    /// The SPU RAM data transfer control register. Only its default value of 4 is meaningful.
+    pub transfer_control: u16,
+    /// The transfer address register, in 8 byte units.
+    pub transfer_address: u16,
+    /// The address of the next halfword of SPU RAM to be transferred.
+    pub current_address: u32,
+    /// The IRQ address register, in 8 byte units.
+    pub irq_address: u16,
+    /// Whether an IRQ has been requested and not acknowledged yet.
+    pub irq: bool,
+}
+
+impl Default for Spu {
+    fn default() -> Self {
+        Self {
+            ram: util::boxed_array(0),
+            control: Control::default(),
+            voices: [Voice::default(); VOICES],
+            key_on: 0,
+            key_off: 0,
+            fm_mode: 0,
+            noise_mode: 0,
+            noise_level: 0,
+            noise_timer: 0,
+            endx: 0,
+            reverb: Reverb::default(),
+            main_volume: [0; 2],
+            cd_volume: [0; 2],
+            extern_volume: [0; 2],
+            transfer_control: 4,
+            transfer_address: 0,
+            current_address: 0,
+            irq_address: 0,
+            irq: false,
+        }
+    }
+}
+
+impl Spu {
+    /// The value of SPUSTAT.
+    pub fn status(&self) -> Status {
+        let mode = self.control.transfer_mode();
+        Status::from_bits(0)
+            .with_mode(u6::new((self.control.to_bits() & 0x3F) as u8))
+            .with_irq(self.irq)
+            .with_dma_request(matches!(
+                mode,
+                TransferMode::DmaWrite | TransferMode::DmaRead
+            ))
+            .with_dma_write_request(mode == TransferMode::DmaWrite)
+            .with_dma_read_request(mode == TransferMode::DmaRead)
+    }
+
+    /// Whether an access to the SPU RAM at `address` hits the IRQ address.
+    pub fn hits_irq_address(&self, address: u32) -> bool {
+        (address as usize % SPU_RAM_LEN) / 8 == usize::from(self.irq_address)
+    }
+}
@@ -76,84 +76,174 @@ pub struct TimerMode {
     pub reached_max: bool,
 }
 
-#[derive(Default)]
-pub struct Timer1 {
-    pub value: u16,
-    pub target: u16,
-    pub mode: TimerMode,
-}
-
-impl Timer1 {
-    pub fn should_tick(&self) -> bool {
-        !self.mode.sync() || matches!(self.mode.sync_mode().value(), 1 | 2)
+impl TimerMode {
+    /// Bits 10 to 12 are status flags set by the hardware, and bits 13 to 15 always read as zero.
+    pub const WRITE_MASK: u64 = Self::SYNC_MASK
+        | Self::SYNC_MODE_MASK
+        | Self::RESET_AT_TARGET_MASK
+        | Self::IRQ_WHEN_AT_TARGET_MASK
+        | Self::IRQ_AT_MAX_MASK
+        | Self::IRQ_REPEAT_MODE_MASK
+        | Self::IRQ_TOGGLE_MODE_MASK
+        | Self::CLOCK_SOURCE_MASK;
+
+    /// The value of the mode register at reset.
+    pub fn reset() -> Self {
+        Self::default().with_no_irq(true)
     }
 
     pub fn can_raise_irq(&self) -> bool {
-        match self.mode.irq_repeat_mode() {
-            IrqRepeatMode::Oneshot => self.mode.no_irq(),
+        match self.irq_repeat_mode() {
+            IrqRepeatMode::Oneshot => self.no_irq(),
             IrqRepeatMode::Repeat => true,
         }
     }
 
     pub fn update_no_irq(&mut self) {
-        match self.mode.irq_toggle_mode() {
+        match self.irq_toggle_mode() {
             IrqToggleMode::Pulse => {
-                self.mode.set_no_irq(false);
+                self.set_no_irq(false);
             }
             IrqToggleMode::Toggle => {
-                self.mode.set_no_irq(!self.mode.no_irq());
+                self.set_no_irq(!self.no_irq());
             }
         }
     }
 
     pub fn cycles_per_tick(&self) -> u64 {
-        if self.mode.clock_source().value() < 2 {
+        if self.clock_source().value() < 2 {
             2
         } else {
             16
         }
     }
+
+    /// Returns the value of a timer at `value` with the given `target` after it ticks `ticks`
+    /// times.
+    ///
+    /// A timer which resets at its target stays at the target for a tick and then goes back to
+    /// zero, unless it's already past the target, in which case it first counts up to 0xFFFF.
+    pub fn advance(&self, value: u16, target: u16, ticks: u64) -> u16 {
+        let (value, target) = (u64::from(value), u64::from(target));
+        if self.reset_at_target() && value <= target {
+            return ((value + ticks) % (target + 1)) as u16;
+        }
+
+        let to_zero = 0x1_0000 - value;
+        if ticks < to_zero {
+            return (value + ticks) as u16;
+        }
+
+        let period = if self.reset_at_target() {
+            target + 1
+        } else {
+            0x1_0000
+        };
+
+        ((ticks - to_zero) % period) as u16
+    }
+
+    /// Returns how many ticks it takes for a timer at `value` with the given `target` to be at
+    /// `at`, or [`None`] if it never will. If the timer already is at `at`, this is the amount of
+    /// ticks until it's there again.
+    pub fn ticks_until(&self, value: u16, target: u16, at: u16) -> Option<u64> {
+        let (value, target, at) = (u64::from(value), u64::from(target), u64::from(at));
+        if self.reset_at_target() && value <= target {
+            let period = target + 1;
+            return (at <= target).then(|| (at + period - value - 1) % period + 1);
+        }
+
+        if at > value {
+            return Some(at - value);
+        }
+
+        let to_zero = 0x1_0000 - value;
+        if self.reset_at_target() {
+            (at <= target).then_some(to_zero + at)
+        } else {
+            Some(to_zero + at)
+        }
+    }
 }
 
-#[derive(Default)]
-pub struct Timer2 {
+pub struct Timer1 {
     pub value: u16,
     pub target: u16,
     pub mode: TimerMode,
+    /// The cycle up to which `value` has been counted. Timers are only brought up to date when
+    /// they're accessed or reach a point of interest, such as their target.
+    pub synced_at: u64,
 }
 
-impl Timer2 {
-    pub fn should_tick(&self) -> bool {
-        !self.mode.sync() || matches!(self.mode.sync_mode().value(), 1 | 2)
+impl Default for Timer1 {
+    fn default() -> Self {
+        Self {
+            value: 0,
+            target: 0,
+            mode: TimerMode::reset(),
+            synced_at: 0,
+        }
     }
+}
 
-    pub fn can_raise_irq(&self) -> bool {
-        match self.mode.irq_repeat_mode() {
-            IrqRepeatMode::Oneshot => self.mode.no_irq(),
-            IrqRepeatMode::Repeat => true,
+impl Timer1 {
+    pub fn should_tick(&self, in_vblank: bool) -> bool {
+        if !self.mode.sync() {
+            return true;
+        }
+
+        match self.mode.sync_mode().value() {
+            // pause during vblank
+            0 => !in_vblank,
+            // reset at vblank
+            1 => true,
+            // reset at vblank and pause outside of it
+            2 => in_vblank,
+            // pause until vblank, then free run
+            _ => false,
         }
     }
 
-    pub fn update_no_irq(&mut self) {
-        match self.mode.irq_toggle_mode() {
-            IrqToggleMode::Pulse => {
-                self.mode.set_no_irq(false);
-            }
-            IrqToggleMode::Toggle => {
-                self.mode.set_no_irq(!self.mode.no_irq());
-            }
+    /// Applies the synchronization mode at the start of vertical blanking.
+    pub fn vblank_started(&mut self) {
+        if !self.mode.sync() {
+            return;
+        }
+
+        match self.mode.sync_mode().value() {
+            1 | 2 => self.value = 0,
+            3 => self.mode.set_sync(false),
+            _ => (),
         }
     }
+}
 
-    pub fn cycles_per_tick(&self) -> u64 {
-        if self.mode.clock_source().value() < 2 {
-            2
-        } else {
-            16
+pub struct Timer2 {
+    pub value: u16,
+    pub target: u16,
+    pub mode: TimerMode,
+    /// The cycle up to which `value` has been counted. Timers are only brought up to date when
+    /// they're accessed or reach a point of interest, such as their target.
+    pub synced_at: u64,
+}
+
+impl Default for Timer2 {
+    fn default() -> Self {
+        Self {
+            value: 0,
+            target: 0,
+            mode: TimerMode::reset(),
+            synced_at: 0,
         }
     }
 }
 
+impl Timer2 {
+    pub fn should_tick(&self) -> bool {
+        !self.mode.sync() || matches!(self.mode.sync_mode().value(), 1 | 2)
+    }
+}
+
 #[derive(Default)]
 pub struct Timers {
     pub timer1: Timer1,
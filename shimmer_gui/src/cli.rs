@@ -1,5 +1,5 @@
-use clap::{Args, Parser};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use std::{ffi::CString, path::PathBuf};
 
 fn clap_styles() -> clap::builder::Styles {
     use clap::builder::styling::{AnsiColor, Color, Style};
@@ -31,6 +31,19 @@ fn clap_styles() -> clap::builder::Styles {
         .placeholder(Style::new().fg_color(Some(Color::Ansi(AnsiColor::White))))
 }
 
+fn parse_dirty_region_len(arg: &str) -> Result<u16, String> {
+    let len = arg.parse::<u16>().map_err(|e| e.to_string())?;
+    if shimmer_wgpu::is_valid_dirty_region_len(len) {
+        Ok(len)
+    } else {
+        Err("must be a power of two no larger than 512".to_owned())
+    }
+}
+
+fn parse_sideload_arg(arg: &str) -> Result<CString, String> {
+    CString::new(arg).map_err(|_| "arguments can't contain null bytes".to_owned())
+}
+
 #[derive(Debug, Args)]
 pub struct CliArgs {
     /// Path to the BIOS to use.
@@ -42,6 +55,40 @@ pub struct CliArgs {
     /// Path to the EXE to sideload.
     #[arg(short, long)]
     pub sideload_exe: Option<PathBuf>,
+    /// Argument to pass to the sideloaded EXE. Can be repeated.
+    #[arg(long = "arg", requires = "sideload_exe", value_parser = parse_sideload_arg)]
+    pub sideload_args: Vec<CString>,
+    /// Log every rasterizer synchronization caused by a VRAM hazard.
+    #[arg(long)]
+    pub log_syncs: bool,
+    /// Side length, in pixels, of the VRAM regions used to detect rendering hazards. Must be a
+    /// power of two no larger than 512.
+    #[arg(
+        long,
+        default_value_t = shimmer_wgpu::DEFAULT_DIRTY_REGION_LEN,
+        value_parser = parse_dirty_region_len
+    )]
+    pub dirty_region_len: u16,
+    /// Factor by which to scale the CPU clock relative to the rest of the system. Values above 1.0
+    /// overclock the CPU. Clamped to 0.5-4.0.
+    #[arg(long, default_value_t = 1.0)]
+    pub clock_multiplier: f64,
+    /// Skip loops in which the CPU idles waiting for an interrupt instead of executing them.
+    #[arg(long)]
+    pub skip_idle_loops: bool,
+    /// Fast-forward through the delay loops the BIOS runs while it boots.
+    #[arg(long)]
+    pub skip_post_delays: bool,
+    /// Record the last bus accesses and dump them to the given file on exit or crash.
+    #[arg(long, value_name = "PATH")]
+    pub flight_recorder: Option<PathBuf>,
+    /// Soft reset when L1, R1, Start and Select are held together.
+    #[arg(long)]
+    pub soft_reset_combo: bool,
+    /// Map the expansion ROM at the given path, e.g. the firmware of a cheat cartridge such as an
+    /// Action Replay, to the Expansion 1 region.
+    #[arg(long, value_name = "PATH")]
+    pub expansion_rom: Option<PathBuf>,
 }
 
 /// shimmer psx emulator
@@ -51,4 +98,41 @@ pub struct CliArgs {
 pub struct Cli {
     #[command(flatten)]
     pub args: CliArgs,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a battery of internal checks against the given BIOS and print a report, without
+    /// starting the GUI.
+    SelfTest,
+    /// Boot every BIOS image in a directory up to the shell and compare the resulting state
+    /// against the golden fingerprint stored next to each image, without starting the GUI.
+    BootCheck {
+        /// Directory containing the BIOS images (`.bin` files). The fingerprint of `NAME.bin` is
+        /// stored in `NAME.bin.golden`.
+        dir: PathBuf,
+        /// Maximum amount of cycles to run each BIOS for.
+        #[arg(long, default_value_t = 10 * shimmer::core::CYCLES_SECOND)]
+        max_cycles: u64,
+        /// Record the current fingerprints as the golden ones instead of comparing against them.
+        #[arg(long)]
+        update: bool,
+    },
+    /// Run a reference test executable, such as amidog's CPU and GTE tests, and check that it
+    /// reports no failures, without starting the GUI.
+    TestExe {
+        /// Path to the test executable.
+        exe: PathBuf,
+        /// Text the executable prints once it's done running every test.
+        #[arg(long, default_value = "done")]
+        done_marker: String,
+        /// Maximum amount of cycles to run the executable for.
+        #[arg(long, default_value_t = 60 * shimmer::core::CYCLES_SECOND)]
+        max_cycles: u64,
+        /// Print everything the executable printed.
+        #[arg(long)]
+        verbose: bool,
+    },
 }
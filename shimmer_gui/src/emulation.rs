@@ -3,11 +3,11 @@ use crossbeam::sync::Parker;
 use parking_lot::Mutex;
 use shimmer::core::cpu::FREQUENCY;
 use std::{
+    panic::AssertUnwindSafe,
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
     },
-    time::Duration,
 };
 
 pub fn run(should_advance: Arc<AtomicBool>, state: Arc<Mutex<State>>, parker: Parker) {
@@ -23,7 +23,7 @@ pub fn run(should_advance: Arc<AtomicBool>, state: Arc<Mutex<State>>, parker: Pa
             .timing
             .running_timer
             .elapsed()
-            .saturating_sub(exclusive.timing.emulated_time);
+            .saturating_sub(exclusive.emulator.elapsed_time());
 
         let cycles_to_run = FREQUENCY as f64 * time_behind.as_secs_f64();
         let full_cycles_to_run = cycles_to_run as u64;
@@ -34,16 +34,19 @@ pub fn run(should_advance: Arc<AtomicBool>, state: Arc<Mutex<State>>, parker: Pa
             let taken = CYCLE_GROUP.min(cycles_left);
             cycles_left -= taken;
 
-            exclusive.emulator.cycle_for(taken);
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                exclusive.emulator.cycle_for(taken);
+            }));
+
+            if let Err(panic) = result {
+                exclusive.report_crash();
+                std::panic::resume_unwind(panic);
+            }
 
             let stop = !should_advance.load(Ordering::Relaxed);
             if stop {
                 break;
             }
         }
-
-        let emulated_cycles = full_cycles_to_run - cycles_left;
-        exclusive.timing.emulated_time +=
-            Duration::from_secs_f64(emulated_cycles as f64 / FREQUENCY as f64);
     }
 }
@@ -8,7 +8,7 @@ mod util;
 mod windows;
 
 use clap::Parser;
-use cli::Cli;
+use cli::{Cli, CliArgs};
 use crossbeam::sync::{Parker, Unparker};
 use eframe::{
     egui::{self, Id, menu},
@@ -18,11 +18,14 @@ use eframe::{
 use egui_file_dialog::FileDialog;
 use input::Input;
 use parking_lot::Mutex;
-use shimmer::Emulator;
+use shimmer::{Emulator, EmulatorBuilder, flight_recorder, gpu::interface::Renderer, sio0};
 use shimmer_wgpu::WgpuRenderer;
 use std::{
+    ffi::CString,
     io::BufReader,
-    path::PathBuf,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    process::ExitCode,
     random::random,
     sync::{
         Arc,
@@ -30,14 +33,17 @@ use std::{
     },
     time::Duration,
 };
-use tinylog::{drain::buf::RecordBuf, logger::LoggerFamily};
+use tinylog::{
+    Logger,
+    drain::buf::RecordBuf,
+    logger::{Context as LoggerContext, LoggerFamily},
+};
 use util::Timer;
 use windows::{AppWindow, AppWindowKind};
 
 /// Variables related to timing.
 struct Timing {
     running_timer: Timer,
-    emulated_time: Duration,
 }
 
 /// Variables related to controlling the emulation or the GUI.
@@ -56,6 +62,8 @@ struct State {
     timing: Timing,
     controls: Controls,
     input: Input,
+    /// Where to dump the flight recorder to, if enabled.
+    flight_recorder_path: Option<PathBuf>,
 
     log_family: LoggerFamily,
     log_records: RecordBuf,
@@ -78,6 +86,9 @@ impl State {
 
         let renderer_config = shimmer_wgpu::Config {
             display_tex_format: render_state.target_format,
+            log_syncs: config.log_syncs,
+            dirty_region_len: config.dirty_region_len,
+            initial_vram: shimmer_wgpu::InitialVram::default(),
         };
         let device = render_state.device.clone();
         let queue = render_state.queue.clone();
@@ -89,13 +100,35 @@ impl State {
         );
 
         let bios = std::fs::read(config.bios_path).expect("should be a valid bios path");
-        let emulator_config = shimmer::Config {
-            bios,
-            rom_path: config.rom_path,
-            logger: root_logger,
-        };
+        let expansion1 = config
+            .expansion_rom_path
+            .map(|path| std::fs::read(path).expect("should be a valid expansion ROM path"));
+        let mut builder = Emulator::builder()
+            .bios(bios)
+            .logger(root_logger)
+            .sideload_args(config.sideload_args)
+            .skip_idle_loops(config.skip_idle_loops)
+            .skip_post_delays(config.skip_post_delays)
+            .flight_recorder(flight_recorder::Settings {
+                enabled: config.flight_recorder_path.is_some(),
+                ..Default::default()
+            })
+            .settings(shimmer::Settings {
+                clock_multiplier: config.clock_multiplier,
+                soft_reset_combo: config.soft_reset_combo.then(sio0::soft_reset_combo),
+            });
+        if let Some(path) = config.rom_path {
+            builder = builder.disc(path);
+        }
+        if let Some(rom) = expansion1 {
+            builder = builder.expansion1(rom);
+        }
+        let emulator_config = builder.into_config().unwrap();
 
-        let mut emulator = Emulator::new(emulator_config.clone(), renderer.clone()).unwrap();
+        let mut emulator = EmulatorBuilder::from(emulator_config.clone())
+            .renderer(Box::new(renderer.clone()))
+            .build()
+            .unwrap();
         if let Some(path) = config.sideload_exe_path {
             use shimmer::core::binrw::BinReaderExt;
             let exe = std::fs::read(path).expect("should be a valid sideload exe path");
@@ -109,7 +142,6 @@ impl State {
             renderer,
             timing: Timing {
                 running_timer: Timer::new(),
-                emulated_time: Duration::ZERO,
             },
             controls: Controls {
                 running: false,
@@ -117,11 +149,68 @@ impl State {
                 alternative_names: true,
             },
             input: Input::new(),
+            flight_recorder_path: config.flight_recorder_path,
 
             log_family,
             log_records,
         }
     }
+
+    /// Writes the bus accesses recorded by the flight recorder to the configured path, if any.
+    fn dump_flight_recorder(&mut self) {
+        let Some(path) = &self.flight_recorder_path else {
+            return;
+        };
+
+        let mut dump = String::new();
+        for record in self.emulator.flight_recorder() {
+            dump.push_str(&record.to_string());
+            dump.push('\n');
+        }
+
+        if let Err(err) = std::fs::write(path, dump) {
+            eprintln!(
+                "couldn't write flight recorder to {}: {err}",
+                path.display()
+            );
+        }
+    }
+
+    /// Prints the most recent log records of the emulator and a summary of its state to stderr,
+    /// then dumps the flight recorder. Called when emulation panics, before the panic continues.
+    ///
+    /// The emulator state might be inconsistent at this point, so a panic while writing the
+    /// summary is caught and reported instead of aborting the process.
+    fn report_crash(&mut self) {
+        /// Number of log records printed in a crash report.
+        const CRASH_LOG_RECORDS: usize = 64;
+
+        let ctx = LoggerContext::new("psx");
+        let len = self.log_records.len(ctx.clone());
+
+        eprintln!("=== last {CRASH_LOG_RECORDS} log records ===");
+        for index in len.saturating_sub(CRASH_LOG_RECORDS)..len {
+            let Some(log) = self.log_records.get(&ctx, index) else {
+                continue;
+            };
+
+            eprintln!(
+                "{} {} {} {}",
+                log.value.time().format("%H:%M:%S%.6f"),
+                log.value.static_data.level,
+                log.ctx,
+                log.value.message
+            );
+        }
+
+        let emulator = &mut self.emulator;
+        match std::panic::catch_unwind(AssertUnwindSafe(|| emulator.crash_report())) {
+            Ok(report) => eprintln!("{report}"),
+            Err(_) => eprintln!("couldn't write crash report: the emulator panicked again"),
+        }
+
+        self.dump_flight_recorder();
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -129,6 +218,15 @@ struct Config {
     bios_path: PathBuf,
     rom_path: Option<PathBuf>,
     sideload_exe_path: Option<PathBuf>,
+    sideload_args: Vec<CString>,
+    log_syncs: bool,
+    dirty_region_len: u16,
+    clock_multiplier: f64,
+    skip_idle_loops: bool,
+    skip_post_delays: bool,
+    flight_recorder_path: Option<PathBuf>,
+    soft_reset_combo: bool,
+    expansion_rom_path: Option<PathBuf>,
 }
 
 struct App {
@@ -150,6 +248,15 @@ impl App {
             bios_path,
             rom_path,
             sideload_exe_path,
+            sideload_args: cli.args.sideload_args.clone(),
+            log_syncs: cli.args.log_syncs,
+            dirty_region_len: cli.args.dirty_region_len,
+            clock_multiplier: cli.args.clock_multiplier,
+            skip_idle_loops: cli.args.skip_idle_loops,
+            skip_post_delays: cli.args.skip_post_delays,
+            flight_recorder_path: cli.args.flight_recorder.clone(),
+            soft_reset_combo: cli.args.soft_reset_combo,
+            expansion_rom_path: cli.args.expansion_rom.clone(),
         };
 
         let state = Arc::new(Mutex::new(State::new(
@@ -233,8 +340,10 @@ impl eframe::App for App {
         let state = &mut *state;
 
         if reset {
-            state.emulator =
-                Emulator::new(state.emulator_config.clone(), state.renderer.clone()).unwrap();
+            state.emulator = EmulatorBuilder::from(state.emulator_config.clone())
+                .renderer(Box::new(state.renderer.clone()))
+                .build()
+                .unwrap();
         }
 
         egui::CentralPanel::default()
@@ -243,7 +352,8 @@ impl eframe::App for App {
                 self.file_dialog.update(ctx);
                 if let Some(path) = self.file_dialog.take_picked() {
                     let file = std::fs::File::open(path).unwrap();
-                    state.emulator.cdrom_mut().insert_rom(BufReader::new(file));
+                    let rom = shimmer::cdrom::BinRom::new(BufReader::new(file)).unwrap();
+                    state.emulator.cdrom_mut().insert_rom(rom);
                 }
 
                 self.windows.retain_mut(|window| {
@@ -326,8 +436,232 @@ impl eframe::App for App {
     }
 }
 
-fn main() {
+impl Drop for App {
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+        state.dump_flight_recorder();
+        state.renderer.shutdown();
+    }
+}
+
+/// Returns the construction inputs shared by the subcommands, which run without a disc.
+fn cli_config(args: &CliArgs, bios: Vec<u8>, logger: Logger) -> shimmer::Config {
+    Emulator::builder()
+        .bios(bios)
+        .logger(logger)
+        .skip_idle_loops(args.skip_idle_loops)
+        .skip_post_delays(args.skip_post_delays)
+        .settings(shimmer::Settings {
+            clock_multiplier: args.clock_multiplier,
+            soft_reset_combo: None,
+        })
+        .into_config()
+        .expect("BIOS and logger are given")
+}
+
+fn self_test(args: &CliArgs) -> ExitCode {
+    let bios_path = args.bios.clone().unwrap_or("resources/BIOS.BIN".into());
+    let bios = match std::fs::read(&bios_path) {
+        Ok(bios) => bios,
+        Err(err) => {
+            eprintln!("couldn't read BIOS at {}: {err}", bios_path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let log_family = LoggerFamily::builder().build();
+    let config = cli_config(args, bios, log_family.logger("psx", tinylog::Level::Error));
+
+    let mut renderer = WgpuRenderer::headless(
+        log_family.logger("renderer", tinylog::Level::Error),
+        shimmer_wgpu::Config {
+            display_tex_format: wgpu::TextureFormat::Rgba8Unorm,
+            log_syncs: false,
+            dirty_region_len: args.dirty_region_len,
+            initial_vram: shimmer_wgpu::InitialVram::default(),
+        },
+    );
+    if renderer.is_none() {
+        eprintln!("couldn't find a GPU adapter, the renderer won't be checked");
+    }
+
+    let report = Emulator::self_test(
+        &config,
+        renderer
+            .as_mut()
+            .map(|renderer| renderer as &mut dyn Renderer),
+    );
+    print!("{report}");
+
+    if let Some(renderer) = renderer {
+        renderer.shutdown();
+    }
+
+    if report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn boot_check(args: &CliArgs, dir: &Path, max_cycles: u64, update: bool) -> ExitCode {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("couldn't read {}: {err}", dir.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut images: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("bin"))
+        })
+        .collect();
+    images.sort();
+
+    if images.is_empty() {
+        eprintln!("no BIOS images found in {}", dir.display());
+        return ExitCode::FAILURE;
+    }
+
+    let log_family = LoggerFamily::builder().build();
+    let mut passed = true;
+    for image in images {
+        let name = image.display();
+        let bios = match std::fs::read(&image) {
+            Ok(bios) => bios,
+            Err(err) => {
+                println!("[FAIL] {name}: couldn't read image: {err}");
+                passed = false;
+                continue;
+            }
+        };
+
+        let config = cli_config(args, bios, log_family.logger("psx", tinylog::Level::Error));
+
+        let fingerprint = match Emulator::boot_fingerprint(&config, max_cycles) {
+            Ok(fingerprint) => fingerprint.to_string(),
+            Err(err) => {
+                println!("[FAIL] {name}: couldn't create emulator: {err}");
+                passed = false;
+                continue;
+            }
+        };
+
+        let mut golden_path = image.clone().into_os_string();
+        golden_path.push(".golden");
+        let golden_path = PathBuf::from(golden_path);
+
+        if update {
+            match std::fs::write(&golden_path, &fingerprint) {
+                Ok(()) => println!("[save] {name}"),
+                Err(err) => {
+                    println!("[FAIL] {name}: couldn't write golden fingerprint: {err}");
+                    passed = false;
+                }
+            }
+
+            continue;
+        }
+
+        match std::fs::read_to_string(&golden_path) {
+            Ok(golden) if golden == fingerprint => println!("[pass] {name}"),
+            Ok(golden) => {
+                println!("[FAIL] {name}: fingerprint differs from the golden one");
+                println!("       expected:\n{golden}");
+                println!("       got:\n{fingerprint}");
+                passed = false;
+            }
+            Err(err) => {
+                println!("[FAIL] {name}: couldn't read golden fingerprint: {err}");
+                println!("       hint: record it with --update");
+                passed = false;
+            }
+        }
+    }
+
+    if passed {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn test_exe(
+    args: &CliArgs,
+    exe_path: &Path,
+    done_marker: &str,
+    max_cycles: u64,
+    verbose: bool,
+) -> ExitCode {
+    use shimmer::core::binrw::BinReaderExt;
+
+    let bios_path = args.bios.clone().unwrap_or("resources/BIOS.BIN".into());
+    let bios = match std::fs::read(&bios_path) {
+        Ok(bios) => bios,
+        Err(err) => {
+            eprintln!("couldn't read BIOS at {}: {err}", bios_path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let exe: shimmer::core::exe::Executable = match std::fs::read(exe_path)
+        .map_err(|err| err.to_string())
+        .and_then(|exe| {
+            std::io::Cursor::new(exe)
+                .read_le()
+                .map_err(|err| err.to_string())
+        }) {
+        Ok(exe) => exe,
+        Err(err) => {
+            eprintln!("couldn't load executable at {}: {err}", exe_path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let log_family = LoggerFamily::builder().build();
+    let config = cli_config(args, bios, log_family.logger("psx", tinylog::Level::Error));
+
+    let report = match Emulator::run_test_exe(&config, exe, done_marker, max_cycles) {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("couldn't create emulator: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if verbose {
+        print!("{}", report.stdout);
+    }
+
+    print!("{report}");
+    if report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    match &cli.command {
+        Some(cli::Command::SelfTest) => return self_test(&cli.args),
+        Some(cli::Command::BootCheck {
+            dir,
+            max_cycles,
+            update,
+        }) => return boot_check(&cli.args, dir, *max_cycles, *update),
+        Some(cli::Command::TestExe {
+            exe,
+            done_marker,
+            max_cycles,
+            verbose,
+        }) => return test_exe(&cli.args, exe, done_marker, *max_cycles, *verbose),
+        None => (),
+    }
 
     let mut instance = wgpu::InstanceDescriptor::from_env_or_default();
     instance.flags.insert(InstanceFlags::DEBUG);
@@ -356,5 +690,8 @@ fn main() {
 
     if let Err(e) = result {
         eprintln!("{e:?}");
+        return ExitCode::FAILURE;
     }
+
+    ExitCode::SUCCESS
 }
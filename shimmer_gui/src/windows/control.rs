@@ -1,7 +1,7 @@
 use super::WindowUi;
 use crate::State;
 use eframe::egui::{self, Align, Id, RichText, Ui, Vec2, Window};
-use shimmer::core::cpu::FREQUENCY;
+use shimmer::{CLOCK_MULTIPLIER_RANGE, core::cpu::FREQUENCY};
 
 pub struct Control {
     _id: Id,
@@ -33,12 +33,44 @@ impl WindowUi for Control {
             {
                 state.emulator.cycle_for(1);
             }
+
+            if ui.button("Soft reset").clicked() {
+                state.emulator.soft_reset();
+            }
+        });
+
+        let mut renderer_paused = state.renderer.paused();
+        if ui
+            .checkbox(&mut renderer_paused, "Pause renderer")
+            .changed()
+        {
+            if renderer_paused {
+                state.renderer.pause();
+            } else {
+                state.renderer.resume();
+            }
+        }
+
+        ui.horizontal(|ui| {
+            let mut multiplier = state.emulator.clock_multiplier();
+            ui.label("CPU clock:");
+            if ui
+                .add(
+                    egui::DragValue::new(&mut multiplier)
+                        .speed(0.01)
+                        .range(CLOCK_MULTIPLIER_RANGE)
+                        .suffix("x"),
+                )
+                .changed()
+            {
+                state.emulator.set_clock_multiplier(multiplier);
+            }
         });
 
         ui.horizontal(|ui| {
             ui.label("Emulated:");
             ui.with_layout(egui::Layout::right_to_left(Align::Center), |ui| {
-                ui.label(format!("{:.3?}", state.timing.emulated_time));
+                ui.label(format!("{:.3?}", state.emulator.elapsed_time()));
             });
         });
 
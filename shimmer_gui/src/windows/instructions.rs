@@ -6,10 +6,24 @@ use eframe::{
 };
 use egui_extras::{Column, TableBuilder, TableRow};
 use shimmer::core::{
-    cpu::instr::{Args, ImmKind, Instruction, RegSource},
+    cpu::instr::{Args, Category, ImmKind, Instruction, RegSource},
     mem::Address,
 };
 
+/// Returns the colour of the mnemonic of instructions in `category`.
+fn mnemonic_color(category: Option<Category>) -> Color32 {
+    match category {
+        Some(Category::Load) => Color32::from_rgb(0x8F, 0xD0, 0xFF),
+        Some(Category::Store) => Color32::from_rgb(0xFF, 0xB0, 0x6F),
+        Some(Category::Branch) => Color32::from_rgb(0xE0, 0x8F, 0xFF),
+        Some(Category::Jump) => Color32::from_rgb(0xFF, 0x7F, 0xA0),
+        Some(Category::Arithmetic) => Color32::LIGHT_YELLOW,
+        Some(Category::Coprocessor) => Color32::from_rgb(0x7F, 0xE0, 0xC0),
+        Some(Category::System) => Color32::from_rgb(0xFF, 0x60, 0x60),
+        None => Color32::DARK_GRAY,
+    }
+}
+
 fn ascii_score(bytes: impl Iterator<Item = u8>) -> u16 {
     let is_ascii_text = |value: u8| {
         value.is_ascii_alphanumeric() || value.is_ascii_punctuation() || value.is_ascii_whitespace()
@@ -175,8 +189,6 @@ impl InstructionViewer {
     }
 
     fn draw_row(&mut self, state: &mut State, row: &mut TableRow, begin_addr: u32) {
-        const MNEMONIC_COLOR: Color32 = Color32::LIGHT_YELLOW;
-
         let address = Address(begin_addr + row.index() as u32 * 4);
         let (instr, valid) = fetch_instr(state, address);
 
@@ -202,7 +214,7 @@ impl InstructionViewer {
             ui.horizontal(|ui| {
                 let mnemonic_response = ui.label(
                     RichText::new(format!("{:8}", mnemonic))
-                        .color(MNEMONIC_COLOR)
+                        .color(mnemonic_color(instr.classifies_as()))
                         .monospace(),
                 );
                 mnemonic_response.on_hover_ui(|ui| {
@@ -210,6 +222,15 @@ impl InstructionViewer {
                 });
 
                 self.draw_args(ui, state.controls.alternative_names, instr, args);
+
+                if let Some(target) = instr.target(address) {
+                    let response = ui.label(
+                        RichText::new(format!("-> {target}"))
+                            .color(Color32::GRAY)
+                            .monospace(),
+                    );
+                    response.on_hover_text("branch or jump target");
+                }
             });
         });
     }
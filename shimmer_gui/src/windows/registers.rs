@@ -85,6 +85,17 @@ impl WindowUi for Registers {
                         });
                     }
                 });
+
+                let cop0 = &state.emulator.psx().cop0.regs;
+                for (name, value) in [
+                    ("SR", cop0.decoded_status().to_string()),
+                    ("CAUSE", cop0.decoded_cause().to_string()),
+                ] {
+                    tui.style(default_style()).add_with_border(|tui| {
+                        tui.label(RichText::new(name).monospace().color(Color32::LIGHT_BLUE));
+                        tui.label(RichText::new(value).monospace().color(Color32::LIGHT_GREEN));
+                    });
+                }
             });
     }
 }
@@ -36,6 +36,8 @@ var<storage, read_write> vram: array<u32>;
 struct DisplayArea {
     top_left: u32,
     dimensions: u32,
+    enabled: u32,
+    flipped: u32,
 }
 
 @group(1) @binding(0)
@@ -43,12 +45,22 @@ var<uniform> display_area: DisplayArea;
 
 @fragment
 fn fs_main(in: VertexOut) -> @location(0) vec4f {
+    if display_area.enabled == 0u {
+        return vec4f(0.0, 0.0, 0.0, 1.0);
+    }
+
     var top_left_x = extractBits(display_area.top_left, 0u, 16u);
     var top_left_y = extractBits(display_area.top_left, 16u, 16u);
     var dimensions_x = extractBits(display_area.dimensions, 0u, 16u);
     var dimensions_y = extractBits(display_area.dimensions, 16u, 16u);
 
-    var x = f32(top_left_x) + in.uv.x * f32(dimensions_x);
+    // the reverse flag mirrors the output horizontally
+    var u = in.uv.x;
+    if display_area.flipped != 0u {
+        u = 1.0 - u;
+    }
+
+    var x = f32(top_left_x) + u * f32(dimensions_x);
     var y = f32(top_left_y) + in.uv.y * f32(dimensions_y);
     var vram_coords = vec2u(u32(floor(x)), u32(floor(y)));
 
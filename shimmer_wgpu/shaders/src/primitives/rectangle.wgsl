@@ -6,6 +6,7 @@ struct Rectangle {
     top_left: Vertex,
     dimensions: vec2u,
     transparency_mode: TransparencyMode,
+    blending_mode: BlendingMode,
     texture: TextureConfig,
 }
 
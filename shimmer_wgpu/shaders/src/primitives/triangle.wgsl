@@ -8,8 +8,10 @@ struct Triangle {
     vertices: array<Vertex, 3>,
     // Shading mode of this triangle.
     shading_mode: ShadingMode,
-    // Blending mode of this triangle.
+    // Transparency mode of this triangle.
     transparency_mode: TransparencyMode,
+    // Blending mode used if this triangle is semi-transparent.
+    blending_mode: BlendingMode,
     // Texture configuration of this triangle.
     texture: TextureConfig,
 }
@@ -13,7 +13,11 @@ struct Config {
     texwindow_mask: vec2u,
     texwindow_offset: vec2u,
 
-    blending_mode: BlendingMode,
+    display_area_top_left: vec2u,
+    display_area_dimensions: vec2u,
+    drawing_to_display: u32,
+    interlaced: u32,
+    odd_field: u32,
 }
 
 fn drawing_area_contains(coords: vec2u) -> bool {
@@ -21,6 +25,25 @@ fn drawing_area_contains(coords: vec2u) -> bool {
     return all((relative >= vec2u(0)) && (relative <= config.drawing_area_dimensions));
 }
 
+// whether drawing to the given coordinates is prohibited because they're being displayed
+fn display_area_blocks(coords: vec2u) -> bool {
+    if config.drawing_to_display > 0 {
+        return false;
+    }
+
+    let relative = coords - config.display_area_top_left;
+    if !all(relative < config.display_area_dimensions) {
+        return false;
+    }
+
+    // when interlaced, only the lines of the field being displayed are blocked
+    return (config.interlaced == 0) || ((relative.y & 1u) == config.odd_field);
+}
+
+fn can_draw(coords: vec2u) -> bool {
+    return drawing_area_contains(coords) && !display_area_blocks(coords);
+}
+
 var<private> config: Config;
 
 @group(0) @binding(0)
@@ -80,7 +103,7 @@ fn render_triangle(triangle: Triangle, vram_coords: vec2u) -> bool {
     if triangle.transparency_mode == TRANSPARENCY_MODE_TRANSPARENT && pixel_transparency {
         let bg = rgb5m_to_rgb_norm(vram_get_color_rgb5m(vram_coords));
         let fg = rgb5m_to_rgb_norm(color);
-        let blended = rgb_norm_blend(config.blending_mode, bg, fg);
+        let blended = rgb_norm_blend(triangle.blending_mode, bg, fg);
 
         color = rgb_norm_to_rgb5m(blended);
     }
@@ -129,7 +152,7 @@ fn render_rectangle(rectangle: Rectangle, vram_coords: vec2u) -> bool {
     if rectangle.transparency_mode == TRANSPARENCY_MODE_TRANSPARENT && pixel_transparency {
         let bg = rgb5m_to_rgb_norm(vram_get_color_rgb5m(vram_coords));
         let fg = rgb5m_to_rgb_norm(color);
-        let blended = rgb_norm_blend(config.blending_mode, bg, fg);
+        let blended = rgb_norm_blend(rectangle.blending_mode, bg, fg);
 
         color = rgb_norm_to_rgb5m(blended);
     }
@@ -159,13 +182,13 @@ fn render(@builtin(global_invocation_id) global_id: vec3u) {
                 config_index += 1;
             }
             case COMMAND_TRIANGLE {
-                if drawing_area_contains(vram_coords) {
+                if can_draw(vram_coords) {
                     render_triangle(triangles[triangle_index], vram_coords);
                 }
                 triangle_index += 1u;
             }
             case COMMAND_RECTANGLE {
-                if drawing_area_contains(vram_coords) {
+                if can_draw(vram_coords) {
                     render_rectangle(rectangles[rectangle_index], vram_coords);
                 }
                 rectangle_index += 1u;
@@ -11,18 +11,22 @@ struct TextureConfig {
     texpage: vec2u,
 }
 
-fn apply_texwindow(coords: vec2u) -> vec2u {
+// the texture window applies to texcoords, before they're mapped to VRAM
+fn apply_texwindow(uv: vec2u) -> vec2u {
     return vec2u(
-        (coords.x & ~(config.texwindow_mask.x * 8u)) | ((config.texwindow_offset.x & config.texwindow_mask.x) * 8),
-        (coords.y & ~(config.texwindow_mask.y * 8u)) | ((config.texwindow_offset.y & config.texwindow_mask.y) * 8),
+        (uv.x & ~(config.texwindow_mask.x * 8u)) | ((config.texwindow_offset.x & config.texwindow_mask.x) * 8),
+        (uv.y & ~(config.texwindow_mask.y * 8u)) | ((config.texwindow_offset.y & config.texwindow_mask.y) * 8),
     );
 }
 
-fn texture_texel(config: TextureConfig, uv: vec2u) -> Rgb5m {
+// VRAM accesses wrap around, so CLUTs placed near the right edge of VRAM continue at its left
+// edge, just like on hardware
+fn texture_texel(config: TextureConfig, raw_uv: vec2u) -> Rgb5m {
+    // texcoords wrap around within the 256x256 texpage
+    let uv = apply_texwindow(raw_uv & vec2u(0xFF));
     switch config.mode {
         case TEXTURE_MODE_LUT4 {
-            var texpage_vram_coords = config.texpage + uv / vec2u(4, 1);
-            texpage_vram_coords = apply_texwindow(texpage_vram_coords);
+            let texpage_vram_coords = config.texpage + uv / vec2u(4, 1);
 
             var texel_index_group = vram_get_color_rgb5m(texpage_vram_coords);
             var clut_index = extractBits(texel_index_group.value, 4 * (uv.x % 4), 4u);
@@ -31,8 +35,7 @@ fn texture_texel(config: TextureConfig, uv: vec2u) -> Rgb5m {
             return texel;
         }
         case TEXTURE_MODE_LUT8 {
-            var texpage_vram_coords = config.texpage + uv / vec2u(2, 1);
-            texpage_vram_coords = apply_texwindow(texpage_vram_coords);
+            let texpage_vram_coords = config.texpage + uv / vec2u(2, 1);
 
             var texel_index_group = vram_get_color_rgb5m(texpage_vram_coords);
             var clut_index = extractBits(texel_index_group.value, 8 * (uv.x % 2), 8u);
@@ -41,8 +44,7 @@ fn texture_texel(config: TextureConfig, uv: vec2u) -> Rgb5m {
             return texel;
         }
         case TEXTURE_MODE_FULL {
-            var texpage_vram_coords = config.texpage + uv;
-            texpage_vram_coords = apply_texwindow(texpage_vram_coords);
+            let texpage_vram_coords = config.texpage + uv;
 
             var texel = vram_get_color_rgb5m(texpage_vram_coords);
             return texel;
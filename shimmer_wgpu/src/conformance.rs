@@ -0,0 +1,263 @@
+//! Conformance of the renderer against [`SoftVram`], the software model of VRAM. Each scenario runs
+//! the same commands through both and compares the whole of VRAM, pixel by pixel.
+//!
+//! Only what [`SoftVram`] models is compared: transfers, and untextured opaque rectangles, which
+//! quick fills are lowered to. When a scenario fails, both VRAM images and a diff of them are
+//! written to `target/conformance/<scenario>/` as PPM files.
+
+use crate::{
+    testing,
+    vram::{VRAM_HEIGHT, VRAM_WIDTH},
+};
+use bitos::integer::{i11, u9, u10, u11};
+use shimmer::{
+    core::gpu::{cmd::rendering::TransparencyMode, texture::BlendingMode},
+    gpu::interface::{
+        Command, CopyFromVram, CopyInVram, CopyToVram, DrawingArea, DrawingSettings, Primitive,
+        Rectangle, Renderer, ResponseHandle, Rgba8, Vertex, VramCoords, VramDimensions,
+        soft_vram::SoftVram,
+    },
+};
+use std::path::{Path, PathBuf};
+
+fn coords(x: u16, y: u16) -> VramCoords {
+    VramCoords {
+        x: u10::new(x),
+        y: u9::new(y),
+    }
+}
+
+fn dimensions(width: u16, height: u16) -> VramDimensions {
+    VramDimensions {
+        width: u11::new(width),
+        height: u10::new(height),
+    }
+}
+
+/// A copy of a `width` by `height` pattern to (`x`, `y`), using every bit of the pixels, including
+/// the mask bit.
+fn copy_pattern(x: u16, y: u16, width: u16, height: u16, seed: u16) -> Command {
+    let data = (0..height)
+        .flat_map(|dy| (0..width).map(move |dx| (dx, dy)))
+        .flat_map(|(dx, dy)| {
+            let value = seed ^ dx.wrapping_mul(0x9E37) ^ dy.wrapping_mul(0x7F4A);
+            value.to_le_bytes()
+        })
+        .collect();
+
+    Command::CopyToVram(CopyToVram {
+        coords: coords(x, y),
+        dimensions: dimensions(width, height),
+        data,
+    })
+}
+
+fn copy_in(source: (u16, u16), destination: (u16, u16), width: u16, height: u16) -> Command {
+    Command::CopyInVram(CopyInVram {
+        source: coords(source.0, source.1),
+        destination: coords(destination.0, destination.1),
+        dimensions: dimensions(width, height),
+    })
+}
+
+fn drawing_area(x: u16, y: u16, width: u16, height: u16) -> Command {
+    Command::SetDrawingArea(DrawingArea {
+        coords: coords(x, y),
+        dimensions: dimensions(width, height),
+    })
+}
+
+fn drawing_settings(write_to_mask: bool) -> Command {
+    Command::SetDrawingSettings(DrawingSettings {
+        blending_mode: BlendingMode::Half,
+        write_to_mask,
+        check_mask: false,
+        drawing_to_display: true,
+        interlaced_odd_field: None,
+    })
+}
+
+/// An untextured opaque rectangle. The components of `color` should have their low three bits
+/// set, since those are the only values every conversion to 5-bit color agrees on.
+fn flat_rect(x: i16, y: i16, width: u16, height: u16, color: Rgba8) -> Command {
+    Command::Draw {
+        primitive: Primitive::Rectangle(Rectangle {
+            top_left: Vertex {
+                color,
+                x: i11::new(x),
+                y: i11::new(y),
+                u: 0,
+                v: 0,
+            },
+            width,
+            height,
+            transparency: TransparencyMode::Opaque,
+            blending_mode: BlendingMode::Half,
+            texconfig: None,
+        }),
+    }
+}
+
+/// Reads the whole of VRAM back from `renderer`, row by row.
+fn read_vram(renderer: &mut dyn Renderer) -> Vec<u16> {
+    let (response, receiver) = ResponseHandle::channel();
+    renderer.exec(Command::CopyFromVram {
+        request: CopyFromVram {
+            coords: coords(0, 0),
+            dimensions: dimensions(VRAM_WIDTH, VRAM_HEIGHT),
+        },
+        response,
+    });
+
+    receiver
+        .recv()
+        .unwrap()
+        .chunks_exact(2)
+        .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]))
+        .collect()
+}
+
+/// Converts a 15-bit pixel to 24-bit RGB, ignoring the mask bit.
+fn to_rgb(pixel: u16) -> [u8; 3] {
+    [0, 5, 10].map(|shift| {
+        let value = ((pixel >> shift) & 0x1F) as u8;
+        (value << 3) | (value >> 2)
+    })
+}
+
+/// Writes a VRAM sized image as a binary PPM.
+fn write_ppm(path: &Path, pixels: impl Iterator<Item = [u8; 3]>) {
+    let mut data = format!("P6\n{VRAM_WIDTH} {VRAM_HEIGHT}\n255\n").into_bytes();
+    data.extend(pixels.flatten());
+    std::fs::write(path, data).unwrap();
+}
+
+/// Writes both VRAM images and a diff of them, with mismatching pixels in white, to
+/// `target/conformance/<name>/` and returns the directory.
+fn dump(name: &str, soft: &[u16], gpu: &[u16]) -> PathBuf {
+    let target = std::env::var_os("CARGO_TARGET_DIR").map_or_else(
+        || Path::new(env!("CARGO_MANIFEST_DIR")).join("../target"),
+        PathBuf::from,
+    );
+    let dir = target.join("conformance").join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    write_ppm(&dir.join("soft.ppm"), soft.iter().copied().map(to_rgb));
+    write_ppm(&dir.join("wgpu.ppm"), gpu.iter().copied().map(to_rgb));
+    write_ppm(
+        &dir.join("diff.ppm"),
+        soft.iter()
+            .zip(gpu)
+            .map(|(a, b)| if a == b { [0; 3] } else { [0xFF; 3] }),
+    );
+
+    dir
+}
+
+/// Runs `scenario` on both [`SoftVram`] and the renderer and asserts they end up with the same
+/// VRAM, dumping both images on failure.
+fn assert_conforms(name: &str, scenario: impl Fn(&mut dyn Renderer)) {
+    let mut soft = SoftVram::new();
+    scenario(&mut soft);
+    let soft = read_vram(&mut soft);
+
+    let mut renderer = testing::renderer(|_| ());
+    renderer.shutdown();
+    scenario(&mut renderer);
+    let gpu = read_vram(&mut renderer);
+
+    let mismatches = soft.iter().zip(&gpu).filter(|(a, b)| a != b).count();
+    if mismatches == 0 {
+        return;
+    }
+
+    let index = soft.iter().zip(&gpu).position(|(a, b)| a != b).unwrap();
+    let (x, y) = (
+        index % usize::from(VRAM_WIDTH),
+        index / usize::from(VRAM_WIDTH),
+    );
+    let dir = dump(name, &soft, &gpu);
+    panic!(
+        "{mismatches} pixels differ, the first at ({x}, {y}): {:#06X} in software and {:#06X} \
+         in wgpu. VRAM images dumped to {}",
+        soft[index],
+        gpu[index],
+        dir.display()
+    );
+}
+
+const WHITE: Rgba8 = Rgba8 {
+    r: 0xFF,
+    g: 0xFF,
+    b: 0xFF,
+    a: 0,
+};
+
+const ORANGE: Rgba8 = Rgba8 {
+    r: 0xFF,
+    g: 0x87,
+    b: 0x07,
+    a: 0,
+};
+
+const TEAL: Rgba8 = Rgba8 {
+    r: 0x17,
+    g: 0x9F,
+    b: 0x8F,
+    a: 0,
+};
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn copies_match_the_software_model() {
+    assert_conforms("copies", |renderer| {
+        renderer.exec(copy_pattern(0, 0, 64, 64, 0x1234));
+        renderer.exec(copy_pattern(100, 37, 333, 21, 0x8421));
+        // odd sizes and positions, wrapping around the right and bottom edges
+        renderer.exec(copy_pattern(1000, 500, 57, 31, 0xBEEF));
+        renderer.exec(copy_pattern(511, 0, 1, 512, 0x7FFF));
+
+        renderer.exec(copy_in((0, 0), (600, 200), 64, 64));
+        renderer.exec(copy_in((100, 37), (3, 300), 333, 21));
+        // from the wrapped copy, across the bottom edge again. Overlapping copies are left out,
+        // since their result depends on the order pixels are copied in
+        renderer.exec(copy_in((1000, 500), (700, 500), 50, 30));
+    });
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn fills_match_the_software_model() {
+    assert_conforms("fills", |renderer| {
+        renderer.exec(drawing_area(0, 0, VRAM_WIDTH, VRAM_HEIGHT));
+        renderer.exec(drawing_settings(false));
+
+        // quick fills are 16 pixel aligned, and may cover the whole of VRAM
+        renderer.exec(flat_rect(0, 0, VRAM_WIDTH, VRAM_HEIGHT, TEAL));
+        renderer.exec(flat_rect(16, 8, 128, 33, WHITE));
+        renderer.exec(flat_rect(512, 256, 512, 256, ORANGE));
+        renderer.exec(flat_rect(1008, 0, 16, 512, WHITE));
+    });
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn flat_rectangles_match_the_software_model() {
+    assert_conforms("flat_rectangles", |renderer| {
+        renderer.exec(copy_pattern(0, 0, 256, 256, 0x5A5A));
+        renderer.exec(drawing_area(10, 20, 200, 100));
+        renderer.exec(drawing_settings(false));
+
+        // inside, straddling and outside the drawing area
+        renderer.exec(flat_rect(50, 50, 17, 9, ORANGE));
+        renderer.exec(flat_rect(0, 0, 40, 40, WHITE));
+        renderer.exec(flat_rect(190, 110, 30, 30, TEAL));
+        renderer.exec(flat_rect(300, 300, 8, 8, WHITE));
+
+        // with the mask bit forced
+        renderer.exec(drawing_area(0, 0, VRAM_WIDTH, VRAM_HEIGHT));
+        renderer.exec(drawing_settings(true));
+        renderer.exec(flat_rect(600, 100, 64, 64, TEAL));
+        renderer.exec(flat_rect(-8, 400, 24, 16, ORANGE));
+    });
+}
@@ -1,9 +1,42 @@
+use crate::vram::VRAM_HEIGHT;
 use tinylog::Logger;
 
+/// The default value of [`Config::dirty_region_len`].
+pub const DEFAULT_DIRTY_REGION_LEN: u16 = 32;
+
+/// Whether `len` can be used as [`Config::dirty_region_len`], i.e. whether it's a power of two no
+/// larger than 512.
+pub fn is_valid_dirty_region_len(len: u16) -> bool {
+    len.is_power_of_two() && len <= VRAM_HEIGHT
+}
+
+/// The contents of VRAM when the renderer is created. On hardware, they're undefined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InitialVram {
+    /// Every pixel is zero, i.e. black with the mask bit clear.
+    #[default]
+    Zero,
+    /// Every pixel has the given value.
+    Fill(u16),
+}
+
 /// Configuration for the renderer.
 #[derive(Debug, Clone)]
 pub struct Config {
     pub display_tex_format: wgpu::TextureFormat,
+    /// Whether to log every rasterizer synchronization caused by a VRAM hazard, along with the
+    /// region responsible for it.
+    pub log_syncs: bool,
+    /// Side length, in pixels, of the square VRAM regions used to detect hazards between drawing
+    /// and sampling. Must be a power of two no larger than 512 (see
+    /// [`is_valid_dirty_region_len`]), otherwise creating the renderer panics.
+    ///
+    /// Larger regions are cheaper to track but cause more false positive synchronizations. The
+    /// default is [`DEFAULT_DIRTY_REGION_LEN`].
+    pub dirty_region_len: u16,
+    /// The contents of VRAM when the renderer is created. Defaults to zero, so that runs are
+    /// deterministic and no garbage shows up on screen.
+    pub initial_vram: InitialVram,
 }
 
 /// A context for the renderer.
@@ -14,6 +14,8 @@ pub struct DisplayRenderer {
 
     top_left: [u16; 2],
     dimensions: [u16; 2],
+    enabled: bool,
+    flipped: bool,
 
     display_area: wgpu::Buffer,
     display_area_bg: wgpu::BindGroup,
@@ -95,7 +97,7 @@ impl DisplayRenderer {
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("display coordinates"),
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                contents: [0u32, 0u32].as_bytes(),
+                contents: [0u32, 0u32, 0u32, 0u32].as_bytes(),
             });
 
         let all_of_vram = ctx
@@ -103,7 +105,7 @@ impl DisplayRenderer {
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("display coordinates"),
                 usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                contents: [0u32, (512 << 16) | 1024].as_bytes(),
+                contents: [0u32, (512 << 16) | 1024, 1u32, 0u32].as_bytes(),
             });
 
         let display_area_bg = ctx.device().create_bind_group(&wgpu::BindGroupDescriptor {
@@ -140,6 +142,8 @@ impl DisplayRenderer {
 
             top_left: [0; 2],
             dimensions: [0; 2],
+            enabled: false,
+            flipped: false,
 
             display_area,
             display_area_bg,
@@ -167,6 +171,28 @@ impl DisplayRenderer {
             .write_buffer(&self.display_area, 4, self.dimensions.as_bytes());
     }
 
+    pub fn set_display_enabled(&mut self, enabled: bool) {
+        if self.enabled == enabled {
+            return;
+        }
+
+        self.enabled = enabled;
+        self.ctx
+            .queue()
+            .write_buffer(&self.display_area, 8, u32::from(enabled).as_bytes());
+    }
+
+    pub fn set_display_flipped(&mut self, flipped: bool) {
+        if self.flipped == flipped {
+            return;
+        }
+
+        self.flipped = flipped;
+        self.ctx
+            .queue()
+            .write_buffer(&self.display_area, 12, u32::from(flipped).as_bytes());
+    }
+
     pub fn render(&self, pass: &mut wgpu::RenderPass) {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.vram_bind_group, &[]);
@@ -1,8 +1,12 @@
 #![feature(let_chains)]
 
+#[cfg(test)]
+mod conformance;
 mod context;
 mod display;
 mod rasterizer;
+#[cfg(test)]
+mod testing;
 mod transfers;
 mod util;
 mod vram;
@@ -14,16 +18,21 @@ use shimmer::{
     core::gpu::texture::Depth as TexDepth,
     gpu::interface::{Command, Primitive, Renderer},
 };
-use std::sync::{
-    Arc, Mutex,
-    mpsc::{Sender, channel},
+use std::{
+    sync::{
+        Arc, Condvar, Mutex,
+        mpsc::{SendError, Sender, channel},
+    },
+    thread::JoinHandle,
 };
 use tinylog::Logger;
 use transfers::Transfers;
-use vram::Vram;
+use vram::{VRAM_HEIGHT, VRAM_WIDTH, Vram};
 use zerocopy::{Immutable, IntoBytes};
 
-pub use context::Config;
+pub use context::{Config, DEFAULT_DIRTY_REGION_LEN, InitialVram, is_valid_dirty_region_len};
+pub use rasterizer::FrameStats;
+pub use transfers::ReadPixelError;
 
 #[derive(Debug, Clone, Copy, IntoBytes, Immutable, Default)]
 #[repr(u32)]
@@ -83,19 +92,27 @@ impl Inner {
                 Primitive::Rectangle(rectangle) => self.rasterizer.enqueue_rectangle(rectangle),
             },
             Command::SetDisplayTopLeft(display_top_left) => {
+                self.rasterizer.set_display_top_left(display_top_left);
                 self.display_renderer
                     .set_display_top_left(display_top_left.x, display_top_left.y);
             }
             Command::SetDisplayResolution(display_resolution) => {
+                self.rasterizer.set_display_resolution(&display_resolution);
                 self.display_renderer.set_display_resolution(
                     display_resolution.horizontal,
                     display_resolution.vertical,
                 );
             }
-            Command::CopyFromVram(copy) => {
+            Command::SetDisplayEnabled(enabled) => {
+                self.display_renderer.set_display_enabled(enabled);
+            }
+            Command::SetDisplayFlipped(flipped) => {
+                self.display_renderer.set_display_flipped(flipped);
+            }
+            Command::CopyFromVram { request, response } => {
                 self.rasterizer.sync();
                 self.rasterizer.flush();
-                self.transfers.copy_from_vram(copy);
+                self.transfers.copy_from_vram(&request, response);
             }
             Command::CopyToVram(copy) => {
                 self.rasterizer.sync();
@@ -121,45 +138,159 @@ impl Inner {
     }
 }
 
+/// A message to the rendering thread.
+enum Message {
+    Command(Command),
+    /// Stops the rendering thread once every message before this one has been processed.
+    Shutdown,
+}
+
+/// Whether the rendering thread is paused.
+#[derive(Default)]
+struct Pause {
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl Pause {
+    fn set(&self, paused: bool) {
+        *self.paused.lock().unwrap() = paused;
+        if !paused {
+            self.resumed.notify_all();
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap()
+    }
+
+    /// Blocks until not paused.
+    fn wait(&self) {
+        let paused = self.paused.lock().unwrap();
+        drop(self.resumed.wait_while(paused, |paused| *paused).unwrap());
+    }
+}
+
 /// A WGPU based renderer implementation.
 ///
 /// This type is reference counted and therefore cheaply clonable.
 #[derive(Clone)]
 pub struct WgpuRenderer {
     inner: Arc<Mutex<Inner>>,
-    sender: Sender<Command>,
+    sender: Sender<Message>,
+    thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+    pause: Arc<Pause>,
 }
 
 impl WgpuRenderer {
     pub fn new(device: wgpu::Device, queue: wgpu::Queue, logger: Logger, config: Config) -> Self {
         let inner = Arc::new(Mutex::new(Inner::new(device, queue, logger, config)));
         let (sender, receiver) = channel();
+        let pause = Arc::new(Pause::default());
 
-        std::thread::Builder::new()
+        let thread = std::thread::Builder::new()
             .name("shimmer_wgpu renderer".into())
             .spawn({
                 let inner = inner.clone();
+                let pause = pause.clone();
                 move || {
                     loop {
-                        let Ok(command) = receiver.recv() else {
+                        let Ok(message) = receiver.recv() else {
                             // sender has been dropped
                             return;
                         };
 
-                        {
-                            let mut renderer = inner.lock().unwrap();
-                            renderer.exec(command);
+                        pause.wait();
 
-                            while let Ok(action) = receiver.try_recv() {
-                                renderer.exec(action);
+                        // the lock is released when pausing, so that the paused state can be
+                        // inspected
+                        let mut renderer = inner.lock().unwrap();
+                        let mut next = Some(message);
+                        while let Some(message) = next {
+                            match message {
+                                Message::Command(command) => renderer.exec(command),
+                                Message::Shutdown => return,
                             }
+
+                            next = if pause.is_paused() {
+                                None
+                            } else {
+                                receiver.try_recv().ok()
+                            };
                         }
                     }
                 }
             })
             .unwrap();
 
-        Self { inner, sender }
+        Self {
+            inner,
+            sender,
+            thread: Arc::new(Mutex::new(Some(thread))),
+            pause,
+        }
+    }
+
+    /// Creates a renderer on the default adapter, without a window to present to. Returns `None`
+    /// if there's no adapter, or it can't provide a device.
+    pub fn headless(logger: Logger, config: Config) -> Option<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let adapter =
+            util::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let (device, queue) = util::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("headless device"),
+                required_features: wgpu::Features::default(),
+                required_limits: wgpu::Limits::default(),
+                memory_hints: wgpu::MemoryHints::default(),
+            },
+            None,
+        ))
+        .ok()?;
+
+        Some(Self::new(device, queue, logger, config))
+    }
+
+    /// Pauses the rendering thread once it's done with the command it's currently executing.
+    /// Commands sent while paused are queued until [`WgpuRenderer::resume`] is called, which
+    /// allows inspecting the state of a partially rendered frame. Affects every clone of this
+    /// renderer.
+    ///
+    /// Note that the emulator blocks on VRAM readbacks, so it stalls if it requests one while the
+    /// renderer is paused.
+    pub fn pause(&self) {
+        self.pause.set(true);
+    }
+
+    /// Resumes the rendering thread, which then executes the commands queued while paused.
+    pub fn resume(&self) {
+        self.pause.set(false);
+    }
+
+    /// Whether the rendering thread is paused.
+    pub fn paused(&self) -> bool {
+        self.pause.is_paused()
+    }
+
+    /// Executes every command sent so far, submits the resulting work to the GPU and stops the
+    /// rendering thread, resuming it if paused and waiting for it to exit. Affects every clone of
+    /// this renderer.
+    ///
+    /// The renderer keeps working afterwards, but commands are executed on the calling thread.
+    /// Does nothing if the renderer has already been shut down.
+    pub fn shutdown(&self) {
+        let Some(thread) = self.thread.lock().unwrap().take() else {
+            return;
+        };
+
+        // if sending fails, the thread has already exited
+        _ = self.sender.send(Message::Shutdown);
+        self.resume();
+        thread.join().expect("rendering thread shouldn't panic");
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.rasterizer.sync();
+        inner.rasterizer.flush();
     }
 
     pub fn render_display(&self, pass: &mut wgpu::RenderPass<'_>) {
@@ -168,6 +299,20 @@ impl WgpuRenderer {
         inner.display_renderer.render(pass);
     }
 
+    /// Reads the value of a single VRAM pixel. Commands still queued for the rendering thread are
+    /// not taken into account.
+    pub fn read_pixel(&self, x: u16, y: u16) -> Result<u16, ReadPixelError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.rasterizer.sync();
+        inner.rasterizer.flush();
+        inner.transfers.read_pixel(x % VRAM_WIDTH, y % VRAM_HEIGHT)
+    }
+
+    /// Returns the rasterizer statistics of the last complete frame.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.inner.lock().unwrap().rasterizer.frame_stats()
+    }
+
     pub fn render_vram(&self, pass: &mut wgpu::RenderPass<'_>) {
         let mut inner = self.inner.lock().unwrap();
         inner.rasterizer.sync();
@@ -177,8 +322,433 @@ impl WgpuRenderer {
 
 impl Renderer for WgpuRenderer {
     fn exec(&mut self, command: Command) {
-        self.sender
-            .send(command)
-            .expect("rendering thread is alive");
+        if let Err(SendError(Message::Command(command))) =
+            self.sender.send(Message::Command(command))
+        {
+            // the rendering thread has been shut down
+            self.inner.lock().unwrap().exec(command);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitos::integer::{i11, u4, u6, u9, u10, u11};
+    use shimmer::{
+        core::gpu::{
+            HorizontalResolution, VerticalResolution,
+            cmd::rendering::TransparencyMode,
+            texture::{BlendingMode, Clut, TexPage},
+        },
+        gpu::interface::{
+            CopyFromVram, CopyToVram, DisplayResolution, DrawingArea, DrawingSettings, Rectangle,
+            ResponseHandle, Rgba8, TexConfig, Vertex, VramCoords, VramDimensions,
+        },
+    };
+
+    fn copy_rect(x: u16, y: u16, width: u16, height: u16, value: u16) -> Command {
+        let len = usize::from(width) * usize::from(height);
+        Command::CopyToVram(CopyToVram {
+            coords: VramCoords {
+                x: u10::new(x),
+                y: u9::new(y),
+            },
+            dimensions: VramDimensions {
+                width: u11::new(width),
+                height: u10::new(height),
+            },
+            data: value.to_le_bytes().repeat(len),
+        })
+    }
+
+    fn copy_pixel(x: u16, y: u16, value: u16) -> Command {
+        copy_rect(x, y, 1, 1, value)
+    }
+
+    fn whole_drawing_area() -> Command {
+        Command::SetDrawingArea(DrawingArea {
+            coords: VramCoords {
+                x: u10::new(0),
+                y: u9::new(0),
+            },
+            dimensions: VramDimensions {
+                width: u11::new(1024),
+                height: u10::new(512),
+            },
+        })
+    }
+
+    fn channels(pixel: u16) -> [u16; 3] {
+        [pixel & 0x1F, (pixel >> 5) & 0x1F, (pixel >> 10) & 0x1F]
+    }
+
+    /// Asserts each channel of `pixel` is within one step of `expected`, since the shaders blend
+    /// in normalized floats.
+    fn assert_channels_near(pixel: u16, expected: u16) {
+        for (got, expected) in channels(pixel).into_iter().zip(channels(expected)) {
+            assert!(
+                got.abs_diff(expected) <= 1,
+                "pixel {pixel:#06X} is not close to {expected:#06X}"
+            );
+        }
+    }
+
+    const CORNERS: [(u16, u16); 5] = [(0, 0), (1023, 0), (0, 511), (1023, 511), (512, 256)];
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn vram_is_zero_after_creation() {
+        let renderer = testing::renderer(|_| ());
+        renderer.shutdown();
+
+        for (x, y) in CORNERS {
+            assert_eq!(renderer.read_pixel(x, y).unwrap(), 0, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn vram_can_be_filled_on_creation() {
+        let renderer = testing::renderer(|config| config.initial_vram = InitialVram::Fill(0x8123));
+        renderer.shutdown();
+
+        for (x, y) in CORNERS {
+            assert_eq!(renderer.read_pixel(x, y).unwrap(), 0x8123, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn read_pixel_returns_written_pixel() {
+        let mut renderer = testing::renderer(|_| ());
+        renderer.shutdown();
+
+        renderer.exec(copy_pixel(100, 200, 0x7C1F));
+        renderer.exec(copy_pixel(101, 200, 0x83E0));
+
+        assert_eq!(renderer.read_pixel(100, 200).unwrap(), 0x7C1F);
+        assert_eq!(renderer.read_pixel(101, 200).unwrap(), 0x83E0);
+        // coordinates wrap around the edges of VRAM
+        assert_eq!(renderer.read_pixel(1124, 712).unwrap(), 0x7C1F);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn shutdown_executes_every_queued_command() {
+        let mut renderer = testing::renderer(|_| ());
+
+        // keep the commands queued until the shutdown, which has to resume the thread
+        renderer.pause();
+        for x in 0..256 {
+            renderer.exec(copy_pixel(x, 10, 0x8000 | x));
+        }
+        assert_eq!(renderer.read_pixel(0, 10).unwrap(), 0);
+
+        renderer.shutdown();
+        assert!(renderer.thread.lock().unwrap().is_none());
+        for x in 0..256 {
+            assert_eq!(renderer.read_pixel(x, 10).unwrap(), 0x8000 | x, "x = {x}");
+        }
+
+        // commands sent afterwards run on the calling thread
+        renderer.exec(copy_pixel(0, 11, 0x1234));
+        assert_eq!(renderer.read_pixel(0, 11).unwrap(), 0x1234);
+
+        // shutting down again does nothing
+        renderer.shutdown();
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn paused_renderer_queues_commands_until_resumed() {
+        let mut renderer = testing::renderer(|_| ());
+
+        renderer.pause();
+        assert!(renderer.paused());
+        renderer.exec(copy_pixel(5, 6, 0x4321));
+        assert_eq!(renderer.read_pixel(5, 6).unwrap(), 0);
+
+        renderer.resume();
+        assert!(!renderer.paused());
+
+        // the response is only sent once the queued copy has been executed
+        let (response, receiver) = ResponseHandle::channel();
+        renderer.exec(Command::CopyFromVram {
+            request: CopyFromVram {
+                coords: VramCoords {
+                    x: u10::new(5),
+                    y: u9::new(6),
+                },
+                dimensions: VramDimensions {
+                    width: u11::new(1),
+                    height: u10::new(1),
+                },
+            },
+            response,
+        });
+
+        assert_eq!(receiver.recv().unwrap(), 0x4321u16.to_le_bytes());
+        assert_eq!(renderer.read_pixel(5, 6).unwrap(), 0x4321);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn single_pixel_copy_from_vram_returns_written_pixel() {
+        let mut renderer = testing::renderer(|_| ());
+        renderer.exec(copy_pixel(3, 4, 0x1234));
+
+        let (response, receiver) = ResponseHandle::channel();
+        renderer.exec(Command::CopyFromVram {
+            request: CopyFromVram {
+                coords: VramCoords {
+                    x: u10::new(3),
+                    y: u9::new(4),
+                },
+                dimensions: VramDimensions {
+                    width: u11::new(1),
+                    height: u10::new(1),
+                },
+            },
+            response,
+        });
+
+        assert_eq!(receiver.recv().unwrap(), 0x1234u16.to_le_bytes());
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn primitives_in_one_batch_blend_with_their_own_mode() {
+        const BACKGROUND: u16 = 0x4210; // 16 in every channel
+        const TEXEL: u16 = 0xA108; // 8 in every channel, semi-transparent
+
+        let mut renderer = testing::renderer(|_| ());
+        renderer.shutdown();
+
+        renderer.exec(copy_rect(0, 0, 32, 8, BACKGROUND));
+        // a 15-bit texture in the texpage at x = 512
+        renderer.exec(copy_rect(512, 0, 8, 8, TEXEL));
+        renderer.exec(whole_drawing_area());
+        // GPUSTAT says Sub, which neither primitive should use
+        renderer.exec(Command::SetDrawingSettings(DrawingSettings {
+            blending_mode: BlendingMode::Sub,
+            write_to_mask: false,
+            check_mask: false,
+            drawing_to_display: true,
+            interlaced_odd_field: None,
+        }));
+
+        let vertex = |x: i16, value: u8| Vertex {
+            color: Rgba8 {
+                r: value,
+                g: value,
+                b: value,
+                a: 0,
+            },
+            x: i11::new(x),
+            y: i11::new(0),
+            u: 0,
+            v: 0,
+        };
+
+        // 66 / 255 is 8 in 5-bit color, like the texel
+        renderer.exec(Command::Draw {
+            primitive: Primitive::Rectangle(Rectangle {
+                top_left: vertex(0, 66),
+                width: 8,
+                height: 8,
+                transparency: TransparencyMode::SemiTransparent,
+                blending_mode: BlendingMode::Add,
+                texconfig: None,
+            }),
+        });
+        renderer.exec(Command::Draw {
+            primitive: Primitive::Rectangle(Rectangle {
+                top_left: vertex(16, 0x80),
+                width: 8,
+                height: 8,
+                transparency: TransparencyMode::SemiTransparent,
+                blending_mode: BlendingMode::Half,
+                texconfig: Some(TexConfig {
+                    texpage: TexPage::default()
+                        .with_x_base(u4::new(8))
+                        .with_depth(TexDepth::Full),
+                    ..Default::default()
+                }),
+            }),
+        });
+
+        // 16 + 8
+        assert_channels_near(renderer.read_pixel(4, 4).unwrap(), 0x6318);
+        // (16 + 8) / 2
+        assert_channels_near(renderer.read_pixel(20, 4).unwrap(), 0x318C);
+        // untouched between the two
+        assert_eq!(renderer.read_pixel(12, 4).unwrap(), BACKGROUND);
+    }
+
+    /// Displays the left half of VRAM, 512x480 at the origin, and draws a red rectangle over the
+    /// whole of VRAM with drawing to the display area disabled.
+    fn draw_over_display(interlaced_odd_field: Option<bool>) -> WgpuRenderer {
+        let mut renderer = testing::renderer(|_| ());
+        renderer.shutdown();
+
+        renderer.exec(whole_drawing_area());
+        renderer.exec(Command::SetDisplayTopLeft(VramCoords {
+            x: u10::new(0),
+            y: u9::new(0),
+        }));
+        renderer.exec(Command::SetDisplayResolution(DisplayResolution {
+            horizontal: HorizontalResolution::R512,
+            vertical: VerticalResolution::R480,
+        }));
+        renderer.exec(Command::SetDrawingSettings(DrawingSettings {
+            blending_mode: BlendingMode::Half,
+            write_to_mask: false,
+            check_mask: false,
+            drawing_to_display: false,
+            interlaced_odd_field,
+        }));
+
+        renderer.exec(Command::Draw {
+            primitive: Primitive::Rectangle(Rectangle {
+                top_left: Vertex {
+                    color: Rgba8 {
+                        r: 0xFF,
+                        g: 0,
+                        b: 0,
+                        a: 0,
+                    },
+                    x: i11::new(0),
+                    y: i11::new(0),
+                    u: 0,
+                    v: 0,
+                },
+                width: 1023,
+                height: 511,
+                transparency: TransparencyMode::Opaque,
+                blending_mode: BlendingMode::Half,
+                texconfig: None,
+            }),
+        });
+
+        renderer
+    }
+
+    const RED: u16 = 0x001F;
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn drawing_skips_the_display_area_when_disabled() {
+        let renderer = draw_over_display(None);
+
+        for (x, y) in [(0, 0), (100, 100), (511, 479)] {
+            assert_eq!(renderer.read_pixel(x, y).unwrap(), 0, "({x}, {y})");
+        }
+
+        // right of and below the display area
+        for (x, y) in [(512, 0), (1000, 300), (100, 480), (511, 510)] {
+            assert_eq!(renderer.read_pixel(x, y).unwrap(), RED, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn interlaced_drawing_skips_the_displayed_field_when_disabled() {
+        let renderer = draw_over_display(Some(true));
+
+        // only the odd lines are being displayed
+        assert_eq!(renderer.read_pixel(100, 101).unwrap(), 0);
+        assert_eq!(renderer.read_pixel(100, 100).unwrap(), RED);
+        assert_eq!(renderer.read_pixel(600, 101).unwrap(), RED);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn cluts_at_the_right_edge_wrap_around() {
+        const GREEN: u16 = 0x03E0;
+
+        let mut renderer = testing::renderer(|_| ());
+        renderer.shutdown();
+
+        // a 256 entry CLUT at x = 1008, so entry 20 wraps around to x = 4
+        renderer.exec(copy_pixel(1010, 10, RED));
+        renderer.exec(copy_pixel(4, 10, GREEN));
+        // an 8-bit texture in the texpage at x = 512: texels 0..4 use entry 2 and texels 4..8 use
+        // entry 20
+        renderer.exec(copy_rect(512, 0, 2, 8, 0x0202));
+        renderer.exec(copy_rect(514, 0, 2, 8, 0x1414));
+        renderer.exec(whole_drawing_area());
+
+        renderer.exec(Command::Draw {
+            primitive: Primitive::Rectangle(Rectangle {
+                top_left: Vertex {
+                    color: Rgba8 {
+                        r: 0x80,
+                        g: 0x80,
+                        b: 0x80,
+                        a: 0,
+                    },
+                    x: i11::new(100),
+                    y: i11::new(100),
+                    u: 0,
+                    v: 0,
+                },
+                width: 8,
+                height: 8,
+                transparency: TransparencyMode::Opaque,
+                blending_mode: BlendingMode::Half,
+                texconfig: Some(TexConfig {
+                    clut: Clut::default()
+                        .with_x_by_16(u6::new(63))
+                        .with_y(u9::new(10)),
+                    texpage: TexPage::default()
+                        .with_x_base(u4::new(8))
+                        .with_depth(TexDepth::Byte),
+                    ..Default::default()
+                }),
+            }),
+        });
+
+        assert_channels_near(renderer.read_pixel(101, 104).unwrap(), RED);
+        assert_channels_near(renderer.read_pixel(105, 104).unwrap(), GREEN);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn reverse_flag_mirrors_the_display() {
+        const BLUE: u16 = 0x7C00;
+
+        let mut renderer = testing::renderer(|_| ());
+        renderer.shutdown();
+
+        // a 256x240 display, red on its left half and blue on its right half
+        renderer.exec(copy_rect(0, 0, 128, 240, RED));
+        renderer.exec(copy_rect(128, 0, 128, 240, BLUE));
+        renderer.exec(Command::SetDisplayResolution(DisplayResolution {
+            horizontal: HorizontalResolution::R256,
+            vertical: VerticalResolution::R240,
+        }));
+        renderer.exec(Command::SetDisplayEnabled(true));
+
+        let is_red = |[r, _, b, _]: [u8; 4]| r > 0xC0 && b < 0x40;
+        let is_blue = |[r, _, b, _]: [u8; 4]| b > 0xC0 && r < 0x40;
+        let at = |pixels: &[[u8; 4]], x: usize| pixels[120 * 256 + x];
+
+        let normal = testing::render_display(&renderer, 256, 240);
+        assert!(is_red(at(&normal, 10)));
+        assert!(is_blue(at(&normal, 245)));
+
+        renderer.exec(Command::SetDisplayFlipped(true));
+        let flipped = testing::render_display(&renderer, 256, 240);
+        assert!(is_blue(at(&flipped, 10)));
+        assert!(is_red(at(&flipped, 245)));
+
+        // every row is the mirror of the normal one
+        for y in [0, 120, 239] {
+            let row = |pixels: &[[u8; 4]]| pixels[y * 256..(y + 1) * 256].to_vec();
+            let mut mirrored = row(&normal);
+            mirrored.reverse();
+            assert_eq!(row(&flipped), mirrored, "row {y}");
+        }
     }
 }
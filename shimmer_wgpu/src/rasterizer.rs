@@ -7,13 +7,13 @@ use crate::{
     vram::Vram,
 };
 use data::{Config, to_buffer};
-use dirty::DirtyRegions;
+use dirty::{DirtyRegions, Region};
 use glam::UVec2;
 use shimmer::{
     core::gpu::texture::TexWindow,
     gpu::interface::{
-        DrawingArea, DrawingSettings, Rectangle as InterfaceRectangle,
-        Triangle as InterfaceTriangle,
+        DisplayResolution, DrawingArea, DrawingSettings, Rectangle as InterfaceRectangle,
+        Triangle as InterfaceTriangle, VramCoords,
     },
 };
 use std::sync::Arc;
@@ -31,6 +31,26 @@ pub enum Command {
     Rectangle,
 }
 
+/// The kind of VRAM hazard that forced the rasterizer to synchronize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hazard {
+    /// A primitive sampled a region drawn to by a previous primitive.
+    Sampling,
+    /// A primitive drew to a region sampled by a previous primitive.
+    Drawing,
+}
+
+/// Rasterizer statistics for a single frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// Total number of synchronizations.
+    pub syncs: u32,
+    /// Number of synchronizations caused by sampling hazards.
+    pub sampling_syncs: u32,
+    /// Number of synchronizations caused by drawing hazards.
+    pub drawing_syncs: u32,
+}
+
 pub struct Rasterizer {
     ctx: Arc<Context>,
 
@@ -49,7 +69,8 @@ pub struct Rasterizer {
     triangles: Vec<data::Triangle>,
     rectangles: Vec<data::Rectangle>,
 
-    syncs: u32,
+    stats: FrameStats,
+    last_stats: FrameStats,
     drawn_regions: DirtyRegions,
     sampled_regions: DirtyRegions,
 }
@@ -70,7 +91,11 @@ impl Rasterizer {
             texwindow_mask: UVec2::ZERO,
             texwindow_offset: UVec2::ZERO,
 
-            blending_mode: 0,
+            display_area_coords: UVec2::ZERO,
+            display_area_dimensions: UVec2::new(256, 240),
+            drawing_to_display: false as u32,
+            interlaced: false as u32,
+            odd_field: false as u32,
         };
 
         let data_bind_group_layout =
@@ -159,9 +184,10 @@ impl Rasterizer {
             triangles: Vec::with_capacity(64),
             rectangles: Vec::with_capacity(64),
 
-            syncs: 0,
-            drawn_regions: DirtyRegions::default(),
-            sampled_regions: DirtyRegions::default(),
+            stats: FrameStats::default(),
+            last_stats: FrameStats::default(),
+            drawn_regions: DirtyRegions::new(ctx.config().dirty_region_len),
+            sampled_regions: DirtyRegions::new(ctx.config().dirty_region_len),
 
             ctx,
         }
@@ -173,9 +199,11 @@ impl Rasterizer {
             "changed drawing settings"; settings = settings
         );
 
-        self.config.blending_mode = settings.blending_mode as u32;
         self.config.write_to_mask = settings.write_to_mask as u32;
         self.config.check_mask = settings.check_mask as u32;
+        self.config.drawing_to_display = settings.drawing_to_display as u32;
+        self.config.interlaced = settings.interlaced_odd_field.is_some() as u32;
+        self.config.odd_field = settings.interlaced_odd_field.unwrap_or_default() as u32;
 
         self.commands.push(Command::Config);
         self.configs.push(self.config.clone());
@@ -200,6 +228,24 @@ impl Rasterizer {
         self.configs.push(self.config.clone());
     }
 
+    pub fn set_display_top_left(&mut self, coords: VramCoords) {
+        self.config.display_area_coords =
+            UVec2::new(u32::from(coords.x.value()), u32::from(coords.y.value()));
+
+        self.commands.push(Command::Config);
+        self.configs.push(self.config.clone());
+    }
+
+    pub fn set_display_resolution(&mut self, resolution: &DisplayResolution) {
+        self.config.display_area_dimensions = UVec2::new(
+            u32::from(resolution.horizontal.value()),
+            u32::from(resolution.vertical.value()),
+        );
+
+        self.commands.push(Command::Config);
+        self.configs.push(self.config.clone());
+    }
+
     pub fn set_texwindow(&mut self, window: TexWindow) {
         trace!(
             self.ctx.logger(),
@@ -225,25 +271,20 @@ impl Rasterizer {
         );
 
         let triangle = data::Triangle::new(triangle);
-        if let Some(sampling_region) = triangle.texconfig().sampling_region()
-            && self.drawn_regions.is_dirty(sampling_region)
+        let texconfig = triangle.texconfig();
+        for sampling_region in [texconfig.sampling_region(), texconfig.clut_region()]
+            .into_iter()
+            .flatten()
         {
-            warn!(
-                self.ctx.logger(),
-                "{:?} is dirty (on triangle sampling) - syncing", sampling_region
-            );
-            self.sync();
-
-            self.sampled_regions.mark(sampling_region);
+            if self.drawn_regions.is_dirty(sampling_region) {
+                self.hazard_sync(Hazard::Sampling, "triangle", sampling_region);
+                self.sampled_regions.mark(sampling_region);
+            }
         }
 
         let drawing_region = triangle.bounding_region();
         if self.sampled_regions.is_dirty(drawing_region) {
-            warn!(
-                self.ctx.logger(),
-                "{:?} is dirty (on triangle drawing) - syncing", drawing_region
-            );
-            self.sync();
+            self.hazard_sync(Hazard::Drawing, "triangle", drawing_region);
         }
 
         self.drawn_regions.mark(drawing_region);
@@ -258,25 +299,20 @@ impl Rasterizer {
         );
 
         let rectangle = data::Rectangle::new(rectangle);
-        if let Some(sampling_region) = rectangle.texconfig().sampling_region()
-            && self.drawn_regions.is_dirty(sampling_region)
+        let texconfig = rectangle.texconfig();
+        for sampling_region in [texconfig.sampling_region(), texconfig.clut_region()]
+            .into_iter()
+            .flatten()
         {
-            warn!(
-                self.ctx.logger(),
-                "{:?} is dirty (on rectangle sampling) - syncing", sampling_region
-            );
-            self.sync();
-
-            self.sampled_regions.mark(sampling_region);
+            if self.drawn_regions.is_dirty(sampling_region) {
+                self.hazard_sync(Hazard::Sampling, "rectangle", sampling_region);
+                self.sampled_regions.mark(sampling_region);
+            }
         }
 
         let drawing_region = rectangle.bounding_region();
         if self.sampled_regions.is_dirty(drawing_region) {
-            warn!(
-                self.ctx.logger(),
-                "{:?} is dirty (on rectangle drawing) - syncing", drawing_region
-            );
-            self.sync();
+            self.hazard_sync(Hazard::Drawing, "rectangle", drawing_region);
         }
 
         self.drawn_regions.mark(drawing_region);
@@ -284,8 +320,32 @@ impl Rasterizer {
         self.rectangles.push(rectangle);
     }
 
+    /// Synchronizes because of a VRAM hazard caused by a primitive.
+    fn hazard_sync(&mut self, hazard: Hazard, primitive: &str, region: Region) {
+        match hazard {
+            Hazard::Sampling => self.stats.sampling_syncs += 1,
+            Hazard::Drawing => self.stats.drawing_syncs += 1,
+        }
+
+        if self.ctx.config().log_syncs {
+            warn!(
+                self.ctx.logger(),
+                "{:?} is dirty (on {} {:?}) - syncing", region, primitive, hazard;
+                sampling_syncs = self.stats.sampling_syncs,
+                drawing_syncs = self.stats.drawing_syncs
+            );
+        }
+
+        self.sync();
+    }
+
+    /// Returns the statistics of the last complete frame.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.last_stats
+    }
+
     pub fn vblank(&mut self) {
-        self.syncs = 0;
+        self.last_stats = std::mem::take(&mut self.stats);
         self.sync();
         self.flush();
     }
@@ -301,7 +361,7 @@ impl Rasterizer {
             return;
         }
 
-        if self.syncs >= MAX_SYNCS_PER_VBLANK {
+        if self.stats.syncs >= MAX_SYNCS_PER_VBLANK {
             warn!(
                 self.ctx.logger(),
                 "too many synchronization points - ignoring sync request"
@@ -309,7 +369,7 @@ impl Rasterizer {
             return;
         }
 
-        self.syncs += 1;
+        self.stats.syncs += 1;
         info!(self.ctx.logger(), "synchronizing rasterizer");
         assert_eq!(
             self.rectangles.len() + self.triangles.len() + self.configs.len() - 1,
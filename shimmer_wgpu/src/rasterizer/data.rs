@@ -23,7 +23,11 @@ pub struct Config {
     pub texwindow_mask: UVec2,
     pub texwindow_offset: UVec2,
 
-    pub blending_mode: u32,
+    pub display_area_coords: UVec2,
+    pub display_area_dimensions: UVec2,
+    pub drawing_to_display: u32,
+    pub interlaced: u32,
+    pub odd_field: u32,
 }
 
 #[derive(Debug, Clone, ShaderType)]
@@ -79,6 +83,21 @@ impl TexConfig {
         (self.mode != 0)
             .then(|| Region::new((self.texpage.x as u16, self.texpage.y as u16), (64, 256)))
     }
+
+    /// The region holding the CLUT, if the texture uses one. CLUTs placed near the right edge of
+    /// VRAM wrap around to its left edge, which [`Region`]s account for.
+    pub fn clut_region(&self) -> Option<Region> {
+        let len = match self.mode {
+            1 => 16,
+            2 => 256,
+            _ => return None,
+        };
+
+        Some(Region::new(
+            (self.clut.x as u16, self.clut.y as u16),
+            (len, 1),
+        ))
+    }
 }
 
 #[derive(Debug, Clone, ShaderType)]
@@ -86,6 +105,7 @@ pub struct Triangle {
     vertices: [Vertex; 3],
     shading_mode: u32,
     transparency_mode: u32,
+    blending_mode: u32,
     texconfig: TexConfig,
 }
 
@@ -106,6 +126,7 @@ impl Triangle {
             }),
             shading_mode: triangle.shading as u32,
             transparency_mode: triangle.transparency as u32,
+            blending_mode: triangle.blending_mode as u32,
             texconfig,
         };
 
@@ -141,6 +162,7 @@ pub struct Rectangle {
     top_left: Vertex,
     dimensions: UVec2,
     transparency_mode: u32,
+    blending_mode: u32,
     texconfig: TexConfig,
 }
 
@@ -167,6 +189,7 @@ impl Rectangle {
             },
             dimensions: UVec2::new(u32::from(rectangle.width), u32::from(rectangle.height)),
             transparency_mode: rectangle.transparency as u32,
+            blending_mode: rectangle.blending_mode as u32,
             texconfig,
         }
     }
@@ -1,8 +1,8 @@
-use crate::vram::{VRAM_HEIGHT, VRAM_WIDTH};
-use bitvec::BitArr;
-
-const DIRTY_REGION_LEN: u16 = 32;
-type Regions = BitArr!(for ((1024 / DIRTY_REGION_LEN) * (1024 / DIRTY_REGION_LEN)) as usize);
+use crate::{
+    context::is_valid_dirty_region_len,
+    vram::{VRAM_HEIGHT, VRAM_WIDTH},
+};
+use bitvec::vec::BitVec;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Region {
@@ -27,36 +27,57 @@ impl Region {
 }
 
 /// Helper struct for keeping track of dirty VRAM regions.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct DirtyRegions {
-    regions: Regions,
+    /// Side length of a region, in pixels.
+    len: u16,
+    regions: BitVec,
 }
 
 impl DirtyRegions {
+    /// Creates a new [`DirtyRegions`] which tracks square regions of side `len`. `len` must be a
+    /// power of two no larger than the VRAM height.
+    pub fn new(len: u16) -> Self {
+        assert!(
+            is_valid_dirty_region_len(len),
+            "dirty region length must be a power of two no larger than {VRAM_HEIGHT}"
+        );
+
+        let count = (VRAM_WIDTH / len) as usize * (VRAM_HEIGHT / len) as usize;
+        Self {
+            len,
+            regions: BitVec::repeat(false, count),
+        }
+    }
+
+    /// Returns the indices of the regions covered by a rectangular region in VRAM.
+    fn covered(&self, region: Region) -> impl Iterator<Item = usize> + use<> {
+        let len = self.len;
+        let columns = VRAM_WIDTH / len;
+        let rows = VRAM_HEIGHT / len;
+
+        let start_x = region.top_left.0 / len;
+        let end_x = (region.top_left.0 + region.dimensions.0 - 1) / len;
+        let start_y = region.top_left.1 / len;
+        let end_y = (region.top_left.1 + region.dimensions.1 - 1) / len;
+
+        (start_y..=end_y).flat_map(move |y| {
+            (start_x..=end_x).map(move |x| {
+                let x = x % columns;
+                let y = y % rows;
+                (y * columns + x) as usize
+            })
+        })
+    }
+
     /// Marks a rectangular region in VRAM as dirty.
     pub fn mark(&mut self, region: Region) {
         if region.dimensions.0 == 0 || region.dimensions.1 == 0 {
             return;
         }
 
-        let start_x = region.top_left.0 / DIRTY_REGION_LEN;
-        let end_x = (region.top_left.0 + region.dimensions.0 - 1) / DIRTY_REGION_LEN;
-        let start_y = (region.top_left.1) / DIRTY_REGION_LEN;
-        let end_y = (region.top_left.1 + region.dimensions.1 - 1) / DIRTY_REGION_LEN;
-
-        for y in start_y..=end_y {
-            for x in start_x..=end_x {
-                let x = x % (VRAM_WIDTH / DIRTY_REGION_LEN);
-                let y = y % (VRAM_HEIGHT / DIRTY_REGION_LEN);
-
-                let bit = self
-                    .regions
-                    .get_mut((y * (VRAM_WIDTH / DIRTY_REGION_LEN) + x) as usize);
-
-                if let Some(mut bit) = bit {
-                    *bit = true;
-                }
-            }
+        for index in self.covered(region) {
+            self.regions.set(index, true);
         }
     }
 
@@ -71,27 +92,71 @@ impl DirtyRegions {
             return false;
         }
 
-        let start_x = region.top_left.0 / DIRTY_REGION_LEN;
-        let end_x = (region.top_left.0 + region.dimensions.0 - 1) / DIRTY_REGION_LEN;
-        let start_y = (region.top_left.1) / DIRTY_REGION_LEN;
-        let end_y = (region.top_left.1 + region.dimensions.1 - 1) / DIRTY_REGION_LEN;
-
-        for y in start_y..=end_y {
-            for x in start_x..=end_x {
-                let x = x % (VRAM_WIDTH / DIRTY_REGION_LEN);
-                let y = y % (VRAM_HEIGHT / DIRTY_REGION_LEN);
-
-                if self
-                    .regions
-                    .get((y * (VRAM_WIDTH / DIRTY_REGION_LEN) + x) as usize)
-                    .map(|bit| *bit)
-                    .unwrap()
-                {
-                    return true;
-                }
+        self.covered(region).any(|index| self.regions[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draws a row of 16x16 tiles, each followed by a draw sampling the tile to its right, and
+    /// returns how many times the rasterizer would have had to synchronize.
+    fn syncs_for_adjacent_tiles(len: u16) -> usize {
+        let mut drawn = DirtyRegions::new(len);
+        let mut syncs = 0;
+
+        for i in 0..32 {
+            drawn.mark(Region::new((16 * i, 0), (16, 16)));
+
+            let sampled = Region::new((16 * (i + 1), 0), (16, 16));
+            if drawn.is_dirty(sampled) {
+                drawn.clear();
+                syncs += 1;
             }
         }
 
-        false
+        syncs
+    }
+
+    #[test]
+    fn finer_grid_syncs_less_for_adjacent_regions() {
+        assert_eq!(syncs_for_adjacent_tiles(16), 0);
+        assert_eq!(syncs_for_adjacent_tiles(32), 16);
+        assert!(syncs_for_adjacent_tiles(64) > syncs_for_adjacent_tiles(32));
+    }
+
+    #[test]
+    fn disjoint_regions_are_independent() {
+        let mut regions = DirtyRegions::new(16);
+        regions.mark(Region::new((0, 0), (16, 16)));
+
+        assert!(regions.is_dirty(Region::new((15, 15), (1, 1))));
+        assert!(!regions.is_dirty(Region::new((16, 0), (16, 16))));
+        assert!(!regions.is_dirty(Region::new((0, 16), (16, 16))));
+        assert!(!regions.is_dirty(Region::new((0, 0), (0, 16))));
+
+        regions.clear();
+        assert!(!regions.is_dirty(Region::new((0, 0), (16, 16))));
+    }
+
+    #[test]
+    fn regions_wrap_around_vram() {
+        let mut regions = DirtyRegions::new(32);
+        regions.mark(Region::new((VRAM_WIDTH - 8, VRAM_HEIGHT - 8), (16, 16)));
+
+        assert!(regions.is_dirty(Region::new((0, 0), (1, 1))));
+        assert!(!regions.is_dirty(Region::new((32, 32), (1, 1))));
+    }
+
+    #[test]
+    fn dirty_region_len_must_be_a_power_of_two_up_to_512() {
+        for len in [1, 2, 16, 32, 512] {
+            assert!(is_valid_dirty_region_len(len));
+        }
+
+        for len in [0, 3, 48, 1024] {
+            assert!(!is_valid_dirty_region_len(len));
+        }
     }
 }
@@ -0,0 +1,112 @@
+//! Helpers for tests which need a renderer. Renderers run on a real adapter, so tests using them
+//! are ignored by default: run them with `cargo test -- --ignored` on a machine with a GPU.
+
+use crate::{Config, DEFAULT_DIRTY_REGION_LEN, InitialVram, WgpuRenderer};
+use tinylog::logger::LoggerFamily;
+
+/// Creates a renderer on the default adapter, with its default configuration modified by
+/// `configure`.
+pub fn renderer(configure: impl FnOnce(&mut Config)) -> WgpuRenderer {
+    let mut config = Config {
+        display_tex_format: wgpu::TextureFormat::Rgba8Unorm,
+        log_syncs: false,
+        dirty_region_len: DEFAULT_DIRTY_REGION_LEN,
+        initial_vram: InitialVram::Zero,
+    };
+    configure(&mut config);
+
+    let logger = LoggerFamily::builder()
+        .build()
+        .logger("renderer", tinylog::Level::Error);
+
+    WgpuRenderer::headless(logger, config).expect("tests using a renderer need a GPU adapter")
+}
+
+/// Renders the display output of `renderer` into a `width` by `height` texture and reads it
+/// back, row by row. The renderer must use [`wgpu::TextureFormat::Rgba8Unorm`] for the display.
+pub fn render_display(renderer: &WgpuRenderer, width: u32, height: u32) -> Vec<[u8; 4]> {
+    let mut inner = renderer.inner.lock().unwrap();
+    inner.rasterizer.sync();
+
+    let ctx = inner._ctx.clone();
+    let device = ctx.device();
+    assert_eq!(
+        ctx.config().display_tex_format,
+        wgpu::TextureFormat::Rgba8Unorm
+    );
+
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("test display"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let bytes_per_row = (4 * width).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("test display readback"),
+        size: u64::from(bytes_per_row * height),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("test display"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        inner.display_renderer.render(&mut pass);
+    }
+
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        size,
+    );
+    ctx.queue().submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| {
+        result.expect("readback buffer should be mappable");
+    });
+    device.poll(wgpu::Maintain::Wait);
+
+    let data = slice.get_mapped_range();
+    data.chunks(bytes_per_row as usize)
+        .flat_map(|row| row[..4 * width as usize].chunks(4))
+        .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+        .collect()
+}
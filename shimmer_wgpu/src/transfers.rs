@@ -1,10 +1,23 @@
-use crate::{context::Context, vram::Vram};
+use crate::{
+    context::Context,
+    vram::{VRAM_WIDTH, Vram},
+};
+use easyerr::Error;
 use encase::{ShaderType, StorageBuffer};
 use glam::UVec2;
-use shimmer::gpu::interface::{CopyFromVram, CopyInVram, CopyToVram};
+use shimmer::gpu::interface::{CopyFromVram, CopyInVram, CopyToVram, ResponseHandle};
 use std::sync::Arc;
+use tinylog::error;
 use wgpu::util::DeviceExt;
 
+#[derive(Debug, Error)]
+pub enum ReadPixelError {
+    #[error("couldn't map the VRAM buffer")]
+    Map { source: wgpu::BufferAsyncError },
+    #[error("the readback was never completed, e.g. because the device was lost")]
+    Incomplete,
+}
+
 #[derive(Debug, Clone, ShaderType)]
 struct Config {
     source: UVec2,
@@ -16,6 +29,7 @@ struct Config {
 pub struct Transfers {
     ctx: Arc<Context>,
 
+    vram_buffer: wgpu::Buffer,
     vram_bind_group: wgpu::BindGroup,
     bind_group_layout: wgpu::BindGroupLayout,
     vram_to_cpu_pipeline: wgpu::ComputePipeline,
@@ -103,6 +117,7 @@ impl Transfers {
         Self {
             ctx,
 
+            vram_buffer: vram.buffer().clone(),
             vram_bind_group: vram.bind_group().clone(),
             bind_group_layout: transfers_bind_group_layout,
             vram_to_cpu_pipeline,
@@ -117,7 +132,45 @@ impl Transfers {
         self.check_mask = value;
     }
 
-    pub fn copy_from_vram(&mut self, copy: CopyFromVram) {
+    /// Reads a single pixel from VRAM by copying it directly out of the VRAM buffer, skipping the
+    /// transfer shader.
+    pub fn read_pixel(&mut self, x: u16, y: u16) -> Result<u16, ReadPixelError> {
+        // each pixel is stored as two u32s, one per byte
+        let index = u64::from(y) * u64::from(VRAM_WIDTH) + u64::from(x);
+        let offset = 8 * index;
+
+        let (sender, receiver) = oneshot::channel();
+        wgpu::util::DownloadBuffer::read_buffer(
+            self.ctx.device(),
+            self.ctx.queue(),
+            &self.vram_buffer.slice(offset..offset + 8),
+            move |result| {
+                let value = result.map(|buffer| u16::from_le_bytes([buffer[0], buffer[4]]));
+
+                // if sending fails, the receiver gave up on the readback
+                _ = sender.send(value);
+            },
+        );
+
+        self.ctx.device().poll(wgpu::Maintain::Wait);
+        match receiver.recv() {
+            Ok(value) => value.map_err(|source| ReadPixelError::Map { source }),
+            Err(oneshot::RecvError) => Err(ReadPixelError::Incomplete),
+        }
+    }
+
+    pub fn copy_from_vram(&mut self, copy: &CopyFromVram, response: ResponseHandle) {
+        // fast path: single pixel reads don't need the transfer shader
+        if copy.dimensions.width.value() == 1 && copy.dimensions.height.value() == 1 {
+            // on errors, the response is dropped and the GPU reads zeroes
+            match self.read_pixel(copy.coords.x.value(), copy.coords.y.value()) {
+                Ok(value) => response.respond(value.to_le_bytes().to_vec()),
+                Err(e) => error!(self.ctx.logger(), "couldn't read back VRAM: {e}"),
+            }
+
+            return;
+        }
+
         // create config
         let config = Config {
             source: UVec2::new(
@@ -201,15 +254,18 @@ impl Transfers {
         self.ctx.queue().submit([encoder.finish()]);
 
         // get data back!
+        let logger = self.ctx.logger().clone();
         wgpu::util::DownloadBuffer::read_buffer(
             self.ctx.device(),
             self.ctx.queue(),
             &buffer.slice(..),
-            |result| {
-                let buffer = result.unwrap();
-                let bytes = &*buffer;
-                let actual_data = bytes.iter().copied().step_by(4).collect::<Vec<_>>();
-                copy.response.send(actual_data).unwrap();
+            move |result| match result {
+                Ok(buffer) => {
+                    let actual_data = buffer.iter().copied().step_by(4).collect::<Vec<_>>();
+                    response.respond(actual_data);
+                }
+                // the response is dropped and the GPU reads zeroes
+                Err(e) => error!(logger, "couldn't read back VRAM: {e}"),
             },
         );
 
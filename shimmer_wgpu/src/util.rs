@@ -1,6 +1,23 @@
 use crate::context::Context;
 use encase::{ShaderSize, ShaderType};
-use std::sync::Arc;
+use std::{
+    pin::pin,
+    sync::Arc,
+    task::{Poll, Waker},
+};
+
+/// Polls `future` until it's ready. The futures of wgpu are ready right away on native backends.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = std::task::Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+
+        std::thread::yield_now();
+    }
+}
 
 /// Helper type for treating a slice of elements as a shader type.
 #[derive(ShaderType)]
@@ -1,5 +1,6 @@
-use crate::context::Context;
+use crate::context::{Context, InitialVram};
 use std::sync::Arc;
+use zerocopy::IntoBytes;
 
 pub const VRAM_WIDTH: u16 = 1024;
 pub const VRAM_HEIGHT: u16 = 512;
@@ -7,7 +8,7 @@ pub const VRAM_HEIGHT: u16 = 512;
 pub struct Vram {
     _ctx: Arc<Context>,
 
-    _buffer: wgpu::Buffer,
+    buffer: wgpu::Buffer,
 
     bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
@@ -24,6 +25,20 @@ impl Vram {
             mapped_at_creation: false,
         });
 
+        // buffers are zero initialized, so only fills need to be written
+        if let InitialVram::Fill(value) = ctx.config().initial_vram {
+            let [low, high] = value.to_le_bytes();
+            let pixel = [u32::from(low), u32::from(high)];
+            let contents: Vec<u32> = pixel
+                .iter()
+                .copied()
+                .cycle()
+                .take(2 * usize::from(VRAM_WIDTH) * usize::from(VRAM_HEIGHT))
+                .collect();
+
+            ctx.queue().write_buffer(&buffer, 0, contents.as_bytes());
+        }
+
         let bind_group_layout =
             ctx.device()
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -56,13 +71,17 @@ impl Vram {
         Self {
             _ctx: ctx,
 
-            _buffer: buffer,
+            buffer,
 
             bind_group_layout,
             bind_group,
         }
     }
 
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
     pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
         &self.bind_group_layout
     }